@@ -0,0 +1,40 @@
+//! Benchmarks of the featurization stages; run with `cargo bench` and compare against a saved baseline
+//! (`cargo bench -- --save-baseline before`, then `cargo bench -- --baseline before`) e.g. across bioshell upgrades.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use bioshell_interactions::BackboneHBondMap;
+
+use featurizer::chains::{polymer_structure, select_chain};
+use featurizer::features::extractors_by_name;
+use featurizer::{compute_chain_features, featurize_chain, CancellationToken, FeaturizerConfig, LoadedDeposit};
+
+const INPUT: &str = "tests/input_files/2gb1.cif";
+
+fn parsing(c: &mut Criterion) {
+    let config = FeaturizerConfig::default();
+    c.bench_function("parse 2gb1", |b| b.iter(|| LoadedDeposit::load(INPUT, &config).unwrap()));
+}
+
+fn hbond_map(c: &mut Criterion) {
+    let loaded = LoadedDeposit::load(INPUT, &FeaturizerConfig::default()).unwrap();
+    let chain = select_chain(&polymer_structure(&loaded.deposit), "A").unwrap();
+    c.bench_function("H-bond map 2gb1_A", |b| b.iter(|| BackboneHBondMap::new(&chain)));
+}
+
+fn features(c: &mut Criterion) {
+    let config = FeaturizerConfig { extractors: extractors_by_name(&["ca_geometry", "hse", "residue_depth"]).unwrap(), ..Default::default() };
+    let loaded = LoadedDeposit::load(INPUT, &config).unwrap();
+    c.bench_function("features 2gb1_A", |b| b.iter(|| compute_chain_features(&loaded, "A", &config).unwrap()));
+}
+
+fn featurization(c: &mut Criterion) {
+    let config = FeaturizerConfig::default();
+    let out_root = std::env::temp_dir().join("featurizer_bench_2gb1_A");
+    let out_root = out_root.to_str().unwrap();
+    let token = CancellationToken::new();
+    c.bench_function("featurize 2gb1_A", |b| b.iter(|| featurize_chain(INPUT, "A", out_root, &config, &token).unwrap()));
+}
+
+criterion_group!(benches, parsing, hbond_map, features, featurization);
+criterion_main!(benches);
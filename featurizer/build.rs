@@ -0,0 +1,15 @@
+use git2::Repository;
+
+/// Records the git commit and the compilation time, so every output can be traced back to the featurizer build
+fn main() {
+    let git_hash = Repository::discover(".").ok()
+        .and_then(|repo| repo.head().ok().and_then(|h| h.peel_to_commit().ok()).map(|c| c.id().to_string()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let dirty = Repository::discover(".").ok()
+        .and_then(|repo| repo.statuses(None).ok().map(|s| s.iter().any(|e| !e.status().is_ignored())))
+        .unwrap_or(false);
+    println!("cargo:rustc-env=FEATURIZER_GIT_HASH={}{}", git_hash, if dirty { "-dirty" } else { "" });
+    println!("cargo:rustc-env=FEATURIZER_BUILD_TIME={}", chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"));
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+}
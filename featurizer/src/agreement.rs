@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{ResidueId, Structure};
+use log::warn;
+
+use crate::alignment::align_chains;
+use crate::chains::load_chain;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::distance;
+use crate::superposition::superpose;
+
+/// Agreement of a predicted model (e.g. from AlphaFold DB) with an experimental structure of the same protein.
+///
+/// `references` maps a chain identifier of a model, such as `AF-P69905-F1-model_v4_A` (the name of its output files,
+/// without the output directory), to the file and chain ID of its experimental counterpart. The model is aligned to the reference by sequence and superimposed on CA atoms of aligned residues;
+/// the CA deviation of every residue is written as a label.
+#[derive(Debug, Default)]
+pub struct PredictionAgreement {
+    pub references: HashMap<String, (String, String)>,
+}
+
+impl PredictionAgreement {
+    /// Reads the model to reference mapping: `<model-chain-id> <reference-file> <reference-chain>` lines, `#` starts a comment
+    pub fn from_file(fname: &str) -> Result<PredictionAgreement, String> {
+        let text = fs::read_to_string(fname).map_err(|e| format!("can't read {}: {}", fname, e))?;
+        let mut references = HashMap::new();
+        for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let t: Vec<&str> = line.split_whitespace().collect();
+            if t.len() < 3 { return Err(format!("incorrect line in {}: {}", fname, line)); }
+            references.insert(t[0].to_string(), (t[1].to_string(), t[2].to_string()));
+        }
+
+        Ok(PredictionAgreement { references })
+    }
+}
+
+impl FeatureExtractor for PredictionAgreement {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "prediction_agreement",
+            description: "CA deviation of a predicted model from its experimental counterpart, after sequence alignment and CA superposition",
+            shape: "[L]".to_string(), units: "Å", range: None,
+            mask: "NaN when the chain has no reference or a residue is not aligned to it", option: "--agreement <mapping-file>", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let mut out = vec![vec![f64::NAN]; chain.resids.len()];
+        let id = Path::new(chain.id).file_name().and_then(|f| f.to_str()).unwrap_or(chain.id);
+        let Some((fname, reference_chain)) = self.references.get(id) else { return out };
+        let reference = match load_chain(fname, reference_chain) {
+            Ok(reference) => reference,
+            Err(e) => {
+                warn!("Can't load the reference chain {} of {}: {}", reference_chain, fname, e);
+                return out;
+            }
+        };
+        let alignment = align_chains(chain.structure, &reference);
+        if alignment.pairs.is_empty() { return out; }
+        // --- aligned residues always have a CA atom
+        let ca = |strctr: &Structure, r: &ResidueId| -> Vec3 { strctr.atom(r, " CA ").unwrap().pos.clone() };
+        let moving: Vec<Vec3> = alignment.pairs.iter().map(|(i, _)| ca(chain.structure, &alignment.first[*i])).collect();
+        let fixed: Vec<Vec3> = alignment.pairs.iter().map(|(_, j)| ca(&reference, &alignment.second[*j])).collect();
+        let sup = superpose(&moving, &fixed);
+        let index: HashMap<_, usize> = chain.resids.iter().enumerate().map(|(k, r)| ((r.res_seq, r.i_code), k)).collect();
+        for (k, (i, _)) in alignment.pairs.iter().enumerate() {
+            let r = &alignment.first[*i];
+            if let Some(&row) = index.get(&(r.res_seq, r.i_code)) { out[row][0] = distance(&sup.apply(&moving[k]), &fixed[k]); }
+        }
+
+        out
+    }
+
+    fn reads_files(&self) -> bool { true }
+}
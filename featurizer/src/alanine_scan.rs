@@ -0,0 +1,64 @@
+use bioshell_pdb::PdbAtom;
+
+use crate::chains::residue_heavy_atoms;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::distance;
+
+/// Atoms within this distance from the CB (CA for glycine) of a residue contribute to its burial
+pub const BURIAL_RADIUS: f64 = 8.0;
+/// Two residues are in contact when any pair of their heavy atoms is closer than this distance
+pub const CONTACT_DISTANCE: f64 = 4.5;
+
+const ALA_ATOMS: [&str; 5] = [" N  ", " CA ", " C  ", " O  ", " CB "];
+
+/// Sensitivity of burial and contact features to truncating each residue to alanine.
+///
+/// For the i-th residue its sidechain is removed beyond CB and two deltas are computed:
+/// the burial lost by all the other residues (the number of removed atoms found within [`BURIAL_RADIUS`]
+/// from CB of other residues) and the number of residue-residue contacts lost by the i-th residue.
+#[derive(Debug)]
+pub struct AlanineScan;
+
+fn in_contact(first: &[&PdbAtom], second: &[&PdbAtom]) -> bool {
+    first.iter().any(|a| second.iter().any(|b| distance(&a.pos, &b.pos) <= CONTACT_DISTANCE))
+}
+
+impl FeatureExtractor for AlanineScan {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "alanine_scan",
+            description: "burial lost by other residues and the number of contacts lost when a residue is truncated to alanine",
+            shape: "[L, 2]".to_string(), units: "atom count, contact count", range: None,
+            mask: "none; zeros for glycine and alanine", option: "--alanine-scan", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let atoms = residue_heavy_atoms(chain.structure, chain.resids);
+        let centers: Vec<_> = atoms.iter().map(|res| {
+            res.iter().find(|a| a.name == " CB ").or(res.iter().find(|a| a.name == " CA ")).map(|a| &a.pos)
+        }).collect();
+        let truncated: Vec<Vec<&PdbAtom>> = atoms.iter()
+            .map(|res| res.iter().filter(|a| ALA_ATOMS.contains(&a.name.as_str())).cloned().collect())
+            .collect();
+
+        let mut out = Vec::with_capacity(atoms.len());
+        for i in 0..atoms.len() {
+            let removed: Vec<&&PdbAtom> = atoms[i].iter().filter(|a| !ALA_ATOMS.contains(&a.name.as_str())).collect();
+            if removed.is_empty() {
+                out.push(vec![0.0, 0.0]);
+                continue;
+            }
+            let mut burial_lost = 0;
+            let mut contacts_lost = 0;
+            for j in 0..atoms.len() {
+                if i == j { continue; }
+                if let Some(c) = centers[j] {
+                    burial_lost += removed.iter().filter(|a| distance(&a.pos, c) <= BURIAL_RADIUS).count();
+                }
+                if in_contact(&atoms[i], &atoms[j]) && !in_contact(&truncated[i], &atoms[j]) { contacts_lost += 1; }
+            }
+            out.push(vec![burial_lost as f64, contacts_lost as f64]);
+        }
+
+        out
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt;
+
+use bioshell_pdb::{ResidueId, Structure};
+use clap::ValueEnum;
+
+use crate::chains::residue_heavy_atoms;
+use crate::residue_encoding::{aa_index, ALPHABET};
+
+const MATCH: i32 = 2;
+const MISMATCH: i32 = -1;
+const GAP: i32 = -2;
+
+/// Global (Needleman-Wunsch) alignment of two sequences with a simple identity score and linear gap penalty.
+///
+/// Returns the mapping from positions of `first` to positions of `second`; `None` marks a position aligned to a gap.
+pub fn align_sequences(first: &[u8], second: &[u8]) -> Vec<Option<usize>> {
+    let (n, m) = (first.len(), second.len());
+    let mut score = vec![vec![0i32; m + 1]; n + 1];
+    for i in 0..=n { score[i][0] = i as i32 * GAP; }
+    for j in 0..=m { score[0][j] = j as i32 * GAP; }
+    for i in 1..=n {
+        for j in 1..=m {
+            let s = if first[i - 1] == second[j - 1] { MATCH } else { MISMATCH };
+            score[i][j] = (score[i - 1][j - 1] + s).max(score[i - 1][j] + GAP).max(score[i][j - 1] + GAP);
+        }
+    }
+
+    // ---------- backtrace
+    let mut mapping = vec![None; n];
+    let (mut i, mut j) = (n, m);
+    while i > 0 && j > 0 {
+        let s = if first[i - 1] == second[j - 1] { MATCH } else { MISMATCH };
+        if score[i][j] == score[i - 1][j - 1] + s {
+            mapping[i - 1] = Some(j - 1);
+            i -= 1;
+            j -= 1;
+        } else if score[i][j] == score[i - 1][j] + GAP {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+
+    mapping
+}
+
+/// Maps observed residues of a chain onto positions of its entity sequence.
+///
+/// Residues of a chain are given in the order of their IDs, i.e. by the residue number and insertion code, so they are
+/// first matched one by one with the entity positions marked as `observed`. This matching is accepted when both lists are
+/// of the same length and all residue types agree, regardless of the author numbering (insertion codes, negative numbers).
+/// Otherwise the observed sequence is aligned to the entity sequence; the residues left unaligned are mapped to `None`.
+pub fn map_to_entity(entity_sequence: &[u8], observed: &[bool], strctr: &Structure, resids: &[ResidueId]) -> Vec<Option<usize>> {
+    let sequence = chain_sequence(strctr, resids);
+    let in_order: Vec<usize> = observed.iter().enumerate().filter(|(_, o)| **o).map(|(i, _)| i).collect();
+    if in_order.len() == sequence.len() && in_order.iter().zip(&sequence).all(|(e, aa)| entity_sequence[*e] == *aa) {
+        return in_order.into_iter().map(Some).collect();
+    }
+
+    align_sequences(&sequence, entity_sequence)
+}
+
+/// One-letter codes of residues of a chain, `X` for a residue without heavy atoms
+fn chain_sequence(strctr: &Structure, resids: &[ResidueId]) -> Vec<u8> {
+    residue_heavy_atoms(strctr, resids).iter()
+        .map(|atoms| atoms.first().map_or(b'X', |a| ALPHABET.as_bytes()[aa_index(&a.res_name)])).collect()
+}
+
+/// What is done with a chain whose observed residues disagree with its entity (SEQRES) sequence
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MismatchPolicy {
+    /// mismatching residues are reported with a warning and written as they are
+    #[default]
+    Warn,
+    /// mismatching residues are written as missing
+    Mask,
+    /// the chain is rejected, as a chain that doesn't pass the filters
+    Reject,
+}
+
+/// An observed residue that disagrees with the entity sequence of its chain
+#[derive(Clone, Debug)]
+pub struct SequenceMismatch {
+    /// index of the residue among the observed residues of the chain
+    pub index: usize,
+    pub residue: ResidueId,
+    /// one-letter code of the observed residue
+    pub observed: char,
+    /// position the residue is aligned to and the entity residue found there; `None` for an unaligned residue
+    pub expected: Option<(usize, char)>,
+}
+
+impl fmt::Display for SequenceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expected {
+            Some((e, aa)) => write!(f, "{} {} (entity {} {})", self.residue, self.observed, e + 1, aa),
+            None => write!(f, "{} {} (unaligned)", self.residue, self.observed),
+        }
+    }
+}
+
+/// Observed residues whose types differ from the entity residues they are mapped onto by [`map_to_entity()`].
+///
+/// Residues missing in the structure are not mismatches. A mismatch is e.g. an engineered mutation not declared
+/// in the entity sequence, a microheterogeneous position modelled with the other residue type, or a mis-threaded
+/// numbering; residues that can't be aligned at all are reported as well.
+pub fn sequence_mismatches(entity_sequence: &[u8], strctr: &Structure, resids: &[ResidueId], entity_index: &[Option<usize>]) -> Vec<SequenceMismatch> {
+    chain_sequence(strctr, resids).iter().zip(entity_index).enumerate()
+        .filter(|(_, (aa, e))| !e.is_some_and(|e| entity_sequence[e] == **aa))
+        .map(|(i, (aa, e))| SequenceMismatch { index: i, residue: resids[i].clone(), observed: *aa as char,
+            expected: e.map(|e| (e, entity_sequence[e] as char)) })
+        .collect()
+}
+
+/// Residues of two chains matched by a sequence alignment
+#[derive(Clone, Debug)]
+pub struct ChainAlignment {
+    /// residues of the first chain that have a CA atom
+    pub first: Vec<ResidueId>,
+    /// residues of the second chain that have a CA atom
+    pub second: Vec<ResidueId>,
+    /// indexes (in `first` and `second`) of aligned residues of the same type
+    pub pairs: Vec<(usize, usize)>,
+}
+
+/// Residues of a chain that have a CA atom, with their one-letter codes
+fn observed_sequence(strctr: &Structure) -> (Vec<ResidueId>, Vec<u8>) {
+    strctr.residue_ids().into_iter()
+        .filter_map(|r| strctr.atom(&r, " CA ").ok().map(|a| ALPHABET.as_bytes()[aa_index(&a.res_name)]).map(|aa| (r, aa)))
+        .unzip()
+}
+
+/// Aligns observed residues of two chains (e.g. two conformations of a protein) by their sequences.
+///
+/// Residues aligned to a residue of a different type are left unaligned.
+pub fn align_chains(first: &Structure, second: &Structure) -> ChainAlignment {
+    let (first, seq_first) = observed_sequence(first);
+    let (second, seq_second) = observed_sequence(second);
+    let pairs = align_sequences(&seq_first, &seq_second).into_iter().enumerate()
+        .filter_map(|(i, j)| j.filter(|j| seq_first[i] == seq_second[*j]).map(|j| (i, j))).collect();
+
+    ChainAlignment { first, second, pairs }
+}
@@ -0,0 +1,153 @@
+//! Packing a featurized dataset into sharded tar or zip archives, WebDataset-style.
+//!
+//! Millions of small files destroy the performance of cluster filesystems, so the outputs of all chains listed
+//! in a manifest are packed into a few large archives, e.g. 10k chains per shard. Files of a chain are stored
+//! next to each other and named by its key, e.g. `pdb/2gb1_A.dat` and `pdb/2gb1_A.meta`, so a WebDataset loader
+//! groups them into a single sample. An index lists the shard and the members of every chain.
+
+use std::fs;
+#[cfg(feature = "archive")]
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use bioshell_io::{open_file, out_writer};
+use clap::ValueEnum;
+use log::{info, warn};
+
+use crate::compression::output_root;
+use crate::errors::FeaturizerError;
+use crate::featurize::output_files;
+use crate::manifest::ChainSummary;
+
+/// Number of chains stored in a single shard by default
+pub const DEFAULT_SHARD_SIZE: usize = 10000;
+
+/// Format of archive shards
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    /// uncompressed tar archives, as read by WebDataset
+    Tar,
+    /// zip archives; plain text members are deflated, already compressed ones are stored
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// The archive a chain has been packed into
+#[derive(Clone, Debug)]
+pub struct IndexEntry {
+    pub key: String,
+    /// file name of the shard
+    pub shard: String,
+    /// names of the members holding the outputs of the chain
+    pub members: Vec<String>,
+}
+
+/// A shard being written
+trait Shard {
+    /// Adds a file to the archive under a given name
+    fn add(&mut self, fname: &str, name: &str) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+#[cfg(feature = "archive")]
+impl Shard for tar::Builder<File> {
+    fn add(&mut self, fname: &str, name: &str) -> io::Result<()> { self.append_path_with_name(fname, name) }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> { tar::Builder::finish(&mut self) }
+}
+
+#[cfg(feature = "archive")]
+impl Shard for zip::ZipWriter<File> {
+    fn add(&mut self, fname: &str, name: &str) -> io::Result<()> {
+        let method = if crate::compression::strip_compression(fname) == fname { zip::CompressionMethod::Deflated } else { zip::CompressionMethod::Stored };
+        self.start_file(name, zip::write::SimpleFileOptions::default().compression_method(method)).map_err(io::Error::other)?;
+        io::copy(&mut File::open(fname)?, self)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        zip::ZipWriter::finish(*self).map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "archive")]
+fn open_shard(fname: &str, format: ArchiveFormat) -> io::Result<Box<dyn Shard>> {
+    let file = File::create(fname)?;
+    Ok(match format {
+        ArchiveFormat::Tar => Box::new(tar::Builder::new(file)),
+        ArchiveFormat::Zip => Box::new(zip::ZipWriter::new(file)),
+    })
+}
+
+#[cfg(not(feature = "archive"))]
+fn open_shard(fname: &str, _format: ArchiveFormat) -> io::Result<Box<dyn Shard>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("can't write {}: the featurizer was built without the archive feature", fname)))
+}
+
+/// Packs outputs of featurized chains into shards named `<out_prefix>-00000.tar` etc., `shard_size` chains each;
+/// returns the index of the packed chains. Chains whose outputs are missing are skipped.
+pub fn pack_dataset(summaries: &[ChainSummary], format: ArchiveFormat, shard_size: usize,
+                    out_prefix: &str) -> Result<Vec<IndexEntry>, FeaturizerError> {
+    if let Some(dir) = Path::new(out_prefix).parent().filter(|d| !d.as_os_str().is_empty()) { fs::create_dir_all(dir)?; }
+    let mut index: Vec<IndexEntry> = vec![];
+    for (k, chunk) in summaries.chunks(shard_size.max(1)).enumerate() {
+        let shard_fname = format!("{}-{:05}.{}", out_prefix, k, format.extension());
+        let shard_name = Path::new(&shard_fname).file_name().and_then(|f| f.to_str()).unwrap_or(&shard_fname).to_string();
+        let mut shard = open_shard(&shard_fname, format)?;
+        for s in chunk {
+            let root = output_root(&s.output);
+            let files = output_files(root);
+            if files.is_empty() {
+                warn!("No outputs found for {}, the chain is not packed", s.key);
+                continue;
+            }
+            let mut members = vec![];
+            for fname in &files {
+                // --- members are named by the chain key followed by the extensions of the file, e.g. pdb/2gb1_A.dat.zst
+                let name = format!("{}{}", s.key, &fname[root.len()..]);
+                shard.add(fname, &name)?;
+                members.push(name);
+            }
+            index.push(IndexEntry { key: s.key.clone(), shard: shard_name.clone(), members });
+        }
+        shard.finish()?;
+        info!("{} chains packed into {}", chunk.len(), shard_fname);
+    }
+
+    Ok(index)
+}
+
+/// Writes the index of packed chains as a tab-separated file, with members of a chain separated by commas
+pub fn write_index(index: &[IndexEntry], fname: &str) -> Result<(), FeaturizerError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#key\tshard\tmembers")?;
+    for e in index {
+        writeln!(outfile, "{}\t{}\t{}", e.key, e.shard, e.members.join(","))?;
+    }
+
+    Ok(())
+}
+
+/// Reads an index written by [`write_index()`]
+pub fn read_index(fname: &str) -> Result<Vec<IndexEntry>, FeaturizerError> {
+    let mut index = vec![];
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let t: Vec<&str> = line.split('\t').collect();
+        if t.len() < 3 { return Err(io::Error::new(io::ErrorKind::InvalidData, format!("malformed line of {}: {}", fname, line)).into()); }
+        index.push(IndexEntry { key: t[0].to_string(), shard: t[1].to_string(), members: t[2].split(',').map(|m| m.to_string()).collect() });
+    }
+
+    Ok(index)
+}
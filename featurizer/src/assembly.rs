@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use bioshell_io::open_file;
+use bioshell_pdb::{PDBError, PdbAtom, Structure};
+use bioshell_pdb::calc::Vec3;
+use log::{debug, info};
+
+use crate::geometry::distance;
+
+/// Splits a line of an mmCIF file into tokens, respecting single and double quotes
+fn cif_tokens(line: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() { i += 1; continue; }
+        if chars[i] == '\'' || chars[i] == '"' {
+            let quote = chars[i];
+            let start = i + 1;
+            i += 1;
+            // --- a quote closes a token only when followed by whitespace or the end of line
+            while i < chars.len() && !(chars[i] == quote && (i + 1 == chars.len() || chars[i + 1].is_whitespace())) { i += 1; }
+            tokens.push(chars[start..i.min(chars.len())].iter().collect());
+            i += 1;
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() { i += 1; }
+            tokens.push(chars[start..i].iter().collect());
+        }
+    }
+
+    tokens
+}
+
+/// Reads all the rows of a given mmCIF category, e.g. `_pdbx_struct_oper_list`.
+///
+/// Both the `loop_` and the single-row key-value forms are supported; each row is returned as a map from
+/// item names (without the category prefix) to their values. Multi-line text fields are read for single rows
+/// (with their lines joined by spaces) and skipped in loops.
+pub fn read_cif_category(fname: &str, category: &str) -> Result<Vec<HashMap<String, String>>, PDBError> {
+    Ok(read_cif_categories(fname, &[category])?.remove(category).unwrap_or_default())
+}
+
+/// Reads rows of several mmCIF categories at once, as [`read_cif_category()`] does, in a single pass over the file
+pub fn read_cif_categories(fname: &str, categories: &[&str]) -> Result<HashMap<String, Vec<HashMap<String, String>>>, PDBError> {
+    let reader = open_file(fname)?;
+    let prefixes: Vec<String> = categories.iter().map(|c| format!("{}.", c)).collect();
+    let item_of = |line: &str| prefixes.iter().enumerate().find_map(|(k, p)| line.strip_prefix(p.as_str()).map(|item| (k, item.to_string())));
+    let mut rows: Vec<Vec<HashMap<String, String>>> = vec![vec![]; categories.len()];
+    let mut single_rows: Vec<HashMap<String, String>> = vec![HashMap::new(); categories.len()];
+    let mut columns: Vec<String> = vec![];
+    let mut in_loop_header = false;
+    // --- the category of the loop being read, if it's one of the requested ones
+    let mut our_loop: Option<usize> = None;
+    let mut values: Vec<String> = vec![];
+    // --- a multi-line value of a single row being read: its category, item name and the text so far
+    let mut pending_text: Option<(usize, String, String)> = None;
+    for line in reader.lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if let Some((k, item, text)) = pending_text.as_mut() {
+            if trimmed == ";" {
+                single_rows[*k].insert(item.clone(), text.clone());
+                pending_text = None;
+                continue;
+            }
+            if trimmed.starts_with(';') || !text.is_empty() {
+                if !text.is_empty() { text.push(' '); }
+                text.push_str(trimmed.trim_start_matches(';').trim());
+                continue;
+            }
+            pending_text = None;
+        }
+        if trimmed.starts_with(';') { continue; }
+        if trimmed == "loop_" {
+            in_loop_header = true;
+            our_loop = None;
+            columns.clear();
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            in_loop_header = false;
+            our_loop = None;
+            continue;
+        }
+        if in_loop_header && trimmed.starts_with('_') {
+            if let Some((k, name)) = item_of(trimmed) {
+                columns.push(name.split_whitespace().next().unwrap_or("").to_string());
+                our_loop = Some(k);
+            }
+            continue;
+        }
+        in_loop_header = false;
+        if let Some(k) = our_loop {
+            values.extend(cif_tokens(trimmed));
+            while values.len() >= columns.len() {
+                let row: Vec<String> = values.drain(..columns.len()).collect();
+                rows[k].push(columns.iter().cloned().zip(row).collect());
+            }
+        } else if let Some((k, item)) = item_of(trimmed) {
+            let tokens = cif_tokens(&item);
+            match tokens.len() {
+                1 => pending_text = Some((k, tokens[0].clone(), String::new())),
+                0 => {}
+                _ => { single_rows[k].insert(tokens[0].clone(), tokens[1].clone()); }
+            }
+        }
+    }
+
+    Ok(categories.iter().zip(rows.into_iter().zip(single_rows)).map(|(category, (mut rows, single_row))| {
+        if !single_row.is_empty() { rows.push(single_row); }
+        (category.to_string(), rows)
+    }).collect())
+}
+
+/// Rotation and translation of a symmetry operator
+#[derive(Clone, Debug)]
+pub struct SymmetryOperator {
+    pub rotation: [[f64; 3]; 3],
+    pub translation: [f64; 3],
+}
+
+impl SymmetryOperator {
+    pub fn apply(&self, v: &Vec3) -> Vec3 {
+        let r = &self.rotation;
+        let t = &self.translation;
+        Vec3::new(r[0][0] * v.x + r[0][1] * v.y + r[0][2] * v.z + t[0],
+                  r[1][0] * v.x + r[1][1] * v.y + r[1][2] * v.z + t[1],
+                  r[2][0] * v.x + r[2][1] * v.y + r[2][2] * v.z + t[2])
+    }
+
+    pub fn is_identity(&self) -> bool {
+        (0..3).all(|i| self.translation[i].abs() < 1e-6 && (0..3).all(|j| (self.rotation[i][j] - (i == j) as u8 as f64).abs() < 1e-6))
+    }
+}
+
+/// Reads symmetry operators from the `_pdbx_struct_oper_list` category
+pub fn read_operators(fname: &str) -> Result<HashMap<String, SymmetryOperator>, PDBError> {
+    let mut operators = HashMap::new();
+    for row in read_cif_category(fname, "_pdbx_struct_oper_list")? {
+        let value = |key: String| row.get(&key).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+        let mut op = SymmetryOperator { rotation: [[0.0; 3]; 3], translation: [0.0; 3] };
+        for i in 0..3 {
+            for j in 0..3 { op.rotation[i][j] = value(format!("matrix[{}][{}]", i + 1, j + 1)); }
+            op.translation[i] = value(format!("vector[{}]", i + 1));
+        }
+        if let Some(id) = row.get("id") { operators.insert(id.clone(), op); }
+    }
+
+    Ok(operators)
+}
+
+/// Expands an operator expression such as `1`, `1,2,5`, `(1-60)` or `(1-5)(6-10)` into a list of operator
+/// combinations; each combination is applied right-to-left, as defined by mmCIF
+pub fn expand_operator_expression(expression: &str) -> Vec<Vec<String>> {
+    let expand_group = |group: &str| -> Vec<String> {
+        group.split(',').flat_map(|part| {
+            match part.split_once('-') {
+                Some((from, to)) => match (from.trim().parse::<i32>(), to.trim().parse::<i32>()) {
+                    (Ok(a), Ok(b)) => (a..=b).map(|k| k.to_string()).collect::<Vec<_>>(),
+                    _ => vec![part.trim().to_string()],
+                },
+                None => vec![part.trim().to_string()],
+            }
+        }).collect()
+    };
+    let groups: Vec<Vec<String>> = if expression.contains('(') {
+        expression.split(|c| c == '(' || c == ')').filter(|g| !g.is_empty()).map(expand_group).collect()
+    } else {
+        vec![expand_group(expression)]
+    };
+    // --- cartesian product of all the groups
+    let mut combinations: Vec<Vec<String>> = vec![vec![]];
+    for group in groups {
+        combinations = combinations.iter()
+            .flat_map(|c| group.iter().map(move |op| { let mut c = c.clone(); c.push(op.clone()); c }))
+            .collect();
+    }
+
+    combinations
+}
+
+/// Reads the mapping from `label_asym_id` to `auth_asym_id` chain identifiers from the `_atom_site` category
+fn label_to_auth_chains(fname: &str) -> Result<HashMap<String, String>, PDBError> {
+    let reader = open_file(fname)?;
+    let mut columns: Vec<String> = vec![];
+    let mut in_atom_site = false;
+    let mut mapping = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with("_atom_site.") {
+            columns.push(line.trim()["_atom_site.".len()..].to_string());
+            in_atom_site = true;
+            continue;
+        }
+        if !in_atom_site { continue; }
+        if line.starts_with('#') { break; }
+        let (Some(label), Some(auth)) = (columns.iter().position(|c| c == "label_asym_id"),
+                                         columns.iter().position(|c| c == "auth_asym_id")) else { break };
+        let tokens = cif_tokens(&line);
+        if tokens.len() == columns.len() { mapping.insert(tokens[label].clone(), tokens[auth].clone()); }
+    }
+
+    Ok(mapping)
+}
+
+/// Builds a biological assembly of a deposit by applying the mmCIF symmetry operators to its chains.
+///
+/// The `strctr` should hold the atoms of the asymmetric unit read from the same `fname` file. Chains created
+/// by the identity operator keep their IDs, other copies are named `<chain>-<operators>`, e.g. `A-2`.
+pub fn build_assembly(fname: &str, strctr: &Structure, assembly_id: &str) -> Result<Structure, PDBError> {
+    let operators = read_operators(fname)?;
+    let label_to_auth = label_to_auth_chains(fname)?;
+    let mut atoms: Vec<PdbAtom> = vec![];
+    for generator in read_cif_category(fname, "_pdbx_struct_assembly_gen")? {
+        if generator.get("assembly_id").map(|s| s.as_str()) != Some(assembly_id) { continue; }
+        let (Some(expression), Some(asym_ids)) = (generator.get("oper_expression"), generator.get("asym_id_list")) else { continue };
+        let chains: Vec<&String> = asym_ids.split(',').filter_map(|a| label_to_auth.get(a.trim())).collect();
+        for combination in expand_operator_expression(expression) {
+            let ops: Vec<&SymmetryOperator> = combination.iter().filter_map(|id| operators.get(id)).collect();
+            if ops.len() != combination.len() { continue; }
+            let identity = ops.iter().all(|op| op.is_identity());
+            for a in strctr.atoms().iter().filter(|a| chains.contains(&&a.chain_id)) {
+                let mut atom = a.clone();
+                for op in ops.iter().rev() { atom.pos = op.apply(&atom.pos); }
+                if !identity { atom.chain_id = format!("{}-{}", a.chain_id, combination.join("x")); }
+                atoms.push(atom);
+            }
+        }
+    }
+    if atoms.is_empty() {
+        return Err(PDBError::NoSuchChain { chain_id: format!("(no chains in the assembly {})", assembly_id) });
+    }
+    info!("assembly {} of {} built: {} atoms", assembly_id, fname, atoms.len());
+
+    Ok(Structure::from_iterator(&strctr.id_code, atoms.iter()))
+}
+
+/// Selects chains of `strctr` that are close to the given chain: any of their CA atoms is within `cutoff` from any CA of that chain.
+///
+/// The returned structure contains the given chain as well.
+pub fn chain_neighborhood(strctr: &Structure, chain: &str, cutoff: f64) -> Structure {
+    let ca_of = |pred: &dyn Fn(&PdbAtom) -> bool| -> Vec<&PdbAtom> {
+        strctr.atoms().iter().filter(|a| a.name == " CA " && pred(a)).collect()
+    };
+    let own = ca_of(&|a| a.chain_id == chain);
+    let others = ca_of(&|a| a.chain_id != chain);
+    let mut neighbors: Vec<&String> = vec![];
+    for a in others {
+        if neighbors.contains(&&a.chain_id) { continue; }
+        if own.iter().any(|b| distance(&a.pos, &b.pos) <= cutoff) { neighbors.push(&a.chain_id); }
+    }
+    debug!("chains in contact with {}: {:?}", chain, neighbors);
+
+    Structure::from_iterator(&strctr.id_code, strctr.atoms().iter()
+        .filter(|a| a.chain_id == chain || neighbors.contains(&&a.chain_id)))
+}
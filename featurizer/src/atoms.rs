@@ -0,0 +1,50 @@
+//! Atom-level records, written to an `.atoms` file along with the residue-level `.dat` file.
+//!
+//! Every heavy atom of a written residue is given its own record, followed by the features of its residue, so an
+//! all-atom model may be trained from the same extraction run as a residue-level one.
+
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::{PDBError, PdbAtom};
+use clap::ValueEnum;
+
+use crate::features::FeatureInfo;
+
+/// What a record of the featurizer output stands for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Granularity {
+    /// a record of the `.dat` file for every residue
+    #[default]
+    Residue,
+    /// also an `.atoms` file, with a record for every heavy atom and the features of its residue
+    Atom,
+}
+
+/// Element of an atom, judging by its name as [`is_hydrogen()`](crate::chains::is_hydrogen) does, e.g. `C` for ` CA `
+pub fn element(atom: &PdbAtom) -> char {
+    atom.name.trim().trim_start_matches(|c: char| c.is_ascii_digit()).chars().next().unwrap_or('X')
+}
+
+/// Formats the record of a heavy atom: the index of its residue, element, atom name, coordinates and B-factor,
+/// followed by the features of the residue as written to the `.dat` file
+pub fn atom_record(residue_index: usize, atom: &PdbAtom, residue_features: &str) -> String {
+    format!("{:4} {} {:4} {:8.3} {:8.3} {:8.3} {:6.2} :{}", residue_index, element(atom), atom.name.trim(),
+            atom.pos.x, atom.pos.y, atom.pos.z, atom.temp_factor, residue_features)
+}
+
+/// Writes atom records of a chain, given for every written residue by its index, heavy atoms and features
+pub fn write_atom_records(residues: &[(usize, Vec<&PdbAtom>, String)], out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    for (index, atoms, features) in residues {
+        for atom in atoms { writeln!(outfile, "{}", atom_record(*index, atom, features))?; }
+    }
+    Ok(())
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "atoms",
+        description: "a record of every heavy atom of the observed residues: residue index, element, atom name, Cartesian coordinates and B-factor, followed by the selected features of its residue, written to an .atoms file",
+        shape: "[n_atoms, 6 + F]".to_string(), units: "Å, Å^2", range: None,
+        mask: "atoms of residues that are not written to the .dat file are skipped", option: "--granularity atom", version: 1 }
+}
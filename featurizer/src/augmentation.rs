@@ -0,0 +1,82 @@
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Defines corrupted variants of a CA trace, generated as model inputs along with the ground-truth features
+#[derive(Clone, Debug)]
+pub struct Augmentation {
+    /// number of variants generated for every chain
+    pub n_variants: usize,
+    /// standard deviation of Gaussian noise added to every CA coordinate, in Å
+    pub ca_noise: f64,
+    /// fraction of randomly masked residues
+    pub mask_fraction: f64,
+    /// maximum length of a loop removed from every variant; no loop is removed when 0
+    pub loop_length: usize,
+    /// seed of the random number generator; combined with the chain ID so a chain gets the same variants in every run
+    pub seed: u64,
+}
+
+/// Seed of a chain: FNV-1a hash of its ID combined with the global seed
+fn chain_seed(seed: u64, chain_id: &str) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in chain_id.bytes() { h = (h ^ b as u64).wrapping_mul(0x100000001b3); }
+    h ^ seed
+}
+
+/// Draws a number from the standard normal distribution with the Box-Muller transform
+fn gaussian(rng: &mut StdRng) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Picks a random segment of up to `max_length` consecutive coil residues
+fn random_loop(ss: &[u8], max_length: usize, rng: &mut StdRng) -> Option<(usize, usize)> {
+    let mut loops: Vec<(usize, usize)> = vec![];
+    let mut start: Option<usize> = None;
+    for i in 0..=ss.len() {
+        let is_coil = i < ss.len() && ss[i] == b'C';
+        match (is_coil, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => { loops.push((s, i)); start = None; }
+            _ => {}
+        }
+    }
+    if loops.is_empty() || max_length == 0 { return None; }
+    let (from, to) = loops[rng.gen_range(0..loops.len())];
+    let length = (to - from).min(max_length);
+    let first = from + rng.gen_range(0..=(to - from - length));
+
+    Some((first, first + length))
+}
+
+/// Writes corrupted variants of the CA trace of a chain.
+///
+/// Each line gives the variant index, residue index, noised CA coordinates and a flag set to 1 for residues
+/// masked or removed as a part of a loop; coordinates of such residues are written as NaN.
+pub fn write_augmented(strctr: &Structure, resids: &[ResidueId], ss: &[u8], aug: &Augmentation, chain_id: &str,
+                       out_fname: &str) -> Result<(), PDBError> {
+    let mut rng = StdRng::seed_from_u64(chain_seed(aug.seed, chain_id));
+    let mut outfile = out_writer(out_fname, false);
+    for k in 0..aug.n_variants {
+        let mut masked: Vec<bool> = (0..resids.len()).map(|_| rng.gen::<f64>() < aug.mask_fraction).collect();
+        if let Some((from, to)) = random_loop(ss, aug.loop_length, &mut rng) {
+            masked[from..to].iter_mut().for_each(|m| *m = true);
+        }
+        for (i, r) in resids.iter().enumerate() {
+            let noise = [gaussian(&mut rng), gaussian(&mut rng), gaussian(&mut rng)];
+            let Ok(ca) = strctr.atom(r, " CA ") else { continue };
+            if masked[i] {
+                writeln!(outfile, "{:3} {:4} {:8.3} {:8.3} {:8.3} 1", k, i, f64::NAN, f64::NAN, f64::NAN)?;
+            } else {
+                writeln!(outfile, "{:3} {:4} {:8.3} {:8.3} {:8.3} 0", k, i, ca.pos.x + aug.ca_noise * noise[0],
+                         ca.pos.y + aug.ca_noise * noise[1], ca.pos.z + aug.ca_noise * noise[2])?;
+            }
+        }
+    }
+    Ok(())
+}
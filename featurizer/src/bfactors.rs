@@ -0,0 +1,67 @@
+use clap::ValueEnum;
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+
+/// Atoms whose B-factors and occupancies are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BFactorAtoms {
+    /// CA atom only
+    Ca,
+    /// N, CA, C and O atoms
+    Backbone,
+}
+
+/// B-factors and occupancies of backbone atoms.
+///
+/// When `normalize` is set, B-factors are converted to Z-scores computed over all the values of a chain.
+#[derive(Debug)]
+pub struct BFactors {
+    pub atoms: BFactorAtoms,
+    pub normalize: bool,
+}
+
+impl BFactors {
+    fn atom_names(&self) -> &'static [&'static str] {
+        match self.atoms {
+            BFactorAtoms::Ca => &[" CA "],
+            BFactorAtoms::Backbone => &[" N  ", " CA ", " C  ", " O  "],
+        }
+    }
+}
+
+impl FeatureExtractor for BFactors {
+    fn info(&self) -> FeatureInfo {
+        let shape = match self.atoms {
+            BFactorAtoms::Ca => "[L, 2]",
+            BFactorAtoms::Backbone => "[L, 8]",
+        };
+        FeatureInfo { name: "bfactors",
+            description: "B-factors of CA or N, CA, C, O atoms (optionally normalized to Z-scores within a chain), followed by their occupancies",
+            shape: shape.to_string(), units: "Å² (or Z-score), occupancy fraction", range: None,
+            mask: "NaN for missing atoms", option: "--bfactors ca|backbone [--normalize-bfactors]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let names = self.atom_names();
+        let mut rows: Vec<Vec<f64>> = chain.resids.iter().map(|r| {
+            let atoms: Vec<_> = names.iter().map(|n| chain.structure.atom(r, n).ok()).collect();
+            let mut row: Vec<f64> = atoms.iter().map(|a| a.map_or(f64::NAN, |a| a.temp_factor)).collect();
+            row.extend(atoms.iter().map(|a| a.map_or(f64::NAN, |a| a.occupancy)));
+            row
+        }).collect();
+
+        if self.normalize {
+            let values: Vec<f64> = rows.iter().flat_map(|r| r[..names.len()].iter().cloned()).filter(|v| !v.is_nan()).collect();
+            let n = values.len() as f64;
+            let mean = values.iter().sum::<f64>() / n;
+            let sdev = (values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n).sqrt();
+            for row in rows.iter_mut() {
+                for v in row[..names.len()].iter_mut().filter(|v| !v.is_nan()) {
+                    *v = if sdev > 0.0 { (*v - mean) / sdev } else { 0.0 };
+                }
+            }
+        }
+
+        rows
+    }
+}
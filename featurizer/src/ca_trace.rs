@@ -0,0 +1,63 @@
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{dihedral_angle, distance, planar_angle};
+use crate::ideal_backbone::MAX_CA_CA;
+
+/// Pseudo-bond geometry of a CA trace at a single residue
+#[derive(Clone, Debug)]
+pub struct CaGeometry {
+    /// CA(i)-CA(i+1) distance
+    pub r: f64,
+    /// CA(i-1)-CA(i)-CA(i+1) planar angle
+    pub theta: f64,
+    /// CA(i-1)-CA(i)-CA(i+1)-CA(i+2) pseudo-torsion
+    pub tau: f64,
+}
+
+/// Computes CA-trace descriptors for every residue of a chain.
+///
+/// The CA(i)-CA(i+1) distance is defined whenever both atoms exist; angles and torsions are undefined (NaN)
+/// when any of the pseudo-bonds they span is longer than [`MAX_CA_CA`], i.e. at chain breaks.
+pub fn ca_trace_geometry(strctr: &Structure, resids: &[ResidueId]) -> Vec<CaGeometry> {
+    let cas: Vec<Option<&Vec3>> = resids.iter().map(|r| strctr.atom(r, " CA ").ok().map(|a| &a.pos)).collect();
+    let ca_ca = |i: usize| -> Option<f64> {
+        if i + 1 >= cas.len() { return None; }
+        Some(distance(cas[i]?, cas[i + 1]?))
+    };
+    // --- bonded(i) says whether CA(i) and CA(i+1) are connected by a pseudo-bond
+    let bonded = |i: usize| ca_ca(i).is_some_and(|d| d <= MAX_CA_CA);
+
+    let mut out = Vec::with_capacity(cas.len());
+    for i in 0..cas.len() {
+        let r = ca_ca(i).unwrap_or(f64::NAN);
+        let theta = if i > 0 && bonded(i - 1) && bonded(i) {
+            planar_angle(cas[i - 1].unwrap(), cas[i].unwrap(), cas[i + 1].unwrap())
+        } else { f64::NAN };
+        let tau = if i > 0 && bonded(i - 1) && bonded(i) && bonded(i + 1) {
+            dihedral_angle(cas[i - 1].unwrap(), cas[i].unwrap(), cas[i + 1].unwrap(), cas[i + 2].unwrap())
+        } else { f64::NAN };
+        out.push(CaGeometry { r, theta, tau });
+    }
+
+    out
+}
+
+/// CA-trace pseudo-bond lengths, angles and torsions, see [`ca_trace_geometry()`]
+#[derive(Debug)]
+pub struct CaTraceGeometry;
+
+impl FeatureExtractor for CaTraceGeometry {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "ca_geometry",
+            description: "CA(i)-CA(i+1) distance, CA(i-1)-CA(i)-CA(i+1) planar angle and CA(i-1)-CA(i)-CA(i+1)-CA(i+2) pseudo-torsion",
+            shape: "[L, 3]".to_string(), units: "Å, degrees, degrees", range: None,
+            mask: "NaN when an atom is missing or the angle spans a chain break",
+            option: "--ca-geometry", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        ca_trace_geometry(chain.structure, chain.resids).iter().map(|g| vec![g.r, g.theta, g.tau]).collect()
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Allows another thread to abort a running featurization.
+///
+/// Clones of a token share the same state, so the token may be handed to a worker
+/// while a GUI or a server handler keeps a copy to call [`cancel()`](CancellationToken::cancel) on.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken { CancellationToken::default() }
+
+    /// Requests cancellation of all the featurizations that use this token
+    pub fn cancel(&self) { self.cancelled.store(true, Ordering::Relaxed); }
+
+    pub fn is_cancelled(&self) -> bool { self.cancelled.load(Ordering::Relaxed) }
+}
@@ -0,0 +1,83 @@
+use clap::ValueEnum;
+
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, Structure};
+
+use crate::chains::select_chain;
+use crate::geometry::{cross, dot, sub, unit, LocalFrame};
+use crate::superposition::{center, symmetric_eigen};
+
+/// Canonical coordinate frame a chain is moved into before its coordinates are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CenterFrame {
+    /// coordinates are written as deposited
+    #[default]
+    None,
+    /// the chain is translated so the centroid of its CA atoms is at the origin
+    Centroid,
+    /// the chain is centered and rotated so the principal axes of its CA atoms lie along X, Y and Z
+    Pca,
+    /// the chain is moved into the backbone frame of its first residue: CA at the origin,
+    /// the X axis towards C and N in the XY plane
+    FirstResidue,
+}
+
+/// Principal axes of a set of points, from the largest variance to the smallest one, forming a right-handed system.
+///
+/// The X and Y axes are pointed towards the longer tail of the distribution of projections (positive third moment),
+/// so the result doesn't depend on the orientation of the input.
+fn principal_axes(points: &[Vec3], centroid: &Vec3) -> [Vec3; 3] {
+    let mut cov = [[0.0; 3]; 3];
+    for p in points {
+        let d = sub(p, centroid);
+        let d = [d.x, d.y, d.z];
+        for i in 0..3 { for j in 0..3 { cov[i][j] += d[i] * d[j]; } }
+    }
+    let (values, vectors) = symmetric_eigen(cov);
+    let mut order = [0, 1, 2];
+    order.sort_by(|i, j| values[*j].total_cmp(&values[*i]));
+    let axis = |k: usize| {
+        let a = Vec3::new(vectors[0][k], vectors[1][k], vectors[2][k]);
+        let skew: f64 = points.iter().map(|p| dot(&sub(p, centroid), &a).powi(3)).sum();
+        if skew < 0.0 { Vec3::new(-a.x, -a.y, -a.z) } else { a }
+    };
+    let (x, y) = (axis(order[0]), axis(order[1]));
+    let z = unit(&cross(&x, &y));
+
+    [x, y, z]
+}
+
+/// The canonical frame of a chain, defined by its CA atoms; `None` when the frame can't be defined,
+/// e.g. for a chain without CA atoms or when [`CenterFrame::None`] is requested
+pub fn canonical_frame(strctr: &Structure, mode: CenterFrame) -> Option<LocalFrame> {
+    let ca: Vec<Vec3> = strctr.atoms().iter().filter(|a| a.name == " CA ").map(|a| a.pos.clone()).collect();
+    if ca.is_empty() { return None; }
+    let identity = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+    match mode {
+        CenterFrame::None => None,
+        CenterFrame::Centroid => Some(LocalFrame { origin: center(&ca), axes: identity }),
+        CenterFrame::Pca => {
+            let origin = center(&ca);
+            Some(LocalFrame { axes: principal_axes(&ca, &origin), origin })
+        }
+        CenterFrame::FirstResidue => {
+            let first = strctr.residue_ids().into_iter().next()?;
+            let atom = |name: &str| strctr.atom(&first, name).ok().map(|a| a.pos.clone());
+            Some(LocalFrame::from_backbone(&atom(" N  ")?, &atom(" CA ")?, &atom(" C  ")?))
+        }
+    }
+}
+
+/// Moves all atoms of `polymer` into the canonical frame of one of its chains; the other chains move along,
+/// so inter-chain geometry is preserved. `None` is returned when the frame can't be defined.
+pub fn center_on_chain(polymer: &Structure, chain: &str, mode: CenterFrame) -> Result<Option<Structure>, PDBError> {
+    if mode == CenterFrame::None { return Ok(None); }
+    let Some(frame) = canonical_frame(&select_chain(polymer, chain)?, mode) else { return Ok(None) };
+    let atoms: Vec<_> = polymer.atoms().iter().map(|a| {
+        let mut atom = a.clone();
+        atom.pos = frame.to_local(&a.pos);
+        atom
+    }).collect();
+
+    Ok(Some(Structure::from_iterator(&polymer.id_code, atoms.iter())))
+}
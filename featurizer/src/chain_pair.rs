@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+
+use crate::chains::{is_hydrogen, residue_heavy_atoms, select_chain};
+use crate::contacts::cb_position;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{dihedral_angle, distance, planar_angle};
+use crate::neighbors::NeighborGrid;
+
+/// Residues of two chains closer than this distance (between any pair of their atoms) are at an interface
+pub const INTERFACE_CUTOFF: f64 = 5.0;
+
+/// Distances to other chains larger than this are not recorded by [`partner_distances()`]
+pub const PARTNER_RANGE: f64 = 10.0;
+
+/// Pair features of two residues from different chains.
+///
+/// Orientation angles follow the trRosetta convention: `omega` is the CA(i)-CB(i)-CB(j)-CA(j) dihedral,
+/// `theta` the N(i)-CA(i)-CB(i)-CB(j) dihedral and `phi` the CA(i)-CB(i)-CB(j) planar angle. A missing CB, e.g. of glycine,
+/// is replaced by a virtual one built from N, CA and C (see [`cb_position()`]), which is flagged.
+#[derive(Clone, Debug)]
+pub struct ResiduePairFeatures {
+    pub i: usize,
+    pub j: usize,
+    pub d_ca: f64,
+    pub d_cb: f64,
+    pub omega: f64,
+    pub theta: f64,
+    pub phi: f64,
+    /// true when CB of the first (second) residue is virtual
+    pub virtual_cb: (bool, bool),
+}
+
+fn pos<'a>(strctr: &'a Structure, resid: &ResidueId, name: &str) -> Option<&'a Vec3> {
+    strctr.atom(resid, name).ok().map(|a| &a.pos)
+}
+
+fn pair_features(first: &Structure, ri: &ResidueId, second: &Structure, rj: &ResidueId, i: usize, j: usize) -> Option<ResiduePairFeatures> {
+    let ca_i = pos(first, ri, " CA ")?;
+    let ca_j = pos(second, rj, " CA ")?;
+    let d_ca = distance(ca_i, ca_j);
+    let (Some(n_i), Some((cb_i, virtual_i)), Some((cb_j, virtual_j))) = (pos(first, ri, " N  "), cb_position(first, ri), cb_position(second, rj)) else {
+        return Some(ResiduePairFeatures { i, j, d_ca, d_cb: f64::NAN, omega: f64::NAN, theta: f64::NAN, phi: f64::NAN, virtual_cb: (false, false) });
+    };
+    Some(ResiduePairFeatures { i, j, d_ca, d_cb: distance(&cb_i, &cb_j), omega: dihedral_angle(ca_i, &cb_i, &cb_j, ca_j),
+        theta: dihedral_angle(n_i, ca_i, &cb_i, &cb_j), phi: planar_angle(ca_i, &cb_i, &cb_j), virtual_cb: (virtual_i, virtual_j) })
+}
+
+/// Computes features for all pairs of residues from two chains whose CA atoms are closer than `cutoff`
+pub fn chain_pair_features(first: &Structure, second: &Structure, cutoff: f64) -> Vec<ResiduePairFeatures> {
+    let (resids_i, resids_j) = (first.residue_ids(), second.residue_ids());
+    let mut out = vec![];
+    for (i, ri) in resids_i.iter().enumerate() {
+        for (j, rj) in resids_j.iter().enumerate() {
+            let Some(p) = pair_features(first, ri, second, rj, i, j) else { continue };
+            if p.d_ca <= cutoff { out.push(p); }
+        }
+    }
+
+    out
+}
+
+/// Flags residues of the `first` chain that have any atom within [`INTERFACE_CUTOFF`] from any atom of the `second` chain
+pub fn interface_flags(first: &Structure, second: &Structure) -> Vec<bool> {
+    let resids = first.residue_ids();
+    let index: HashMap<(i32, char), usize> = resids.iter().enumerate().map(|(i, r)| ((r.res_seq, r.i_code), i)).collect();
+    let mut flags = vec![false; resids.len()];
+    for a in first.atoms() {
+        let Some(&i) = index.get(&(a.res_seq, a.i_code)) else { continue };
+        if flags[i] { continue; }
+        flags[i] = second.atoms().iter().any(|b| distance(&a.pos, &b.pos) <= INTERFACE_CUTOFF);
+    }
+
+    flags
+}
+
+/// Writes inter-chain features: interface flags of both chains followed by residue pair features
+pub fn write_chain_pair(first: &Structure, second: &Structure, cutoff: f64, out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    for (label, a, b) in [("first", first, second), ("second", second, first)] {
+        writeln!(outfile, "# interface residues of the {} chain", label)?;
+        for (idx, (resid, flag)) in a.residue_ids().iter().zip(interface_flags(a, b)).enumerate() {
+            writeln!(outfile, "I {:4} {} {}", idx, resid, flag as u8)?;
+        }
+    }
+    writeln!(outfile, "# residue pairs: i j d_ca d_cb omega theta phi virtual_cb_i virtual_cb_j")?;
+    for p in chain_pair_features(first, second, cutoff) {
+        writeln!(outfile, "P {:4} {:4} {:6.3} {:6.3} {:7.2} {:7.2} {:7.2} {} {}", p.i, p.j, p.d_ca, p.d_cb, p.omega, p.theta, p.phi,
+                 p.virtual_cb.0 as u8, p.virtual_cb.1 as u8)?;
+    }
+    Ok(())
+}
+
+/// Distance from every residue of `chain` to the nearest heavy atom of any other chain of `polymer`, computed over
+/// the heavy atoms of the residue; distances larger than [`PARTNER_RANGE`] are NaN
+pub fn partner_distances(polymer: &Structure, chain: &str) -> Result<Vec<f64>, PDBError> {
+    let strctr = select_chain(polymer, chain)?;
+    let resids = strctr.residue_ids();
+    let others: Vec<Option<Vec3>> = polymer.atoms().iter().filter(|a| a.chain_id != chain && !is_hydrogen(a)).map(|a| Some(a.pos.clone())).collect();
+    let grid = NeighborGrid::new(&others, PARTNER_RANGE);
+    let distances = residue_heavy_atoms(&strctr, &resids).iter().map(|atoms| {
+        let nearest = atoms.iter().flat_map(|a| grid.within(&a.pos, PARTNER_RANGE).into_iter()
+            .filter_map(|j| others[j].as_ref()).map(move |b| distance(&a.pos, b))).fold(f64::INFINITY, f64::min);
+        if nearest <= PARTNER_RANGE { nearest } else { f64::NAN }
+    }).collect();
+
+    Ok(distances)
+}
+
+/// Interface residues: those with a heavy atom within [`INTERFACE_CUTOFF`] of a heavy atom of another polymer chain.
+///
+/// Other chains are those of the deposit the chain comes from, or of the biological assembly with `--assembly`; they are
+/// looked at before the chain is extracted, so backbone conformations induced by an interface may be down-weighted
+/// or studied separately. A chain alone in its deposit has no interface residues.
+#[derive(Debug)]
+pub struct InterfaceResidues;
+
+impl FeatureExtractor for InterfaceResidues {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "interface",
+            description: "1 when a heavy atom of a residue is within 5 Å of a heavy atom of another polymer chain of the deposit, 0 otherwise; distance to the nearest such atom",
+            shape: "[L, 2]".to_string(), units: "flag, Å", range: None,
+            mask: "NaN distance beyond 10 Å; both NaN in the low-memory mode, where other chains are not loaded", option: "--interface-labels", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let Some(distances) = chain.partner_distances.as_ref() else { return vec![vec![f64::NAN; 2]; chain.resids.len()] };
+        distances.iter().map(|d| {
+            let flag = if *d <= INTERFACE_CUTOFF { 1.0 } else { 0.0 };
+            vec![flag, *d]
+        }).collect()
+    }
+
+    fn needs_partners(&self) -> bool { true }
+}
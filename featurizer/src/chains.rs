@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::io::{stdin, BufReader, Read};
+use std::sync::OnceLock;
+
+use bioshell_pdb::{Deposit, PDBError, PdbAtom, ResidueId, Structure};
+use bioshell_pdb::PDBError::NoSuchChain;
+
+/// Returns all polymer chains of a deposit; ligands are removed
+pub fn polymer_structure(deposit: &Deposit) -> Structure {
+    let mut strctr = deposit.structure();
+    strctr.remove_ligands();
+    strctr
+}
+
+/// Selects a single chain from a structure
+pub fn select_chain(strctr: &Structure, chain: &str) -> Result<Structure, PDBError> {
+    let strctr = Structure::from_iterator(&strctr.id_code, strctr.atoms().iter().filter(|a| a.chain_id == chain));
+    if strctr.atoms().len() < 1 {
+        return Err(NoSuchChain { chain_id: chain.to_string() });
+    }
+
+    Ok(strctr)
+}
+
+/// IDs of all polymer chains of a deposit, in the order they appear in the file
+pub fn polymer_chain_ids(deposit: &Deposit) -> Vec<String> {
+    let mut ids: Vec<String> = vec![];
+    for a in polymer_structure(deposit).atoms() {
+        if !ids.contains(&a.chain_id) { ids.push(a.chain_id.clone()); }
+    }
+
+    ids
+}
+
+/// Chains of a deposit selected by a chain specification: comma-separated chain IDs (e.g. `A,B`) or, for a token that
+/// is not an ID of a polymer chain of the deposit, single-letter IDs written together (e.g. `AB`)
+pub fn selected_chains(deposit: &Deposit, selection: &str) -> Vec<String> {
+    select_chain_ids(&polymer_chain_ids(deposit), selection)
+}
+
+/// Chains selected by a chain specification, as [`selected_chains()`] gives them, among the `known` chain IDs
+pub fn select_chain_ids(known: &[String], selection: &str) -> Vec<String> {
+    let mut chains: Vec<String> = vec![];
+    for token in selection.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        let letters: Vec<String> = token.chars().map(|c| c.to_string()).collect();
+        let ids = if !known.iter().any(|k| k == token) && letters.len() > 1 && letters.iter().all(|l| known.contains(l)) {
+            letters
+        } else {
+            vec![token.to_string()]
+        };
+        for id in ids { if !chains.contains(&id) { chains.push(id); } }
+    }
+
+    chains
+}
+
+/// Keeps a single representative of identical chains, e.g. of a homooligomer: the chain of every entity with the most
+/// observed residues, i.e. the fewest missing ones; ties are broken by the order of `chains`, which is preserved
+pub fn representative_chains(deposit: &Deposit, chains: &[String]) -> Vec<String> {
+    let polymer = polymer_structure(deposit);
+    let mut best: HashMap<String, (usize, usize)> = HashMap::new();
+    // --- chains missing in the deposit are kept, so they are reported when featurized
+    let mut kept: Vec<usize> = vec![];
+    for (k, chain) in chains.iter().enumerate() {
+        let Ok(strctr) = select_chain(&polymer, chain) else { kept.push(k); continue };
+        let n_observed = strctr.residue_ids().len();
+        let entry = best.entry(strctr.atoms()[0].entity_id.clone()).or_insert((k, n_observed));
+        if n_observed > entry.1 { *entry = (k, n_observed); }
+    }
+    kept.extend(best.values().map(|(k, _)| *k));
+    kept.sort();
+
+    kept.into_iter().map(|k| chains[k].clone()).collect()
+}
+
+/// Extracts a single polymer chain from a deposit; ligands are removed
+pub fn extract_chain(deposit: &Deposit, chain: &str) -> Result<Structure, PDBError> {
+    select_chain(&polymer_structure(deposit), chain)
+}
+
+/// ID of the asymmetric unit chain a chain of a biological assembly was copied from, e.g. `A` for `A-2`
+pub fn source_chain(chain: &str) -> &str {
+    chain.split('-').next().unwrap_or(chain)
+}
+
+/// File name that stands for the standard input
+pub const STDIN: &str = "-";
+
+/// Content of the standard input, read at the first use so the same deposit may be loaded many times
+static STDIN_TEXT: OnceLock<String> = OnceLock::new();
+
+/// Content of the standard input
+pub(crate) fn stdin_text() -> &'static str {
+    STDIN_TEXT.get_or_init(|| {
+        let mut text = String::new();
+        if let Err(e) = stdin().read_to_string(&mut text) { log::error!("Can't read the standard input: {}", e); }
+        text
+    })
+}
+
+/// Loads a deposit from a file, or from the standard input when `fname` is `-`.
+///
+/// The format of the standard input is detected by its content, see [`parse_deposit()`].
+pub fn load_deposit(fname: &str) -> Result<Deposit, PDBError> {
+    if fname != STDIN { return Deposit::from_file(fname); }
+    parse_deposit(stdin_text())
+}
+
+/// Parses a deposit from the text of an mmCIF or PDB file, e.g. one uploaded to a server or a browser.
+///
+/// The format is detected by the content: mmCIF data starts with a `data_` block.
+pub fn parse_deposit(text: &str) -> Result<Deposit, PDBError> {
+    if text.trim_start().starts_with("data_") {
+        Deposit::from_cif_reader(BufReader::new(text.as_bytes()))
+    } else {
+        Deposit::from_pdb_reader(BufReader::new(text.as_bytes()))
+    }
+}
+
+/// Loads a deposit from a file and extracts a single chain from it
+pub fn load_chain(fname: &str, chain: &str) -> Result<Structure, PDBError> {
+    let deposit = load_deposit(fname)?;
+    extract_chain(&deposit, chain)
+}
+
+/// Returns true if an atom is a hydrogen (or deuterium), judging by its name
+pub fn is_hydrogen(atom: &PdbAtom) -> bool {
+    let name = atom.name.trim().trim_start_matches(|c: char| c.is_ascii_digit());
+    name.starts_with('H') || name.starts_with('D')
+}
+
+/// Groups heavy atoms of a structure by residues; the order of the groups follows `resids`
+pub fn residue_heavy_atoms<'a>(strctr: &'a Structure, resids: &[ResidueId]) -> Vec<Vec<&'a PdbAtom>> {
+    let index: HashMap<(i32, char), usize> = resids.iter().enumerate().map(|(i, r)| ((r.res_seq, r.i_code), i)).collect();
+    let mut atoms: Vec<Vec<&PdbAtom>> = vec![vec![]; resids.len()];
+    for a in strctr.atoms() {
+        if is_hydrogen(a) { continue; }
+        if let Some(&i) = index.get(&(a.res_seq, a.i_code)) { atoms[i].push(a); }
+    }
+
+    atoms
+}
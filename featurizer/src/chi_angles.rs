@@ -0,0 +1,84 @@
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::dihedral_angle;
+
+/// Returns atom names defining chi angles of a given amino acid, from chi1 to chi4
+fn chi_atoms(res_name: &str) -> Vec<[&'static str; 4]> {
+    let chi1_end = match res_name {
+        "ARG" | "ASN" | "ASP" | "GLN" | "GLU" | "HIS" | "LEU" | "LYS" | "MET" | "PHE" | "PRO" | "TRP" | "TYR" => " CG ",
+        "CYS" => " SG ",
+        "ILE" | "VAL" => " CG1",
+        "SER" => " OG ",
+        "THR" => " OG1",
+        _ => return vec![],
+    };
+    let mut chis = vec![[" N  ", " CA ", " CB ", chi1_end]];
+    let chi2_end = match res_name {
+        "ARG" | "GLN" | "GLU" | "LYS" | "PRO" => " CD ",
+        "ASN" | "ASP" => " OD1",
+        "HIS" => " ND1",
+        "ILE" | "LEU" | "PHE" | "TRP" | "TYR" => " CD1",
+        "MET" => " SD ",
+        _ => return chis,
+    };
+    let chi2_start = if res_name == "ILE" { " CG1" } else { " CG " };
+    chis.push([" CA ", " CB ", chi2_start, chi2_end]);
+    match res_name {
+        "ARG" => chis.push([" CB ", " CG ", " CD ", " NE "]),
+        "GLN" | "GLU" => chis.push([" CB ", " CG ", " CD ", " OE1"]),
+        "LYS" => chis.push([" CB ", " CG ", " CD ", " CE "]),
+        "MET" => chis.push([" CB ", " CG ", " SD ", " CE "]),
+        _ => return chis,
+    }
+    match res_name {
+        "ARG" => chis.push([" CG ", " CD ", " NE ", " CZ "]),
+        "LYS" => chis.push([" CG ", " CD ", " CE ", " NZ "]),
+        _ => {}
+    }
+
+    chis
+}
+
+/// Computes chi1 - chi4 angles of a residue.
+///
+/// Returns the angles (NaN when a chi angle can't be computed) and the missing-atom mask: `true` for each chi
+/// that is defined for this residue type but can't be computed because some of its atoms are missing.
+pub fn chi_angles(strctr: &Structure, resid: &ResidueId) -> ([f64; 4], [bool; 4]) {
+    let mut angles = [f64::NAN; 4];
+    let mut missing = [false; 4];
+    let Ok(ca) = strctr.atom(resid, " CA ") else { return (angles, missing) };
+    for (k, names) in chi_atoms(&ca.res_name).iter().enumerate() {
+        let atoms: Vec<_> = names.iter().filter_map(|n| strctr.atom(resid, n).ok()).collect();
+        if atoms.len() < 4 {
+            missing[k] = true;
+            continue;
+        }
+        angles[k] = dihedral_angle(&atoms[0].pos, &atoms[1].pos, &atoms[2].pos, &atoms[3].pos);
+    }
+
+    (angles, missing)
+}
+
+/// Sidechain chi angles with missing-atom masks, see [`chi_angles()`]
+#[derive(Debug)]
+pub struct ChiAngles;
+
+impl FeatureExtractor for ChiAngles {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "chi_angles",
+            description: "sidechain chi1, chi2, chi3 and chi4 angles, followed by four missing-atom flags (1 when the chi is defined for the residue type but some of its atoms are missing)",
+            shape: "[L, 8]".to_string(), units: "degrees", range: Some((-180.0, 180.0)),
+            mask: "NaN when a chi is not defined for the residue type or its atoms are missing",
+            option: "--chi-angles", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        chain.resids.iter().map(|r| {
+            let (angles, missing) = chi_angles(chain.structure, r);
+            let mut row = angles.to_vec();
+            row.extend(missing.iter().map(|m| *m as u8 as f64));
+            row
+        }).collect()
+    }
+}
@@ -0,0 +1,98 @@
+//! Compressed output files. A featurization of the whole PDB produces hundreds of GB of highly compressible text,
+//! so `.dat` files and side outputs may be written as gzip or zstd streams, e.g. `2gb1_A.dat.zst`.
+//!
+//! `.meta` files are always written as plain text, so provenance of a file can be checked without decompressing it.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+#[cfg(feature = "compression")]
+use std::io::BufReader;
+
+use bioshell_io::open_file;
+use clap::ValueEnum;
+
+/// Compression of output files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    /// gzip, readable by any tool; `.gz` extension
+    Gzip,
+    /// zstd, faster and smaller than gzip; `.zst` extension
+    Zstd,
+}
+
+/// Extensions appended to names of compressed files
+pub const COMPRESSED_EXTENSIONS: [&str; 2] = ["gz", "zst"];
+
+impl Compression {
+    /// Extension appended to the name of a file compressed this way
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => COMPRESSED_EXTENSIONS[0],
+            Compression::Zstd => COMPRESSED_EXTENSIONS[1],
+        }
+    }
+
+    /// Name of a file once it's compressed, e.g. `2gb1_A.dat.zst`
+    pub fn file_name(&self, fname: &str) -> String { format!("{}.{}", fname, self.extension()) }
+}
+
+/// Name of an output file with its compression extension removed, if any
+pub fn strip_compression(fname: &str) -> &str {
+    COMPRESSED_EXTENSIONS.iter().find_map(|ext| fname.strip_suffix(ext).and_then(|f| f.strip_suffix('.'))).unwrap_or(fname)
+}
+
+/// Output root of a chain given the name of its `.dat` file, which may be compressed
+pub fn output_root(dat_fname: &str) -> &str {
+    let fname = strip_compression(dat_fname);
+    fname.strip_suffix(".dat").unwrap_or(fname)
+}
+
+/// The file of a given name or its compressed variant, whichever exists
+pub fn existing_file(fname: &str) -> Option<String> {
+    std::iter::once(fname.to_string()).chain(COMPRESSED_EXTENSIONS.iter().map(|ext| format!("{}.{}", fname, ext)))
+        .find(|f| Path::new(f).exists())
+}
+
+/// Opens a file for writing; its content is compressed as it's written. The name is used as given,
+/// see [`Compression::file_name()`]
+#[cfg(feature = "compression")]
+pub fn compressed_writer(fname: &str, compression: Compression) -> io::Result<Box<dyn Write>> {
+    let file = io::BufWriter::new(File::create(fname)?);
+    Ok(match compression {
+        Compression::Gzip => Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default())),
+        Compression::Zstd => Box::new(zstd::Encoder::new(file, 0)?.auto_finish()),
+    })
+}
+
+#[cfg(not(feature = "compression"))]
+pub fn compressed_writer(fname: &str, _compression: Compression) -> io::Result<Box<dyn Write>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("can't write {}: the featurizer was built without the compression feature", fname)))
+}
+
+/// Compresses a file into `<fname>.gz` or `<fname>.zst` and removes the plain text file
+pub fn compress_file(fname: &str, compression: Compression) -> io::Result<()> {
+    let mut writer = compressed_writer(&compression.file_name(fname), compression)?;
+    io::copy(&mut File::open(fname)?, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    fs::remove_file(fname)
+}
+
+/// Opens an output file for reading, decompressing it when its name ends with `.gz` or `.zst`
+#[cfg(feature = "compression")]
+pub fn open_output(fname: &str) -> io::Result<Box<dyn BufRead>> {
+    if fname.ends_with(".zst") { return Ok(Box::new(BufReader::new(zstd::Decoder::new(File::open(fname)?)?))); }
+    if fname.ends_with(".gz") { return Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(File::open(fname)?)))); }
+
+    open_file(fname)
+}
+
+#[cfg(not(feature = "compression"))]
+pub fn open_output(fname: &str) -> io::Result<Box<dyn BufRead>> {
+    if fname.ends_with(".zst") {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, format!("can't read {}: the featurizer was built without the compression feature", fname)));
+    }
+
+    open_file(fname)
+}
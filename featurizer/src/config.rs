@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use bioshell_io::open_file;
+
+/// Settings read from a configuration file.
+///
+/// The file holds `key = value` lines; keys are namespaced by the part of the featurizer they configure,
+/// e.g. `microenvironment.shells = 4.0, 6.0, 8.0`. Everything after `#` is a comment.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn from_file(fname: &str) -> Result<Config, String> {
+        let reader = open_file(fname).map_err(|e| e.to_string())?;
+        let mut values = HashMap::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue; }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {} of {} should be given as key = value; found: {}", i + 1, fname, line));
+            };
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        Ok(Config { values })
+    }
+
+    /// Returns a value of a key, or `default` when the key is not set
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        match self.values.get(key) {
+            Some(v) => v.parse().map_err(|_| format!("incorrect value of {}: {}", key, v)),
+            None => Ok(default),
+        }
+    }
+
+    /// Returns a comma-separated list of values of a key, or `default` when the key is not set
+    pub fn get_list_or<T: FromStr>(&self, key: &str, default: Vec<T>) -> Result<Vec<T>, String> {
+        match self.values.get(key) {
+            Some(v) => v.split(',').map(|t| t.trim().parse().map_err(|_| format!("incorrect value of {}: {}", key, v))).collect(),
+            None => Ok(default),
+        }
+    }
+}
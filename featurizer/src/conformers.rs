@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+
+use bioshell_pdb::{PDBError, Structure};
+
+use crate::chains::{extract_chain, load_deposit};
+
+/// Alternate location labels used by a structure, sorted alphabetically
+pub fn altloc_labels(strctr: &Structure) -> Vec<char> {
+    let mut labels: Vec<char> = strctr.atoms().iter().map(|a| a.alt_loc).filter(|l| *l != ' ').collect();
+    labels.sort();
+    labels.dedup();
+
+    labels
+}
+
+/// Alternate location labels of a single chain of a deposit file
+pub fn chain_altloc_labels(fname: &str, chain: &str) -> Result<Vec<char>, PDBError> {
+    let deposit = load_deposit(fname)?;
+    Ok(altloc_labels(&extract_chain(&deposit, chain)?))
+}
+
+/// Selects a single conformer of a structure.
+///
+/// Alternate locations sharing a label are assumed to form a consistent network, as it's common in crystallographic
+/// models. Atoms without an alternate location are always kept; a residue that doesn't have the `label` location
+/// contributes its first alternate location instead.
+pub fn select_conformer(strctr: &Structure, label: char) -> Structure {
+    let mut residue_labels: HashMap<(&str, i32, char), Vec<char>> = HashMap::new();
+    for a in strctr.atoms().iter().filter(|a| a.alt_loc != ' ') {
+        residue_labels.entry((a.chain_id.as_str(), a.res_seq, a.i_code)).or_default().push(a.alt_loc);
+    }
+    let chosen: HashMap<(&str, i32, char), char> = residue_labels.into_iter()
+        .map(|(k, labels)| {
+            let l = if labels.contains(&label) { label } else { *labels.iter().min().unwrap() };
+            (k, l)
+        }).collect();
+
+    Structure::from_iterator(&strctr.id_code, strctr.atoms().iter().filter(|a| {
+        a.alt_loc == ' ' || chosen.get(&(a.chain_id.as_str(), a.res_seq, a.i_code)) == Some(&a.alt_loc)
+    }))
+}
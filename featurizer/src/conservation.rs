@@ -0,0 +1,186 @@
+//! Per-residue conservation computed in-crate, for users who can't run external MSA pipelines such as HHblits.
+//!
+//! Homologs of an entity sequence are searched for in a FASTA database (e.g. a small UniRef subset): database sequences
+//! sharing the most 3-mers with the query are aligned to it and those of sufficient sequence identity are stacked into
+//! a multiple sequence alignment, from which conservation and gap frequency are computed for every query position.
+
+use std::fmt;
+use std::io::BufRead;
+
+use bioshell_io::open_file;
+use log::warn;
+
+use crate::alignment::align_sequences;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::residue_encoding::{ALPHABET, UNKNOWN_INDEX};
+
+/// Length of words used to search for homologs
+const K: usize = 3;
+
+/// Number of distinct words of length [`K`]
+const N_WORDS: usize = 21 * 21 * 21;
+
+/// Number of database sequences aligned to a query for every homolog that is requested
+const CANDIDATES_PER_HIT: usize = 4;
+
+/// Default maximum number of homologs stacked into an alignment
+pub const MAX_HITS: usize = 100;
+
+/// Default minimum sequence identity of a homolog, computed over aligned positions
+pub const MIN_IDENTITY: f64 = 0.3;
+
+/// Index of a one-letter code in the [`ALPHABET`]
+fn letter_index(aa: u8) -> u8 {
+    ALPHABET.as_bytes().iter().position(|c| *c == aa.to_ascii_uppercase()).unwrap_or(UNKNOWN_INDEX) as u8
+}
+
+/// Word index of each position of a sequence given as letter indexes; `None` for words with an unknown residue
+fn words(sequence: &[u8]) -> impl Iterator<Item = Option<usize>> + '_ {
+    sequence.windows(K).map(|w| {
+        if w.iter().any(|k| *k as usize == UNKNOWN_INDEX) { None } else { Some(w.iter().fold(0, |acc, k| acc * 21 + *k as usize)) }
+    })
+}
+
+/// Database of protein sequences indexed by words of length 3
+#[derive(Default)]
+pub struct SequenceDatabase {
+    pub fname: String,
+    /// sequences given as indexes of their letters in the [`ALPHABET`]
+    sequences: Vec<Vec<u8>>,
+    /// for every word, indexes of sequences it occurs in
+    index: Vec<Vec<u32>>,
+}
+
+impl fmt::Debug for SequenceDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SequenceDatabase {{ fname: {:?}, sequences: {} }}", self.fname, self.sequences.len())
+    }
+}
+
+impl SequenceDatabase {
+    /// Reads sequences of a FASTA file, which may be compressed
+    pub fn from_fasta(fname: &str) -> Result<SequenceDatabase, String> {
+        let reader = open_file(fname).map_err(|e| format!("can't read {}: {}", fname, e))?;
+        let mut sequences: Vec<Vec<u8>> = vec![];
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("{} in {}", e, fname))?;
+            if line.starts_with('>') { sequences.push(vec![]); continue; }
+            if let Some(seq) = sequences.last_mut() {
+                seq.extend(line.trim().bytes().filter(|c| c.is_ascii_alphabetic()).map(letter_index));
+            }
+        }
+        if sequences.is_empty() { return Err(format!("no sequences found in {}", fname)); }
+
+        Ok(SequenceDatabase::new(fname, sequences))
+    }
+
+    fn new(fname: &str, sequences: Vec<Vec<u8>>) -> SequenceDatabase {
+        let mut index: Vec<Vec<u32>> = vec![vec![]; N_WORDS];
+        for (i, seq) in sequences.iter().enumerate() {
+            for w in words(seq).flatten() {
+                // --- a sequence is listed once per word, even if the word occurs in it many times
+                if index[w].last() != Some(&(i as u32)) { index[w].push(i as u32); }
+            }
+        }
+
+        SequenceDatabase { fname: fname.to_string(), sequences, index }
+    }
+
+    /// Number of sequences in the database
+    pub fn len(&self) -> usize { self.sequences.len() }
+
+    pub fn is_empty(&self) -> bool { self.sequences.is_empty() }
+
+    /// Finds homologs of a query sequence; returns a mapping of every query position onto each homolog's sequence.
+    ///
+    /// Database sequences sharing the most words with the query are aligned to it; an alignment is accepted when
+    /// the identity over its aligned positions is at least `min_identity`.
+    pub fn homologs(&self, query: &[u8], max_hits: usize, min_identity: f64) -> Vec<(usize, Vec<Option<usize>>)> {
+        if self.sequences.is_empty() { return vec![]; }
+        let mut query_words: Vec<usize> = words(query).flatten().collect();
+        query_words.sort_unstable();
+        query_words.dedup();
+        let mut shared = vec![0u32; self.sequences.len()];
+        for w in &query_words {
+            for i in &self.index[*w] { shared[*i as usize] += 1; }
+        }
+        // --- at least two shared words, so a random match of a single word doesn't trigger an alignment
+        let mut candidates: Vec<usize> = (0..shared.len()).filter(|i| shared[*i] >= 2).collect();
+        candidates.sort_by(|a, b| shared[*b].cmp(&shared[*a]));
+        candidates.truncate(max_hits * CANDIDATES_PER_HIT);
+
+        let mut hits = vec![];
+        for i in candidates {
+            let target = &self.sequences[i];
+            let mapping = align_sequences(query, target);
+            let aligned: Vec<(usize, usize)> = mapping.iter().enumerate().filter_map(|(q, t)| t.map(|t| (q, t))).collect();
+            if aligned.is_empty() { continue; }
+            let identical = aligned.iter().filter(|(q, t)| query[*q] == target[*t]).count();
+            if (identical as f64) < min_identity * aligned.len() as f64 { continue; }
+            hits.push((i, mapping));
+            if hits.len() == max_hits { break; }
+        }
+
+        hits
+    }
+}
+
+/// Conservation and gap frequency of every position of a one-letter query sequence, computed from its homologs found in a database;
+/// `None` when no homologs are found
+pub fn conservation(database: &SequenceDatabase, sequence: &str, max_hits: usize, min_identity: f64) -> Option<Vec<[f64; 2]>> {
+    let query: Vec<u8> = sequence.bytes().map(letter_index).collect();
+    let hits = database.homologs(&query, max_hits, min_identity);
+    if hits.is_empty() { return None; }
+    let n_sequences = (hits.len() + 1) as f64;
+    let max_entropy = 20f64.ln();
+
+    let profile = (0..query.len()).map(|pos| {
+        let mut counts = [0.0; 20];
+        let mut n_gaps = 0.0;
+        let aligned = hits.iter().filter_map(|(i, mapping)| match mapping[pos] {
+            Some(t) => Some(database.sequences[*i][t] as usize),
+            None => { n_gaps += 1.0; None }
+        });
+        for k in std::iter::once(query[pos] as usize).chain(aligned) {
+            if k < 20 { counts[k] += 1.0; }
+        }
+        let total: f64 = counts.iter().sum();
+        let entropy: f64 = counts.iter().filter(|c| **c > 0.0).map(|c| -(c / total) * (c / total).ln()).sum();
+        let conserved = if total > 0.0 { 1.0 - entropy / max_entropy } else { f64::NAN };
+        [conserved, n_gaps / n_sequences]
+    }).collect();
+
+    Some(profile)
+}
+
+/// Conservation of residues computed from homologs found in a sequence database.
+///
+/// Conservation is given as `1 - H / ln(20)`, where `H` is the Shannon entropy of amino acids aligned at a position
+/// (the query included); gap frequency is the fraction of sequences of the alignment with a gap at that position.
+#[derive(Debug)]
+pub struct SequenceConservation {
+    pub database: SequenceDatabase,
+    pub max_hits: usize,
+    pub min_identity: f64,
+}
+
+impl Default for SequenceConservation {
+    fn default() -> Self { SequenceConservation { database: SequenceDatabase::default(), max_hits: MAX_HITS, min_identity: MIN_IDENTITY } }
+}
+
+impl FeatureExtractor for SequenceConservation {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "conservation",
+            description: "conservation (1 - normalized Shannon entropy) and gap frequency from an alignment of homologs found by a k-mer search in a sequence database",
+            shape: "[L, 2]".to_string(), units: "", range: Some((0.0, 1.0)),
+            mask: "NaN when no homologs are found or a residue is not aligned to the entity", option: "--conservation-db <fasta>", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let Some(profile) = conservation(&self.database, &chain.entity_sequence, self.max_hits, self.min_identity) else {
+            if !self.database.is_empty() { warn!("No homologs of {} found in {}", chain.id, self.database.fname); }
+            return vec![vec![f64::NAN; 2]; chain.resids.len()];
+        };
+        chain.entity_index.iter().map(|e| e.map_or(vec![f64::NAN; 2], |e| profile[e].to_vec())).collect()
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+
+use crate::features::FeatureInfo;
+use crate::geometry::{distance, virtual_cb};
+use crate::neighbors::NeighborGrid;
+use crate::separation::{separation_columns, sequence_separation};
+
+/// Defines which atom represents a residue and the distance cutoff for a contact, e.g. `CB:8.0`
+#[derive(Clone, Debug)]
+pub struct ContactSpec {
+    /// atom name, padded as in PDB files, e.g. `" CB "`
+    pub atom_name: String,
+    pub cutoff: f64,
+}
+
+impl FromStr for ContactSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((atom, cutoff)) = s.split_once(':') else {
+            return Err(format!("contact definition should be given as atom:cutoff, e.g. CB:8.0; found: {}", s));
+        };
+        let cutoff: f64 = cutoff.parse().map_err(|_| format!("incorrect contact cutoff value: {}", cutoff))?;
+        if atom.is_empty() || atom.len() > 4 { return Err(format!("incorrect atom name: {}", atom)); }
+
+        Ok(ContactSpec { atom_name: pdb_atom_name(atom), cutoff })
+    }
+}
+
+impl ContactSpec {
+    /// True when residues are represented by CB atoms, which are built as virtual atoms when missing
+    pub fn is_cb(&self) -> bool { self.atom_name == " CB " }
+
+    /// Representative atom positions of residues, `None` for residues that lack them
+    fn positions(&self, strctr: &Structure, resids: &[ResidueId]) -> Vec<Option<Vec3>> {
+        resids.iter().map(|r| {
+            if self.is_cb() { return cb_position(strctr, r).map(|(p, _)| p); }
+            strctr.atom(r, &self.atom_name).ok().map(|a| a.pos.clone())
+        }).collect()
+    }
+}
+
+/// Position of the CB atom of a residue and whether it's virtual.
+///
+/// For glycine and residues whose CB is missing an idealized CB is built from N, CA and C with [`virtual_cb()`];
+/// `None` is returned when any of these atoms is missing too.
+pub fn cb_position(strctr: &Structure, resid: &ResidueId) -> Option<(Vec3, bool)> {
+    if let Ok(cb) = strctr.atom(resid, " CB ") { return Some((cb.pos.clone(), false)); }
+    let pos = |name: &str| strctr.atom(resid, name).ok().map(|a| &a.pos);
+    Some((virtual_cb(pos(" N  ")?, pos(" CA ")?, pos(" C  ")?), true))
+}
+
+/// Flags residues whose CB atom is virtual, i.e. built by [`cb_position()`]
+pub fn virtual_cb_flags(strctr: &Structure, resids: &[ResidueId]) -> Vec<bool> {
+    resids.iter().map(|r| cb_position(strctr, r).is_some_and(|(_, is_virtual)| is_virtual)).collect()
+}
+
+/// Pads an atom name to the four-character form used by PDB files, e.g. `CB` becomes `" CB "`
+pub fn pdb_atom_name(name: &str) -> String {
+    if name.len() >= 4 { name.to_string() } else { format!(" {:<3}", name) }
+}
+
+/// Finds all pairs of residues whose representative atoms are closer than the cutoff.
+///
+/// Returns `(i, j, distance)` tuples with `i < j`, where indexes refer to `resids`;
+/// residues that lack the representative atom are not included, except for CB, which is built when missing (see [`cb_position()`]).
+pub fn find_contacts(strctr: &Structure, resids: &[ResidueId], spec: &ContactSpec) -> Vec<(usize, usize, f64)> {
+    NeighborGrid::new(&spec.positions(strctr, resids), spec.cutoff).pairs(spec.cutoff)
+}
+
+/// Finds contacts between residues of a chain and residues of its partner chains.
+///
+/// Returns `(i, j, distance)` tuples, where `i` refers to `resids` and `j` to `partner_resids`;
+/// both lists of residues must come from the same `strctr`.
+pub fn find_interchain_contacts(strctr: &Structure, resids: &[ResidueId], partner_resids: &[ResidueId],
+                                spec: &ContactSpec) -> Vec<(usize, usize, f64)> {
+    let partners = spec.positions(strctr, partner_resids);
+    let grid = NeighborGrid::new(&partners, spec.cutoff);
+    let mut contacts = vec![];
+    for (i, ai) in spec.positions(strctr, resids).iter().enumerate() {
+        let Some(ai) = ai else { continue };
+        for j in grid.within(ai, spec.cutoff) {
+            contacts.push((i, j, distance(ai, partners[j].as_ref().unwrap())));
+        }
+    }
+
+    contacts
+}
+
+/// Appends inter-chain contacts to a contact list as `i <chain>:<j> distance` lines.
+///
+/// When virtual CB flags of the chain and of its partners are given, they follow the distance; every line ends with
+/// the separation columns of an inter-chain pair (see [`separation_columns()`]).
+pub fn append_interchain_contacts(contacts: &[(usize, usize, f64)], partner_labels: &[String],
+                                  virtual_cb: Option<(&[bool], &[bool])>, out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, true);
+    for (i, j, d) in contacts {
+        match virtual_cb {
+            Some((vi, vj)) => writeln!(outfile, "{:4} {:>4} {:6.3} {} {} {}", i, partner_labels[*j], d, vi[*i] as u8, vj[*j] as u8,
+                                       separation_columns(None))?,
+            None => writeln!(outfile, "{:4} {:>4} {:6.3} {}", i, partner_labels[*j], d, separation_columns(None))?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes contacts either as a list of `i j distance` lines or as a binary contact matrix.
+///
+/// When virtual CB flags of residues are given, the flags of both residues follow the distance in each line of a list;
+/// every line ends with the sequence separation of the pair and its range class, see [`sequence_separation()`].
+pub fn write_contacts(contacts: &[(usize, usize, f64)], n_res: usize, as_matrix: bool, virtual_cb: Option<&[bool]>,
+                      entity_index: &[Option<usize>], out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    if as_matrix {
+        let mut matrix = vec![vec![0u8; n_res]; n_res];
+        for (i, j, _) in contacts {
+            matrix[*i][*j] = 1;
+            matrix[*j][*i] = 1;
+        }
+        for row in matrix {
+            let line: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(outfile, "{}", line.join(" "))?;
+        }
+    } else {
+        for (i, j, d) in contacts { writeln!(outfile, "{}", contact_line(*i, *j, *d, virtual_cb, entity_index))?; }
+    }
+    Ok(())
+}
+
+/// A line of a contact list, as written by [`write_contacts()`]
+fn contact_line(i: usize, j: usize, d: f64, virtual_cb: Option<&[bool]>, entity_index: &[Option<usize>]) -> String {
+    let separation = separation_columns(Some(sequence_separation(entity_index, i, j)));
+    match virtual_cb {
+        Some(v) => format!("{:4} {:4} {:6.3} {} {} {}", i, j, d, v[i] as u8, v[j] as u8, separation),
+        None => format!("{:4} {:4} {:6.3} {}", i, j, d, separation),
+    }
+}
+
+/// Finds and writes contacts of a chain as [`find_contacts()`] and [`write_contacts()`] do, `chunk` residues at a time.
+///
+/// Only the contacts of a single chunk are held in memory and a contact matrix is written row by row, so the memory
+/// needed doesn't grow with the square of the chain length. Returns the number of contacts found.
+pub fn write_contacts_chunked(strctr: &Structure, resids: &[ResidueId], spec: &ContactSpec, as_matrix: bool, virtual_cb: Option<&[bool]>,
+                              entity_index: &[Option<usize>], chunk: usize, out_fname: &str) -> Result<usize, PDBError> {
+    let positions = spec.positions(strctr, resids);
+    let grid = NeighborGrid::new(&positions, spec.cutoff);
+    let mut outfile = out_writer(out_fname, false);
+    let mut n_contacts = 0;
+    for start in (0..resids.len()).step_by(chunk.max(1)) {
+        let rows: Vec<(usize, Vec<usize>)> = (start..(start + chunk.max(1)).min(resids.len()))
+            .map(|i| (i, positions[i].as_ref().map_or(vec![], |p| grid.within(p, spec.cutoff)))).collect();
+        for (i, neighbors) in rows {
+            if as_matrix {
+                n_contacts += neighbors.iter().filter(|j| **j > i).count();
+                let mut row = vec!["0"; resids.len()];
+                for j in neighbors.into_iter().filter(|j| *j != i) { row[j] = "1"; }
+                writeln!(outfile, "{}", row.join(" "))?;
+                continue;
+            }
+            for j in neighbors.into_iter().filter(|j| *j > i) {
+                let d = distance(positions[i].as_ref().unwrap(), positions[j].as_ref().unwrap());
+                writeln!(outfile, "{}", contact_line(i, j, d, virtual_cb, entity_index))?;
+                n_contacts += 1;
+            }
+        }
+    }
+
+    Ok(n_contacts)
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "contacts",
+        description: "pairs of residues whose representative atoms are closer than a cutoff, as (i, j, distance) rows or a binary matrix; partners from other chains are given as chain:index; CB-based rows give virtual CB flags of both residues next; rows end with the signed sequence separation and its range (local, short, medium, long or inter)",
+        shape: "[n_contacts, 5], [n_contacts, 7] for CB or [L, L]".to_string(), units: "Å", range: None,
+        mask: "residues lacking the representative atom are not listed; a missing CB is built from N, CA and C",
+        option: "--contacts atom:cutoff [--contacts-matrix]", version: 4 }
+}
@@ -0,0 +1,48 @@
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::PDBError;
+
+/// Defines fixed-length windows a chain is cropped into
+#[derive(Clone, Debug)]
+pub struct Crop {
+    /// number of residues (entity positions, including gaps) in every window
+    pub length: usize,
+    /// shift between the first positions of consecutive windows
+    pub stride: usize,
+}
+
+impl Crop {
+    /// Ranges of positions covered by windows of a chain of `n` positions.
+    ///
+    /// The last window is aligned with the chain end, so every position is covered; a chain shorter than the window
+    /// length gives a single, shorter window.
+    pub fn windows(&self, n: usize) -> Vec<(usize, usize)> {
+        if n <= self.length { return vec![(0, n)]; }
+        let mut out: Vec<(usize, usize)> = (0..n - self.length).step_by(self.stride.max(1))
+            .map(|from| (from, from + self.length)).collect();
+        out.push((n - self.length, n));
+
+        out
+    }
+}
+
+/// Writes windows of a chain as blocks of `.dat` records.
+///
+/// Every block starts with a `# window k first last` line giving the range of entity positions it covers,
+/// followed by the records of these positions copied verbatim. Residue indexes and H-bond partners are therefore
+/// global for the chain, so H-bonds that cross a window boundary are still given.
+pub fn write_crops(records: &[String], crop: &Crop, out_fname: &str) -> Result<(), PDBError> {
+    write_blocks(records, &crop.windows(records.len()), |k, first, last| format!("# window {} {} {}", k, first, last), out_fname)
+}
+
+/// Writes ranges of records as blocks; a block starts with a line given by `header` for its index, first and last position
+pub fn write_blocks(records: &[String], ranges: &[(usize, usize)], header: impl Fn(usize, usize, usize) -> String,
+                    out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    for (k, (from, to)) in ranges.iter().enumerate() {
+        writeln!(outfile, "{}", header(k, *from, to - 1))?;
+        for r in &records[*from..*to] { writeln!(outfile, "{}", r)?; }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! Protection of restricted datasets: encryption of output files and signing of manifests.
+//!
+//! Files are encrypted in the [age](https://age-encryption.org) format for X25519 recipients (`age1...` public keys),
+//! so they may be decrypted with any age-compatible tool, e.g. `age -d -i key.txt 2gb1_A.dat.age`.
+//! A manifest is signed with an Ed25519 key; the detached signature is written next to it.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use age::x25519::Recipient;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use log::info;
+
+use crate::compression::output_root;
+use crate::errors::FeaturizerError;
+use crate::featurize::output_files;
+use crate::manifest::ChainSummary;
+
+/// Extension appended to the name of an encrypted file
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
+/// Extension appended to the name of a signed file to name its signature
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+fn invalid(message: String) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, message) }
+
+fn to_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() }
+
+fn from_hex<const N: usize>(text: &str) -> Result<[u8; N], io::Error> {
+    let text = text.trim();
+    if text.len() != 2 * N || !text.is_ascii() { return Err(invalid(format!("{} hex digits expected, found {}", 2 * N, text.len()))); }
+    let mut out = [0u8; N];
+    for (k, b) in out.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&text[2 * k..2 * k + 2], 16).map_err(|e| invalid(e.to_string()))?;
+    }
+    Ok(out)
+}
+
+/// Parses age public keys of the recipients of encrypted files
+pub fn parse_recipients(keys: &[String]) -> Result<Vec<Recipient>, FeaturizerError> {
+    keys.iter().map(|k| k.parse::<Recipient>().map_err(|e| invalid(format!("incorrect age recipient {}: {}", k, e)).into())).collect()
+}
+
+/// Encrypts a file for the given recipients into `<fname>.age` and removes the plain text file
+pub fn encrypt_file(fname: &str, recipients: &[Recipient]) -> Result<(), FeaturizerError> {
+    let boxed = recipients.iter().map(|r| Box::new(r.clone()) as Box<dyn age::Recipient + Send>).collect();
+    let encryptor = age::Encryptor::with_recipients(boxed).ok_or_else(|| invalid("no recipients of encrypted files given".to_string()))?;
+    let out_fname = format!("{}.{}", fname, ENCRYPTED_EXTENSION);
+    let mut writer = encryptor.wrap_output(File::create(&out_fname)?)?;
+    io::copy(&mut File::open(fname)?, &mut writer)?;
+    writer.finish()?;
+    fs::remove_file(fname)?;
+
+    Ok(())
+}
+
+/// Encrypts all the output files of featurized chains; returns the number of encrypted files
+pub fn encrypt_outputs(summaries: &[ChainSummary], recipients: &[Recipient]) -> Result<usize, FeaturizerError> {
+    let mut n_files = 0;
+    for s in summaries {
+        for fname in output_files(output_root(&s.output)) {
+            encrypt_file(&fname, recipients)?;
+            n_files += 1;
+        }
+    }
+    info!("{} output files encrypted for {} recipient(s)", n_files, recipients.len());
+
+    Ok(n_files)
+}
+
+/// Encrypts all files found under a directory, e.g. Parquet part files; returns the number of encrypted files
+pub fn encrypt_dir(dir: &Path, recipients: &[Recipient]) -> Result<usize, FeaturizerError> {
+    let mut n_files = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() { n_files += encrypt_dir(&path, recipients)?; continue; }
+        if path.extension().is_some_and(|e| e == ENCRYPTED_EXTENSION) { continue; }
+        encrypt_file(path.to_str().unwrap_or_default(), recipients)?;
+        n_files += 1;
+    }
+
+    Ok(n_files)
+}
+
+/// Reads an Ed25519 signing key: a file holding its 32-byte seed as 64 hex digits
+pub fn read_signing_key(fname: &str) -> Result<SigningKey, FeaturizerError> {
+    Ok(SigningKey::from_bytes(&from_hex::<32>(&fs::read_to_string(fname)?)?))
+}
+
+/// Signs a file with an Ed25519 key; the signature is written to `<fname>.sig` as two lines of hex digits:
+/// the public key and the signature of the file content
+pub fn sign_file(fname: &str, key: &SigningKey) -> Result<(), FeaturizerError> {
+    let signature = key.sign(&fs::read(fname)?);
+    let mut out = File::create(format!("{}.{}", fname, SIGNATURE_EXTENSION))?;
+    writeln!(out, "{}", to_hex(key.verifying_key().as_bytes()))?;
+    writeln!(out, "{}", to_hex(&signature.to_bytes()))?;
+
+    Ok(())
+}
+
+/// Checks the signature of a file written by [`sign_file()`]; returns the hex-encoded public key it was signed with
+pub fn verify_file(fname: &str) -> Result<String, FeaturizerError> {
+    let text = fs::read_to_string(format!("{}.{}", fname, SIGNATURE_EXTENSION))?;
+    let mut lines = text.lines();
+    let (Some(public), Some(signature)) = (lines.next(), lines.next()) else {
+        return Err(invalid(format!("incorrect signature file of {}", fname)).into());
+    };
+    let key = VerifyingKey::from_bytes(&from_hex::<32>(public)?).map_err(|e| invalid(e.to_string()))?;
+    let signature = Signature::from_bytes(&from_hex::<64>(signature)?);
+    key.verify(&fs::read(fname)?, &signature).map_err(|_| invalid(format!("{} doesn't match its signature", fname)))?;
+
+    Ok(public.to_string())
+}
@@ -0,0 +1,444 @@
+use std::io::BufRead;
+
+use bioshell_io::open_file;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, Structure};
+use log::{debug, warn};
+
+use crate::assembly::{read_cif_category, SymmetryOperator};
+use crate::chains::{is_hydrogen, residue_heavy_atoms, select_chain, STDIN};
+use crate::config::Config;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::distance;
+
+/// Atoms of symmetry mates are looked for within this distance from a chain, in Å; nearest-mate distances are
+/// reported up to this value
+pub const LATTICE_RANGE: f64 = 10.0;
+
+/// Heavy atoms of symmetry mates closer than this to a chain clash with it, in Å
+const CLASH_DISTANCE: f64 = 2.0;
+
+/// Generators of the space groups found in the PDB, by their Hermann-Mauguin symbols.
+///
+/// The operators of a group are generated from these and from the lattice centering given by the first letter
+/// of a symbol; `H` stands for the hexagonal setting of a rhombohedral lattice, `R` for its rhombohedral axes.
+const SPACE_GROUPS: &[(&str, &[&str])] = &[
+    ("P 1", &[]),
+    ("P 1 2 1", &["-x,y,-z"]),
+    ("P 1 21 1", &["-x,y+1/2,-z"]),
+    ("C 1 2 1", &["-x,y,-z"]),
+    ("I 1 2 1", &["-x,y,-z"]),
+    ("P 2 2 2", &["-x,-y,z", "-x,y,-z"]),
+    ("P 2 2 21", &["-x,-y,z+1/2", "-x,y,-z+1/2"]),
+    ("P 21 2 2", &["-x+1/2,-y,z", "x+1/2,-y,-z"]),
+    ("P 2 21 2", &["-x,-y,z", "-x,y+1/2,-z"]),
+    ("P 21 21 2", &["-x,-y,z", "-x+1/2,y+1/2,-z"]),
+    ("P 21 2 21", &["-x+1/2,-y,z+1/2", "x+1/2,-y,-z"]),
+    ("P 2 21 21", &["-x,-y+1/2,z+1/2", "-x,y+1/2,-z+1/2"]),
+    ("P 21 21 21", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2"]),
+    ("C 2 2 21", &["-x,-y,z+1/2", "-x,y,-z+1/2"]),
+    ("C 2 2 2", &["-x,-y,z", "-x,y,-z"]),
+    ("F 2 2 2", &["-x,-y,z", "-x,y,-z"]),
+    ("I 2 2 2", &["-x,-y,z", "-x,y,-z"]),
+    ("I 21 21 21", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2"]),
+    ("P 4", &["-y,x,z"]),
+    ("P 41", &["-y,x,z+1/4"]),
+    ("P 42", &["-y,x,z+1/2"]),
+    ("P 43", &["-y,x,z+3/4"]),
+    ("I 4", &["-y,x,z"]),
+    ("I 41", &["-y,x+1/2,z+1/4"]),
+    ("P 4 2 2", &["-y,x,z", "-x,y,-z"]),
+    ("P 4 21 2", &["-y+1/2,x+1/2,z", "-x+1/2,y+1/2,-z"]),
+    ("P 41 2 2", &["-y,x,z+1/4", "-x,y,-z"]),
+    ("P 41 21 2", &["-y+1/2,x+1/2,z+1/4", "-x+1/2,y+1/2,-z+1/4"]),
+    ("P 42 2 2", &["-y,x,z+1/2", "-x,y,-z"]),
+    ("P 42 21 2", &["-y+1/2,x+1/2,z+1/2", "-x+1/2,y+1/2,-z+1/2"]),
+    ("P 43 2 2", &["-y,x,z+3/4", "-x,y,-z"]),
+    ("P 43 21 2", &["-y+1/2,x+1/2,z+3/4", "-x+1/2,y+1/2,-z+3/4"]),
+    ("I 4 2 2", &["-y,x,z", "-x,y,-z"]),
+    ("I 41 2 2", &["-y,x+1/2,z+1/4", "-x+1/2,y,-z+3/4"]),
+    ("P 3", &["-y,x-y,z"]),
+    ("P 31", &["-y,x-y,z+1/3"]),
+    ("P 32", &["-y,x-y,z+2/3"]),
+    ("H 3", &["-y,x-y,z"]),
+    ("R 3", &["z,x,y"]),
+    ("P 3 1 2", &["-y,x-y,z", "-y,-x,-z"]),
+    ("P 3 2 1", &["-y,x-y,z", "y,x,-z"]),
+    ("P 31 1 2", &["-y,x-y,z+1/3", "-y,-x,-z+2/3"]),
+    ("P 31 2 1", &["-y,x-y,z+1/3", "y,x,-z"]),
+    ("P 32 1 2", &["-y,x-y,z+2/3", "-y,-x,-z+1/3"]),
+    ("P 32 2 1", &["-y,x-y,z+2/3", "y,x,-z"]),
+    ("H 3 2", &["-y,x-y,z", "y,x,-z"]),
+    ("R 3 2", &["z,x,y", "-y,-x,-z"]),
+    ("P 6", &["x-y,x,z"]),
+    ("P 61", &["x-y,x,z+1/6"]),
+    ("P 65", &["x-y,x,z+5/6"]),
+    ("P 62", &["x-y,x,z+1/3"]),
+    ("P 64", &["x-y,x,z+2/3"]),
+    ("P 63", &["x-y,x,z+1/2"]),
+    ("P 6 2 2", &["x-y,x,z", "y,x,-z"]),
+    ("P 61 2 2", &["x-y,x,z+1/6", "y,x,-z+1/3"]),
+    ("P 65 2 2", &["x-y,x,z+5/6", "y,x,-z+2/3"]),
+    ("P 62 2 2", &["x-y,x,z+1/3", "y,x,-z+2/3"]),
+    ("P 64 2 2", &["x-y,x,z+2/3", "y,x,-z+1/3"]),
+    ("P 63 2 2", &["x-y,x,z+1/2", "y,x,-z"]),
+    ("P 2 3", &["-x,-y,z", "-x,y,-z", "z,x,y"]),
+    ("F 2 3", &["-x,-y,z", "-x,y,-z", "z,x,y"]),
+    ("I 2 3", &["-x,-y,z", "-x,y,-z", "z,x,y"]),
+    ("P 21 3", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2", "z,x,y"]),
+    ("I 21 3", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2", "z,x,y"]),
+    ("P 4 3 2", &["-x,-y,z", "-x,y,-z", "z,x,y", "y,x,-z"]),
+    ("P 42 3 2", &["-x,-y,z", "-x,y,-z", "z,x,y", "y+1/2,x+1/2,-z+1/2"]),
+    ("F 4 3 2", &["-x,-y,z", "-x,y,-z", "z,x,y", "y,x,-z"]),
+    ("F 41 3 2", &["-x,-y+1/2,z+1/2", "-x+1/2,y+1/2,-z", "z,x,y", "y+3/4,x+1/4,-z+3/4"]),
+    ("I 4 3 2", &["-x,-y,z", "-x,y,-z", "z,x,y", "y,x,-z"]),
+    ("P 43 3 2", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2", "z,x,y", "y+1/4,x+3/4,-z+3/4"]),
+    ("P 41 3 2", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2", "z,x,y", "y+3/4,x+1/4,-z+1/4"]),
+    ("I 41 3 2", &["-x+1/2,-y,z+1/2", "-x,y+1/2,-z+1/2", "z,x,y", "y+3/4,x+1/4,-z+1/4"]),
+];
+
+/// Largest number of operators of a space group, guarding against generators that don't close into a group
+const MAX_OPERATORS: usize = 192;
+
+/// Lattice centering translations given by the first letter of a Hermann-Mauguin symbol
+fn centering(symbol: &str) -> Vec<[f64; 3]> {
+    match symbol.chars().next() {
+        Some('A') => vec![[0.0, 0.5, 0.5]],
+        Some('B') => vec![[0.5, 0.0, 0.5]],
+        Some('C') => vec![[0.5, 0.5, 0.0]],
+        Some('I') => vec![[0.5, 0.5, 0.5]],
+        Some('F') => vec![[0.0, 0.5, 0.5], [0.5, 0.0, 0.5], [0.5, 0.5, 0.0]],
+        Some('H') => vec![[2.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0], [1.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0]],
+        _ => vec![],
+    }
+}
+
+/// Parses a symmetry operator written in the `x,y,z` notation, e.g. `-x+1/2,y+1/2,-z` or `1/2+X,-Y,Z`
+pub fn parse_xyz(s: &str) -> Option<SymmetryOperator> {
+    let rows: Vec<String> = s.trim().trim_matches(|c: char| c == '\'' || c == '"').split(',')
+        .map(|r| r.replace(' ', "").to_lowercase()).collect();
+    if rows.len() != 3 { return None; }
+    let mut op = SymmetryOperator { rotation: [[0.0; 3]; 3], translation: [0.0; 3] };
+    for (i, row) in rows.iter().enumerate() {
+        let mut terms: Vec<String> = vec![];
+        for c in row.chars() {
+            match terms.last_mut() {
+                Some(term) if !((c == '+' || c == '-') && !term.is_empty()) => term.push(c),
+                _ => terms.push(c.to_string()),
+            }
+        }
+        for term in &terms {
+            let (sign, body) = match term.strip_prefix('-') {
+                Some(body) => (-1.0, body),
+                None => (1.0, term.trim_start_matches('+')),
+            };
+            let axis = body.chars().last().and_then(|c| "xyz".find(c));
+            let number = (if axis.is_some() { &body[..body.len() - 1] } else { body }).trim_end_matches('*');
+            let value = match number.split_once('/') {
+                _ if number.is_empty() => 1.0,
+                Some((p, q)) => p.parse::<f64>().ok()? / q.parse::<f64>().ok()?,
+                None => number.parse::<f64>().ok()?,
+            };
+            match axis {
+                Some(j) => op.rotation[i][j] += sign * value,
+                None => op.translation[i] += sign * value,
+            }
+        }
+    }
+
+    Some(op)
+}
+
+/// Operator `a` applied after `b`, with its translation reduced to the unit cell
+fn compose(a: &SymmetryOperator, b: &SymmetryOperator) -> SymmetryOperator {
+    let mut op = SymmetryOperator { rotation: [[0.0; 3]; 3], translation: a.translation };
+    for i in 0..3 {
+        for j in 0..3 {
+            op.rotation[i][j] = (0..3).map(|k| a.rotation[i][k] * b.rotation[k][j]).sum();
+            op.translation[i] += a.rotation[i][j] * b.translation[j];
+        }
+    }
+    reduce(op)
+}
+
+fn reduce(mut op: SymmetryOperator) -> SymmetryOperator {
+    for t in op.translation.iter_mut() {
+        *t -= t.floor();
+        if *t > 1.0 - 1e-6 { *t = 0.0; }
+    }
+    op
+}
+
+fn same_operator(a: &SymmetryOperator, b: &SymmetryOperator) -> bool {
+    (0..3).all(|i| (a.translation[i] - b.translation[i]).abs() < 1e-6
+        && (0..3).all(|j| (a.rotation[i][j] - b.rotation[i][j]).abs() < 1e-6))
+}
+
+/// All the operators of a group generated by the given ones, translations taken modulo the unit cell
+fn expand_group(generators: &[SymmetryOperator]) -> Vec<SymmetryOperator> {
+    let identity = parse_xyz("x,y,z").unwrap();
+    let mut ops = vec![identity];
+    for g in generators.iter().cloned().map(reduce) {
+        if !ops.iter().any(|o| same_operator(o, &g)) { ops.push(g); }
+    }
+    let mut k = 0;
+    while k < ops.len() && ops.len() <= MAX_OPERATORS {
+        for l in 0..ops.len() {
+            let product = compose(&ops[k], &ops[l]);
+            if !ops.iter().any(|o| same_operator(o, &product)) { ops.push(product); }
+        }
+        k += 1;
+    }
+
+    ops
+}
+
+/// Operators of a space group given by its Hermann-Mauguin symbol, including lattice centering; `None` for
+/// a symbol this module doesn't know. Symbols are compared without spaces, so `P21`, `P 1 21 1` and `P1211` are the same.
+pub fn space_group_operators(symbol: &str) -> Option<Vec<SymmetryOperator>> {
+    let compact: String = symbol.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+    // --- short monoclinic symbols, such as P21 or C2, stand for the settings with the unique axis b
+    let compact = match compact.as_str() {
+        "P2" | "P21" | "C2" | "I2" => format!("{}1{}1", &compact[..1], &compact[1..]),
+        _ => compact,
+    };
+    let (name, generators) = SPACE_GROUPS.iter().find(|(name, _)| name.replace(' ', "") == compact)?;
+    let mut generators: Vec<SymmetryOperator> = generators.iter().filter_map(|g| parse_xyz(g)).collect();
+    for t in centering(name) {
+        generators.push(SymmetryOperator { rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], translation: t });
+    }
+
+    Some(expand_group(&generators))
+}
+
+/// Unit cell and symmetry of a crystal structure
+#[derive(Clone, Debug)]
+pub struct CrystalLattice {
+    /// Hermann-Mauguin symbol of the space group
+    pub space_group: String,
+    /// symmetry operators of the space group, acting on fractional coordinates
+    pub operators: Vec<SymmetryOperator>,
+    /// orthogonalization matrix: columns are the cell vectors a, b and c, with a along X and b in the XY plane
+    orthogonal: [[f64; 3]; 3],
+}
+
+impl CrystalLattice {
+    /// Creates a lattice from the cell edges (in Å) and angles (in degrees)
+    pub fn new(cell: [f64; 6], space_group: &str, operators: Vec<SymmetryOperator>) -> CrystalLattice {
+        let [a, b, c, alpha, beta, gamma] = cell;
+        let (ca, cb, cg) = (alpha.to_radians().cos(), beta.to_radians().cos(), gamma.to_radians().cos());
+        let sg = gamma.to_radians().sin();
+        let volume_factor = (1.0 - ca * ca - cb * cb - cg * cg + 2.0 * ca * cb * cg).max(0.0).sqrt();
+        let orthogonal = [[a, b * cg, c * cb], [0.0, b * sg, c * (ca - cb * cg) / sg], [0.0, 0.0, c * volume_factor / sg]];
+
+        CrystalLattice { space_group: space_group.to_string(), operators, orthogonal }
+    }
+
+    /// Reads the unit cell and the space group of a deposit.
+    ///
+    /// For an mmCIF file these are given by the `_cell` and `_symmetry` (or `_space_group`) categories; symmetry
+    /// operators listed by `_space_group_symop` or `_symmetry_equiv` are used when present, otherwise they are generated
+    /// from the space group symbol. For a PDB file the `CRYST1` record is used. `None` is returned for a deposit that
+    /// doesn't describe a crystal, e.g. an NMR or cryo-EM structure with the conventional 1 Å cell, and when the space
+    /// group is not known.
+    pub fn from_file(fname: &str) -> Result<Option<CrystalLattice>, PDBError> {
+        if fname == STDIN {
+            warn!("Crystal lattice can't be built for a deposit read from the standard input");
+            return Ok(None);
+        }
+        let records = match cif_lattice(fname)? {
+            Some(records) => Some(records),
+            None => pdb_lattice(fname)?,
+        };
+        let Some((cell, space_group, operators)) = records else {
+            debug!("No unit cell found in {}", fname);
+            return Ok(None);
+        };
+        if cell[..3].iter().any(|edge| *edge <= 1.0) {
+            debug!("{} doesn't describe a crystal structure: its unit cell is {:?}", fname, cell);
+            return Ok(None);
+        }
+        // --- the R symbols of the PDB stand for the hexagonal setting when the cell is hexagonal
+        let symbol = match space_group.trim().strip_prefix('R') {
+            Some(rest) if (cell[5] - 120.0).abs() < 0.1 => format!("H{}", rest),
+            _ => space_group.trim().to_string(),
+        };
+        let operators = if operators.is_empty() { space_group_operators(&symbol) } else { Some(operators) };
+        let Some(operators) = operators else {
+            warn!("Unknown space group {} of {}; crystal contacts are not found", space_group, fname);
+            return Ok(None);
+        };
+
+        Ok(Some(CrystalLattice::new(cell, &space_group, operators)))
+    }
+
+    pub fn to_cartesian(&self, f: &Vec3) -> Vec3 {
+        let m = &self.orthogonal;
+        Vec3::new(m[0][0] * f.x + m[0][1] * f.y + m[0][2] * f.z, m[1][1] * f.y + m[1][2] * f.z, m[2][2] * f.z)
+    }
+
+    pub fn to_fractional(&self, v: &Vec3) -> Vec3 {
+        let m = &self.orthogonal;
+        let w = v.z / m[2][2];
+        let y = (v.y - m[1][2] * w) / m[1][1];
+        Vec3::new((v.x - m[0][1] * y - m[0][2] * w) / m[0][0], y, w)
+    }
+
+    /// Heavy atoms of the symmetry mates of `strctr` (typically the asymmetric unit) found within `range`
+    /// from any of the `around` positions; the asymmetric unit itself is not included
+    pub fn mates(&self, strctr: &Structure, around: &[Vec3], range: f64) -> Vec<Vec3> {
+        let atoms: Vec<Vec3> = strctr.atoms().iter().filter(|a| !is_hydrogen(a)).map(|a| self.to_fractional(&a.pos)).collect();
+        if atoms.is_empty() || around.is_empty() { return vec![]; }
+        let (center, radius) = bounding_sphere(around);
+        let (au_center, au_radius) = bounding_sphere(&atoms.iter().map(|f| self.to_cartesian(f)).collect::<Vec<_>>());
+        let target = self.to_fractional(&center);
+        let reach = radius + au_radius + range;
+        // --- how many cells are spanned by the reach along each axis: the reach divided by the spacing of lattice planes
+        let spacing = |i: usize| {
+            let row = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)].map(|e| {
+                let f = self.to_fractional(&e);
+                [f.x, f.y, f.z][i]
+            });
+            (row.iter().map(|v| v * v).sum::<f64>().sqrt() * reach).ceil() as i32
+        };
+        let span = [spacing(0), spacing(1), spacing(2)];
+        let mut mates = vec![];
+        for op in &self.operators {
+            let image_center = op.apply(&self.to_fractional(&au_center));
+            let base = [(target.x - image_center.x).round(), (target.y - image_center.y).round(), (target.z - image_center.z).round()];
+            for i in -span[0]..=span[0] {
+                for j in -span[1]..=span[1] {
+                    for k in -span[2]..=span[2] {
+                        let shift = Vec3::new(base[0] + i as f64, base[1] + j as f64, base[2] + k as f64);
+                        if op.is_identity() && shift.x == 0.0 && shift.y == 0.0 && shift.z == 0.0 { continue; }
+                        let shifted = |f: &Vec3| { let p = op.apply(f); Vec3::new(p.x + shift.x, p.y + shift.y, p.z + shift.z) };
+                        if distance(&self.to_cartesian(&shifted(&self.to_fractional(&au_center))), &center) > reach { continue; }
+                        mates.extend(atoms.iter().map(|f| self.to_cartesian(&shifted(f)))
+                            .filter(|p| distance(p, &center) <= radius + range));
+                    }
+                }
+            }
+        }
+
+        mates
+    }
+}
+
+/// Centroid of a set of points and the largest distance of a point from it
+fn bounding_sphere(points: &[Vec3]) -> (Vec3, f64) {
+    let n = points.len() as f64;
+    let center = Vec3::new(points.iter().map(|p| p.x).sum::<f64>() / n, points.iter().map(|p| p.y).sum::<f64>() / n,
+                           points.iter().map(|p| p.z).sum::<f64>() / n);
+    let radius = points.iter().map(|p| distance(p, &center)).fold(0.0, f64::max);
+    (center, radius)
+}
+
+type LatticeRecords = ([f64; 6], String, Vec<SymmetryOperator>);
+
+fn cif_lattice(fname: &str) -> Result<Option<LatticeRecords>, PDBError> {
+    let cells = read_cif_category(fname, "_cell")?;
+    let Some(cell) = cells.first() else { return Ok(None) };
+    let keys = ["length_a", "length_b", "length_c", "angle_alpha", "angle_beta", "angle_gamma"];
+    let values: Vec<f64> = keys.iter().filter_map(|k| cell.get(*k)?.parse().ok()).collect();
+    let Ok(cell) = <[f64; 6]>::try_from(values) else { return Ok(None) };
+    let mut space_group = read_cif_category(fname, "_symmetry")?.first().and_then(|s| s.get("space_group_name_H-M").cloned());
+    if space_group.is_none() {
+        space_group = read_cif_category(fname, "_space_group")?.first().and_then(|s| s.get("name_H-M_alt").cloned());
+    }
+    let mut operators: Vec<SymmetryOperator> = read_cif_category(fname, "_space_group_symop")?.iter()
+        .filter_map(|r| parse_xyz(r.get("operation_xyz")?)).collect();
+    if operators.is_empty() {
+        operators = read_cif_category(fname, "_symmetry_equiv")?.iter().filter_map(|r| parse_xyz(r.get("pos_as_xyz")?)).collect();
+    }
+
+    Ok(Some((cell, space_group.unwrap_or_default(), operators)))
+}
+
+fn pdb_lattice(fname: &str) -> Result<Option<LatticeRecords>, PDBError> {
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        if line.starts_with("CRYST1") {
+            let field = |from: usize, to: usize| line.get(from..to.min(line.len())).and_then(|v| v.trim().parse::<f64>().ok());
+            let values: Option<Vec<f64>> = [(6, 15), (15, 24), (24, 33), (33, 40), (40, 47), (47, 54)].iter().map(|(f, t)| field(*f, *t)).collect();
+            let Some(Ok(cell)) = values.map(<[f64; 6]>::try_from) else { return Ok(None) };
+            let space_group = line.get(55..66.min(line.len())).unwrap_or("").trim().to_string();
+            return Ok(Some((cell, space_group, vec![])));
+        }
+        if line.starts_with("ATOM") { break; }
+    }
+
+    Ok(None)
+}
+
+/// Distance from every residue of a chain to the nearest heavy atom of a symmetry mate of `polymer`, computed over
+/// the heavy atoms of the residue.
+///
+/// `polymer` should hold the asymmetric unit as deposited; the distances follow the order of its residues of `chain`.
+/// Distances larger than [`LATTICE_RANGE`] are NaN. `None` is returned when the deposit doesn't describe a crystal
+/// or its space group is not known.
+pub fn lattice_distances(fname: &str, polymer: &Structure, chain: &str) -> Result<Option<Vec<f64>>, PDBError> {
+    let Some(lattice) = CrystalLattice::from_file(fname)? else { return Ok(None) };
+    let strctr = select_chain(polymer, chain)?;
+    let resids = strctr.residue_ids();
+    let residues = residue_heavy_atoms(&strctr, &resids);
+    let around: Vec<Vec3> = residues.iter().flatten().map(|a| a.pos.clone()).collect();
+    let mates = lattice.mates(polymer, &around, LATTICE_RANGE);
+    let distances: Vec<f64> = residues.iter().map(|atoms| {
+        let nearest = atoms.iter().flat_map(|a| mates.iter().map(|m| distance(&a.pos, m))).fold(f64::INFINITY, f64::min);
+        if nearest <= LATTICE_RANGE { nearest } else { f64::NAN }
+    }).collect();
+    let n_clashes = distances.iter().filter(|d| **d < CLASH_DISTANCE).count();
+    if n_clashes > 0 {
+        warn!("{} residues of chain {} of {} clash with symmetry mates in {}; the unit cell or the space group may be wrong",
+              n_clashes, chain, fname, lattice.space_group);
+    }
+
+    Ok(Some(distances))
+}
+
+/// Crystal contacts: residues within a cutoff distance of a symmetry mate in the crystal lattice.
+///
+/// Symmetry mates are generated from the unit cell and the space group of a deposit, and every residue is flagged when
+/// any of its heavy atoms lies within the cutoff from a heavy atom of a mate, followed by that nearest distance.
+/// Such a surface is buried by the lattice rather than by the molecule itself, so the flag lets lattice artifacts
+/// be masked out of surface exposure features and interface labels.
+#[derive(Debug)]
+pub struct CrystalContacts {
+    /// distance to a symmetry mate that makes a contact, in Å
+    pub cutoff: f64,
+}
+
+impl Default for CrystalContacts {
+    fn default() -> Self { CrystalContacts { cutoff: 4.0 } }
+}
+
+impl CrystalContacts {
+    /// Reads the `crystal_contacts.cutoff` setting
+    pub fn from_config(config: &Config) -> Result<CrystalContacts, String> {
+        let cutoff = config.get_or("crystal_contacts.cutoff", CrystalContacts::default().cutoff)?;
+        if cutoff <= 0.0 || cutoff > LATTICE_RANGE {
+            return Err(format!("crystal contact cutoff should be positive and not larger than {}; found: {}", LATTICE_RANGE, cutoff));
+        }
+        Ok(CrystalContacts { cutoff })
+    }
+}
+
+impl FeatureExtractor for CrystalContacts {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "crystal_contacts",
+            description: "1 when a heavy atom of a residue is within the cutoff of a symmetry mate in the crystal lattice, 0 otherwise; distance to the nearest heavy atom of a mate",
+            shape: "[L, 2]".to_string(), units: "-, Å", range: None,
+            mask: "NaN when the deposit gives no crystal lattice (e.g. NMR and cryo-EM structures); NaN distance beyond 10 Å",
+            option: "--crystal-contacts [--config <file>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let Some(distances) = chain.lattice_distances.as_ref() else { return vec![vec![f64::NAN; 2]; chain.resids.len()] };
+        distances.iter().map(|d| {
+            let flag = if *d <= self.cutoff { 1.0 } else { 0.0 };
+            vec![flag, *d]
+        }).collect()
+    }
+
+    fn needs_lattice(&self) -> bool { true }
+}
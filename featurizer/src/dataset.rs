@@ -0,0 +1,230 @@
+//! Zero-copy reading of a dataset packed by the `pack` subcommand, for the training data loader.
+//!
+//! Tar shards are memory-mapped (with the `mmap` feature; otherwise they are read into memory once) and the members
+//! of every chain are located by their tar headers, so the outputs of a chain are given as slices of the shard,
+//! without copying or decompressing a file. Records are parsed by the same functions the rest of the featurizer uses,
+//! so loaders written in Rust, or in Python through the bindings, don't repeat the `.dat` format.
+//!
+//! Only tar shards with uncompressed members are read: `pack --format zip` deflates the text members, which therefore
+//! can't be given as slices of a shard, and [`FeatureDataset::open()`] rejects such a dataset with an `Unsupported` error.
+//! There is no HDF5 output to read either. A dataset meant for this loader is packed with `--format tar` and without `--compress`.
+
+use std::collections::HashMap;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::archive::read_index;
+use crate::errors::FeaturizerError;
+use crate::records::{feature_columns, parse_record, DatRecord};
+
+/// Size of a tar block; every header takes one, and the content of a member is padded to a multiple of it
+const TAR_BLOCK: usize = 512;
+
+/// Content of a shard: memory-mapped or read into memory
+enum ShardData {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    #[cfg(not(feature = "mmap"))]
+    Loaded(Vec<u8>),
+}
+
+impl ShardData {
+    #[cfg(feature = "mmap")]
+    fn open(fname: &Path) -> io::Result<ShardData> {
+        let file = File::open(fname)?;
+        // SAFETY: shards are written once by `pack` and never modified while a dataset is read
+        Ok(ShardData::Mapped(unsafe { memmap2::Mmap::map(&file)? }))
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn open(fname: &Path) -> io::Result<ShardData> { Ok(ShardData::Loaded(std::fs::read(fname)?)) }
+
+    fn bytes(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            ShardData::Mapped(map) => &map[..],
+            #[cfg(not(feature = "mmap"))]
+            ShardData::Loaded(data) => &data[..],
+        }
+    }
+}
+
+/// Value of a numeric field of a tar header: octal digits, or a big-endian number when the highest bit is set (GNU)
+fn tar_number(field: &[u8]) -> Option<usize> {
+    if field[0] & 0x80 != 0 { return Some(field[1..].iter().fold(0, |n, b| (n << 8) | *b as usize)); }
+    let digits = std::str::from_utf8(field).ok()?.trim_matches(|c| c == '\0' || c == ' ');
+    if digits.is_empty() { return Some(0); }
+
+    usize::from_str_radix(digits, 8).ok()
+}
+
+/// A NUL-terminated string of a tar header
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Regular files of a tar archive: their names and where their content lies in the archive
+fn tar_members(data: &[u8]) -> io::Result<HashMap<String, Range<usize>>> {
+    let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidData, what);
+    let mut members = HashMap::new();
+    let mut long_name: Option<String> = None;
+    let mut offset = 0;
+    while offset + TAR_BLOCK <= data.len() {
+        let header = &data[offset..offset + TAR_BLOCK];
+        // --- the archive ends with empty blocks
+        if header.iter().all(|b| *b == 0) { break; }
+        let size = tar_number(&header[124..136]).ok_or_else(|| invalid(format!("corrupted tar header at byte {}", offset)))?;
+        let content = offset + TAR_BLOCK..offset + TAR_BLOCK + size;
+        if content.end > data.len() { return Err(invalid(format!("truncated tar archive: a member ends past byte {}", data.len()))); }
+        match header[156] {
+            // --- a GNU long name is stored as the content of an extra member preceding the file
+            b'L' => long_name = Some(tar_string(&data[content.clone()])),
+            b'0' | 0 => {
+                let name = long_name.take().unwrap_or_else(|| match &header[257..263] {
+                    b"ustar\0" if header[345] != 0 => format!("{}/{}", tar_string(&header[345..500]), tar_string(&header[..100])),
+                    _ => tar_string(&header[..100]),
+                });
+                members.insert(name, content.clone());
+            }
+            _ => long_name = None,
+        }
+        offset = content.start + size.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+    }
+
+    Ok(members)
+}
+
+/// A chain of a dataset: the shard it's stored in and its members
+struct ChainEntry {
+    key: String,
+    shard: usize,
+    /// names of the members of the chain with their location in the shard
+    members: Vec<(String, Range<usize>)>,
+}
+
+/// A dataset packed into tar shards, opened by its index (`<prefix>.index.tsv`, written by the `pack` subcommand).
+///
+/// Shards are found next to the index. Chains are given in the order of the index, or by their keys:
+/// ```no_run
+/// use featurizer::dataset::FeatureDataset;
+///
+/// let dataset = FeatureDataset::open("shards/dataset.index.tsv").unwrap();
+/// for chain in dataset.iter() {
+///     let records = chain.records().unwrap();
+///     println!("{}: {} residues", chain.key, records.len());
+/// }
+/// ```
+pub struct FeatureDataset {
+    shards: Vec<ShardData>,
+    chains: Vec<ChainEntry>,
+    by_key: HashMap<String, usize>,
+}
+
+impl FeatureDataset {
+    /// Opens a dataset by its index; every shard is mapped and its tar headers read, member contents are not read
+    pub fn open(index_fname: &str) -> Result<FeatureDataset, FeaturizerError> {
+        let index = read_index(index_fname)?;
+        let dir = Path::new(index_fname).parent().unwrap_or(Path::new(""));
+        let mut shard_ids: HashMap<String, usize> = HashMap::new();
+        let mut shards: Vec<ShardData> = vec![];
+        let mut shard_members: Vec<HashMap<String, Range<usize>>> = vec![];
+        let mut chains: Vec<ChainEntry> = vec![];
+        for entry in index {
+            let shard = match shard_ids.get(&entry.shard) {
+                Some(k) => *k,
+                None => {
+                    if !entry.shard.ends_with(".tar") {
+                        let message = format!("{}: only tar shards can be mapped, pack the dataset with --format tar", entry.shard);
+                        return Err(io::Error::new(io::ErrorKind::Unsupported, message).into());
+                    }
+                    let data = ShardData::open(&dir.join(&entry.shard))?;
+                    shard_members.push(tar_members(data.bytes())?);
+                    shards.push(data);
+                    shard_ids.insert(entry.shard.clone(), shards.len() - 1);
+                    shards.len() - 1
+                }
+            };
+            let mut members = vec![];
+            for name in entry.members {
+                let Some(range) = shard_members[shard].get(&name) else {
+                    let message = format!("{} is listed in {} but missing from {}", name, index_fname, entry.shard);
+                    return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+                };
+                members.push((name, range.clone()));
+            }
+            chains.push(ChainEntry { key: entry.key, shard, members });
+        }
+        let by_key = chains.iter().enumerate().map(|(k, c)| (c.key.clone(), k)).collect();
+
+        Ok(FeatureDataset { shards, chains, by_key })
+    }
+
+    /// Number of chains of the dataset
+    pub fn len(&self) -> usize { self.chains.len() }
+
+    pub fn is_empty(&self) -> bool { self.chains.is_empty() }
+
+    /// Keys of all the chains, in the order of the index
+    pub fn keys(&self) -> impl Iterator<Item = &str> { self.chains.iter().map(|c| c.key.as_str()) }
+
+    /// The `k`-th chain of the index
+    pub fn chain(&self, k: usize) -> ChainView<'_> {
+        let entry = &self.chains[k];
+        ChainView { key: &entry.key, members: &entry.members, data: self.shards[entry.shard].bytes() }
+    }
+
+    /// A chain given by its key, e.g. `pdb/2gb1_A`
+    pub fn get(&self, key: &str) -> Option<ChainView<'_>> { self.by_key.get(key).map(|k| self.chain(*k)) }
+
+    /// All the chains, in the order of the index
+    pub fn iter(&self) -> impl Iterator<Item = ChainView<'_>> { (0..self.len()).map(|k| self.chain(k)) }
+}
+
+/// Outputs of a single chain of a [`FeatureDataset`], borrowed from its shard
+#[derive(Clone, Copy)]
+pub struct ChainView<'a> {
+    /// key of the chain, e.g. `pdb/2gb1_A`
+    pub key: &'a str,
+    members: &'a [(String, Range<usize>)],
+    data: &'a [u8],
+}
+
+impl<'a> ChainView<'a> {
+    /// Content of the output file of the chain with a given extension, e.g. `dat`, `meta` or `dat.zst`
+    pub fn member(&self, extension: &str) -> Option<&'a [u8]> {
+        let name = format!("{}.{}", self.key, extension);
+        self.members.iter().find(|(m, _)| *m == name).map(|(_, range)| &self.data[range.clone()])
+    }
+
+    /// Content of the `.meta` file
+    pub fn meta(&self) -> Option<&'a str> { self.member("meta").and_then(|m| std::str::from_utf8(m).ok()) }
+
+    /// Names of the `.dat` columns, read from the `.meta` file as [`read_columns()`](crate::records::read_columns) does
+    pub fn columns(&self) -> Option<Vec<String>> {
+        let columns = self.meta()?.lines().find_map(|l| l.strip_prefix("columns = "))?;
+        Some(columns.split_whitespace().map(|c| c.to_string()).collect())
+    }
+
+    /// Content of the `.dat` file; an error when it has been packed compressed, since it can't be read in place
+    pub fn dat(&self) -> Result<&'a str, FeaturizerError> {
+        let Some(dat) = self.member("dat") else {
+            let message = format!("{}: no uncompressed .dat member, a dataset written with --compress can't be read in place", self.key);
+            return Err(io::Error::new(io::ErrorKind::Unsupported, message).into());
+        };
+        std::str::from_utf8(dat).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}.dat: {}", self.key, e)).into())
+    }
+
+    /// Records of the observed residues of the chain, parsed with [`parse_record()`]; gaps and masked residues are skipped
+    pub fn records(&self) -> Result<Vec<DatRecord>, FeaturizerError> {
+        let Some(columns) = self.columns() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: no columns in the .meta member", self.key)).into());
+        };
+        let n_features = feature_columns(&columns);
+
+        Ok(self.dat()?.lines().filter_map(|l| parse_record(l, n_features)).collect())
+    }
+}
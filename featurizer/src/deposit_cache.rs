@@ -0,0 +1,47 @@
+//! Deposits parsed once for many chains: when a list file gives several lines for chains of the same entry
+//! (e.g. of a ribosome or a virus capsid), parsing the mmCIF file again for every line may take minutes.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::errors::FeaturizerError;
+use crate::{FeaturizerConfig, LoadedDeposit};
+
+/// Least-recently-used cache of parsed deposits, keyed by the input file name.
+///
+/// Deposits are loaded as [`LoadedDeposit::load()`] does for a given config, so a cache should be used with a single config.
+pub struct DepositCache {
+    /// maximum number of deposits kept in memory; no deposit is kept when zero
+    pub capacity: usize,
+    /// number of requests served from the cache
+    pub n_hits: usize,
+    /// number of deposits parsed
+    pub n_loads: usize,
+    /// cached deposits, the most recently used last
+    entries: VecDeque<Arc<LoadedDeposit>>,
+}
+
+impl DepositCache {
+    pub fn new(capacity: usize) -> DepositCache { DepositCache { capacity, n_hits: 0, n_loads: 0, entries: VecDeque::new() } }
+
+    /// Returns a deposit read from a file, parsing it only when it's not cached
+    pub fn get(&mut self, fname: &str, config: &FeaturizerConfig) -> Result<Arc<LoadedDeposit>, FeaturizerError> {
+        if let Some(k) = self.entries.iter().position(|d| d.fname == fname) {
+            let deposit = self.entries.remove(k).unwrap();
+            self.entries.push_back(deposit.clone());
+            self.n_hits += 1;
+            debug!("{} taken from the cache of parsed deposits", fname);
+            return Ok(deposit);
+        }
+        let deposit = Arc::new(LoadedDeposit::load(fname, config)?);
+        self.n_loads += 1;
+        if self.capacity > 0 {
+            if self.entries.len() == self.capacity { self.entries.pop_front(); }
+            self.entries.push_back(deposit.clone());
+        }
+
+        Ok(deposit)
+    }
+}
@@ -0,0 +1,91 @@
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::PdbAtom;
+
+use crate::chains::residue_heavy_atoms;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{add, distance, scale};
+
+/// Van der Waals radius of a heavy atom, by its element
+pub(crate) fn vdw_radius(atom: &PdbAtom) -> f64 {
+    match atom.name.trim().trim_start_matches(|c: char| c.is_ascii_digit()).chars().next() {
+        Some('C') => 1.7,
+        Some('N') => 1.55,
+        Some('O') => 1.52,
+        Some('S') => 1.8,
+        _ => 1.8,
+    }
+}
+
+/// `n` points evenly spread over a unit sphere, along a golden-section spiral
+pub(crate) fn sphere_points(n: usize) -> Vec<Vec3> {
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+    (0..n).map(|k| {
+        let z = 1.0 - 2.0 * (k as f64 + 0.5) / n as f64;
+        let r = (1.0 - z * z).sqrt();
+        let phi = golden_angle * k as f64;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }).collect()
+}
+
+/// Points of the molecular surface of a set of atoms.
+///
+/// The surface is approximated by its contact part: points of the van der Waals spheres that a solvent probe can touch,
+/// i.e. whose probe center, placed along the sphere normal, doesn't overlap any other atom.
+pub fn surface_points(atoms: &[&PdbAtom], probe: f64, n_dots: usize) -> Vec<Vec3> {
+    let dots = sphere_points(n_dots);
+    let radii: Vec<f64> = atoms.iter().map(|a| vdw_radius(a)).collect();
+    let max_radius = radii.iter().cloned().fold(0.0, f64::max);
+    let mut out = vec![];
+    for (i, a) in atoms.iter().enumerate() {
+        // --- only atoms closer than both radii and the probe diameter may cover dots of this atom
+        let neighbors: Vec<usize> = (0..atoms.len())
+            .filter(|j| *j != i && distance(&a.pos, &atoms[*j].pos) < radii[i] + max_radius + 2.0 * probe).collect();
+        for d in &dots {
+            let center = add(&a.pos, &scale(d, radii[i] + probe));
+            if neighbors.iter().all(|j| distance(&center, &atoms[*j].pos) >= radii[*j] + probe) {
+                out.push(add(&a.pos, &scale(d, radii[i])));
+            }
+        }
+    }
+
+    out
+}
+
+/// Residue depth: distance of the CA atom, and the average distance of all heavy atoms of a residue, from the nearest
+/// point of the molecular surface of its chain (see [`surface_points()`]).
+///
+/// Depth complements solvent accessibility: buried residues all have zero accessible area, while their depth tells
+/// the core apart from residues just beneath the surface.
+#[derive(Debug)]
+pub struct ResidueDepth {
+    /// radius of the solvent probe, in Å
+    pub probe: f64,
+    /// number of surface dots placed on every atom
+    pub n_dots: usize,
+}
+
+impl Default for ResidueDepth {
+    fn default() -> Self { ResidueDepth { probe: 1.4, n_dots: 100 } }
+}
+
+impl FeatureExtractor for ResidueDepth {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "residue_depth",
+            description: "distance of the CA atom and the average distance of heavy atoms of a residue from the nearest point of the molecular surface of its chain",
+            shape: "[L, 2]".to_string(), units: "Å, Å", range: None,
+            mask: "NaN CA depth when the CA atom is missing", option: "--residue-depth", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let atoms = residue_heavy_atoms(chain.structure, chain.resids);
+        let all: Vec<&PdbAtom> = atoms.iter().flatten().cloned().collect();
+        let surface = surface_points(&all, self.probe, self.n_dots);
+        let depth = |p: &Vec3| surface.iter().map(|s| distance(p, s)).fold(f64::INFINITY, f64::min);
+
+        atoms.iter().map(|res| {
+            let ca = res.iter().find(|a| a.name == " CA ").map_or(f64::NAN, |a| depth(&a.pos));
+            let mean = if res.is_empty() { f64::NAN } else { res.iter().map(|a| depth(&a.pos)).sum::<f64>() / res.len() as f64 };
+            vec![ca, mean]
+        }).collect()
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use bioshell_interactions::BackboneHBondMap;
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+
+use crate::alignment::align_chains;
+use crate::geometry::distance;
+use crate::superposition::{superpose, Superposition};
+
+/// Everything the diff compares for the observed residues of a chain
+struct DiffChain {
+    ca: Vec<Vec3>,
+    ss: Vec<u8>,
+    /// for every residue: indexes of residues it's H-bonded to, either as a donor or as an acceptor
+    hbond_partners: Vec<HashSet<usize>>,
+}
+
+impl DiffChain {
+    fn new(strctr: &Structure, resids: &[ResidueId]) -> Result<DiffChain, PDBError> {
+        let mut ca = vec![];
+        let mut ss = vec![];
+        for r in resids {
+            ca.push(strctr.atom(r, " CA ")?.pos.clone());
+            ss.push(strctr.residue_secondary(r)?.hec_code());
+        }
+        let hbonds = BackboneHBondMap::new(strctr);
+        let hbond_partners = resids.iter().map(|ri| {
+            resids.iter().enumerate()
+                .filter(|(_, rj)| hbonds.h_bond(ri, rj).is_some() || hbonds.h_bond(rj, ri).is_some())
+                .map(|(j, _)| j).collect()
+        }).collect();
+
+        Ok(DiffChain { ca, ss, hbond_partners })
+    }
+}
+
+/// Per-residue difference between two conformations of the same chain
+#[derive(Clone, Debug)]
+pub struct ResidueDelta {
+    /// index of the residue in the first chain
+    pub i: usize,
+    /// index of the aligned residue in the second chain
+    pub j: usize,
+    pub resid_first: ResidueId,
+    pub resid_second: ResidueId,
+    /// CA displacement after the optimal superposition of the second chain onto the first one, in Å
+    pub displacement: f64,
+    pub ss_first: u8,
+    pub ss_second: u8,
+    /// backbone H-bonds of the first chain whose aligned counterpart is missing in the second one
+    pub hbonds_lost: usize,
+    /// backbone H-bonds of the second chain whose aligned counterpart is missing in the first one
+    pub hbonds_gained: usize,
+}
+
+/// Aligns two conformations of a chain by sequence, superimposes them and computes per-residue deltas.
+///
+/// Aligned residues of different amino acid types are skipped. Returns the deltas along with the superposition
+/// of the second chain onto the first one, computed on the CA atoms of all aligned residues.
+pub fn chain_diff(first: &Structure, second: &Structure) -> Result<(Vec<ResidueDelta>, Superposition), PDBError> {
+    let alignment = align_chains(first, second);
+    let (a, b) = (DiffChain::new(first, &alignment.first)?, DiffChain::new(second, &alignment.second)?);
+    let mut mapping = vec![None; alignment.first.len()];
+    let mut reverse = vec![None; alignment.second.len()];
+    for &(i, j) in &alignment.pairs {
+        mapping[i] = Some(j);
+        reverse[j] = Some(i);
+    }
+    let pairs = &alignment.pairs;
+    if pairs.is_empty() { return Err(PDBError::ResidueNotDefined { residue_index: 0 }); }
+    let fixed: Vec<Vec3> = pairs.iter().map(|(i, _)| a.ca[*i].clone()).collect();
+    let moving: Vec<Vec3> = pairs.iter().map(|(_, j)| b.ca[*j].clone()).collect();
+    let sup = superpose(&moving, &fixed);
+
+    let deltas = pairs.iter().map(|&(i, j)| {
+        let hbonds_lost = a.hbond_partners[i].iter()
+            .filter(|k| mapping[**k].map_or(true, |l| !b.hbond_partners[j].contains(&l))).count();
+        let hbonds_gained = b.hbond_partners[j].iter()
+            .filter(|l| reverse[**l].map_or(true, |k| !a.hbond_partners[i].contains(&k))).count();
+        ResidueDelta { i, j, resid_first: alignment.first[i].clone(), resid_second: alignment.second[j].clone(),
+            displacement: distance(&a.ca[i], &sup.apply(&b.ca[j])), ss_first: a.ss[i], ss_second: b.ss[j],
+            hbonds_lost, hbonds_gained }
+    }).collect();
+
+    Ok((deltas, sup))
+}
+
+/// Writes per-residue deltas between two conformations of a chain.
+///
+/// Every line gives residue indexes and IDs in both chains, their secondary structure, a flag set to 1 when the
+/// secondary structure changes, the CA displacement and the numbers of lost and gained backbone H-bonds.
+pub fn write_chain_diff(first: &Structure, second: &Structure, out_fname: &str) -> Result<(), PDBError> {
+    let (deltas, sup) = chain_diff(first, second)?;
+    let mut outfile = out_writer(out_fname, false);
+    writeln!(outfile, "# aligned residues: {} CA rmsd: {:.3}", deltas.len(), sup.rmsd)?;
+    writeln!(outfile, "# i j resid_first resid_second ss_first ss_second ss_change displacement hbonds_lost hbonds_gained")?;
+    for d in &deltas {
+        writeln!(outfile, "{:4} {:4} {} {} {} {} {} {:7.3} {} {}", d.i, d.j, d.resid_first, d.resid_second,
+                 d.ss_first as char, d.ss_second as char, (d.ss_first != d.ss_second) as u8, d.displacement,
+                 d.hbonds_lost, d.hbonds_gained)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::contacts::{find_contacts, ContactSpec};
+
+/// Resolution assumed for structures that don't report one, e.g. NMR models
+const DEFAULT_RESOLUTION: f64 = 2.5;
+
+/// Relative contact order of a chain, computed from CA-CA contacts shorter than 8 Å.
+///
+/// Pairs closer than three residues along the chain are not counted as contacts.
+pub fn relative_contact_order(strctr: &Structure, resids: &[ResidueId]) -> f64 {
+    let spec = ContactSpec { atom_name: " CA ".to_string(), cutoff: 8.0 };
+    let contacts: Vec<_> = find_contacts(strctr, resids, &spec).into_iter().filter(|(i, j, _)| j - i >= 3).collect();
+    if contacts.is_empty() { return 0.0; }
+    let total_separation: usize = contacts.iter().map(|(i, j, _)| j - i).sum();
+
+    total_separation as f64 / (contacts.len() as f64 * resids.len() as f64)
+}
+
+/// Curriculum difficulty of a chain, from 0 (easiest) to 1 (hardest).
+///
+/// The score is an average of four terms, each scaled to the [0, 1] range: the fraction of loop residues,
+/// relative contact order (saturated at 0.3), the fraction of residues missing from the structure
+/// and resolution (saturated at 4 Å).
+pub fn difficulty_score(loop_fraction: f64, contact_order: f64, gap_fraction: f64, resolution: Option<f64>) -> f64 {
+    let resolution = resolution.unwrap_or(DEFAULT_RESOLUTION);
+    let terms = [loop_fraction, (contact_order / 0.3).min(1.0), gap_fraction, (resolution / 4.0).min(1.0)];
+
+    terms.iter().sum::<f64>() / terms.len() as f64
+}
@@ -0,0 +1,47 @@
+//! Conformer groups: chains of the same protein deposited in alternative experimental conformations.
+//!
+//! Chains are grouped by the UniProt accessions their entity is mapped onto, when a mapping is given (see
+//! [`UniProtMapping`](crate::sifts::UniProtMapping)), otherwise by their entity sequence. A group ID depends only on
+//! what identifies the protein, so chains featurized by different runs, or by the shards of a run, share it.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::PDBError;
+
+use crate::manifest::ChainSummary;
+use crate::sifts::UniProtSegment;
+use crate::splits::stable_hash;
+
+/// ID of the conformer group of a chain, e.g. `uniprot:P06654` or `sequence:9f2c0e5d7a1b3c44`.
+///
+/// A chain mapped onto several UniProt entries (e.g. of a fusion protein) is grouped by all of them, joined with `+`;
+/// an unmapped chain is grouped by a hash of its entity sequence.
+pub fn conformer_group(entity_sequence: &str, segments: &[UniProtSegment]) -> String {
+    let mut accessions: Vec<&str> = segments.iter().map(|s| s.accession.as_str()).collect();
+    accessions.sort();
+    accessions.dedup();
+    if accessions.is_empty() { format!("sequence:{:016x}", stable_hash(entity_sequence)) } else { format!("uniprot:{}", accessions.join("+")) }
+}
+
+/// Chains of every conformer group, by group ID; chains without a group are skipped
+pub fn group_conformers(summaries: &[ChainSummary]) -> BTreeMap<&str, Vec<&ChainSummary>> {
+    let mut groups: BTreeMap<&str, Vec<&ChainSummary>> = BTreeMap::new();
+    for s in summaries {
+        if let Some(group) = &s.conformer_group { groups.entry(group.as_str()).or_default().push(s); }
+    }
+
+    groups
+}
+
+/// Writes conformer groups as a tab-separated file, one row per group: its ID, the number of chains and their keys
+pub fn write_conformer_groups(summaries: &[ChainSummary], fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#group\tn_chains\tkeys")?;
+    for (group, chains) in group_conformers(summaries) {
+        let keys: Vec<&str> = chains.iter().map(|s| s.key.as_str()).collect();
+        writeln!(outfile, "{}\t{}\t{}", group, chains.len(), keys.join(","))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+use std::io;
+
+use bioshell_pdb::PDBError;
+
+/// Errors reported by the featurizer
+#[derive(Debug)]
+pub enum FeaturizerError {
+    /// the input structure can't be loaded or it's inconsistent
+    Pdb(PDBError),
+    /// an output file can't be written
+    Io(io::Error),
+    /// the chain doesn't meet the given criteria, see [`ChainFilters`](crate::filters::ChainFilters)
+    Filtered(String),
+    /// the featurization has been aborted with a [`CancellationToken`](crate::CancellationToken)
+    Cancelled,
+}
+
+impl fmt::Display for FeaturizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeaturizerError::Pdb(e) => write!(f, "{}", e),
+            FeaturizerError::Io(e) => write!(f, "I/O error: {}", e),
+            FeaturizerError::Filtered(reason) => write!(f, "chain filtered out: {}", reason),
+            FeaturizerError::Cancelled => write!(f, "featurization cancelled"),
+        }
+    }
+}
+
+impl FeaturizerError {
+    /// Short name of the kind of this error, e.g. `NoSuchChain` or `Io`, used in failure reports
+    pub fn kind(&self) -> String {
+        match self {
+            // --- the name of the PDBError variant
+            FeaturizerError::Pdb(e) => format!("{:?}", e).split(|c: char| !c.is_alphanumeric()).next().unwrap_or("Pdb").to_string(),
+            FeaturizerError::Io(_) => "Io".to_string(),
+            FeaturizerError::Filtered(_) => "Filtered".to_string(),
+            FeaturizerError::Cancelled => "Cancelled".to_string(),
+        }
+    }
+}
+
+impl std::error::Error for FeaturizerError {}
+
+impl From<PDBError> for FeaturizerError {
+    fn from(e: PDBError) -> Self { FeaturizerError::Pdb(e) }
+}
+
+impl From<io::Error> for FeaturizerError {
+    fn from(e: io::Error) -> Self { FeaturizerError::Io(e) }
+}
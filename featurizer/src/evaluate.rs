@@ -0,0 +1,133 @@
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+
+use crate::alignment::align_chains;
+use crate::geometry::{distance, planar_angle};
+use crate::ideal_backbone::{CA_C, CA_C_N, CA_C_O, C_N, C_N_CA, C_O, MAX_CA_CA, N_CA, N_CA_C};
+use crate::superposition::superpose;
+
+/// Backbone atoms compared between a model and its reference
+const BACKBONE: [&str; 4] = [" N  ", " CA ", " C  ", " O  "];
+
+// ---------- Engh & Huber standard deviations of backbone bond lengths (Å) and planar angles (degrees)
+const N_CA_SIGMA: f64 = 0.019;
+const CA_C_SIGMA: f64 = 0.021;
+const C_N_SIGMA: f64 = 0.014;
+const C_O_SIGMA: f64 = 0.020;
+const N_CA_C_SIGMA: f64 = 2.8;
+const CA_C_N_SIGMA: f64 = 2.5;
+const C_N_CA_SIGMA: f64 = 2.5;
+const CA_C_O_SIGMA: f64 = 2.4;
+
+/// A bond length or a planar angle deviating from its ideal value by more than that many standard deviations is an outlier
+pub const OUTLIER_SIGMAS: f64 = 4.0;
+
+/// Evaluation of a single residue of a model
+#[derive(Clone, Debug)]
+pub struct ResidueEvaluation {
+    pub reference: ResidueId,
+    pub model: ResidueId,
+    /// CA deviation from the reference after superposition, in Å
+    pub ca_deviation: f64,
+    /// RMSD of N, CA, C and O atoms after superposition; NaN when any of them is missing
+    pub backbone_rmsd: f64,
+    /// backbone bonds of the model residue, including the peptide bond to the next residue, that are outliers
+    pub bond_outliers: usize,
+    /// backbone planar angles of the model residue, including these of the peptide bond to the next residue, that are outliers
+    pub angle_outliers: usize,
+}
+
+/// Evaluation of a model against its reference structure
+#[derive(Clone, Debug)]
+pub struct ModelEvaluation {
+    /// RMSD of N, CA, C and O atoms of aligned residues, on which the model is superimposed
+    pub backbone_rmsd: f64,
+    /// RMSD of CA atoms of aligned residues, after the backbone superposition
+    pub ca_rmsd: f64,
+    pub residues: Vec<ResidueEvaluation>,
+}
+
+fn pos<'a>(strctr: &'a Structure, resid: &ResidueId, name: &str) -> Option<&'a Vec3> {
+    strctr.atom(resid, name).ok().map(|a| &a.pos)
+}
+
+fn is_outlier(value: f64, ideal: f64, sigma: f64) -> bool { (value - ideal).abs() > OUTLIER_SIGMAS * sigma }
+
+/// Counts backbone bond length and planar angle outliers of the i-th residue; the peptide bond to the next residue
+/// is checked only when the two residues are connected
+fn geometry_outliers(strctr: &Structure, resids: &[ResidueId], i: usize) -> (usize, usize) {
+    let (mut bonds, mut angles) = (0, 0);
+    let r = &resids[i];
+    let (n, ca, c, o) = (pos(strctr, r, " N  "), pos(strctr, r, " CA "), pos(strctr, r, " C  "), pos(strctr, r, " O  "));
+    for (a, b, ideal, sigma) in [(n, ca, N_CA, N_CA_SIGMA), (ca, c, CA_C, CA_C_SIGMA), (c, o, C_O, C_O_SIGMA)] {
+        if let (Some(a), Some(b)) = (a, b) { bonds += is_outlier(distance(a, b), ideal, sigma) as usize; }
+    }
+    for (a, b, d, ideal, sigma) in [(n, ca, c, N_CA_C, N_CA_C_SIGMA), (ca, c, o, CA_C_O, CA_C_O_SIGMA)] {
+        if let (Some(a), Some(b), Some(d)) = (a, b, d) { angles += is_outlier(planar_angle(a, b, d), ideal, sigma) as usize; }
+    }
+    let Some(next) = resids.get(i + 1) else { return (bonds, angles) };
+    let (n_next, ca_next) = (pos(strctr, next, " N  "), pos(strctr, next, " CA "));
+    let connected = matches!((ca, ca_next), (Some(a), Some(b)) if distance(a, b) <= MAX_CA_CA);
+    if let (true, Some(c), Some(n_next)) = (connected, c, n_next) {
+        bonds += is_outlier(distance(c, n_next), C_N, C_N_SIGMA) as usize;
+        if let Some(ca) = ca { angles += is_outlier(planar_angle(ca, c, n_next), CA_C_N, CA_C_N_SIGMA) as usize; }
+        if let Some(ca_next) = ca_next { angles += is_outlier(planar_angle(c, n_next, ca_next), C_N_CA, C_N_CA_SIGMA) as usize; }
+    }
+
+    (bonds, angles)
+}
+
+/// Evaluates a reconstructed (or predicted) backbone against a reference chain.
+///
+/// Residues are aligned by sequence and the model is superimposed onto the reference on backbone atoms of all aligned
+/// residues. Geometry outliers are counted for the model only.
+pub fn evaluate_model(model: &Structure, reference: &Structure) -> Result<ModelEvaluation, PDBError> {
+    let alignment = align_chains(reference, model);
+    let (mut fixed, mut moving) = (vec![], vec![]);
+    for &(i, j) in &alignment.pairs {
+        for name in BACKBONE {
+            if let (Some(r), Some(m)) = (pos(reference, &alignment.first[i], name), pos(model, &alignment.second[j], name)) {
+                fixed.push(r.clone());
+                moving.push(m.clone());
+            }
+        }
+    }
+    if fixed.is_empty() { return Err(PDBError::ResidueNotDefined { residue_index: 0 }); }
+    let sup = superpose(&moving, &fixed);
+
+    let mut residues = vec![];
+    let mut ca_sum = 0.0;
+    for &(i, j) in &alignment.pairs {
+        let (r_ref, r_model) = (&alignment.first[i], &alignment.second[j]);
+        let deviations: Vec<Option<f64>> = BACKBONE.iter().map(|name| {
+            Some(distance(pos(reference, r_ref, name)?, &sup.apply(pos(model, r_model, name)?)))
+        }).collect();
+        let ca_deviation = deviations[1].unwrap_or(f64::NAN);
+        ca_sum += ca_deviation * ca_deviation;
+        let backbone_rmsd = deviations.iter().map(|d| d.map_or(f64::NAN, |d| d * d)).sum::<f64>() / BACKBONE.len() as f64;
+        let (bond_outliers, angle_outliers) = geometry_outliers(model, &alignment.second, j);
+        residues.push(ResidueEvaluation { reference: r_ref.clone(), model: r_model.clone(), ca_deviation,
+            backbone_rmsd: backbone_rmsd.sqrt(), bond_outliers, angle_outliers });
+    }
+    let ca_rmsd = (ca_sum / residues.len() as f64).sqrt();
+
+    Ok(ModelEvaluation { backbone_rmsd: sup.rmsd, ca_rmsd, residues })
+}
+
+/// Writes the evaluation of a model: global RMSD values and outlier counts in the header, followed by a line per aligned residue
+pub fn write_evaluation(eval: &ModelEvaluation, out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    let bonds: usize = eval.residues.iter().map(|r| r.bond_outliers).sum();
+    let angles: usize = eval.residues.iter().map(|r| r.angle_outliers).sum();
+    writeln!(outfile, "# aligned residues: {} backbone rmsd: {:.3} CA rmsd: {:.3} bond outliers: {} angle outliers: {}",
+             eval.residues.len(), eval.backbone_rmsd, eval.ca_rmsd, bonds, angles)?;
+    writeln!(outfile, "# resid_reference resid_model ca_deviation backbone_rmsd bond_outliers angle_outliers")?;
+    for r in &eval.residues {
+        writeln!(outfile, "{} {} {:7.3} {:7.3} {} {}", r.reference, r.model, r.ca_deviation, r.backbone_rmsd,
+                 r.bond_outliers, r.angle_outliers)?;
+    }
+    Ok(())
+}
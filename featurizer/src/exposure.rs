@@ -0,0 +1,193 @@
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::PdbAtom;
+use clap::ValueEnum;
+
+use crate::chains::residue_heavy_atoms;
+use crate::config::Config;
+use crate::depth::{sphere_points, vdw_radius};
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{add, distance, dot, scale, sub};
+use crate::neighbors::NeighborGrid;
+use crate::residue_encoding::aa_index;
+
+/// Half-sphere exposure and contact number: cheap burial descriptors computed from CA and CB positions only.
+///
+/// For every radius, CA atoms of other residues within that distance from the CA of a residue are counted: those in
+/// the half-sphere the CA-CB vector points to (HSE-up), those in the opposite half-sphere (HSE-down) and all of them
+/// (contact number). When CB is missing, e.g. for glycine, the CA-CB direction is approximated by
+/// `(CA(i) - CA(i-1)) + (CA(i) - CA(i+1))`, as in the HSE-alpha variant.
+#[derive(Debug)]
+pub struct HalfSphereExposure {
+    /// radii of the spheres, in Å
+    pub radii: Vec<f64>,
+}
+
+impl Default for HalfSphereExposure {
+    fn default() -> Self { HalfSphereExposure { radii: vec![8.0, 13.0] } }
+}
+
+impl HalfSphereExposure {
+    /// Reads the `hse.radii` setting
+    pub fn from_config(config: &Config) -> Result<HalfSphereExposure, String> {
+        let radii = config.get_list_or("hse.radii", HalfSphereExposure::default().radii)?;
+        if radii.is_empty() || radii.iter().any(|r| *r <= 0.0) {
+            return Err(format!("hse radii should be positive; found: {:?}", radii));
+        }
+        Ok(HalfSphereExposure { radii })
+    }
+}
+
+/// Direction of the side chain of residue `i`: CA-CB vector or, without CB, its approximation from neighbouring CA atoms
+fn side_chain_direction(chain: &ChainContext, cas: &[Option<&Vec3>], i: usize) -> Option<Vec3> {
+    let ca = cas[i]?;
+    if let Ok(cb) = chain.structure.atom(&chain.resids[i], " CB ") { return Some(sub(&cb.pos, ca)); }
+    let (prev, next) = (cas.get(i.wrapping_sub(1)).copied().flatten()?, cas.get(i + 1).copied().flatten()?);
+    Some(add(&sub(ca, prev), &sub(ca, next)))
+}
+
+impl FeatureExtractor for HalfSphereExposure {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "hse",
+            description: "half-sphere exposure (CA atoms in the upper and lower half-spheres defined by the CA-CB vector) and contact number (all CA atoms) within each radius",
+            shape: format!("[L, {}]", 3 * self.radii.len()), units: "residue count", range: None,
+            mask: "NaN when CA is missing; HSE is NaN when neither CB nor both neighbouring CA atoms exist", option: "--hse [--config <file>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let cas: Vec<Option<&Vec3>> = chain.resids.iter().map(|r| chain.structure.atom(r, " CA ").ok().map(|a| &a.pos)).collect();
+        let max_radius = self.radii.iter().cloned().fold(0.0, f64::max);
+        let grid = NeighborGrid::new(&cas.iter().map(|ca| ca.cloned()).collect::<Vec<_>>(), max_radius);
+        let mut out = Vec::with_capacity(cas.len());
+        for (i, ca) in cas.iter().enumerate() {
+            let Some(ca) = ca else {
+                out.push(vec![f64::NAN; 3 * self.radii.len()]);
+                continue;
+            };
+            let direction = side_chain_direction(chain, &cas, i);
+            let mut row = Vec::with_capacity(3 * self.radii.len());
+            let neighbors: Vec<usize> = grid.within(ca, max_radius).into_iter().filter(|j| *j != i).collect();
+            for radius in &self.radii {
+                let (mut up, mut down) = (0.0, 0.0);
+                for other in neighbors.iter().filter_map(|j| cas[*j]).filter(|other| distance(ca, other) <= *radius) {
+                    match &direction {
+                        Some(d) if dot(d, &sub(other, ca)) < 0.0 => down += 1.0,
+                        _ => up += 1.0,
+                    }
+                }
+                if direction.is_some() { row.extend([up, down, up + down]); } else { row.extend([f64::NAN, f64::NAN, up]); }
+            }
+            out.push(row);
+        }
+
+        out
+    }
+}
+
+/// Maximum solvent accessible areas of residue types, in Å^2, ordered by [`ALPHABET`](crate::residue_encoding::ALPHABET)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MaxAsaTable {
+    /// theoretical values of Tien et al. (2013), computed for Gly-X-Gly tripeptides
+    #[default]
+    Tien,
+    /// values of Miller et al. (1987), derived from Gly-X-Gly tripeptides in extended conformation
+    Miller,
+}
+
+const TIEN_MAX_ASA: [f64; 20] = [129.0, 274.0, 195.0, 193.0, 167.0, 225.0, 223.0, 104.0, 224.0, 197.0,
+    201.0, 236.0, 224.0, 240.0, 159.0, 155.0, 172.0, 285.0, 263.0, 174.0];
+
+const MILLER_MAX_ASA: [f64; 20] = [113.0, 241.0, 158.0, 151.0, 140.0, 189.0, 183.0, 85.0, 194.0, 182.0,
+    180.0, 211.0, 204.0, 218.0, 143.0, 122.0, 146.0, 259.0, 229.0, 160.0];
+
+impl MaxAsaTable {
+    /// Maximum accessible area of a residue type given by its index in the alphabet; `None` for the unknown type
+    pub fn max_asa(&self, aa: usize) -> Option<f64> {
+        let table = match self { MaxAsaTable::Tien => &TIEN_MAX_ASA, MaxAsaTable::Miller => &MILLER_MAX_ASA };
+        table.get(aa).copied()
+    }
+}
+
+/// Exposure class of a residue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExposureClass { Core = 0, Boundary = 1, Surface = 2 }
+
+/// Solvent accessible surface area of every atom, by the Shrake-Rupley method: the fraction of dots of its sphere,
+/// enlarged by the probe radius, that aren't inside the enlarged sphere of any other atom
+pub fn atom_sasa(atoms: &[&PdbAtom], probe: f64, n_dots: usize) -> Vec<f64> {
+    let dots = sphere_points(n_dots);
+    let radii: Vec<f64> = atoms.iter().map(|a| vdw_radius(a) + probe).collect();
+    let max_radius = radii.iter().cloned().fold(0.0, f64::max);
+    let grid = NeighborGrid::new(&atoms.iter().map(|a| Some(a.pos.clone())).collect::<Vec<_>>(), 2.0 * max_radius);
+    atoms.iter().enumerate().map(|(i, a)| {
+        let neighbors: Vec<usize> = grid.within(&a.pos, radii[i] + max_radius).into_iter().filter(|j| *j != i).collect();
+        let n_free = dots.iter().filter(|d| {
+            let p = add(&a.pos, &scale(d, radii[i]));
+            neighbors.iter().all(|j| distance(&p, &atoms[*j].pos) >= radii[*j])
+        }).count();
+        4.0 * std::f64::consts::PI * radii[i] * radii[i] * n_free as f64 / n_dots as f64
+    }).collect()
+}
+
+/// Solvent accessible surface area of every residue, its relative accessibility and a three-class exposure label.
+///
+/// The area is computed from all heavy atoms of the chain (see [`atom_sasa()`]) and divided by the maximum area of
+/// the residue type, taken from the selected [`MaxAsaTable`]. A residue whose relative accessibility is below
+/// the first threshold is in the core, below the second at the boundary, otherwise at the surface.
+#[derive(Debug)]
+pub struct SolventExposure {
+    /// relative accessibility separating the core from the boundary and the boundary from the surface
+    pub thresholds: (f64, f64),
+    pub table: MaxAsaTable,
+    /// radius of the solvent probe, in Å
+    pub probe: f64,
+    /// number of dots placed on every atom
+    pub n_dots: usize,
+}
+
+impl Default for SolventExposure {
+    fn default() -> Self { SolventExposure { thresholds: (0.2, 0.5), table: MaxAsaTable::default(), probe: 1.4, n_dots: 100 } }
+}
+
+impl SolventExposure {
+    /// Exposure labels of given thresholds: two increasing relative accessibility values
+    pub fn new(thresholds: &[f64], table: MaxAsaTable) -> Result<SolventExposure, String> {
+        let &[core, surface] = thresholds else { return Err(format!("two exposure thresholds expected; found: {:?}", thresholds)) };
+        if !(0.0..surface).contains(&core) {
+            return Err(format!("exposure thresholds should be non-negative and increasing; found: {:?}", thresholds));
+        }
+        Ok(SolventExposure { thresholds: (core, surface), table, ..Default::default() })
+    }
+
+    /// Exposure class of a residue of a given relative accessibility
+    pub fn classify(&self, rsa: f64) -> ExposureClass {
+        if rsa < self.thresholds.0 { ExposureClass::Core }
+        else if rsa < self.thresholds.1 { ExposureClass::Boundary }
+        else { ExposureClass::Surface }
+    }
+}
+
+impl FeatureExtractor for SolventExposure {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "exposure_class",
+            description: "solvent accessible surface area of a residue, its relative accessibility and the exposure class: 0 for core, 1 for boundary, 2 for surface",
+            shape: "[L, 3]".to_string(), units: "Å^2, fraction, class index", range: None,
+            mask: "NaN when the residue has no heavy atoms; relative accessibility and class are NaN for non-standard residues",
+            option: "--exposure-classes [--exposure-thresholds <core>,<surface>] [--max-asa <table>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let atoms = residue_heavy_atoms(chain.structure, chain.resids);
+        let all: Vec<&PdbAtom> = atoms.iter().flatten().cloned().collect();
+        let mut sasa = atom_sasa(&all, self.probe, self.n_dots).into_iter();
+
+        atoms.iter().map(|res| {
+            let Some(first) = res.first() else { return vec![f64::NAN; 3] };
+            let area: f64 = sasa.by_ref().take(res.len()).sum();
+            let aa = aa_index(&first.res_name);
+            match self.table.max_asa(aa) {
+                Some(max_asa) => vec![area, area / max_asa, self.classify(area / max_asa) as usize as f64],
+                None => vec![area, f64::NAN, f64::NAN],
+            }
+        }).collect()
+    }
+}
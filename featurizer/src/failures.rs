@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use bioshell_io::{open_file, out_writer};
+use bioshell_pdb::PDBError;
+
+/// A chain that couldn't be featurized
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Failure {
+    pub input: String,
+    /// chain ID, `-` when it wasn't given
+    pub chain: String,
+    /// kind of the error, e.g. `NoSuchChain`
+    pub kind: String,
+    pub message: String,
+}
+
+impl Failure {
+    /// Whether the chain was rejected by chain filters; such a chain is not an error of the run and retrying it won't help
+    pub fn is_filtered(&self) -> bool { self.kind == "Filtered" }
+}
+
+/// Writes chains that failed with an error as a list file, which may be fed back with `-l` to retry only them.
+///
+/// A chain is given by the code of its deposit, i.e. the input file name up to the first dot, followed by the chain ID
+/// (e.g. `1abc:A`); chains rejected by filters are not listed
+pub fn write_retry_list(failures: &[Failure], fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    let mut written: HashSet<String> = HashSet::new();
+    for f in failures.iter().filter(|f| !f.is_filtered()) {
+        let file_name = Path::new(&f.input).file_name().and_then(|n| n.to_str()).unwrap_or(&f.input);
+        let code = file_name.split('.').next().unwrap_or(file_name);
+        let entry = if f.chain == "-" { code.to_string() } else { format!("{}:{}", code, f.chain) };
+        if written.insert(entry.clone()) { writeln!(outfile, "{}", entry)?; }
+    }
+    Ok(())
+}
+
+/// Writes failures of a run, either as a tab-separated file or, when `fname` ends with `.json`, as a JSON array
+pub fn write_failures(failures: &[Failure], fname: &str) -> Result<(), PDBError> {
+    if fname.ends_with(".json") { return write_failures_json(failures, fname); }
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#input\tchain\tkind\tmessage")?;
+    for f in failures {
+        // --- messages may span many lines
+        let message = f.message.replace(['\t', '\n'], " ");
+        writeln!(outfile, "{}\t{}\t{}\t{}", f.input, f.chain, f.kind, message)?;
+    }
+    Ok(())
+}
+
+/// Reads failures written as a tab-separated file by [`write_failures()`]
+pub fn read_failures(fname: &str) -> Result<Vec<Failure>, PDBError> {
+    let mut failures = vec![];
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let t: Vec<&str> = line.splitn(4, '\t').collect();
+        let &[input, chain, kind, message] = t.as_slice() else {
+            log::warn!("Incorrect line of {} skipped: {}", fname, line);
+            continue;
+        };
+        failures.push(Failure { input: input.to_string(), chain: chain.to_string(), kind: kind.to_string(), message: message.to_string() });
+    }
+
+    Ok(failures)
+}
+
+#[cfg(feature = "json")]
+fn write_failures_json(failures: &[Failure], fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "{}", serde_json::to_string_pretty(failures).unwrap())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "json"))]
+fn write_failures_json(_failures: &[Failure], fname: &str) -> Result<(), PDBError> {
+    log::error!("Can't write {}: the featurizer was built without the \"json\" feature", fname);
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+
+#[cfg(feature = "json")]
+use std::io::Write;
+
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+use bioshell_interactions::BackboneHBondMap;
+#[cfg(feature = "json")]
+use bioshell_io::out_writer;
+use bioshell_pdb::{Entity, PDBError, ResidueId, Structure};
+use bioshell_seq::chemical::StandardResidueType;
+
+use crate::alignment::map_to_entity;
+use crate::residue_encoding::{aa_index, ALPHABET};
+
+/// Describes a feature: what it is, its shape, units and how undefined values are marked
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+pub struct FeatureInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// shape of the feature for a chain, `L` stands for the number of observed residues
+    pub shape: String,
+    pub units: &'static str,
+    /// range of valid values, if bounded
+    pub range: Option<(f64, f64)>,
+    /// how missing or undefined values are marked
+    pub mask: &'static str,
+    /// command line option that turns this feature on
+    pub option: &'static str,
+    /// version of the extractor; bumped every time the values it produces change
+    pub version: u32,
+}
+
+/// Data of a single chain shared by all feature extractors
+pub struct ChainContext<'a> {
+    /// chain identifier, such as `2gb1_A`
+    pub id: &'a str,
+    pub structure: &'a Structure,
+    /// observed residues of the chain, in the order of the output file
+    pub resids: &'a [ResidueId],
+    pub hbonds: &'a BackboneHBondMap,
+    /// secondary structure (H, E or C) of every residue
+    pub ss: Vec<u8>,
+    /// one-letter sequence of the entity, including residues missing in the structure
+    pub entity_sequence: String,
+    /// position in the entity sequence of every observed residue; `None` for a residue that can't be aligned to the entity
+    pub entity_index: Vec<Option<usize>>,
+    /// ID of the entity this chain belongs to
+    pub entity_id: &'a str,
+    /// number of chains of this entity in the deposit
+    pub entity_copies: usize,
+    /// distance from every residue to the nearest symmetry mate in the crystal lattice, NaN beyond
+    /// [`LATTICE_RANGE`](crate::crystal::LATTICE_RANGE); `None` when the lattice isn't known or hasn't been requested
+    /// by any extractor (see [`FeatureExtractor::needs_lattice()`])
+    pub lattice_distances: Option<Vec<f64>>,
+    /// distance from every residue to the nearest heavy atom of another polymer chain of the deposit (of the assembly
+    /// when one is built), NaN beyond [`PARTNER_RANGE`](crate::chain_pair::PARTNER_RANGE); `None` when it hasn't been
+    /// requested by any extractor (see [`FeatureExtractor::needs_partners()`])
+    pub partner_distances: Option<Vec<f64>>,
+    /// what has been repaired in every residue; `None` when the backbone hasn't been repaired, see
+    /// [`repair_backbone()`](crate::repair::repair_backbone)
+    pub repairs: Option<Vec<crate::repair::ResidueRepairs>>,
+}
+
+impl<'a> ChainContext<'a> {
+    pub fn new(id: &'a str, structure: &'a Structure, resids: &'a [ResidueId], entity: &'a Entity, chain: &str,
+               hbonds: &'a BackboneHBondMap) -> Result<ChainContext<'a>, PDBError> {
+        let entity_resids = entity.chain_monomers(chain)?;
+        let mut ss = Vec::with_capacity(resids.len());
+        for r in resids { ss.push(structure.residue_secondary(r)?.hec_code()); }
+        let entity_sequence: String = entity_resids.iter()
+            .map(|r| ALPHABET.as_bytes()[aa_index(&r.code3)] as char).collect();
+        let observed: Vec<bool> = entity_resids.iter().map(|r| r.parent_type != StandardResidueType::GAP).collect();
+        let entity_index = map_to_entity(entity_sequence.as_bytes(), &observed, structure, resids);
+        Ok(ChainContext { id, structure, resids, hbonds, ss, entity_sequence, entity_index,
+            entity_id: entity.id(), entity_copies: entity.chain_ids().len(), lattice_distances: None, partner_distances: None, repairs: None })
+    }
+}
+
+/// Computes a per-residue feature of a chain
+pub trait FeatureExtractor: Send + Sync + fmt::Debug {
+    fn info(&self) -> FeatureInfo;
+
+    /// Computes the feature for every residue of a chain.
+    ///
+    /// Returns one row of values per residue of `chain.resids`; all rows have the same length,
+    /// undefined values are set to NaN.
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>>;
+
+    /// True when the values don't change under rotation and translation of the structure
+    fn is_rigid_invariant(&self) -> bool { true }
+
+    /// True when the extractor reads [`ChainContext::lattice_distances`], which are computed only on request
+    fn needs_lattice(&self) -> bool { false }
+
+    /// True when the extractor reads [`ChainContext::partner_distances`], which are computed only on request
+    fn needs_partners(&self) -> bool { false }
+
+    /// True when the extractor reads side files, such as profiles or annotations, while computing the values
+    fn reads_files(&self) -> bool { false }
+}
+
+/// Columns written for every residue regardless of the selected features
+pub fn core_features() -> Vec<FeatureInfo> {
+    vec![
+        FeatureInfo { name: "index", description: "0-based index of a residue among the observed residues of a chain",
+            shape: "[L]".to_string(), units: "", range: None, mask: "'-' for residues missing in the structure", option: "", version: 1 },
+        FeatureInfo { name: "author_number", description: "author residue numbering: residue number followed by the insertion code, if any",
+            shape: "[L]".to_string(), units: "", range: None, mask: "'-' for residues missing in the structure", option: "", version: 1 },
+        FeatureInfo { name: "secondary_structure", description: "secondary structure as H, E or C, assigned by the method chosen with --ss-method: DSSP codes given with the deposit (default), STRIDE-like or P-SEA-like from CA atoms",
+            shape: "[L]".to_string(), units: "", range: None, mask: "none", option: "", version: 1 },
+        FeatureInfo { name: "ca_coordinates", description: "Cartesian coordinates of the CA atom",
+            shape: "[L, 3]".to_string(), units: "Å", range: None, mask: "none", option: "", version: 1 },
+        FeatureInfo { name: "backbone_hbonds", description: "donor and acceptor indexes, DSSP energy, N..O and H..O distances, N-H..O angle, direction, sequence separation and its range of every backbone H-bond of a chain, written to a .hbonds file",
+            shape: "[*, 9]".to_string(), units: "kcal/mol, Å, Å, degrees, residues", range: None, mask: "NaN when the amide hydrogen can't be placed; NaN separation for inter-chain H-bonds", option: "", version: 4 },
+    ]
+}
+
+/// All the per-residue feature extractors known to the featurizer, in their default configuration
+pub fn registered_extractors() -> Vec<Box<dyn FeatureExtractor>> {
+    vec![
+        Box::new(crate::ideal_backbone::ResidualTargets),
+        Box::new(crate::internal_coords::InternalCoordinates),
+        Box::new(crate::frames::ResidueFrames::default()),
+        Box::new(crate::ca_trace::CaTraceGeometry),
+        Box::new(crate::ramachandran::RamachandranRegions),
+        Box::new(crate::torsions::TorsionAngles { bins: None }),
+        Box::new(crate::gaps::ChainBreaks),
+        Box::new(crate::gaps::Connectivity),
+        Box::new(crate::repair::RepairFlags),
+        Box::new(crate::inference::TraceBreaks),
+        Box::new(crate::motifs::HBondMotifs),
+        Box::new(crate::sheets::SheetTopologyFeatures),
+        Box::new(crate::helices::HelixGeometry),
+        Box::new(crate::chi_angles::ChiAngles),
+        Box::new(crate::alanine_scan::AlanineScan),
+        Box::new(crate::residue_encoding::ResidueEncoding { encoding: crate::residue_encoding::Encoding::Onehot }),
+        Box::new(crate::scales::PropertyScales::default()),
+        Box::new(crate::profiles::SequenceProfiles { dir: String::new() }),
+        Box::new(crate::conservation::SequenceConservation::default()),
+        Box::new(crate::symmetry::SymmetryEquivalence),
+        Box::new(crate::bfactors::BFactors { atoms: crate::bfactors::BFactorAtoms::Ca, normalize: false }),
+        Box::new(crate::plddt::Plddt),
+        Box::new(crate::map_quality::MapQualityFeatures { dir: String::new(), min_rscc: None }),
+        Box::new(crate::microenvironment::MicroEnvironment::default()),
+        Box::new(crate::exposure::HalfSphereExposure::default()),
+        Box::new(crate::exposure::SolventExposure::default()),
+        Box::new(crate::depth::ResidueDepth::default()),
+        Box::new(crate::crystal::CrystalContacts::default()),
+        Box::new(crate::chain_pair::InterfaceResidues),
+        Box::new(crate::agreement::PredictionAgreement::default()),
+        Box::new(crate::membrane::MembraneFeatures::default()),
+    ]
+}
+
+/// Registered extractors of the given names (see [`FeatureInfo::name`]) in their default configuration, in the order given
+pub fn extractors_by_name(names: &[&str]) -> Result<Vec<Box<dyn FeatureExtractor>>, String> {
+    let mut known = registered_extractors();
+    names.iter().map(|name| match known.iter().position(|e| e.info().name == *name) {
+        Some(k) => Ok(known.swap_remove(k)),
+        None => Err(format!("unknown feature: {}", name)),
+    }).collect()
+}
+
+/// Features that are written to separate files as lists of residue pairs
+pub fn pair_features() -> Vec<FeatureInfo> {
+    vec![crate::contacts::feature_info(), crate::interactions::feature_info(), crate::graph::feature_info(), crate::ligands::feature_info()]
+}
+
+/// Features of chain segments, written to separate files
+pub fn segment_features() -> Vec<FeatureInfo> {
+    vec![crate::gaps::feature_info(), crate::sifts::feature_info()]
+}
+
+/// Prints descriptions of all the features and optionally writes them to a JSON file
+pub fn describe_features(json_fname: Option<&str>) -> Result<(), PDBError> {
+    let per_residue: Vec<FeatureInfo> = registered_extractors().iter().map(|e| e.info()).collect();
+    let all: Vec<(&str, Vec<FeatureInfo>)> = vec![("core", core_features()), ("per-residue", per_residue), ("pair", pair_features()),
+        ("segment", segment_features()), ("atom", vec![crate::atoms::feature_info()])];
+    for (kind, infos) in &all {
+        println!("# ---------- {} features", kind);
+        for f in infos {
+            let range = f.range.map_or("-".to_string(), |(lo, hi)| format!("[{}, {}]", lo, hi));
+            println!("{:<20} v{} {:<12} units: {}; range: {}; option: {}\n    {}\n    missing values: {}",
+                     f.name, f.version, f.shape, f.units, range, f.option, f.description, f.mask);
+        }
+    }
+    if let Some(fname) = json_fname { write_features_json(&all, fname)?; }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+fn write_features_json(all: &[(&str, Vec<FeatureInfo>)], fname: &str) -> Result<(), PDBError> {
+    let json: serde_json::Map<String, serde_json::Value> = all.iter()
+        .map(|(kind, infos)| (kind.to_string(), serde_json::to_value(infos).unwrap()))
+        .collect();
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "{}", serde_json::to_string_pretty(&json).unwrap())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "json"))]
+fn write_features_json(_all: &[(&str, Vec<FeatureInfo>)], fname: &str) -> Result<(), PDBError> {
+    log::error!("Can't write {}: the featurizer was built without the \"json\" feature", fname);
+    Ok(())
+}
@@ -0,0 +1,671 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use bioshell_interactions::BackboneHBondMap;
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{Deposit, PdbAtom, ResidueId, Structure};
+use bioshell_seq::chemical::StandardResidueType;
+use log::{error, warn};
+
+use crate::alignment::{sequence_mismatches, MismatchPolicy};
+use crate::atoms::{write_atom_records, Granularity};
+use crate::augmentation::{write_augmented, Augmentation};
+use crate::assembly::{build_assembly, chain_neighborhood};
+use crate::cancel::CancellationToken;
+use crate::centering::{center_on_chain, CenterFrame};
+use crate::chain_pair::partner_distances;
+use crate::chains::{load_deposit, parse_deposit, polymer_structure, residue_heavy_atoms, select_chain, source_chain, STDIN};
+use crate::conformers::select_conformer;
+use crate::crops::{write_blocks, write_crops, Crop};
+use crate::crystal::lattice_distances;
+use crate::compression::{compress_file, compressed_writer, Compression, COMPRESSED_EXTENSIONS};
+use crate::contacts::{append_interchain_contacts, find_contacts, find_interchain_contacts, virtual_cb_flags, write_contacts, write_contacts_chunked, ContactSpec};
+use crate::difficulty::{difficulty_score, relative_contact_order};
+use crate::ensembles::conformer_group;
+use crate::errors::FeaturizerError;
+use crate::features::{ChainContext, FeatureExtractor};
+use crate::gaps::{find_gaps, write_gaps};
+use crate::graph::{build_graph, write_graph};
+use crate::hbonds::{canonical_hbond_pairs, hbond_geometry, write_hbond_pairs, write_hbonds, HBondDirection, HBondPair, HBondPolicy, HBONDS_HEADER, HBOND_CA_CUTOFF};
+use crate::inference::mask_secondary_structure;
+use crate::interactions::{find_interactions, write_interactions};
+use crate::lenient::{load_deposit_lenient, parse_deposit_lenient};
+use crate::ligands::{ligand_atoms, nearest_ligands, write_ligand_contacts};
+use crate::linkers::LinkerSplit;
+use crate::neighbors::NeighborGrid;
+use crate::filters::{statistics, ChainFilters};
+use crate::manifest::ChainSummary;
+use crate::metadata::entity_metadata;
+use crate::plddt::low_confidence;
+use crate::polymers::{polymer_type, PolymerPolicy, PolymerType, NUCLEOTIDE_REFERENCE_ATOM};
+use crate::provenance::write_provenance;
+use crate::records::{FlushPolicy, Numbering, RecordWriter};
+use crate::release::release_date;
+use crate::repair::repair_backbone;
+use crate::secondary::{assign_secondary, SsMethod};
+use crate::separation::sequence_separation;
+use crate::sifts::{write_uniprot_mapping, UniProtMapping, UniProtSegment};
+use crate::sources::Source;
+use crate::timings::StageTimings;
+use crate::streaming::{chain_text, LOW_MEMORY_CHUNK};
+
+/// Extensions of all the files that may be written for a single chain
+pub const OUTPUT_EXTENSIONS: [&str; 14] = ["dat", "meta", "atoms", "hbonds", "hbpairs", "contacts", "interactions", "ligands", "gaps", "aug", "crops", "parts", "graph", "uniprot"];
+
+/// Output root name that stands for the standard output: the `.dat` content is printed there, other outputs are not written
+pub const STDOUT: &str = "-";
+
+/// Chains with any CA atom within this distance from a featurized chain are its context
+pub const CONTEXT_CUTOFF: f64 = 12.0;
+
+/// Defines what is computed for every chain
+#[derive(Default, Debug)]
+pub struct FeaturizerConfig {
+    /// when given, residue contacts are written to a `.contacts` file
+    pub contacts: Option<ContactSpec>,
+    /// write contacts as a binary matrix rather than a list of pairs
+    pub contacts_matrix: bool,
+    /// write disulfide bonds and salt bridges to an `.interactions` file
+    pub interactions: bool,
+    /// write the closest ligand or metal ion of residues to a `.ligands` file
+    pub ligands: bool,
+    /// write segments missing in the structure to a `.gaps` file
+    pub gaps: bool,
+    /// stream deposits, keeping only the atoms of the featurized chain, and build pair features in chunks, see [`crate::streaming`]
+    pub low_memory: bool,
+    /// how secondary structure is assigned, see [`SsMethod`]
+    pub ss_method: SsMethod,
+    /// with [`Granularity::Atom`], heavy atoms of the written residues are also written to an `.atoms` file
+    pub granularity: Granularity,
+    /// when given, UniProt accessions and residue numbers of residues are written to a `.uniprot` file
+    pub uniprot: Option<UniProtMapping>,
+    /// group chains of the same protein, see [`conformer_group()`]
+    pub conformer_groups: bool,
+    /// when given, corrupted variants of the CA trace are written to an `.aug` file
+    pub augmentation: Option<Augmentation>,
+    /// when given, the chain is written as a residue graph to a `.graph` file, with contact edges up to this CA-CA distance
+    pub graph: Option<f64>,
+    /// when given, fixed-length windows of the chain are written to a `.crops` file
+    pub crop: Option<Crop>,
+    /// when given, chains longer than its `max_length` are split at linkers into parts written to a `.parts` file
+    pub split: Option<LinkerSplit>,
+    /// ID of the biological assembly to build before featurization; the asymmetric unit is used when `None`
+    pub assembly: Option<String>,
+    /// alternate location label of the conformer to featurize; the default conformer of the deposit is used when `None`
+    pub conformer: Option<char>,
+    /// residues of AlphaFold DB models with pLDDT below this value are written as missing
+    pub min_plddt: Option<f64>,
+    /// chains that don't meet these criteria are not featurized
+    pub filters: ChainFilters,
+    /// namespace of the record keys; detected from input file names when `None`
+    pub source: Option<Source>,
+    /// detect H-bonds and contacts also to other chains of a deposit; always on for an assembly
+    pub inter_chain: bool,
+    /// skip malformed atom records of an input file instead of failing the whole deposit
+    pub lenient: bool,
+    /// when given, the `.dat` file and side outputs are written compressed, e.g. as `.dat.zst`; `.meta` files are left as plain text
+    pub compression: Option<Compression>,
+    /// when records of the `.dat` output are written out
+    pub flush: FlushPolicy,
+    /// residue numbering of the records
+    pub numbering: Numbering,
+    /// canonical frame the chain is moved into before anything is computed
+    pub center_frame: CenterFrame,
+    /// which backbone H-bonds are written
+    pub hbonds: HBondPolicy,
+    /// rebuild missing backbone O atoms and standardize peptide bond lengths before anything is computed
+    pub repair: bool,
+    /// accept CA-only traces: the secondary structure of residues lacking other backbone atoms is written as `-`
+    pub inference: bool,
+    /// what is done with a chain whose observed residues disagree with its entity sequence
+    pub on_mismatch: MismatchPolicy,
+    /// which chains are featurized by the polymer type of their entity; nucleic acid and hybrid chains are rejected by default
+    pub polymer: PolymerPolicy,
+    /// per-residue features, in the order their columns are written
+    pub extractors: Vec<Box<dyn FeatureExtractor>>,
+}
+
+/// Author numbering of a residue: its number followed by the insertion code, if any (e.g. `100A` or `-3`)
+fn author_number(resid: &ResidueId) -> String { format!("{}{}", resid.res_seq, resid.i_code).trim_end().to_string() }
+
+/// Polymer type of a chain's entity, given by the three-letter codes of its residues; a chain the polymer policy doesn't
+/// accept is rejected, before any of its outputs is written
+fn check_polymer<S: AsRef<str>>(codes: impl IntoIterator<Item = S>, chain: &str, policy: PolymerPolicy) -> Result<PolymerType, FeaturizerError> {
+    let polymer = polymer_type(codes);
+    if !policy.accepts(polymer) {
+        return Err(FeaturizerError::Filtered(format!("chain {} is a {} polymer, required --polymer all", chain, polymer)));
+    }
+    Ok(polymer)
+}
+
+/// Atom a record of a residue is written with: CA or, for a nucleotide of a non-protein chain, its reference atom
+fn reference_atom<'a>(strctr: &'a Structure, resid: &ResidueId, polymer: PolymerType) -> Option<&'a PdbAtom> {
+    strctr.atom(resid, " CA ").ok()
+        .or_else(|| (polymer != PolymerType::Protein).then(|| strctr.atom(resid, NUCLEOTIDE_REFERENCE_ATOM).ok()).flatten())
+}
+
+/// Reports residues of a chain that disagree with its entity sequence and applies the mismatch policy;
+/// returns flags of the residues to be written as missing
+fn check_sequence(chain_data: &ChainContext, policy: MismatchPolicy) -> Result<Vec<bool>, FeaturizerError> {
+    let mismatches = sequence_mismatches(chain_data.entity_sequence.as_bytes(), chain_data.structure, chain_data.resids,
+                                         &chain_data.entity_index);
+    let mut flags = vec![false; chain_data.resids.len()];
+    if mismatches.is_empty() { return Ok(flags); }
+    let listed = mismatches.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(", ");
+    match policy {
+        MismatchPolicy::Reject => return Err(FeaturizerError::Filtered(format!("{} residues don't match the entity sequence: {}", mismatches.len(), listed))),
+        MismatchPolicy::Warn => warn!("{}: {} residues don't match the entity sequence: {}", chain_data.id, mismatches.len(), listed),
+        MismatchPolicy::Mask => {
+            warn!("{}: {} residues don't match the entity sequence and are written as missing: {}", chain_data.id, mismatches.len(), listed);
+            for m in &mismatches { flags[m.index] = true; }
+        }
+    }
+    Ok(flags)
+}
+
+fn check(token: &CancellationToken) -> Result<(), FeaturizerError> {
+    if token.is_cancelled() { Err(FeaturizerError::Cancelled) } else { Ok(()) }
+}
+
+/// Computes features of a single chain and writes them to files named `<out_root>.dat` etc.
+///
+/// Every `.dat` file is accompanied by a `<out_root>.meta` file recording its provenance, see [`write_provenance()`].
+///
+/// The token is checked between consecutive stages of the computation as well as for every residue written;
+/// once it's cancelled, the function returns [`FeaturizerError::Cancelled`]. Output files of a failed
+/// or cancelled featurization are removed.
+pub fn featurize_chain(fname: &str, chain: &str, out_root: &str, config: &FeaturizerConfig,
+                       token: &CancellationToken) -> Result<ChainSummary, FeaturizerError> {
+    let stage = Instant::now();
+    let loaded = match config.low_memory {
+        true => LoadedDeposit::load_chains(fname, &[source_chain(chain)], config),
+        false => LoadedDeposit::load(fname, config),
+    };
+    let parsing = stage.elapsed();
+    match loaded {
+        Ok(loaded) => featurize_loaded_chain(&loaded, chain, out_root, config, token).map(|mut summary| {
+            summary.timings.parsing = parsing;
+            summary
+        }),
+        Err(e) => {
+            remove_outputs(out_root);
+            Err(e)
+        }
+    }
+}
+
+/// A deposit parsed once, so that several of its chains may be featurized without reading the file again
+pub struct LoadedDeposit {
+    /// the input file, or `-` for the standard input
+    pub fname: String,
+    pub deposit: Deposit,
+    /// number of malformed atom records skipped in the lenient mode
+    pub skipped_records: usize,
+}
+
+impl LoadedDeposit {
+    /// Loads a deposit as [`featurize_chain()`] does, i.e. in the lenient mode when the config says so
+    pub fn load(fname: &str, config: &FeaturizerConfig) -> Result<LoadedDeposit, FeaturizerError> {
+        let (deposit, skipped_records) = if config.lenient { load_deposit_lenient(fname)? } else { (load_deposit(fname)?, 0) };
+        Ok(LoadedDeposit { fname: fname.to_string(), deposit, skipped_records })
+    }
+
+    /// Loads only the atoms of the given chains of a deposit, streaming the file rather than parsing it whole (see
+    /// [`chain_text()`]), so that the memory taken doesn't depend on the size of the deposit
+    pub fn load_chains(fname: &str, chains: &[&str], config: &FeaturizerConfig) -> Result<LoadedDeposit, FeaturizerError> {
+        let text = chain_text(fname, chains)?;
+        let (deposit, skipped_records) = if config.lenient { parse_deposit_lenient(&text)? } else { (parse_deposit(&text)?, 0) };
+        Ok(LoadedDeposit { fname: fname.to_string(), deposit, skipped_records })
+    }
+
+    /// Parses a deposit from the text of an mmCIF or PDB file, without any filesystem access; `name` stands for the file name,
+    /// e.g. in logs and to detect the source of the structure. Biological assemblies can't be built for such a deposit.
+    pub fn from_text(name: &str, text: &str, config: &FeaturizerConfig) -> Result<LoadedDeposit, FeaturizerError> {
+        let (deposit, skipped_records) = if config.lenient { parse_deposit_lenient(text)? } else { (parse_deposit(text)?, 0) };
+        Ok(LoadedDeposit { fname: name.to_string(), deposit, skipped_records })
+    }
+}
+
+/// Featurizes a chain of an already loaded deposit, like [`featurize_chain()`]
+pub fn featurize_loaded_chain(loaded: &LoadedDeposit, chain: &str, out_root: &str, config: &FeaturizerConfig,
+                              token: &CancellationToken) -> Result<ChainSummary, FeaturizerError> {
+    let result = featurize(loaded, chain, out_root, config, token);
+    if result.is_err() { remove_outputs(out_root); }
+
+    result
+}
+
+/// Backbone H-bonds of the written residues of a chain, each listed once, in the order of the residues.
+///
+/// Residues are indexed in the context structure, whose residues of the featurized chain start at `own_offset`;
+/// `written` tells whether a residue of the chain (given by its index in the chain) has a `.dat` record;
+/// `entity_index` gives the positions of residues of the chain in the entity, to find their sequence separation.
+fn chain_hbonds(hbonds: &BackboneHBondMap, context: &Structure, context_resids: &[ResidueId], labels: &[String], own_offset: usize,
+                chain: &str, entity_index: &[Option<usize>], written: impl Fn(usize) -> bool) -> Vec<HBondPair> {
+    let own = |j_ctx: usize| context_resids[j_ctx].chain_id == chain;
+    let separation = |donor_ctx: usize, acceptor_ctx: usize| (own(donor_ctx) && own(acceptor_ctx))
+        .then(|| sequence_separation(entity_index, donor_ctx - own_offset, acceptor_ctx - own_offset));
+    // --- H-bond partners are looked up only among residues close enough to form one
+    let ca: Vec<Option<Vec3>> = context_resids.iter().map(|r| context.atom(r, " CA ").ok().map(|a| a.pos.clone())).collect();
+    let grid = NeighborGrid::new(&ca, HBOND_CA_CUTOFF);
+    let mut pairs = vec![];
+    for i_ctx in own_offset..context_resids.len() {
+        if !own(i_ctx) || !written(i_ctx - own_offset) { continue; }
+        let i_res = &context_resids[i_ctx];
+        let Some(ca_i) = &ca[i_ctx] else { continue };
+        for j_ctx in grid.within(ca_i, HBOND_CA_CUTOFF) {
+            let j_res = &context_resids[j_ctx];
+            if let Some(hb) = hbonds.h_bond(i_res, j_res) {
+                let g = hbond_geometry(context, context_resids, i_ctx, j_ctx);
+                let direction = if !own(j_ctx) { HBondDirection::Out } else if j_ctx > i_ctx { HBondDirection::Forward } else { HBondDirection::Backward };
+                pairs.push(HBondPair { donor: labels[i_ctx].clone(), acceptor: labels[j_ctx].clone(), energy: hb.dssp_energy(),
+                    d_da: g.d_da, d_ha: g.d_ha, angle_dha: g.angle_dha, direction, separation: separation(i_ctx, j_ctx) });
+            }
+            // --- H-bonds donated by written residues of the chain are listed with their donor
+            if own(j_ctx) && written(j_ctx - own_offset) { continue; }
+            if let Some(hb) = hbonds.h_bond(j_res, i_res) {
+                let g = hbond_geometry(context, context_resids, j_ctx, i_ctx);
+                let direction = if !own(j_ctx) { HBondDirection::In } else if i_ctx > j_ctx { HBondDirection::Forward } else { HBondDirection::Backward };
+                pairs.push(HBondPair { donor: labels[j_ctx].clone(), acceptor: labels[i_ctx].clone(), energy: hb.dssp_energy(),
+                    d_da: g.d_da, d_ha: g.d_ha, angle_dha: g.angle_dha, direction, separation: separation(j_ctx, i_ctx) });
+            }
+        }
+    }
+
+    pairs
+}
+
+fn featurize(loaded: &LoadedDeposit, chain: &str, out_root: &str, config: &FeaturizerConfig,
+             token: &CancellationToken) -> Result<ChainSummary, FeaturizerError> {
+
+    let (fname, deposit, skipped_records) = (loaded.fname.as_str(), &loaded.deposit, loaded.skipped_records);
+    check(token)?;
+    let started = Instant::now();
+    let mut timings = StageTimings::default();
+    let mut polymer = polymer_structure(deposit);
+    if let Some(label) = config.conformer { polymer = select_conformer(&polymer, label); }
+    let mut repairs = None;
+    if config.repair {
+        let (repaired, found) = repair_backbone(&polymer);
+        polymer = repaired;
+        repairs = Some(found);
+    }
+    // --- symmetry operators are read from the input file again, which isn't possible for the standard input
+    if config.assembly.is_some() && fname == STDIN {
+        warn!("Biological assemblies can't be built for a deposit read from the standard input; the asymmetric unit is used");
+    }
+    if let Some(assembly_id) = config.assembly.as_ref().filter(|_| fname != STDIN) {
+        polymer = build_assembly(fname, &polymer, assembly_id)?;
+        check(token)?;
+    }
+    if let Some(centered) = center_on_chain(&polymer, chain, config.center_frame)? { polymer = centered; }
+    let strctr = select_chain(&polymer, chain)?;
+    let entity_id = &strctr.atoms()[0].entity_id;
+    let entity = deposit.entity(entity_id);
+    // ResidueType objects for all residues in the entity; some of them are gaps
+    let entity_resids = entity.chain_monomers(source_chain(chain))?;
+    // ResidueIDs for all residues in the chain; it may have fewer residues than in the entity (because of gaps)
+    let chain_resids = strctr.residue_ids();
+    let polymer_kind = check_polymer(entity_resids.iter().map(|r| &r.code3), chain, config.polymer)?;
+    let source = config.source.unwrap_or_else(|| Source::detect(fname));
+    let mut stats = statistics(&strctr, &chain_resids, entity_resids.len(), deposit.resolution, source, &config.filters);
+    if config.filters.checks_release_date() { stats.release_date = release_date(fname)?; }
+    config.filters.complete_statistics(&mut stats, fname);
+    if let Some(v) = config.filters.violations(&stats).first() {
+        let (value, threshold) = v.formatted();
+        return Err(FeaturizerError::Filtered(format!("{} is {}, required --{} {}", v.criterion, value, v.criterion, threshold)));
+    }
+    let mut n_loop = 0;
+    // H-bonds and contacts are detected within the chain and, for inter-chain features, also to the chains it's in contact with
+    let context = if config.inter_chain || config.assembly.is_some() {
+        chain_neighborhood(&polymer, chain, CONTEXT_CUTOFF)
+    } else {
+        select_chain(&polymer, chain)?
+    };
+    let context_resids = context.residue_ids();
+    let own_offset = context_resids.iter().position(|r| r.chain_id == chain).unwrap_or(0);
+    let mut per_chain_index: HashMap<&str, usize> = HashMap::new();
+    // partner labels: a residue index within this chain, or chain-qualified (e.g. B-2:47) for other chains
+    let partner_labels: Vec<String> = context_resids.iter().map(|r| {
+        let idx = per_chain_index.entry(r.chain_id.as_str()).or_insert(0);
+        let label = if r.chain_id == chain { idx.to_string() } else { format!("{}:{}", r.chain_id, idx) };
+        *idx += 1;
+        label
+    }).collect();
+    let stage = Instant::now();
+    let hbonds = BackboneHBondMap::new(&context);
+    timings.hbonds = stage.elapsed();
+    check(token)?;
+    // --- a chain is rejected for a sequence mismatch before any of its outputs is written
+    let mut chain_data = ChainContext::new(out_root, &strctr, &chain_resids, entity, source_chain(chain), &hbonds)?;
+    let mismatched = check_sequence(&chain_data, config.on_mismatch)?;
+    let side_outputs = out_root != STDOUT;
+    if !side_outputs && (config.contacts.is_some() || config.interactions || config.ligands || config.gaps || config.crop.is_some() || config.split.is_some()
+        || config.graph.is_some() || config.uniprot.is_some() || config.hbonds.pairs || config.granularity == Granularity::Atom) {
+        warn!("Only the .dat content is written to the standard output; contacts, interactions, ligands, gaps, crops, parts, graphs, UniProt mappings, H-bond pairs and atom records are skipped");
+    }
+    if let Some(spec) = config.contacts.as_ref().filter(|_| side_outputs) {
+        let virtual_cb = spec.is_cb().then(|| virtual_cb_flags(&strctr, &chain_resids));
+        if config.low_memory {
+            write_contacts_chunked(&strctr, &chain_resids, spec, config.contacts_matrix, virtual_cb.as_deref(), &chain_data.entity_index,
+                                   LOW_MEMORY_CHUNK, &format!("{}.contacts", out_root))?;
+        } else {
+            let contacts = find_contacts(&strctr, &chain_resids, spec);
+            write_contacts(&contacts, chain_resids.len(), config.contacts_matrix, virtual_cb.as_deref(), &chain_data.entity_index,
+                           &format!("{}.contacts", out_root))?;
+        }
+        // --- a contact matrix covers this chain only
+        if !config.contacts_matrix {
+            let (partner_resids, labels): (Vec<_>, Vec<_>) = context_resids.iter().zip(partner_labels.iter())
+                .filter(|(r, _)| r.chain_id != chain).map(|(r, l)| (r.clone(), l.clone())).unzip();
+            let interchain = find_interchain_contacts(&context, &chain_resids, &partner_resids, spec);
+            let partner_virtual_cb = spec.is_cb().then(|| virtual_cb_flags(&context, &partner_resids));
+            let flags = virtual_cb.as_deref().zip(partner_virtual_cb.as_deref());
+            append_interchain_contacts(&interchain, &labels, flags, &format!("{}.contacts", out_root))?;
+        }
+    }
+    if config.interactions && side_outputs {
+        write_interactions(&find_interactions(&strctr, &chain_resids), &format!("{}.interactions", out_root))?;
+    }
+    if config.ligands && side_outputs {
+        write_ligand_contacts(&nearest_ligands(&strctr, &chain_resids, &ligand_atoms(deposit)), &format!("{}.ligands", out_root))?;
+    }
+    if config.gaps && side_outputs {
+        let missing: Vec<bool> = entity_resids.iter().map(|r| r.parent_type == StandardResidueType::GAP).collect();
+        write_gaps(&find_gaps(&missing, &strctr, &chain_resids), &format!("{}.gaps", out_root))?;
+    }
+    chain_data.lattice_distances = crystal_lattice(loaded, chain, config, chain_resids.len())?;
+    chain_data.partner_distances = chain_partners(&polymer, chain, config)?;
+    chain_data.repairs = repairs.map(|r| r.of_chain(source_chain(chain), &chain_resids));
+    let stage = Instant::now();
+    assign_chain_secondary(&mut chain_data, config);
+    timings.secondary = stage.elapsed();
+    let mut segments: Vec<UniProtSegment> = vec![];
+    if let Some(mapping) = config.uniprot.as_ref().filter(|_| side_outputs) {
+        let pdb_code = if strctr.id_code.trim().is_empty() {
+            let file_root = Path::new(out_root).file_name().and_then(|f| f.to_str()).unwrap_or(out_root);
+            file_root.split('_').next().unwrap_or(file_root).to_string()
+        } else { strctr.id_code.trim().to_string() };
+        segments = mapping.segments(&pdb_code, source_chain(chain))?;
+        if segments.is_empty() { warn!("No UniProt mapping found for chain {} of {}", chain, pdb_code); }
+        write_uniprot_mapping(&segments, &chain_data.entity_index, &format!("{}.uniprot", out_root))?;
+    }
+    if let Some(aug) = config.augmentation.as_ref().filter(|_| side_outputs) {
+        let chain_id = Path::new(out_root).file_name().and_then(|f| f.to_str()).unwrap_or(out_root);
+        write_augmented(&strctr, &chain_resids, &chain_data.ss, aug, chain_id, &format!("{}.aug", out_root))?;
+    }
+    if let Some(cutoff) = config.graph.filter(|_| side_outputs) {
+        write_graph(&build_graph(&chain_data, cutoff), &format!("{}.graph", out_root))?;
+    }
+    let stage = Instant::now();
+    let mut features: Vec<Vec<Vec<f64>>> = vec![];
+    for extractor in &config.extractors {
+        check(token)?;
+        features.push(extractor.extract(&chain_data));
+    }
+    timings.features = stage.elapsed();
+    let masked: Vec<bool> = match config.min_plddt {
+        Some(min_plddt) if source == Source::Afdb => low_confidence(&strctr, &chain_resids, min_plddt),
+        Some(_) => {
+            warn!("{} is not an AlphaFold DB model, its B-factors are not used as pLDDT", fname);
+            vec![false; chain_resids.len()]
+        }
+        _ => vec![false; chain_resids.len()],
+    };
+    let masked: Vec<bool> = masked.iter().zip(&mismatched).map(|(a, b)| *a || *b).collect();
+    let hbond_pairs = config.hbonds.apply(chain_hbonds(&hbonds, &context, &context_resids, &partner_labels, own_offset, chain,
+                                                     &chain_data.entity_index, |i| {
+        chain_data.entity_index[i].is_some() && !masked[i] && strctr.atom(&chain_resids[i], " CA ").is_ok()
+    }));
+    let dat_fname = match config.compression {
+        Some(compression) => compression.file_name(&format!("{}.dat", out_root)),
+        None => format!("{}.dat", out_root),
+    };
+    let out: Box<dyn Write> = match config.compression {
+        _ if !side_outputs => Box::new(stdout()),
+        Some(compression) => compressed_writer(&dat_fname, compression)?,
+        None => out_writer(&dat_fname, false),
+    };
+    let mut outfile = RecordWriter::new(out, config.flush);
+    // --- a single line buffer is reused for all the records of a chain
+    let mut line = String::with_capacity(256);
+    // records (and CA positions of their residues) are kept only when they are also written as crops or parts
+    let mut records: Vec<String> = vec![];
+    let mut records_ca: Vec<Option<Vec3>> = vec![];
+    let keep_records = (config.crop.is_some() || config.split.is_some()) && side_outputs;
+    // --- heavy atoms of every written residue, with the features of the residue
+    let heavy_atoms = (config.granularity == Granularity::Atom && side_outputs).then(|| residue_heavy_atoms(&strctr, &chain_resids));
+    let mut atom_residues: Vec<(usize, Vec<&PdbAtom>, String)> = vec![];
+    // --- observed residues at every entity position; residues that can't be aligned to the entity aren't written
+    let mut entity_to_chain: Vec<Option<usize>> = vec![None; entity_resids.len()];
+    for (i_res_idx, e) in chain_data.entity_index.iter().enumerate() {
+        match e {
+            Some(e) => entity_to_chain[*e] = Some(i_res_idx),
+            None => warn!("Residue {} of {} can't be aligned to its entity sequence and is skipped", chain_resids[i_res_idx], out_root),
+        }
+    }
+    for (e, (res, i_res_idx)) in entity_resids.iter().zip(&entity_to_chain).enumerate() {
+        check(token)?;
+        let Some(i_res_idx) = i_res_idx.filter(|i| !masked[*i]) else {
+            line.clear();
+            let _ = write!(line, "{:^4} {}", '-', res);
+            outfile.push(&line)?;
+            if keep_records { records.push(line.clone()); records_ca.push(None); }
+            continue;
+        };
+        let i_res = &chain_resids[i_res_idx];
+        if let Some(ca) = reference_atom(&strctr, i_res, polymer_kind) {
+            let ss_code = chain_data.ss[i_res_idx];
+            if ss_code == b'C' { n_loop += 1; }
+            line.clear();
+            let number = match config.numbering {
+                Numbering::Auth => author_number(i_res),
+                Numbering::Label => (e + 1).to_string(),
+            };
+            let _ = write!(line, "{:4} {:7} {} {:>6} : {} {:8.3} {:8.3} {:8.3}", i_res_idx, res, i_res, number,
+                           ss_code as char, ca.pos.x, ca.pos.y, ca.pos.z);
+            let features_start = line.len();
+            for feature in &features {
+                for v in &feature[i_res_idx] { let _ = write!(line, " {:8.3}", v); }
+            }
+            outfile.push(&line)?;
+            if let Some(atoms) = &heavy_atoms { atom_residues.push((i_res_idx, atoms[i_res_idx].clone(), line[features_start..].to_string())); }
+            if keep_records { records.push(line.clone()); records_ca.push(Some(ca.pos.clone())); }
+        } else {
+            warn!("CA atom missing for residue: {}", i_res);
+        }
+    }
+    // --- H-bonds follow the records on the standard output, in a section of their own
+    if side_outputs {
+        write_hbonds(&hbond_pairs, &mut out_writer(&format!("{}.hbonds", out_root), false))?;
+        if config.hbonds.pairs {
+            write_hbond_pairs(&canonical_hbond_pairs(&hbond_pairs), &mut out_writer(&format!("{}.hbpairs", out_root), false))?;
+        }
+    } else {
+        outfile.push(HBONDS_HEADER)?;
+        for hb in &hbond_pairs { outfile.push(&hb.to_string())?; }
+    }
+    outfile.finish()?;
+    if heavy_atoms.is_some() { write_atom_records(&atom_residues, &format!("{}.atoms", out_root))?; }
+    if let Some(crop) = config.crop.as_ref().filter(|_| keep_records) {
+        write_crops(&records, crop, &format!("{}.crops", out_root))?;
+    }
+    if let Some(split) = config.split.as_ref().filter(|s| keep_records && records.len() > s.max_length) {
+        let parent = Path::new(out_root).file_name().and_then(|f| f.to_str()).unwrap_or(out_root);
+        write_blocks(&records, &split.parts(&records_ca), |k, first, last| format!("# part {} {} {} {}", k, first, last, parent),
+                     &format!("{}.parts", out_root))?;
+    }
+    if let Some(compression) = config.compression.filter(|_| side_outputs) {
+        for ext in OUTPUT_EXTENSIONS.iter().filter(|ext| !["dat", "meta"].contains(ext)) {
+            let out_fname = format!("{}.{}", out_root, ext);
+            if Path::new(&out_fname).exists() { compress_file(&out_fname, compression)?; }
+        }
+    }
+    let conformer_group = config.conformer_groups.then(|| conformer_group(&chain_data.entity_sequence, &segments));
+    if side_outputs {
+        let mut metadata = entity_metadata(fname, entity_id, source_chain(chain), deposit.resolution)?;
+        metadata.conformer_group = conformer_group.clone();
+        write_provenance(fname, chain, &metadata, config, &format!("{}.meta", out_root))?;
+    }
+
+    let n_residues = entity_resids.len();
+    let n_observed = chain_resids.len();
+    let loop_fraction = n_loop as f64 / n_observed as f64;
+    let contact_order = relative_contact_order(&strctr, &chain_resids);
+    // --- gaps are entity positions without an aligned residue; unaligned residues make n_observed exceed them
+    let n_gaps = entity_to_chain.iter().filter(|i| i.is_none()).count();
+    let gap_fraction = if n_residues > 0 { n_gaps as f64 / n_residues as f64 } else { 0.0 };
+    let difficulty = difficulty_score(loop_fraction, contact_order, gap_fraction, deposit.resolution);
+
+    let key = format!("{}/{}", source, Path::new(out_root).file_name().and_then(|f| f.to_str()).unwrap_or(out_root));
+    timings.writing = started.elapsed().saturating_sub(timings.hbonds + timings.secondary + timings.features);
+
+    Ok(ChainSummary { id: out_root.to_string(), key, source, output: dat_fname, input: fname.to_string(), chain: chain.to_string(),
+        conformer: config.conformer, n_residues, n_observed, loop_fraction, contact_order, gap_fraction, resolution: deposit.resolution, difficulty, fold: None,
+        skipped_records, superseded: None, conformer_group, timings })
+}
+
+/// Assigns secondary structure of a chain by the method of the config; with inference features, that of residues
+/// lacking backbone atoms is masked, unless the method needs CA atoms only
+fn assign_chain_secondary(chain_data: &mut ChainContext, config: &FeaturizerConfig) {
+    if config.ss_method != SsMethod::Dssp { chain_data.ss = assign_secondary(chain_data, config.ss_method); }
+    if config.inference && config.ss_method != SsMethod::PSeaFromCa { mask_secondary_structure(chain_data); }
+}
+
+/// Distances of the residues of a chain to its symmetry mates, when any of the extractors needs them.
+///
+/// The lattice is built around the asymmetric unit as deposited, so the distances of a chain copied into a biological
+/// assembly are those of its source chain; `None` is also returned when the residues of the two don't match.
+fn crystal_lattice(loaded: &LoadedDeposit, chain: &str, config: &FeaturizerConfig, n_residues: usize) -> Result<Option<Vec<f64>>, FeaturizerError> {
+    if !config.extractors.iter().any(|e| e.needs_lattice()) { return Ok(None); }
+    let mut deposited = polymer_structure(&loaded.deposit);
+    if let Some(label) = config.conformer { deposited = select_conformer(&deposited, label); }
+    let distances = lattice_distances(&loaded.fname, &deposited, source_chain(chain))?;
+
+    Ok(distances.filter(|d| d.len() == n_residues))
+}
+
+/// Distances of the residues of a chain to the other chains of `polymer`, when any of the extractors needs them.
+///
+/// In the low-memory mode only the featurized chain is loaded, so `None` is returned rather than distances that would
+/// find no interface.
+fn chain_partners(polymer: &Structure, chain: &str, config: &FeaturizerConfig) -> Result<Option<Vec<f64>>, FeaturizerError> {
+    if !config.extractors.iter().any(|e| e.needs_partners()) { return Ok(None); }
+    if config.low_memory {
+        warn!("Other chains are not loaded in the low-memory mode; interface labels of chain {} are not computed", chain);
+        return Ok(None);
+    }
+
+    Ok(Some(partner_distances(polymer, chain)?))
+}
+
+/// Features of a single chain computed in memory, for callers that featurize on the fly rather than read `.dat` files
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct ChainFeatures {
+    /// one-letter sequence of the entity, including residues missing in the structure
+    pub entity_sequence: String,
+    /// IDs of the observed residues; all the other fields are indexed the same way
+    pub residue_ids: Vec<String>,
+    /// author numbering (`auth_seq_id`) of every residue, followed by its insertion code, if any
+    pub author_numbers: Vec<String>,
+    /// label numbering (`label_seq_id`) of every residue, i.e. `entity_index + 1`
+    pub label_numbers: Vec<Option<usize>>,
+    /// position in the entity sequence of every observed residue; `None` for a residue that can't be aligned to the entity
+    pub entity_index: Vec<Option<usize>>,
+    /// secondary structure (H, E or C) of every residue
+    pub ss: String,
+    /// CA coordinates of every residue; NaN when the CA atom is missing
+    pub ca: Vec<[f64; 3]>,
+    /// values of every selected feature, named as in [`FeatureInfo`](crate::features::FeatureInfo); a row per residue
+    pub features: Vec<(String, Vec<Vec<f64>>)>,
+}
+
+/// Computes features of a chain as [`featurize_chain()`] does, but returns them instead of writing any file.
+///
+/// Only the per-residue features of `config.extractors` are computed, with H-bonds detected within the chain;
+/// H-bond partners and side outputs, such as contacts, are not returned. Stages are not timed, since `std::time::Instant`
+/// panics on `wasm32-unknown-unknown`, where this is the entry point of the `wasm` crate.
+pub fn compute_chain_features(loaded: &LoadedDeposit, chain: &str, config: &FeaturizerConfig) -> Result<ChainFeatures, FeaturizerError> {
+    let (fname, deposit) = (loaded.fname.as_str(), &loaded.deposit);
+    let mut polymer = polymer_structure(deposit);
+    if let Some(label) = config.conformer { polymer = select_conformer(&polymer, label); }
+    let mut repairs = None;
+    if config.repair {
+        let (repaired, found) = repair_backbone(&polymer);
+        polymer = repaired;
+        repairs = Some(found);
+    }
+    if let Some(assembly_id) = config.assembly.as_ref().filter(|_| fname != STDIN) {
+        polymer = build_assembly(fname, &polymer, assembly_id)?;
+    }
+    if let Some(centered) = center_on_chain(&polymer, chain, config.center_frame)? { polymer = centered; }
+    let strctr = select_chain(&polymer, chain)?;
+    let entity = deposit.entity(&strctr.atoms()[0].entity_id);
+    let entity_resids = entity.chain_monomers(source_chain(chain))?;
+    let n_residues = entity_resids.len();
+    let polymer_kind = check_polymer(entity_resids.iter().map(|r| &r.code3), chain, config.polymer)?;
+    let resids = strctr.residue_ids();
+    let source = config.source.unwrap_or_else(|| Source::detect(fname));
+    let mut stats = statistics(&strctr, &resids, n_residues, deposit.resolution, source, &config.filters);
+    if config.filters.checks_release_date() { stats.release_date = release_date(fname)?; }
+    if let Some(v) = config.filters.violations(&stats).first() {
+        let (value, threshold) = v.formatted();
+        return Err(FeaturizerError::Filtered(format!("{} is {}, required --{} {}", v.criterion, value, v.criterion, threshold)));
+    }
+    let hbonds = BackboneHBondMap::new(&strctr);
+    let mut chain_data = ChainContext::new(chain, &strctr, &resids, entity, source_chain(chain), &hbonds)?;
+    let mismatched = check_sequence(&chain_data, config.on_mismatch)?;
+    chain_data.lattice_distances = crystal_lattice(loaded, chain, config, resids.len())?;
+    chain_data.partner_distances = chain_partners(&polymer, chain, config)?;
+    chain_data.repairs = repairs.map(|r| r.of_chain(source_chain(chain), &resids));
+    assign_chain_secondary(&mut chain_data, config);
+    let ca = resids.iter().map(|r| reference_atom(&strctr, r, polymer_kind).map_or([f64::NAN; 3], |a| [a.pos.x, a.pos.y, a.pos.z])).collect();
+    // --- features of residues masked for a sequence mismatch are NaN
+    let features = config.extractors.iter().map(|e| {
+        let mut values = e.extract(&chain_data);
+        for (row, _) in values.iter_mut().zip(&mismatched).filter(|(_, m)| **m) { row.fill(f64::NAN); }
+        (e.info().name.to_string(), values)
+    }).collect();
+
+    Ok(ChainFeatures { entity_sequence: chain_data.entity_sequence.clone(), residue_ids: resids.iter().map(|r| r.to_string()).collect(),
+        author_numbers: resids.iter().map(author_number).collect(), label_numbers: chain_data.entity_index.iter().map(|e| e.map(|e| e + 1)).collect(),
+        entity_index: chain_data.entity_index.clone(), ss: String::from_utf8_lossy(&chain_data.ss).to_string(), ca, features })
+}
+
+/// Output files of a chain that exist for a given output root name, compressed or not
+pub fn output_files(out_root: &str) -> Vec<String> {
+    OUTPUT_EXTENSIONS.iter().flat_map(|ext| {
+        let out_fname = format!("{}.{}", out_root, ext);
+        std::iter::once(out_fname.clone()).chain(COMPRESSED_EXTENSIONS.iter().map(move |c| format!("{}.{}", out_fname, c)))
+    }).filter(|f| Path::new(f).exists()).collect()
+}
+
+/// Removes all output files that may have been created for a given output root name
+pub fn remove_outputs(out_root: &str) {
+    for out_fname in output_files(out_root) {
+        if let Err(err) = fs::remove_file(&out_fname) { error!("Can't remove the output file: {}", err); }
+        else { warn!("Removed the incomplete output file: {}", &out_fname); }
+    }
+}
+
+/// Path of a file moved into a given directory: the directory part of `fname` is replaced by `dir`
+pub fn moved_path(fname: &str, dir: &str) -> String {
+    let name = Path::new(fname).file_name().map_or(fname.into(), |f| f.to_string_lossy());
+    Path::new(dir).join(name.as_ref()).to_string_lossy().to_string()
+}
+
+/// Moves all output files of a chain into a given directory, where they keep their file names;
+/// returns the new root name of the outputs
+pub fn move_outputs(out_root: &str, dir: &str) -> Result<String, FeaturizerError> {
+    fs::create_dir_all(dir)?;
+    for out_fname in output_files(out_root) {
+        fs::rename(&out_fname, moved_path(&out_fname, dir))?;
+    }
+    Ok(moved_path(out_root, dir))
+}
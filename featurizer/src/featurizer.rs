@@ -1,145 +1,1567 @@
-use std::{env, fs};
-use std::fmt::format;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
 use std::path::Path;
-use bioshell_interactions::BackboneHBondMap;
-use bioshell_io::{open_file, out_writer, read_whitespace_delimited_values};
-use clap::{Parser};
+use std::process::ExitCode;
+use std::time::Instant;
+use bioshell_io::{open_file, read_whitespace_delimited_values};
+use clap::{Parser, Subcommand, ValueEnum};
+use glob::glob;
+use indicatif::{ProgressBar, ProgressStyle};
 
-use bioshell_pdb::{Structure, Deposit, code_and_chain, find_cif_file_name, find_pdb_file_name, PDBError};
-use bioshell_pdb::PDBError::NoSuchChain;
-use bioshell_seq::chemical::{MonomerType, StandardResidueType};
+use bioshell_pdb::{code_and_chain, find_cif_file_name, find_pdb_file_name, Deposit};
 use log::{debug, error, info, warn};
 
+use featurizer::{featurize_chain, featurize_loaded_chain, move_outputs, moved_path, output_files, remove_outputs, CancellationToken, FeaturizerConfig, FeaturizerError, STDOUT};
+use featurizer::alignment::MismatchPolicy;
+use featurizer::deposit_cache::DepositCache;
+use featurizer::logging::{init_logging, LogFormat};
+use featurizer::contacts::ContactSpec;
+use featurizer::ideal_backbone::ResidualTargets;
+use featurizer::atoms::Granularity;
+use featurizer::ensembles::{group_conformers, write_conformer_groups};
+#[cfg(feature = "json")]
+use featurizer::reproducibility::{read_dataset_json, verify_dataset};
+use featurizer::reproducibility::{dataset_manifest, write_dataset_json};
+use featurizer::manifest::{merge_manifests, read_manifest, source_statistics, write_manifest, ChainSummary};
+use featurizer::polymers::PolymerPolicy;
+use featurizer::secondary::SsMethod;
+use featurizer::timings::{write_timings, StageTimings};
+use featurizer::sources::Source;
+use featurizer::compression::{output_root, Compression};
+use featurizer::records::{FlushPolicy, Numbering};
+use featurizer::centering::CenterFrame;
+use featurizer::hbonds::HBondPolicy;
+use featurizer::archive::{pack_dataset, write_index, ArchiveFormat, DEFAULT_SHARD_SIZE};
+use featurizer::frames::{FrameReference, ResidueFrames};
+use featurizer::splits::{assign_folds, load_clusters, sample_entries, Shard};
+use featurizer::ca_trace::CaTraceGeometry;
+use featurizer::ramachandran::RamachandranRegions;
+use featurizer::torsions::TorsionAngles;
+use featurizer::gaps::{ChainBreaks, Connectivity};
+use featurizer::motifs::HBondMotifs;
+use featurizer::sheets::SheetTopologyFeatures;
+use featurizer::helices::HelixGeometry;
+use featurizer::features::{describe_features, FeatureExtractor};
+use featurizer::chains::{extract_chain, load_chain, load_deposit, polymer_chain_ids, representative_chains, select_chain_ids, selected_chains, STDIN};
+use featurizer::streaming::atom_chain_ids;
+use featurizer::chain_pair::{write_chain_pair, InterfaceResidues};
+use featurizer::diff::write_chain_diff;
+use featurizer::evaluate::{evaluate_model, write_evaluation};
+use featurizer::chi_angles::ChiAngles;
+use featurizer::alanine_scan::AlanineScan;
+use featurizer::residue_encoding::{Encoding, ResidueEncoding};
+use featurizer::scales::PropertyScales;
+use featurizer::profiles::SequenceProfiles;
+use featurizer::conservation::{SequenceConservation, SequenceDatabase, MAX_HITS, MIN_IDENTITY};
+use featurizer::symmetry::SymmetryEquivalence;
+use featurizer::bfactors::{BFactorAtoms, BFactors};
+use featurizer::map_quality::MapQualityFeatures;
+use featurizer::membrane::MembraneFeatures;
+use featurizer::plddt::Plddt;
+use featurizer::agreement::PredictionAgreement;
+use featurizer::microenvironment::MicroEnvironment;
+use featurizer::exposure::{HalfSphereExposure, MaxAsaTable, SolventExposure};
+use featurizer::depth::ResidueDepth;
+use featurizer::crystal::CrystalContacts;
+use featurizer::inference::inference_extractors;
+use featurizer::repair::RepairFlags;
+use featurizer::internal_coords::{InternalCoordinates, TargetRepresentation};
+use featurizer::config::Config;
+use featurizer::augmentation::Augmentation;
+use featurizer::crops::Crop;
+use featurizer::linkers::LinkerSplit;
+use featurizer::filters::{deposit_chain_statistics, write_triage, ChainFilters, TriageResult};
+use featurizer::failures::{read_failures, write_failures, write_retry_list, Failure};
+use featurizer::conformers::altloc_labels;
+use featurizer::fragments::FragmentLibrary;
+use featurizer::stats::{dataset_stats, write_stats};
+use featurizer::validate::{validate_dir, write_validation};
+use featurizer::rebuild::{rebuild_chain, write_cif, write_pdb};
+use featurizer::importance::{importance_probe, sample_residues, write_report, ProbeParams, ProbeTarget};
+#[cfg(feature = "parquet")]
+use featurizer::parquet::ParquetDataset;
+#[cfg(feature = "crypto")]
+use featurizer::crypto::{encrypt_dir, encrypt_outputs, parse_recipients, read_signing_key, sign_file, verify_file};
+use featurizer::sifts::UniProtMapping;
+use featurizer::obsolete::ObsoleteEntries;
+use featurizer::sync::{bump_dataset_version, download_deposit, is_url, pdb_code, PdbUpdates, WWPDB_STATUS_URL};
+use featurizer::update::{check_input, from_entry, read_input_database, write_input_database, InputEntry, INPUT_DATABASE};
+
 const SHORT_HELP: &str = "\n\nCommand line application to create input data for training deep_bbq v.2 model\n\n
 Say featurizer -h to see options or featurizer --help for a longer description of the program";
 
 const LONG_AFTER_HELP: &str = "\x1b[4mExamples:\x1b[0m
 1. To featurize a single .cif or .pdb file:
-\tfeaturizer -i tests/input_files/2gb1.cif -c A\n\n\
+\tfeaturizer featurize -i tests/input_files/2gb1.cif -c A\n\n\
+2. To featurize chains listed in a file and write also CB-CB contacts shorter than 8 Å:
+\tfeaturizer featurize -l tests/input_files/list.txt -p tests/input_files/ --contacts CB:8.0\n\n\
+3. To list all the features the featurizer can compute and save their description in JSON:
+\tfeaturizer describe-features --json features.json\n\n\
+4. To featurize a gzipped deposit streamed through a pipeline and print its features:
+\tzcat 2gb1.cif.gz | featurizer featurize -i - -c A -o - > 2gb1_A.dat\n\n\
+5. To compute inter-chain features between chains L and H of thrombin:
+\tfeaturizer chain-pair --first tests/input_files/1c5n.cif --first-chain L --second tests/input_files/1c5n.cif --second-chain H -o 1c5n_LH.pairs\n\n\
+6. To compare apo and holo conformations of a chain:
+\tfeaturizer diff --first apo.cif --first-chain A --second holo.cif --second-chain A -o apo_holo.diff\n\n\
+7. To evaluate a reconstructed backbone against the deposited structure:
+\tfeaturizer evaluate --model 2gb1_rebuilt.pdb --model-chain A --reference tests/input_files/2gb1.cif --reference-chain A -o 2gb1.eval\n\n\
+8. To apply the latest weekly PDB update to a dataset described by manifest.tsv (e.g. from a cron job):
+\tfeaturizer --manifest manifest.tsv sync --all-chains -p pdb_mirror/\n\n\
+9. To build a fragment library of 4-residue windows from chains listed in a file:
+\tfeaturizer fragment-library -l tests/input_files/list.txt -p tests/input_files/ -k 4 -o fragments.bin\n\n\
+10. To check distributions of backbone geometry and H-bond energies over a featurized dataset:
+\tfeaturizer --manifest manifest.tsv stats -o stats.json\n\n\
+11. To check which features of a dataset carry signal about the strand secondary structure:
+\tfeaturizer --manifest manifest.tsv importance-probe --target ss=E -o importance.tsv\n\n\
+12. To pack a featurized dataset into tar shards of 10k chains each:
+\tfeaturizer --manifest manifest.tsv pack --shard-size 10000 -o shards/dataset\n\n\
+13. To bring a dataset in the dataset/ directory up to date with a new list file, featurizing only new and changed entries:
+\tfeaturizer update --all-chains -l list.txt -p pdb_mirror/ --dir dataset/\n\n\
+14. To featurize a CA-only trace with the input columns the model was trained on:
+\tfeaturizer featurize -i trace.pdb -c A --inference-features\n\n\
+15. To download deposits listed in a file that are missing from a local mirror:
+\tfeaturizer fetch -l list.txt -p pdb_mirror/\n\n\
+16. To split a featurized dataset into 5 cross-validation folds, keeping chains of a sequence cluster in the same fold:
+\tfeaturizer --manifest manifest.tsv split -k 5 --clusters clusters.txt\n\n\
+17. To check outputs stored in the dataset/ directory before training:
+\tfeaturizer validate dataset/ -o validation.tsv\n\n\
+18. To featurize a list file as 8 cluster jobs and merge their manifests afterwards:
+\tfeaturizer --manifest manifest_$K.tsv featurize -l list.txt -p pdb_mirror/ --shard $K/8 --failures failures_$K.tsv
+\tfeaturizer merge-summaries manifest_*.tsv --failures failures_*.tsv\n\n\
 ";
 
+/// Exit status of a run where some of the chains failed with an error; fatal errors end the run with 1
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// Exit status of a run where all the chains failed with an error
+const EXIT_ALL_FAILED: u8 = 3;
+
+/// How the features of a run are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// text files, a .dat file (and its side outputs) per chain
+    Text,
+    /// text files, also converted into partitioned Parquet tables of residues and residue pairs of the whole run
+    Parquet,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// featurize chains of a single file (-i), of those listed in a file (-l) or of all the files found in a directory (--input-dir)
+    Featurize {
+        #[clap(flatten)]
+        input: InputArgs,
+        #[clap(flatten)]
+        features: Box<FeatureArgs>,
+    },
+    /// assign chains listed in the manifest to cross-validation folds; the fold index is written to the manifest
+    Split {
+        /// number of folds, at least 1
+        #[clap(short = 'k', long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        folds: usize,
+        /// file assigning chains to sequence clusters; chains of the same cluster are placed in the same fold
+        #[clap(long)]
+        clusters: Option<String>,
+        /// move output files of each fold into its own fold_<k> directory
+        #[clap(long)]
+        fold_dirs: bool,
+    },
+    /// download deposits listed in a file from the RCSB into a local folder, skipping those already there
+    Fetch {
+        /// file with a list of PDB IDs
+        #[clap(short, long)]
+        list_file: String,
+        /// folder the deposits are downloaded to
+        #[clap(short, long, default_value = ".")]
+        path: String,
+        /// download also deposits already found in the folder, replacing them
+        #[clap(long)]
+        force: bool,
+    },
+    /// print a description of every feature the featurizer can compute
+    DescribeFeatures {
+        /// write the descriptions also to a JSON file
+        #[clap(long)]
+        json: Option<String>,
+    },
+    /// check which chains of a list file pass the filters, without featurizing them
+    Triage {
+        /// file with a list of PDB IDs
+        #[clap(short, long)]
+        list_file: String,
+        /// path to the folder with mmCIF files
+        #[clap(short, long, default_value = "")]
+        path: String,
+        #[clap(flatten)]
+        filters: ChainFilters,
+        /// output file with the triage result of every chain
+        #[clap(short, long, default_value = "triage.tsv")]
+        out: String,
+    },
+    /// compute inter-chain features between two chains, possibly coming from different files
+    ChainPair {
+        /// file with the first chain
+        #[clap(long)]
+        first: String,
+        /// ID of the first chain
+        #[clap(long)]
+        first_chain: String,
+        /// file with the second chain
+        #[clap(long)]
+        second: String,
+        /// ID of the second chain
+        #[clap(long)]
+        second_chain: String,
+        /// residue pairs with CA atoms closer than this distance are written
+        #[clap(long, default_value = "12.0")]
+        cutoff: f64,
+        /// output file name
+        #[clap(short, long)]
+        out: String,
+    },
+    /// compare two conformations of the same chain, e.g. apo and holo or predicted and experimental
+    Diff {
+        /// file with the first conformation
+        #[clap(long)]
+        first: String,
+        /// chain ID of the first conformation
+        #[clap(long)]
+        first_chain: String,
+        /// file with the second conformation
+        #[clap(long)]
+        second: String,
+        /// chain ID of the second conformation
+        #[clap(long)]
+        second_chain: String,
+        /// output file name
+        #[clap(short, long)]
+        out: String,
+    },
+    /// build a BBQ fragment library: CA pseudo-geometry of k-residue windows mapped to their backbone atoms
+    FragmentLibrary {
+        /// file with a list of PDB IDs
+        #[clap(short, long)]
+        list_file: String,
+        /// path to the folder with mmCIF files
+        #[clap(short, long, default_value = "")]
+        path: String,
+        /// number of residues in a window
+        #[clap(short = 'k', long, default_value = "4")]
+        length: usize,
+        /// width of a CA-CA distance bin, in Å
+        #[clap(long, default_value = "0.2")]
+        bin_width: f64,
+        /// output binary file
+        #[clap(short, long, default_value = "fragments.bin")]
+        out: String,
+    },
+    /// apply a weekly PDB update to a dataset: featurize added and modified entries and retire obsolete ones from the manifest
+    Sync {
+        /// folder or URL with the added.pdb, modified.pdb and obsolete.pdb lists; deposits are downloaded along with lists from a URL
+        #[clap(long, default_value = WWPDB_STATUS_URL)]
+        updates: String,
+        /// folder with the deposits of the dataset
+        #[clap(short, long, default_value = ".")]
+        path: String,
+        /// file with the dataset version, incremented by every sync
+        #[clap(long, default_value = "dataset_version.txt")]
+        dataset_version: String,
+        #[clap(flatten)]
+        features: Box<FeatureArgs>,
+    },
+    /// update a dataset to a new list file: featurize only entries that are new or whose deposit file has changed,
+    /// keep outputs of unchanged entries and retire those no longer listed
+    Update {
+        /// file with a list of PDB IDs: the new content of the dataset
+        #[clap(short, long)]
+        list_file: String,
+        /// path to the folder with mmCIF files
+        #[clap(short, long, default_value = "")]
+        path: String,
+        /// directory with outputs of an earlier run, its manifest (--manifest, relative to this directory) and the database of input files;
+        /// other paths of the command line are relative to the working directory
+        #[clap(long, default_value = ".")]
+        dir: String,
+        /// database of input files featurized so far, relative to --dir
+        #[clap(long, default_value = INPUT_DATABASE)]
+        inputs: String,
+        #[clap(flatten)]
+        obsolete: ObsoleteArgs,
+        #[clap(flatten)]
+        features: Box<FeatureArgs>,
+    },
+    /// evaluate a reconstructed backbone against a reference chain: RMSD, per-residue deviations and geometry outliers
+    Evaluate {
+        /// file with the evaluated model
+        #[clap(long)]
+        model: String,
+        /// chain ID of the model
+        #[clap(long)]
+        model_chain: String,
+        /// file with the reference structure
+        #[clap(long)]
+        reference: String,
+        /// chain ID of the reference structure
+        #[clap(long)]
+        reference_chain: String,
+        /// output file name
+        #[clap(short, long)]
+        out: String,
+    },
+    /// check the signature of a file signed with --sign-key, e.g. of a manifest
+    VerifySignature {
+        /// the signed file; its signature is read from the file of the same name with the .sig extension
+        file: String,
+    },
+    /// check that the files listed in a dataset manifest written with --dataset-json exist and haven't changed since
+    VerifyDataset {
+        /// the dataset manifest, e.g. dataset.json
+        file: String,
+    },
+    /// aggregate distributions of bond lengths, CA-CA distances, phi/psi, secondary structure and H-bond energies
+    /// over all the chains listed in the manifest
+    Stats {
+        /// output file: a CSV table or, when its name ends with .json, a JSON object
+        #[clap(short, long, default_value = "stats.csv")]
+        out: String,
+    },
+    /// fit a quick gradient-boosted baseline predicting a target column from the other features over a sample of
+    /// the chains listed in the manifest, and report feature importances
+    ImportanceProbe {
+        /// predicted column, e.g. ca_geometry.0, or ss=<code> for an indicator of a secondary structure type, e.g. ss=E
+        #[clap(short, long)]
+        target: String,
+        /// comma-separated feature columns used as predictors; all but the target by default
+        #[clap(short, long, value_delimiter = ',')]
+        features: Vec<String>,
+        /// number of residues sampled from the dataset
+        #[clap(long, default_value = "100000")]
+        sample: usize,
+        /// number of boosted trees
+        #[clap(long, default_value = "50")]
+        trees: usize,
+        /// depth of each tree
+        #[clap(long, default_value = "3")]
+        depth: usize,
+        /// output table of feature importances
+        #[clap(short, long, default_value = "importance.tsv")]
+        out: String,
+    },
+    /// check outputs of earlier runs found under a directory: their schema and feature versions, truncated files,
+    /// values out of range and the number of records against the input deposits; a PASS or FAIL line is written per chain
+    Validate {
+        /// directory searched for .meta and .dat files of featurized chains
+        dir: String,
+        /// output file with the report
+        #[clap(short, long, default_value = "validation.tsv")]
+        out: String,
+    },
+    /// write a minimal PDB or mmCIF file of a featurized chain: CA atoms of its .dat file (or heavy atoms of its .atoms
+    /// file, when written), with helices and strands of its secondary structure, e.g. to check its indexing in PyMOL
+    RebuildPdb {
+        /// output root name of the chain, e.g. out/2gb1_A, or its .dat file
+        root: String,
+        /// output file: mmCIF when its name ends with .cif, PDB otherwise
+        #[clap(short, long, default_value = "rebuilt.pdb")]
+        out: String,
+    },
+    /// merge manifests and failure reports of the jobs of a run split with --shard; the merged manifest is written to --manifest
+    MergeSummaries {
+        /// manifests written by the jobs
+        #[clap(required = true)]
+        manifests: Vec<String>,
+        /// failure reports written by the jobs
+        #[clap(long, num_args = 1..)]
+        failures: Vec<String>,
+        /// output file with the merged failure report
+        #[clap(long, default_value = "failures.tsv")]
+        failures_out: String,
+    },
+    /// pack outputs of all the chains listed in the manifest into sharded tar or zip archives, WebDataset-style
+    Pack {
+        /// format of the archives
+        #[clap(long, value_enum, default_value = "tar")]
+        format: ArchiveFormat,
+        /// number of chains stored in a single shard
+        #[clap(long, default_value_t = DEFAULT_SHARD_SIZE)]
+        shard_size: usize,
+        /// prefix of the shard names, e.g. shards/dataset gives shards/dataset-00000.tar; the index is written to <prefix>.index.tsv
+        #[clap(short, long, default_value = "dataset")]
+        out: String,
+    },
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about = SHORT_HELP, long_about = None, after_long_help = LONG_AFTER_HELP)]
-struct Args {
-    /// a single CIF or PDB file to process
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+/// Settings shared by all the subcommands; they may be given before or after the subcommand name
+#[derive(clap::Args, Debug)]
+struct CommonArgs {
+    /// configuration file with key = value settings, e.g. microenvironment.shells = 4, 6, 8 or hse.radii = 8, 13
+    #[clap(long, global = true)]
+    config: Option<String>,
+    /// manifest file listing the chains of a dataset: written by featurize, sync and update, read by the other subcommands
+    #[clap(long, default_value = "manifest.tsv", global = true)]
+    manifest: String,
+    /// seed of the random number generator used to corrupt CA traces, to sample residues for importance-probe
+    /// and to sample inputs with --sample
+    #[clap(long, default_value = "0", global = true)]
+    seed: u64,
+    /// don't show the progress bar when processing a list of files
+    #[clap(short, long, global = true)]
+    quiet: bool,
+    /// number of parsed deposits kept in memory, so chains of the same deposit listed in separate lines are parsed once
+    #[clap(long, default_value = "4", global = true)]
+    deposit_cache: usize,
+    /// write log messages to this file instead of the standard error; the level is set by RUST_LOG, info by default
+    #[clap(long, global = true)]
+    log_file: Option<String>,
+    /// format of log messages: text lines or a JSON object per line; both give the time and the worker of a message
+    #[clap(long, value_enum, default_value = "text", global = true)]
+    log_format: LogFormat,
+}
+
+/// Replacement of obsolete entries of a list file by those that superseded them
+#[derive(clap::Args, Debug)]
+struct ObsoleteArgs {
+    /// file mapping obsolete PDB IDs of the list file to the entries that superseded them, e.g. the wwPDB obsolete.dat,
+    /// which are featurized instead
+    #[clap(long)]
+    obsolete_map: Option<String>,
+    /// ask the RCSB which entry superseded a PDB ID of the list file whose deposit can't be found
+    #[clap(long)]
+    resolve_obsolete: bool,
+}
+
+/// Structures featurized by the `featurize` subcommand
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// a single CIF or PDB file to process; use - to read it from the standard input
     #[clap(short, long,  short='i')]
     input_file: Option<String>,
-    /// select chain to process from the input file provided with -i option
+    /// select chains to process from the input file provided with -i option, e.g. A or A,B,C
     #[clap(short, long,  short='c')]
     select_chain: Option<String>,
+    /// root name of output files for the input given with -i option; use - to print features to the standard output
+    #[clap(short, long, conflicts_with = "input_dir")]
+    output: Option<String>,
     /// file with a list of PDB IDs
     #[clap(short, long,  short='l')]
     list_file: Option<String>,
+    /// featurize all the structure files found under this directory
+    #[clap(long)]
+    input_dir: Option<String>,
+    /// featurize only this many entries of the list file (or files of --input-dir), selected at random with --seed
+    #[clap(long)]
+    sample: Option<usize>,
+    /// featurize only the K-th of N slices of the list file (or files of --input-dir), e.g. 0/8, so that N independent jobs
+    /// cover the list once; entries are assigned by a stable hash of their PDB ID, after --sample
+    #[clap(long, conflicts_with = "input_file")]
+    shard: Option<Shard>,
+    /// pattern of file names searched under --input-dir, relative to that directory
+    #[clap(long, default_value = "**/*.cif*", requires = "input_dir")]
+    glob: String,
     /// path to the folder with mmCIF files
     #[clap(short, long, default_value = "", short='p')]
     path: String,
+    /// print the chains that would be featurized, those whose outputs already exist and deposits that can't be found,
+    /// without parsing any structure; chain filters (resolution, length, release date etc.) are not applied
+    #[clap(long)]
+    dry_run: bool,
+    #[clap(flatten)]
+    obsolete: ObsoleteArgs,
+}
+
+/// Features and outputs of featurized chains, shared by `featurize`, `sync` and `update`
+#[derive(clap::Args, Debug)]
+struct FeatureArgs {
+    /// featurize all polymer chains of deposits given without a chain ID
+    #[clap(long)]
+    all_chains: bool,
+    /// featurize only one of identical chains of a deposit (e.g. of a homooligomer): the one with the fewest missing residues
+    #[clap(long)]
+    dedup_identical_chains: bool,
+    /// for huge deposits (ribosomes, viral capsids): stream input files keeping only the atoms of the featurized chain, which
+    /// is then parsed alone, and build pair features in chunks; other chains give no context and deposits aren't cached
+    #[clap(long)]
+    low_memory: bool,
+    /// write residue contacts defined as atom:cutoff, e.g. CB:8.0, to a .contacts file
+    #[clap(long)]
+    contacts: Option<ContactSpec>,
+    /// write contacts as a binary matrix rather than a list of pairs
+    #[clap(long, requires = "contacts")]
+    contacts_matrix: bool,
+    /// write N, C and O deviations from an ideal backbone built on the CA trace for the given secondary structure
+    #[clap(long)]
+    residual_targets: bool,
+    /// representation of backbone training targets: Cartesian deviations (as with --residual-targets), internal coordinates or both
+    #[clap(long, value_enum)]
+    targets: Option<TargetRepresentation>,
+    /// write a rigid-body frame (from N, CA, C atoms) of every residue, given in the global frame or relative to the previous residue
+    #[clap(long, value_enum)]
+    frames: Option<FrameReference>,
+    /// write frame rotations as 3x3 matrices rather than quaternions
+    #[clap(long, requires = "frames")]
+    frames_as_matrix: bool,
+    /// rebuild missing backbone O atoms and set distorted peptide bonds to the ideal length before features are computed;
+    /// repaired residues are flagged in two extra columns
+    #[clap(long)]
+    repair_backbone: bool,
+    /// write the model input features (one-hot residue types, CA-trace geometry and CA-trace breaks) as the first columns;
+    /// CA-only traces are accepted, with the secondary structure of residues lacking backbone atoms written as '-'
+    #[clap(long)]
+    inference_features: bool,
+    /// write CA-trace geometry: CA(i)-CA(i+1) distance, CA planar angle and CA pseudo-torsion
+    #[clap(long)]
+    ca_geometry: bool,
+    /// write the Ramachandran basin of every residue: alpha, beta, left-handed alpha, polyproline II or outlier
+    #[clap(long)]
+    rama_regions: bool,
+    /// write backbone phi and psi angles
+    #[clap(long)]
+    torsions: bool,
+    /// also write bin indexes of phi and psi, for the full circle split into that many bins (e.g. 36 bins of 10 degrees); implies --torsions
+    #[clap(long)]
+    torsion_bins: Option<usize>,
+    /// flag residues that follow a chain break (missing residues, CA-CA distance above 4.5 Å or missing backbone atoms)
+    /// and write the number of residues missing before each of them
+    #[clap(long)]
+    chain_breaks: bool,
+    /// flag the N- and C-terminal residues of the entity and write the indexes of the previous and next residues
+    /// connected by a peptide bond (-1 when none)
+    #[clap(long)]
+    connectivity: bool,
+    /// flag residues of 3-10 helices, pi-helices and beta bulges found from backbone H-bond patterns
+    #[clap(long)]
+    hbond_motifs: bool,
+    /// write the strand and sheet index of every residue and its beta bridge partners with their orientation
+    #[clap(long)]
+    sheet_topology: bool,
+    /// write the local helix axis direction, rise per residue and radius of helical residues
+    #[clap(long)]
+    helix_geometry: bool,
+    /// write sidechain chi1 - chi4 angles with missing-atom flags
+    #[clap(long)]
+    chi_angles: bool,
+    /// write changes in burial and contacts caused by truncating each residue to alanine
+    #[clap(long)]
+    alanine_scan: bool,
+    /// write the amino acid identity encoded as numbers
+    #[clap(long, value_enum)]
+    encode: Option<Encoding>,
+    /// write physicochemical scales of residue types: Kyte-Doolittle hydropathy, charge, volume and polarity
+    #[clap(long)]
+    scales: bool,
+    /// CSV table of custom scales used by --scales instead of the default ones: a `residue,<scale>,...` header and a line per residue type
+    #[clap(long, requires = "scales")]
+    scale_table: Option<String>,
+    /// folder with precomputed sequence profiles (.a3m or PSI-BLAST .pssm files) named by chain, e.g. 2gb1_A.a3m
+    #[clap(long)]
+    profiles: Option<String>,
+    /// FASTA database (e.g. a UniRef subset) searched for homologs to compute conservation and gap frequency of residues
+    #[clap(long)]
+    conservation_db: Option<String>,
+    /// maximum number of homologs aligned to compute conservation
+    #[clap(long, default_value_t = MAX_HITS, requires = "conservation_db")]
+    conservation_hits: usize,
+    /// minimum sequence identity of a homolog used to compute conservation
+    #[clap(long, default_value_t = MIN_IDENTITY, requires = "conservation_db")]
+    conservation_identity: f64,
+    /// label residues with their symmetry-equivalence group, shared by copies of the same entity in homo-oligomers
+    #[clap(long)]
+    symmetry_labels: bool,
+    /// write B-factors and occupancies of CA or all backbone atoms
+    #[clap(long, value_enum)]
+    bfactors: Option<BFactorAtoms>,
+    /// convert B-factors to Z-scores computed within each chain
+    #[clap(long, requires = "bfactors")]
+    normalize_bfactors: bool,
+    /// write pLDDT confidence of predicted models, read from the B-factor column
+    #[clap(long)]
+    plddt: bool,
+    /// write residues of AlphaFold DB models with pLDDT below this value as missing
+    #[clap(long)]
+    min_plddt: Option<f64>,
+    /// write a fingerprint of the 3D neighbourhood of every residue: atom and residue counts in spherical shells
+    #[clap(long)]
+    microenvironment: bool,
+    /// write half-sphere exposure and contact number: counts of CA atoms within spheres around CA, split by the CA-CB direction
+    #[clap(long)]
+    hse: bool,
+    /// write residue depth: distances of the CA atom and of all heavy atoms of a residue from the molecular surface of its chain
+    #[clap(long)]
+    residue_depth: bool,
+    /// write the solvent accessible surface area of every residue, its relative accessibility and a core (0), boundary (1)
+    /// or surface (2) exposure class
+    #[clap(long)]
+    exposure_classes: bool,
+    /// relative accessibility thresholds between the core and boundary and between the boundary and surface classes
+    #[clap(long, value_delimiter = ',', default_value = "0.2,0.5")]
+    exposure_thresholds: Vec<f64>,
+    /// maximum accessible areas of residue types the relative accessibility is computed with
+    #[clap(long, value_enum, default_value = "tien")]
+    max_asa: MaxAsaTable,
+    /// flag residues in crystal contacts: within 4 Å of a symmetry mate generated from the unit cell and the space group
+    #[clap(long)]
+    crystal_contacts: bool,
+    /// flag interface residues: within 5 Å of another polymer chain of the deposit (of the assembly with --assembly)
+    #[clap(long)]
+    interface_labels: bool,
+    /// file mapping predicted model chains (e.g. AF-P69905-F1-model_v4_A) to experimental structures: `<chain-id> <file> <chain>` lines;
+    /// the CA deviation from the experimental structure is written as a label
+    #[clap(long)]
+    agreement: Option<String>,
+    /// folder with per-residue electron density map quality (RSR, RSCC) tables named by PDB code, e.g. 2gb1.txt
+    #[clap(long)]
+    map_quality: Option<String>,
+    /// mark residues whose RSCC is below the given value as poorly supported by the density
+    #[clap(long, requires = "map_quality")]
+    min_rscc: Option<f64>,
+    /// flag residues embedded in a lipid bilayer and write their depth, i.e. the distance from the membrane mid-plane;
+    /// the membrane is fitted as a hydrophobic slab unless --membrane-annotations is given
+    #[clap(long)]
+    membrane: bool,
+    /// folder with membranes of deposits named by PDB code, e.g. 1occ.txt, converted from OPM or PDBTM
+    #[clap(long, requires = "membrane")]
+    membrane_annotations: Option<String>,
+    /// detect disulfide bonds and salt bridges and write them to an .interactions file
+    #[clap(long)]
+    interactions: bool,
+    /// write the distance to the closest ligand or metal ion and its chemical ID for residues near ligands to a .ligands file
+    #[clap(long)]
+    ligands: bool,
+    /// write every chain as a residue graph to a .graph file: node features and peptide, H-bond and contact edges;
+    /// contact edges join residues with CA atoms closer than this distance
+    #[clap(long)]
+    graph: Option<f64>,
+    /// build the given biological assembly (mmCIF input only) and featurize chains in their oligomeric context
+    #[clap(long)]
+    assembly: Option<String>,
+    /// write gaps in the structure with the CA-CA distance between their flanking residues to a .gaps file
+    #[clap(long)]
+    gaps: bool,
+    /// SIFTS pdb_chain_uniprot.tsv table used to write UniProt accessions and residue numbers of residues to a .uniprot file
+    #[clap(long)]
+    sifts: Option<String>,
+    /// fetch UniProt mappings of every chain from the PDBe API instead of a SIFTS table
+    #[clap(long, conflicts_with = "sifts")]
+    uniprot_api: bool,
+    /// group chains of the same protein (by UniProt accession with --sifts or --uniprot-api, by entity sequence otherwise)
+    /// into conformer groups, written to the manifest and .meta files; the chains of every group are listed in this file
+    #[clap(long)]
+    conformer_groups: Option<String>,
+    /// source of the input structures, used as the namespace of record keys; detected from file names by default
+    #[clap(long, value_enum)]
+    source: Option<Source>,
+    /// featurize every alternate conformer (altloc A, B, ...) of a chain separately, as <pdb-code>_<chain>_alt<label>
+    #[clap(long)]
+    conformers: bool,
+    /// write corrupted CA-trace variants with Gaussian noise of this standard deviation (in Å) added to coordinates
+    #[clap(long)]
+    ca_noise: Option<f64>,
+    /// fraction of residues randomly masked in every corrupted variant
+    #[clap(long)]
+    mask_fraction: Option<f64>,
+    /// remove a random loop of up to this many residues from every corrupted variant
+    #[clap(long)]
+    loop_length: Option<usize>,
+    /// number of corrupted variants written for every chain
+    #[clap(long, default_value = "1")]
+    variants: usize,
+    /// crop every chain into overlapping windows of this many residues, written to a .crops file
+    #[clap(long)]
+    crop: Option<usize>,
+    /// shift between consecutive crop windows; half of the window length by default
+    #[clap(long, requires = "crop")]
+    stride: Option<usize>,
+    /// split chains spanning more entity positions than this at low-contact linkers; parts are written to a .parts file
+    #[clap(long)]
+    split_longer_than: Option<usize>,
+    /// number of positions a part extends over a split point on both sides
+    #[clap(long, default_value = "20", requires = "split_longer_than")]
+    split_overlap: usize,
+    /// skip malformed atom records (logged and counted in the manifest) instead of failing the whole deposit
+    #[clap(long)]
+    lenient: bool,
+    /// detect backbone H-bonds and contacts also to other chains of the deposit; partners are written as e.g. B:47
+    #[clap(long)]
+    inter_chain: bool,
+    #[clap(flatten)]
+    filters: ChainFilters,
+    #[clap(flatten)]
+    hbonds: HBondPolicy,
+    /// write .dat files and side outputs compressed, e.g. as 2gb1_A.dat.zst; .meta files are left as plain text
+    #[clap(long, value_enum)]
+    compress: Option<Compression>,
+    /// write the records of a chain at once (chain) or flush every record as soon as it's formatted (residue)
+    #[clap(long, value_enum, default_value = "chain")]
+    flush: FlushPolicy,
+    /// residue numbering of the records: author (auth_seq_id) or mmCIF label (label_seq_id, the position in the entity sequence)
+    #[clap(long, value_enum, default_value = "auth")]
+    numbering: Numbering,
+    /// records written for a chain: residues only (residue) or also every heavy atom, with the features of its residue,
+    /// to an .atoms file (atom)
+    #[clap(long, value_enum, default_value = "residue")]
+    granularity: Granularity,
+    /// how secondary structure is assigned: DSSP, STRIDE-like (DSSP H-bonds confirmed by backbone torsions) or P-SEA-like
+    /// from CA atoms only, which also assigns CA traces
+    #[clap(long, value_enum, default_value = "dssp")]
+    ss_method: SsMethod,
+    /// which chains are featurized by the polymer type of their entity: protein chains only, or also nucleic acid and
+    /// hybrid chains, whose nucleotides are written with their C4' atom in place of CA
+    #[clap(long, value_enum, default_value = "protein")]
+    polymer: PolymerPolicy,
+    /// what to do with a chain whose observed residues disagree with its entity (SEQRES) sequence: report the mismatching
+    /// residues (warn), also write them as missing (mask) or reject the chain
+    #[clap(long, value_enum, default_value = "warn")]
+    on_mismatch: MismatchPolicy,
+    /// move every chain into a canonical frame before writing coordinates: centered at the centroid of its CA atoms,
+    /// also rotated onto their principal axes, or placed in the backbone frame of its first residue
+    #[clap(long, value_enum, default_value = "none")]
+    center_frame: CenterFrame,
+    /// encrypt all output files of featurized chains for this age recipient (an age1... public key); may be repeated
+    #[clap(long)]
+    encrypt_to: Vec<String>,
+    /// sign the manifest with the Ed25519 key stored in this file (its 32-byte seed as hex digits)
+    #[clap(long)]
+    sign_key: Option<String>,
+    /// output format; Parquet tables are written in addition to the text files, which the manifest refers to
+    #[clap(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+    /// directory of the Parquet tables written with --format parquet
+    #[clap(long, default_value = "parquet")]
+    parquet_dir: String,
+    /// number of residues stored in a single Parquet part file
+    #[clap(long, default_value = "1000000")]
+    rows_per_file: usize,
+    /// file listing chains that couldn't be featurized, with the reason; written as JSON when the name ends with .json
+    #[clap(long, default_value = "failures.tsv")]
+    failures: String,
+    /// also write chains that failed with an error as a list file, which may be given back with -l to retry only them
+    #[clap(long)]
+    failed_out: Option<String>,
+    /// write a dataset manifest, listing every output file with its SHA-256 checksum, the featurizer build and a hash of
+    /// the settings, to this JSON file (e.g. dataset.json); checksums are of files before --encrypt-to
+    #[clap(long)]
+    dataset_json: Option<String>,
+    /// write time spent in every stage (parsing, H-bond map, secondary structure, features, writing) per input file and
+    /// in total to this tab-separated file; the totals are also logged
+    #[clap(long)]
+    profile_timings: Option<String>,
+    /// list chains in the manifest from the easiest to the most difficult one, for curriculum learning
+    #[clap(long)]
+    sort_by_difficulty: bool,
 }
 
-fn find_deposit_files(list_file: &str, path: &str) -> Vec<(String, Option<String>)> {
+/// Finds deposit files of chains listed in a file; returns the files found, along with chain selections, PDB codes
+/// whose files can't be found and obsolete PDB codes replaced by the files of their superseding entries.
+/// When `sample` is given, only that many entries of the file, selected at random, are used.
+fn find_deposit_files(list_file: &str, path: &str, sample: Option<(usize, u64)>, obsolete: Option<&ObsoleteEntries>)
+        -> (Vec<(String, Option<String>)>, Vec<String>, HashMap<String, String>) {
 
     let reader = open_file(list_file).expect(&format!("Can't open {} file!", list_file));
     let lines: Vec<Vec<String>> = read_whitespace_delimited_values(reader).expect("Can't parse a flat text file!");
     debug!("Loading a list-file: {}", list_file);
+    let mut lines: Vec<Vec<String>> = lines.into_iter().filter(|l| !l.is_empty() && !l[0].is_empty() && !l[0].starts_with('#')).collect();
+    if let Some((n, seed)) = sample {
+        let n_entries = lines.len();
+        lines = sample_entries(lines, n, seed);
+        info!("{} of {} entries of {} sampled with seed {}", lines.len(), n_entries, list_file, seed);
+    }
+    let locate = |code: &str| find_cif_file_name(code, path).or_else(|_| find_pdb_file_name(code, path)).ok();
     let mut input_files: Vec<(String, Option<String>)> = Vec::new();
+    let mut missing: Vec<String> = vec![];
+    let mut superseded: HashMap<String, String> = HashMap::new();
     for line in lines {
-        if line.len() < 1 { continue; }
-        if line[0].len() < 1 || line[0].starts_with("#") { continue; }
-        let (pdb_code, chain_id) = code_and_chain(&line[0]);
-        if let Ok(cif_fname) = find_cif_file_name(&pdb_code, path) {
-            input_files.push((cif_fname, chain_id));
+        // --- several chains may be given as 1abcAB or 1abc:A,B
+        let (pdb_code, chain_id) = match line[0].split_once(':') {
+            Some((code, chains)) => (code.to_string(), Some(chains.to_string())),
+            None => code_and_chain(&line[0]),
+        };
+        if let Some(fname) = locate(&pdb_code) {
+            input_files.push((fname, chain_id));
             continue;
         }
-        if let Ok(pdb_fname) = find_pdb_file_name(&pdb_code, path) {
-            input_files.push((pdb_fname, chain_id));
+        // --- an obsolete entry is replaced by the one that superseded it
+        if let Some(code) = obsolete.and_then(|o| o.resolve(&pdb_code, |c| locate(c).is_some())) {
+            info!("{} is obsolete, superseded by {}", &pdb_code, &code);
+            let fname = locate(&code).unwrap();
+            superseded.insert(fname.clone(), pdb_code.to_lowercase());
+            input_files.push((fname, chain_id));
             continue;
         }
         warn!("Can't find a PDB file for the following PDB ID: {:?}!\nSpecify folder with --path option", &pdb_code);
+        missing.push(pdb_code);
     }
     info!("{} input files found in {}",input_files.len(), list_file);
 
-    return input_files;
-}
-
-fn process_deposit(fname: &str, chain: &str, out_fname: &str) -> Result<(), PDBError> {
-
-    let deposit = Deposit::from_file(fname)?;
-    let mut strctr = deposit.structure();
-    strctr.remove_ligands();
-    let strctr = Structure::from_iterator(&strctr.id_code, strctr.atoms().iter().filter(|a| a.chain_id == chain));
-    if strctr.atoms().len() < 1 {
-        return Err(NoSuchChain { chain_id: chain.to_string() });
-    }
-    let entity_id = &strctr.atoms()[0].entity_id;
-    let entity = deposit.entity(entity_id);
-    // ResidueType objects for all residues in the entity; some of them are gaps
-    let entity_resids = entity.chain_monomers(chain)?;
-    // ResidueIDs for all residues in the chain; it may have fewer residues than in the entity (because of gaps)
-    let chain_resids = strctr.residue_ids();
-    let mut i_res_idx = 0;
-    let hbonds = BackboneHBondMap::new(&strctr);
-    let mut outfile = out_writer(out_fname, false);
-    for res in entity_resids {
-        if res.parent_type==StandardResidueType::GAP {
-            writeln!(outfile, "{:^4} {}", '-', res)?;
+    return (input_files, missing, superseded);
+}
+
+/// Obsolete PDB entries to be replaced by their superseding ones, as given by --obsolete-map and --resolve-obsolete
+fn obsolete_entries(args: &ObsoleteArgs) -> Result<Option<ObsoleteEntries>, FeaturizerError> {
+    let mut entries = match &args.obsolete_map {
+        Some(fname) => ObsoleteEntries::from_file(fname)?,
+        None if args.resolve_obsolete => ObsoleteEntries::default(),
+        None => return Ok(None),
+    };
+    entries.query_rcsb = args.resolve_obsolete;
+
+    Ok(Some(entries))
+}
+
+/// Records in the summaries which obsolete PDB entries their chains were featurized in place of
+fn record_superseded(summaries: &mut [ChainSummary], superseded: &HashMap<String, String>) {
+    for s in summaries.iter_mut() { s.superseded = superseded.get(&s.input).cloned(); }
+}
+
+/// Finds all the files matching a glob pattern under a directory, e.g. `**/*.cif*`
+fn find_files_in_dir(dir: &str, pattern: &str) -> Vec<String> {
+    let full_pattern = Path::new(dir).join(pattern);
+    let mut files: Vec<String> = match glob(full_pattern.to_str().unwrap()) {
+        Ok(paths) => paths.filter_map(|p| p.ok()).filter(|p| p.is_file())
+            .map(|p| p.to_str().unwrap().to_string()).collect(),
+        Err(e) => {
+            error!("Incorrect glob pattern {}: {}", pattern, e);
+            vec![]
+        }
+    };
+    files.sort();
+    info!("{} input files found in {}", files.len(), dir);
+
+    files
+}
+
+/// Chains of a deposit to featurize: those selected in a list file or with `-c` (e.g. `A,B`), otherwise all polymer chains
+/// with `--all-chains` or the only polymer chain of a deposit (e.g. of an AlphaFold DB model)
+fn deposit_chains(deposit: &Deposit, selection: Option<&String>, all_chains: bool) -> Vec<String> {
+    if let Some(selection) = selection { return selected_chains(deposit, selection); }
+    let chains = polymer_chain_ids(deposit);
+    if all_chains || chains.len() == 1 { chains } else { vec![] }
+}
+
+/// Applies filters to every chain of a list file and writes which of them pass
+fn triage(list_file: &str, path: &str, filters: &ChainFilters, deposit_cache: usize, out: &str) -> Result<(), FeaturizerError> {
+    let mut results: Vec<TriageResult> = vec![];
+    let mut cache = DepositCache::new(deposit_cache);
+    for (fname, chain) in find_deposit_files(list_file, path, None, None).0 {
+        let loaded = match cache.get(&fname, &FeaturizerConfig::default()) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                results.push(TriageResult { input: fname.clone(), chain: "-".to_string(), result: Err(e) });
+                continue;
+            }
+        };
+        for chain in deposit_chains(&loaded.deposit, chain.as_ref(), true) {
+            let result = deposit_chain_statistics(&loaded.deposit, &fname, &chain, filters).map(|stats| filters.violations(&stats));
+            results.push(TriageResult { input: fname.clone(), chain, result });
+        }
+    }
+    write_triage(&results, out)?;
+
+    let n_passed = results.iter().filter(|r| r.result.as_ref().map_or(false, |v| v.is_empty())).count();
+    info!("{} of {} chains pass the filters, results written to {}", n_passed, results.len(), out);
+    let mut first_failing: BTreeMap<&str, usize> = BTreeMap::new();
+    for violations in results.iter().filter_map(|r| r.result.as_ref().ok()) {
+        if let Some(v) = violations.first() { *first_failing.entry(v.criterion).or_default() += 1; }
+    }
+    for (criterion, n) in first_failing { info!("--{}: the main reason of {} failures", criterion, n); }
+
+    Ok(())
+}
+
+/// Creates feature extractors selected by command line options, in the order their columns are written
+fn selected_extractors(args: &FeatureArgs, settings: &Config) -> Result<Vec<Box<dyn FeatureExtractor>>, String> {
+    let mut extractors: Vec<Box<dyn FeatureExtractor>> = vec![];
+    let targets = args.targets.or(args.residual_targets.then_some(TargetRepresentation::Cartesian));
+    if let Some(targets) = targets {
+        if targets.has_cartesian() || args.residual_targets { extractors.push(Box::new(ResidualTargets)); }
+        if targets.has_internal() { extractors.push(Box::new(InternalCoordinates)); }
+    }
+    if let Some(reference) = args.frames {
+        extractors.push(Box::new(ResidueFrames { reference, as_matrix: args.frames_as_matrix }));
+    }
+    if args.ca_geometry { extractors.push(Box::new(CaTraceGeometry)); }
+    if args.rama_regions { extractors.push(Box::new(RamachandranRegions)); }
+    if args.torsions || args.torsion_bins.is_some() {
+        if args.torsion_bins == Some(0) { return Err("--torsion-bins must be positive".to_string()); }
+        extractors.push(Box::new(TorsionAngles { bins: args.torsion_bins }));
+    }
+    if args.chain_breaks { extractors.push(Box::new(ChainBreaks)); }
+    if args.connectivity { extractors.push(Box::new(Connectivity)); }
+    if args.hbond_motifs { extractors.push(Box::new(HBondMotifs)); }
+    if args.sheet_topology { extractors.push(Box::new(SheetTopologyFeatures)); }
+    if args.helix_geometry { extractors.push(Box::new(HelixGeometry)); }
+    if args.chi_angles { extractors.push(Box::new(ChiAngles)); }
+    if args.alanine_scan { extractors.push(Box::new(AlanineScan)); }
+    if let Some(encoding) = args.encode { extractors.push(Box::new(ResidueEncoding { encoding })); }
+    if args.scales {
+        let scales = args.scale_table.as_ref().map_or(Ok(PropertyScales::default()), |f| PropertyScales::from_csv(f))?;
+        extractors.push(Box::new(scales));
+    }
+    if let Some(dir) = &args.profiles { extractors.push(Box::new(SequenceProfiles { dir: dir.clone() })); }
+    if let Some(fname) = &args.conservation_db {
+        let database = SequenceDatabase::from_fasta(fname)?;
+        info!("{} sequences read from {}", database.len(), fname);
+        extractors.push(Box::new(SequenceConservation { database, max_hits: args.conservation_hits, min_identity: args.conservation_identity }));
+    }
+    if args.symmetry_labels { extractors.push(Box::new(SymmetryEquivalence)); }
+    if let Some(atoms) = args.bfactors { extractors.push(Box::new(BFactors { atoms, normalize: args.normalize_bfactors })); }
+    if args.plddt { extractors.push(Box::new(Plddt)); }
+    if args.microenvironment { extractors.push(Box::new(MicroEnvironment::from_config(settings)?)); }
+    if args.hse { extractors.push(Box::new(HalfSphereExposure::from_config(settings)?)); }
+    if args.residue_depth { extractors.push(Box::new(ResidueDepth::default())); }
+    if args.exposure_classes { extractors.push(Box::new(SolventExposure::new(&args.exposure_thresholds, args.max_asa)?)); }
+    if args.crystal_contacts { extractors.push(Box::new(CrystalContacts::from_config(settings)?)); }
+    if args.interface_labels { extractors.push(Box::new(InterfaceResidues)); }
+    if let Some(fname) = &args.agreement { extractors.push(Box::new(PredictionAgreement::from_file(fname)?)); }
+    if let Some(dir) = &args.map_quality {
+        extractors.push(Box::new(MapQualityFeatures { dir: dir.clone(), min_rscc: args.min_rscc }));
+    }
+    if args.membrane { extractors.push(Box::new(MembraneFeatures::from_config(settings, args.membrane_annotations.clone())?)); }
+    if args.repair_backbone { extractors.push(Box::new(RepairFlags)); }
+    // --- the model input layout comes first; e.g. training targets requested along with it follow
+    if args.inference_features {
+        let mut layout = inference_extractors();
+        let names: Vec<&str> = layout.iter().map(|e| e.info().name).collect();
+        layout.extend(extractors.into_iter().filter(|e| !names.contains(&e.info().name)));
+        extractors = layout;
+    }
+
+    Ok(extractors)
+}
+
+fn augmentation_from_args(args: &FeatureArgs, seed: u64) -> Option<Augmentation> {
+    if args.ca_noise.is_none() && args.mask_fraction.is_none() && args.loop_length.is_none() { return None; }
+    Some(Augmentation { n_variants: args.variants, ca_noise: args.ca_noise.unwrap_or(0.0),
+        mask_fraction: args.mask_fraction.unwrap_or(0.0), loop_length: args.loop_length.unwrap_or(0), seed })
+}
+
+fn crop_from_args(args: &FeatureArgs) -> Option<Crop> {
+    args.crop.map(|length| Crop { length, stride: args.stride.unwrap_or((length / 2).max(1)) })
+}
+
+fn config_from_args(common: &CommonArgs, args: &FeatureArgs, settings: &Config) -> Result<FeaturizerConfig, String> {
+    let uniprot = match &args.sifts {
+        Some(fname) => Some(UniProtMapping::from_sifts(fname).map_err(|e| e.to_string())?),
+        None => args.uniprot_api.then_some(UniProtMapping::Api),
+    };
+    Ok(FeaturizerConfig { uniprot, conformer_groups: args.conformer_groups.is_some(), contacts: args.contacts.clone(), contacts_matrix: args.contacts_matrix,
+        interactions: args.interactions, ligands: args.ligands, gaps: args.gaps, graph: args.graph, assembly: args.assembly.clone(), inter_chain: args.inter_chain,
+        augmentation: augmentation_from_args(args, common.seed), crop: crop_from_args(args),
+        split: args.split_longer_than.map(|max_length| LinkerSplit { max_length, overlap: args.split_overlap }),
+        lenient: args.lenient, compression: args.compress, flush: args.flush, numbering: args.numbering, granularity: args.granularity, low_memory: args.low_memory, ss_method: args.ss_method, polymer: args.polymer, center_frame: args.center_frame, hbonds: args.hbonds.clone(), repair: args.repair_backbone, inference: args.inference_features, on_mismatch: args.on_mismatch, conformer: None, min_plddt: args.min_plddt, filters: args.filters.clone(), source: args.source,
+        extractors: selected_extractors(args, settings)? })
+}
+
+/// Root name of the outputs of a chain, when `n_chains` chains of a file are featurized; `output` is the root name
+/// given with -o, which names the outputs of a single input file. Otherwise outputs are named after the input file
+/// and placed in `out_dir`; an empty name or `.` stands for the working directory.
+fn chain_root(output: Option<&str>, out_dir: &str, fname: &str, chain: &str, n_chains: usize) -> String {
+    let file_root = if fname == STDIN { "stdin" }
+        else { Path::new(&fname).file_name().unwrap().to_str().unwrap().split(".").next().unwrap() };
+    match output {
+        Some(output) if n_chains == 1 || output == STDOUT => output.to_string(),
+        Some(output) => format!("{}_{}", output, chain),
+        None => in_dir(out_dir, &format!("{}_{}", file_root, chain)),
+    }
+}
+
+/// Path of a file in a directory; an empty name or `.` stands for the working directory, so the name is left as it is
+fn in_dir(dir: &str, fname: &str) -> String {
+    if dir.is_empty() || dir == "." { fname.to_string() } else { Path::new(dir).join(fname).to_string_lossy().to_string() }
+}
+
+/// Prints what a run would do, without parsing any structure: a `status input chain output` line for every chain,
+/// where the status is `todo`, `exists` (outputs of the chain are already there), `unknown` (no atoms of the chain
+/// are found in its deposit file) or `missing` (the deposit file can't be found).
+///
+/// A chain selection is expanded by the chain IDs of the atom records, as the actual run does; chains of a deposit
+/// given without a chain ID are known only once it's parsed, so they are printed as `-`. Chain filters are applied
+/// by the actual run, since they need the structures
+fn print_plan(output: Option<&str>, input_files: &[(String, Option<String>)], missing: &[String]) {
+    let (mut n_todo, mut n_exists, mut n_unknown) = (0, 0, 0);
+    println!("#status\tinput\tchain\toutput");
+    for (fname, selection) in input_files {
+        let Some(selection) = selection.as_deref().filter(|s| !s.trim().is_empty()) else {
+            println!("todo\t{}\t-\t-", fname);
+            n_todo += 1;
             continue;
+        };
+        // --- a file that can't be read gives no chain IDs, so every selected chain is reported as unknown;
+        // the standard input is read by the actual run only
+        let known = (fname != STDIN).then(|| atom_chain_ids(fname).unwrap_or_default());
+        let chains = select_chain_ids(known.as_deref().unwrap_or(&[]), selection);
+        for chain in &chains {
+            let root = chain_root(output, "", fname, chain, chains.len());
+            let status = if known.as_ref().is_some_and(|k| !k.contains(chain)) { n_unknown += 1; "unknown" }
+                else if output_files(&root).is_empty() { n_todo += 1; "todo" } else { n_exists += 1; "exists" };
+            println!("{}\t{}\t{}\t{}", status, fname, chain, root);
         }
-        if i_res_idx >= chain_resids.len() {
-            return Err(PDBError::ResidueNotDefined { residue_index: i_res_idx});
+    }
+    for code in missing { println!("missing\t{}\t-\t-", code); }
+    info!("dry run: {} chains to featurize, {} with existing outputs, {} unknown chains, {} deposits missing", n_todo, n_exists,
+        n_unknown, missing.len());
+}
+
+/// Featurizes chains of a file in the low-memory mode: the file is streamed once to find its chains and once more for
+/// every chain, which is parsed alone; returns summaries of featurized chains and the list of failures
+fn featurize_streamed(args: &FeatureArgs, output: Option<&str>, out_dir: &str, fname: &str, selection: Option<&String>, config: &FeaturizerConfig,
+                      token: &CancellationToken) -> (Vec<ChainSummary>, Vec<Failure>) {
+    let failure = |chain: &str, kind: String, message: String| Failure { input: fname.to_string(), chain: chain.to_string(), kind, message };
+    let known = match atom_chain_ids(fname) {
+        Ok(ids) => ids,
+        Err(e) => {
+            let error = FeaturizerError::from(e);
+            return (vec![], vec![failure(selection.map_or("-", |s| s.as_str()), error.kind(), error.to_string())]);
         }
-        let i_res = &chain_resids[i_res_idx];
-        if let Ok(ca) = strctr.atom(i_res, " CA ") {
-            let ss_code = strctr.residue_secondary(&i_res)?.hec_code();
-            write!(outfile, "{:4} {:7} {} : {} {:8.3} {:8.3} {:8.3}", i_res_idx, res, i_res, ss_code as char, ca.pos.x, ca.pos.y, ca.pos.z)?;
-            for (j_res_idx, j_res) in chain_resids.iter().enumerate() {
-                if let Some(hb) = hbonds.h_bond(i_res, j_res) {
-                    write!(outfile, " {:4} {:.3}", j_res_idx, hb.dssp_energy())?;
-                }
-                if let Some(hb) = hbonds.h_bond(j_res, i_res) {
-                    write!(outfile, " {:4} {:.3}", j_res_idx, hb.dssp_energy())?;
+    };
+    let chains = match selection {
+        Some(selection) => select_chain_ids(&known, selection),
+        None if args.all_chains || known.len() == 1 => known,
+        None => return (vec![], vec![failure("-", "NoChainId".to_string(), "chain ID not given".to_string())]),
+    };
+    if args.dedup_identical_chains || args.conformers { warn!("--dedup-identical-chains and --conformers are ignored with --low-memory"); }
+    let (mut summaries, mut failures) = (vec![], vec![]);
+    for chain in &chains {
+        match featurize_chain(fname, chain, &chain_root(output, out_dir, fname, chain, chains.len()), config, token) {
+            Ok(summary) => summaries.push(summary),
+            Err(error) => failures.push(failure(chain, error.kind(), error.to_string())),
+        }
+    }
+
+    (summaries, failures)
+}
+
+/// Featurizes chains of the given files; returns summaries of featurized chains and the list of failures
+fn featurize_files(common: &CommonArgs, args: &FeatureArgs, output: Option<&str>, out_dir: &str, input_files: &[(String, Option<String>)],
+                   config: &mut FeaturizerConfig, token: &CancellationToken) -> (Vec<ChainSummary>, Vec<Failure>) {
+    let mut summaries: Vec<ChainSummary> = vec![];
+    let progress = progress_bar(input_files.len(), common.quiet || input_files.len() < 2);
+    let mut failures: Vec<Failure> = vec![];
+    // --- a deposit is parsed once for all its chains, also when they are given in separate lines of a list file
+    let mut cache = DepositCache::new(common.deposit_cache);
+    for (fname, chain) in input_files {
+        if config.low_memory {
+            let (featurized, failed) = featurize_streamed(args, output, out_dir, fname, chain.as_ref(), config, token);
+            for f in &failed { progress.suspend(|| error!("Can't process {}; reason: {}", fname, f.message)); }
+            summaries.extend(featurized);
+            failures.extend(failed);
+            progress.set_prefix(format!("failed: {}", failures.len()));
+            progress.inc(1);
+            continue;
+        }
+        let stage = Instant::now();
+        let loaded = match cache.get(fname, config) {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                progress.suspend(|| error!("Can't process {}; reason: {}", fname, error));
+                failures.push(Failure { input: fname.clone(), chain: chain.clone().unwrap_or("-".to_string()), kind: error.kind(),
+                    message: error.to_string() });
+                progress.set_prefix(format!("failed: {}", failures.len()));
+                progress.inc(1);
+                continue;
+            }
+        };
+        let parsing = stage.elapsed();
+        let n_before = summaries.len();
+        let mut chains = deposit_chains(&loaded.deposit, chain.as_ref(), args.all_chains);
+        if args.dedup_identical_chains {
+            let unique = representative_chains(&loaded.deposit, &chains);
+            if unique.len() < chains.len() {
+                progress.suspend(|| debug!("{}: {} of {} chains skipped as identical to others", fname, chains.len() - unique.len(), chains.len()));
+            }
+            chains = unique;
+        }
+        if chains.is_empty() {
+            failures.push(Failure { input: fname.clone(), chain: "-".to_string(), kind: "NoChainId".to_string(),
+                message: "chain ID not given".to_string() });
+            progress.suspend(|| warn!("Can't find a chain ID for the following file: {}\nuse -c or --all-chains option or provide the chain code together with PDB id in the list file", fname));
+        }
+        for chain in &chains {
+            let chain_root = chain_root(output, out_dir, fname, chain, chains.len());
+            let conformers: Vec<Option<char>> = match args.conformers {
+                false => vec![None],
+                true => match extract_chain(&loaded.deposit, chain).map(|c| altloc_labels(&c)) {
+                    Ok(labels) if !labels.is_empty() => labels.into_iter().map(Some).collect(),
+                    _ => vec![None],
+                },
+            };
+            for conformer in conformers {
+                let out_root = match conformer {
+                    Some(label) if chain_root != STDOUT => format!("{}_alt{}", chain_root, label),
+                    _ => chain_root.clone(),
+                };
+                config.conformer = conformer;
+                progress.set_message(out_root.clone());
+                match featurize_loaded_chain(&loaded, chain, &out_root, config, token) {
+                    Ok(summary) => summaries.push(summary),
+                    Err(error) => {
+                        progress.suspend(|| error!("Can't process {}; reason: {}", fname, error));
+                        failures.push(Failure { input: fname.clone(), chain: chain.clone(), kind: error.kind(), message: error.to_string() });
+                    }
                 }
             }
-            writeln!(outfile, "")?;
-        } else {
-            warn!("CA atom missing for residue: {}", i_res);
         }
+        // --- a deposit is parsed once for all its chains, so the time is counted for the first of them
+        if let Some(first) = summaries.get_mut(n_before) { first.timings.parsing += parsing; }
+        progress.set_prefix(format!("failed: {}", failures.len()));
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+    if cache.n_hits > 0 { info!("{} deposits parsed, {} times reused", cache.n_loads, cache.n_hits); }
 
-        i_res_idx += 1;
+    (summaries, failures)
+}
+
+/// Converts outputs of all the featurized chains into Parquet tables
+#[cfg(feature = "parquet")]
+fn write_parquet(summaries: &[ChainSummary], dir: &str, rows_per_file: usize) -> Result<(), FeaturizerError> {
+    let mut dataset = ParquetDataset::new(dir, rows_per_file);
+    for s in summaries { dataset.add_chain(s)?; }
+    dataset.finish()
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_summaries: &[ChainSummary], dir: &str, _rows_per_file: usize) -> Result<(), FeaturizerError> {
+    error!("Can't write Parquet tables to {}: the featurizer was built without the \"parquet\" feature", dir);
+    Ok(())
+}
+
+/// Encrypts outputs of featurized chains and signs the `manifest` file, as requested by --encrypt-to and --sign-key
+#[cfg(feature = "crypto")]
+fn protect_outputs(manifest: &str, args: &FeatureArgs, summaries: &[ChainSummary]) -> Result<(), FeaturizerError> {
+    if !args.encrypt_to.is_empty() {
+        let recipients = parse_recipients(&args.encrypt_to)?;
+        encrypt_outputs(summaries, &recipients)?;
+        let parquet_dir = Path::new(&args.parquet_dir);
+        if args.format == OutputFormat::Parquet && parquet_dir.exists() {
+            info!("{} Parquet files encrypted", encrypt_dir(parquet_dir, &recipients)?);
+        }
+    }
+    if let Some(key_file) = &args.sign_key {
+        sign_file(manifest, &read_signing_key(key_file)?)?;
+        info!("{} signed", manifest);
     }
     Ok(())
 }
 
-fn main() -> Result<(), PDBError> {
+#[cfg(not(feature = "crypto"))]
+fn protect_outputs(_manifest: &str, args: &FeatureArgs, _summaries: &[ChainSummary]) -> Result<(), FeaturizerError> {
+    if !args.encrypt_to.is_empty() || args.sign_key.is_some() {
+        error!("Can't encrypt outputs or sign the manifest: the featurizer was built without the \"crypto\" feature");
+    }
+    Ok(())
+}
+
+/// Checks the signature of a file and logs the key it was signed with
+#[cfg(feature = "crypto")]
+fn verify_signature(fname: &str) -> Result<(), FeaturizerError> {
+    let public_key = verify_file(fname)?;
+    info!("{} signature is correct, signed with the key {}", fname, public_key);
+    Ok(())
+}
+
+#[cfg(not(feature = "crypto"))]
+fn verify_signature(fname: &str) -> Result<(), FeaturizerError> {
+    error!("Can't verify the signature of {}: the featurizer was built without the \"crypto\" feature", fname);
+    Ok(())
+}
+
+/// Checks the files of a dataset manifest; fails when any of them is missing or has changed
+#[cfg(feature = "json")]
+fn verify_dataset_json(fname: &str) -> Result<ExitCode, FeaturizerError> {
+    let dataset = read_dataset_json(fname)?;
+    let problems = verify_dataset(&dataset);
+    for p in &problems { error!("{}", p); }
+    let n_files: usize = dataset.chains.iter().map(|c| c.files.len()).sum();
+    info!("{} of {} files listed in {} are intact (featurizer {}, {})", n_files - problems.len(), n_files, fname, dataset.featurizer_version, dataset.git_hash);
+    Ok(if problems.is_empty() { ExitCode::SUCCESS } else { ExitCode::from(EXIT_ALL_FAILED) })
+}
+
+#[cfg(not(feature = "json"))]
+fn verify_dataset_json(fname: &str) -> Result<ExitCode, FeaturizerError> {
+    error!("Can't read {}: the featurizer was built without the \"json\" feature", fname);
+    Ok(ExitCode::FAILURE)
+}
 
-    unsafe {
-        if env::var("RUST_LOG").is_err() { env::set_var("RUST_LOG", "info") }
+/// Builds a fragment library from all chains of deposits listed in a file
+fn build_fragment_library(list_file: &str, path: &str, length: usize, bin_width: f64, out: &str) -> Result<(), FeaturizerError> {
+    let mut library = FragmentLibrary::new(length, bin_width);
+    let mut n_windows = 0;
+    for (fname, chain) in find_deposit_files(list_file, path, None, None).0 {
+        let deposit = load_deposit(&fname)?;
+        for chain in deposit_chains(&deposit, chain.as_ref(), true) {
+            match extract_chain(&deposit, &chain) {
+                Ok(strctr) => n_windows += library.add_chain(&strctr, &strctr.residue_ids()),
+                Err(e) => warn!("Can't load chain {} of {}: {}", chain, fname, e),
+            }
+        }
     }
-    env_logger::init();
+    library.write(out)?;
+    info!("{} windows of {} residues stored in {} library entries, written to {}", n_windows, length, library.entries.len(), out);
+
+    Ok(())
+}
 
-    let args = Args::parse();
+/// Builds the featurizer configuration from the command line and the configuration file; panics on incorrect settings
+fn featurizer_config(common: &CommonArgs, args: &FeatureArgs) -> FeaturizerConfig {
+    let settings = common.config.as_ref().map_or(Ok(Config::default()), |f| Config::from_file(f));
+    match settings.and_then(|settings| config_from_args(common, args, &settings)) {
+        Ok(config) => config,
+        Err(e) => panic!("Incorrect settings: {}", e),
+    }
+}
+
+/// Applies a weekly PDB update to a dataset: featurizes added and modified entries, retires obsolete ones
+/// from the manifest and bumps the dataset version
+fn sync(common: &CommonArgs, args: &FeatureArgs, updates_location: &str, path: &str, version_file: &str) -> Result<ExitCode, FeaturizerError> {
+    let updates = PdbUpdates::load(updates_location)?;
+    info!("PDB update: {} entries added, {} modified, {} obsolete", updates.added.len(), updates.modified.len(), updates.obsolete.len());
+    let previous = if Path::new(&common.manifest).exists() { read_manifest(&common.manifest)? } else { vec![] };
+    let (retired, mut summaries): (Vec<ChainSummary>, Vec<ChainSummary>) = previous.into_iter().partition(|s| updates.retires(s));
+    for s in retired.iter().filter(|s| updates.obsolete.contains(&pdb_code(s))) { remove_outputs(&s.id); }
+
+    // ---------- deposits are downloaded along with update lists, otherwise they must be already in the path
     let mut input_files: Vec<(String, Option<String>)> = vec![];
+    let mut failures: Vec<Failure> = vec![];
+    for code in updates.to_featurize() {
+        let fname = if is_url(updates_location) { download_deposit(&code, path) } else {
+            find_cif_file_name(&code, path).map_err(FeaturizerError::from)
+        };
+        match fname {
+            Ok(fname) => input_files.push((fname, None)),
+            Err(error) => failures.push(Failure { input: code.clone(), chain: "-".to_string(), kind: error.kind(), message: error.to_string() }),
+        }
+    }
+    let mut config = featurizer_config(common, args);
+    let (featurized, failed) = featurize_files(common, args, None, "", &input_files, &mut config, &CancellationToken::new());
+    failures.extend(failed);
+    info!("{} chains retired, {} chains featurized", retired.len(), featurized.len());
+    let n_featurized = featurized.len();
+    let n_previous = summaries.len();
+    summaries.extend(featurized);
+
+    write_manifest(&summaries, &common.manifest)?;
+    write_failures(&failures, &args.failures)?;
+    if let Some(fname) = &args.failed_out { write_retry_list(&failures, fname)?; }
+    // --- outputs of earlier syncs are already encrypted
+    protect_outputs(&common.manifest, args, &summaries[n_previous..])?;
+    let version = bump_dataset_version(version_file)?;
+    info!("dataset version {}: {} chains listed in {}", version, summaries.len(), &common.manifest);
+
+    Ok(exit_code(n_featurized, &failures))
+}
+
+/// Updates a dataset to a new list file: featurizes only entries whose deposit files are new or have changed
+/// since the previous run, keeps outputs of unchanged entries and retires outputs of entries no longer listed,
+/// e.g. obsoleted by the PDB
+fn update(common: &CommonArgs, obsolete: &ObsoleteArgs, args: &FeatureArgs, list_file: &str, path: &str, dir: &str, inputs: &str)
+        -> Result<ExitCode, FeaturizerError> {
+    let mut config = featurizer_config(common, args);
+    let (input_files, missing, superseded) = find_deposit_files(list_file, path, None, obsolete_entries(obsolete)?.as_ref());
+    // --- deposit files are recorded by their absolute paths, so the database doesn't depend on the working directory
+    let absolute = |f: &str| fs::canonicalize(f).map(|f| f.to_string_lossy().to_string());
+    let input_files: Vec<InputEntry> = input_files.into_iter()
+        .map(|(f, c)| Ok((absolute(&f)?, c))).collect::<Result<_, std::io::Error>>()?;
+    let superseded: HashMap<String, String> = superseded.into_iter()
+        .map(|(f, code)| Ok((absolute(&f)?, code))).collect::<Result<_, std::io::Error>>()?;
+    // --- the manifest, the database and the outputs are kept in the dataset directory; other paths are relative to the working directory
+    let (inputs, manifest) = (in_dir(dir, inputs), in_dir(dir, &common.manifest));
+    let database = if Path::new(&inputs).exists() { read_input_database(&inputs)? } else { BTreeMap::new() };
+    let previous = if Path::new(&manifest).exists() { read_manifest(&manifest)? } else { vec![] };
+
+    let mut states = BTreeMap::new();
+    let mut to_featurize: Vec<InputEntry> = vec![];
+    for entry in input_files {
+        let (state, changed) = check_input(&entry.0, database.get(&entry))?;
+        if changed { to_featurize.push(entry.clone()); }
+        states.insert(entry, (state, changed));
+    }
+    let (mut summaries, retired): (Vec<ChainSummary>, Vec<ChainSummary>) = previous.into_iter()
+        .partition(|s| states.iter().any(|(entry, (_, changed))| !changed && from_entry(s, entry)));
+    for s in &retired { remove_outputs(&s.id); }
+    info!("{} entries of {} unchanged, {} to featurize, {} chains retired", states.len() - to_featurize.len(), list_file,
+        to_featurize.len(), retired.len());
+
+    let (mut featurized, mut failures) = featurize_files(common, args, None, dir, &to_featurize, &mut config, &CancellationToken::new());
+    record_superseded(&mut featurized, &superseded);
+    failures.extend(missing.iter().map(|code| Failure { input: code.clone(), chain: "-".to_string(), kind: "NotFound".to_string(),
+        message: format!("no deposit file found in {}", path) }));
+    // --- entries that failed with an error are not recorded, so the next update tries them again
+    states.retain(|entry, _| !failures.iter().any(|f| f.input == entry.0 && !f.is_filtered()));
+    let n_featurized = featurized.len();
+    let n_previous = summaries.len();
+    summaries.extend(featurized);
+
+    write_manifest(&summaries, &manifest)?;
+    write_input_database(&states.into_iter().map(|(entry, (state, _))| (entry, state)).collect(), &inputs)?;
+    write_failures(&failures, &args.failures)?;
+    if let Some(fname) = &args.failed_out { write_retry_list(&failures, fname)?; }
+    protect_outputs(&manifest, args, &summaries[n_previous..])?;
+    info!("{} chains featurized, {} chains listed in {}", n_featurized, summaries.len(), &manifest);
+
+    Ok(exit_code(n_featurized, &failures))
+}
+
+/// Featurizes the input structures and writes the manifest of the run, the list of failures and the requested side outputs
+fn featurize(common: &CommonArgs, input: &InputArgs, args: &FeatureArgs) -> Result<ExitCode, FeaturizerError> {
+    let mut input_files: Vec<(String, Option<String>)> = vec![];
+    let mut missing: Vec<String> = vec![];
+    let mut superseded: HashMap<String, String> = HashMap::new();
 
     // ---------- Load a list of PDB IDs and try to locate all the files
-    if let Some(fname) = args.list_file {
-        input_files = find_deposit_files(&fname, &args.path);
-    } else if let Some(fname) = args.input_file {
-        input_files.push((fname, args.select_chain));
+    if let Some(fname) = &input.list_file {
+        (input_files, missing, superseded) = find_deposit_files(fname, &input.path, input.sample.map(|n| (n, common.seed)),
+            obsolete_entries(&input.obsolete)?.as_ref());
+    } else if let Some(dir) = &input.input_dir {
+        input_files = find_files_in_dir(dir, &input.glob).into_iter().map(|f| (f, input.select_chain.clone())).collect();
+        if let Some(n) = input.sample { input_files = sample_entries(input_files, n, common.seed); }
+    } else if let Some(fname) = &input.input_file {
+        input_files.push((fname.clone(), input.select_chain.clone()));
     } else {
-        panic!("No input file provided! Use -i or -l options to specify an input file!");
+        panic!("No input file provided! Use -i, -l or --input-dir options to specify input files!");
+    }
+    if let Some(shard) = input.shard {
+        let n_entries = input_files.len() + missing.len();
+        input_files.retain(|(fname, _)| shard.contains(deposit_code(fname)));
+        missing.retain(|code| shard.contains(code));
+        info!("Shard {}: {} of {} entries", shard, input_files.len() + missing.len(), n_entries);
+    }
+    // --- the -o option names the outputs of a single input file
+    let output = input.output.as_deref().filter(|_| input.list_file.is_none() && input.input_dir.is_none());
+    if input.dry_run {
+        print_plan(output, &input_files, &missing);
+        return Ok(ExitCode::SUCCESS);
     }
 
-    for (fname, chain) in input_files {
-        if let Some(chain) = chain {
-            let file_root = Path::new(&fname).file_name().unwrap().to_str().unwrap().split(".").next().unwrap();
-            let out_fname = format!("{}_{}.dat", file_root, chain);
-            if let Err(error) = process_deposit(&fname, &chain, &out_fname) {
-                error!("Can't process {}; reason: {}", fname, error);
-                if let Err(err) = fs::remove_file(&out_fname) { error!("Can't remove the output file: {}", err); }
-                else { warn!("Removed the incomplete output file: {}", &out_fname); }
+    let mut config = featurizer_config(common, args);
+    let token = CancellationToken::new();
+    let (mut summaries, mut failures) = featurize_files(common, args, output, "", &input_files, &mut config, &token);
+    record_superseded(&mut summaries, &superseded);
+    failures.extend(missing.iter().map(|code| Failure { input: code.clone(), chain: "-".to_string(), kind: "NotFound".to_string(),
+        message: format!("no deposit file found in {}", &input.path) }));
+
+    // ---------- Write the manifest of this run
+    if args.sort_by_difficulty {
+        summaries.sort_by(|a, b| a.difficulty.total_cmp(&b.difficulty));
+    }
+    write_manifest(&summaries, &common.manifest)?;
+    info!("{} chains featurized, manifest written to {}", summaries.len(), &common.manifest);
+    if let Some(fname) = &args.conformer_groups {
+        write_conformer_groups(&summaries, fname)?;
+        info!("{} conformer groups written to {}", group_conformers(&summaries).len(), fname);
+    }
+    for (source, (n_chains, n_residues)) in source_statistics(&summaries) {
+        let n_failed = failures.iter().filter(|f| args.source.unwrap_or_else(|| Source::detect(&f.input)) == source).count();
+        info!("{:>6}: {} chains, {} residues featurized, {} failed", source, n_chains, n_residues, n_failed);
+    }
+    write_failures(&failures, &args.failures)?;
+    if !failures.is_empty() { warn!("{} chains failed, listed in {}", failures.len(), &args.failures); }
+    if let Some(fname) = &args.failed_out { write_retry_list(&failures, fname)?; }
+    if let Some(fname) = &args.dataset_json {
+        let dataset = dataset_manifest(&summaries, &config)?;
+        write_dataset_json(&dataset, fname)?;
+        info!("{} files of {} chains listed in {}", dataset.chains.iter().map(|c| c.files.len()).sum::<usize>(), dataset.chains.len(), fname);
+    }
+    if let Some(fname) = &args.profile_timings {
+        let total = write_timings(&summaries, fname)?;
+        let stages: Vec<String> = StageTimings::STAGES.iter().zip(total.stages()).map(|(name, d)| format!("{} {:.2} s", name, d.as_secs_f64())).collect();
+        info!("time spent: {}; per file timings written to {}", stages.join(", "), fname);
+    }
+    if args.format == OutputFormat::Parquet { write_parquet(&summaries, &args.parquet_dir, args.rows_per_file)?; }
+    protect_outputs(&common.manifest, args, &summaries)?;
+
+    Ok(exit_code(summaries.len(), &failures))
+}
+
+/// Code of a deposit file, i.e. its name up to the first dot, e.g. `2gb1` for `pdb_mirror/2gb1.cif.gz`
+fn deposit_code(fname: &str) -> &str {
+    let file_name = Path::new(fname).file_name().and_then(|n| n.to_str()).unwrap_or(fname);
+    file_name.split('.').next().unwrap_or(file_name)
+}
+
+/// Merges manifests and failure reports of the jobs of a sharded run
+fn merge_summaries(manifests: &[String], failures: &[String], manifest_out: &str, failures_out: &str) -> Result<(), FeaturizerError> {
+    let summaries = merge_manifests(manifests)?;
+    write_manifest(&summaries, manifest_out)?;
+    let mut merged: Vec<Failure> = vec![];
+    for fname in failures { merged.extend(read_failures(fname)?); }
+    write_failures(&merged, failures_out)?;
+    info!("{} chains of {} manifests merged into {}, {} failures into {}", summaries.len(), manifests.len(), manifest_out, merged.len(), failures_out);
+    for (source, (n_chains, n_residues)) in source_statistics(&summaries) {
+        info!("{:>6}: {} chains, {} residues", source, n_chains, n_residues);
+    }
+
+    Ok(())
+}
+
+/// Assigns chains listed in a manifest to cross-validation folds and writes the manifest back with their fold indexes;
+/// with `fold_dirs` the outputs of every fold are moved into its own `fold_<k>` directory
+fn split(manifest: &str, n_folds: usize, clusters: Option<&str>, fold_dirs: bool) -> Result<(), FeaturizerError> {
+    let mut summaries = read_manifest(manifest)?;
+    let clusters = clusters.map(load_clusters).transpose()?.unwrap_or_default();
+    let ids: Vec<String> = summaries.iter().map(|s| s.id.clone()).collect();
+    for (summary, fold) in summaries.iter_mut().zip(assign_folds(&ids, &clusters, n_folds)) {
+        summary.fold = Some(fold);
+        if fold_dirs {
+            let dir = format!("fold_{}", fold);
+            summary.id = move_outputs(&summary.id, &dir)?;
+            summary.output = moved_path(&summary.output, &dir);
+        }
+    }
+    write_manifest(&summaries, manifest)?;
+    info!("{} chains of {} assigned to {} folds", summaries.len(), manifest, n_folds);
+
+    Ok(())
+}
+
+/// Downloads deposits listed in a file into a folder; those already found there are kept unless `force` is set
+fn fetch(list_file: &str, path: &str, force: bool) -> Result<ExitCode, FeaturizerError> {
+    let reader = open_file(list_file).expect(&format!("Can't open {} file!", list_file));
+    let lines: Vec<Vec<String>> = read_whitespace_delimited_values(reader).expect("Can't parse a flat text file!");
+    let mut codes: Vec<String> = lines.iter().filter(|l| !l.is_empty() && !l[0].is_empty() && !l[0].starts_with('#'))
+        .map(|l| l[0].split_once(':').map_or_else(|| code_and_chain(&l[0]).0, |(code, _)| code.to_string()).to_lowercase())
+        .collect();
+    codes.sort();
+    codes.dedup();
+    let (mut n_downloaded, mut n_found) = (0, 0);
+    let mut failures: Vec<Failure> = vec![];
+    for code in &codes {
+        if !force && find_cif_file_name(code, path).is_ok() {
+            n_found += 1;
+            continue;
+        }
+        match download_deposit(code, path) {
+            Ok(_) => n_downloaded += 1,
+            Err(error) => {
+                error!("Can't download {}; reason: {}", code, error);
+                failures.push(Failure { input: code.clone(), chain: "-".to_string(), kind: error.kind(), message: error.to_string() });
             }
-        } else {
-            warn!("Can't find a chain ID for the following file: {}\nuse -c option together with -i or provide the chain code together with PDB id in the list file", fname);
         }
     }
+    info!("{} deposits downloaded to {}, {} already there, {} failed", n_downloaded, path, n_found, failures.len());
+
+    Ok(exit_code(n_downloaded + n_found, &failures))
+}
 
-    return Ok(());
-}
\ No newline at end of file
+/// Exit status of a run that featurized `n_featurized` chains: success when no chain failed with an error
+/// (chains rejected by filters don't count), [`EXIT_PARTIAL_FAILURE`] when some did and [`EXIT_ALL_FAILED`] when nothing was featurized
+fn exit_code(n_featurized: usize, failures: &[Failure]) -> ExitCode {
+    if failures.iter().all(|f| f.is_filtered()) { ExitCode::SUCCESS }
+    else if n_featurized > 0 { ExitCode::from(EXIT_PARTIAL_FAILURE) }
+    else { ExitCode::from(EXIT_ALL_FAILED) }
+}
+
+/// Creates a progress bar showing the number of files processed and failed, the current chain, throughput and ETA
+fn progress_bar(n_files: usize, hidden: bool) -> ProgressBar {
+    if hidden { return ProgressBar::hidden(); }
+    let progress = ProgressBar::new(n_files as u64);
+    progress.set_style(ProgressStyle::with_template("{elapsed_precise} [{bar:40}] {pos}/{len} {prefix} ({per_sec}, ETA {eta}) {msg}")
+        .unwrap().progress_chars("=> "));
+    progress.set_prefix("failed: 0");
+    progress
+}
+
+fn main() -> Result<ExitCode, FeaturizerError> {
+
+    let cli = Cli::parse();
+    let common = &cli.common;
+    init_logging(common.log_file.as_deref(), common.log_format)?;
+    match &cli.command {
+        Command::Featurize { input, features } => featurize(common, input, features),
+        Command::Split { folds, clusters, fold_dirs } => {
+            split(&common.manifest, *folds, clusters.as_deref(), *fold_dirs)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Fetch { list_file, path, force } => fetch(list_file, path, *force),
+        Command::DescribeFeatures { json } => {
+            describe_features(json.as_deref())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Triage { list_file, path, filters, out } => {
+            triage(list_file, path, filters, common.deposit_cache, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ChainPair { first, first_chain, second, second_chain, cutoff, out } => {
+            let first = load_chain(first, first_chain)?;
+            let second = load_chain(second, second_chain)?;
+            write_chain_pair(&first, &second, *cutoff, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Diff { first, first_chain, second, second_chain, out } => {
+            let first = load_chain(first, first_chain)?;
+            let second = load_chain(second, second_chain)?;
+            write_chain_diff(&first, &second, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::FragmentLibrary { list_file, path, length, bin_width, out } => {
+            build_fragment_library(list_file, path, *length, *bin_width, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Sync { updates, path, dataset_version, features } => sync(common, features, updates, path, dataset_version),
+        Command::Update { list_file, path, dir, inputs, obsolete, features } => update(common, obsolete, features, list_file, path, dir, inputs),
+        Command::Evaluate { model, model_chain, reference, reference_chain, out } => {
+            let eval = evaluate_model(&load_chain(model, model_chain)?, &load_chain(reference, reference_chain)?)?;
+            info!("{} residues aligned, backbone RMSD: {:.3}", eval.residues.len(), eval.backbone_rmsd);
+            write_evaluation(&eval, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::VerifySignature { file } => {
+            verify_signature(file)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::VerifyDataset { file } => verify_dataset_json(file),
+        Command::Stats { out } => {
+            let stats = dataset_stats(&read_manifest(&common.manifest)?)?;
+            info!("{} chains and {} residues of {} aggregated", stats.n_chains, stats.n_residues, &common.manifest);
+            write_stats(&stats, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ImportanceProbe { target, features, sample, trees, depth, out } => {
+            let data = sample_residues(&read_manifest(&common.manifest)?, &ProbeTarget::parse(target), features, *sample, common.seed)?;
+            let params = ProbeParams { n_trees: *trees, max_depth: *depth, ..Default::default() };
+            let report = importance_probe(&data, &params);
+            info!("R2 of the baseline predicting {}: {:.3} on {} held-out residues", target, report.r2_holdout, report.n_holdout);
+            for (name, importance) in report.importances.iter().take(10) { info!("{:<20} {:.4}", name, importance); }
+            write_report(&report, out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Validate { dir, out } => {
+            let reports = validate_dir(dir);
+            write_validation(&reports, out)?;
+            let n_passed = reports.iter().filter(|r| r.passed()).count();
+            info!("{} of {} chains found in {} passed the validation, report written to {}", n_passed, reports.len(), dir, out);
+            if n_passed == reports.len() { Ok(ExitCode::SUCCESS) }
+            else if n_passed > 0 { Ok(ExitCode::from(EXIT_PARTIAL_FAILURE)) }
+            else { Ok(ExitCode::from(EXIT_ALL_FAILED)) }
+        }
+        Command::RebuildPdb { root, out } => {
+            let root = output_root(root);
+            let chain = rebuild_chain(root)?;
+            if out.ends_with(".cif") {
+                let name = Path::new(root).file_name().and_then(|n| n.to_str()).unwrap_or("rebuilt");
+                write_cif(&chain, name, out)?;
+            } else { write_pdb(&chain, out)?; }
+            let n_atoms: usize = chain.residues.iter().map(|r| r.atoms.len()).sum();
+            info!("{} residues and {} atoms of {} written to {}", chain.residues.len(), n_atoms, root, out);
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::MergeSummaries { manifests, failures, failures_out } => {
+            merge_summaries(manifests, failures, &common.manifest, failures_out)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Pack { format, shard_size, out } => {
+            let index = pack_dataset(&read_manifest(&common.manifest)?, *format, *shard_size, out)?;
+            write_index(&index, &format!("{}.index.tsv", out))?;
+            info!("{} chains of {} packed, index written to {}.index.tsv", index.len(), &common.manifest, out);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
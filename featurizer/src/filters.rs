@@ -0,0 +1,203 @@
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::{Deposit, PDBError, ResidueId, Structure};
+use clap::Args;
+
+use crate::chains::{load_deposit, polymer_structure, select_chain, source_chain};
+use crate::errors::FeaturizerError;
+use crate::plddt::residue_plddt;
+use crate::quality::{geometry_quality, GeometryQuality};
+use crate::release::{release_date, ReleaseDate};
+use crate::remote::{entry_code, RemoteMetadata, DEFAULT_INTERVAL_MS};
+use crate::sources::Source;
+
+/// Criteria a chain must meet to be featurized
+#[derive(Args, Clone, Debug, Default)]
+pub struct ChainFilters {
+    /// skip deposits solved at resolution worse than this value (in Å); deposits without resolution always pass,
+    /// unless it's found with --remote-metadata
+    #[clap(long)]
+    pub max_resolution: Option<f64>,
+    /// skip chains with fewer observed residues
+    #[clap(long)]
+    pub min_length: Option<usize>,
+    /// skip chains with more observed residues
+    #[clap(long)]
+    pub max_length: Option<usize>,
+    /// skip chains with a larger fraction of entity residues missing in the structure
+    #[clap(long)]
+    pub max_gap_fraction: Option<f64>,
+    /// skip AlphaFold DB models with lower average pLDDT
+    #[clap(long)]
+    pub min_mean_plddt: Option<f64>,
+    /// skip chains with more pairs of non-bonded heavy atoms closer than 1.9 Å
+    #[clap(long)]
+    pub max_clashes: Option<usize>,
+    /// skip chains with more backbone bonds deviating from their ideal length by over 3 standard deviations
+    #[clap(long)]
+    pub max_bond_outliers: Option<usize>,
+    /// skip chains with more residues outside of the allowed Ramachandran regions
+    #[clap(long)]
+    pub max_rama_outliers: Option<usize>,
+    /// skip deposits released on or after this date (YYYY-MM-DD); deposits without a release date always pass
+    #[clap(long)]
+    pub released_before: Option<ReleaseDate>,
+    /// skip deposits released before this date (YYYY-MM-DD); deposits without a release date always pass
+    #[clap(long)]
+    pub released_after: Option<ReleaseDate>,
+    /// look up the resolution and release date the filters need but a deposit file doesn't give in the RCSB API,
+    /// caching the responses in this directory
+    #[clap(long)]
+    pub remote_metadata: Option<String>,
+    /// minimum interval between two RCSB lookups, in milliseconds (200 by default)
+    #[clap(long, requires = "remote_metadata")]
+    pub remote_interval: Option<u64>,
+}
+
+/// Chain properties the filters are applied to
+#[derive(Clone, Debug)]
+pub struct ChainStatistics {
+    pub n_residues: usize,
+    pub n_observed: usize,
+    pub resolution: Option<f64>,
+    /// average pLDDT, given for AlphaFold DB models only
+    pub mean_plddt: Option<f64>,
+    /// backbone geometry checks, given only when any of the filters needs them
+    pub quality: Option<GeometryQuality>,
+    /// date of the initial release of the deposit, given only when any of the filters needs it
+    pub release_date: Option<ReleaseDate>,
+}
+
+impl ChainStatistics {
+    /// fraction of the entity sequence that wasn't observed; 0 for an empty entity
+    pub fn gap_fraction(&self) -> f64 {
+        if self.n_residues == 0 { return 0.0; }
+        self.n_residues.saturating_sub(self.n_observed) as f64 / self.n_residues as f64
+    }
+}
+
+/// A criterion a chain doesn't meet
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// name of the command line option that defines the criterion, e.g. `max-resolution`
+    pub criterion: &'static str,
+    pub value: f64,
+    pub threshold: f64,
+}
+
+impl Violation {
+    /// How far the value is from the threshold, relative to the threshold
+    pub fn severity(&self) -> f64 { ((self.value - self.threshold) / self.threshold).abs() }
+
+    /// The value and the threshold as given on the command line: dates as `YYYY-MM-DD`, other values with two decimals
+    pub fn formatted(&self) -> (String, String) {
+        if self.criterion.starts_with("released-") {
+            (ReleaseDate::from_number(self.value).to_string(), ReleaseDate::from_number(self.threshold).to_string())
+        } else { (format!("{:.2}", self.value), self.threshold.to_string()) }
+    }
+}
+
+impl ChainFilters {
+    /// True when the filters check backbone geometry, which has to be computed for every chain
+    pub fn checks_geometry(&self) -> bool {
+        self.max_clashes.is_some() || self.max_bond_outliers.is_some() || self.max_rama_outliers.is_some()
+    }
+
+    /// True when the filters check the release date, which has to be read from the deposit file again
+    pub fn checks_release_date(&self) -> bool { self.released_before.is_some() || self.released_after.is_some() }
+
+    /// Fills in the resolution and the release date the filters need when the deposit file doesn't give them, by looking
+    /// the entry up in the RCSB (see [`RemoteMetadata`]) when `remote_metadata` is set; files not named by a PDB ID are left as they are
+    pub fn complete_statistics(&self, stats: &mut ChainStatistics, fname: &str) {
+        let Some(dir) = &self.remote_metadata else { return };
+        let needs_resolution = self.max_resolution.is_some() && stats.resolution.is_none();
+        let needs_release_date = self.checks_release_date() && stats.release_date.is_none();
+        if !needs_resolution && !needs_release_date { return; }
+        let Some(code) = entry_code(fname) else { return };
+        let Some(entry) = RemoteMetadata::new(dir, self.remote_interval.unwrap_or(DEFAULT_INTERVAL_MS)).entry(&code) else { return };
+        if needs_resolution { stats.resolution = entry.resolution; }
+        if needs_release_date { stats.release_date = entry.release_date; }
+    }
+
+    /// Lists all the criteria a chain doesn't meet, from the most severely violated one
+    pub fn violations(&self, stats: &ChainStatistics) -> Vec<Violation> {
+        let mut out = vec![];
+        let mut check = |criterion: &'static str, value: Option<f64>, threshold: Option<f64>, fails: fn(f64, f64) -> bool| {
+            if let (Some(value), Some(threshold)) = (value, threshold) {
+                if fails(value, threshold) { out.push(Violation { criterion, value, threshold }); }
+            }
+        };
+        check("max-resolution", stats.resolution, self.max_resolution, |v, t| v > t);
+        check("min-length", Some(stats.n_observed as f64), self.min_length.map(|l| l as f64), |v, t| v < t);
+        check("max-length", Some(stats.n_observed as f64), self.max_length.map(|l| l as f64), |v, t| v > t);
+        check("max-gap-fraction", Some(stats.gap_fraction()), self.max_gap_fraction, |v, t| v > t);
+        check("min-mean-plddt", stats.mean_plddt, self.min_mean_plddt, |v, t| v < t);
+        let quality = stats.quality.as_ref();
+        let count = |n: Option<usize>| n.map(|n| n as f64);
+        check("max-clashes", count(quality.map(|q| q.n_clashes)), count(self.max_clashes), |v, t| v > t);
+        check("max-bond-outliers", count(quality.map(|q| q.n_bond_outliers)), count(self.max_bond_outliers), |v, t| v > t);
+        check("max-rama-outliers", count(quality.map(|q| q.n_rama_outliers)), count(self.max_rama_outliers), |v, t| v > t);
+        let date = |d: Option<ReleaseDate>| d.map(|d| d.as_number());
+        check("released-before", date(stats.release_date), date(self.released_before), |v, t| v >= t);
+        check("released-after", date(stats.release_date), date(self.released_after), |v, t| v < t);
+        out.sort_by(|a, b| b.severity().total_cmp(&a.severity()));
+
+        out
+    }
+}
+
+/// Computes properties of a chain used by the filters, without featurizing it
+pub fn chain_statistics(fname: &str, chain: &str, filters: &ChainFilters) -> Result<ChainStatistics, FeaturizerError> {
+    deposit_chain_statistics(&load_deposit(fname)?, fname, chain, filters)
+}
+
+/// Computes properties of a chain of an already loaded deposit read from `fname`
+pub fn deposit_chain_statistics(deposit: &Deposit, fname: &str, chain: &str, filters: &ChainFilters) -> Result<ChainStatistics, FeaturizerError> {
+    let strctr = select_chain(&polymer_structure(deposit), chain)?;
+    let entity = deposit.entity(&strctr.atoms()[0].entity_id);
+    let n_residues = entity.chain_monomers(source_chain(chain))?.len();
+
+    let mut stats = statistics(&strctr, &strctr.residue_ids(), n_residues, deposit.resolution, Source::detect(fname), filters);
+    if filters.checks_release_date() { stats.release_date = release_date(fname)?; }
+    filters.complete_statistics(&mut stats, fname);
+
+    Ok(stats)
+}
+
+/// Computes properties of an already loaded chain; backbone geometry is checked only when `filters` need it.
+///
+/// The release date is not read here, as it isn't loaded with a deposit; see [`release_date()`].
+pub fn statistics(strctr: &Structure, resids: &[ResidueId], n_residues: usize, resolution: Option<f64>, source: Source,
+                  filters: &ChainFilters) -> ChainStatistics {
+    let mean_plddt = (source == Source::Afdb && !resids.is_empty())
+        .then(|| resids.iter().map(|r| residue_plddt(strctr, r)).sum::<f64>() / resids.len() as f64);
+    let quality = filters.checks_geometry().then(|| geometry_quality(strctr, resids));
+
+    ChainStatistics { n_residues, n_observed: resids.len(), resolution, mean_plddt, quality, release_date: None }
+}
+
+/// Result of triage of a single chain
+pub struct TriageResult {
+    pub input: String,
+    pub chain: String,
+    pub result: Result<Vec<Violation>, FeaturizerError>,
+}
+
+/// Writes triage results: `PASS` or `FAIL` for every chain, the most severely violated criterion
+/// and thresholds that would let the chain pass, e.g. `max-resolution=3.20`
+pub fn write_triage(results: &[TriageResult], fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#input\tchain\tstatus\tfirst_failing\trelaxed_thresholds")?;
+    for r in results {
+        match &r.result {
+            Ok(violations) if violations.is_empty() => writeln!(outfile, "{}\t{}\tPASS\t-\t-", r.input, r.chain)?,
+            Ok(violations) => {
+                let relaxed: Vec<String> = violations.iter().map(|v| format!("{}={}", v.criterion, v.formatted().0)).collect();
+                writeln!(outfile, "{}\t{}\tFAIL\t{}\t{}", r.input, r.chain, violations[0].criterion, relaxed.join(","))?;
+            }
+            Err(e) => writeln!(outfile, "{}\t{}\tERROR\t{}\t-", r.input, r.chain, e.kind())?,
+        }
+    }
+    Ok(())
+}
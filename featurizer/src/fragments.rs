@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+
+use crate::geometry::{dihedral_angle, distance, LocalFrame};
+use crate::ideal_backbone::MAX_CA_CA;
+
+/// First bytes of a fragment library file
+const MAGIC: &[u8; 4] = b"BBQF";
+
+/// Version of the binary format of a fragment library
+const FORMAT_VERSION: u32 = 1;
+
+/// Backbone atoms stored for every inner residue of a fragment
+const STORED_ATOMS: [&str; 3] = [" N  ", " C  ", " O  "];
+
+/// Key of a fragment: CA-CA distances between residues `i` and `j > i + 1` of a window, in bins.
+///
+/// The distance between the two terminal residues is signed by the chirality of the first four CA atoms,
+/// so for 4-residue windows the key is the classical BBQ (r13, r24, ±r14) triplet.
+pub type FragmentKey = Vec<i32>;
+
+/// Average backbone of all fragments that share a key
+#[derive(Clone, Debug)]
+pub struct FragmentEntry {
+    /// number of fragments averaged
+    pub count: u32,
+    /// N, C and O of every inner residue of a window, expressed in the [`LocalFrame`] of the central CA
+    pub atoms: Vec<Vec3>,
+}
+
+/// Lookup table from CA pseudo-geometry of a k-residue window to its backbone atoms
+#[derive(Clone, Debug)]
+pub struct FragmentLibrary {
+    /// window length; at least 4
+    pub length: usize,
+    /// width of a distance bin, in Å
+    pub bin_width: f64,
+    pub entries: BTreeMap<FragmentKey, FragmentEntry>,
+}
+
+impl FragmentLibrary {
+    pub fn new(length: usize, bin_width: f64) -> FragmentLibrary {
+        assert!(length >= 4, "fragments must span at least 4 residues");
+        FragmentLibrary { length, bin_width, entries: BTreeMap::new() }
+    }
+
+    /// Key of a window of consecutive CA positions
+    pub fn key(&self, ca: &[Vec3]) -> FragmentKey {
+        let k = ca.len();
+        let chirality = dihedral_angle(&ca[0], &ca[1], &ca[2], &ca[3]).signum();
+        let mut key = vec![];
+        for i in 0..k {
+            for j in i + 2..k {
+                let d = if i == 0 && j == k - 1 { chirality * distance(&ca[i], &ca[j]) } else { distance(&ca[i], &ca[j]) };
+                key.push((d / self.bin_width).floor() as i32);
+            }
+        }
+
+        key
+    }
+
+    /// Local frame of a window: the frame of its central CA atom
+    fn frame(&self, ca: &[Vec3]) -> LocalFrame {
+        let c = self.length / 2;
+        LocalFrame::from_ca(&ca[c - 1], &ca[c], &ca[c + 1])
+    }
+
+    /// Adds every window of a chain whose residues have all the backbone atoms and are covalently connected;
+    /// returns the number of windows added
+    pub fn add_chain(&mut self, strctr: &Structure, resids: &[ResidueId]) -> usize {
+        let mut n_added = 0;
+        for window in resids.windows(self.length) {
+            let ca: Option<Vec<Vec3>> = window.iter().map(|r| strctr.atom(r, " CA ").ok().map(|a| a.pos.clone())).collect();
+            let Some(ca) = ca else { continue };
+            if ca.windows(2).any(|p| distance(&p[0], &p[1]) > MAX_CA_CA) { continue; }
+            let atoms: Option<Vec<Vec3>> = window[1..self.length - 1].iter()
+                .flat_map(|r| STORED_ATOMS.iter().map(move |name| strctr.atom(r, name).ok().map(|a| a.pos.clone())))
+                .collect();
+            let Some(atoms) = atoms else { continue };
+            let frame = self.frame(&ca);
+            let local: Vec<Vec3> = atoms.iter().map(|p| frame.to_local(p)).collect();
+            let entry = self.entries.entry(self.key(&ca))
+                .or_insert_with(|| FragmentEntry { count: 0, atoms: vec![Vec3::new(0.0, 0.0, 0.0); local.len()] });
+            // --- running average of atom positions
+            entry.count += 1;
+            let w = 1.0 / entry.count as f64;
+            for (avg, p) in entry.atoms.iter_mut().zip(local.iter()) {
+                *avg = Vec3::new(avg.x + (p.x - avg.x) * w, avg.y + (p.y - avg.y) * w, avg.z + (p.z - avg.z) * w);
+            }
+            n_added += 1;
+        }
+
+        n_added
+    }
+
+    /// Backbone atoms of the inner residues of a window, in global coordinates, or `None` when its key is not in the library
+    pub fn lookup(&self, ca: &[Vec3]) -> Option<Vec<Vec3>> {
+        let entry = self.entries.get(&self.key(ca))?;
+        let frame = self.frame(ca);
+        Some(entry.atoms.iter().map(|p| frame.to_global(p)).collect())
+    }
+
+    /// Writes the library in a little-endian binary format: a header (magic bytes, format version, window length,
+    /// bin width, number of entries) followed by entries, each given as its key, count and local atom coordinates
+    pub fn write(&self, fname: &str) -> Result<(), PDBError> {
+        let mut out = BufWriter::new(File::create(fname)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        out.write_all(&(self.length as u32).to_le_bytes())?;
+        out.write_all(&self.bin_width.to_le_bytes())?;
+        out.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for (key, entry) in &self.entries {
+            for k in key { out.write_all(&k.to_le_bytes())?; }
+            out.write_all(&entry.count.to_le_bytes())?;
+            for p in &entry.atoms {
+                for v in [p.x, p.y, p.z] { out.write_all(&v.to_le_bytes())?; }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a library written by [`FragmentLibrary::write()`]
+    pub fn read(fname: &str) -> Result<FragmentLibrary, PDBError> {
+        let mut input = BufReader::new(File::open(fname)?);
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        let version = read_u32(&mut input)?;
+        if &magic != MAGIC || version != FORMAT_VERSION {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{} is not a fragment library", fname)).into());
+        }
+        let length = read_u32(&mut input)? as usize;
+        let mut lib = FragmentLibrary::new(length, read_f64(&mut input)?);
+        let mut n_entries = [0u8; 8];
+        input.read_exact(&mut n_entries)?;
+        let key_length = (length - 1) * (length - 2) / 2;
+        let n_atoms = (length - 2) * STORED_ATOMS.len();
+        for _ in 0..u64::from_le_bytes(n_entries) {
+            let key = (0..key_length).map(|_| read_u32(&mut input).map(|k| k as i32)).collect::<Result<FragmentKey, _>>()?;
+            let count = read_u32(&mut input)?;
+            let mut atoms = Vec::with_capacity(n_atoms);
+            for _ in 0..n_atoms { atoms.push(Vec3::new(read_f64(&mut input)?, read_f64(&mut input)?, read_f64(&mut input)?)); }
+            lib.entries.insert(key, FragmentEntry { count, atoms });
+        }
+
+        Ok(lib)
+    }
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f64(input: &mut impl Read) -> std::io::Result<f64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
@@ -0,0 +1,132 @@
+use clap::ValueEnum;
+
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{dot, LocalFrame};
+
+/// Defines the reference of a residue frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FrameReference {
+    /// frames are given in the global coordinate system of the deposit
+    Global,
+    /// each frame is given relative to the frame of the preceding residue
+    Relative,
+}
+
+/// Rotation and translation of a residue frame
+#[derive(Clone, Debug)]
+pub struct RigidTransform {
+    /// rotation matrix; its columns are the axes of the frame
+    pub rotation: [[f64; 3]; 3],
+    pub translation: [f64; 3],
+}
+
+impl RigidTransform {
+    pub fn undefined() -> RigidTransform { RigidTransform { rotation: [[f64::NAN; 3]; 3], translation: [f64::NAN; 3] } }
+
+    /// Transformation that brings the global coordinate system onto a given frame
+    pub fn from_frame(frame: &LocalFrame) -> RigidTransform {
+        let mut rotation = [[0.0; 3]; 3];
+        for (col, axis) in frame.axes.iter().enumerate() {
+            rotation[0][col] = axis.x;
+            rotation[1][col] = axis.y;
+            rotation[2][col] = axis.z;
+        }
+        RigidTransform { rotation, translation: [frame.origin.x, frame.origin.y, frame.origin.z] }
+    }
+
+    /// Transformation of the `frame` expressed in the coordinate system of the `reference` frame
+    pub fn relative(reference: &LocalFrame, frame: &LocalFrame) -> RigidTransform {
+        let mut rotation = [[0.0; 3]; 3];
+        for a in 0..3 {
+            for b in 0..3 { rotation[a][b] = dot(&reference.axes[a], &frame.axes[b]); }
+        }
+        let t = reference.to_local(&frame.origin);
+        RigidTransform { rotation, translation: [t.x, t.y, t.z] }
+    }
+
+    /// Rotation of this transformation as a unit quaternion (w, x, y, z), with non-negative w
+    pub fn quaternion(&self) -> [f64; 4] {
+        let m = &self.rotation;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = 0.5 / (trace + 1.0).sqrt();
+            [0.25 / s, (m[2][1] - m[1][2]) * s, (m[0][2] - m[2][0]) * s, (m[1][0] - m[0][1]) * s]
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            [(m[2][1] - m[1][2]) / s, 0.25 * s, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s]
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            [(m[0][2] - m[2][0]) / s, (m[0][1] + m[1][0]) / s, 0.25 * s, (m[1][2] + m[2][1]) / s]
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            [(m[1][0] - m[0][1]) / s, (m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, 0.25 * s]
+        };
+        if q[0] < 0.0 { [-q[0], -q[1], -q[2], -q[3]] } else { q }
+    }
+}
+
+/// Backbone frame of the i-th residue, if its N, CA and C atoms are all present
+pub fn residue_frame(strctr: &Structure, resid: &ResidueId) -> Option<LocalFrame> {
+    let n = strctr.atom(resid, " N  ").ok()?;
+    let ca = strctr.atom(resid, " CA ").ok()?;
+    let c = strctr.atom(resid, " C  ").ok()?;
+    Some(LocalFrame::from_backbone(&n.pos, &ca.pos, &c.pos))
+}
+
+/// Computes a rigid transformation for every residue of a chain.
+///
+/// In the [`FrameReference::Relative`] mode the first residue, as well as any residue preceded by a residue
+/// without a complete frame, is given an undefined (NaN) transformation.
+pub fn residue_transforms(strctr: &Structure, resids: &[ResidueId], reference: FrameReference) -> Vec<RigidTransform> {
+    let frames: Vec<Option<LocalFrame>> = resids.iter().map(|r| residue_frame(strctr, r)).collect();
+    let mut out = Vec::with_capacity(frames.len());
+    for i in 0..frames.len() {
+        let t = match (reference, &frames[i]) {
+            (_, None) => RigidTransform::undefined(),
+            (FrameReference::Global, Some(f)) => RigidTransform::from_frame(f),
+            (FrameReference::Relative, Some(f)) => match i.checked_sub(1).and_then(|p| frames[p].as_ref()) {
+                Some(prev) => RigidTransform::relative(prev, f),
+                None => RigidTransform::undefined(),
+            },
+        };
+        out.push(t);
+    }
+
+    out
+}
+
+/// Rigid-body frames of residues, see [`residue_transforms()`]
+#[derive(Debug)]
+pub struct ResidueFrames {
+    pub reference: FrameReference,
+    /// when true, rotations are given as 3x3 matrices rather than quaternions
+    pub as_matrix: bool,
+}
+
+impl Default for ResidueFrames {
+    fn default() -> Self { ResidueFrames { reference: FrameReference::Global, as_matrix: false } }
+}
+
+impl FeatureExtractor for ResidueFrames {
+    fn info(&self) -> FeatureInfo {
+        let shape = if self.as_matrix { "[L, 12]" } else { "[L, 7]" };
+        FeatureInfo { name: "residue_frames",
+            description: "rigid-body frame of a residue (origin at CA, X towards C, Y in the N-CA-C plane): rotation as quaternion (w, x, y, z) or a 3x3 matrix, followed by translation",
+            shape: shape.to_string(), units: "Å for translation", range: None,
+            mask: "NaN when N, CA or C is missing; in the relative mode also for the first residue and after a missing frame",
+            option: "--frames global|relative [--frames-as-matrix]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        residue_transforms(chain.structure, chain.resids, self.reference).iter().map(|t| {
+            let mut row: Vec<f64> = if self.as_matrix { t.rotation.iter().flatten().cloned().collect() } else { t.quaternion().to_vec() };
+            row.extend_from_slice(&t.translation);
+            row
+        }).collect()
+    }
+
+    /// Global frames follow the structure, relative ones don't
+    fn is_rigid_invariant(&self) -> bool { self.reference == FrameReference::Relative }
+}
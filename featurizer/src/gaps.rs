@@ -0,0 +1,146 @@
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::{PDBError, ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::distance;
+
+/// Consecutive observed residues whose CA atoms are farther apart than that are separated by a chain break
+pub const MAX_BREAK_CA_CA: f64 = 4.5;
+
+/// A run of consecutive entity residues missing in the structure
+#[derive(Clone, Debug)]
+pub struct Gap {
+    /// 0-based position of the first missing residue in the entity sequence
+    pub entity_start: usize,
+    /// number of missing residues
+    pub length: usize,
+    /// index of the observed residue preceding the gap, `None` for a gap at the N-terminus
+    pub before: Option<usize>,
+    /// index of the observed residue following the gap, `None` for a gap at the C-terminus
+    pub after: Option<usize>,
+    /// CA-CA distance between the flanking residues; NaN for terminal gaps
+    pub distance: f64,
+}
+
+/// Finds gaps of a chain.
+///
+/// `missing` flags every position of the entity sequence that is not observed in the structure;
+/// observed residues are indexed as in `resids`.
+pub fn find_gaps(missing: &[bool], strctr: &Structure, resids: &[ResidueId]) -> Vec<Gap> {
+    let mut gaps: Vec<Gap> = vec![];
+    let mut n_observed = 0;
+    for (pos, is_missing) in missing.iter().enumerate() {
+        if !is_missing {
+            n_observed += 1;
+            continue;
+        }
+        if let Some(last) = gaps.last_mut() {
+            if last.entity_start + last.length == pos { last.length += 1; continue; }
+        }
+        gaps.push(Gap { entity_start: pos, length: 1, before: n_observed.checked_sub(1), after: None, distance: f64::NAN });
+    }
+    for gap in gaps.iter_mut() {
+        let next = gap.before.map_or(0, |b| b + 1);
+        if next < resids.len() { gap.after = Some(next); }
+        let (Some(b), Some(a)) = (gap.before, gap.after) else { continue };
+        if let (Ok(ca_b), Ok(ca_a)) = (strctr.atom(&resids[b], " CA "), strctr.atom(&resids[a], " CA ")) {
+            gap.distance = distance(&ca_b.pos, &ca_a.pos);
+        }
+    }
+
+    gaps
+}
+
+/// Writes gaps, one per line: entity position, length, flanking residue indexes (`-` for terminal gaps),
+/// CA-CA distance and that distance divided by the number of peptide bonds it has to span
+pub fn write_gaps(gaps: &[Gap], out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    let index = |i: Option<usize>| i.map_or("-".to_string(), |i| i.to_string());
+    for g in gaps {
+        writeln!(outfile, "{:4} {:4} {:>4} {:>4} {:8.3} {:6.3}", g.entity_start, g.length, index(g.before), index(g.after),
+                 g.distance, g.distance / (g.length + 1) as f64)?;
+    }
+    Ok(())
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "gaps",
+        description: "segments of the entity sequence missing in the structure: start position in the entity, length, indexes of the flanking residues, CA-CA distance between them and that distance per peptide bond to bridge",
+        shape: "[n_gaps, 6]".to_string(), units: "Å", range: None,
+        mask: "'-' and NaN for gaps at chain termini", option: "--gaps", version: 1 }
+}
+
+/// Flags physical chain breaks and the number of entity residues missing before every residue.
+///
+/// A break occurs before a residue when the previous observed residue is separated from it by residues missing in
+/// the structure (GAP records of the entity), their CA atoms are more than [`MAX_BREAK_CA_CA`] apart, or a backbone atom
+/// needed for the peptide bond (C of the previous residue; N or CA of either) is missing. The first residue of a chain never follows a break.
+#[derive(Debug)]
+pub struct ChainBreaks;
+
+impl FeatureExtractor for ChainBreaks {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "chain_breaks",
+            description: "chain_break_before flag (1 when the residue is not covalently connected to the previous observed one: missing residues, CA-CA distance above 4.5 Å or missing backbone atoms) and the number of entity residues missing right before it",
+            shape: "[L, 2]".to_string(), units: "", range: None,
+            mask: "gap length is NaN when a residue or its predecessor is not aligned to the entity", option: "--chain-breaks", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        (0..chain.resids.len()).map(|i| {
+            // --- residues missing at the N-terminus precede the first residue, but don't break the chain
+            let Some(prev) = i.checked_sub(1) else { return vec![0.0, chain.entity_index[0].map_or(f64::NAN, |e| e as f64)] };
+            let gap_length = match (chain.entity_index[prev], chain.entity_index[i]) {
+                (Some(p), Some(e)) => e.saturating_sub(p + 1) as f64,
+                _ => f64::NAN,
+            };
+            vec![if bonded_to_previous(chain, i) { 0.0 } else { 1.0 }, gap_length]
+        }).collect()
+    }
+}
+
+/// True when residue `i` of a chain is covalently connected to the previous observed residue: no entity residues are
+/// missing between them, the backbone atoms of the peptide bond are present and their CA atoms are at most
+/// [`MAX_BREAK_CA_CA`] apart
+fn bonded_to_previous(chain: &ChainContext, i: usize) -> bool {
+    let Some(prev) = i.checked_sub(1) else { return false };
+    let pos = |i: usize, name: &str| chain.structure.atom(&chain.resids[i], name).ok().map(|a| &a.pos);
+    let adjacent = matches!((chain.entity_index[prev], chain.entity_index[i]), (Some(p), Some(e)) if e == p + 1);
+    match (pos(prev, " CA "), pos(i, " CA "), pos(prev, " C  "), pos(prev, " N  "), pos(i, " N  ")) {
+        (Some(ca_prev), Some(ca), Some(_), Some(_), Some(_)) => adjacent && distance(ca_prev, ca) <= MAX_BREAK_CA_CA,
+        _ => false,
+    }
+}
+
+/// Peptide-bond connectivity of residues: N- and C-terminal flags and the indexes of the connected neighbours.
+///
+/// A residue is N-terminal (C-terminal) when it's the first (last) residue of its entity sequence, i.e. its amine
+/// (carboxyl) group is free. The previous and next residues are given by their index in the chain, as in the first
+/// column of a `.dat` file, when they are covalently connected to the residue (see [`ChainBreaks`]) and -1 otherwise.
+#[derive(Debug)]
+pub struct Connectivity;
+
+impl FeatureExtractor for Connectivity {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "connectivity",
+            description: "is_N_terminal and is_C_terminal flags (first and last residue of the entity sequence), index of the previous and of the next residue connected by a peptide bond (-1 when none: chain termini, missing residues, CA-CA distance above 4.5 Å or missing backbone atoms)",
+            shape: "[L, 4]".to_string(), units: "", range: None,
+            mask: "terminal flags are NaN when a residue is not aligned to the entity", option: "--connectivity", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let n = chain.resids.len();
+        let last = chain.entity_sequence.len().checked_sub(1);
+        let flag = |on: bool| if on { 1.0 } else { 0.0 };
+        (0..n).map(|i| {
+            let prev = if bonded_to_previous(chain, i) { (i - 1) as f64 } else { -1.0 };
+            let next = if i + 1 < n && bonded_to_previous(chain, i + 1) { (i + 1) as f64 } else { -1.0 };
+            match chain.entity_index[i] {
+                Some(e) => vec![flag(e == 0), flag(Some(e) == last), prev, next],
+                None => vec![f64::NAN, f64::NAN, prev, next],
+            }
+        }).collect()
+    }
+}
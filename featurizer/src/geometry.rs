@@ -0,0 +1,103 @@
+use bioshell_pdb::calc::Vec3;
+
+pub fn sub(a: &Vec3, b: &Vec3) -> Vec3 { Vec3::new(a.x - b.x, a.y - b.y, a.z - b.z) }
+
+pub fn add(a: &Vec3, b: &Vec3) -> Vec3 { Vec3::new(a.x + b.x, a.y + b.y, a.z + b.z) }
+
+pub fn scale(a: &Vec3, f: f64) -> Vec3 { Vec3::new(a.x * f, a.y * f, a.z * f) }
+
+pub fn dot(a: &Vec3, b: &Vec3) -> f64 { a.x * b.x + a.y * b.y + a.z * b.z }
+
+pub fn cross(a: &Vec3, b: &Vec3) -> Vec3 {
+    Vec3::new(a.y * b.z - a.z * b.y, a.z * b.x - a.x * b.z, a.x * b.y - a.y * b.x)
+}
+
+pub fn norm(a: &Vec3) -> f64 { dot(a, a).sqrt() }
+
+pub fn unit(a: &Vec3) -> Vec3 { scale(a, 1.0 / norm(a)) }
+
+pub fn distance(a: &Vec3, b: &Vec3) -> f64 { norm(&sub(a, b)) }
+
+/// Planar angle a-b-c (in degrees)
+pub fn planar_angle(a: &Vec3, b: &Vec3, c: &Vec3) -> f64 {
+    let ba = sub(a, b);
+    let bc = sub(c, b);
+    let cos = dot(&ba, &bc) / (norm(&ba) * norm(&bc));
+    cos.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// Dihedral angle a-b-c-d (in degrees, from -180 to 180)
+pub fn dihedral_angle(a: &Vec3, b: &Vec3, c: &Vec3, d: &Vec3) -> f64 {
+    let b1 = sub(b, a);
+    let b2 = sub(c, b);
+    let b3 = sub(d, c);
+    let n1 = cross(&b1, &b2);
+    let n2 = cross(&b2, &b3);
+    let y = norm(&b2) * dot(&b1, &n2);
+    let x = dot(&n1, &n2);
+    y.atan2(x).to_degrees()
+}
+
+/// Position of an idealized CB atom built from backbone N, CA and C atoms, as in trRosetta
+pub fn virtual_cb(n: &Vec3, ca: &Vec3, c: &Vec3) -> Vec3 {
+    let (b, c) = (sub(ca, n), sub(c, ca));
+    let a = cross(&b, &c);
+    add(&add(&add(&scale(&a, -0.58273431), &scale(&b, 0.56802827)), &scale(&c, -0.54067466)), ca)
+}
+
+/// Places atom D given positions of A, B and C, the C-D bond length, B-C-D planar angle and A-B-C-D dihedral angle.
+///
+/// This is the NeRF (Natural Extension Reference Frame) method; angles are given in degrees.
+pub fn nerf(a: &Vec3, b: &Vec3, c: &Vec3, bond: f64, angle: f64, dihedral: f64) -> Vec3 {
+    let (angle, dihedral) = (angle.to_radians(), dihedral.to_radians());
+    let bc = unit(&sub(c, b));
+    let n = unit(&cross(&sub(b, a), &bc));
+    let m = cross(&n, &bc);
+    let d2 = [-bond * angle.cos(), bond * angle.sin() * dihedral.cos(), bond * angle.sin() * dihedral.sin()];
+    let d = add(&add(&scale(&bc, d2[0]), &scale(&m, d2[1])), &scale(&n, d2[2]));
+    add(c, &d)
+}
+
+/// Local coordinate system defined by three consecutive CA atoms.
+///
+/// The origin is placed at the central CA, the X axis runs along CA(i-1)->CA(i+1) direction,
+/// the Y axis lies in the CA(i-1), CA(i), CA(i+1) plane and points away from the bend.
+#[derive(Clone, Debug)]
+pub struct LocalFrame {
+    pub origin: Vec3,
+    pub axes: [Vec3; 3],
+}
+
+impl LocalFrame {
+    pub fn from_ca(prev: &Vec3, ca: &Vec3, next: &Vec3) -> LocalFrame {
+        let x = unit(&sub(next, prev));
+        let bisector = add(&sub(ca, prev), &sub(ca, next));
+        let y = unit(&sub(&bisector, &scale(&x, dot(&bisector, &x))));
+        let z = cross(&x, &y);
+        LocalFrame { origin: ca.clone(), axes: [x, y, z] }
+    }
+
+    /// Rigid-body frame of a residue built from its N, CA and C atoms.
+    ///
+    /// The origin is placed at CA, the X axis points towards C and the Y axis lies in the N, CA, C plane,
+    /// on the N side; this is the convention used by AlphaFold.
+    pub fn from_backbone(n: &Vec3, ca: &Vec3, c: &Vec3) -> LocalFrame {
+        let x = unit(&sub(c, ca));
+        let can = sub(n, ca);
+        let y = unit(&sub(&can, &scale(&x, dot(&can, &x))));
+        let z = cross(&x, &y);
+        LocalFrame { origin: ca.clone(), axes: [x, y, z] }
+    }
+
+    /// Expresses a global position in this local coordinate system
+    pub fn to_local(&self, p: &Vec3) -> Vec3 {
+        let v = sub(p, &self.origin);
+        Vec3::new(dot(&v, &self.axes[0]), dot(&v, &self.axes[1]), dot(&v, &self.axes[2]))
+    }
+
+    /// Converts local coordinates back into the global coordinate system
+    pub fn to_global(&self, p: &Vec3) -> Vec3 {
+        let v = add(&add(&scale(&self.axes[0], p.x), &scale(&self.axes[1], p.y)), &scale(&self.axes[2], p.z));
+        add(&self.origin, &v)
+    }
+}
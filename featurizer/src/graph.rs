@@ -0,0 +1,128 @@
+use std::fmt;
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::PDBError;
+
+use crate::features::{ChainContext, FeatureInfo};
+use crate::geometry::distance;
+use crate::hbonds::HBOND_CA_CUTOFF;
+use crate::ideal_backbone::MAX_CA_CA;
+use crate::neighbors::NeighborGrid;
+use crate::residue_encoding::{aa_index, UNKNOWN_INDEX};
+
+/// Secondary structure codes in the order of their indexes in a graph
+pub const SS_CODES: [u8; 3] = [b'H', b'E', b'C'];
+
+/// Type of an edge of a residue graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeType {
+    /// covalent connection between consecutive residues
+    Peptide,
+    /// backbone H-bond, from the donor to the acceptor
+    HBond,
+    /// CA atoms closer than the contact cutoff
+    Contact,
+}
+
+impl fmt::Display for EdgeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeType::Peptide => write!(f, "peptide"),
+            EdgeType::HBond => write!(f, "hbond"),
+            EdgeType::Contact => write!(f, "contact"),
+        }
+    }
+}
+
+/// A node of a residue graph: an observed residue of a chain
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// index of the residue type in the [`ALPHABET`](crate::residue_encoding::ALPHABET)
+    pub residue_type: usize,
+    /// index of the secondary structure code in [`SS_CODES`]
+    pub ss: usize,
+    pub ca: [f64; 3],
+}
+
+/// A typed edge between two residues, referred to by their indexes in a chain
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub i: usize,
+    pub j: usize,
+    pub kind: EdgeType,
+    /// CA-CA distance, or the N..O distance for an H-bond
+    pub distance: f64,
+    /// DSSP energy of an H-bond; NaN for other edges
+    pub energy: f64,
+}
+
+/// A chain as a graph of residues
+#[derive(Clone, Debug)]
+pub struct ChainGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// Builds the residue graph of a chain.
+///
+/// Peptide edges join consecutive residues unless the chain is broken between them; H-bond edges are directed
+/// from the donor to the acceptor; contact edges join residues at least two positions apart whose CA atoms are
+/// closer than `contact_cutoff`, each pair listed once.
+pub fn build_graph(chain: &ChainContext, contact_cutoff: f64) -> ChainGraph {
+    let (strctr, resids) = (chain.structure, chain.resids);
+    let ca: Vec<Option<&Vec3>> = resids.iter().map(|r| strctr.atom(r, " CA ").ok().map(|a| &a.pos)).collect();
+    let nodes = resids.iter().enumerate().map(|(i, r)| {
+        let residue_type = strctr.atom(r, " CA ").map_or(UNKNOWN_INDEX, |a| aa_index(&a.res_name));
+        let ss = SS_CODES.iter().position(|c| *c == chain.ss[i]).unwrap_or(2);
+        let ca = ca[i].map_or([f64::NAN; 3], |p| [p.x, p.y, p.z]);
+        Node { residue_type, ss, ca }
+    }).collect();
+
+    let grid = NeighborGrid::new(&ca.iter().map(|p| p.cloned()).collect::<Vec<_>>(), contact_cutoff.max(HBOND_CA_CUTOFF));
+    let mut edges = vec![];
+    for (i, j, d) in grid.pairs(contact_cutoff.max(MAX_CA_CA)) {
+        if j == i + 1 && d <= MAX_CA_CA {
+            edges.push(Edge { i, j, kind: EdgeType::Peptide, distance: d, energy: f64::NAN });
+        } else if j > i + 1 && d <= contact_cutoff {
+            edges.push(Edge { i, j, kind: EdgeType::Contact, distance: d, energy: f64::NAN });
+        }
+    }
+    for (i, donor) in resids.iter().enumerate() {
+        let Some(ca_i) = ca[i] else { continue };
+        for j in grid.within(ca_i, HBOND_CA_CUTOFF) {
+            let acceptor = &resids[j];
+            let Some(hb) = chain.hbonds.h_bond(donor, acceptor) else { continue };
+            let d = match (strctr.atom(donor, " N  "), strctr.atom(acceptor, " O  ")) {
+                (Ok(n), Ok(o)) => distance(&n.pos, &o.pos),
+                _ => f64::NAN,
+            };
+            edges.push(Edge { i, j, kind: EdgeType::HBond, distance: d, energy: hb.dssp_energy() });
+        }
+    }
+
+    ChainGraph { nodes, edges }
+}
+
+/// Writes a residue graph: `N` lines with node features followed by `E` lines with typed edges
+pub fn write_graph(graph: &ChainGraph, out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    writeln!(outfile, "# nodes: {} index residue_type ss x y z", graph.nodes.len())?;
+    for (i, n) in graph.nodes.iter().enumerate() {
+        writeln!(outfile, "N {:4} {:2} {} {:8.3} {:8.3} {:8.3}", i, n.residue_type, n.ss, n.ca[0], n.ca[1], n.ca[2])?;
+    }
+    writeln!(outfile, "# edges: {} i j type distance energy", graph.edges.len())?;
+    for e in &graph.edges {
+        writeln!(outfile, "E {:4} {:4} {} {:6.3} {:.3}", e.i, e.j, e.kind, e.distance, e.energy)?;
+    }
+    Ok(())
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "graph",
+        description: "residue graph: nodes with residue type index, SS index (H=0, E=1, C=2) and CA coordinates; peptide, H-bond (donor to acceptor) and CA contact edges with distance and DSSP energy",
+        shape: "[L, 5] nodes, [E, 4] edges".to_string(), units: "Å, kcal/mol", range: None,
+        mask: "NaN energy for peptide and contact edges; NaN distance for an H-bond with a missing N or O atom",
+        option: "--graph <cutoff>", version: 1 }
+}
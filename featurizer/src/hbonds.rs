@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{ResidueId, Structure};
+use clap::Args;
+
+use crate::geometry::{add, distance, planar_angle, sub, unit};
+use crate::separation::{parse_separation, separation_columns};
+
+/// Geometric parameters of a backbone N-H...O=C hydrogen bond
+#[derive(Clone, Debug)]
+pub struct HBondGeometry {
+    /// donor N to acceptor O distance
+    pub d_da: f64,
+    /// donor H to acceptor O distance
+    pub d_ha: f64,
+    /// N-H...O angle in degrees
+    pub angle_dha: f64,
+}
+
+impl HBondGeometry {
+    pub fn undefined() -> HBondGeometry { HBondGeometry { d_da: f64::NAN, d_ha: f64::NAN, angle_dha: f64::NAN } }
+}
+
+/// Position of the amide hydrogen of the i-th residue.
+///
+/// An explicit H atom is used when present in the structure; otherwise the hydrogen is placed
+/// 1.0 Å from N, in the direction opposite to the C=O bond of the preceding residue, as DSSP does.
+pub fn amide_hydrogen(strctr: &Structure, resids: &[ResidueId], i: usize) -> Option<Vec3> {
+    if let Ok(h) = strctr.atom(&resids[i], " H  ") { return Some(h.pos.clone()); }
+    if i == 0 { return None; }
+    let n = strctr.atom(&resids[i], " N  ").ok()?;
+    let c_prev = strctr.atom(&resids[i - 1], " C  ").ok()?;
+    let o_prev = strctr.atom(&resids[i - 1], " O  ").ok()?;
+    // --- the previous residue must be covalently bonded to this one
+    if distance(&n.pos, &c_prev.pos) > 2.0 { return None; }
+    let co = unit(&sub(&c_prev.pos, &o_prev.pos));
+    Some(add(&n.pos, &co))
+}
+
+/// Computes geometry of a hydrogen bond between the donor (N-H) and the acceptor (C=O) residue
+pub fn hbond_geometry(strctr: &Structure, resids: &[ResidueId], donor: usize, acceptor: usize) -> HBondGeometry {
+    let (Ok(n), Ok(o)) = (strctr.atom(&resids[donor], " N  "), strctr.atom(&resids[acceptor], " O  ")) else {
+        return HBondGeometry::undefined();
+    };
+    let d_da = distance(&n.pos, &o.pos);
+    if let Some(h) = amide_hydrogen(strctr, resids, donor) {
+        HBondGeometry { d_da, d_ha: distance(&h, &o.pos), angle_dha: planar_angle(&n.pos, &h, &o.pos) }
+    } else {
+        HBondGeometry { d_da, d_ha: f64::NAN, angle_dha: f64::NAN }
+    }
+}
+
+/// Header of the H-bond section, i.e. of a `.hbonds` file; it also starts the section printed to the standard output after `.dat` records
+pub const HBONDS_HEADER: &str = "# donor acceptor energy d_da d_ha angle_dha direction separation range";
+
+/// Direction of a backbone H-bond relative to the chain it was detected for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HBondDirection {
+    /// the acceptor follows the donor in the chain, as in antiparallel sheets
+    Forward,
+    /// the acceptor precedes the donor in the chain, as in helices
+    Backward,
+    /// the donor belongs to the featurized chain, the acceptor to another one
+    Out,
+    /// the acceptor belongs to the featurized chain, the donor to another one
+    In,
+}
+
+impl HBondDirection {
+    /// Parses a direction as written in a `.hbonds` file
+    pub fn parse(s: &str) -> Option<HBondDirection> {
+        match s {
+            "forward" => Some(HBondDirection::Forward),
+            "backward" => Some(HBondDirection::Backward),
+            "out" => Some(HBondDirection::Out),
+            "in" => Some(HBondDirection::In),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HBondDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HBondDirection::Forward => write!(f, "forward"),
+            HBondDirection::Backward => write!(f, "backward"),
+            HBondDirection::Out => write!(f, "out"),
+            HBondDirection::In => write!(f, "in"),
+        }
+    }
+}
+
+/// A backbone H-bond of a featurized chain, as written to its `.hbonds` file
+#[derive(Clone, Debug)]
+pub struct HBondPair {
+    /// index of the donor (N-H) residue, chain-qualified (e.g. `B:47`) for a residue from another chain
+    pub donor: String,
+    /// index of the acceptor (C=O) residue, given as the donor
+    pub acceptor: String,
+    /// DSSP energy, in kcal/mol
+    pub energy: f64,
+    pub d_da: f64,
+    pub d_ha: f64,
+    pub angle_dha: f64,
+    pub direction: HBondDirection,
+    /// sequence separation of the acceptor from the donor, see [`sequence_separation()`](crate::separation::sequence_separation);
+    /// `None` for an inter-chain H-bond
+    pub separation: Option<i64>,
+}
+
+impl HBondPair {
+    /// Parses a line of the H-bond section; `None` for the header and malformed lines.
+    ///
+    /// Lines written before the separation columns were introduced are read too, with no separation.
+    pub fn parse(line: &str) -> Option<HBondPair> {
+        let t: Vec<&str> = line.split_whitespace().collect();
+        let (t, separation) = match t.as_slice() {
+            [head @ .., separation, range] if head.len() == 7 => (head, parse_separation(separation, range)?),
+            head => (head, None),
+        };
+        let &[donor, acceptor, energy, d_da, d_ha, angle_dha, direction] = t else { return None };
+        let value = |v: &str| v.parse::<f64>().unwrap_or(f64::NAN);
+        Some(HBondPair { donor: donor.to_string(), acceptor: acceptor.to_string(), energy: energy.parse().ok()?,
+            d_da: value(d_da), d_ha: value(d_ha), angle_dha: value(angle_dha), direction: HBondDirection::parse(direction)?, separation })
+    }
+}
+
+impl fmt::Display for HBondPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>4} {:>4} {:.3} {:.3} {:.3} {:.1} {} {}", self.donor, self.acceptor, self.energy, self.d_da, self.d_ha, self.angle_dha,
+               self.direction, separation_columns(self.separation))
+    }
+}
+
+/// DSSP energy cutoff of a backbone H-bond, in kcal/mol
+pub const DSSP_CUTOFF: f64 = -0.5;
+
+/// DSSP never bonds residues whose CA atoms are farther apart than this, so farther pairs are not even looked up
+pub const HBOND_CA_CUTOFF: f64 = 9.0;
+
+/// Which of the detected backbone H-bonds are written to `.hbonds` files
+#[derive(Args, Clone, Debug)]
+pub struct HBondPolicy {
+    /// write only H-bonds with DSSP energy at or below this value, in kcal/mol
+    #[clap(long = "hbond-cutoff", default_value_t = DSSP_CUTOFF, allow_hyphen_values = true)]
+    pub cutoff: f64,
+    /// keep at most this many H-bonds per donor and per acceptor residue, the strongest ones; bifurcated H-bonds are kept by default
+    #[clap(long = "hbond-best")]
+    pub best: Option<usize>,
+    /// also write a .hbpairs file listing every H-bonded pair of residues once, with both of its H-bonds
+    #[clap(long = "hbond-pairs")]
+    pub pairs: bool,
+}
+
+impl Default for HBondPolicy {
+    fn default() -> Self { HBondPolicy { cutoff: DSSP_CUTOFF, best: None, pairs: false } }
+}
+
+impl HBondPolicy {
+    /// H-bonds that pass the energy cutoff and, when requested, are among the strongest ones of both their residues;
+    /// the order of H-bonds is preserved
+    pub fn apply(&self, hbonds: Vec<HBondPair>) -> Vec<HBondPair> {
+        let hbonds: Vec<HBondPair> = hbonds.into_iter().filter(|hb| hb.energy <= self.cutoff).collect();
+        let Some(best) = self.best else { return hbonds };
+        // --- the strongest H-bonds are taken first, as long as neither of their residues has enough of them already
+        let mut order: Vec<usize> = (0..hbonds.len()).collect();
+        order.sort_by(|a, b| hbonds[*a].energy.total_cmp(&hbonds[*b].energy));
+        let mut n_donated: HashMap<&str, usize> = HashMap::new();
+        let mut n_accepted: HashMap<&str, usize> = HashMap::new();
+        let mut keep = vec![false; hbonds.len()];
+        for k in order {
+            let (donor, acceptor) = (hbonds[k].donor.as_str(), hbonds[k].acceptor.as_str());
+            if n_donated.get(donor).copied().unwrap_or(0) >= best || n_accepted.get(acceptor).copied().unwrap_or(0) >= best { continue; }
+            *n_donated.entry(donor).or_insert(0) += 1;
+            *n_accepted.entry(acceptor).or_insert(0) += 1;
+            keep[k] = true;
+        }
+
+        hbonds.into_iter().zip(keep).filter_map(|(hb, keep)| keep.then_some(hb)).collect()
+    }
+}
+
+/// Header of a `.hbpairs` file
+pub const HBOND_PAIRS_HEADER: &str = "# first second first_donates second_donates energy_first_second energy_second_first separation range";
+
+/// A pair of H-bonded residues with both of its possible backbone H-bonds, given in a canonical order
+#[derive(Clone, Debug)]
+pub struct HBondResiduePair {
+    /// the residue of the pair that comes first, see [`canonical_hbond_pairs()`]
+    pub first: String,
+    pub second: String,
+    /// DSSP energy of the H-bond donated by `first` to `second`, NaN when there is none
+    pub energy_first_second: f64,
+    /// DSSP energy of the H-bond donated by `second` to `first`, NaN when there is none
+    pub energy_second_first: f64,
+    /// sequence separation of `second` from `first`; `None` for residues of different chains
+    pub separation: Option<i64>,
+}
+
+impl HBondResiduePair {
+    pub fn first_donates(&self) -> bool { !self.energy_first_second.is_nan() }
+
+    pub fn second_donates(&self) -> bool { !self.energy_second_first.is_nan() }
+}
+
+impl fmt::Display for HBondResiduePair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>4} {:>4} {} {} {:.3} {:.3} {}", self.first, self.second, self.first_donates() as u8, self.second_donates() as u8,
+               self.energy_first_second, self.energy_second_first, separation_columns(self.separation))
+    }
+}
+
+/// Sort key of a residue label: residues of the featurized chain (plain indexes) come first, then residues of the other chains
+fn label_key(label: &str) -> (&str, usize) {
+    let (chain, index) = label.rsplit_once(':').unwrap_or(("", label));
+    (chain, index.parse().unwrap_or(usize::MAX))
+}
+
+/// Merges H-bonds into pairs of residues, so that residues bonded both ways (e.g. in antiparallel sheets) are counted once.
+///
+/// The residue of a pair with the lower index comes first; a residue of the featurized chain comes before a residue
+/// of another chain, and residues of other chains are ordered by their chain-qualified labels. Pairs are sorted the same way.
+pub fn canonical_hbond_pairs(hbonds: &[HBondPair]) -> Vec<HBondResiduePair> {
+    let mut pairs: Vec<HBondResiduePair> = vec![];
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for hb in hbonds {
+        let donor_first = label_key(&hb.donor) <= label_key(&hb.acceptor);
+        let (first, second) = if donor_first { (&hb.donor, &hb.acceptor) } else { (&hb.acceptor, &hb.donor) };
+        let k = *index.entry((first.clone(), second.clone())).or_insert_with(|| {
+            let separation = if donor_first { hb.separation } else { hb.separation.map(|s| -s) };
+            pairs.push(HBondResiduePair { first: first.clone(), second: second.clone(), energy_first_second: f64::NAN,
+                energy_second_first: f64::NAN, separation });
+            pairs.len() - 1
+        });
+        // --- of bifurcated duplicates the strongest H-bond is kept
+        let energy = if donor_first { &mut pairs[k].energy_first_second } else { &mut pairs[k].energy_second_first };
+        if energy.is_nan() || hb.energy < *energy { *energy = hb.energy; }
+    }
+    pairs.sort_by(|a, b| (label_key(&a.first), label_key(&a.second)).cmp(&(label_key(&b.first), label_key(&b.second))));
+
+    pairs
+}
+
+/// Writes H-bonded residue pairs: the header followed by a line per pair
+pub fn write_hbond_pairs(pairs: &[HBondResiduePair], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{}", HBOND_PAIRS_HEADER)?;
+    for p in pairs { writeln!(out, "{}", p)?; }
+
+    Ok(())
+}
+
+/// Writes the H-bond section: its header followed by a line per H-bond
+pub fn write_hbonds(hbonds: &[HBondPair], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{}", HBONDS_HEADER)?;
+    for hb in hbonds { writeln!(out, "{}", hb)?; }
+
+    Ok(())
+}
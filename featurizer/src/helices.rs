@@ -0,0 +1,83 @@
+use bioshell_pdb::calc::Vec3;
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{add, cross, distance, dot, norm, scale, sub, unit};
+use crate::ideal_backbone::MAX_CA_CA;
+
+/// Number of local estimates averaged for a residue, centered at the residue
+pub const HELIX_WINDOW: usize = 3;
+
+/// Local helix parameters estimated at a single residue
+#[derive(Clone, Debug)]
+pub struct HelixParameters {
+    /// unit vector along the helix axis, pointing from the N- to the C-terminus
+    pub axis: Vec3,
+    /// translation along the axis per residue
+    pub rise: f64,
+    /// distance of CA atoms from the axis
+    pub radius: f64,
+}
+
+/// Helix parameters of the CA(i)-CA(i+1) step, from the bisectors of the CA(i-1)-CA(i)-CA(i+1) and CA(i)-CA(i+1)-CA(i+2) angles.
+///
+/// Bisectors of consecutive CA angles point towards the helix axis, so their cross product gives the axis direction
+/// and the angle between them gives the radius (Kahn, Comput. Chem. 1989).
+fn local_parameters(cas: &[Option<&Vec3>], i: usize) -> Option<HelixParameters> {
+    if i == 0 || i + 2 >= cas.len() { return None; }
+    let (a, b, c, d) = (cas[i - 1]?, cas[i]?, cas[i + 1]?, cas[i + 2]?);
+    if distance(a, b) > MAX_CA_CA || distance(b, c) > MAX_CA_CA || distance(c, d) > MAX_CA_CA { return None; }
+    let bisector_i = unit(&add(&sub(a, b), &sub(c, b)));
+    let bisector_j = unit(&add(&sub(b, c), &sub(d, c)));
+    let step = sub(c, b);
+    let mut axis = cross(&bisector_i, &bisector_j);
+    if norm(&axis) < 1e-6 { return None; }
+    axis = unit(&axis);
+    if dot(&axis, &step) < 0.0 { axis = scale(&axis, -1.0); }
+    let rise = dot(&step, &axis);
+    let half_sin = ((1.0 - dot(&bisector_i, &bisector_j)) / 2.0).sqrt();
+    let radius = (dot(&step, &step) - rise * rise).max(0.0).sqrt() / (2.0 * half_sin);
+
+    Some(HelixParameters { axis, rise, radius })
+}
+
+/// Helix parameters of every helical residue of a chain, averaged over [`HELIX_WINDOW`] local estimates of helical residues;
+/// `None` for residues outside of helices or too close to a chain break
+pub fn helix_parameters(chain: &ChainContext) -> Vec<Option<HelixParameters>> {
+    let cas: Vec<Option<&Vec3>> = chain.resids.iter().map(|r| chain.structure.atom(r, " CA ").ok().map(|a| &a.pos)).collect();
+    let local: Vec<Option<HelixParameters>> = (0..cas.len())
+        .map(|i| if chain.ss[i] == b'H' { local_parameters(&cas, i) } else { None }).collect();
+    let half = HELIX_WINDOW / 2;
+    (0..cas.len()).map(|i| {
+        if chain.ss[i] != b'H' { return None; }
+        let window: Vec<&HelixParameters> = local[i.saturating_sub(half)..(i + half + 1).min(cas.len())].iter().flatten().collect();
+        if window.is_empty() { return None; }
+        let n = window.len() as f64;
+        let axis = window.iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, p| add(&sum, &p.axis));
+        Some(HelixParameters { axis: unit(&axis), rise: window.iter().map(|p| p.rise).sum::<f64>() / n,
+            radius: window.iter().map(|p| p.radius).sum::<f64>() / n })
+    }).collect()
+}
+
+/// Local helix axis direction, rise per residue and radius of helical residues, see [`helix_parameters()`]
+#[derive(Debug)]
+pub struct HelixGeometry;
+
+impl FeatureExtractor for HelixGeometry {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "helix_geometry",
+            description: "local helix axis direction (a unit vector from the N- to the C-terminus), rise per residue and radius, fitted to CA atoms in a moving window",
+            shape: "[L, 5]".to_string(), units: "-, Å, Å", range: None,
+            mask: "NaN for residues outside of helices (H) and next to chain breaks",
+            option: "--helix-geometry", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        helix_parameters(chain).iter().map(|p| match p {
+            Some(p) => vec![p.axis.x, p.axis.y, p.axis.z, p.rise, p.radius],
+            None => vec![f64::NAN; 5],
+        }).collect()
+    }
+
+    /// The axis direction follows the structure
+    fn is_rigid_invariant(&self) -> bool { false }
+}
@@ -0,0 +1,106 @@
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{distance, nerf, sub, LocalFrame};
+
+// ---------- Engh & Huber backbone geometry
+pub(crate) const N_CA: f64 = 1.458;
+pub(crate) const CA_C: f64 = 1.525;
+pub(crate) const C_N: f64 = 1.329;
+pub(crate) const C_O: f64 = 1.231;
+pub(crate) const N_CA_C: f64 = 111.2;
+pub(crate) const CA_C_N: f64 = 116.2;
+pub(crate) const C_N_CA: f64 = 121.7;
+pub(crate) const CA_C_O: f64 = 120.5;
+
+/// Largest CA-CA distance between two residues that are still considered covalently connected
+pub const MAX_CA_CA: f64 = 4.2;
+
+/// Positions of N, C and O atoms of an ideal residue, expressed in the [`LocalFrame`] of its CA trace
+#[derive(Clone, Debug)]
+pub struct IdealResidue {
+    pub n: Vec3,
+    pub c: Vec3,
+    pub o: Vec3,
+}
+
+/// Builds an ideal backbone of `n_res` residues with constant `phi`, `psi` and trans peptide bonds.
+///
+/// Returns N, CA, C, O positions of every residue.
+pub fn build_ideal_backbone(phi: f64, psi: f64, n_res: usize) -> Vec<[Vec3; 4]> {
+    let n = Vec3::new(0.0, 0.0, 0.0);
+    let ca = Vec3::new(N_CA, 0.0, 0.0);
+    let c = Vec3::new(N_CA - CA_C * N_CA_C.to_radians().cos(), CA_C * N_CA_C.to_radians().sin(), 0.0);
+    let mut chain: Vec<[Vec3; 3]> = vec![[n, ca, c]];
+    for i in 1..n_res {
+        let [n0, ca0, c0] = &chain[i - 1];
+        let n = nerf(n0, ca0, c0, C_N, CA_C_N, psi);
+        let ca = nerf(ca0, c0, &n, N_CA, C_N_CA, 180.0);
+        let c = nerf(c0, &n, &ca, CA_C, N_CA_C, phi);
+        chain.push([n, ca, c]);
+    }
+    chain.iter().map(|[n, ca, c]| {
+        let o = nerf(n, ca, c, C_O, CA_C_O, psi + 180.0);
+        [n.clone(), ca.clone(), c.clone(), o]
+    }).collect()
+}
+
+/// Ideal N, C and O positions for a residue in a given secondary structure (`H`, `E` or `C`)
+///
+/// Helices and strands are built with their canonical phi, psi angles, coil residues are approximated by a polyproline II conformation.
+pub fn ideal_residue(ss_code: u8) -> IdealResidue {
+    let (phi, psi) = match ss_code {
+        b'H' => (-57.0, -47.0),
+        b'E' => (-120.0, 130.0),
+        _ => (-75.0, 145.0),
+    };
+    let bb = build_ideal_backbone(phi, psi, 3);
+    let frame = LocalFrame::from_ca(&bb[0][1], &bb[1][1], &bb[2][1]);
+    IdealResidue { n: frame.to_local(&bb[1][0]), c: frame.to_local(&bb[1][2]), o: frame.to_local(&bb[1][3]) }
+}
+
+/// Deviations of N, C and O atoms of the i-th residue from its SS-conditioned ideal positions.
+///
+/// The deviations are expressed in the local frame of the CA trace, nine values in total: (dx, dy, dz) for N, C and O.
+/// Returns `None` for terminal residues or when any of the atoms is missing or the CA trace is broken around the residue.
+pub fn residual_targets(strctr: &Structure, resids: &[ResidueId], i: usize, ss_code: u8) -> Option<[f64; 9]> {
+    if i == 0 || i + 1 >= resids.len() { return None; }
+    let ca_prev = &strctr.atom(&resids[i - 1], " CA ").ok()?.pos;
+    let ca = &strctr.atom(&resids[i], " CA ").ok()?.pos;
+    let ca_next = &strctr.atom(&resids[i + 1], " CA ").ok()?.pos;
+    if distance(ca_prev, ca) > MAX_CA_CA || distance(ca, ca_next) > MAX_CA_CA { return None; }
+
+    let frame = LocalFrame::from_ca(ca_prev, ca, ca_next);
+    let ideal = ideal_residue(ss_code);
+    let mut out = [0.0; 9];
+    for (k, (name, ideal_pos)) in [(" N  ", &ideal.n), (" C  ", &ideal.c), (" O  ", &ideal.o)].iter().enumerate() {
+        let actual = frame.to_local(&strctr.atom(&resids[i], name).ok()?.pos);
+        let d = sub(&actual, ideal_pos);
+        out[3 * k] = d.x;
+        out[3 * k + 1] = d.y;
+        out[3 * k + 2] = d.z;
+    }
+
+    Some(out)
+}
+
+/// Deviations of backbone atoms from an SS-idealized backbone, see [`residual_targets()`]
+#[derive(Debug)]
+pub struct ResidualTargets;
+
+impl FeatureExtractor for ResidualTargets {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "residual_targets",
+            description: "deviations (dx, dy, dz) of N, C and O atoms from an ideal backbone built on the CA trace for the residue's secondary structure, in the local CA frame",
+            shape: "[L, 9]".to_string(), units: "Å", range: None,
+            mask: "NaN for terminal residues, residues with missing atoms and at chain breaks",
+            option: "--residual-targets", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        (0..chain.resids.len())
+            .map(|i| residual_targets(chain.structure, chain.resids, i, chain.ss[i]).unwrap_or([f64::NAN; 9]).to_vec())
+            .collect()
+    }
+}
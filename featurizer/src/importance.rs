@@ -0,0 +1,243 @@
+//! A quick check that features carry signal: a small gradient-boosted tree model is fitted to predict a target column
+//! from the other columns over a sample of a dataset, and the importance of every feature is reported.
+//!
+//! Trees are grown on features quantized into at most [`N_BINS`] quantile bins (a missing value is a bin of its own) and
+//! fitted to residuals of the squared loss. The importance of a feature is the total loss reduction of the splits made on it,
+//! normalized to sum up to one; the coefficient of determination on held-out residues tells how much signal there is at all.
+
+use std::io::{self, BufRead, Write};
+
+use bioshell_io::out_writer;
+use log::warn;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::compression::{open_output, output_root};
+use crate::errors::FeaturizerError;
+use crate::manifest::ChainSummary;
+use crate::records::{feature_names, parse_record, read_columns};
+
+/// Maximum number of bins a feature is quantized into
+pub const N_BINS: usize = 32;
+
+/// Every that many residues of a sample are held out to evaluate the model
+const HOLDOUT_EVERY: usize = 5;
+
+/// Settings of the gradient-boosted model
+#[derive(Clone, Debug)]
+pub struct ProbeParams {
+    pub n_trees: usize,
+    pub max_depth: usize,
+    pub learning_rate: f64,
+    /// minimum number of residues in a leaf
+    pub min_leaf: usize,
+}
+
+impl Default for ProbeParams {
+    fn default() -> Self { ProbeParams { n_trees: 50, max_depth: 3, learning_rate: 0.1, min_leaf: 20 } }
+}
+
+/// Predicted variable: a feature column or, given as `ss=<code>`, an indicator of a secondary structure type
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProbeTarget {
+    Column(String),
+    SecondaryStructure(char),
+}
+
+impl ProbeTarget {
+    pub fn parse(text: &str) -> ProbeTarget {
+        match text.strip_prefix("ss=").and_then(|c| c.chars().next()) {
+            Some(code) => ProbeTarget::SecondaryStructure(code),
+            None => ProbeTarget::Column(text.to_string()),
+        }
+    }
+}
+
+/// Residues sampled from a dataset: feature values and the target value of every residue
+#[derive(Clone, Debug, Default)]
+pub struct ProbeData {
+    pub names: Vec<String>,
+    pub rows: Vec<Vec<f64>>,
+    pub target: Vec<f64>,
+}
+
+/// Samples up to `sample` residues of featurized chains, uniformly over the whole dataset.
+///
+/// `features` selects the columns used as predictors; all feature columns but the target are used when it's empty.
+/// Chains lacking any of the columns are skipped, as are residues with an undefined target value.
+pub fn sample_residues(summaries: &[ChainSummary], target: &ProbeTarget, features: &[String], sample: usize,
+                       seed: u64) -> Result<ProbeData, FeaturizerError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut data = ProbeData::default();
+    let mut n_seen = 0;
+    for s in summaries {
+        let root = output_root(&s.output);
+        let Some(columns) = read_columns(root) else {
+            warn!("{}.meta not found, {} skipped", root, s.key);
+            continue;
+        };
+        let available = feature_names(&columns);
+        let target_column = match target {
+            ProbeTarget::Column(name) => match available.iter().position(|c| c == name) {
+                Some(k) => Some(k),
+                None => { warn!("{} has no {} column, skipped", s.key, name); continue; }
+            },
+            ProbeTarget::SecondaryStructure(_) => None,
+        };
+        let names: Vec<String> = if features.is_empty() {
+            available.iter().enumerate().filter(|(k, _)| Some(*k) != target_column).map(|(_, c)| c.clone()).collect()
+        } else {
+            features.to_vec()
+        };
+        let Some(selected) = names.iter().map(|n| available.iter().position(|c| c == n)).collect::<Option<Vec<usize>>>() else {
+            warn!("{} lacks some of the selected feature columns, skipped", s.key);
+            continue;
+        };
+        if data.names.is_empty() { data.names = names; } else if data.names != names {
+            warn!("{} has different feature columns than the other chains, skipped", s.key);
+            continue;
+        }
+        for line in open_output(&s.output)?.lines() {
+            let Some(record) = parse_record(&line?, available.len()) else { continue };
+            let y = match (target, target_column) {
+                (ProbeTarget::SecondaryStructure(code), _) => if record.ss == *code { 1.0 } else { 0.0 },
+                (_, Some(k)) => record.features[k],
+                _ => f64::NAN,
+            };
+            if y.is_nan() { continue; }
+            let row: Vec<f64> = selected.iter().map(|k| record.features[*k]).collect();
+            // --- reservoir sampling keeps every residue seen so far with the same probability
+            n_seen += 1;
+            if data.rows.len() < sample {
+                data.rows.push(row);
+                data.target.push(y);
+            } else {
+                let k = rng.gen_range(0..n_seen);
+                if k < sample { data.rows[k] = row; data.target[k] = y; }
+            }
+        }
+    }
+    if data.rows.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no residues with the target value found in the dataset").into());
+    }
+
+    Ok(data)
+}
+
+/// Quantile bin edges of a feature; a value `v` falls into bin `1 + (number of edges below v)`, bin 0 holds missing values
+fn bin_edges(values: &mut Vec<f64>) -> Vec<f64> {
+    values.retain(|v| !v.is_nan());
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mut edges: Vec<f64> = (1..N_BINS).filter_map(|k| values.get(k * values.len() / N_BINS).copied()).collect();
+    edges.dedup();
+    edges
+}
+
+fn bin_of(edges: &[f64], v: f64) -> u8 { if v.is_nan() { 0 } else { 1 + edges.partition_point(|e| *e < v) as u8 } }
+
+enum Node {
+    Leaf(f64),
+    /// residues whose bin of the feature is not greater than `bin` go to the left child
+    Split { feature: usize, bin: u8, left: Box<Node>, right: Box<Node> },
+}
+
+impl Node {
+    fn predict(&self, bins: &[u8]) -> f64 {
+        match self {
+            Node::Leaf(v) => *v,
+            Node::Split { feature, bin, left, right } => if bins[*feature] <= *bin { left.predict(bins) } else { right.predict(bins) },
+        }
+    }
+}
+
+/// Grows a regression tree fitted to `residuals` of the given residues; loss reductions of splits are added to `gains`
+fn grow(bins: &[Vec<u8>], residuals: &[f64], rows: Vec<usize>, depth: usize, params: &ProbeParams, gains: &mut [f64]) -> Node {
+    let sum: f64 = rows.iter().map(|i| residuals[*i]).sum();
+    let n = rows.len() as f64;
+    let leaf = Node::Leaf(params.learning_rate * sum / n);
+    if depth == params.max_depth || rows.len() < 2 * params.min_leaf { return leaf; }
+
+    let mut best: Option<(f64, usize, u8)> = None;
+    for f in 0..gains.len() {
+        let mut hist_sum = [0.0; N_BINS + 1];
+        let mut hist_n = [0usize; N_BINS + 1];
+        for i in &rows {
+            hist_sum[bins[*i][f] as usize] += residuals[*i];
+            hist_n[bins[*i][f] as usize] += 1;
+        }
+        let (mut left_sum, mut left_n) = (0.0, 0);
+        for b in 0..N_BINS {
+            left_sum += hist_sum[b];
+            left_n += hist_n[b];
+            let right_n = rows.len() - left_n;
+            if left_n < params.min_leaf || right_n < params.min_leaf { continue; }
+            let right_sum = sum - left_sum;
+            let gain = left_sum * left_sum / left_n as f64 + right_sum * right_sum / right_n as f64 - sum * sum / n;
+            if best.map_or(true, |(g, _, _)| gain > g) { best = Some((gain, f, b as u8)); }
+        }
+    }
+    let Some((gain, feature, bin)) = best.filter(|(g, _, _)| *g > 1e-12) else { return leaf };
+    gains[feature] += gain;
+    let (left, right): (Vec<usize>, Vec<usize>) = rows.into_iter().partition(|i| bins[*i][feature] <= bin);
+    Node::Split { feature, bin, left: Box::new(grow(bins, residuals, left, depth + 1, params, gains)),
+        right: Box::new(grow(bins, residuals, right, depth + 1, params, gains)) }
+}
+
+/// Outcome of a feature importance probe
+#[derive(Clone, Debug)]
+pub struct ProbeReport {
+    /// feature names with their importances, the most important first
+    pub importances: Vec<(String, f64)>,
+    /// coefficient of determination on the residues the model was fitted to
+    pub r2_train: f64,
+    /// coefficient of determination on the held-out residues
+    pub r2_holdout: f64,
+    pub n_train: usize,
+    pub n_holdout: usize,
+}
+
+fn r2(target: &[f64], predicted: &[f64]) -> f64 {
+    let mean = target.iter().sum::<f64>() / target.len() as f64;
+    let total: f64 = target.iter().map(|y| (y - mean).powi(2)).sum();
+    let residual: f64 = target.iter().zip(predicted).map(|(y, p)| (y - p).powi(2)).sum();
+    if total > 0.0 { 1.0 - residual / total } else { f64::NAN }
+}
+
+/// Fits a gradient-boosted model to sampled residues and reports importances of their features
+pub fn importance_probe(data: &ProbeData, params: &ProbeParams) -> ProbeReport {
+    let n_features = data.names.len();
+    let edges: Vec<Vec<f64>> = (0..n_features).map(|f| bin_edges(&mut data.rows.iter().map(|r| r[f]).collect())).collect();
+    let bins: Vec<Vec<u8>> = data.rows.iter().map(|r| (0..n_features).map(|f| bin_of(&edges[f], r[f])).collect()).collect();
+    let (train, holdout): (Vec<usize>, Vec<usize>) = (0..data.rows.len()).partition(|i| i % HOLDOUT_EVERY != HOLDOUT_EVERY - 1);
+
+    let base = train.iter().map(|i| data.target[*i]).sum::<f64>() / train.len().max(1) as f64;
+    let mut predicted = vec![base; data.rows.len()];
+    let mut gains = vec![0.0; n_features];
+    for _ in 0..params.n_trees {
+        let residuals: Vec<f64> = data.target.iter().zip(&predicted).map(|(y, p)| y - p).collect();
+        let tree = grow(&bins, &residuals, train.clone(), 0, params, &mut gains);
+        for (p, b) in predicted.iter_mut().zip(&bins) { *p += tree.predict(b); }
+    }
+
+    let total: f64 = gains.iter().sum();
+    let mut importances: Vec<(String, f64)> = data.names.iter().cloned()
+        .zip(gains.iter().map(|g| if total > 0.0 { g / total } else { 0.0 })).collect();
+    importances.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let subset = |rows: &[usize], values: &[f64]| -> Vec<f64> { rows.iter().map(|i| values[*i]).collect() };
+
+    ProbeReport { importances,
+        r2_train: r2(&subset(&train, &data.target), &subset(&train, &predicted)),
+        r2_holdout: if holdout.is_empty() { f64::NAN } else { r2(&subset(&holdout, &data.target), &subset(&holdout, &predicted)) },
+        n_train: train.len(), n_holdout: holdout.len() }
+}
+
+/// Writes feature importances as a tab-separated table, preceded by `#` comment lines with the model quality
+pub fn write_report(report: &ProbeReport, fname: &str) -> Result<(), FeaturizerError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "# residues: {} train, {} held out", report.n_train, report.n_holdout)?;
+    writeln!(outfile, "# R2: {:.4} train, {:.4} held out", report.r2_train, report.r2_holdout)?;
+    writeln!(outfile, "#feature\timportance")?;
+    for (name, importance) in &report.importances { writeln!(outfile, "{}\t{:.4}", name, importance)?; }
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::ca_trace::CaTraceGeometry;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::gaps::MAX_BREAK_CA_CA;
+use crate::geometry::distance;
+use crate::residue_encoding::{Encoding, ResidueEncoding};
+
+/// Backbone atoms, other than CA, needed to assign secondary structure and to detect H-bonds
+const BACKBONE_ATOMS: [&str; 3] = [" N  ", " C  ", " O  "];
+
+/// Features of the model input tensor, in the order of its columns: one-hot residue types, CA-trace geometry and
+/// chain breaks of the trace. All of them are computed from CA atoms and the sequence only, so they are defined
+/// for CA-only models as well as for deposits, which makes training inputs identical to those given at inference.
+pub fn inference_extractors() -> Vec<Box<dyn FeatureExtractor>> {
+    vec![Box::new(ResidueEncoding { encoding: Encoding::Onehot }), Box::new(CaTraceGeometry), Box::new(TraceBreaks)]
+}
+
+/// True when a residue has all the backbone atoms needed for its secondary structure and H-bonds
+pub fn has_full_backbone(strctr: &Structure, resid: &ResidueId) -> bool {
+    BACKBONE_ATOMS.iter().all(|name| strctr.atom(resid, name).is_ok())
+}
+
+/// Masks the secondary structure of residues that lack backbone atoms: their DSSP code is replaced by `-`,
+/// since a CA trace gives no H-bonds to assign it from
+pub fn mask_secondary_structure(chain: &mut ChainContext) {
+    for (ss, resid) in chain.ss.iter_mut().zip(chain.resids) {
+        if !has_full_backbone(chain.structure, resid) { *ss = b'-'; }
+    }
+}
+
+/// Chain breaks of a CA trace: the same flag and gap length as [`ChainBreaks`](crate::gaps::ChainBreaks) give,
+/// but the connectivity is judged from the CA-CA distance alone, so a trace without other backbone atoms isn't broken
+/// at every residue
+#[derive(Debug)]
+pub struct TraceBreaks;
+
+impl FeatureExtractor for TraceBreaks {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "trace_breaks",
+            description: "chain_break_before flag (1 when the residue is not connected to the previous observed one: missing residues or CA-CA distance above 4.5 Å) and the number of entity residues missing right before it",
+            shape: "[L, 2]".to_string(), units: "", range: None,
+            mask: "gap length is NaN when a residue or its predecessor is not aligned to the entity", option: "--inference-features", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let ca = |i: usize| chain.structure.atom(&chain.resids[i], " CA ").ok().map(|a| &a.pos);
+        (0..chain.resids.len()).map(|i| {
+            let Some(prev) = i.checked_sub(1) else { return vec![0.0, chain.entity_index[0].map_or(f64::NAN, |e| e as f64)] };
+            let gap_length = match (chain.entity_index[prev], chain.entity_index[i]) {
+                (Some(p), Some(e)) => e.saturating_sub(p + 1) as f64,
+                _ => f64::NAN,
+            };
+            let connected = match (ca(prev), ca(i)) {
+                (Some(ca_prev), Some(ca)) => distance(ca_prev, ca) <= MAX_BREAK_CA_CA,
+                _ => false,
+            };
+            vec![if !connected || gap_length != 0.0 { 1.0 } else { 0.0 }, gap_length]
+        }).collect()
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::{PDBError, PdbAtom, ResidueId, Structure};
+
+use crate::features::FeatureInfo;
+use crate::geometry::distance;
+use crate::neighbors::NeighborGrid;
+
+/// Largest SG-SG distance of a disulfide bond
+pub const DISULFIDE_CUTOFF: f64 = 2.5;
+/// Largest distance between charged groups forming a salt bridge
+pub const SALT_BRIDGE_CUTOFF: f64 = 4.0;
+
+const CATIONIC: [(&str, &str); 4] = [("LYS", " NZ "), ("ARG", " NE "), ("ARG", " NH1"), ("ARG", " NH2")];
+const ANIONIC: [(&str, &str); 4] = [("ASP", " OD1"), ("ASP", " OD2"), ("GLU", " OE1"), ("GLU", " OE2")];
+
+/// Type of a residue-residue interaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteractionType {
+    Disulfide,
+    SaltBridge,
+}
+
+impl fmt::Display for InteractionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InteractionType::Disulfide => write!(f, "SS"),
+            InteractionType::SaltBridge => write!(f, "SB"),
+        }
+    }
+}
+
+/// An interaction between two residues, referred to by their indexes in a chain
+#[derive(Clone, Debug)]
+pub struct Interaction {
+    pub i: usize,
+    pub j: usize,
+    pub kind: InteractionType,
+    /// distance between the closest pair of atoms that define this interaction
+    pub distance: f64,
+}
+
+fn atoms_of<'a>(strctr: &'a Structure, resid: &ResidueId, definitions: &[(&str, &str)]) -> Vec<&'a PdbAtom> {
+    definitions.iter()
+        .filter_map(|(res_name, atom_name)| strctr.atom(resid, atom_name).ok().filter(|a| a.res_name == *res_name))
+        .collect()
+}
+
+fn closest(first: &[&PdbAtom], second: &[&PdbAtom]) -> Option<f64> {
+    first.iter().flat_map(|a| second.iter().map(|b| distance(&a.pos, &b.pos))).min_by(|a, b| a.total_cmp(b))
+}
+
+/// Detects disulfide bonds and salt bridges between residues of a chain.
+///
+/// A disulfide is recognised when SG atoms of two cysteines are closer than [`DISULFIDE_CUTOFF`];
+/// a salt bridge when any of the charged nitrogens of Lys or Arg is closer than [`SALT_BRIDGE_CUTOFF`]
+/// to a carboxyl oxygen of Asp or Glu.
+pub fn find_interactions(strctr: &Structure, resids: &[ResidueId]) -> Vec<Interaction> {
+    let sulfurs: Vec<Vec<&PdbAtom>> = resids.iter().map(|r| atoms_of(strctr, r, &[("CYS", " SG ")])).collect();
+    let cations: Vec<Vec<&PdbAtom>> = resids.iter().map(|r| atoms_of(strctr, r, &CATIONIC)).collect();
+    let anions: Vec<Vec<&PdbAtom>> = resids.iter().map(|r| atoms_of(strctr, r, &ANIONIC)).collect();
+
+    // --- only residues whose sulfur or charged atoms are close enough may interact
+    let (owners, positions): (Vec<usize>, Vec<_>) = sulfurs.iter().chain(&cations).chain(&anions)
+        .enumerate().flat_map(|(k, atoms)| atoms.iter().map(move |a| (k % resids.len(), Some(a.pos.clone())))).unzip();
+    let cutoff = DISULFIDE_CUTOFF.max(SALT_BRIDGE_CUTOFF);
+    let mut candidates: Vec<(usize, usize)> = NeighborGrid::new(&positions, cutoff).pairs(cutoff).into_iter()
+        .map(|(a, b, _)| (owners[a].min(owners[b]), owners[a].max(owners[b]))).filter(|(i, j)| i != j).collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let mut out = vec![];
+    for (i, j) in candidates {
+        if let Some(d) = closest(&sulfurs[i], &sulfurs[j]) {
+            if d <= DISULFIDE_CUTOFF { out.push(Interaction { i, j, kind: InteractionType::Disulfide, distance: d }); }
+        }
+        let salt_bridge = [closest(&cations[i], &anions[j]), closest(&anions[i], &cations[j])]
+            .into_iter().flatten().min_by(|a, b| a.total_cmp(b));
+        if let Some(d) = salt_bridge {
+            if d <= SALT_BRIDGE_CUTOFF { out.push(Interaction { i, j, kind: InteractionType::SaltBridge, distance: d }); }
+        }
+    }
+
+    out
+}
+
+/// Writes interactions as `i j type distance` lines
+pub fn write_interactions(interactions: &[Interaction], out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    for it in interactions {
+        writeln!(outfile, "{:4} {:4} {} {:6.3}", it.i, it.j, it.kind, it.distance)?;
+    }
+    Ok(())
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "interactions",
+        description: "disulfide bonds (SS) and salt bridges (SB) as (i, j, type, distance) rows",
+        shape: "[n_interactions, 4]".to_string(), units: "Å", range: Some((0.0, SALT_BRIDGE_CUTOFF)),
+        mask: "none", option: "--interactions", version: 1 }
+}
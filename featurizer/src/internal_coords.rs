@@ -0,0 +1,71 @@
+use clap::ValueEnum;
+
+use bioshell_pdb::calc::Vec3;
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{dihedral_angle, distance, planar_angle};
+use crate::repair::MAX_PEPTIDE_BOND;
+
+/// Representation of the backbone written as training targets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TargetRepresentation {
+    /// deviations of N, C and O from an ideal backbone, in the local CA frame (see `--residual-targets`)
+    Cartesian,
+    /// bond lengths, bond angles and torsions of backbone atoms, in NeRF order
+    Internal,
+    /// both representations, Cartesian first
+    Both,
+}
+
+impl TargetRepresentation {
+    pub fn has_cartesian(&self) -> bool { *self != TargetRepresentation::Internal }
+
+    pub fn has_internal(&self) -> bool { *self != TargetRepresentation::Cartesian }
+}
+
+/// Backbone of a residue: N, CA, C and O positions, `None` for missing atoms
+type Backbone = [Option<Vec3>; 4];
+
+/// Internal coordinates of the backbone, in the order NeRF places the atoms along a chain.
+///
+/// For every residue four atoms are placed, each given by its bond length, bond angle and torsion with respect to the three
+/// atoms placed before it: N from C, CA and N of the previous residue (C-N bond, CA-C-N angle, psi of the previous residue),
+/// CA from C of the previous residue and N (N-CA bond, C-N-CA angle, omega), C from N and CA (CA-C bond,
+/// N-CA-C angle, phi) and finally O from N, CA and C (C=O bond, CA-C=O angle, N-CA-C=O torsion). A backbone rebuilt
+/// with [`nerf()`](crate::geometry::nerf) from these values and the first three atoms reproduces the chain.
+#[derive(Debug)]
+pub struct InternalCoordinates;
+
+impl FeatureExtractor for InternalCoordinates {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "internal_coordinates",
+            description: "bond length, bond angle and torsion placing N, CA, C and O of a residue in NeRF order: C-N, CA-C-N, psi(i-1); N-CA, C-N-CA, omega(i-1); CA-C, N-CA-C, phi; C-O, CA-C-O, N-CA-C-O",
+            shape: "[L, 12]".to_string(), units: "Å, degrees, degrees", range: None,
+            mask: "NaN when any of the atoms a value is defined by is missing; values referring to the previous residue are NaN at chain breaks",
+            option: "--targets internal|both", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let backbone: Vec<Backbone> = chain.resids.iter()
+            .map(|r| [" N  ", " CA ", " C  ", " O  "].map(|name| chain.structure.atom(r, name).ok().map(|a| a.pos.clone())))
+            .collect();
+        (0..backbone.len()).map(|i| {
+            let [n, ca, c, o] = &backbone[i];
+            // --- atoms of the previous residue are used only when it's bonded to this one
+            let previous = i.checked_sub(1).map(|k| &backbone[k])
+                .filter(|[_, _, c_prev, _]| matches!((c_prev, n), (Some(c_prev), Some(n)) if distance(c_prev, n) <= MAX_PEPTIDE_BOND));
+            let (n_prev, ca_prev, c_prev) = match previous {
+                Some([n_prev, ca_prev, c_prev, _]) => (n_prev.as_ref(), ca_prev.as_ref(), c_prev.as_ref()),
+                None => (None, None, None),
+            };
+            let (n, ca, c, o) = (n.as_ref(), ca.as_ref(), c.as_ref(), o.as_ref());
+            let mut row = Vec::with_capacity(12);
+            for [a, b, p, d] in [[n_prev, ca_prev, c_prev, n], [ca_prev, c_prev, n, ca], [c_prev, n, ca, c], [n, ca, c, o]] {
+                row.push(match (p, d) { (Some(p), Some(d)) => distance(p, d), _ => f64::NAN });
+                row.push(match (b, p, d) { (Some(b), Some(p), Some(d)) => planar_angle(b, p, d), _ => f64::NAN });
+                row.push(match (a, b, p, d) { (Some(a), Some(b), Some(p), Some(d)) => dihedral_angle(a, b, p, d), _ => f64::NAN });
+            }
+            row
+        }).collect()
+    }
+}
@@ -0,0 +1,147 @@
+//! Lenient parsing: malformed atom records are skipped instead of failing the whole deposit.
+//!
+//! Atom records are checked before a deposit is handed over to the parser, and those that can't be read are removed
+//! from its text: a PDB `ATOM` / `HETATM` line with unreadable coordinates or residue number, or an mmCIF `_atom_site` row
+//! with a wrong number of values or unreadable coordinates. Every skipped record is logged.
+
+use std::io::{self, BufReader};
+
+use bioshell_io::open_file;
+use bioshell_pdb::{Deposit, PDBError};
+use log::warn;
+
+use crate::chains::{stdin_text, STDIN};
+
+/// Splits an mmCIF data line into values; a value may be quoted with `'` or `"` when it contains spaces
+pub(crate) fn cif_tokens(line: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() { i += 1; continue; }
+        let start = i;
+        if bytes[i] == b'\'' || bytes[i] == b'"' {
+            // --- a quote closes a value only when followed by a whitespace or the end of the line
+            let quote = bytes[i];
+            i += 1;
+            while i < bytes.len() && !(bytes[i] == quote && bytes.get(i + 1).map_or(true, |b| b.is_ascii_whitespace())) { i += 1; }
+            i = (i + 1).min(bytes.len());
+        } else {
+            while i < bytes.len() && !bytes[i].is_ascii_whitespace() { i += 1; }
+        }
+        tokens.push(&line[start..i]);
+    }
+
+    tokens
+}
+
+fn is_number(v: &str) -> bool { v.trim().parse::<f64>().is_ok() }
+
+/// True when a value is a number or is marked as unknown (`?`) or not applicable (`.`)
+fn is_number_or_missing(v: &str) -> bool { v == "?" || v == "." || is_number(v) }
+
+/// Returns the reason why a PDB `ATOM` / `HETATM` line can't be read, or `None` when it's correct
+fn pdb_atom_error(line: &str) -> Option<&'static str> {
+    let field = |from: usize, to: usize| line.get(from..to.min(line.len()));
+    if line.len() < 54 { return Some("line too short"); }
+    if !field(22, 26).is_some_and(|v| v.trim().parse::<i32>().is_ok()) { return Some("incorrect residue number"); }
+    if ![(30, 38), (38, 46), (46, 54)].iter().all(|&(from, to)| field(from, to).is_some_and(is_number)) {
+        return Some("incorrect coordinates");
+    }
+    // --- occupancy and B-factor are optional, but must be numbers when given
+    for (from, to) in [(54, 60), (60, 66)] {
+        match field(from, to) {
+            Some(v) if !v.trim().is_empty() && !is_number(v) => return Some("incorrect occupancy or B-factor"),
+            None if line.len() > from => return Some("incorrect occupancy or B-factor"),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Removes malformed `ATOM` and `HETATM` lines of a deposit in the PDB format; returns the text and the number of removed lines
+pub fn sanitize_pdb(text: &str) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut n_skipped = 0;
+    for (i, line) in text.lines().enumerate() {
+        if line.starts_with("ATOM") || line.starts_with("HETATM") {
+            if let Some(reason) = pdb_atom_error(line) {
+                warn!("Malformed atom record skipped ({}) at line {}: {}", reason, i + 1, line);
+                n_skipped += 1;
+                continue;
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    (out, n_skipped)
+}
+
+/// Removes malformed rows of the `_atom_site` loop of a deposit in the mmCIF format; returns the text and the number of removed rows.
+///
+/// Every row of the loop is expected to be given in a single line, as in files distributed by the wwPDB.
+pub fn sanitize_cif(text: &str) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut n_skipped = 0;
+    let mut columns: Vec<&str> = vec![];
+    let mut in_loop_header = false;
+    let mut in_atom_site = false;
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("loop_") {
+            in_loop_header = true;
+            in_atom_site = false;
+            columns.clear();
+        } else if in_loop_header && trimmed.starts_with('_') {
+            if let Some(column) = trimmed.strip_prefix("_atom_site.") { columns.push(column.trim()); }
+        } else if trimmed.starts_with('_') || trimmed.starts_with('#') || trimmed.starts_with("data_") {
+            in_loop_header = false;
+            in_atom_site = false;
+        } else if !trimmed.is_empty() {
+            if in_loop_header { in_atom_site = !columns.is_empty(); }
+            in_loop_header = false;
+            if in_atom_site {
+                if let Some(reason) = cif_atom_error(&columns, &cif_tokens(line)) {
+                    warn!("Malformed atom record skipped ({}) at line {}: {}", reason, i + 1, line);
+                    n_skipped += 1;
+                    continue;
+                }
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    (out, n_skipped)
+}
+
+/// Returns the reason why an `_atom_site` row can't be read, or `None` when it's correct
+fn cif_atom_error(columns: &[&str], values: &[&str]) -> Option<&'static str> {
+    if values.len() != columns.len() { return Some("wrong number of values"); }
+    let value = |name: &str| columns.iter().position(|c| *c == name).map(|k| values[k]);
+    if !["Cartn_x", "Cartn_y", "Cartn_z"].iter().all(|c| value(c).is_some_and(is_number)) { return Some("incorrect coordinates"); }
+    if !["auth_seq_id", "label_seq_id", "occupancy", "B_iso_or_equiv"].iter().all(|c| value(c).map_or(true, is_number_or_missing)) {
+        return Some("incorrect residue number, occupancy or B-factor");
+    }
+    None
+}
+
+/// Loads a deposit like [`load_deposit()`](crate::chains::load_deposit), skipping its malformed atom records;
+/// returns the deposit and the number of skipped records
+pub fn load_deposit_lenient(fname: &str) -> Result<(Deposit, usize), PDBError> {
+    let text = if fname == STDIN { stdin_text().to_string() } else { io::read_to_string(open_file(fname)?)? };
+    parse_deposit_lenient(&text)
+}
+
+/// Parses a deposit like [`parse_deposit()`](crate::chains::parse_deposit), skipping its malformed atom records;
+/// returns the deposit and the number of skipped records
+pub fn parse_deposit_lenient(text: &str) -> Result<(Deposit, usize), PDBError> {
+    if text.trim_start().starts_with("data_") {
+        let (text, n_skipped) = sanitize_cif(text);
+        Ok((Deposit::from_cif_reader(BufReader::new(text.as_bytes()))?, n_skipped))
+    } else {
+        let (text, n_skipped) = sanitize_pdb(text);
+        Ok((Deposit::from_pdb_reader(BufReader::new(text.as_bytes()))?, n_skipped))
+    }
+}
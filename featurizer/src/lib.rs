@@ -0,0 +1,102 @@
+//! Computes features of protein chains used to train the deep_bbq v.2 model.
+//!
+//! The [`featurize_chain()`] function runs the whole pipeline for a single chain of a deposit; it keeps no global state,
+//! so it may be called concurrently from many threads. A long featurization may be aborted with a [`CancellationToken`].
+//! Several chains of a deposit are featurized with [`featurize_loaded_chain()`] from a [`LoadedDeposit`], parsed only once.
+
+pub mod geometry;
+pub mod neighbors;
+pub mod hbonds;
+pub mod separation;
+pub mod contacts;
+pub mod ideal_backbone;
+pub mod difficulty;
+pub mod manifest;
+pub mod frames;
+pub mod splits;
+pub mod ca_trace;
+pub mod interactions;
+pub mod features;
+pub mod chains;
+pub mod polymers;
+pub mod chain_pair;
+pub mod chi_angles;
+pub mod alanine_scan;
+pub mod residue_encoding;
+pub mod alignment;
+pub mod profiles;
+pub mod symmetry;
+pub mod bfactors;
+pub mod assembly;
+pub mod gaps;
+pub mod atoms;
+pub mod graph;
+pub mod map_quality;
+pub mod failures;
+pub mod conformers;
+pub mod sources;
+pub mod plddt;
+pub mod config;
+pub mod microenvironment;
+pub mod filters;
+pub mod augmentation;
+pub mod superposition;
+pub mod centering;
+pub mod diff;
+pub mod evaluate;
+pub mod crops;
+pub mod linkers;
+pub mod provenance;
+pub mod metadata;
+pub mod testing;
+pub mod sync;
+pub mod obsolete;
+pub mod update;
+pub mod fragments;
+pub mod agreement;
+pub mod records;
+pub mod lenient;
+pub mod stats;
+pub mod validate;
+pub mod rebuild;
+pub mod conservation;
+pub mod motifs;
+pub mod secondary;
+pub mod sheets;
+pub mod helices;
+pub mod importance;
+pub mod deposit_cache;
+pub mod streaming;
+pub mod timings;
+pub mod exposure;
+pub mod depth;
+pub mod crystal;
+pub mod membrane;
+pub mod inference;
+pub mod repair;
+pub mod internal_coords;
+pub mod scales;
+pub mod ramachandran;
+pub mod torsions;
+pub mod quality;
+pub mod release;
+pub mod remote;
+pub mod sifts;
+pub mod ensembles;
+pub mod reproducibility;
+pub mod ligands;
+pub mod compression;
+pub mod archive;
+pub mod dataset;
+pub mod logging;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+mod cancel;
+mod errors;
+mod featurize;
+
+pub use cancel::CancellationToken;
+pub use errors::FeaturizerError;
+pub use featurize::{compute_chain_features, featurize_chain, featurize_loaded_chain, move_outputs, moved_path, output_files, remove_outputs, ChainFeatures, FeaturizerConfig, LoadedDeposit, OUTPUT_EXTENSIONS, STDOUT};
@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::{Deposit, PDBError, PdbAtom, ResidueId, Structure};
+
+use crate::chains::{is_hydrogen, polymer_structure, residue_heavy_atoms};
+use crate::features::FeatureInfo;
+use crate::geometry::distance;
+
+/// Residues farther than that from any ligand atom are not listed
+pub const MAX_LIGAND_DISTANCE: f64 = 10.0;
+
+/// Chemical IDs of water molecules, which are not considered ligands
+const WATER: [&str; 3] = ["HOH", "DOD", "WAT"];
+
+/// Chemical IDs of metal ions
+pub const METALS: [&str; 16] = ["ZN", "MG", "CA", "MN", "FE", "FE2", "CU", "CU1", "CO", "NI", "NA", "K", "CD", "HG", "SR", "BA"];
+
+/// Heavy atoms of a deposit that belong neither to a polymer chain nor to a water molecule
+pub fn ligand_atoms(deposit: &Deposit) -> Vec<PdbAtom> {
+    let polymer: HashSet<(String, i32, char)> = polymer_structure(deposit).atoms().iter()
+        .map(|a| (a.chain_id.clone(), a.res_seq, a.i_code)).collect();
+    deposit.structure().atoms().iter()
+        .filter(|a| !is_hydrogen(a) && !WATER.contains(&a.res_name.as_str()))
+        .filter(|a| !polymer.contains(&(a.chain_id.clone(), a.res_seq, a.i_code)))
+        .cloned().collect()
+}
+
+/// The ligand closest to a residue
+#[derive(Clone, Debug)]
+pub struct LigandContact {
+    /// distance between the closest heavy atoms of the residue and the ligand
+    pub distance: f64,
+    /// chemical ID of the ligand, e.g. `HEM` or `ZN`
+    pub comp_id: String,
+    /// the ligand given as `<chain>:<residue number>`
+    pub ligand: String,
+    pub is_metal: bool,
+}
+
+/// Finds the closest ligand of every residue; `None` for residues farther than [`MAX_LIGAND_DISTANCE`] from all ligands
+pub fn nearest_ligands(strctr: &Structure, resids: &[ResidueId], ligands: &[PdbAtom]) -> Vec<Option<LigandContact>> {
+    residue_heavy_atoms(strctr, resids).iter().map(|atoms| {
+        let mut best: Option<(f64, &PdbAtom)> = None;
+        for a in atoms {
+            for l in ligands {
+                let d = distance(&a.pos, &l.pos);
+                if d <= MAX_LIGAND_DISTANCE && best.map_or(true, |(b, _)| d < b) { best = Some((d, l)); }
+            }
+        }
+        best.map(|(d, l)| LigandContact { distance: d, comp_id: l.res_name.clone(),
+            ligand: format!("{}:{}{}", l.chain_id, l.res_seq, l.i_code).trim_end().to_string(), is_metal: METALS.contains(&l.res_name.as_str()) })
+    }).collect()
+}
+
+/// Writes the closest ligand of residues as `i distance comp_id chain:residue metal` lines, where the last column is 1 for metal ions
+pub fn write_ligand_contacts(contacts: &[Option<LigandContact>], out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    for (i, c) in contacts.iter().enumerate() {
+        let Some(c) = c else { continue };
+        writeln!(outfile, "{:4} {:6.3} {:>3} {:>8} {}", i, c.distance, c.comp_id, c.ligand, c.is_metal as u8)?;
+    }
+    Ok(())
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "ligands",
+        description: "the closest ligand or metal ion of a residue: distance between the closest heavy atoms, chemical ID, the ligand residue and a metal flag",
+        shape: "[n_residues, 5]".to_string(), units: "Å", range: None,
+        mask: "residues farther than 10 Å from all ligands are not listed", option: "--ligands", version: 1 }
+}
@@ -0,0 +1,59 @@
+use bioshell_pdb::calc::Vec3;
+
+use crate::geometry::distance;
+
+/// CA atoms of two residues closer than that are in contact
+pub const LINKER_CONTACT_CUTOFF: f64 = 8.0;
+
+/// Residues closer in sequence than that don't count as a contact
+const MIN_SEPARATION: usize = 4;
+
+/// Defines how chains too long for training are split into domain-sized parts
+#[derive(Clone, Debug)]
+pub struct LinkerSplit {
+    /// chains (and parts) spanning more entity positions are split
+    pub max_length: usize,
+    /// number of positions each part extends over a split point on both sides
+    pub overlap: usize,
+}
+
+impl LinkerSplit {
+    /// Splits a chain at low-contact linkers until every part spans at most `max_length` positions (before extension by the overlap).
+    ///
+    /// `ca` gives the CA position of every entity position (`None` for gaps). A segment is split at the point crossed
+    /// by the smallest number of CA-CA contacts, at least a third of `max_length` away from its ends, so flexible linkers
+    /// are preferred over cuts through a domain. Returns ranges of positions of the parts, extended by the overlap.
+    pub fn parts(&self, ca: &[Option<Vec3>]) -> Vec<(usize, usize)> {
+        let mut cuts = vec![];
+        self.split(ca, 0, ca.len(), &mut cuts);
+        cuts.sort();
+        let mut bounds = vec![0];
+        bounds.extend(cuts);
+        bounds.push(ca.len());
+
+        bounds.windows(2).map(|b| (b[0].saturating_sub(self.overlap), (b[1] + self.overlap).min(ca.len()))).collect()
+    }
+
+    fn split(&self, ca: &[Option<Vec3>], from: usize, to: usize, cuts: &mut Vec<usize>) {
+        let min_part = (self.max_length / 3).max(1);
+        if to - from <= self.max_length || to - from < 2 * min_part { return; }
+        // --- crossing[s]: number of contacts (i, j) such that i < s <= j, from a difference array
+        let mut diff = vec![0i64; to - from + 1];
+        for i in from..to {
+            let Some(a) = &ca[i] else { continue };
+            for j in i + MIN_SEPARATION..to {
+                let Some(b) = &ca[j] else { continue };
+                if distance(a, b) <= LINKER_CONTACT_CUTOFF {
+                    diff[i - from + 1] += 1;
+                    diff[j - from + 1] -= 1;
+                }
+            }
+        }
+        let mut crossing = vec![0i64; to - from + 1];
+        for s in 1..=to - from { crossing[s] = crossing[s - 1] + diff[s]; }
+        let cut = (from + min_part..=to - min_part).min_by_key(|s| crossing[s - from]).unwrap();
+        cuts.push(cut);
+        self.split(ca, from, cut, cuts);
+        self.split(ca, cut, to, cuts);
+    }
+}
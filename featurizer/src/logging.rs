@@ -0,0 +1,73 @@
+//! Logging of a run, to the standard error or to a log file.
+//!
+//! Every message is written as a single line with a timestamp and the name of the thread that logged it, so messages
+//! of workers featurizing chains concurrently can be told apart after the run; with [`LogFormat::Json`] every line is
+//! a JSON object, e.g. to be filtered by worker or level with `jq`.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use clap::ValueEnum;
+use env_logger::{Builder, Env, Target};
+
+/// How log messages are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// `<time> <level> [<worker>] <target>: <message>` lines
+    Text,
+    /// a JSON object per line, with `time`, `level`, `worker`, `target` and `message` keys
+    Json,
+}
+
+/// Number given to the next unnamed thread that logs a message
+static NEXT_WORKER: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// name of the current thread as written in the log: its own name (`main` for the main thread) or `worker-<N>`
+    static WORKER: String = match thread::current().name() {
+        Some(name) => name.to_string(),
+        None => format!("worker-{}", NEXT_WORKER.fetch_add(1, Ordering::Relaxed)),
+    };
+}
+
+/// Escapes a string to be written as a JSON string value
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Sets up the logger of a run.
+///
+/// Messages are written to `log_file` when it's given, replacing the output to the standard error, otherwise to
+/// the standard error. The log level is read from `RUST_LOG`, `info` by default. Every message is written by a single
+/// write, so lines of concurrent workers never interleave.
+pub fn init_logging(log_file: Option<&str>, format: LogFormat) -> io::Result<()> {
+    let mut builder = Builder::from_env(Env::default().default_filter_or("info"));
+    if let Some(fname) = log_file { builder.target(Target::Pipe(Box::new(File::create(fname)?))); }
+    builder.format(move |buf, record| {
+        let time = buf.timestamp_millis().to_string();
+        let line = WORKER.with(|worker| match format {
+            LogFormat::Text => format!("{} {:<5} [{}] {}: {}\n", time, record.level(), worker, record.target(), record.args()),
+            LogFormat::Json => format!("{{\"time\":{},\"level\":{},\"worker\":{},\"target\":{},\"message\":{}}}\n",
+                json_string(&time), json_string(record.level().as_str()), json_string(worker),
+                json_string(record.target()), json_string(&record.args().to_string())),
+        });
+        buf.write_all(line.as_bytes())
+    });
+    builder.try_init().map_err(|e| io::Error::new(io::ErrorKind::AlreadyExists, e.to_string()))
+}
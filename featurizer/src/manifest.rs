@@ -0,0 +1,120 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, Write};
+
+use bioshell_io::{open_file, out_writer};
+use bioshell_pdb::PDBError;
+use log::warn;
+
+use crate::compression::output_root;
+use crate::sources::Source;
+use crate::timings::StageTimings;
+
+/// Summary of a single featurized chain, written as a row of the run manifest
+#[derive(Clone, Debug)]
+pub struct ChainSummary {
+    /// chain identifier, e.g. `2gb1_A`; used also as the root of output file names
+    pub id: String,
+    /// key of the record, namespaced by the source of the structure, e.g. `pdb/2gb1_A`
+    pub key: String,
+    pub source: Source,
+    pub output: String,
+    pub input: String,
+    pub chain: String,
+    /// alternate location label of the featurized conformer, if selected
+    pub conformer: Option<char>,
+    /// number of residues in the entity sequence
+    pub n_residues: usize,
+    /// number of residues observed in the structure
+    pub n_observed: usize,
+    pub loop_fraction: f64,
+    pub contact_order: f64,
+    pub gap_fraction: f64,
+    pub resolution: Option<f64>,
+    pub difficulty: f64,
+    /// cross-validation fold this chain has been assigned to
+    pub fold: Option<usize>,
+    /// number of malformed atom records of the input file skipped by the lenient parsing
+    pub skipped_records: usize,
+    /// obsolete PDB code listed in place of the featurized entry, which superseded it
+    pub superseded: Option<String>,
+    /// conformer group of the chain, shared by chains of the same protein (see [`conformer_group()`](crate::ensembles::conformer_group))
+    pub conformer_group: Option<String>,
+    /// time spent in every stage of the featurization; not written to the manifest
+    pub timings: StageTimings,
+}
+
+/// Writes the manifest of a run as a tab-separated file, one row per featurized chain
+pub fn write_manifest(summaries: &[ChainSummary], fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#key\toutput\tinput\tchain\tconformer\tn_residues\tn_observed\tloop_fraction\tcontact_order\tgap_fraction\tresolution\tdifficulty\tfold\tskipped_records\tsuperseded\tconformer_group")?;
+    for s in summaries {
+        let resolution = s.resolution.map_or("-".to_string(), |r| format!("{:.2}", r));
+        let fold = s.fold.map_or("-".to_string(), |f| f.to_string());
+        let conformer = s.conformer.map_or("-".to_string(), |c| c.to_string());
+        writeln!(outfile, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.3}\t{:.3}\t{:.3}\t{}\t{:.3}\t{}\t{}\t{}\t{}", s.key, s.output, s.input, s.chain, conformer,
+                 s.n_residues, s.n_observed, s.loop_fraction, s.contact_order, s.gap_fraction, resolution, s.difficulty, fold, s.skipped_records,
+                 s.superseded.as_deref().unwrap_or("-"), s.conformer_group.as_deref().unwrap_or("-"))?;
+    }
+    Ok(())
+}
+
+/// Reads the manifest of a previous run, as written by [`write_manifest()`]
+pub fn read_manifest(fname: &str) -> Result<Vec<ChainSummary>, PDBError> {
+    let mut summaries = vec![];
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let t: Vec<&str> = line.split('\t').collect();
+        if t.len() < 13 {
+            warn!("Incorrect manifest line skipped: {}", line);
+            continue;
+        }
+        let optional = |v: &str| if v == "-" { None } else { Some(v.to_string()) };
+        let number = |v: &str| v.parse::<f64>().unwrap_or(f64::NAN);
+        let source = match t[0].split('/').next() {
+            Some("afdb") => Source::Afdb,
+            Some("local") => Source::Local,
+            _ => Source::Pdb,
+        };
+        // --- outputs are named by the chain identifier
+        let id = output_root(t[1]).to_string();
+        summaries.push(ChainSummary { id, key: t[0].to_string(), source, output: t[1].to_string(), input: t[2].to_string(),
+            chain: t[3].to_string(), conformer: optional(t[4]).and_then(|c| c.chars().next()),
+            n_residues: t[5].parse().unwrap_or(0), n_observed: t[6].parse().unwrap_or(0), loop_fraction: number(t[7]),
+            contact_order: number(t[8]), gap_fraction: number(t[9]), resolution: optional(t[10]).map(|r| number(&r)),
+            difficulty: number(t[11]), fold: optional(t[12]).and_then(|f| f.parse().ok()),
+            // --- manifests written before the lenient parsing was added have no such column
+            skipped_records: t.get(13).and_then(|n| n.parse().ok()).unwrap_or(0),
+            superseded: t.get(14).and_then(|c| optional(c)), conformer_group: t.get(15).and_then(|g| optional(g)), timings: StageTimings::default() });
+    }
+
+    Ok(summaries)
+}
+
+/// Merges manifests written by the jobs of a sharded run (see [`Shard`](crate::splits::Shard)), in the order they are given.
+///
+/// Shards don't overlap, so a chain listed by several manifests means a shard has been run twice, e.g. after a job
+/// was restarted; it's reported and kept once, as listed first.
+pub fn merge_manifests(fnames: &[String]) -> Result<Vec<ChainSummary>, PDBError> {
+    let mut merged = vec![];
+    let mut keys: HashSet<String> = HashSet::new();
+    for fname in fnames {
+        for s in read_manifest(fname)? {
+            if keys.insert(s.key.clone()) { merged.push(s); } else { warn!("{} is listed more than once, {} skipped", s.key, fname); }
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Counts chains and observed residues featurized from every source
+pub fn source_statistics(summaries: &[ChainSummary]) -> BTreeMap<Source, (usize, usize)> {
+    let mut stats: BTreeMap<Source, (usize, usize)> = BTreeMap::new();
+    for s in summaries {
+        let entry = stats.entry(s.source).or_default();
+        entry.0 += 1;
+        entry.1 += s.n_observed;
+    }
+
+    stats
+}
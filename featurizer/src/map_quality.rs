@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bioshell_io::{open_file, read_whitespace_delimited_values};
+use log::warn;
+
+use crate::chains::source_chain;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+
+/// Real-space R and real-space correlation coefficient of a residue
+#[derive(Clone, Copy, Debug)]
+pub struct MapQuality {
+    pub rsr: f64,
+    pub rscc: f64,
+}
+
+/// Splits a residue number such as `27A` into the sequence number and an insertion code
+fn parse_residue_number(token: &str) -> Option<(i32, char)> {
+    let last = token.chars().last()?;
+    if last.is_ascii_alphabetic() {
+        Some((token[..token.len() - 1].parse().ok()?, last))
+    } else {
+        Some((token.parse().ok()?, ' '))
+    }
+}
+
+/// Reads per-residue map quality of a deposit, e.g. converted from PDBe EDS statistics.
+///
+/// Every line of the file provides: chain ID, residue number (optionally followed by an insertion code, e.g. `27A`),
+/// residue name, RSR and RSCC; lines starting with `#` are skipped.
+pub fn load_map_quality(fname: &str) -> Result<HashMap<(String, i32, char), MapQuality>, String> {
+    let reader = open_file(fname).map_err(|e| e.to_string())?;
+    let lines: Vec<Vec<String>> = read_whitespace_delimited_values(reader).map_err(|e| e.to_string())?;
+    let mut values = HashMap::new();
+    for line in lines {
+        if line.len() < 5 || line[0].starts_with('#') { continue; }
+        let Some((res_seq, i_code)) = parse_residue_number(&line[1]) else {
+            return Err(format!("incorrect residue number {} in {}", line[1], fname));
+        };
+        let parse = |s: &str| s.parse::<f64>().map_err(|e| format!("{} in {}", e, fname));
+        values.insert((line[0].clone(), res_seq, i_code), MapQuality { rsr: parse(&line[3])?, rscc: parse(&line[4])? });
+    }
+
+    Ok(values)
+}
+
+/// Per-residue electron density map quality: RSR and RSCC.
+///
+/// Values are read from a `<dir>/<pdb-code>.txt` file (see [`load_map_quality()`]). When `min_rscc` is given,
+/// a mask column is also written: 1 for residues whose RSCC is at least `min_rscc`, 0 otherwise.
+#[derive(Debug)]
+pub struct MapQualityFeatures {
+    pub dir: String,
+    pub min_rscc: Option<f64>,
+}
+
+impl FeatureExtractor for MapQualityFeatures {
+    fn info(&self) -> FeatureInfo {
+        let shape = if self.min_rscc.is_some() { "[L, 3]" } else { "[L, 2]" };
+        FeatureInfo { name: "map_quality",
+            description: "real-space R and real-space correlation coefficient of a residue, optionally followed by a mask of residues well supported by the electron density",
+            shape: shape.to_string(), units: "", range: None,
+            mask: "NaN when a residue is not listed; the mask is 0 for such residues", option: "--map-quality <dir> [--min-rscc <value>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let n_cols = if self.min_rscc.is_some() { 3 } else { 2 };
+        let file_root = Path::new(chain.id).file_name().and_then(|f| f.to_str()).unwrap_or(chain.id);
+        let pdb_code = file_root.split('_').next().unwrap_or(file_root).to_lowercase();
+        let fname = Path::new(&self.dir).join(format!("{}.txt", pdb_code));
+        let values = match load_map_quality(fname.to_str().unwrap()) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("No map quality data for {}: {}", chain.id, e);
+                return vec![vec![f64::NAN; n_cols]; chain.resids.len()];
+            }
+        };
+        chain.resids.iter().map(|r| {
+            let key = (source_chain(&r.chain_id).to_string(), r.res_seq, r.i_code);
+            let q = values.get(&key);
+            let mut row = vec![q.map_or(f64::NAN, |q| q.rsr), q.map_or(f64::NAN, |q| q.rscc)];
+            if let Some(min_rscc) = self.min_rscc {
+                row.push(q.map_or(0.0, |q| if q.rscc >= min_rscc { 1.0 } else { 0.0 }));
+            }
+            row
+        }).collect()
+    }
+
+    fn reads_files(&self) -> bool { true }
+}
@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use bioshell_io::{open_file, read_whitespace_delimited_values};
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::PdbAtom;
+use log::{debug, warn};
+
+use crate::config::Config;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{add, dot, norm, scale, sub, unit};
+use crate::neighbors::NeighborGrid;
+use crate::residue_encoding::{aa_index, ALPHABET};
+
+/// Residues that favour the hydrocarbon core of a bilayer
+const HYDROPHOBIC: &str = "AVLIMFWC";
+
+/// A residue is exposed, and thus may face lipids, when fewer CA atoms than this are within [`BURIAL_RADIUS`] of its CA
+const EXPOSED_NEIGHBORS: usize = 20;
+
+const BURIAL_RADIUS: f64 = 10.0;
+
+/// Number of membrane normals tried by the slab fit, spread evenly over a hemisphere
+const N_DIRECTIONS: usize = 500;
+
+/// A fitted slab is accepted as a membrane when it holds at least this many exposed residues ...
+const MIN_EXPOSED_IN_SLAB: usize = 10;
+
+/// ... and at least this fraction of them is hydrophobic
+const MIN_HYDROPHOBIC_FRACTION: f64 = 0.6;
+
+/// Mid-plane of a lipid bilayer
+#[derive(Clone, Debug)]
+pub struct MembranePlane {
+    /// a point of the mid-plane
+    pub center: Vec3,
+    /// unit normal of the mid-plane
+    pub normal: Vec3,
+    /// half of the thickness of the hydrocarbon core, in Å
+    pub half_thickness: f64,
+}
+
+impl MembranePlane {
+    /// Signed distance of a point from the mid-plane
+    pub fn depth(&self, pos: &Vec3) -> f64 { dot(&sub(pos, &self.center), &self.normal) }
+}
+
+/// Reads the membrane of a deposit from an annotation file.
+///
+/// The file gives the membrane in the coordinates of the deposit, e.g. converted from the `MEMBRANE` record of PDBTM
+/// or from OPM planes moved back into the deposit frame, as three lines: `center <x> <y> <z>`, `normal <x> <y> <z>`
+/// and `half_thickness <value>`; lines starting with `#` are skipped. A file that holds just `none` says the protein
+/// is not a membrane protein, in which case `None` is returned.
+pub fn load_membrane(fname: &str) -> Result<Option<MembranePlane>, String> {
+    let reader = open_file(fname).map_err(|e| e.to_string())?;
+    let lines: Vec<Vec<String>> = read_whitespace_delimited_values(reader).map_err(|e| e.to_string())?;
+    let parse = |s: &str| s.parse::<f64>().map_err(|e| format!("{} in {}", e, fname));
+    let (mut center, mut normal, mut half_thickness) = (None, None, None);
+    for line in lines.iter().filter(|l| !l.is_empty() && !l[0].starts_with('#')) {
+        match (line[0].as_str(), line.len()) {
+            ("none", 1) => return Ok(None),
+            ("center", 4) => center = Some(Vec3::new(parse(&line[1])?, parse(&line[2])?, parse(&line[3])?)),
+            ("normal", 4) => normal = Some(Vec3::new(parse(&line[1])?, parse(&line[2])?, parse(&line[3])?)),
+            ("half_thickness", 2) => half_thickness = Some(parse(&line[1])?),
+            _ => return Err(format!("unexpected line in {}: {}", fname, line.join(" "))),
+        }
+    }
+    match (center, normal, half_thickness) {
+        (Some(center), Some(normal), Some(half_thickness)) if norm(&normal) > 0.0 =>
+            Ok(Some(MembranePlane { center, normal: unit(&normal), half_thickness })),
+        _ => Err(format!("{} should give the center, a non-zero normal and the half thickness of a membrane", fname)),
+    }
+}
+
+/// Directions spread evenly over the upper hemisphere, on a Fibonacci spiral
+fn hemisphere_directions(n: usize) -> Vec<Vec3> {
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    (0..n).map(|k| {
+        let z = 1.0 - (k as f64 + 0.5) / n as f64;
+        let r = (1.0 - z * z).sqrt();
+        let phi = golden_angle * k as f64;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }).collect()
+}
+
+/// Fits a hydrophobic slab of a given half thickness to residues of a chain, given by CA positions and one-letter codes.
+///
+/// Every trial normal (see [`N_DIRECTIONS`]) is combined with slab centers placed every 1 Å along it; a slab scores
+/// +1 for every exposed hydrophobic residue it holds and -1 for every other exposed one. The best slab is accepted
+/// as the membrane when it holds at least [`MIN_EXPOSED_IN_SLAB`] exposed residues, at least
+/// [`MIN_HYDROPHOBIC_FRACTION`] of them hydrophobic; otherwise `None` is returned.
+pub fn fit_membrane(ca: &[Vec3], sequence: &[u8], half_thickness: f64) -> Option<MembranePlane> {
+    // --- only residues at the surface can face lipids
+    let grid = NeighborGrid::new(&ca.iter().map(|p| Some(p.clone())).collect::<Vec<_>>(), BURIAL_RADIUS);
+    let exposed: Vec<usize> = (0..ca.len()).filter(|i| grid.within(&ca[*i], BURIAL_RADIUS).len() <= EXPOSED_NEIGHBORS).collect();
+    if exposed.len() < MIN_EXPOSED_IN_SLAB { return None; }
+    let hydrophobic: Vec<bool> = exposed.iter().map(|i| HYDROPHOBIC.contains(sequence[*i] as char)).collect();
+    let centroid = scale(&exposed.iter().fold(Vec3::new(0.0, 0.0, 0.0), |s, i| add(&s, &ca[*i])), 1.0 / exposed.len() as f64);
+    // --- (score, number of exposed residues, number of the hydrophobic ones, normal, offset of the center)
+    let mut best: Option<(i32, usize, usize, Vec3, f64)> = None;
+    for normal in hemisphere_directions(N_DIRECTIONS) {
+        let t: Vec<f64> = exposed.iter().map(|i| dot(&sub(&ca[*i], &centroid), &normal)).collect();
+        let (lo, hi) = t.iter().fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+        let mut c = lo;
+        while c <= hi {
+            let (n, n_hydrophobic) = t.iter().zip(&hydrophobic).filter(|(v, _)| (**v - c).abs() <= half_thickness)
+                .fold((0, 0), |(n, n_hydrophobic), (_, h)| (n + 1, n_hydrophobic + *h as usize));
+            let score = 2 * n_hydrophobic as i32 - n as i32;
+            if !best.as_ref().is_some_and(|b| score <= b.0) { best = Some((score, n, n_hydrophobic, normal.clone(), c)); }
+            c += 1.0;
+        }
+    }
+    let (_, n, n_hydrophobic, normal, offset) = best?;
+    debug!("best hydrophobic slab: {} of {} exposed residues hydrophobic", n_hydrophobic, n);
+    if n < MIN_EXPOSED_IN_SLAB || (n_hydrophobic as f64) < MIN_HYDROPHOBIC_FRACTION * n as f64 { return None; }
+
+    Some(MembranePlane { center: add(&centroid, &scale(&normal, offset)), normal, half_thickness })
+}
+
+/// Position of residues with respect to a lipid bilayer: whether a residue is embedded in the hydrocarbon core
+/// and the signed distance of its CA from the mid-plane.
+///
+/// The membrane is read from a `<dir>/<pdb-code>.txt` annotation file (see [`load_membrane()`]) when `annotations`
+/// is given; otherwise a hydrophobic slab is fitted to the chain (see [`fit_membrane()`]). Residues of a protein
+/// without a membrane are flagged 0, with NaN depth.
+#[derive(Debug)]
+pub struct MembraneFeatures {
+    /// directory of annotation files named by PDB code
+    pub annotations: Option<String>,
+    /// half thickness of the slab fitted when there are no annotations, in Å
+    pub half_thickness: f64,
+}
+
+impl Default for MembraneFeatures {
+    fn default() -> Self { MembraneFeatures { annotations: None, half_thickness: 15.0 } }
+}
+
+impl MembraneFeatures {
+    /// Reads the `membrane.half_thickness` setting
+    pub fn from_config(config: &Config, annotations: Option<String>) -> Result<MembraneFeatures, String> {
+        let half_thickness = config.get_or("membrane.half_thickness", MembraneFeatures::default().half_thickness)?;
+        if half_thickness <= 0.0 { return Err(format!("membrane half thickness should be positive; found: {}", half_thickness)); }
+        Ok(MembraneFeatures { annotations, half_thickness })
+    }
+}
+
+impl FeatureExtractor for MembraneFeatures {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "membrane",
+            description: "1 when CA of a residue lies within the hydrocarbon core of the membrane, 0 otherwise; signed distance of CA from the membrane mid-plane",
+            shape: "[L, 2]".to_string(), units: "-, Å", range: None,
+            mask: "the flag is 0 and the depth NaN for proteins without a membrane; both NaN when a deposit has no annotation file or a residue has no CA",
+            option: "--membrane [--membrane-annotations <dir>] [--config <file>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let ca: Vec<Option<&PdbAtom>> = chain.resids.iter().map(|r| chain.structure.atom(r, " CA ").ok()).collect();
+        let membrane = match &self.annotations {
+            Some(dir) => {
+                let file_root = Path::new(chain.id).file_name().and_then(|f| f.to_str()).unwrap_or(chain.id);
+                let pdb_code = file_root.split('_').next().unwrap_or(file_root).to_lowercase();
+                let fname = Path::new(dir).join(format!("{}.txt", pdb_code));
+                match load_membrane(fname.to_str().unwrap()) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        warn!("No membrane annotation for {}: {}", chain.id, e);
+                        return vec![vec![f64::NAN; 2]; chain.resids.len()];
+                    }
+                }
+            }
+            None => {
+                let positions: Vec<Vec3> = ca.iter().flatten().map(|a| a.pos.clone()).collect();
+                let sequence: Vec<u8> = ca.iter().flatten().map(|a| ALPHABET.as_bytes()[aa_index(&a.res_name)]).collect();
+                fit_membrane(&positions, &sequence, self.half_thickness)
+            }
+        };
+        ca.iter().map(|atom| match (&membrane, atom) {
+            (_, None) => vec![f64::NAN; 2],
+            (None, Some(_)) => vec![0.0, f64::NAN],
+            (Some(m), Some(atom)) => {
+                let depth = m.depth(&atom.pos);
+                vec![if depth.abs() <= m.half_thickness { 1.0 } else { 0.0 }, depth]
+            }
+        }).collect()
+    }
+
+    fn reads_files(&self) -> bool { self.annotations.is_some() }
+}
@@ -0,0 +1,170 @@
+//! Descriptive metadata of the entity a chain belongs to and of its deposit, written to `.meta` files.
+//!
+//! For an mmCIF file the metadata are read from the `_entity`, `_entity_poly`, `_entity_src_*`, `_exptl` and
+//! `_pdbx_database_status` categories; for a PDB file from the `HEADER`, `EXPDTA`, `COMPND` and `SOURCE` records.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+
+use bioshell_io::open_file;
+use bioshell_pdb::PDBError;
+use log::warn;
+
+use crate::assembly::read_cif_categories;
+use crate::chains::STDIN;
+use crate::release::{release_date, ReleaseDate};
+
+/// Entity and deposit metadata of a chain; a field is `None` when the input file doesn't give it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityMetadata {
+    /// ID of the entity, as given in the input file
+    pub entity_id: String,
+    /// e.g. `PROTEIN G`
+    pub description: Option<String>,
+    /// e.g. `polypeptide(L)`
+    pub polymer_type: Option<String>,
+    /// EC numbers of an enzyme
+    pub ec_numbers: Vec<String>,
+    /// scientific name of the source organism
+    pub organism: Option<String>,
+    /// NCBI taxonomy ID of the source organism
+    pub taxonomy_id: Option<String>,
+    /// experimental methods, e.g. `X-RAY DIFFRACTION`
+    pub methods: Vec<String>,
+    /// resolution in Å
+    pub resolution: Option<f64>,
+    pub deposition_date: Option<ReleaseDate>,
+    pub release_date: Option<ReleaseDate>,
+    /// conformer group of the chain, when chains are grouped (see [`conformer_group()`](crate::ensembles::conformer_group))
+    pub conformer_group: Option<String>,
+}
+
+impl EntityMetadata {
+    /// The metadata as `(key, value)` pairs, in the order they are written to a `.meta` file; missing values are skipped
+    pub fn key_values(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![("entity.id", self.entity_id.clone())];
+        let mut push = |key: &'static str, value: Option<String>| if let Some(v) = value { pairs.push((key, v)); };
+        push("entity.description", self.description.clone());
+        push("entity.polymer_type", self.polymer_type.clone());
+        push("entity.ec", Some(self.ec_numbers.join(",")).filter(|v| !v.is_empty()));
+        push("entity.organism", self.organism.clone());
+        push("entity.taxonomy_id", self.taxonomy_id.clone());
+        push("entity.conformer_group", self.conformer_group.clone());
+        push("deposit.method", Some(self.methods.join(",")).filter(|v| !v.is_empty()));
+        push("deposit.resolution", self.resolution.map(|r| format!("{:.2}", r)));
+        push("deposit.deposition_date", self.deposition_date.map(|d| d.to_string()));
+        push("deposit.release_date", self.release_date.map(|d| d.to_string()));
+
+        pairs
+    }
+}
+
+impl fmt::Display for EntityMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in self.key_values() { writeln!(f, "{} = {}", key, value)?; }
+        Ok(())
+    }
+}
+
+/// An mmCIF value, `None` for the `?` (unknown) and `.` (not applicable) placeholders
+fn cif_value(row: &HashMap<String, String>, item: &str) -> Option<String> {
+    row.get(item).map(|v| v.trim().to_string()).filter(|v| !v.is_empty() && v != "?" && v != ".")
+}
+
+/// Items of a comma-separated list, e.g. of EC numbers
+fn split_list(value: Option<String>) -> Vec<String> {
+    value.map(|v| v.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()).unwrap_or_default()
+}
+
+/// Reads the metadata of an entity from an mmCIF file; `None` when the file has no `_entity` category, i.e. it's a PDB file
+fn cif_metadata(fname: &str, entity_id: &str) -> Result<Option<EntityMetadata>, PDBError> {
+    const CATEGORIES: [&str; 7] = ["_entity", "_entity_poly", "_entity_src_gen", "_entity_src_nat",
+        "_pdbx_entity_src_syn", "_exptl", "_pdbx_database_status"];
+    let categories = read_cif_categories(fname, &CATEGORIES)?;
+    let rows = |category: &str| categories.get(category).map(|r| r.as_slice()).unwrap_or(&[]);
+    let of_entity = |category: &str, id_item: &str| rows(category).iter()
+        .find(|r| r.get(id_item).is_some_and(|id| id == entity_id)).cloned();
+    let Some(entity) = of_entity("_entity", "id") else { return Ok(None) };
+
+    let mut metadata = EntityMetadata { entity_id: entity_id.to_string(), ..Default::default() };
+    metadata.description = cif_value(&entity, "pdbx_description");
+    metadata.ec_numbers = split_list(cif_value(&entity, "pdbx_ec"));
+    metadata.polymer_type = of_entity("_entity_poly", "entity_id").and_then(|r| cif_value(&r, "type"));
+    // --- the source is given by one of three categories, depending on how the entity was produced
+    let sources = [("_entity_src_gen", "pdbx_gene_src_scientific_name", "pdbx_gene_src_ncbi_taxonomy_id"),
+        ("_entity_src_nat", "pdbx_organism_scientific", "pdbx_ncbi_taxonomy_id"),
+        ("_pdbx_entity_src_syn", "organism_scientific", "ncbi_taxonomy_id")];
+    if let Some((source, name, taxonomy)) = sources.iter().find_map(|&(c, n, t)| of_entity(c, "entity_id").map(|r| (r, n, t))) {
+        metadata.organism = cif_value(&source, name);
+        metadata.taxonomy_id = cif_value(&source, taxonomy);
+    }
+    metadata.methods = rows("_exptl").iter().filter_map(|r| cif_value(r, "method")).collect();
+    metadata.deposition_date = rows("_pdbx_database_status").first()
+        .and_then(|r| cif_value(r, "recvd_initial_deposition_date")?.parse().ok());
+
+    Ok(Some(metadata))
+}
+
+/// Splits the text of `COMPND` or `SOURCE` records into molecules, given as `(token, value)` pairs; a molecule starts with its `MOL_ID`
+fn pdb_specifications(text: &str) -> Vec<HashMap<String, String>> {
+    let mut molecules: Vec<HashMap<String, String>> = vec![];
+    for spec in text.split(';') {
+        let Some((token, value)) = spec.split_once(':') else { continue };
+        let (token, value) = (token.trim().to_string(), value.trim().to_string());
+        if token == "MOL_ID" || molecules.is_empty() { molecules.push(HashMap::new()); }
+        molecules.last_mut().unwrap().insert(token, value);
+    }
+
+    molecules
+}
+
+/// Reads the metadata of the molecule a chain belongs to from the header of a PDB file
+fn pdb_metadata(fname: &str, entity_id: &str, chain: &str) -> Result<EntityMetadata, PDBError> {
+    let mut metadata = EntityMetadata { entity_id: entity_id.to_string(), ..Default::default() };
+    let (mut compnd, mut source) = (String::new(), String::new());
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        let text = line.get(10..).unwrap_or("").trim_end();
+        match line.get(..6).unwrap_or("") {
+            "HEADER" => metadata.deposition_date = line.get(50..59).and_then(ReleaseDate::from_pdb),
+            "EXPDTA" => metadata.methods = text.split(';').map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect(),
+            "COMPND" => { compnd.push(' '); compnd.push_str(text.trim()); }
+            "SOURCE" => { source.push(' '); source.push_str(text.trim()); }
+            "ATOM  " | "HETATM" | "MODEL " => break,
+            _ => {}
+        }
+    }
+    let molecules = pdb_specifications(&compnd);
+    let Some(molecule) = molecules.iter().find(|m| m.get("CHAIN").is_some_and(|c| c.split(',').any(|c| c.trim() == chain))) else {
+        return Ok(metadata);
+    };
+    metadata.description = molecule.get("MOLECULE").cloned();
+    metadata.ec_numbers = split_list(molecule.get("EC").cloned());
+    if let Some(source) = pdb_specifications(&source).into_iter().find(|s| s.get("MOL_ID") == molecule.get("MOL_ID")) {
+        metadata.organism = source.get("ORGANISM_SCIENTIFIC").cloned();
+        metadata.taxonomy_id = source.get("ORGANISM_TAXID").cloned();
+    }
+
+    Ok(metadata)
+}
+
+/// Metadata of the entity a chain belongs to and of its deposit.
+///
+/// `entity_id` and `chain` select the entity in an mmCIF and a PDB file, respectively; the resolution, as read along
+/// with the deposit, is passed on unchanged. The input file is read again, so only the entity ID and the resolution are
+/// known for a deposit read from the standard input.
+pub fn entity_metadata(fname: &str, entity_id: &str, chain: &str, resolution: Option<f64>) -> Result<EntityMetadata, PDBError> {
+    if fname == STDIN {
+        warn!("Entity metadata can't be read for a deposit read from the standard input");
+        return Ok(EntityMetadata { entity_id: entity_id.to_string(), resolution, ..Default::default() });
+    }
+    let mut metadata = match cif_metadata(fname, entity_id)? {
+        Some(metadata) => metadata,
+        None => pdb_metadata(fname, entity_id, chain)?,
+    };
+    metadata.resolution = resolution;
+    metadata.release_date = release_date(fname)?;
+
+    Ok(metadata)
+}
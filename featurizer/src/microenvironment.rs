@@ -0,0 +1,97 @@
+use crate::chains::residue_heavy_atoms;
+use crate::config::Config;
+use crate::contacts::pdb_atom_name;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::distance;
+use crate::neighbors::NeighborGrid;
+
+/// Atom types counted in every shell of a microenvironment
+pub const ATOM_TYPES: [char; 4] = ['C', 'N', 'O', 'S'];
+
+/// Residue classes counted in every shell of a microenvironment: hydrophobic, polar, positively and negatively charged
+pub const RESIDUE_CLASSES: [&str; 4] = ["AVLIMFWPC", "GSTNQYH", "KR", "DE"];
+
+fn residue_class(res_name: &str) -> Option<usize> {
+    let aa = crate::residue_encoding::ALPHABET.as_bytes()[crate::residue_encoding::aa_index(res_name)] as char;
+    RESIDUE_CLASSES.iter().position(|c| c.contains(aa))
+}
+
+/// Rotation-invariant fingerprint of the 3D neighbourhood of a residue.
+///
+/// Space around the center atom of a residue (CB by default, CA for glycine) is divided into spherical shells
+/// bounded by `shells` radii. For every shell the fingerprint counts heavy atoms of other residues by their type
+/// (C, N, O, S) followed by other residues, whose center atom falls into the shell, by their class (see [`RESIDUE_CLASSES`]).
+#[derive(Debug)]
+pub struct MicroEnvironment {
+    /// name of the center atom, padded as in PDB files
+    pub center: String,
+    /// outer radii of consecutive shells, in increasing order
+    pub shells: Vec<f64>,
+}
+
+impl Default for MicroEnvironment {
+    fn default() -> Self { MicroEnvironment { center: " CB ".to_string(), shells: vec![4.0, 6.0, 8.0, 10.0, 12.0] } }
+}
+
+impl MicroEnvironment {
+    /// Reads `microenvironment.center` and `microenvironment.shells` settings
+    pub fn from_config(config: &Config) -> Result<MicroEnvironment, String> {
+        let default = MicroEnvironment::default();
+        let center: String = config.get_or("microenvironment.center", "CB".to_string())?;
+        let shells = config.get_list_or("microenvironment.shells", default.shells)?;
+        if shells.is_empty() || shells.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(format!("microenvironment shells should be given in increasing order; found: {:?}", shells));
+        }
+        Ok(MicroEnvironment { center: pdb_atom_name(&center), shells })
+    }
+
+    fn shell(&self, d: f64) -> Option<usize> { self.shells.iter().position(|r| d < *r) }
+}
+
+impl FeatureExtractor for MicroEnvironment {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "microenvironment",
+            description: "counts of C, N, O, S atoms and of hydrophobic, polar, positive and negative residues of a chain in consecutive spherical shells around a residue",
+            shape: format!("[L, {}]", self.shells.len() * (ATOM_TYPES.len() + RESIDUE_CLASSES.len())),
+            units: "atom count, residue count", range: None,
+            mask: "NaN when a residue has neither the center atom nor CA", option: "--microenvironment [--config <file>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let per_shell = ATOM_TYPES.len() + RESIDUE_CLASSES.len();
+        let atoms = residue_heavy_atoms(chain.structure, chain.resids);
+        let centers: Vec<_> = atoms.iter().map(|res| {
+            res.iter().find(|a| a.name == self.center).or(res.iter().find(|a| a.name == " CA ")).cloned()
+        }).collect();
+
+        // --- heavy atoms and center atoms of all the residues, indexed for the outermost shell
+        let outer = self.shells.last().copied().unwrap_or(0.0);
+        let owners: Vec<usize> = atoms.iter().enumerate().flat_map(|(j, res)| std::iter::repeat(j).take(res.len())).collect();
+        let all_atoms: Vec<_> = atoms.iter().flatten().collect();
+        let atom_grid = NeighborGrid::new(&all_atoms.iter().map(|a| Some(a.pos.clone())).collect::<Vec<_>>(), outer);
+        let center_grid = NeighborGrid::new(&centers.iter().map(|c| c.as_ref().map(|a| a.pos.clone())).collect::<Vec<_>>(), outer);
+        let mut out = Vec::with_capacity(atoms.len());
+        for (i, center) in centers.iter().enumerate() {
+            let Some(center) = center else {
+                out.push(vec![f64::NAN; self.shells.len() * per_shell]);
+                continue;
+            };
+            let mut row = vec![0.0; self.shells.len() * per_shell];
+            for k in atom_grid.within(&center.pos, outer).into_iter().filter(|k| owners[*k] != i) {
+                let a = all_atoms[k];
+                let element = a.name.trim().trim_start_matches(|c: char| c.is_ascii_digit()).chars().next().unwrap_or(' ');
+                let (Some(t), Some(s)) = (ATOM_TYPES.iter().position(|e| *e == element), self.shell(distance(&a.pos, &center.pos))) else { continue };
+                row[s * per_shell + t] += 1.0;
+            }
+            for j in center_grid.within(&center.pos, outer).into_iter().filter(|j| *j != i) {
+                let Some(other) = &centers[j] else { continue };
+                if let (Some(c), Some(s)) = (residue_class(&other.res_name), self.shell(distance(&other.pos, &center.pos))) {
+                    row[s * per_shell + ATOM_TYPES.len() + c] += 1.0;
+                }
+            }
+            out.push(row);
+        }
+
+        out
+    }
+}
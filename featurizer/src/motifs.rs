@@ -0,0 +1,103 @@
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+
+/// True when the C=O group of residue `acceptor` is H-bonded to the N-H group of residue `donor`, both indexed in a chain
+fn hbond(chain: &ChainContext, acceptor: usize, donor: usize) -> bool {
+    let n = chain.resids.len();
+    acceptor < n && donor < n && chain.hbonds.h_bond(&chain.resids[donor], &chain.resids[acceptor]).is_some()
+}
+
+/// True when residues `from..=to` are consecutive in the entity sequence, i.e. no residue between them is missing
+fn contiguous(chain: &ChainContext, from: usize, to: usize) -> bool {
+    if to >= chain.resids.len() { return false; }
+    let Some(first) = chain.entity_index[from] else { return false };
+    (from..=to).all(|i| chain.entity_index[i] == Some(first + i - from))
+}
+
+/// True when an `n`-turn starts at residue `i`: its C=O is H-bonded to the N-H of residue `i + n`
+fn turn(chain: &ChainContext, i: usize, n: usize) -> bool { contiguous(chain, i, i + n) && hbond(chain, i, i + n) }
+
+/// Residues of helices of the given H-bond pattern: two consecutive `n`-turns at `i - 1` and `i` make residues `i..i+n` helical
+pub(crate) fn helix(chain: &ChainContext, n: usize) -> Vec<bool> {
+    let mut flags = vec![false; chain.resids.len()];
+    for i in 1..chain.resids.len() {
+        if turn(chain, i - 1, n) && turn(chain, i, n) {
+            for f in flags.iter_mut().skip(i).take(n) { *f = true; }
+        }
+    }
+    flags
+}
+
+/// A beta bridge between residues `i < j`, as defined by DSSP
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Bridge {
+    pub(crate) i: usize,
+    pub(crate) j: usize,
+    pub(crate) parallel: bool,
+}
+
+/// Finds beta bridges of a chain from its H-bond map
+pub(crate) fn bridges(chain: &ChainContext) -> Vec<Bridge> {
+    let n = chain.resids.len();
+    let mut out = vec![];
+    for i in 1..n.saturating_sub(1) {
+        for j in i + 3..n.saturating_sub(1) {
+            let parallel = (hbond(chain, i - 1, j) && hbond(chain, j, i + 1)) || (hbond(chain, j - 1, i) && hbond(chain, i, j + 1));
+            let antiparallel = (hbond(chain, i, j) && hbond(chain, j, i)) || (hbond(chain, i - 1, j + 1) && hbond(chain, j - 1, i + 1));
+            if parallel || antiparallel { out.push(Bridge { i, j, parallel }); }
+        }
+    }
+    out
+}
+
+/// Residues skipped between two bridges of the same ladder on each of its strands, as ranges with exclusive bounds;
+/// `a` precedes `b` on the first strand.
+///
+/// Following DSSP, two bridges of the same type belong to one ladder when the gap between them is at most one residue
+/// on one strand and at most four residues on the other one; `None` is returned for bridges that can't be linked.
+pub(crate) fn ladder_gaps(a: &Bridge, b: &Bridge) -> Option<((usize, usize), (usize, usize))> {
+    if b.parallel != a.parallel || b.i <= a.i { return None; }
+    let (from_j, to_j) = if a.parallel { (a.j, b.j) } else { (b.j, a.j) };
+    if to_j <= from_j { return None; }
+    let (gap_i, gap_j) = (b.i - a.i - 1, to_j - from_j - 1);
+    if gap_i.min(gap_j) > 1 || gap_i.max(gap_j) > 4 { return None; }
+    Some(((a.i, b.i), (from_j, to_j)))
+}
+
+/// Residues of beta bulges: extra residues inserted into one strand between two bridges of the same ladder,
+/// see [`ladder_gaps()`]; residues within the gap are flagged.
+fn bulges(chain: &ChainContext) -> Vec<bool> {
+    let mut flags = vec![false; chain.resids.len()];
+    let found = bridges(chain);
+    for (k, a) in found.iter().enumerate() {
+        for b in found.iter().skip(k + 1) {
+            let Some(((from_i, to_i), (from_j, to_j))) = ladder_gaps(a, b) else { continue };
+            // --- consecutive bridges, with no residue skipped, make a regular ladder
+            if to_i == from_i + 1 && to_j == from_j + 1 { continue; }
+            for f in flags.iter_mut().take(to_i).skip(from_i + 1) { *f = true; }
+            for f in flags.iter_mut().take(to_j).skip(from_j + 1) { *f = true; }
+        }
+    }
+    flags
+}
+
+/// Flags residues of rare H-bond motifs: 3-10 helices, pi-helices and beta bulges.
+///
+/// Motifs are detected from the backbone H-bond map, like in DSSP: a 3-10 (pi) helix is formed by two consecutive
+/// residues starting 3-turns (5-turns), i.e. residues whose C=O group is H-bonded to the N-H group three (five) residues
+/// later. Unlike the DSSP code, the flags are not exclusive: a residue may belong to an alpha helix and a 3-10 helix at once.
+#[derive(Debug)]
+pub struct HBondMotifs;
+
+impl FeatureExtractor for HBondMotifs {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "hbond_motifs",
+            description: "flags of residues in 3-10 helices, pi-helices and beta bulges, detected from backbone H-bond patterns",
+            shape: "[L, 3]".to_string(), units: "", range: Some((0.0, 1.0)), mask: "none", option: "--hbond-motifs", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let flag = |v: bool| if v { 1.0 } else { 0.0 };
+        let (helix_3_10, helix_pi, bulge) = (helix(chain, 3), helix(chain, 5), bulges(chain));
+        (0..chain.resids.len()).map(|i| vec![flag(helix_3_10[i]), flag(helix_pi[i]), flag(bulge[i])]).collect()
+    }
+}
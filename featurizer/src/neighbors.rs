@@ -0,0 +1,71 @@
+//! Cell-list spatial index that finds points within a distance without scanning all pairs.
+//!
+//! Points are binned into cubic cells, so a query visits only the cells that overlap its sphere. For a chain of
+//! thousands of residues this replaces the quadratic scans of contacts, exposure, interactions and H-bond candidates,
+//! which otherwise dominate the time of a featurization. Results come in the order of point indexes, as a scan would give them.
+
+use std::collections::HashMap;
+
+use bioshell_pdb::calc::Vec3;
+
+use crate::geometry::distance;
+
+/// Points binned into cubic cells of a given size; `None` stands for a missing point, which is never found
+#[derive(Clone, Debug)]
+pub struct NeighborGrid {
+    cell: f64,
+    points: Vec<Option<Vec3>>,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+}
+
+impl NeighborGrid {
+    /// Bins points into cells of the given size, best set to the distance most often searched for
+    pub fn new(points: &[Option<Vec3>], cell: f64) -> NeighborGrid {
+        let cell = if cell > 0.0 { cell } else { 1.0 };
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        for (i, p) in points.iter().enumerate() {
+            let Some(p) = p else { continue };
+            cells.entry(cell_of(p, cell)).or_default().push(i);
+        }
+        NeighborGrid { cell, points: points.to_vec(), cells }
+    }
+
+    /// Number of points of the grid, missing ones included
+    pub fn len(&self) -> usize { self.points.len() }
+
+    pub fn is_empty(&self) -> bool { self.points.is_empty() }
+
+    /// Indexes of points not farther than `cutoff` from `pos`, in increasing order
+    pub fn within(&self, pos: &Vec3, cutoff: f64) -> Vec<usize> {
+        let span = (cutoff / self.cell).ceil() as i64;
+        let [x, y, z] = cell_of(pos, self.cell);
+        let mut found = vec![];
+        for dx in -span..=span {
+            for dy in -span..=span {
+                for dz in -span..=span {
+                    let Some(members) = self.cells.get(&[x + dx, y + dy, z + dz]) else { continue };
+                    found.extend(members.iter().copied().filter(|k| self.points[*k].as_ref().is_some_and(|p| distance(p, pos) <= cutoff)));
+                }
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+
+    /// All pairs of points not farther apart than `cutoff`, as `(i, j, distance)` tuples with `i < j`, ordered by `i` and then `j`
+    pub fn pairs(&self, cutoff: f64) -> Vec<(usize, usize, f64)> {
+        let mut pairs = vec![];
+        for (i, p) in self.points.iter().enumerate() {
+            let Some(p) = p else { continue };
+            for j in self.within(p, cutoff).into_iter().filter(|j| *j > i) {
+                pairs.push((i, j, distance(p, self.points[j].as_ref().unwrap())));
+            }
+        }
+        pairs
+    }
+}
+
+/// Cell a point falls into
+fn cell_of(p: &Vec3, cell: f64) -> [i64; 3] {
+    [(p.x / cell).floor() as i64, (p.y / cell).floor() as i64, (p.z / cell).floor() as i64]
+}
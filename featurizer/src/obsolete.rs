@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use bioshell_io::open_file;
+use bioshell_pdb::PDBError;
+use log::{info, warn};
+
+use crate::sync::fetch_text;
+
+/// Where the RCSB describes removed entries, e.g. `https://data.rcsb.org/rest/v1/holdings/removed/1bsh`
+pub const RCSB_REMOVED_URL: &str = "https://data.rcsb.org/rest/v1/holdings/removed";
+
+/// Longest chain of supersessions followed, guarding against cycles in a mapping file
+const MAX_SUPERSESSIONS: usize = 10;
+
+/// Obsolete PDB entries mapped to the entries that superseded them
+#[derive(Clone, Debug, Default)]
+pub struct ObsoleteEntries {
+    replaced_by: HashMap<String, String>,
+    /// when true, entries missing in the mapping are looked up in the RCSB API
+    pub query_rcsb: bool,
+}
+
+impl ObsoleteEntries {
+    /// Reads a mapping file: either the wwPDB `obsolete.dat` file, with `OBSLTE <date> <obsolete ID> <successors>` lines,
+    /// or a file with an obsolete ID and the ID that superseded it in every line.
+    ///
+    /// Entries withdrawn without a successor are skipped; when an entry has been split into many, the first successor is used.
+    pub fn from_file(fname: &str) -> Result<ObsoleteEntries, PDBError> {
+        let mut replaced_by = HashMap::new();
+        for line in open_file(fname)?.lines() {
+            let line = line?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let (old, new) = match tokens.first() {
+                Some(&"OBSLTE") => (tokens.get(2), tokens.get(3)),
+                Some(t) if !t.starts_with('#') && tokens.len() >= 2 && t.len() == 4 => (tokens.first(), tokens.get(1)),
+                _ => continue,
+            };
+            if let (Some(old), Some(new)) = (old, new) { replaced_by.insert(old.to_lowercase(), new.to_lowercase()); }
+        }
+        info!("{} superseded entries read from {}", replaced_by.len(), fname);
+
+        Ok(ObsoleteEntries { replaced_by, query_rcsb: false })
+    }
+
+    /// The entry that directly superseded an obsolete one; `None` when it's not known to be superseded
+    pub fn replaced_by(&self, code: &str) -> Option<String> {
+        let code = code.to_lowercase();
+        match self.replaced_by.get(&code) {
+            Some(next) => Some(next.clone()),
+            None if self.query_rcsb => rcsb_replaced_by(&code),
+            None => None,
+        }
+    }
+
+    /// Resolves an obsolete entry to the first entry superseding it that `exists`, following a chain of supersessions
+    pub fn resolve(&self, code: &str, exists: impl Fn(&str) -> bool) -> Option<String> {
+        let mut code = code.to_string();
+        for _ in 0..MAX_SUPERSESSIONS {
+            code = self.replaced_by(&code)?;
+            if exists(&code) { return Some(code); }
+        }
+
+        None
+    }
+}
+
+/// Asks the RCSB which entry superseded a removed one
+fn rcsb_replaced_by(code: &str) -> Option<String> {
+    let text = match fetch_text(&format!("{}/{}", RCSB_REMOVED_URL, code)) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Can't check at the RCSB whether {} is obsolete: {}", code, e);
+            return None;
+        }
+    };
+    parse_replaced_by(&text)
+}
+
+/// The first ID of the `id_codes_replaced_by` list of an RCSB description of a removed entry
+pub fn parse_replaced_by(json: &str) -> Option<String> {
+    let list = json.split_once("\"id_codes_replaced_by\"")?.1;
+    let list = list.split_once('[')?.1.split_once(']')?.0;
+    let first = list.split(',').next()?.trim().trim_matches('"');
+    (!first.is_empty()).then(|| first.to_lowercase())
+}
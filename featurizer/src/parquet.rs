@@ -0,0 +1,229 @@
+//! Parquet output backend: per-residue rows and residue pairs of a whole run, in tables that may be queried with DuckDB or Polars.
+//!
+//! Two tables are written under the output directory: `residues`, a row per observed residue with all its `.dat` columns,
+//! and `pairs`, a row per backbone H-bond or contact. Both are partitioned Hive-style by the source of structures
+//! (e.g. `residues/source=pdb/part-00000.parquet`), and a new part file is started every [`ParquetDataset::rows_per_file`] residues.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray, UInt32Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use log::{info, warn};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::compression::{existing_file, open_output, output_root};
+use crate::errors::FeaturizerError;
+use crate::manifest::ChainSummary;
+use crate::hbonds::HBondDirection;
+use crate::records::{feature_names, parse_record, read_columns, read_hbonds, Numbering};
+use crate::separation::{parse_separation, SeparationRange};
+use crate::sources::Source;
+
+/// Default number of residues stored in a single part file
+pub const ROWS_PER_FILE: usize = 1_000_000;
+
+fn arrow_error(e: impl ToString) -> io::Error { io::Error::new(io::ErrorKind::Other, e.to_string()) }
+
+/// Columns of the `residues` table buffered before they are written
+#[derive(Default)]
+struct ResidueColumns {
+    key: Vec<String>,
+    index: Vec<u32>,
+    residue: Vec<String>,
+    residue_id: Vec<String>,
+    /// residue numbers, in the numbering the chains were featurized with
+    number: Vec<String>,
+    ss: Vec<String>,
+    x: Vec<f64>,
+    y: Vec<f64>,
+    z: Vec<f64>,
+    features: Vec<Vec<f64>>,
+}
+
+/// Columns of the `pairs` table buffered before they are written
+#[derive(Default)]
+struct PairColumns {
+    key: Vec<String>,
+    i: Vec<u32>,
+    partner: Vec<String>,
+    kind: Vec<String>,
+    /// direction of an H-bond, as in `.hbonds` files
+    direction: Vec<Option<String>>,
+    distance: Vec<f64>,
+    energy: Vec<Option<f64>>,
+    d_ha: Vec<Option<f64>>,
+    angle_dha: Vec<Option<f64>>,
+    /// signed sequence separation, null for inter-chain pairs
+    separation: Vec<Option<i64>>,
+    /// range class of the separation
+    range: Vec<String>,
+}
+
+/// Rows of a single partition, i.e. of a single source of structures
+#[derive(Default)]
+struct Partition {
+    residues: ResidueColumns,
+    pairs: PairColumns,
+    n_files: usize,
+}
+
+/// Collects featurized chains of a run and writes them as partitioned Parquet tables
+pub struct ParquetDataset {
+    /// output directory, holding the `residues` and `pairs` tables
+    pub dir: String,
+    /// a part file is written once that many residues of a partition are collected
+    pub rows_per_file: usize,
+    /// names of the residue number column and of the feature columns; all chains of a dataset must have the same ones
+    columns: Option<(String, Vec<String>)>,
+    partitions: BTreeMap<Source, Partition>,
+}
+
+impl ParquetDataset {
+    pub fn new(dir: &str, rows_per_file: usize) -> ParquetDataset {
+        ParquetDataset { dir: dir.to_string(), rows_per_file, columns: None, partitions: BTreeMap::new() }
+    }
+
+    /// Adds a featurized chain: its `.dat` records, `.hbonds` file and, when it was written as a list, its `.contacts` file
+    pub fn add_chain(&mut self, summary: &ChainSummary) -> Result<(), FeaturizerError> {
+        let root = output_root(&summary.output);
+        let Some(columns) = read_columns(root) else {
+            warn!("{}.meta not found, {} is not converted to Parquet", root, summary.key);
+            return Ok(());
+        };
+        let names = feature_names(&columns);
+        let n_features = names.len();
+        // --- chains featurized with --numbering label have their numbers in the label_number column
+        let number_column = columns.get(3).cloned().unwrap_or_else(|| Numbering::Auth.column().to_string());
+        match &self.columns {
+            Some((number, known)) if number != &number_column || known != &names => {
+                warn!("{} has different columns than the other chains and is not converted to Parquet", summary.key);
+                return Ok(());
+            }
+            Some(_) => {}
+            None => self.columns = Some((number_column, names)),
+        }
+
+        let partition = self.partitions.entry(summary.source).or_default();
+        let (residues, pairs) = (&mut partition.residues, &mut partition.pairs);
+        if residues.features.is_empty() { residues.features = vec![vec![]; n_features]; }
+        for line in open_output(&summary.output)?.lines() {
+            let Some(record) = parse_record(&line?, n_features) else { continue };
+            residues.key.push(summary.key.clone());
+            residues.index.push(record.index as u32);
+            residues.residue.push(record.residue);
+            residues.residue_id.push(record.residue_id);
+            residues.number.push(record.author_number);
+            residues.ss.push(record.ss.to_string());
+            residues.x.push(record.ca[0]);
+            residues.y.push(record.ca[1]);
+            residues.z.push(record.ca[2]);
+            for (column, v) in residues.features.iter_mut().zip(record.features) { column.push(v); }
+        }
+        // --- an H-bond is given by the residue of this chain, i.e. by its donor unless the donor belongs to another chain
+        for hb in read_hbonds(root)? {
+            let (i, partner) = if hb.direction == HBondDirection::In { (hb.acceptor, hb.donor) } else { (hb.donor, hb.acceptor) };
+            let Ok(i) = i.parse::<u32>() else { continue };
+            pairs.key.push(summary.key.clone());
+            pairs.i.push(i);
+            pairs.partner.push(partner);
+            pairs.kind.push("hbond".to_string());
+            pairs.direction.push(Some(hb.direction.to_string()));
+            pairs.distance.push(hb.d_da);
+            pairs.energy.push(Some(hb.energy));
+            pairs.d_ha.push(Some(hb.d_ha));
+            pairs.angle_dha.push(Some(hb.angle_dha));
+            pairs.separation.push(hb.separation);
+            pairs.range.push(SeparationRange::of(hb.separation).to_string());
+        }
+        // --- a contact matrix has a line per residue instead of (i, j, distance) triplets
+        if let Some(contacts) = existing_file(&format!("{}.contacts", root)) {
+            for line in open_output(&contacts)?.lines() {
+                let line = line?;
+                let t: Vec<&str> = line.split_whitespace().collect();
+                // --- CB-based contacts are followed by virtual CB flags, then come the separation columns
+                let (&[i, j, d, separation, range] | &[i, j, d, _, _, separation, range]) = t.as_slice() else { break };
+                let (Ok(i), Ok(d), Some(separation)) = (i.parse::<u32>(), d.parse::<f64>(), parse_separation(separation, range)) else { break };
+                pairs.key.push(summary.key.clone());
+                pairs.i.push(i);
+                pairs.partner.push(j.to_string());
+                pairs.kind.push("contact".to_string());
+                pairs.direction.push(None);
+                pairs.distance.push(d);
+                pairs.energy.push(None);
+                pairs.d_ha.push(None);
+                pairs.angle_dha.push(None);
+                pairs.separation.push(separation);
+                pairs.range.push(range.to_string());
+            }
+        }
+        if partition.residues.key.len() >= self.rows_per_file { self.flush(summary.source)?; }
+
+        Ok(())
+    }
+
+    /// Writes rows collected for a partition to its next part files
+    fn flush(&mut self, source: Source) -> Result<(), FeaturizerError> {
+        let (number_column, names) = self.columns.clone().unwrap_or_default();
+        let Some(partition) = self.partitions.get_mut(&source) else { return Ok(()) };
+        if partition.residues.key.is_empty() { return Ok(()); }
+        let residues = std::mem::take(&mut partition.residues);
+        let pairs = std::mem::take(&mut partition.pairs);
+        let part = format!("source={}/part-{:05}.parquet", source, partition.n_files);
+        partition.n_files += 1;
+
+        let mut fields = vec![Field::new("key", DataType::Utf8, false), Field::new("index", DataType::UInt32, false),
+            Field::new("residue", DataType::Utf8, false), Field::new("residue_id", DataType::Utf8, false),
+            Field::new(&number_column, DataType::Utf8, false), Field::new("ss", DataType::Utf8, false), Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false), Field::new("z", DataType::Float64, false)];
+        fields.extend(names.iter().map(|n| Field::new(n, DataType::Float64, false)));
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from(residues.key)), Arc::new(UInt32Array::from(residues.index)),
+            Arc::new(StringArray::from(residues.residue)), Arc::new(StringArray::from(residues.residue_id)),
+            Arc::new(StringArray::from(residues.number)), Arc::new(StringArray::from(residues.ss)), Arc::new(Float64Array::from(residues.x)),
+            Arc::new(Float64Array::from(residues.y)), Arc::new(Float64Array::from(residues.z))];
+        columns.extend(residues.features.into_iter().map(|c| Arc::new(Float64Array::from(c)) as ArrayRef));
+        write_table(&Path::new(&self.dir).join("residues").join(&part), fields, columns)?;
+
+        let fields = vec![Field::new("key", DataType::Utf8, false), Field::new("i", DataType::UInt32, false),
+            Field::new("partner", DataType::Utf8, false), Field::new("kind", DataType::Utf8, false), Field::new("direction", DataType::Utf8, true),
+            Field::new("distance", DataType::Float64, false), Field::new("energy", DataType::Float64, true),
+            Field::new("d_ha", DataType::Float64, true), Field::new("angle_dha", DataType::Float64, true),
+            Field::new("separation", DataType::Int64, true), Field::new("range", DataType::Utf8, false)];
+        let columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from(pairs.key)), Arc::new(UInt32Array::from(pairs.i)),
+            Arc::new(StringArray::from(pairs.partner)), Arc::new(StringArray::from(pairs.kind)), Arc::new(StringArray::from(pairs.direction)),
+            Arc::new(Float64Array::from(pairs.distance)), Arc::new(Float64Array::from(pairs.energy)),
+            Arc::new(Float64Array::from(pairs.d_ha)), Arc::new(Float64Array::from(pairs.angle_dha)),
+            Arc::new(Int64Array::from(pairs.separation)), Arc::new(StringArray::from(pairs.range))];
+        write_table(&Path::new(&self.dir).join("pairs").join(&part), fields, columns)?;
+
+        Ok(())
+    }
+
+    /// Writes all the rows that are still buffered
+    pub fn finish(mut self) -> Result<(), FeaturizerError> {
+        let sources: Vec<Source> = self.partitions.keys().cloned().collect();
+        for source in sources { self.flush(source)?; }
+        let n_files: usize = self.partitions.values().map(|p| p.n_files).sum();
+        info!("{} Parquet part files of each table written to {}", n_files, self.dir);
+
+        Ok(())
+    }
+}
+
+/// Writes a single Parquet file, Snappy-compressed
+fn write_table(fname: &Path, fields: Vec<Field>, columns: Vec<ArrayRef>) -> Result<(), FeaturizerError> {
+    if let Some(dir) = fname.parent() { fs::create_dir_all(dir)?; }
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(arrow_error)?;
+    let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut writer = ArrowWriter::try_new(File::create(fname)?, batch.schema(), Some(props)).map_err(arrow_error)?;
+    writer.write(&batch).map_err(arrow_error)?;
+    writer.close().map_err(arrow_error)?;
+
+    Ok(())
+}
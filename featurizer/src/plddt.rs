@@ -0,0 +1,30 @@
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+
+/// pLDDT of a residue of a predicted model, stored in the B-factor column of its CA atom; NaN when CA is missing
+pub fn residue_plddt(strctr: &Structure, resid: &ResidueId) -> f64 {
+    strctr.atom(resid, " CA ").map_or(f64::NAN, |a| a.temp_factor)
+}
+
+/// Flags residues whose pLDDT is below `min_plddt`
+pub fn low_confidence(strctr: &Structure, resids: &[ResidueId], min_plddt: f64) -> Vec<bool> {
+    resids.iter().map(|r| residue_plddt(strctr, r) < min_plddt).collect()
+}
+
+/// Per-residue confidence of a predicted model (e.g. from AlphaFold DB), read from the B-factor column
+#[derive(Debug)]
+pub struct Plddt;
+
+impl FeatureExtractor for Plddt {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "plddt",
+            description: "predicted local distance difference test (pLDDT) of a residue of a predicted model, taken from the B-factor column of its CA atom",
+            shape: "[L]".to_string(), units: "", range: Some((0.0, 100.0)),
+            mask: "NaN when the CA atom is missing", option: "--plddt", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        chain.resids.iter().map(|r| vec![residue_plddt(chain.structure, r)]).collect()
+    }
+}
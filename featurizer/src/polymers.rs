@@ -0,0 +1,72 @@
+//! Polymer type of an entity: protein, nucleic acid or a hybrid of both, and which of them are featurized.
+//!
+//! The features assume amino acid residues, e.g. a CA atom of every residue, so by default (`--polymer protein`)
+//! chains of other entities are rejected before any of their outputs is written. With `--polymer all` they are
+//! featurized as well: nucleotides are written with their C4' atom in place of CA and the unknown residue type `X`.
+
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::residue_encoding::{aa_index, UNKNOWN_INDEX};
+
+/// Three-letter codes of standard ribo- and deoxyribonucleotides
+pub const NUCLEOTIDES: [&str; 11] = ["A", "C", "G", "U", "I", "DA", "DC", "DG", "DT", "DU", "DI"];
+
+/// Atom of a nucleotide written in place of CA
+pub const NUCLEOTIDE_REFERENCE_ATOM: &str = " C4'";
+
+/// Type of the polymer an entity is made of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolymerType {
+    Protein,
+    /// DNA, RNA or a DNA/RNA hybrid
+    NucleicAcid,
+    /// both amino acids and nucleotides, e.g. a peptide-nucleic acid conjugate
+    Hybrid,
+}
+
+impl fmt::Display for PolymerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolymerType::Protein => write!(f, "protein"),
+            PolymerType::NucleicAcid => write!(f, "nucleic acid"),
+            PolymerType::Hybrid => write!(f, "hybrid"),
+        }
+    }
+}
+
+/// True for a standard nucleotide given by its three-letter code
+pub fn is_nucleotide(code3: &str) -> bool { NUCLEOTIDES.contains(&code3.trim()) }
+
+/// Polymer type of an entity, judging by the three-letter codes of its residues.
+///
+/// Residues that are neither standard amino acids nor standard nucleotides, e.g. modified ones, are not counted;
+/// an entity of such residues only (e.g. of `UNK`) is taken for a protein.
+pub fn polymer_type<S: AsRef<str>>(codes: impl IntoIterator<Item = S>) -> PolymerType {
+    let (mut n_amino_acids, mut n_nucleotides) = (0, 0);
+    for code in codes {
+        let code = code.as_ref().trim();
+        if is_nucleotide(code) { n_nucleotides += 1; }
+        else if aa_index(code) != UNKNOWN_INDEX { n_amino_acids += 1; }
+    }
+    match (n_amino_acids, n_nucleotides) {
+        (_, 0) => PolymerType::Protein,
+        (0, _) => PolymerType::NucleicAcid,
+        _ => PolymerType::Hybrid,
+    }
+}
+
+/// Which chains are featurized, by the polymer type of their entity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PolymerPolicy {
+    /// protein chains only; nucleic acid and hybrid chains are skipped
+    #[default]
+    Protein,
+    /// every polymer chain
+    All,
+}
+
+impl PolymerPolicy {
+    pub fn accepts(&self, polymer: PolymerType) -> bool { *self == PolymerPolicy::All || polymer == PolymerType::Protein }
+}
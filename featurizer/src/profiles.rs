@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use bioshell_io::{open_file, read_whitespace_delimited_values};
+use log::warn;
+
+use crate::alignment::align_sequences;
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::residue_encoding::{ALPHABET, UNKNOWN_INDEX};
+
+/// Sequence profile: the query sequence and 20 amino acid frequencies for each of its positions
+pub struct Profile {
+    pub sequence: Vec<u8>,
+    pub frequencies: Vec<[f64; 20]>,
+}
+
+fn letter_index(aa: u8) -> usize {
+    ALPHABET.as_bytes().iter().position(|c| *c == aa).unwrap_or(UNKNOWN_INDEX)
+}
+
+/// Computes a profile from a multiple sequence alignment in the a3m format.
+///
+/// The first sequence of the alignment is the query; lowercase letters (insertions) and `.` are skipped,
+/// gaps are not counted in the frequencies.
+pub fn profile_from_a3m(fname: &str) -> Result<Profile, String> {
+    let reader = open_file(fname).map_err(|e| e.to_string())?;
+    let lines: Vec<Vec<String>> = read_whitespace_delimited_values(reader).map_err(|e| e.to_string())?;
+    let mut sequences: Vec<Vec<u8>> = vec![];
+    for line in lines {
+        if line.is_empty() { continue; }
+        if line[0].starts_with('>') { sequences.push(vec![]); continue; }
+        let Some(seq) = sequences.last_mut() else { continue };
+        seq.extend(line[0].bytes().filter(|c| c.is_ascii_uppercase() || *c == b'-'));
+    }
+    let Some(query) = sequences.first() else { return Err(format!("no sequences found in {}", fname)) };
+    let mut counts = vec![[0.0; 20]; query.len()];
+    for seq in sequences.iter().filter(|s| s.len() == query.len()) {
+        for (pos, aa) in seq.iter().enumerate() {
+            let k = letter_index(*aa);
+            if k < 20 { counts[pos][k] += 1.0; }
+        }
+    }
+    for c in counts.iter_mut() {
+        let total: f64 = c.iter().sum();
+        if total > 0.0 { c.iter_mut().for_each(|v| *v /= total); }
+    }
+
+    Ok(Profile { sequence: query.clone(), frequencies: counts })
+}
+
+/// Reads a profile from an ASCII PSSM file produced by PSI-BLAST.
+///
+/// The weighted observed percentages (the second block of 20 columns) are used, converted to frequencies.
+pub fn profile_from_pssm(fname: &str) -> Result<Profile, String> {
+    let reader = open_file(fname).map_err(|e| e.to_string())?;
+    let lines: Vec<Vec<String>> = read_whitespace_delimited_values(reader).map_err(|e| e.to_string())?;
+    let mut sequence = vec![];
+    let mut frequencies = vec![];
+    for line in lines {
+        // --- data rows: position, residue, 20 log-odds scores, 20 percentages and two more columns
+        if line.len() < 42 || line[0].parse::<usize>().is_err() { continue; }
+        sequence.push(line[1].as_bytes()[0]);
+        let mut f = [0.0; 20];
+        for k in 0..20 {
+            f[k] = line[22 + k].parse::<f64>().map_err(|e| format!("{} in {}", e, fname))? / 100.0;
+        }
+        frequencies.push(f);
+    }
+    if sequence.is_empty() { return Err(format!("no profile rows found in {}", fname)); }
+
+    Ok(Profile { sequence, frequencies })
+}
+
+/// Precomputed sequence profiles, appended as 20 amino acid frequencies per residue.
+///
+/// A profile of a chain is looked up in the `dir` folder as `<id>.a3m` or `<id>.pssm`, where `id` is the file name of the chain outputs
+/// such as `2gb1_A`, without the output directory.
+#[derive(Debug)]
+pub struct SequenceProfiles {
+    pub dir: String,
+}
+
+impl SequenceProfiles {
+    fn load(&self, id: &str) -> Option<Profile> {
+        let id = Path::new(id).file_name().and_then(|f| f.to_str()).unwrap_or(id);
+        for (ext, loader) in [("a3m", profile_from_a3m as fn(&str) -> Result<Profile, String>), ("pssm", profile_from_pssm)] {
+            let fname = Path::new(&self.dir).join(format!("{}.{}", id, ext));
+            if !fname.exists() { continue; }
+            match loader(fname.to_str().unwrap()) {
+                Ok(p) => return Some(p),
+                Err(e) => warn!("Can't load a profile from {:?}: {}", fname, e),
+            }
+        }
+        None
+    }
+}
+
+impl FeatureExtractor for SequenceProfiles {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "sequence_profile",
+            description: "amino acid frequencies from a precomputed MSA (a3m) or PSI-BLAST PSSM, aligned to the entity sequence; columns ordered as ARNDCQEGHILKMFPSTWYV",
+            shape: "[L, 20]".to_string(), units: "", range: Some((0.0, 1.0)),
+            mask: "NaN when no profile is found or a residue is not aligned to the profile", option: "--profiles <dir>", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let Some(profile) = self.load(chain.id) else {
+            warn!("No sequence profile found for {} in {}", chain.id, self.dir);
+            return vec![vec![f64::NAN; 20]; chain.resids.len()];
+        };
+        // --- map entity positions onto profile positions
+        let to_profile = align_sequences(chain.entity_sequence.as_bytes(), &profile.sequence);
+        chain.entity_index.iter().map(|e| match e.and_then(|e| to_profile[e]) {
+            Some(p) => profile.frequencies[p].to_vec(),
+            None => vec![f64::NAN; 20],
+        }).collect()
+    }
+
+    fn reads_files(&self) -> bool { true }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io::Write;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::PDBError;
+use sha2::{Digest, Sha256};
+
+use crate::atoms::Granularity;
+use crate::chains::{stdin_text, STDIN};
+use crate::featurize::FeaturizerConfig;
+use crate::metadata::EntityMetadata;
+
+/// Version of the featurizer, as given in its `Cargo.toml`
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Commit the featurizer was built from; `-dirty` is appended when the working tree had uncommitted changes
+pub const GIT_HASH: &str = env!("FEATURIZER_GIT_HASH");
+
+/// When the featurizer was built
+pub const BUILD_TIME: &str = env!("FEATURIZER_BUILD_TIME");
+
+/// SHA-256 checksum of an input file (or of the standard input when `fname` is `-`), as a hex string
+pub fn input_sha256(fname: &str) -> Result<String, PDBError> {
+    let digest = if fname == STDIN { Sha256::digest(stdin_text().as_bytes()) } else { Sha256::digest(fs::read(fname)?) };
+
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Names of the `.dat` columns, in the order they are written.
+///
+/// A feature of `[L, n]` shape gives `n` columns named `<feature>.0` to `<feature>.<n-1>`; H-bonds are written
+/// to a separate `.hbonds` file.
+pub fn column_schema(config: &FeaturizerConfig) -> Vec<String> {
+    let mut columns: Vec<String> = ["index", "residue", "residue_id", config.numbering.column(), ":", "ss", "x", "y", "z"].iter().map(|c| c.to_string()).collect();
+    for e in &config.extractors {
+        let info = e.info();
+        let width = info.shape.trim_matches(|c| c == '[' || c == ']').split(", ").nth(1).and_then(|n| n.parse::<usize>().ok());
+        match width {
+            Some(n) => columns.extend((0..n).map(|k| format!("{}.{}", info.name, k))),
+            None => columns.push(info.name.to_string()),
+        }
+    }
+
+    columns
+}
+
+/// Names of the `.atoms` columns, in the order they are written: those of an atom, then the features of its residue
+/// named as in [`column_schema()`]
+pub fn atom_column_schema(config: &FeaturizerConfig) -> Vec<String> {
+    let mut columns: Vec<String> = ["residue_index", "element", "atom_name", "x", "y", "z", "bfactor", ":"].iter().map(|c| c.to_string()).collect();
+    columns.extend(column_schema(config).into_iter().skip(9));
+
+    columns
+}
+
+/// Writes the provenance of a `.dat` file: everything needed to tell which featurizer build, settings and input produced it.
+///
+/// The file consists of `key = value` lines, so it may be read back with [`Config`](crate::config::Config). The chain
+/// line is followed by the metadata of its entity and deposit (`entity.*` and `deposit.*` keys, see [`EntityMetadata`]).
+pub fn write_provenance(fname: &str, chain: &str, metadata: &EntityMetadata, config: &FeaturizerConfig, out_fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(out_fname, false);
+    writeln!(outfile, "featurizer_version = {}", VERSION)?;
+    writeln!(outfile, "git_hash = {}", GIT_HASH)?;
+    writeln!(outfile, "build_time = {}", BUILD_TIME)?;
+    writeln!(outfile, "input = {}", fname)?;
+    writeln!(outfile, "input_sha256 = {}", input_sha256(fname)?)?;
+    writeln!(outfile, "chain = {}", chain)?;
+    write!(outfile, "{}", metadata)?;
+    writeln!(outfile, "config = {:?}", config)?;
+    writeln!(outfile, "columns = {}", column_schema(config).join(" "))?;
+    if config.granularity == Granularity::Atom { writeln!(outfile, "atom_columns = {}", atom_column_schema(config).join(" "))?; }
+    for e in &config.extractors {
+        let info = e.info();
+        writeln!(outfile, "feature.{} = {} v{}", info.name, info.shape, info.version)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Backbone geometry checks that catch pathological structures: clashing atoms, distorted bonds and implausible
+//! phi, psi angles. Chains failing them are rejected by the `--max-clashes`, `--max-bond-outliers`
+//! and `--max-rama-outliers` filters.
+
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::chains::residue_heavy_atoms;
+use crate::geometry::distance;
+use crate::ideal_backbone::{CA_C, C_N, C_O, MAX_CA_CA, N_CA};
+use crate::ramachandran::{phi_psi, rama_region, RamaRegion};
+
+/// Non-bonded heavy atoms closer than that clash
+pub const CLASH_DISTANCE: f64 = 1.9;
+
+/// A bond is an outlier when its length deviates from the ideal one by more than that many standard deviations
+pub const BOND_SIGMAS: f64 = 3.0;
+
+/// Ideal lengths and their standard deviations (Engh & Huber) of N-CA, CA-C, C-O and peptide C-N bonds
+const BONDS: [(f64, f64); 4] = [(N_CA, 0.019), (CA_C, 0.021), (C_O, 0.020), (C_N, 0.014)];
+
+/// Results of backbone geometry checks of a chain
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeometryQuality {
+    /// number of pairs of non-bonded heavy atoms closer than [`CLASH_DISTANCE`]
+    pub n_clashes: usize,
+    /// number of backbone bonds longer or shorter than ideal by more than [`BOND_SIGMAS`] standard deviations
+    pub n_bond_outliers: usize,
+    /// number of non-glycine residues whose phi, psi angles fall outside of all the Ramachandran regions, see [`rama_region()`]
+    pub n_rama_outliers: usize,
+}
+
+/// Counts clashes between heavy atoms of different residues; atoms of the peptide bond and alternate locations
+/// of the same atom are not counted
+fn count_clashes(strctr: &Structure, resids: &[ResidueId]) -> usize {
+    let atoms = residue_heavy_atoms(strctr, resids);
+    let cas: Vec<Option<&Vec3>> = atoms.iter().map(|res| res.iter().find(|a| a.name == " CA ").map(|a| &a.pos)).collect();
+    let mut n_clashes = 0;
+    for i in 0..atoms.len() {
+        for j in i + 1..atoms.len() {
+            // --- no heavy atom is farther than 8 Å from CA, so residues with distant CA atoms can't clash
+            if let (Some(a), Some(b)) = (cas[i], cas[j]) { if distance(a, b) > 2.0 * 8.0 + CLASH_DISTANCE { continue; } }
+            for a in &atoms[i] {
+                for b in &atoms[j] {
+                    if a.alt_loc != ' ' && b.alt_loc != ' ' && a.alt_loc != b.alt_loc { continue; }
+                    if j == i + 1 && a.name == " C  " && b.name == " N  " { continue; }
+                    if distance(&a.pos, &b.pos) < CLASH_DISTANCE { n_clashes += 1; }
+                }
+            }
+        }
+    }
+
+    n_clashes
+}
+
+/// Checks backbone geometry of a chain; the peptide bond and torsions are skipped at chain breaks
+pub fn geometry_quality(strctr: &Structure, resids: &[ResidueId]) -> GeometryQuality {
+    let backbone: Vec<[Option<Vec3>; 4]> = resids.iter()
+        .map(|r| [" N  ", " CA ", " C  ", " O  "].map(|name| strctr.atom(r, name).ok().map(|a| a.pos.clone())))
+        .collect();
+    let connected = |i: usize| matches!((&backbone[i][1], backbone.get(i + 1).and_then(|b| b[1].as_ref())),
+        (Some(a), Some(b)) if distance(a, b) <= MAX_CA_CA);
+
+    let mut quality = GeometryQuality { n_clashes: count_clashes(strctr, resids), ..Default::default() };
+    let mut check_bond = |k: usize, a: &Option<Vec3>, b: &Option<Vec3>| {
+        let (Some(a), Some(b)) = (a, b) else { return };
+        let (ideal, sigma) = BONDS[k];
+        if (distance(a, b) - ideal).abs() > BOND_SIGMAS * sigma { quality.n_bond_outliers += 1; }
+    };
+    for (i, [n, ca, c, o]) in backbone.iter().enumerate() {
+        check_bond(0, n, ca);
+        check_bond(1, ca, c);
+        check_bond(2, c, o);
+        if connected(i) { check_bond(3, c, &backbone[i + 1][0]); }
+    }
+    for (r, angles) in resids.iter().zip(phi_psi(strctr, resids)) {
+        let Some((phi, psi)) = angles else { continue };
+        if strctr.atom(r, " CA ").is_ok_and(|a| a.res_name == "GLY") { continue; }
+        if rama_region(phi, psi) == RamaRegion::Outlier { quality.n_rama_outliers += 1; }
+    }
+
+    quality
+}
@@ -0,0 +1,68 @@
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{ResidueId, Structure};
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{dihedral_angle, distance};
+use crate::ideal_backbone::MAX_CA_CA;
+
+/// A named basin of the Ramachandran plot; the discriminant is the label written to the output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamaRegion {
+    Alpha = 0,
+    Beta = 1,
+    LeftAlpha = 2,
+    Ppii = 3,
+    Outlier = 4,
+}
+
+/// Regions of the Ramachandran plot, given as (phi_min, phi_max, psi_min, psi_max) in degrees; the first matching one is used
+const REGIONS: [(f64, f64, f64, f64, RamaRegion); 5] = [
+    (-180.0, -90.0, 90.0, 180.0, RamaRegion::Beta),
+    (-90.0, -45.0, 90.0, 180.0, RamaRegion::Ppii),
+    // --- beta region wrapped around psi = 180
+    (-180.0, -45.0, -180.0, -150.0, RamaRegion::Beta),
+    (-180.0, -20.0, -120.0, 50.0, RamaRegion::Alpha),
+    (20.0, 120.0, -60.0, 100.0, RamaRegion::LeftAlpha),
+];
+
+/// Classifies phi, psi angles into a basin of the Ramachandran plot
+pub fn rama_region(phi: f64, psi: f64) -> RamaRegion {
+    REGIONS.iter().find(|(phi_min, phi_max, psi_min, psi_max, _)| phi >= *phi_min && phi <= *phi_max && psi >= *psi_min && psi <= *psi_max)
+        .map_or(RamaRegion::Outlier, |r| r.4)
+}
+
+/// Phi and psi angles of every residue; `None` for terminal residues, at chain breaks and when a backbone atom is missing
+pub fn phi_psi(strctr: &Structure, resids: &[ResidueId]) -> Vec<Option<(f64, f64)>> {
+    let backbone: Vec<[Option<Vec3>; 3]> = resids.iter()
+        .map(|r| [" N  ", " CA ", " C  "].map(|name| strctr.atom(r, name).ok().map(|a| a.pos.clone())))
+        .collect();
+    let connected = |i: usize| matches!((&backbone[i][1], backbone.get(i + 1).and_then(|b| b[1].as_ref())),
+        (Some(a), Some(b)) if distance(a, b) <= MAX_CA_CA);
+
+    (0..backbone.len()).map(|i| {
+        if i == 0 || !connected(i - 1) || !connected(i) { return None; }
+        let (Some(c_prev), Some(n), Some(ca), Some(c), Some(n_next)) =
+            (&backbone[i - 1][2], &backbone[i][0], &backbone[i][1], &backbone[i][2], &backbone[i + 1][0]) else { return None };
+        Some((dihedral_angle(c_prev, n, ca, c), dihedral_angle(n, ca, c, n_next)))
+    }).collect()
+}
+
+/// Ramachandran basin of every residue: alpha (0), beta (1), left-handed alpha (2), polyproline II (3) or outlier (4).
+///
+/// Gives the trainer a discrete torsion target; the regions are generous, so the outlier class marks truly unusual backbones.
+#[derive(Debug)]
+pub struct RamachandranRegions;
+
+impl FeatureExtractor for RamachandranRegions {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "rama_region",
+            description: "Ramachandran basin of the residue's phi, psi angles: 0 alpha, 1 beta, 2 left-handed alpha, 3 polyproline II, 4 outlier",
+            shape: "[L, 1]".to_string(), units: "", range: Some((0.0, 4.0)),
+            mask: "NaN when phi or psi is undefined: at chain termini and breaks", option: "--rama-regions", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        phi_psi(chain.structure, chain.resids).iter()
+            .map(|a| vec![a.map_or(f64::NAN, |(phi, psi)| rama_region(phi, psi) as u8 as f64)]).collect()
+    }
+}
@@ -0,0 +1,215 @@
+//! Structures rebuilt from featurizer outputs, written by the `rebuild-pdb` subcommand for visual checks.
+//!
+//! A chain is rebuilt from its `.dat` records: the CA atom of every written residue, at the coordinates of the record
+//! (i.e. in the frame the chain was featurized in), and its secondary structure as `HELIX` and `SHEET` records
+//! (`_struct_conf` and `_struct_sheet_range` of mmCIF). When the chain was featurized with `--granularity atom`,
+//! all heavy atoms of its `.atoms` file are written instead of CA alone. Gaps and masked residues are left out,
+//! so indexing, gaps and frames of a featurization may be checked against the input deposit, e.g. in PyMOL.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use bioshell_io::out_writer;
+
+use crate::compression::{existing_file, open_output};
+use crate::errors::FeaturizerError;
+use crate::records::parse_record;
+
+/// An atom of a rebuilt residue
+#[derive(Clone, Debug)]
+pub struct RebuiltAtom {
+    /// atom name, e.g. `CA`
+    pub name: String,
+    pub element: char,
+    pub pos: [f64; 3],
+    pub bfactor: f64,
+}
+
+/// A residue of a rebuilt chain
+#[derive(Clone, Debug)]
+pub struct RebuiltResidue {
+    /// index of the residue in the chain, as in the `.dat` file
+    pub index: usize,
+    /// three-letter code of the residue
+    pub name: String,
+    /// residue number and insertion code, from the numbering column of the record
+    pub number: i32,
+    pub i_code: char,
+    /// secondary structure: H, E, C or `-` when it was masked
+    pub ss: char,
+    pub atoms: Vec<RebuiltAtom>,
+}
+
+/// A chain rebuilt from its outputs
+#[derive(Clone, Debug)]
+pub struct RebuiltChain {
+    /// chain ID, as given in the `.meta` file; `A` when there is no such file
+    pub chain: String,
+    pub residues: Vec<RebuiltResidue>,
+}
+
+/// Reads a file that may be compressed
+fn read_output(fname: &str) -> io::Result<String> {
+    let mut text = String::new();
+    open_output(fname)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Splits a residue number of a record, e.g. `100A`, into the number and the insertion code
+fn residue_number(number: &str) -> (i32, char) {
+    let digits = number.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    (digits.parse().unwrap_or(0), number[digits.len()..].chars().next().unwrap_or(' '))
+}
+
+/// Three-letter code of the residue of a record: the first three-letter word of its entity residue column
+fn residue_name(residue: &str) -> String {
+    let mut words = residue.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty());
+    let name = words.clone().find(|w| w.len() == 3).or_else(|| words.next()).unwrap_or("UNK");
+    name.chars().take(3).collect()
+}
+
+/// Heavy atoms of every residue given in an `.atoms` file, by the index of the residue
+fn read_atoms(text: &str) -> HashMap<usize, Vec<RebuiltAtom>> {
+    let mut atoms: HashMap<usize, Vec<RebuiltAtom>> = HashMap::new();
+    for line in text.lines() {
+        let Some((atom, _)) = line.split_once(" :") else { continue };
+        let t: Vec<&str> = atom.split_whitespace().collect();
+        let &[index, element, name, x, y, z, bfactor] = t.as_slice() else { continue };
+        let (Ok(index), Ok(x), Ok(y), Ok(z)) = (index.parse::<usize>(), x.parse(), y.parse(), z.parse()) else { continue };
+        atoms.entry(index).or_default().push(RebuiltAtom { name: name.to_string(), element: element.chars().next().unwrap_or('X'),
+            pos: [x, y, z], bfactor: bfactor.parse().unwrap_or(0.0) });
+    }
+
+    atoms
+}
+
+/// Rebuilds a chain from the outputs of a given root name, e.g. `out/2gb1_A`; the `.dat` and `.atoms` files may be compressed
+pub fn rebuild_chain(out_root: &str) -> Result<RebuiltChain, FeaturizerError> {
+    let dat = existing_file(&format!("{}.dat", out_root))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no .dat file found for {}", out_root)))?;
+    let chain = fs::read_to_string(format!("{}.meta", out_root)).ok()
+        .and_then(|meta| meta.lines().find_map(|l| l.strip_prefix("chain = ").map(|c| c.trim().to_string())))
+        .unwrap_or_else(|| "A".to_string());
+    let mut atoms = match existing_file(&format!("{}.atoms", out_root)) {
+        Some(fname) => read_atoms(&read_output(&fname)?),
+        None => HashMap::new(),
+    };
+    let mut residues = vec![];
+    // --- only the leading columns are needed, so no feature column is parsed
+    for record in read_output(&dat)?.lines().filter_map(|l| parse_record(l, 0)) {
+        let (number, i_code) = residue_number(&record.author_number);
+        let name = residue_name(&record.residue);
+        let atoms = atoms.remove(&record.index).unwrap_or_else(|| vec![RebuiltAtom { name: "CA".to_string(), element: 'C', pos: record.ca, bfactor: 0.0 }]);
+        residues.push(RebuiltResidue { index: record.index, name, number, i_code, ss: record.ss, atoms });
+    }
+
+    Ok(RebuiltChain { chain, residues })
+}
+
+/// Runs of consecutive residues of the same secondary structure `code`, as (first, last) indexes in `residues`
+fn ss_runs(residues: &[RebuiltResidue], code: char) -> Vec<(usize, usize)> {
+    let mut runs = vec![];
+    let mut start: Option<usize> = None;
+    for i in 0..=residues.len() {
+        let inside = i < residues.len() && residues[i].ss == code && (start.is_none() || residues[i].index == residues[i - 1].index + 1);
+        if inside {
+            start.get_or_insert(i);
+            continue;
+        }
+        if let Some(s) = start.take() { runs.push((s, i - 1)); }
+        // --- a residue that doesn't follow the previous one starts a new run
+        if i < residues.len() && residues[i].ss == code { start = Some(i); }
+    }
+
+    runs
+}
+
+/// Atom name aligned as in the columns 13-16 of a PDB record: names of one-letter elements start at the column 14
+fn pdb_atom_name(name: &str) -> String {
+    if name.len() < 4 { format!(" {:<3}", name) } else { format!("{:<4}", name) }
+}
+
+/// Writes a rebuilt chain as a PDB file
+pub fn write_pdb(chain: &RebuiltChain, fname: &str) -> Result<(), FeaturizerError> {
+    let mut outfile = out_writer(fname, false);
+    let residues = &chain.residues;
+    let chain_id = chain.chain.chars().next().unwrap_or('A');
+    for (k, (first, last)) in ss_runs(residues, 'H').into_iter().enumerate() {
+        let (a, b) = (&residues[first], &residues[last]);
+        writeln!(outfile, "HELIX  {:>3} {:>3} {:>3} {} {:>4}{} {:>3} {} {:>4}{}{:>2}{:30} {:>5}", k + 1, k + 1, a.name, chain_id, a.number, a.i_code,
+                 b.name, chain_id, b.number, b.i_code, 1, "", last - first + 1)?;
+    }
+    // --- strands are written as one-strand sheets, since the pairing of strands isn't stored
+    for (k, (first, last)) in ss_runs(residues, 'E').into_iter().enumerate() {
+        let (a, b) = (&residues[first], &residues[last]);
+        writeln!(outfile, "SHEET  {:>3} {:>3}{:>2} {:>3} {}{:>4}{} {:>3} {}{:>4}{}{:>2}", 1, format!("S{}", k + 1), 1, a.name, chain_id, a.number, a.i_code,
+                 b.name, chain_id, b.number, b.i_code, 0)?;
+    }
+    let mut serial = 0;
+    for r in residues {
+        for a in &r.atoms {
+            serial += 1;
+            writeln!(outfile, "ATOM  {:>5} {} {:>3} {}{:>4}{}   {:8.3}{:8.3}{:8.3}{:6.2}{:6.2}          {:>2}", serial, pdb_atom_name(&a.name),
+                     r.name, chain_id, r.number, r.i_code, a.pos[0], a.pos[1], a.pos[2], 1.0, a.bfactor, a.element)?;
+        }
+    }
+    if let Some(r) = residues.last() { writeln!(outfile, "TER   {:>5}      {:>3} {}{:>4}{}", serial + 1, r.name, chain_id, r.number, r.i_code)?; }
+    writeln!(outfile, "END")?;
+
+    Ok(())
+}
+
+/// A value of an mmCIF loop, quoted when it holds a quote, e.g. `"C4'"`
+fn cif_value(value: &str) -> String {
+    if value.contains('\'') { format!("\"{}\"", value) } else { value.to_string() }
+}
+
+/// Writes a rebuilt chain as an mmCIF file; the residue numbers of the records are given as both label and author numbers
+pub fn write_cif(chain: &RebuiltChain, name: &str, fname: &str) -> Result<(), FeaturizerError> {
+    let mut outfile = out_writer(fname, false);
+    let residues = &chain.residues;
+    let asym = &chain.chain;
+    writeln!(outfile, "data_{}", name)?;
+    let helices = ss_runs(residues, 'H');
+    if !helices.is_empty() {
+        writeln!(outfile, "#\nloop_")?;
+        for item in ["conf_type_id", "id", "beg_label_comp_id", "beg_label_asym_id", "beg_label_seq_id", "end_label_comp_id",
+                     "end_label_asym_id", "end_label_seq_id", "beg_auth_asym_id", "beg_auth_seq_id", "end_auth_asym_id", "end_auth_seq_id"] {
+            writeln!(outfile, "_struct_conf.{}", item)?;
+        }
+        for (k, (first, last)) in helices.into_iter().enumerate() {
+            let (a, b) = (&residues[first], &residues[last]);
+            writeln!(outfile, "HELX_P HELX_P{} {} {} {} {} {} {} {} {} {} {}", k + 1, a.name, asym, a.number, b.name, asym, b.number, asym, a.number, asym, b.number)?;
+        }
+    }
+    let strands = ss_runs(residues, 'E');
+    if !strands.is_empty() {
+        writeln!(outfile, "#\nloop_")?;
+        for item in ["sheet_id", "id", "beg_label_comp_id", "beg_label_asym_id", "beg_label_seq_id", "end_label_comp_id",
+                     "end_label_asym_id", "end_label_seq_id", "beg_auth_asym_id", "beg_auth_seq_id", "end_auth_asym_id", "end_auth_seq_id"] {
+            writeln!(outfile, "_struct_sheet_range.{}", item)?;
+        }
+        for (k, (first, last)) in strands.into_iter().enumerate() {
+            let (a, b) = (&residues[first], &residues[last]);
+            writeln!(outfile, "S{} 1 {} {} {} {} {} {} {} {} {} {}", k + 1, a.name, asym, a.number, b.name, asym, b.number, asym, a.number, asym, b.number)?;
+        }
+    }
+    writeln!(outfile, "#\nloop_")?;
+    for item in ["group_PDB", "id", "type_symbol", "label_atom_id", "label_comp_id", "label_asym_id", "label_seq_id", "pdbx_PDB_ins_code",
+                 "Cartn_x", "Cartn_y", "Cartn_z", "occupancy", "B_iso_or_equiv", "auth_seq_id", "auth_asym_id", "pdbx_PDB_model_num"] {
+        writeln!(outfile, "_atom_site.{}", item)?;
+    }
+    let mut serial = 0;
+    for r in residues {
+        let i_code = if r.i_code == ' ' { '?' } else { r.i_code };
+        for a in &r.atoms {
+            serial += 1;
+            writeln!(outfile, "ATOM {} {} {} {} {} {} {} {:.3} {:.3} {:.3} 1.00 {:.2} {} {} 1", serial, a.element, cif_value(&a.name), r.name, asym,
+                     r.number, i_code, a.pos[0], a.pos[1], a.pos[2], a.bfactor, r.number, asym)?;
+        }
+    }
+    writeln!(outfile, "#")?;
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use clap::ValueEnum;
+
+use crate::compression::{existing_file, open_output};
+use crate::hbonds::HBondPair;
+
+/// A single record of a `.dat` file, i.e. an observed residue
+#[derive(Clone, Debug)]
+pub struct DatRecord {
+    pub index: usize,
+    /// the entity residue, as written in the second column
+    pub residue: String,
+    /// ID of the residue in the structure
+    pub residue_id: String,
+    /// number of the residue: the author numbering, including its insertion code (e.g. `100A`), or the label numbering
+    /// when the chain was featurized with `--numbering label`; see [`Numbering::column()`]
+    pub author_number: String,
+    pub ss: char,
+    pub ca: [f64; 3],
+    pub features: Vec<f64>,
+}
+
+/// Names of the `.dat` columns of a chain, read from the `columns` line of its `.meta` file
+pub fn read_columns(out_root: &str) -> Option<Vec<String>> {
+    let meta = fs::read_to_string(format!("{}.meta", out_root)).ok()?;
+    let columns = meta.lines().find_map(|l| l.strip_prefix("columns = "))?;
+
+    Some(columns.split_whitespace().map(|c| c.to_string()).collect())
+}
+
+/// Names of the feature columns of a `.dat` file: those following the CA coordinates, up to the trailing hbonds
+/// of files written before H-bonds were moved to `.hbonds` files
+pub fn feature_names(columns: &[String]) -> Vec<String> {
+    let first = columns.iter().position(|c| c == "z").map_or(columns.len(), |k| k + 1);
+    let last = columns.iter().rposition(|c| c == "hbonds").filter(|k| *k >= first).unwrap_or(columns.len());
+
+    columns[first..last].to_vec()
+}
+
+/// Number of feature columns of a `.dat` file, see [`feature_names()`]
+pub fn feature_columns(columns: &[String]) -> usize { feature_names(columns).len() }
+
+/// Parses a `.dat` line with `n_features` feature values; returns `None` for gaps and masked residues
+pub fn parse_record(line: &str, n_features: usize) -> Option<DatRecord> {
+    let (left, data) = line.split_once(" : ")?;
+    let left = left.trim_start();
+    let (index, rest) = left.split_once(' ')?;
+    // --- the entity residue is padded to 7 characters; the author numbering comes last
+    let (residue, rest) = if rest.len() > 8 { rest.split_at(7) } else { (rest, "") };
+    let (residue_id, author_number) = rest.trim_end().rsplit_once(' ').unwrap_or((rest, ""));
+    let t: Vec<&str> = data.split_whitespace().collect();
+    if t.len() < 4 + n_features { return None; }
+    let value = |v: &str| v.parse::<f64>().unwrap_or(f64::NAN);
+
+    Some(DatRecord { index: index.parse().ok()?, residue: residue.trim().to_string(), residue_id: residue_id.trim().to_string(),
+        author_number: author_number.to_string(),
+        ss: t[0].chars().next()?, ca: [value(t[1]), value(t[2]), value(t[3])],
+        features: t[4..4 + n_features].iter().map(|v| value(v)).collect() })
+}
+
+/// Backbone H-bonds of a chain, read from its `.hbonds` file, which may be compressed; empty when there's no such file
+pub fn read_hbonds(out_root: &str) -> io::Result<Vec<HBondPair>> {
+    let Some(fname) = existing_file(&format!("{}.hbonds", out_root)) else { return Ok(vec![]) };
+    let mut hbonds = vec![];
+    for line in open_output(&fname)?.lines() {
+        if let Some(hb) = HBondPair::parse(&line?) { hbonds.push(hb); }
+    }
+
+    Ok(hbonds)
+}
+
+/// Residue numbering of the `.dat` records
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Numbering {
+    /// author numbering (`auth_seq_id`), followed by the insertion code, if any
+    #[default]
+    Auth,
+    /// mmCIF label numbering (`label_seq_id`): the 1-based position of a residue in its entity sequence
+    Label,
+}
+
+impl Numbering {
+    /// Name of the `.dat` column with residue numbers
+    pub fn column(&self) -> &'static str {
+        match self {
+            Numbering::Auth => "author_number",
+            Numbering::Label => "label_number",
+        }
+    }
+}
+
+/// When the records of a chain are written out, see [`RecordWriter`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FlushPolicy {
+    /// records of a chain are formatted in memory and written at once
+    #[default]
+    Chain,
+    /// every record is written and flushed as soon as it's formatted, e.g. for a process reading the standard output
+    Residue,
+}
+
+/// Writes `.dat` records to a file or a stream in large blocks rather than in many small writes.
+///
+/// Formatted records are collected in memory and written at once by [`finish()`](RecordWriter::finish),
+/// unless [`FlushPolicy::Residue`] is requested.
+pub struct RecordWriter {
+    out: Box<dyn Write>,
+    buffer: String,
+    policy: FlushPolicy,
+}
+
+impl RecordWriter {
+    pub fn new(out: Box<dyn Write>, policy: FlushPolicy) -> RecordWriter {
+        RecordWriter { out, buffer: String::with_capacity(64 * 1024), policy }
+    }
+
+    /// Appends a record; the line must not end with a newline
+    pub fn push(&mut self, line: &str) -> io::Result<()> {
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        if self.policy == FlushPolicy::Residue { self.write_buffer()?; self.out.flush()?; }
+
+        Ok(())
+    }
+
+    /// Writes all the pending records and flushes the underlying writer, which is closed afterwards
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_buffer()?;
+        self.out.flush()
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        self.out.write_all(self.buffer.as_bytes())?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use bioshell_io::open_file;
+use bioshell_pdb::PDBError;
+use log::warn;
+
+use crate::assembly::read_cif_category;
+use crate::chains::STDIN;
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+/// A calendar date, e.g. of the initial release of a PDB entry; written as `YYYY-MM-DD`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReleaseDate {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl ReleaseDate {
+    /// The date as a single number `YYYYMMDD`, which orders just like dates do
+    pub fn as_number(&self) -> f64 { (self.year * 10000 + self.month * 100 + self.day) as f64 }
+
+    /// Reverts [`as_number()`](ReleaseDate::as_number)
+    pub fn from_number(v: f64) -> ReleaseDate {
+        let v = v as u32;
+        ReleaseDate { year: v / 10000, month: v / 100 % 100, day: v % 100 }
+    }
+
+    /// Parses a date of a PDB header, e.g. `15-APR-93`; two-digit years below 50 are taken as 20xx
+    pub fn from_pdb(s: &str) -> Option<ReleaseDate> {
+        let mut tokens = s.trim().split('-');
+        let day: u32 = tokens.next()?.parse().ok()?;
+        let month = MONTHS.iter().position(|m| Some(*m) == tokens.next())? as u32 + 1;
+        let year: u32 = tokens.next()?.parse().ok()?;
+        let year = if year < 50 { 2000 + year } else { 1900 + year };
+        Some(ReleaseDate { year, month, day })
+    }
+}
+
+impl FromStr for ReleaseDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{} is not a date given as YYYY-MM-DD", s);
+        let tokens: Vec<&str> = s.trim().split('-').collect();
+        if tokens.len() != 3 { return Err(error()); }
+        let year: u32 = tokens[0].parse().map_err(|_| error())?;
+        let month: u32 = tokens[1].parse().map_err(|_| error())?;
+        let day: u32 = tokens[2].parse().map_err(|_| error())?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) { return Err(error()); }
+        Ok(ReleaseDate { year, month, day })
+    }
+}
+
+impl fmt::Display for ReleaseDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Date of the initial release of a deposit.
+///
+/// For an mmCIF file that's the earliest `revision_date` of the `_pdbx_audit_revision_history` category, for a PDB file
+/// the date of the first `REVDAT` record. `None` is returned when the file gives no release date, e.g. for a model
+/// which has never been released, and for a deposit read from the standard input, which can't be read again.
+pub fn release_date(fname: &str) -> Result<Option<ReleaseDate>, PDBError> {
+    if fname == STDIN {
+        warn!("Release date can't be found for a deposit read from the standard input");
+        return Ok(None);
+    }
+    let revisions = read_cif_category(fname, "_pdbx_audit_revision_history")?;
+    if !revisions.is_empty() {
+        return Ok(revisions.iter().filter_map(|r| r.get("revision_date")?.parse::<ReleaseDate>().ok()).min());
+    }
+    // --- REVDAT records are listed from the latest modification, the initial release is numbered 1
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        if line.starts_with("REVDAT") && line.get(7..10).is_some_and(|n| n.trim() == "1") {
+            return Ok(line.get(13..22).and_then(ReleaseDate::from_pdb));
+        }
+        if line.starts_with("ATOM") { break; }
+    }
+
+    Ok(None)
+}
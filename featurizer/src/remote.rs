@@ -0,0 +1,112 @@
+//! Entry metadata looked up in the RCSB Data API, for filters that need what a local file doesn't give.
+//!
+//! Mirrors are heterogeneous: minimized PDB files have no `REMARK 2` resolution, some mmCIF files are stripped of their
+//! revision history. Responses of the API are cached on disk, one JSON file per entry, so every entry is requested at most
+//! once whatever the number of runs, and requests are spaced by a minimum interval to stay within the limits of the service.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+
+use crate::release::ReleaseDate;
+use crate::sync::fetch_text;
+
+/// Where the RCSB describes an entry, e.g. `https://data.rcsb.org/rest/v1/core/entry/2gb1`
+pub const RCSB_ENTRY_URL: &str = "https://data.rcsb.org/rest/v1/core/entry";
+
+/// Minimum interval between two requests to the RCSB, in milliseconds
+pub const DEFAULT_INTERVAL_MS: u64 = 200;
+
+/// When the last request to the RCSB was sent, shared by all the workers of a run
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Metadata of a PDB entry given by the RCSB
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryMetadata {
+    /// the best resolution of the entry in Å, `None` e.g. for NMR entries
+    pub resolution: Option<f64>,
+    pub release_date: Option<ReleaseDate>,
+}
+
+/// The value following a key of a JSON text, as the text that follows the colon
+fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let rest = json.split_once(&format!("\"{}\"", key))?.1;
+    Some(rest.trim_start().strip_prefix(':')?.trim_start())
+}
+
+/// Reads entry metadata from an RCSB description of an entry: the first value of `resolution_combined` and the date
+/// of `initial_release_date`
+pub fn parse_entry(json: &str) -> EntryMetadata {
+    let resolution = json_value(json, "resolution_combined").and_then(|v| {
+        let list = v.strip_prefix('[')?.split_once(']')?.0;
+        list.split(',').next()?.trim().parse::<f64>().ok()
+    });
+    let release_date = json_value(json, "initial_release_date")
+        .and_then(|v| v.strip_prefix('"')?.get(..10)?.parse::<ReleaseDate>().ok());
+
+    EntryMetadata { resolution, release_date }
+}
+
+/// True for a four-character PDB ID, e.g. `2gb1`
+pub fn is_pdb_code(code: &str) -> bool {
+    code.len() == 4 && code.starts_with(|c: char| c.is_ascii_digit()) && code.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// PDB ID of a deposit file named by it, e.g. `2gb1` for `mirror/2gb1.cif.gz` or `mirror/pdb2gb1.ent`; `None` for other names
+pub fn entry_code(fname: &str) -> Option<String> {
+    let file_name = Path::new(fname).file_name()?.to_str()?;
+    let stem = file_name.split('.').next()?.to_lowercase();
+    let code: &str = if stem.len() == 7 { stem.strip_prefix("pdb").unwrap_or(&stem) } else { &stem };
+
+    is_pdb_code(code).then(|| code.to_string())
+}
+
+/// Looks entries up in the RCSB Data API, caching the responses in a directory
+#[derive(Clone, Debug)]
+pub struct RemoteMetadata {
+    /// directory of cached responses, named `<pdb-code>.json`
+    pub cache_dir: String,
+    /// minimum interval between two requests
+    pub interval: Duration,
+}
+
+impl RemoteMetadata {
+    pub fn new(cache_dir: &str, interval_ms: u64) -> RemoteMetadata {
+        RemoteMetadata { cache_dir: cache_dir.to_string(), interval: Duration::from_millis(interval_ms) }
+    }
+
+    /// Metadata of an entry, read from the cache or requested from the RCSB; `None` when the request fails
+    pub fn entry(&self, code: &str) -> Option<EntryMetadata> {
+        let code = code.to_lowercase();
+        let fname = Path::new(&self.cache_dir).join(format!("{}.json", code));
+        if let Ok(json) = fs::read_to_string(&fname) {
+            debug!("Metadata of {} read from {}", code, fname.display());
+            return Some(parse_entry(&json));
+        }
+        self.wait_turn();
+        let json = match fetch_text(&format!("{}/{}", RCSB_ENTRY_URL, code)) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Can't look up metadata of {} at the RCSB: {}", code, e);
+                return None;
+            }
+        };
+        // --- a failure to cache a response only costs a request next time
+        if let Err(e) = fs::create_dir_all(&self.cache_dir).and_then(|_| fs::write(&fname, &json)) {
+            warn!("Can't cache metadata of {} in {}: {}", code, fname.display(), e);
+        }
+
+        Some(parse_entry(&json))
+    }
+
+    /// Waits until the interval since the last request of any worker has passed
+    fn wait_turn(&self) {
+        let mut last = LAST_REQUEST.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(elapsed) = last.map(|t| t.elapsed()).filter(|e| *e < self.interval) { thread::sleep(self.interval - elapsed); }
+        *last = Some(Instant::now());
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use bioshell_pdb::{PdbAtom, ResidueId, Structure};
+use log::debug;
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::geometry::{add, distance, nerf, scale, sub, unit};
+use crate::ideal_backbone::{CA_C_O, C_N, C_O};
+use crate::quality::BOND_SIGMAS;
+
+/// Standard deviation of the peptide C-N bond length (Engh & Huber), in Å
+const C_N_SIGMA: f64 = 0.014;
+
+/// Atoms of consecutive residues farther apart than this are not bonded; such a gap is a chain break, not a distorted bond
+pub const MAX_PEPTIDE_BOND: f64 = 2.0;
+
+/// What has been repaired in a residue by [`repair_backbone()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResidueRepairs {
+    /// the backbone O atom was missing and has been rebuilt from CA and C of the residue and N of the next one
+    pub rebuilt_o: bool,
+    /// the peptide bond to the previous residue has been set to the ideal length by moving N (and its H) of this residue
+    pub peptide_bond: bool,
+}
+
+/// Residues repaired by [`repair_backbone()`], by chain ID, residue number and insertion code
+#[derive(Clone, Debug, Default)]
+pub struct BackboneRepairs {
+    repaired: HashMap<(String, i32, char), ResidueRepairs>,
+}
+
+impl BackboneRepairs {
+    /// Repairs of every residue of a chain, in the order of `resids`
+    pub fn of_chain(&self, chain: &str, resids: &[ResidueId]) -> Vec<ResidueRepairs> {
+        resids.iter().map(|r| self.repaired.get(&(chain.to_string(), r.res_seq, r.i_code)).copied().unwrap_or_default()).collect()
+    }
+
+    /// Number of rebuilt O atoms and of standardized peptide bonds
+    pub fn counts(&self) -> (usize, usize) {
+        (self.repaired.values().filter(|r| r.rebuilt_o).count(), self.repaired.values().filter(|r| r.peptide_bond).count())
+    }
+}
+
+/// Index of the first atom of a given name among atoms of a residue
+fn find(atoms: &[PdbAtom], residue: &[usize], name: &str) -> Option<usize> {
+    residue.iter().copied().find(|k| atoms[*k].name == name)
+}
+
+/// Fixes trivially broken backbone geometry of all the chains of a structure.
+///
+/// Two repairs are made. A peptide C-N bond whose length deviates from the ideal one by more than [`BOND_SIGMAS`]
+/// standard deviations, but is shorter than [`MAX_PEPTIDE_BOND`], is set to the ideal length by moving N of the latter
+/// residue (along with its amide H) along the bond. Then a missing O atom is rebuilt in the peptide plane from CA and C
+/// of its residue and N of the next one, with ideal C=O length and CA-C=O angle; O of the last residue of a chain
+/// can't be placed this way and stays missing. Atoms are never removed, and residues without these defects are untouched.
+pub fn repair_backbone(strctr: &Structure) -> (Structure, BackboneRepairs) {
+    let mut atoms: Vec<PdbAtom> = strctr.atoms().to_vec();
+    // --- indexes of atoms of every residue; atoms of a residue are listed together
+    let key = |a: &PdbAtom| (a.chain_id.clone(), a.res_seq, a.i_code);
+    let mut residues: Vec<Vec<usize>> = vec![];
+    for (k, a) in atoms.iter().enumerate() {
+        match residues.last_mut() {
+            Some(last) if key(&atoms[last[0]]) == key(a) => last.push(k),
+            _ => residues.push(vec![k]),
+        }
+    }
+    let mut repairs = BackboneRepairs::default();
+    let mut rebuilt: Vec<(usize, PdbAtom)> = vec![];
+    for pair in residues.windows(2) {
+        let (this, next) = (&pair[0], &pair[1]);
+        if atoms[this[0]].chain_id != atoms[next[0]].chain_id { continue; }
+        let (Some(c), Some(n)) = (find(&atoms, this, " C  "), find(&atoms, next, " N  ")) else { continue };
+        let d = distance(&atoms[c].pos, &atoms[n].pos);
+        if d >= MAX_PEPTIDE_BOND { continue; }
+        if (d - C_N).abs() > BOND_SIGMAS * C_N_SIGMA {
+            let shift = sub(&add(&atoms[c].pos, &scale(&unit(&sub(&atoms[n].pos, &atoms[c].pos)), C_N)), &atoms[n].pos);
+            for k in next.iter().copied().filter(|k| atoms[*k].name == " N  " || atoms[*k].name == " H  ") {
+                atoms[k].pos = add(&atoms[k].pos, &shift);
+            }
+            repairs.repaired.entry(key(&atoms[n])).or_default().peptide_bond = true;
+        }
+        let Some(ca) = find(&atoms, this, " CA ") else { continue };
+        if find(&atoms, this, " O  ").is_some() { continue; }
+        let mut o = atoms[c].clone();
+        o.name = " O  ".to_string();
+        // --- O lies in the peptide plane, trans to N of the next residue with respect to the CA-C bond
+        o.pos = nerf(&atoms[n].pos, &atoms[ca].pos, &atoms[c].pos, C_O, CA_C_O, 180.0);
+        repairs.repaired.entry(key(&atoms[c])).or_default().rebuilt_o = true;
+        rebuilt.push((c, o));
+    }
+    // --- every rebuilt O follows C of its residue
+    for (c, o) in rebuilt.into_iter().rev() { atoms.insert(c + 1, o); }
+    let (n_o, n_bonds) = repairs.counts();
+    if n_o + n_bonds > 0 { debug!("{}: {} backbone O atoms rebuilt, {} peptide bonds standardized", strctr.id_code, n_o, n_bonds); }
+
+    (Structure::from_iterator(&strctr.id_code, atoms.iter()), repairs)
+}
+
+/// Flags of residues repaired by [`repair_backbone()`] before their features were computed
+#[derive(Debug)]
+pub struct RepairFlags;
+
+impl FeatureExtractor for RepairFlags {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "backbone_repairs",
+            description: "1 when the backbone O atom of a residue has been rebuilt, 0 otherwise; 1 when its peptide bond to the previous residue has been set to the ideal length, 0 otherwise",
+            shape: "[L, 2]".to_string(), units: "", range: Some((0.0, 1.0)),
+            mask: "NaN when the backbone hasn't been repaired", option: "--repair-backbone", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let flag = |v: bool| if v { 1.0 } else { 0.0 };
+        match chain.repairs.as_ref() {
+            Some(repairs) => repairs.iter().map(|r| vec![flag(r.rebuilt_o), flag(r.peptide_bond)]).collect(),
+            None => vec![vec![f64::NAN; 2]; chain.resids.len()],
+        }
+    }
+}
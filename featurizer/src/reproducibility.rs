@@ -0,0 +1,107 @@
+//! Dataset manifest with content hashes, written as `dataset.json` with `--dataset-json`.
+//!
+//! The manifest lists every output file of every featurized chain with its SHA-256 checksum, along with the featurizer
+//! build and a hash of the settings the dataset was made with. Two runs made the same dataset when their manifests
+//! are equal; [`verify_dataset()`] checks that the files a training job is about to read are the ones listed.
+
+use std::path::Path;
+
+use bioshell_pdb::PDBError;
+use sha2::{Digest, Sha256};
+
+use crate::compression::output_root;
+use crate::featurize::{output_files, FeaturizerConfig};
+use crate::manifest::ChainSummary;
+use crate::provenance::{input_sha256, GIT_HASH, VERSION};
+
+/// An output file listed in a dataset manifest
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatasetFile {
+    pub path: String,
+    /// SHA-256 checksum of the file content, as a hex string
+    pub sha256: String,
+}
+
+/// A featurized chain and its output files
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatasetChain {
+    /// key of the record, e.g. `pdb/2gb1_A`
+    pub key: String,
+    /// code of the source deposit, i.e. its file name up to the first dot, e.g. `2gb1`
+    pub pdb_id: String,
+    pub chain: String,
+    pub files: Vec<DatasetFile>,
+}
+
+/// Content of a `dataset.json` file
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatasetManifest {
+    pub featurizer_version: String,
+    pub git_hash: String,
+    /// SHA-256 checksum of the settings, as given by the `config` line of `.meta` files
+    pub config_sha256: String,
+    pub chains: Vec<DatasetChain>,
+}
+
+/// SHA-256 checksum of featurizer settings, as a hex string; runs of the same settings give the same checksum
+pub fn config_sha256(config: &FeaturizerConfig) -> String {
+    Sha256::digest(format!("{:?}", config).as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Lists output files of the given chains with their checksums; files are given by the paths they were written to
+pub fn dataset_manifest(summaries: &[ChainSummary], config: &FeaturizerConfig) -> Result<DatasetManifest, PDBError> {
+    let mut chains = vec![];
+    for s in summaries {
+        let file_name = Path::new(&s.input).file_name().and_then(|n| n.to_str()).unwrap_or(&s.input);
+        let mut files = vec![];
+        for path in output_files(output_root(&s.output)) {
+            let sha256 = input_sha256(&path)?;
+            files.push(DatasetFile { path, sha256 });
+        }
+        chains.push(DatasetChain { key: s.key.clone(), pdb_id: file_name.split('.').next().unwrap_or(file_name).to_string(),
+            chain: s.chain.clone(), files });
+    }
+
+    Ok(DatasetManifest { featurizer_version: VERSION.to_string(), git_hash: GIT_HASH.to_string(), config_sha256: config_sha256(config), chains })
+}
+
+/// Checks the files listed in a dataset manifest; returns a message for every file that is missing or whose content
+/// has changed, so an empty list means the dataset is intact
+pub fn verify_dataset(manifest: &DatasetManifest) -> Vec<String> {
+    let mut problems = vec![];
+    for file in manifest.chains.iter().flat_map(|c| c.files.iter()) {
+        match input_sha256(&file.path) {
+            Ok(sha256) if sha256 == file.sha256 => {}
+            Ok(sha256) => problems.push(format!("{}: checksum {} differs from {} listed in the manifest", file.path, sha256, file.sha256)),
+            Err(e) => problems.push(format!("{}: can't be read: {}", file.path, e)),
+        }
+    }
+
+    problems
+}
+
+/// Writes a dataset manifest as a JSON object
+#[cfg(feature = "json")]
+pub fn write_dataset_json(manifest: &DatasetManifest, fname: &str) -> Result<(), PDBError> {
+    use std::io::Write;
+
+    let mut outfile = bioshell_io::out_writer(fname, false);
+    writeln!(outfile, "{}", serde_json::to_string_pretty(manifest).unwrap())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "json"))]
+pub fn write_dataset_json(_manifest: &DatasetManifest, fname: &str) -> Result<(), PDBError> {
+    log::error!("Can't write {}: the featurizer was built without the \"json\" feature", fname);
+    Ok(())
+}
+
+/// Reads a dataset manifest written by [`write_dataset_json()`]
+#[cfg(feature = "json")]
+pub fn read_dataset_json(fname: &str) -> Result<DatasetManifest, crate::errors::FeaturizerError> {
+    let text = std::fs::read_to_string(fname)?;
+    serde_json::from_str(&text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", fname, e)).into())
+}
@@ -0,0 +1,90 @@
+use clap::ValueEnum;
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+
+/// Amino acid alphabet used by all numeric residue encodings; unknown residues are encoded as `X`, the last letter.
+///
+/// This ordering is a part of the output format shared with the training code and must not be changed.
+pub const ALPHABET: &str = "ARNDCQEGHILKMFPSTWYVX";
+
+const CODE3: [&str; 20] = ["ALA", "ARG", "ASN", "ASP", "CYS", "GLN", "GLU", "GLY", "HIS", "ILE",
+    "LEU", "LYS", "MET", "PHE", "PRO", "SER", "THR", "TRP", "TYR", "VAL"];
+
+/// Index of the unknown residue type `X` in the [`ALPHABET`]
+pub const UNKNOWN_INDEX: usize = 20;
+
+/// BLOSUM62 substitution matrix, rows and columns ordered by [`ALPHABET`]
+const BLOSUM62: [[i8; 20]; 20] = [
+    [ 4, -1, -2, -2,  0, -1, -1,  0, -2, -1, -1, -1, -1, -2, -1,  1,  0, -3, -2,  0],
+    [-1,  5,  0, -2, -3,  1,  0, -2,  0, -3, -2,  2, -1, -3, -2, -1, -1, -3, -2, -3],
+    [-2,  0,  6,  1, -3,  0,  0,  0,  1, -3, -3,  0, -2, -3, -2,  1,  0, -4, -2, -3],
+    [-2, -2,  1,  6, -3,  0,  2, -1, -1, -3, -4, -1, -3, -3, -1,  0, -1, -4, -3, -3],
+    [ 0, -3, -3, -3,  9, -3, -4, -3, -3, -1, -1, -3, -1, -2, -3, -1, -1, -2, -2, -1],
+    [-1,  1,  0,  0, -3,  5,  2, -2,  0, -3, -2,  1,  0, -3, -1,  0, -1, -2, -1, -2],
+    [-1,  0,  0,  2, -4,  2,  5, -2,  0, -3, -3,  1, -2, -3, -1,  0, -1, -3, -2, -2],
+    [ 0, -2,  0, -1, -3, -2, -2,  6, -2, -4, -4, -2, -3, -3, -2,  0, -2, -2, -3, -3],
+    [-2,  0,  1, -1, -3,  0,  0, -2,  8, -3, -3, -1, -2, -1, -2, -1, -2, -2,  2, -3],
+    [-1, -3, -3, -3, -1, -3, -3, -4, -3,  4,  2, -3,  1,  0, -3, -2, -1, -3, -1,  3],
+    [-1, -2, -3, -4, -1, -2, -3, -4, -3,  2,  4, -2,  2,  0, -3, -2, -1, -2, -1,  1],
+    [-1,  2,  0, -1, -3,  1,  1, -2, -1, -3, -2,  5, -1, -3, -1,  0, -1, -3, -2, -2],
+    [-1, -1, -2, -3, -1,  0, -2, -3, -2,  1,  2, -1,  5,  0, -2, -1, -1, -1, -1,  1],
+    [-2, -3, -3, -3, -2, -3, -3, -3, -1,  0,  0, -3,  0,  6, -4, -2, -2,  1,  3, -1],
+    [-1, -2, -2, -1, -3, -1, -1, -2, -2, -3, -3, -1, -2, -4,  7, -1, -1, -4, -3, -2],
+    [ 1, -1,  1,  0, -1,  0,  0,  0, -1, -2, -2,  0, -1, -2, -1,  4,  1, -3, -2, -2],
+    [ 0, -1,  0, -1, -1, -1, -1, -2, -2, -1, -1, -1, -1, -2, -1,  1,  5, -2, -2,  0],
+    [-3, -3, -4, -4, -2, -2, -3, -2, -2, -3, -2, -3, -1,  1, -4, -3, -2, 11,  2, -3],
+    [-2, -2, -2, -3, -2, -1, -2, -3,  2, -1, -1, -2, -1,  3, -3, -2, -2,  2,  7, -1],
+    [ 0, -3, -3, -3, -1, -2, -2, -3, -3,  3,  1, -2,  1, -1, -2, -2,  0, -3, -1,  4],
+];
+
+/// Index of a residue (given by its three-letter code) in the [`ALPHABET`].
+///
+/// Selenomethionine is encoded as methionine; any other non-standard residue as `X`.
+pub fn aa_index(res_name: &str) -> usize {
+    let res_name = if res_name == "MSE" { "MET" } else { res_name };
+    CODE3.iter().position(|c| *c == res_name).unwrap_or(UNKNOWN_INDEX)
+}
+
+/// How the amino acid identity is encoded as numbers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// 21 columns: one-hot vector over the alphabet
+    Onehot,
+    /// a single column: index in the alphabet
+    Index,
+    /// 20 columns: the BLOSUM62 row of a residue type (zeros for unknown residues)
+    Blosum62,
+}
+
+/// Numeric encoding of the residue type
+#[derive(Debug)]
+pub struct ResidueEncoding {
+    pub encoding: Encoding,
+}
+
+impl FeatureExtractor for ResidueEncoding {
+    fn info(&self) -> FeatureInfo {
+        let shape = match self.encoding {
+            Encoding::Onehot => "[L, 21]",
+            Encoding::Index => "[L, 1]",
+            Encoding::Blosum62 => "[L, 20]",
+        };
+        FeatureInfo { name: "residue_encoding",
+            description: "amino acid identity encoded as a one-hot vector, alphabet index or a BLOSUM62 row; alphabet order: ARNDCQEGHILKMFPSTWYVX",
+            shape: shape.to_string(), units: "", range: None,
+            mask: "none; unknown residues are encoded as X", option: "--encode onehot|index|blosum62", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        chain.resids.iter().map(|r| {
+            let idx = chain.structure.atom(r, " CA ").map_or(UNKNOWN_INDEX, |a| aa_index(&a.res_name));
+            match self.encoding {
+                Encoding::Index => vec![idx as f64],
+                Encoding::Onehot => (0..ALPHABET.len()).map(|k| (k == idx) as u8 as f64).collect(),
+                Encoding::Blosum62 => {
+                    if idx == UNKNOWN_INDEX { vec![0.0; 20] } else { BLOSUM62[idx].iter().map(|v| *v as f64).collect() }
+                }
+            }
+        }).collect()
+    }
+}
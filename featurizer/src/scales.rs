@@ -0,0 +1,103 @@
+use std::io::BufRead;
+
+use bioshell_io::open_file;
+
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::residue_encoding::{aa_index, ALPHABET, UNKNOWN_INDEX};
+
+/// Names of the default physicochemical scales
+pub const DEFAULT_SCALES: [&str; 4] = ["hydropathy", "charge", "volume", "polarity"];
+
+/// Values of the default scales, rows ordered by [`ALPHABET`]: Kyte-Doolittle hydropathy, net charge at pH 7,
+/// residue volume (Zamyatnin, in Å³) and Grantham polarity
+const DEFAULT_VALUES: [[f64; 4]; 20] = [
+    [ 1.8,  0.0,  88.6,  8.1],
+    [-4.5,  1.0, 173.4, 10.5],
+    [-3.5,  0.0, 114.1, 11.6],
+    [-3.5, -1.0, 111.1, 13.0],
+    [ 2.5,  0.0, 108.5,  5.5],
+    [-3.5,  0.0, 143.8, 10.5],
+    [-3.5, -1.0, 138.4, 12.3],
+    [-0.4,  0.0,  60.1,  9.0],
+    [-3.2,  0.1, 153.2, 10.4],
+    [ 4.5,  0.0, 166.7,  5.2],
+    [ 3.8,  0.0, 166.7,  4.9],
+    [-3.9,  1.0, 168.6, 11.3],
+    [ 1.9,  0.0, 162.9,  5.7],
+    [ 2.8,  0.0, 189.9,  5.2],
+    [-1.6,  0.0, 112.7,  8.0],
+    [-0.8,  0.0,  89.0,  9.2],
+    [-0.7,  0.0, 116.1,  8.6],
+    [-0.9,  0.0, 227.8,  5.4],
+    [-1.3,  0.0, 193.6,  6.2],
+    [ 4.2,  0.0, 140.0,  5.9],
+];
+
+/// Physicochemical property scales of residue types, e.g. hydropathy or volume.
+///
+/// Every scale gives a value for each of the 20 standard amino acids; residues of unknown types get NaN.
+#[derive(Clone, Debug)]
+pub struct PropertyScales {
+    /// names of the scales, in the order their columns are written
+    pub names: Vec<String>,
+    /// values of all the scales for every residue type, indexed by the [`ALPHABET`]
+    values: Vec<Vec<f64>>,
+}
+
+impl Default for PropertyScales {
+    fn default() -> Self {
+        PropertyScales { names: DEFAULT_SCALES.iter().map(|s| s.to_string()).collect(),
+            values: DEFAULT_VALUES.iter().map(|row| row.to_vec()).chain(std::iter::once(vec![f64::NAN; 4])).collect() }
+    }
+}
+
+impl PropertyScales {
+    /// Reads custom scales from a CSV table.
+    ///
+    /// The header line names the scales (`residue,<scale>,<scale>,...`); each following line gives a residue type, by its one-
+    /// or three-letter code, followed by its values of the scales. Residue types missing from the table get NaN values.
+    pub fn from_csv(fname: &str) -> Result<PropertyScales, String> {
+        let reader = open_file(fname).map_err(|e| format!("can't read {}: {}", fname, e))?;
+        let mut lines = reader.lines().map_while(Result::ok).filter(|l| !l.trim().is_empty() && !l.starts_with('#'));
+        let header = lines.next().ok_or_else(|| format!("{} is empty", fname))?;
+        let names: Vec<String> = header.split(',').skip(1).map(|s| s.trim().to_string()).collect();
+        if names.is_empty() { return Err(format!("no scales named in the header of {}", fname)); }
+
+        let mut values = vec![vec![f64::NAN; names.len()]; ALPHABET.len()];
+        for line in lines {
+            let tokens: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            let idx = match tokens[0].len() {
+                1 => ALPHABET.find(tokens[0].to_ascii_uppercase().as_str()).unwrap_or(UNKNOWN_INDEX),
+                _ => aa_index(&tokens[0].to_ascii_uppercase()),
+            };
+            if idx == UNKNOWN_INDEX { return Err(format!("unknown residue type {} in {}", tokens[0], fname)); }
+            if tokens.len() != names.len() + 1 {
+                return Err(format!("{} values expected for {} in {}, found {}", names.len(), tokens[0], fname, tokens.len() - 1));
+            }
+            for (v, t) in values[idx].iter_mut().zip(&tokens[1..]) {
+                *v = t.parse().map_err(|_| format!("incorrect value {} for {} in {}", t, tokens[0], fname))?;
+            }
+        }
+
+        Ok(PropertyScales { names, values })
+    }
+
+    /// Values of all the scales for a residue type given by its index in the [`ALPHABET`]
+    pub fn values(&self, idx: usize) -> &[f64] { &self.values[idx] }
+}
+
+impl FeatureExtractor for PropertyScales {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "property_scales",
+            description: "physicochemical scales of the residue type; by default Kyte-Doolittle hydropathy, net charge at pH 7, volume and Grantham polarity",
+            shape: format!("[L, {}]", self.names.len()), units: "as defined by each scale; by default: hydropathy index, e, Å³, polarity index",
+            range: None, mask: "NaN for unknown residue types", option: "--scales [--scale-table <csv>]", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        chain.resids.iter().map(|r| {
+            let idx = chain.structure.atom(r, " CA ").map_or(UNKNOWN_INDEX, |a| aa_index(&a.res_name));
+            self.values(idx).to_vec()
+        }).collect()
+    }
+}
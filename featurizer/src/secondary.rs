@@ -0,0 +1,114 @@
+//! Secondary structure assignment methods, selected with `--ss-method`.
+//!
+//! By default the DSSP codes given with the deposit, i.e. by its helix and sheet records, are used. Two alternatives are provided: a STRIDE-like assignment,
+//! which confirms DSSP H-bond patterns by backbone torsions, and a P-SEA-like one computed from CA atoms alone, which
+//! is the only one defined for CA traces, e.g. at inference.
+
+use bioshell_pdb::calc::Vec3;
+use clap::ValueEnum;
+
+use crate::ca_trace::ca_trace_geometry;
+use crate::features::ChainContext;
+use crate::geometry::distance;
+use crate::ideal_backbone::MAX_CA_CA;
+use crate::motifs::{bridges, helix};
+use crate::ramachandran::{phi_psi, rama_region, RamaRegion};
+
+/// (mean, tolerance) of the CA(i)-CA(i+2), CA(i)-CA(i+3) and CA(i)-CA(i+4) distances in an alpha helix, in Å
+const HELIX_DISTANCES: [(f64, f64); 3] = [(5.5, 0.5), (5.3, 0.5), (6.4, 0.6)];
+
+/// (mean, tolerance) of the same distances in a beta strand, in Å
+const STRAND_DISTANCES: [(f64, f64); 3] = [(6.7, 0.6), (9.9, 0.9), (12.4, 1.1)];
+
+/// (mean, tolerance) of the CA(i-1)-CA(i)-CA(i+1) planar angle and the CA(i-1)-CA(i)-CA(i+1)-CA(i+2) pseudo-torsion
+/// in an alpha helix, in degrees
+const HELIX_ANGLES: [(f64, f64); 2] = [(89.0, 12.0), (50.0, 20.0)];
+
+/// ... and in a beta strand
+const STRAND_ANGLES: [(f64, f64); 2] = [(124.0, 14.0), (-170.0, 45.0)];
+
+/// Shortest helix and strand assigned from a CA trace
+const MIN_HELIX_LENGTH: usize = 5;
+const MIN_STRAND_LENGTH: usize = 3;
+
+/// How secondary structure is assigned
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SsMethod {
+    /// DSSP codes given with the deposit, by its helix and sheet records
+    #[default]
+    Dssp,
+    /// DSSP H-bond patterns, kept where backbone torsions agree with them, as in STRIDE
+    StrideLike,
+    /// from distances and angles of the CA trace, as in P-SEA; needs no other backbone atom
+    PSeaFromCa,
+}
+
+/// Secondary structure (H, E or C) of every residue of a chain, assigned by the given method
+pub fn assign_secondary(chain: &ChainContext, method: SsMethod) -> Vec<u8> {
+    match method {
+        SsMethod::Dssp => chain.ss.clone(),
+        SsMethod::StrideLike => stride_like(chain),
+        SsMethod::PSeaFromCa => p_sea(chain),
+    }
+}
+
+/// Helices are made of two consecutive 4-turns, strands of residues in at least two consecutive bridges; a residue
+/// of either is kept only when its phi, psi angles fall into the alpha (helix) or the beta or PPII (strand) basin,
+/// or can't be computed
+fn stride_like(chain: &ChainContext) -> Vec<u8> {
+    let n = chain.resids.len();
+    let torsions: Vec<Option<RamaRegion>> = phi_psi(chain.structure, chain.resids).iter().map(|t| t.map(|(phi, psi)| rama_region(phi, psi))).collect();
+    let mut in_bridge = vec![false; n];
+    for b in bridges(chain) { in_bridge[b.i] = true; in_bridge[b.j] = true; }
+    let helical = helix(chain, 4);
+    (0..n).map(|i| {
+        let region = torsions[i];
+        let strand = in_bridge[i] && ((i > 0 && in_bridge[i - 1]) || (i + 1 < n && in_bridge[i + 1]));
+        if helical[i] && region.map_or(true, |r| r == RamaRegion::Alpha) { b'H' }
+        else if strand && region.map_or(true, |r| r == RamaRegion::Beta || r == RamaRegion::Ppii) { b'E' }
+        else { b'C' }
+    }).collect()
+}
+
+/// True when all the values are within the tolerances around their means
+fn within(values: &[f64], criteria: &[(f64, f64)]) -> bool {
+    values.len() == criteria.len() && values.iter().zip(criteria).all(|(v, (mean, tol))| (v - mean).abs() <= *tol)
+}
+
+/// True when all the angles are within the tolerances around their means, in degrees; NaN angles are never within
+fn within_angles(angles: &[f64], criteria: &[(f64, f64)]) -> bool {
+    angles.iter().zip(criteria).all(|(v, (mean, tol))| ((v - mean + 540.0) % 360.0 - 180.0).abs() <= *tol)
+}
+
+/// Flags runs of at least `min_length` consecutive flagged residues; shorter runs are cleared
+fn keep_runs(flags: &[bool], min_length: usize) -> Vec<bool> {
+    let mut out = vec![false; flags.len()];
+    let mut start = 0;
+    for i in 0..=flags.len() {
+        if i < flags.len() && flags[i] { continue; }
+        if i - start >= min_length { for f in out.iter_mut().take(i).skip(start) { *f = true; } }
+        start = i + 1;
+    }
+    out
+}
+
+/// A residue is helical (extended) when its CA(i)-CA(i+2..i+4) distances or its CA angle and pseudo-torsion
+/// match those of an ideal helix (strand); runs of at least [`MIN_HELIX_LENGTH`] helical residues make helices,
+/// runs of at least [`MIN_STRAND_LENGTH`] extended residues make strands
+fn p_sea(chain: &ChainContext) -> Vec<u8> {
+    let n = chain.resids.len();
+    let cas: Vec<Option<&Vec3>> = chain.resids.iter().map(|r| chain.structure.atom(r, " CA ").ok().map(|a| &a.pos)).collect();
+    let bonded = |i: usize| i + 1 < n && matches!((cas[i], cas[i + 1]), (Some(a), Some(b)) if distance(a, b) <= MAX_CA_CA);
+    let geometry = ca_trace_geometry(chain.structure, chain.resids);
+    let (mut helical, mut extended) = (vec![false; n], vec![false; n]);
+    for i in 0..n {
+        // --- distances to the residues that follow, as long as the trace isn't broken
+        let distances: Vec<f64> = (2..=4).take_while(|k| (i..i + k).all(|j| bonded(j)))
+            .map(|k| distance(cas[i].unwrap(), cas[i + k].unwrap())).collect();
+        let angles = [geometry[i].theta, geometry[i].tau];
+        helical[i] = within(&distances, &HELIX_DISTANCES) || within_angles(&angles, &HELIX_ANGLES);
+        extended[i] = within(&distances, &STRAND_DISTANCES) || within_angles(&angles, &STRAND_ANGLES);
+    }
+    let (helix, strand) = (keep_runs(&helical, MIN_HELIX_LENGTH), keep_runs(&extended, MIN_STRAND_LENGTH));
+    (0..n).map(|i| if helix[i] { b'H' } else if strand[i] { b'E' } else { b'C' }).collect()
+}
@@ -0,0 +1,93 @@
+//! Sequence separation of residue pairs written to `.contacts`, `.hbonds` and `.hbpairs` files.
+//!
+//! Every pair is written with its signed separation along the entity sequence and the range class of the separation,
+//! as used by CASP to evaluate contact predictions, so losses may be stratified by range without joining `.dat` records.
+
+use std::fmt;
+
+/// Pairs separated by fewer residues than this are local
+pub const SHORT_RANGE: i64 = 6;
+
+/// Pairs separated by fewer residues than this (and at least [`SHORT_RANGE`]) are short-range
+pub const MEDIUM_RANGE: i64 = 12;
+
+/// Pairs separated by at least this many residues are long-range; the ones below are medium-range
+pub const LONG_RANGE: i64 = 24;
+
+/// Range class of a residue pair
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SeparationRange {
+    /// separated by fewer than [`SHORT_RANGE`] residues
+    Local,
+    Short,
+    Medium,
+    Long,
+    /// residues of different chains, which have no sequence separation
+    Inter,
+}
+
+impl SeparationRange {
+    /// Range class of a signed separation; `None` stands for a pair of residues of different chains
+    pub fn of(separation: Option<i64>) -> SeparationRange {
+        match separation.map(|s| s.abs()) {
+            None => SeparationRange::Inter,
+            Some(s) if s < SHORT_RANGE => SeparationRange::Local,
+            Some(s) if s < MEDIUM_RANGE => SeparationRange::Short,
+            Some(s) if s < LONG_RANGE => SeparationRange::Medium,
+            Some(_) => SeparationRange::Long,
+        }
+    }
+
+    /// Parses a range class as written by [`Display`](fmt::Display)
+    pub fn parse(s: &str) -> Option<SeparationRange> {
+        match s {
+            "local" => Some(SeparationRange::Local),
+            "short" => Some(SeparationRange::Short),
+            "medium" => Some(SeparationRange::Medium),
+            "long" => Some(SeparationRange::Long),
+            "inter" => Some(SeparationRange::Inter),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SeparationRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeparationRange::Local => write!(f, "local"),
+            SeparationRange::Short => write!(f, "short"),
+            SeparationRange::Medium => write!(f, "medium"),
+            SeparationRange::Long => write!(f, "long"),
+            SeparationRange::Inter => write!(f, "inter"),
+        }
+    }
+}
+
+/// Signed sequence separation of residues `i` and `j` of a chain, i.e. the position of `j` minus the position of `i`.
+///
+/// Positions are taken from the entity sequence, so missing residues count; when either residue can't be aligned
+/// to the entity, the difference of their indexes in the chain is returned instead.
+pub fn sequence_separation(entity_index: &[Option<usize>], i: usize, j: usize) -> i64 {
+    match (entity_index[i], entity_index[j]) {
+        (Some(ei), Some(ej)) => ej as i64 - ei as i64,
+        _ => j as i64 - i as i64,
+    }
+}
+
+/// The two separation columns of a pair: the signed separation (`nan` for an inter-chain pair) and its range class
+pub fn separation_columns(separation: Option<i64>) -> String {
+    match separation {
+        Some(s) => format!("{} {}", s, SeparationRange::of(separation)),
+        None => format!("nan {}", SeparationRange::Inter),
+    }
+}
+
+/// Reads the separation columns written by [`separation_columns()`]; `None` when they are malformed
+pub fn parse_separation(separation: &str, range: &str) -> Option<Option<i64>> {
+    let range = SeparationRange::parse(range)?;
+    match separation.parse::<i64>() {
+        Ok(s) if SeparationRange::of(Some(s)) == range => Some(Some(s)),
+        Err(_) if separation == "nan" && range == SeparationRange::Inter => Some(None),
+        _ => None,
+    }
+}
@@ -0,0 +1,128 @@
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::motifs::{bridges, ladder_gaps, Bridge};
+
+/// Maximum number of bridge partners written for a residue, as in DSSP
+const MAX_PARTNERS: usize = 2;
+
+/// Root of the `k`-th element of a union-find forest
+fn find(parent: &mut [usize], k: usize) -> usize {
+    let mut root = k;
+    while parent[root] != root { root = parent[root]; }
+    parent[k] = root;
+    root
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    parent[a.max(b)] = a.min(b);
+}
+
+/// Groups bridges into ladders: bridges are linked when they are consecutive or separated by a bulge
+fn ladders(found: &[Bridge]) -> Vec<Vec<Bridge>> {
+    let mut parent: Vec<usize> = (0..found.len()).collect();
+    for (k, a) in found.iter().enumerate() {
+        for (l, b) in found.iter().enumerate().skip(k + 1) {
+            if ladder_gaps(a, b).is_some() || ladder_gaps(b, a).is_some() { union(&mut parent, k, l); }
+        }
+    }
+    let mut out: Vec<Vec<Bridge>> = vec![];
+    let mut ladder_of_root: Vec<Option<usize>> = vec![None; found.len()];
+    for (k, b) in found.iter().enumerate() {
+        let root = find(&mut parent, k);
+        let idx = *ladder_of_root[root].get_or_insert_with(|| { out.push(vec![]); out.len() - 1 });
+        out[idx].push(*b);
+    }
+    out
+}
+
+/// Strands and sheets of a chain, as assigned to its residues
+struct SheetTopology {
+    /// strand index of every residue, numbered in chain order
+    strand: Vec<Option<usize>>,
+    /// sheet index of every residue, numbered by the first strand of a sheet
+    sheet: Vec<Option<usize>>,
+    /// bridge partners of every residue, with the orientation (true for parallel) of their bridge
+    partners: Vec<Vec<(usize, bool)>>,
+}
+
+impl SheetTopology {
+    fn new(chain: &ChainContext) -> SheetTopology {
+        let n = chain.resids.len();
+        let found = bridges(chain);
+        let mut partners: Vec<Vec<(usize, bool)>> = vec![vec![]; n];
+        for b in &found {
+            partners[b.i].push((b.j, b.parallel));
+            partners[b.j].push((b.i, b.parallel));
+        }
+        for p in partners.iter_mut() { p.sort_by_key(|(k, _)| *k); }
+
+        // --- every ladder pairs two residue ranges; overlapping ranges of different ladders form a single strand
+        let ladders = ladders(&found);
+        let sides: Vec<[(usize, usize); 2]> = ladders.iter().map(|l| {
+            let range = |v: Vec<usize>| (*v.iter().min().unwrap(), *v.iter().max().unwrap());
+            [range(l.iter().map(|b| b.i).collect()), range(l.iter().map(|b| b.j).collect())]
+        }).collect();
+        let mut ranges: Vec<(usize, usize)> = sides.iter().flatten().cloned().collect();
+        ranges.sort();
+        let mut strands: Vec<(usize, usize)> = vec![];
+        for (from, to) in ranges {
+            match strands.last_mut() {
+                Some(last) if from <= last.1 => last.1 = last.1.max(to),
+                _ => strands.push((from, to)),
+            }
+        }
+        let mut strand = vec![None; n];
+        for (k, (from, to)) in strands.iter().enumerate() {
+            for s in strand.iter_mut().take(to + 1).skip(*from) { *s = Some(k); }
+        }
+
+        // --- strands paired by a ladder belong to the same sheet
+        let mut parent: Vec<usize> = (0..strands.len()).collect();
+        for [a, b] in &sides {
+            if let (Some(sa), Some(sb)) = (strand[a.0], strand[b.0]) { union(&mut parent, sa, sb); }
+        }
+        let mut sheet_of_root: Vec<Option<usize>> = vec![None; strands.len()];
+        let mut n_sheets = 0;
+        let sheet_of_strand: Vec<usize> = (0..strands.len()).map(|k| {
+            let root = find(&mut parent, k);
+            *sheet_of_root[root].get_or_insert_with(|| { n_sheets += 1; n_sheets - 1 })
+        }).collect();
+        let sheet = strand.iter().map(|s| s.map(|k| sheet_of_strand[k])).collect();
+
+        SheetTopology { strand, sheet, partners }
+    }
+}
+
+/// Beta-sheet topology: the strand and the sheet of every residue and its bridge partners.
+///
+/// Bridges are found from the backbone H-bond map as in DSSP and linked into ladders, allowing for beta bulges
+/// (see [`HBondMotifs`](crate::motifs::HBondMotifs)). Residues paired by the ladders form strands, numbered in chain order,
+/// and strands paired with each other form sheets. For every residue up to two bridge partners are written, in the order
+/// of their indexes, each followed by the orientation of the bridge: 1 for parallel, -1 for antiparallel.
+#[derive(Debug)]
+pub struct SheetTopologyFeatures;
+
+impl FeatureExtractor for SheetTopologyFeatures {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "sheet_topology",
+            description: "strand index, sheet index and up to two bridge partners (residue index followed by 1 for a parallel, -1 for an antiparallel bridge)",
+            shape: "[L, 6]".to_string(), units: "", range: None,
+            mask: "NaN strand and sheet for residues outside of strands, NaN partner and orientation for residues without bridges",
+            option: "--sheet-topology", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let topology = SheetTopology::new(chain);
+        let value = |v: Option<usize>| v.map_or(f64::NAN, |v| v as f64);
+        (0..chain.resids.len()).map(|i| {
+            let mut row = vec![value(topology.strand[i]), value(topology.sheet[i])];
+            for k in 0..MAX_PARTNERS {
+                match topology.partners[i].get(k) {
+                    Some((j, parallel)) => row.extend([*j as f64, if *parallel { 1.0 } else { -1.0 }]),
+                    None => row.extend([f64::NAN, f64::NAN]),
+                }
+            }
+            row
+        }).collect()
+    }
+}
@@ -0,0 +1,116 @@
+//! Mapping of residues onto UniProt sequences, as provided by SIFTS, so featurized chains can be joined
+//! with sequence-level annotations such as domains or post-translational modifications.
+//!
+//! Mappings are read from the SIFTS `pdb_chain_uniprot.tsv` file or fetched, chain by chain, from the PDBe API.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use bioshell_io::{open_file, out_writer};
+
+use crate::errors::FeaturizerError;
+use crate::features::FeatureInfo;
+
+/// PDBe API endpoint returning UniProt mappings of a PDB entry
+pub const PDBE_MAPPINGS_URL: &str = "https://www.ebi.ac.uk/pdbe/api/mappings/uniprot";
+
+/// A segment of an entity sequence mapped onto a UniProt sequence without gaps
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniProtSegment {
+    pub accession: String,
+    /// first and last residue of the segment, numbered from 1 along the entity sequence (SEQRES)
+    pub entity_begin: usize,
+    pub entity_end: usize,
+    /// UniProt residue number of the first residue of the segment
+    pub uniprot_begin: i64,
+}
+
+/// UniProt accession and residue number of an entity position (counted from 0), `None` when it's not mapped
+pub fn uniprot_position(segments: &[UniProtSegment], entity_pos: usize) -> Option<(&str, i64)> {
+    let pos = entity_pos + 1;
+    segments.iter().find(|s| s.entity_begin <= pos && pos <= s.entity_end)
+        .map(|s| (s.accession.as_str(), s.uniprot_begin + (pos - s.entity_begin) as i64))
+}
+
+/// Where UniProt mappings come from
+#[derive(Debug)]
+pub enum UniProtMapping {
+    /// segments read from a SIFTS file, by lower-case PDB code and author chain ID
+    Sifts(HashMap<(String, String), Vec<UniProtSegment>>),
+    /// segments fetched from the PDBe API for every featurized chain
+    Api,
+}
+
+impl UniProtMapping {
+    /// Reads the SIFTS `pdb_chain_uniprot.tsv` table, which may be compressed.
+    ///
+    /// Every line gives PDB code, chain, UniProt accession, the first and the last SEQRES residue,
+    /// the first and the last PDB residue number and the first and the last UniProt residue number of a segment.
+    pub fn from_sifts(fname: &str) -> Result<UniProtMapping, FeaturizerError> {
+        let mut segments: HashMap<(String, String), Vec<UniProtSegment>> = HashMap::new();
+        for line in open_file(fname)?.lines() {
+            let line = line?;
+            let t: Vec<&str> = line.split('\t').map(|t| t.trim()).collect();
+            if line.starts_with('#') || t.len() < 9 || t[0] == "PDB" { continue; }
+            let parse = |k: usize| t[k].parse::<i64>()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("incorrect residue number {} in {}", t[k], fname)));
+            let (begin, end, uniprot_begin) = (parse(3)?, parse(4)?, parse(7)?);
+            if begin < 1 || end < begin { continue; }
+            segments.entry((t[0].to_lowercase(), t[1].to_string())).or_default().push(UniProtSegment {
+                accession: t[2].to_string(), entity_begin: begin as usize, entity_end: end as usize, uniprot_begin });
+        }
+
+        Ok(UniProtMapping::Sifts(segments))
+    }
+
+    /// Segments of a chain of a PDB entry; an empty list when the chain is not mapped
+    pub fn segments(&self, pdb_code: &str, chain: &str) -> Result<Vec<UniProtSegment>, FeaturizerError> {
+        match self {
+            UniProtMapping::Sifts(segments) => Ok(segments.get(&(pdb_code.to_lowercase(), chain.to_string())).cloned().unwrap_or_default()),
+            UniProtMapping::Api => fetch_segments(pdb_code, chain),
+        }
+    }
+}
+
+/// Fetches segments of a chain from the PDBe API
+#[cfg(feature = "json")]
+fn fetch_segments(pdb_code: &str, chain: &str) -> Result<Vec<UniProtSegment>, FeaturizerError> {
+    let code = pdb_code.to_lowercase();
+    let text = crate::sync::fetch_text(&format!("{}/{}", PDBE_MAPPINGS_URL, code))?;
+    let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut out = vec![];
+    let Some(accessions) = json[&code]["UniProt"].as_object() else { return Ok(out) };
+    for (accession, entry) in accessions {
+        for m in entry["mappings"].as_array().into_iter().flatten().filter(|m| m["chain_id"].as_str() == Some(chain)) {
+            let (Some(begin), Some(end), Some(uniprot_begin)) = (m["start"]["residue_number"].as_u64(),
+                m["end"]["residue_number"].as_u64(), m["unp_start"].as_i64()) else { continue };
+            out.push(UniProtSegment { accession: accession.clone(), entity_begin: begin as usize, entity_end: end as usize, uniprot_begin });
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(not(feature = "json"))]
+fn fetch_segments(pdb_code: &str, _chain: &str) -> Result<Vec<UniProtSegment>, FeaturizerError> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("can't fetch UniProt mappings of {}: the featurizer was built without the json feature", pdb_code)).into())
+}
+
+/// Writes UniProt accession and residue number of every mapped residue as `index accession number` lines;
+/// `entity_index` gives the entity position of every residue, indexed as in the `.dat` file
+pub fn write_uniprot_mapping(segments: &[UniProtSegment], entity_index: &[Option<usize>], out_fname: &str) -> Result<(), FeaturizerError> {
+    let mut outfile = out_writer(out_fname, false);
+    for (i, e) in entity_index.iter().enumerate() {
+        let Some((accession, number)) = e.and_then(|e| uniprot_position(segments, e)) else { continue };
+        writeln!(outfile, "{:4} {:10} {:5}", i, accession, number)?;
+    }
+
+    Ok(())
+}
+
+pub fn feature_info() -> FeatureInfo {
+    FeatureInfo { name: "uniprot",
+        description: "UniProt accession and residue number of residues mapped by SIFTS segments, as (index, accession, number) rows",
+        shape: "[n_mapped, 3]".to_string(), units: "", range: None,
+        mask: "residues outside of the mapped segments are not listed", option: "--sifts <tsv> | --uniprot-api", version: 1 }
+}
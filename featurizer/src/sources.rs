@@ -0,0 +1,48 @@
+use std::fmt;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Where a structure comes from; used as the namespace of record keys, e.g. `pdb/2gb1_A`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ValueEnum)]
+pub enum Source {
+    /// experimental structure from the PDB
+    Pdb,
+    /// model from the AlphaFold Protein Structure Database
+    Afdb,
+    /// any other structure, e.g. an in-house prediction
+    Local,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::Pdb => "pdb",
+            Source::Afdb => "afdb",
+            Source::Local => "local",
+        };
+        f.pad(name)
+    }
+}
+
+/// Returns true if a file name follows the AlphaFold DB naming, e.g. `AF-P69905-F1-model_v4.cif.gz`
+pub fn is_afdb_file(fname: &str) -> bool {
+    let name = Path::new(fname).file_name().and_then(|f| f.to_str()).unwrap_or("");
+    name.starts_with("AF-") && name.contains("-model_v")
+}
+
+impl Source {
+    /// Guesses the source of a structure from its file name.
+    ///
+    /// AlphaFold DB models are recognized by their naming; files named by a PDB code (`2gb1.cif`, `pdb2gb1.ent.gz`, etc.)
+    /// are assumed to come from the PDB; all other files are local.
+    pub fn detect(fname: &str) -> Source {
+        if is_afdb_file(fname) { return Source::Afdb; }
+        let name = Path::new(fname).file_name().and_then(|f| f.to_str()).unwrap_or("");
+        let root = name.split('.').next().unwrap_or("").to_lowercase();
+        let code = root.strip_prefix("pdb").filter(|c| c.len() == 4).unwrap_or(&root);
+        let is_pdb_code = code.len() == 4 && code.starts_with(|c: char| c.is_ascii_digit() && c != '0')
+            && code.chars().all(|c| c.is_ascii_alphanumeric());
+        if is_pdb_code { Source::Pdb } else { Source::Local }
+    }
+}
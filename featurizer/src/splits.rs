@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use bioshell_io::open_file;
+use log::debug;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::errors::FeaturizerError;
+
+/// Loads a file assigning chains to sequence clusters.
+///
+/// Each line of the file provides a chain identifier, e.g. `2gb1_A`, followed by a cluster identifier.
+/// Chain identifiers are case-insensitive.
+pub fn load_clusters(fname: &str) -> Result<HashMap<String, String>, FeaturizerError> {
+    let mut clusters: HashMap<String, String> = HashMap::new();
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        let t: Vec<&str> = line.split_whitespace().collect();
+        if t.len() < 2 || t[0].starts_with('#') { continue; }
+        clusters.insert(t[0].to_lowercase(), t[1].to_string());
+    }
+    debug!("{} chains assigned to clusters in {}", clusters.len(), fname);
+
+    Ok(clusters)
+}
+
+/// Assigns each chain to one of `n_folds` cross-validation folds; `n_folds` must be at least 1.
+///
+/// All the chains that belong to the same cluster are placed in the same fold; chains not listed in `clusters`
+/// form their own singleton clusters. Clusters are distributed from the largest to the smallest, each one
+/// to the fold that holds the fewest chains so far, which is deterministic and keeps folds balanced.
+pub fn assign_folds(chain_ids: &[String], clusters: &HashMap<String, String>, n_folds: usize) -> Vec<usize> {
+    let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, id) in chain_ids.iter().enumerate() {
+        let cluster = clusters.get(&id.to_lowercase()).cloned().unwrap_or(id.to_lowercase());
+        members.entry(cluster).or_default().push(i);
+    }
+    let mut members: Vec<(String, Vec<usize>)> = members.into_iter().collect();
+    members.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(&b.0)));
+
+    let mut fold_size = vec![0; n_folds];
+    let mut folds = vec![0; chain_ids.len()];
+    for (_, chains) in members {
+        let fold = (0..n_folds).min_by_key(|f| fold_size[*f]).unwrap();
+        fold_size[fold] += chains.len();
+        for i in chains { folds[i] = fold; }
+    }
+
+    folds
+}
+
+/// Randomly selects `n` entries, e.g. of a list file, reproducibly for a given seed; the selected entries keep their order.
+///
+/// All the entries are returned when there are no more than `n` of them.
+pub fn sample_entries<T>(entries: Vec<T>, n: usize, seed: u64) -> Vec<T> {
+    if entries.len() <= n { return entries; }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut selected = rand::seq::index::sample(&mut rng, entries.len(), n).into_vec();
+    selected.sort_unstable();
+    let mut selected = selected.into_iter().peekable();
+
+    entries.into_iter().enumerate().filter_map(|(i, e)| {
+        if selected.peek() == Some(&i) { selected.next(); Some(e) } else { None }
+    }).collect()
+}
+
+/// A slice of the entries of a list file, processed by one of `count` independent jobs of a cluster run; written as `K/N`.
+///
+/// Slices are numbered from 0, as array jobs usually are, so `--shard $SLURM_ARRAY_TASK_ID/16` gives the slice of
+/// the current task when the array is numbered 0-15.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("a shard should be given as K/N with 0 <= K < N, e.g. 0/8; found: {}", s);
+        let (index, count) = s.split_once('/').ok_or_else(error)?;
+        let (index, count): (usize, usize) = (index.trim().parse().map_err(|_| error())?, count.trim().parse().map_err(|_| error())?);
+        if index >= count { return Err(error()); }
+
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}/{}", self.index, self.count) }
+}
+
+impl Shard {
+    /// True when an entry, given by its PDB ID, belongs to this shard.
+    ///
+    /// Entries are assigned by a [`stable_hash()`] of the lowercase PDB ID, so all the chains of a deposit land in
+    /// the same shard and every job of a run, on whatever machine, computes the same assignment.
+    pub fn contains(&self, pdb_code: &str) -> bool { stable_hash(&pdb_code.to_lowercase()) % self.count as u64 == self.index as u64 }
+}
+
+/// 64-bit FNV-1a hash of a string; unlike the hasher of the standard library it's the same for every build and platform
+pub fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
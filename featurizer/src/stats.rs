@@ -0,0 +1,182 @@
+//! Distributions of geometric properties aggregated over a whole dataset, used to sanity-check its quality before training.
+//!
+//! Secondary structure, CA-CA distances and H-bond energies are read from the `.dat` files of a run; backbone bond
+//! lengths and phi, psi angles are computed from the input structures recorded in its manifest.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+use bioshell_io::out_writer;
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::{PDBError, Structure};
+use log::warn;
+
+use crate::chains::load_chain;
+use crate::compression::open_output;
+use crate::geometry::{dihedral_angle, distance};
+use crate::ideal_backbone::MAX_CA_CA;
+use crate::manifest::ChainSummary;
+use crate::records::{feature_columns, parse_record, read_columns, read_hbonds};
+
+/// Width of a bin of the Ramachandran histogram, in degrees
+pub const RAMACHANDRAN_BIN: f64 = 10.0;
+
+/// Histogram of values of a single property
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Histogram {
+    /// lower bound of the first bin
+    pub min: f64,
+    pub bin_width: f64,
+    pub counts: Vec<u64>,
+    /// number of values that fell outside the histogram range
+    pub outside: u64,
+}
+
+impl Histogram {
+    pub fn new(min: f64, max: f64, bin_width: f64) -> Histogram {
+        Histogram { min, bin_width, counts: vec![0; ((max - min) / bin_width).ceil() as usize], outside: 0 }
+    }
+
+    /// Counts a value; NaN values are ignored
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() { return; }
+        let bin = ((value - self.min) / self.bin_width).floor();
+        if bin < 0.0 || bin as usize >= self.counts.len() { self.outside += 1; } else { self.counts[bin as usize] += 1; }
+    }
+
+    /// Center of the k-th bin
+    pub fn center(&self, k: usize) -> f64 { self.min + (k as f64 + 0.5) * self.bin_width }
+}
+
+/// Distributions aggregated over all chains of a dataset
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct DatasetStats {
+    pub n_chains: usize,
+    /// number of observed residues
+    pub n_residues: usize,
+    /// N-CA, CA-C, C-O and peptide C-N bond lengths, in Å
+    pub bonds: BTreeMap<String, Histogram>,
+    /// distances between CA atoms of consecutive observed residues, in Å
+    pub ca_ca: Histogram,
+    /// counts of residues in [`RAMACHANDRAN_BIN`] bins of phi (rows) and psi (columns), both starting from -180 degrees
+    pub ramachandran: Vec<Vec<u64>>,
+    /// number of residues of every secondary structure type
+    pub ss: BTreeMap<char, u64>,
+    /// DSSP energies of backbone H-bonds, in kcal/mol
+    pub hbond_energy: Histogram,
+}
+
+impl Default for DatasetStats {
+    fn default() -> Self {
+        let bonds = ["N-CA", "CA-C", "C-O", "C-N"].iter().map(|b| (b.to_string(), Histogram::new(1.0, 2.0, 0.01))).collect();
+        let n = (360.0 / RAMACHANDRAN_BIN) as usize;
+        DatasetStats { n_chains: 0, n_residues: 0, bonds, ca_ca: Histogram::new(2.5, 4.5, 0.02),
+            ramachandran: vec![vec![0; n]; n], ss: BTreeMap::new(), hbond_energy: Histogram::new(-10.0, 0.0, 0.1) }
+    }
+}
+
+impl DatasetStats {
+    /// Adds a chain: its `.dat` file and, when it can be loaded, its input structure
+    pub fn add_chain(&mut self, summary: &ChainSummary) -> Result<(), PDBError> {
+        let Some(columns) = read_columns(&summary.id) else {
+            warn!("{}.meta not found, chain skipped", summary.id);
+            return Ok(());
+        };
+        let n_features = feature_columns(&columns);
+        self.n_chains += 1;
+        let mut last_ca: Option<Vec3> = None;
+        for line in open_output(&summary.output)?.lines() {
+            // --- gaps and masked residues have no data
+            let Some(record) = parse_record(&line?, n_features) else { last_ca = None; continue; };
+            self.n_residues += 1;
+            *self.ss.entry(record.ss).or_insert(0) += 1;
+            let ca = Vec3::new(record.ca[0], record.ca[1], record.ca[2]);
+            if let Some(prev) = &last_ca { self.ca_ca.add(distance(prev, &ca)); }
+            last_ca = Some(ca);
+        }
+        for hbond in read_hbonds(&summary.id)? { self.hbond_energy.add(hbond.energy); }
+        match load_chain(&summary.input, &summary.chain) {
+            Ok(strctr) => self.add_backbone(&strctr),
+            Err(e) => warn!("Can't load chain {} of {}, its backbone is skipped: {}", summary.chain, summary.input, e),
+        }
+        Ok(())
+    }
+
+    /// Adds bond lengths and phi, psi angles of a chain; the peptide bond and torsions are skipped at chain breaks
+    fn add_backbone(&mut self, strctr: &Structure) {
+        let backbone: Vec<[Option<Vec3>; 4]> = strctr.residue_ids().iter()
+            .map(|r| [" N  ", " CA ", " C  ", " O  "].map(|name| strctr.atom(r, name).ok().map(|a| a.pos.clone())))
+            .collect();
+        let mut add_bond = |name: &str, a: &Option<Vec3>, b: &Option<Vec3>| {
+            if let (Some(a), Some(b)) = (a, b) { self.bonds.get_mut(name).unwrap().add(distance(a, b)); }
+        };
+        for (i, [n, ca, c, o]) in backbone.iter().enumerate() {
+            add_bond("N-CA", n, ca);
+            add_bond("CA-C", ca, c);
+            add_bond("C-O", c, o);
+            let Some([n_next, ca_next, _, _]) = backbone.get(i + 1) else { continue };
+            if matches!((ca, ca_next), (Some(a), Some(b)) if distance(a, b) <= MAX_CA_CA) { add_bond("C-N", c, n_next); }
+        }
+        for i in 1..backbone.len().saturating_sub(1) {
+            let ([_, ca_prev, c_prev, _], [n, ca, c, _], [n_next, ca_next, _, _]) = (&backbone[i - 1], &backbone[i], &backbone[i + 1]);
+            let all = [ca_prev, c_prev, n, ca, c, n_next, ca_next];
+            if all.iter().any(|a| a.is_none()) { continue; }
+            let p = all.map(|a| a.as_ref().unwrap());
+            if distance(p[0], p[3]) > MAX_CA_CA || distance(p[3], p[6]) > MAX_CA_CA { continue; }
+            let phi = dihedral_angle(p[1], p[2], p[3], p[4]);
+            let psi = dihedral_angle(p[2], p[3], p[4], p[5]);
+            let n_bins = self.ramachandran.len();
+            let bin = |angle: f64| (((angle + 180.0) / RAMACHANDRAN_BIN).floor() as usize).min(n_bins - 1);
+            self.ramachandran[bin(phi)][bin(psi)] += 1;
+        }
+    }
+}
+
+/// Aggregates distributions over all chains listed in a manifest
+pub fn dataset_stats(summaries: &[ChainSummary]) -> Result<DatasetStats, PDBError> {
+    let mut stats = DatasetStats::default();
+    for s in summaries { stats.add_chain(s)?; }
+
+    Ok(stats)
+}
+
+/// Writes dataset statistics, either as a CSV table or, when `fname` ends with `.json`, as a JSON object.
+///
+/// Every row of the table gives a single bin: `distribution,x,y,count`; `x` is the bin center (the secondary structure
+/// code for the `ss` distribution) and `y` is given only for the two-dimensional Ramachandran histogram.
+pub fn write_stats(stats: &DatasetStats, fname: &str) -> Result<(), PDBError> {
+    if fname.ends_with(".json") { return write_stats_json(stats, fname); }
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "distribution,x,y,count")?;
+    writeln!(outfile, "chains,,,{}", stats.n_chains)?;
+    writeln!(outfile, "residues,,,{}", stats.n_residues)?;
+    for (ss, count) in &stats.ss { writeln!(outfile, "ss,{},,{}", ss, count)?; }
+    let mut histograms: Vec<(String, &Histogram)> = stats.bonds.iter().map(|(name, h)| (format!("bond_{}", name), h)).collect();
+    histograms.push(("ca_ca".to_string(), &stats.ca_ca));
+    histograms.push(("hbond_energy".to_string(), &stats.hbond_energy));
+    for (name, h) in histograms {
+        for (k, count) in h.counts.iter().enumerate() { writeln!(outfile, "{},{:.3},,{}", name, h.center(k), count)?; }
+    }
+    let center = |k: usize| -180.0 + (k as f64 + 0.5) * RAMACHANDRAN_BIN;
+    for (i, row) in stats.ramachandran.iter().enumerate() {
+        for (j, count) in row.iter().enumerate() {
+            writeln!(outfile, "ramachandran,{:.1},{:.1},{}", center(i), center(j), count)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+fn write_stats_json(stats: &DatasetStats, fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "{}", serde_json::to_string_pretty(stats).unwrap())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "json"))]
+fn write_stats_json(_stats: &DatasetStats, fname: &str) -> Result<(), PDBError> {
+    log::error!("Can't write {}: the featurizer was built without the \"json\" feature", fname);
+    Ok(())
+}
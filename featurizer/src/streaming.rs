@@ -0,0 +1,91 @@
+//! Low-memory reading of huge deposits, such as ribosomes and viral capsids.
+//!
+//! A deposit of millions of atoms takes gigabytes once parsed, although a single chain is featurized at a time. In the
+//! low-memory mode the input file is streamed line by line and only the atom records of the selected chain are kept,
+//! along with everything else the file gives (entities, sequences, resolution), so only that chain is ever parsed.
+//! Pair features are then built [`LOW_MEMORY_CHUNK`] residues at a time, see [`write_contacts_chunked()`](crate::contacts::write_contacts_chunked).
+
+use std::io::{BufRead, BufReader};
+
+use bioshell_io::open_file;
+use bioshell_pdb::PDBError;
+
+use crate::chains::{stdin_text, STDIN};
+use crate::lenient::cif_tokens;
+
+/// Number of residues whose pair features are computed at once in the low-memory mode
+pub const LOW_MEMORY_CHUNK: usize = 512;
+
+/// Records of a PDB file that belong to a chain, which is given in their 22nd column
+const PDB_CHAIN_RECORDS: [&str; 4] = ["ATOM", "HETATM", "ANISOU", "TER"];
+
+/// Calls `visit` for every line of a deposit file, or of the standard input when `fname` is `-`.
+///
+/// Atom records are given with their chain ID and whether they are `ATOM` (rather than `HETATM`) records; the chain
+/// of an mmCIF row is its `auth_asym_id`, or `label_asym_id` when the file gives no author IDs.
+fn scan_deposit(fname: &str, mut visit: impl FnMut(&str, Option<(&str, bool)>)) -> Result<(), PDBError> {
+    let reader: Box<dyn BufRead> = if fname == STDIN { Box::new(BufReader::new(stdin_text().as_bytes())) } else { Box::new(open_file(fname)?) };
+    let mut is_cif: Option<bool> = None;
+    let (mut columns, mut in_loop_header, mut in_atom_site) = (Vec::<String>::new(), false, false);
+    for line in reader.lines() {
+        let line = line?;
+        let trimmed = line.trim_start();
+        // --- mmCIF data starts with a data_ block
+        if is_cif.is_none() && !trimmed.is_empty() { is_cif = Some(trimmed.starts_with("data_")); }
+        if is_cif != Some(true) {
+            let record = line.get(..6).unwrap_or(&line).trim_end();
+            if PDB_CHAIN_RECORDS.contains(&record) {
+                visit(&line, Some((line.get(21..22).unwrap_or("").trim(), record == "ATOM")));
+            } else { visit(&line, None); }
+            continue;
+        }
+        // --- the _atom_site loop is followed like in sanitize_cif(), one row per line
+        if trimmed.starts_with("loop_") {
+            (in_loop_header, in_atom_site) = (true, false);
+            columns.clear();
+        } else if in_loop_header && trimmed.starts_with('_') {
+            if let Some(column) = trimmed.strip_prefix("_atom_site.") { columns.push(column.trim().to_string()); }
+        } else if trimmed.starts_with('_') || trimmed.starts_with('#') || trimmed.starts_with("data_") {
+            (in_loop_header, in_atom_site) = (false, false);
+        } else if !trimmed.is_empty() {
+            if in_loop_header { in_atom_site = !columns.is_empty(); }
+            in_loop_header = false;
+            if in_atom_site {
+                let column = |name: &str| columns.iter().position(|c| c == name);
+                let tokens = cif_tokens(&line);
+                let chain = column("auth_asym_id").or_else(|| column("label_asym_id")).and_then(|k| tokens.get(k)).copied().unwrap_or("");
+                let is_atom = column("group_PDB").and_then(|k| tokens.get(k)).is_some_and(|g| *g == "ATOM");
+                visit(&line, Some((chain, is_atom)));
+                continue;
+            }
+        }
+        visit(&line, None);
+    }
+
+    Ok(())
+}
+
+/// Text of a deposit file with the atom records of the given chains only; all the other lines are kept as they are
+pub fn chain_text(fname: &str, chains: &[&str]) -> Result<String, PDBError> {
+    let mut text = String::new();
+    scan_deposit(fname, |line, atom| {
+        if atom.map_or(true, |(chain, _)| chains.contains(&chain)) {
+            text.push_str(line);
+            text.push('\n');
+        }
+    })?;
+
+    Ok(text)
+}
+
+/// IDs of the chains of `ATOM` records of a deposit file, in the order they first appear, found without parsing it
+pub fn atom_chain_ids(fname: &str) -> Result<Vec<String>, PDBError> {
+    let mut ids: Vec<String> = vec![];
+    scan_deposit(fname, |_, atom| {
+        if let Some((chain, true)) = atom {
+            if !ids.iter().any(|id| id == chain) { ids.push(chain.to_string()); }
+        }
+    })?;
+
+    Ok(ids)
+}
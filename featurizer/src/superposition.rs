@@ -0,0 +1,110 @@
+use bioshell_pdb::calc::Vec3;
+
+use crate::geometry::{add, distance, scale, sub};
+
+/// Optimal rigid-body superposition of one set of points onto another
+#[derive(Clone, Debug)]
+pub struct Superposition {
+    pub rotation: [[f64; 3]; 3],
+    /// center of the moving points
+    pub moving_center: Vec3,
+    /// center of the fixed points
+    pub fixed_center: Vec3,
+    /// root-mean-square deviation between superimposed points
+    pub rmsd: f64,
+}
+
+impl Superposition {
+    /// Transforms a point of the moving set onto the fixed set
+    pub fn apply(&self, v: &Vec3) -> Vec3 {
+        let p = sub(v, &self.moving_center);
+        let r = &self.rotation;
+        add(&Vec3::new(r[0][0] * p.x + r[0][1] * p.y + r[0][2] * p.z,
+                       r[1][0] * p.x + r[1][1] * p.y + r[1][2] * p.z,
+                       r[2][0] * p.x + r[2][1] * p.y + r[2][2] * p.z), &self.fixed_center)
+    }
+}
+
+/// Centroid of a non-empty set of points
+pub(crate) fn center(points: &[Vec3]) -> Vec3 {
+    let mut c = Vec3::new(0.0, 0.0, 0.0);
+    for p in points { c = add(&c, p); }
+    scale(&c, 1.0 / points.len() as f64)
+}
+
+/// Eigenvalues and eigenvectors (columns of the returned matrix) of a symmetric matrix, computed with the cyclic Jacobi method
+pub(crate) fn symmetric_eigen<const N: usize>(mut a: [[f64; N]; N]) -> ([f64; N], [[f64; N]; N]) {
+    let mut v = [[0.0; N]; N];
+    for i in 0..N { v[i][i] = 1.0; }
+    for _sweep in 0..50 {
+        let off: f64 = (0..N).flat_map(|p| (p + 1..N).map(move |q| (p, q))).map(|(p, q)| a[p][q] * a[p][q]).sum();
+        if off < 1e-18 { break; }
+        for p in 0..N {
+            for q in p + 1..N {
+                if a[p][q].abs() < 1e-30 { continue; }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..N {
+                    let (akp, akq) = (a[k][p], a[k][q]);
+                    a[k][p] = c * akp - s * akq;
+                    a[k][q] = s * akp + c * akq;
+                }
+                for k in 0..N {
+                    let (apk, aqk) = (a[p][k], a[q][k]);
+                    a[p][k] = c * apk - s * aqk;
+                    a[q][k] = s * apk + c * aqk;
+                }
+                for k in 0..N {
+                    let (vkp, vkq) = (v[k][p], v[k][q]);
+                    v[k][p] = c * vkp - s * vkq;
+                    v[k][q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let mut values = [0.0; N];
+    for i in 0..N { values[i] = a[i][i]; }
+
+    (values, v)
+}
+
+/// Eigenvector of the largest eigenvalue of a symmetric 4x4 matrix
+fn largest_eigenvector(a: [[f64; 4]; 4]) -> [f64; 4] {
+    let (values, v) = symmetric_eigen(a);
+    let best = (0..4).max_by(|i, j| values[*i].total_cmp(&values[*j])).unwrap();
+
+    [v[0][best], v[1][best], v[2][best], v[3][best]]
+}
+
+/// Superimposes `moving` points onto `fixed` ones, minimizing their RMSD (Horn's quaternion method).
+///
+/// Both slices must have the same, non-zero length.
+pub fn superpose(moving: &[Vec3], fixed: &[Vec3]) -> Superposition {
+    let (cm, cf) = (center(moving), center(fixed));
+    let mut s = [[0.0; 3]; 3];
+    for (m, f) in moving.iter().zip(fixed.iter()) {
+        let (m, f) = (sub(m, &cm), sub(f, &cf));
+        let (m, f) = ([m.x, m.y, m.z], [f.x, f.y, f.z]);
+        for i in 0..3 { for j in 0..3 { s[i][j] += m[i] * f[j]; } }
+    }
+    let n = [
+        [s[0][0] + s[1][1] + s[2][2], s[1][2] - s[2][1], s[2][0] - s[0][2], s[0][1] - s[1][0]],
+        [s[1][2] - s[2][1], s[0][0] - s[1][1] - s[2][2], s[0][1] + s[1][0], s[2][0] + s[0][2]],
+        [s[2][0] - s[0][2], s[0][1] + s[1][0], -s[0][0] + s[1][1] - s[2][2], s[1][2] + s[2][1]],
+        [s[0][1] - s[1][0], s[2][0] + s[0][2], s[1][2] + s[2][1], -s[0][0] - s[1][1] + s[2][2]],
+    ];
+    let [w, x, y, z] = largest_eigenvector(n);
+    let rotation = [
+        [w * w + x * x - y * y - z * z, 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+        [2.0 * (x * y + w * z), w * w - x * x + y * y - z * z, 2.0 * (y * z - w * x)],
+        [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), w * w - x * x - y * y + z * z],
+    ];
+    let mut sup = Superposition { rotation, moving_center: cm, fixed_center: cf, rmsd: 0.0 };
+    let sum: f64 = moving.iter().zip(fixed.iter()).map(|(m, f)| distance(&sup.apply(m), f).powi(2)).sum();
+    sup.rmsd = (sum / moving.len() as f64).sqrt();
+
+    sup
+}
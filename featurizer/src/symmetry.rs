@@ -0,0 +1,23 @@
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+
+/// Labels residues of homo-oligomers related by internal symmetry.
+///
+/// Residues of different chains are symmetry-equivalent when they belong to the same entity and occupy the same
+/// position of its sequence; the pair (entity ID, entity position) is therefore written as the equivalence group ID,
+/// followed by the number of chains of this entity in the deposit. A residue has symmetry mates when this number is greater than one.
+#[derive(Debug)]
+pub struct SymmetryEquivalence;
+
+impl FeatureExtractor for SymmetryEquivalence {
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo { name: "symmetry_equivalence",
+            description: "equivalence group of a residue given as (entity ID, 0-based position in the entity sequence), followed by the number of chains of the entity in the deposit",
+            shape: "[L, 3]".to_string(), units: "", range: None,
+            mask: "entity ID is NaN when it is not a number; position is NaN for a residue not aligned to the entity", option: "--symmetry-labels", version: 1 }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        let entity_id = chain.entity_id.parse::<f64>().unwrap_or(f64::NAN);
+        chain.entity_index.iter().map(|pos| vec![entity_id, pos.map_or(f64::NAN, |p| p as f64), chain.entity_copies as f64]).collect()
+    }
+}
@@ -0,0 +1,100 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use log::info;
+
+use crate::errors::FeaturizerError;
+use crate::manifest::ChainSummary;
+
+/// Where the wwPDB publishes lists of entries added, modified and obsoleted in the latest weekly update
+pub const WWPDB_STATUS_URL: &str = "https://files.wwpdb.org/pub/pdb/data/status/latest";
+
+/// Where deposits in the mmCIF format are downloaded from
+pub const RCSB_DOWNLOAD_URL: &str = "https://files.rcsb.org/download";
+
+/// PDB entries changed by a weekly update
+#[derive(Clone, Debug, Default)]
+pub struct PdbUpdates {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub obsolete: Vec<String>,
+}
+
+/// Reads PDB codes listed one per line, e.g. in `added.pdb`
+fn parse_codes(text: &str) -> Vec<String> {
+    text.lines().map(|l| l.trim().to_lowercase()).filter(|l| l.len() == 4).collect()
+}
+
+impl PdbUpdates {
+    /// Reads update lists (`added.pdb`, `modified.pdb` and `obsolete.pdb`) from a local folder or from a URL.
+    ///
+    /// A missing list is treated as empty. Lists are fetched from a URL only when the `network` feature is on.
+    pub fn load(location: &str) -> Result<PdbUpdates, FeaturizerError> {
+        let read = |name: &str| -> Result<Vec<String>, FeaturizerError> {
+            let text = if is_url(location) { fetch_text(&format!("{}/{}", location, name))? } else {
+                let fname = Path::new(location).join(name);
+                if !fname.exists() { return Ok(vec![]); }
+                fs::read_to_string(fname)?
+            };
+            Ok(parse_codes(&text))
+        };
+
+        Ok(PdbUpdates { added: read("added.pdb")?, modified: read("modified.pdb")?, obsolete: read("obsolete.pdb")? })
+    }
+
+    /// Entries to featurize: the added and the modified ones
+    pub fn to_featurize(&self) -> Vec<String> { self.added.iter().chain(self.modified.iter()).cloned().collect() }
+
+    /// True when chains of the given deposit should be removed from a manifest: it's either obsolete or has been modified
+    pub fn retires(&self, summary: &ChainSummary) -> bool {
+        let code = pdb_code(summary);
+        self.obsolete.contains(&code) || self.modified.contains(&code)
+    }
+}
+
+/// PDB code of a chain listed in a manifest, e.g. `2gb1` for the `pdb/2gb1_A` key
+pub fn pdb_code(summary: &ChainSummary) -> String {
+    let name = summary.key.rsplit('/').next().unwrap_or(&summary.key);
+    name.split('_').next().unwrap_or(name).to_lowercase()
+}
+
+/// True when the location of update lists is a URL rather than a local folder
+pub fn is_url(location: &str) -> bool { location.starts_with("http://") || location.starts_with("https://") }
+
+#[cfg(feature = "network")]
+pub(crate) fn fetch_text(url: &str) -> Result<String, FeaturizerError> {
+    ureq::get(url).call().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+        .into_string().map_err(FeaturizerError::Io)
+}
+
+#[cfg(not(feature = "network"))]
+pub(crate) fn fetch_text(url: &str) -> Result<String, FeaturizerError> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("can't fetch {}: the featurizer was built without the network feature", url)).into())
+}
+
+/// Downloads a deposit from the RCSB into a folder, replacing its older copy; returns the name of the local file
+pub fn download_deposit(code: &str, dir: &str) -> Result<String, FeaturizerError> {
+    fs::create_dir_all(dir)?;
+    let fname = Path::new(dir).join(format!("{}.cif", code));
+    let text = fetch_text(&format!("{}/{}.cif", RCSB_DOWNLOAD_URL, code))?;
+    fs::write(&fname, text)?;
+    info!("{} downloaded to {}", code, fname.display());
+
+    Ok(fname.to_str().unwrap_or_default().to_string())
+}
+
+/// Reads the dataset version from a file holding a single integer; 0 when the file doesn't exist
+pub fn read_dataset_version(fname: &str) -> Result<u32, FeaturizerError> {
+    if !Path::new(fname).exists() { return Ok(0); }
+    fs::read_to_string(fname)?.trim().parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("incorrect dataset version in {}: {}", fname, e)).into())
+}
+
+/// Increments the dataset version stored in a file and returns the new version
+pub fn bump_dataset_version(fname: &str) -> Result<u32, FeaturizerError> {
+    let version = read_dataset_version(fname)? + 1;
+    fs::write(fname, format!("{}\n", version))?;
+
+    Ok(version)
+}
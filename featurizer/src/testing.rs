@@ -0,0 +1,130 @@
+//! Correctness checks every [`FeatureExtractor`] should pass.
+//!
+//! [`check_extractor()`] runs all of them for a single chain of a deposit; the [`extractor_tests!`](crate::extractor_tests)
+//! macro turns such a check into a `#[test]` function. Extractors of the registry are checked by the integration tests
+//! of this crate, so a new extractor gets the checks as soon as it's added to [`registered_extractors()`](crate::features::registered_extractors).
+
+use bioshell_interactions::BackboneHBondMap;
+use bioshell_pdb::{Entity, PdbAtom, Structure};
+
+use crate::chains::{load_deposit, polymer_structure, select_chain, source_chain};
+use crate::features::{ChainContext, FeatureExtractor};
+
+/// Values computed for a structure and for its transformed copy may differ by at most that much
+pub const TOLERANCE: f64 = 1e-3;
+
+/// True when two values are equal within [`TOLERANCE`]; NaN equals NaN and angles of ±180 degrees are equal
+fn same_value(a: f64, b: f64) -> bool {
+    if a.is_nan() || b.is_nan() { return a.is_nan() && b.is_nan(); }
+    let d = (a - b).abs();
+    d < TOLERANCE || (d - 360.0).abs() < TOLERANCE
+}
+
+fn compare(name: &str, test: &str, expected: &[Vec<f64>], actual: &[Vec<f64>]) -> Result<(), String> {
+    if expected.len() != actual.len() {
+        return Err(format!("{}: {} gives {} rows instead of {}", name, test, actual.len(), expected.len()));
+    }
+    for (i, (e, a)) in expected.iter().zip(actual.iter()).enumerate() {
+        if e.len() != a.len() || !e.iter().zip(a.iter()).all(|(x, y)| same_value(*x, *y)) {
+            return Err(format!("{}: {} changes values of residue {}: {:?} != {:?}", name, test, i, e, a));
+        }
+    }
+    Ok(())
+}
+
+/// Applies a fixed rotation (by 60 degrees about the [1, 1, 1] axis) and translation to all atoms of a structure
+fn moved(strctr: &Structure) -> Structure {
+    let atoms: Vec<PdbAtom> = strctr.atoms().iter().map(|a| {
+        let mut a = a.clone();
+        let (x, y, z) = (a.pos.x, a.pos.y, a.pos.z);
+        let (c, s) = (60f64.to_radians().cos(), 60f64.to_radians().sin());
+        let (t, k) = (1.0 - c, 1.0 / 3f64.sqrt());
+        a.pos.x = (t * k * k + c) * x + (t * k * k - s * k) * y + (t * k * k + s * k) * z + 12.5;
+        a.pos.y = (t * k * k + s * k) * x + (t * k * k + c) * y + (t * k * k - s * k) * z - 7.25;
+        a.pos.z = (t * k * k - s * k) * x + (t * k * k + s * k) * y + (t * k * k + c) * z + 31.0;
+        a
+    }).collect();
+
+    Structure::from_iterator(&strctr.id_code, atoms.iter())
+}
+
+/// Lists atoms of every residue in the reverse alphabetical order of their names; alternate locations of an atom keep their order
+fn reordered(strctr: &Structure) -> Structure {
+    let mut atoms: Vec<PdbAtom> = vec![];
+    let mut residue: Vec<PdbAtom> = vec![];
+    fn flush(residue: &mut Vec<PdbAtom>, atoms: &mut Vec<PdbAtom>) {
+        residue.sort_by(|a, b| b.name.cmp(&a.name));
+        atoms.append(residue);
+    }
+    for a in strctr.atoms() {
+        if let Some(last) = residue.last() {
+            if (&last.chain_id, last.res_seq, last.i_code) != (&a.chain_id, a.res_seq, a.i_code) { flush(&mut residue, &mut atoms); }
+        }
+        residue.push(a.clone());
+    }
+    flush(&mut residue, &mut atoms);
+
+    Structure::from_iterator(&strctr.id_code, atoms.iter())
+}
+
+/// Runs an extractor on a structure of a chain
+fn extract(extractor: &dyn FeatureExtractor, id: &str, strctr: &Structure, entity: &Entity,
+           chain: &str) -> Result<Vec<Vec<f64>>, String> {
+    let resids = strctr.residue_ids();
+    let hbonds = BackboneHBondMap::new(strctr);
+    let context = ChainContext::new(id, strctr, &resids, entity, source_chain(chain), &hbonds).map_err(|e| e.to_string())?;
+
+    Ok(extractor.extract(&context))
+}
+
+/// Checks that an extractor gives one row per residue, of the width declared in its [`FeatureInfo`](crate::features::FeatureInfo)
+pub fn check_shape(extractor: &dyn FeatureExtractor, n_residues: usize, values: &[Vec<f64>]) -> Result<(), String> {
+    let info = extractor.info();
+    if values.len() != n_residues {
+        return Err(format!("{}: {} rows given for {} residues", info.name, values.len(), n_residues));
+    }
+    let declared = info.shape.trim_matches(|c| c == '[' || c == ']').split(", ").nth(1).map_or(Some(1), |n| n.parse::<usize>().ok());
+    for (i, row) in values.iter().enumerate() {
+        if row.len() != values[0].len() || declared.is_some_and(|n| row.len() != n) {
+            return Err(format!("{}: residue {} has {} values, {} declared", info.name, i, row.len(), info.shape));
+        }
+    }
+    Ok(())
+}
+
+/// Runs all the checks of an extractor on a single chain of a deposit.
+///
+/// An extractor must give rows of the declared shape, the same values when run twice and when atoms of every residue are listed
+/// in a different order, and — unless [`FeatureExtractor::is_rigid_invariant()`] says otherwise — the same values for
+/// a rotated and translated copy of the chain. Returns a description of the first failed check.
+pub fn check_extractor(extractor: &dyn FeatureExtractor, fname: &str, chain: &str) -> Result<(), String> {
+    let name = extractor.info().name;
+    let deposit = load_deposit(fname).map_err(|e| e.to_string())?;
+    let strctr = select_chain(&polymer_structure(&deposit), chain).map_err(|e| e.to_string())?;
+    let entity = deposit.entity(&strctr.atoms()[0].entity_id);
+    let id = format!("{}_{}", strctr.id_code, chain);
+
+    let values = extract(extractor, &id, &strctr, entity, chain)?;
+    check_shape(extractor, strctr.residue_ids().len(), &values)?;
+    compare(name, "repeated extraction", &values, &extract(extractor, &id, &strctr, entity, chain)?)?;
+    compare(name, "reordering atoms", &values, &extract(extractor, &id, &reordered(&strctr), entity, chain)?)?;
+    if extractor.is_rigid_invariant() {
+        compare(name, "rigid-body motion", &values, &extract(extractor, &id, &moved(&strctr), entity, chain)?)?;
+    }
+    Ok(())
+}
+
+/// Defines a `#[test]` function running [`check_extractor()`] for an extractor on a chain of a deposit, e.g.
+///
+/// ```ignore
+/// featurizer::extractor_tests!(ca_geometry_is_correct, featurizer::ca_trace::CaTraceGeometry, "tests/input_files/2gb1.cif", "A");
+/// ```
+#[macro_export]
+macro_rules! extractor_tests {
+    ($name:ident, $extractor:expr, $fname:expr, $chain:expr) => {
+        #[test]
+        fn $name() {
+            if let Err(message) = $crate::testing::check_extractor(&$extractor, $fname, $chain) { panic!("{}", message); }
+        }
+    };
+}
@@ -0,0 +1,76 @@
+//! Time spent in the stages of a featurization, reported with `--profile-timings`.
+//!
+//! Every [`ChainSummary`] carries the timings of its chain, so they are collected without any global state, also
+//! when chains are featurized concurrently. The report lists them per input file and aggregated over the run, which
+//! tells where the time goes and catches slowdowns, e.g. after a bioshell upgrade.
+
+use std::io::Write;
+use std::time::Duration;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::PDBError;
+
+use crate::manifest::ChainSummary;
+
+/// Time spent in every stage of the featurization of a chain
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StageTimings {
+    /// reading and parsing the input file
+    pub parsing: Duration,
+    /// detecting backbone H-bonds
+    pub hbonds: Duration,
+    /// assigning secondary structure
+    pub secondary: Duration,
+    /// computing the selected features
+    pub features: Duration,
+    /// writing the records and the side outputs, along with anything else not counted above
+    pub writing: Duration,
+}
+
+impl StageTimings {
+    /// Names of the stages, in the order of the columns of a report
+    pub const STAGES: [&'static str; 5] = ["parsing", "hbonds", "secondary", "features", "writing"];
+
+    /// Durations of the stages, in the order of [`StageTimings::STAGES`]
+    pub fn stages(&self) -> [Duration; 5] { [self.parsing, self.hbonds, self.secondary, self.features, self.writing] }
+
+    pub fn total(&self) -> Duration { self.stages().iter().sum() }
+
+    /// Adds the timings of another chain to these
+    pub fn add(&mut self, other: &StageTimings) {
+        self.parsing += other.parsing;
+        self.hbonds += other.hbonds;
+        self.secondary += other.secondary;
+        self.features += other.features;
+        self.writing += other.writing;
+    }
+}
+
+/// Timings of featurized chains summed up for every input file, in the order the files were processed
+pub fn file_timings(summaries: &[ChainSummary]) -> Vec<(&str, StageTimings)> {
+    let mut files: Vec<(&str, StageTimings)> = vec![];
+    for s in summaries {
+        match files.iter_mut().find(|(input, _)| *input == s.input) {
+            Some((_, timings)) => timings.add(&s.timings),
+            None => files.push((s.input.as_str(), s.timings)),
+        }
+    }
+
+    files
+}
+
+/// Writes timings of every input file, in milliseconds, followed by a `total` row summed over all of them
+pub fn write_timings(summaries: &[ChainSummary], fname: &str) -> Result<StageTimings, PDBError> {
+    let mut outfile = out_writer(fname, false);
+    let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+    let row = |timings: &StageTimings| timings.stages().iter().chain([timings.total()].iter()).map(|d| ms(*d)).collect::<Vec<_>>().join("\t");
+    writeln!(outfile, "#input\t{}\ttotal", StageTimings::STAGES.join("\t"))?;
+    let mut total = StageTimings::default();
+    for (input, timings) in file_timings(summaries) {
+        writeln!(outfile, "{}\t{}", input, row(&timings))?;
+        total.add(&timings);
+    }
+    writeln!(outfile, "total\t{}", row(&total))?;
+
+    Ok(total)
+}
@@ -0,0 +1,43 @@
+use crate::features::{ChainContext, FeatureExtractor, FeatureInfo};
+use crate::ramachandran::phi_psi;
+
+/// Index of the bin of an angle in degrees, when the full circle [-180, 180] is split into `n_bins` equal bins
+pub fn torsion_bin(angle: f64, n_bins: usize) -> usize {
+    let width = 360.0 / n_bins as f64;
+    (((angle + 180.0) / width).floor().max(0.0) as usize).min(n_bins - 1)
+}
+
+/// Backbone phi and psi angles, optionally followed by their bin indexes.
+///
+/// Bins let classification heads be trained on the featurizer output directly: with `bins = Some(36)`, bin 0 covers
+/// angles from -180 to -170 degrees, bin 35 those from 170 to 180 degrees.
+#[derive(Debug)]
+pub struct TorsionAngles {
+    /// number of bins the full circle is split into; bin indexes are not written when `None`
+    pub bins: Option<usize>,
+}
+
+impl FeatureExtractor for TorsionAngles {
+    fn info(&self) -> FeatureInfo {
+        match self.bins {
+            None => FeatureInfo { name: "torsions", description: "backbone phi and psi angles",
+                shape: "[L, 2]".to_string(), units: "degrees", range: Some((-180.0, 180.0)),
+                mask: "NaN when phi or psi is undefined: at chain termini and breaks", option: "--torsions", version: 1 },
+            Some(_) => FeatureInfo { name: "torsions",
+                description: "backbone phi and psi angles followed by their bin indexes, for the full circle split into equal bins starting at -180 degrees",
+                shape: "[L, 4]".to_string(), units: "degrees, degrees, bin, bin", range: None,
+                mask: "NaN when phi or psi is undefined: at chain termini and breaks", option: "--torsions --torsion-bins <n>", version: 1 },
+        }
+    }
+
+    fn extract(&self, chain: &ChainContext) -> Vec<Vec<f64>> {
+        phi_psi(chain.structure, chain.resids).iter().map(|a| {
+            let (phi, psi) = a.unwrap_or((f64::NAN, f64::NAN));
+            let mut row = vec![phi, psi];
+            if let Some(n) = self.bins {
+                row.extend([phi, psi].map(|v| if v.is_nan() { f64::NAN } else { torsion_bin(v, n) as f64 }));
+            }
+            row
+        }).collect()
+    }
+}
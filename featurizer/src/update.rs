@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::time::UNIX_EPOCH;
+
+use bioshell_io::{open_file, out_writer};
+use bioshell_pdb::PDBError;
+use log::warn;
+
+use crate::chains::source_chain;
+use crate::manifest::ChainSummary;
+use crate::provenance::input_sha256;
+
+/// Default name of the database of input files, kept next to the manifest of a dataset
+pub const INPUT_DATABASE: &str = "inputs.tsv";
+
+/// An entry of a list file: a deposit file and the selection of its chains, `None` for the default chains
+pub type InputEntry = (String, Option<String>);
+
+/// State of an input file when it was featurized
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputState {
+    pub size: u64,
+    /// modification time, in seconds since the Unix epoch
+    pub mtime: u64,
+    pub sha256: String,
+}
+
+impl InputState {
+    /// Reads the current state of a file, including the checksum of its content
+    pub fn of(fname: &str) -> Result<InputState, PDBError> {
+        let (size, mtime) = size_and_mtime(fname)?;
+        Ok(InputState { size, mtime, sha256: input_sha256(fname)? })
+    }
+}
+
+fn size_and_mtime(fname: &str) -> Result<(u64, u64), PDBError> {
+    let meta = fs::metadata(fname)?;
+    let mtime = meta.modified()?.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    Ok((meta.len(), mtime))
+}
+
+/// Checks whether an input file has changed since it was featurized in the `previous` state.
+///
+/// The file is hashed only when its size or modification time differ, so a file that has been touched
+/// (e.g. copied again by a mirror) but has the same content is reported as unchanged. Returns the current state
+/// of the file and `true` when it's new or changed.
+pub fn check_input(fname: &str, previous: Option<&InputState>) -> Result<(InputState, bool), PDBError> {
+    if let Some(previous) = previous {
+        let (size, mtime) = size_and_mtime(fname)?;
+        if size == previous.size && mtime == previous.mtime { return Ok((previous.clone(), false)); }
+        let sha256 = input_sha256(fname)?;
+        let changed = sha256 != previous.sha256;
+        return Ok((InputState { size, mtime, sha256 }, changed));
+    }
+
+    Ok((InputState::of(fname)?, true))
+}
+
+/// True when a chain listed in a manifest comes from the given entry of a list file; chain selections are given
+/// as comma-separated IDs or single-letter IDs written together, see [`selected_chains()`](crate::chains::selected_chains)
+pub fn from_entry(summary: &ChainSummary, entry: &InputEntry) -> bool {
+    let chain = source_chain(&summary.chain);
+    let selected = |token: &str| token == chain || (chain.chars().count() == 1 && token.contains(chain));
+    summary.input == entry.0 && entry.1.as_ref().map_or(true, |sel| sel.split(',').any(|t| selected(t.trim())))
+}
+
+/// Writes the database of input files: the state of every featurized entry of a list file, as a tab-separated file
+pub fn write_input_database(entries: &BTreeMap<InputEntry, InputState>, fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#input\tchains\tsize\tmtime\tsha256")?;
+    for ((input, chains), state) in entries {
+        writeln!(outfile, "{}\t{}\t{}\t{}\t{}", input, chains.as_deref().unwrap_or("-"), state.size, state.mtime, state.sha256)?;
+    }
+    Ok(())
+}
+
+/// Reads the database of input files, as written by [`write_input_database()`]
+pub fn read_input_database(fname: &str) -> Result<BTreeMap<InputEntry, InputState>, PDBError> {
+    let mut entries = BTreeMap::new();
+    for line in open_file(fname)?.lines() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let t: Vec<&str> = line.split('\t').collect();
+        let (Some(size), Some(mtime)) = (t.get(2).and_then(|v| v.parse().ok()), t.get(3).and_then(|v| v.parse().ok())) else {
+            warn!("Incorrect line of the input database skipped: {}", line);
+            continue;
+        };
+        let chains = if t[1] == "-" { None } else { Some(t[1].to_string()) };
+        entries.insert((t[0].to_string(), chains), InputState { size, mtime, sha256: t.get(4).unwrap_or(&"").to_string() });
+    }
+
+    Ok(entries)
+}
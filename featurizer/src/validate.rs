@@ -0,0 +1,200 @@
+//! Checks of outputs written by earlier runs, e.g. before a dataset is used for training or after it has been copied.
+//!
+//! Every chain is checked by its `.meta` and `.dat` files: the columns and feature versions against the features
+//! known to this featurizer, every record against the declared columns and value ranges, the end of the file
+//! for truncation and, when the input deposit can still be found, the number of records against its chain.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use bioshell_io::out_writer;
+use bioshell_pdb::PDBError;
+use glob::glob;
+use log::warn;
+
+use crate::chains::{extract_chain, load_deposit, source_chain, STDIN};
+use crate::compression::{existing_file, open_output};
+use crate::errors::FeaturizerError;
+use crate::features::{registered_extractors, FeatureInfo};
+use crate::records::{feature_names, parse_record, Numbering};
+
+/// Values are written with three decimal places, so they may exceed the range of their feature by this much
+const ROUNDING: f64 = 5e-4;
+
+/// Result of checking the outputs of a single chain
+#[derive(Clone, Debug)]
+pub struct FileReport {
+    /// output root name of the chain, e.g. `dataset/2gb1_A`
+    pub output: String,
+    /// number of residue records (gaps excluded) of its `.dat` file
+    pub n_records: usize,
+    /// everything found wrong with the outputs; empty when they pass
+    pub problems: Vec<String>,
+}
+
+impl FileReport {
+    pub fn passed(&self) -> bool { self.problems.is_empty() }
+}
+
+/// Number of columns of a feature of the given shape, e.g. 3 for `[L, 3]` and 1 for `[L]`
+fn shape_width(shape: &str) -> usize {
+    shape.trim_matches(|c| c == '[' || c == ']').split(", ").nth(1).and_then(|n| n.parse().ok()).unwrap_or(1)
+}
+
+/// Feature a `.dat` column belongs to, e.g. `ca_geometry` for `ca_geometry.2`
+fn column_feature(column: &str) -> &str {
+    match column.rsplit_once('.') {
+        Some((name, k)) if k.parse::<usize>().is_ok() => name,
+        _ => column,
+    }
+}
+
+/// Number of residues of the entity of a chain, of its residues with a CA atom and of those without one
+fn source_residue_counts(input: &str, chain: &str) -> Result<(usize, usize, usize), FeaturizerError> {
+    let deposit = load_deposit(input)?;
+    let strctr = extract_chain(&deposit, source_chain(chain))?;
+    let entity = deposit.entity(&strctr.atoms()[0].entity_id);
+    let n_entity = entity.chain_monomers(source_chain(chain))?.len();
+    let resids = strctr.residue_ids();
+    let n_ca = resids.iter().filter(|r| strctr.atom(r, " CA ").is_ok()).count();
+
+    Ok((n_entity, n_ca, resids.len() - n_ca))
+}
+
+/// Checks the outputs of a chain given by their root name, e.g. `dataset/2gb1_A` for `dataset/2gb1_A.dat`
+pub fn validate_output(out_root: &str) -> FileReport {
+    let mut report = FileReport { output: out_root.to_string(), n_records: 0, problems: vec![] };
+    let meta = match fs::read_to_string(format!("{}.meta", out_root)) {
+        Ok(meta) => meta,
+        Err(e) => {
+            report.problems.push(format!("can't read the .meta file: {}", e));
+            return report;
+        }
+    };
+    let values: HashMap<&str, &str> = meta.lines().filter_map(|l| l.split_once(" = ")).collect();
+    let Some(columns) = values.get("columns").map(|c| c.split_whitespace().map(|c| c.to_string()).collect::<Vec<_>>()) else {
+        report.problems.push("no columns given in the .meta file".to_string());
+        return report;
+    };
+
+    // ---------- the schema: leading columns, feature versions and widths
+    let numbering = [Numbering::Auth.column(), Numbering::Label.column()];
+    let core = columns.len() >= 9 && columns[..3] == ["index", "residue", "residue_id"] && numbering.contains(&columns[3].as_str())
+        && columns[4..9] == [":", "ss", "x", "y", "z"];
+    if !core { report.problems.push(format!("unexpected leading columns: {}", columns.iter().take(9).cloned().collect::<Vec<_>>().join(" "))); }
+    let known: HashMap<&str, FeatureInfo> = registered_extractors().iter().map(|e| e.info()).map(|info| (info.name, info)).collect();
+    let names = feature_names(&columns);
+    for (name, value) in meta.lines().filter_map(|l| l.strip_prefix("feature.")?.split_once(" = ")) {
+        let (shape, version) = value.rsplit_once(" v").unwrap_or((value, ""));
+        match known.get(name) {
+            None => report.problems.push(format!("{}: unknown feature", name)),
+            Some(info) if version.parse::<u32>().ok() != Some(info.version) =>
+                report.problems.push(format!("{}: written as v{}, this featurizer writes v{}", name, version, info.version)),
+            _ => {}
+        }
+        let n_columns = names.iter().filter(|c| column_feature(c) == name).count();
+        if n_columns != shape_width(shape) {
+            report.problems.push(format!("{}: {} columns, {} expected for its {} shape", name, n_columns, shape_width(shape), shape));
+        }
+    }
+    // --- the range of every column and whether it may be NaN
+    let bounds: Vec<(Option<(f64, f64)>, bool)> = names.iter()
+        .map(|c| known.get(column_feature(c)).map_or((None, true), |info| (info.range, info.mask != "none"))).collect();
+
+    // ---------- the records
+    let Some(dat) = existing_file(&format!("{}.dat", out_root)) else {
+        report.problems.push("no .dat file".to_string());
+        return report;
+    };
+    let mut text = String::new();
+    if let Err(e) = open_output(&dat).and_then(|mut reader| reader.read_to_string(&mut text)) {
+        report.problems.push(format!("can't read {}, it may be truncated: {}", dat, e));
+        return report;
+    }
+    if text.is_empty() {
+        report.problems.push("the .dat file is empty".to_string());
+        return report;
+    }
+    if !text.ends_with('\n') { report.problems.push("the last record is incomplete: the .dat file is truncated".to_string()); }
+    let (mut n_lines, mut malformed, mut bad_ca) = (0, vec![], 0);
+    let mut out_of_range = vec![0; names.len()];
+    let mut unexpected_nan = vec![0; names.len()];
+    for (k, line) in text.lines().enumerate() {
+        n_lines += 1;
+        // --- gaps and masked residues are written as `-` followed by the entity residue
+        let Some((_, data)) = line.split_once(" : ") else {
+            if !line.trim_start().starts_with('-') { malformed.push(k + 1); }
+            continue;
+        };
+        let record = parse_record(line, names.len()).filter(|_| data.split_whitespace().count() == 4 + names.len());
+        let Some(record) = record else {
+            malformed.push(k + 1);
+            continue;
+        };
+        report.n_records += 1;
+        if record.ca.iter().any(|v| !v.is_finite()) { bad_ca += 1; }
+        for (j, v) in record.features.iter().enumerate() {
+            let (range, nan_allowed) = bounds[j];
+            if v.is_nan() {
+                if !nan_allowed { unexpected_nan[j] += 1; }
+                continue;
+            }
+            if v.is_infinite() || range.is_some_and(|(lo, hi)| *v < lo - ROUNDING || *v > hi + ROUNDING) { out_of_range[j] += 1; }
+        }
+    }
+    if let Some(first) = malformed.first() {
+        report.problems.push(format!("{} malformed lines, the first one is line {}", malformed.len(), first));
+    }
+    if bad_ca > 0 { report.problems.push(format!("{} records without finite CA coordinates", bad_ca)); }
+    for (j, name) in names.iter().enumerate() {
+        if out_of_range[j] > 0 {
+            let range = bounds[j].0.map_or("finite values".to_string(), |(lo, hi)| format!("[{}, {}]", lo, hi));
+            report.problems.push(format!("{}: {} values out of {}", name, out_of_range[j], range));
+        }
+        if unexpected_nan[j] > 0 { report.problems.push(format!("{}: {} NaN values, but it's never missing", name, unexpected_nan[j])); }
+    }
+
+    // ---------- the number of records and the chain of the input deposit
+    let (Some(input), Some(chain)) = (values.get("input"), values.get("chain")) else { return report };
+    if *input == STDIN || !Path::new(input).exists() {
+        warn!("{}: the input {} can't be found, residue counts are not checked", out_root, input);
+        return report;
+    }
+    match source_residue_counts(input, chain) {
+        Ok((n_entity, n_ca, n_without_ca)) => {
+            // --- residues without CA atoms are not written at all
+            if n_lines > n_entity || n_lines + n_without_ca < n_entity {
+                report.problems.push(format!("{} lines, but the entity of chain {} of {} has {} residues", n_lines, chain, input, n_entity));
+            }
+            if report.n_records > n_ca {
+                report.problems.push(format!("{} records, but chain {} of {} has {} residues with a CA atom", report.n_records, chain, input, n_ca));
+            }
+        }
+        Err(e) => report.problems.push(format!("can't load chain {} of {}: {}", chain, input, e)),
+    }
+
+    report
+}
+
+/// Checks outputs of all the chains found under a directory, i.e. of every `.meta` file there, in the order of their names
+pub fn validate_dir(dir: &str) -> Vec<FileReport> {
+    let pattern = Path::new(dir).join("**").join("*.meta");
+    let mut roots: Vec<String> = glob(&pattern.to_string_lossy()).map(|paths| paths.filter_map(Result::ok)
+        .filter_map(|p| p.to_str().and_then(|p| p.strip_suffix(".meta")).map(|p| p.to_string())).collect()).unwrap_or_default();
+    roots.sort();
+
+    roots.iter().map(|root| validate_output(root)).collect()
+}
+
+/// Writes a validation report: a `output status records problems` line for every chain, where the status is `PASS` or `FAIL`
+pub fn write_validation(reports: &[FileReport], fname: &str) -> Result<(), PDBError> {
+    let mut outfile = out_writer(fname, false);
+    writeln!(outfile, "#output\tstatus\trecords\tproblems")?;
+    for r in reports {
+        let problems = if r.passed() { "-".to_string() } else { r.problems.join("; ") };
+        writeln!(outfile, "{}\t{}\t{}\t{}", r.output, if r.passed() { "PASS" } else { "FAIL" }, r.n_records, problems)?;
+    }
+    Ok(())
+}
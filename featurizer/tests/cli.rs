@@ -0,0 +1,248 @@
+//! Tests of the `featurizer` executable: batch runs over list files and directories, their reports and exit codes.
+//!
+//! Every test runs the executable in its own working directory, where the outputs are written, and reads the
+//! deposits from `tests/input_files` (see `featurize_chain.rs`).
+
+use std::fs;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const INPUTS: &str = "tests/input_files";
+
+/// Empty working directory of a test
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join("featurizer_cli_tests").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Absolute path to the folder with input deposits
+fn inputs() -> String { fs::canonicalize(INPUTS).unwrap().to_str().unwrap().to_string() }
+
+/// Runs the featurizer with given arguments in the `dir` directory
+fn featurizer(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_featurizer")).args(args).current_dir(dir).output().unwrap()
+}
+
+/// Non-comment lines of a file, split at tabs
+fn rows(fname: PathBuf) -> Vec<Vec<String>> {
+    fs::read_to_string(fname).unwrap().lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+        .map(|l| l.split('\t').map(|t| t.to_string()).collect()).collect()
+}
+
+#[test]
+fn batch_runs_go_on_after_a_failed_chain() {
+    let dir = work_dir("batch");
+    fs::write(dir.join("list.txt"), "2gb1A\n2gb1X\n9zzzA\n1c5nL\n").unwrap();
+    let out = featurizer(&dir, &["featurize", "-l", "list.txt", "-p", &inputs(), "--quiet"]);
+    // --- some chains have been featurized, some failed
+    assert_eq!(out.status.code(), Some(2), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(dir.join("2gb1_A.dat").exists() && dir.join("1c5n_L.dat").exists());
+    assert!(!dir.join("2gb1_X.dat").exists());
+    let manifest = rows(dir.join("manifest.tsv"));
+    assert_eq!(manifest.iter().map(|r| r[3].as_str()).collect::<Vec<_>>(), vec!["A", "L"]);
+    // --- the progress bar is not drawn with --quiet, so only log messages are printed
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!stderr.contains("ETA") && !stderr.contains("failed: "), "{}", stderr);
+}
+
+#[test]
+fn failed_chains_are_reported_and_listed_for_a_retry() {
+    let dir = work_dir("failures");
+    fs::write(dir.join("list.txt"), "2gb1A\n2gb1X\n9zzzA\n").unwrap();
+    let out = featurizer(&dir, &["featurize", "-l", "list.txt", "-p", &inputs(), "--quiet", "--failed-out", "retry.txt"]);
+    assert_eq!(out.status.code(), Some(2));
+    let failures = rows(dir.join("failures.tsv"));
+    assert_eq!(failures.len(), 2);
+    assert!(failures[0][0].ends_with("2gb1.cif") && failures[0][1] == "X" && failures[0][2] == "NoSuchChain", "{:?}", failures[0]);
+    assert_eq!(failures[1][..3], ["9zzz", "-", "NotFound"]);
+    assert_eq!(fs::read_to_string(dir.join("retry.txt")).unwrap(), "2gb1:X\n9zzz\n");
+
+    // --- the retry list is a list file itself; nothing of it can be featurized
+    let out = featurizer(&dir, &["featurize", "-l", "retry.txt", "-p", &inputs(), "--quiet", "--failures", "failures.json"]);
+    assert_eq!(out.status.code(), Some(3));
+    if !cfg!(feature = "json") { return; }
+    let json = fs::read_to_string(dir.join("failures.json")).unwrap();
+    assert!(json.trim_start().starts_with('[') && json.contains("\"NoSuchChain\"") && json.contains("\"NotFound\""), "{}", json);
+}
+
+#[test]
+fn a_deposit_may_be_piped_through_the_featurizer() {
+    let dir = work_dir("pipe");
+    let fname = format!("{}/2gb1.cif", inputs());
+    assert!(featurizer(&dir, &["featurize", "-i", &fname, "-c", "A"]).status.success());
+    let mut child = Command::new(env!("CARGO_BIN_EXE_featurizer")).args(["featurize", "-i", "-", "-c", "A", "-o", "-"]).current_dir(&dir)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(&fs::read(&fname).unwrap()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    // --- the standard output is the .dat file followed by the H-bond section, and logs go to the standard error only
+    let expected = fs::read_to_string(dir.join("2gb1_A.dat")).unwrap() + &fs::read_to_string(dir.join("2gb1_A.hbonds")).unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+    assert!(!dir.join("-.dat").exists() && !dir.join("stdin_A.dat").exists());
+}
+
+#[test]
+fn files_of_a_directory_are_selected_by_a_glob_pattern() {
+    let dir = work_dir("input_dir");
+    fs::create_dir_all(dir.join("deposits/thrombin")).unwrap();
+    fs::copy(format!("{}/2gb1.cif", inputs()), dir.join("deposits/2gb1.cif")).unwrap();
+    fs::copy(format!("{}/1c5n.cif", inputs()), dir.join("deposits/thrombin/1c5n.cif")).unwrap();
+    fs::write(dir.join("deposits/thrombin/notes.txt"), "not a deposit\n").unwrap();
+
+    let out = featurizer(&dir, &["featurize", "--input-dir", "deposits", "--glob", "thrombin/*.cif", "-c", "L", "--quiet"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(dir.join("1c5n_L.dat").exists() && !dir.join("2gb1_A.dat").exists());
+
+    // --- the default pattern finds deposits at any depth; --all-chains takes every polymer chain of each
+    let out = featurizer(&dir, &["featurize", "--input-dir", "deposits", "--all-chains", "--quiet"]);
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("notes.txt"));
+    for root in ["2gb1_A", "1c5n_L", "1c5n_H"] { assert!(dir.join(format!("{}.dat", root)).exists(), "{} not featurized", root); }
+    let chains: Vec<String> = rows(dir.join("manifest.tsv")).into_iter().map(|r| format!("{}:{}", r[2].rsplit('/').next().unwrap(), r[3])).collect();
+    assert!(chains.contains(&"2gb1.cif:A".to_string()) && chains.contains(&"1c5n.cif:H".to_string()), "{:?}", chains);
+
+    // --- a single root name would be shared by the outputs of all the files
+    let out = featurizer(&dir, &["featurize", "--input-dir", "deposits", "-c", "A", "-o", "single", "--quiet"]);
+    assert!(!out.status.success());
+    assert!(!dir.join("single.dat").exists());
+}
+
+#[test]
+fn triage_ranks_the_criteria_a_chain_fails() {
+    let dir = work_dir("triage");
+    fs::write(dir.join("list.txt"), "2gb1A\n1c5nL\n1c5nH\n9zzzA\n").unwrap();
+    let out = featurizer(&dir, &["triage", "-l", "list.txt", "-p", &inputs(), "--min-length", "40", "--max-resolution", "1.2"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let triage = rows(dir.join("triage.tsv"));
+    assert_eq!(triage.len(), 4);
+    // --- an NMR structure has no resolution, so it passes the resolution filter
+    assert_eq!(triage[0][1..], ["A", "PASS", "-", "-"]);
+    // --- the light chain of thrombin is too short, but its resolution is relatively further from the threshold
+    assert_eq!(triage[1][1..], ["L", "FAIL", "max-resolution", "max-resolution=1.50,min-length=36.00"]);
+    assert_eq!(triage[2][1..], ["H", "FAIL", "max-resolution", "max-resolution=1.50"]);
+    assert_eq!(triage[3][2], "ERROR");
+    // --- triage doesn't featurize anything
+    assert!(!dir.join("2gb1_A.dat").exists());
+}
+
+#[test]
+fn sync_applies_a_weekly_update_to_the_manifest() {
+    let dir = work_dir("sync");
+    fs::write(dir.join("list.txt"), "2gb1A\n1c5nL\n").unwrap();
+    assert!(featurizer(&dir, &["featurize", "-l", "list.txt", "-p", &inputs(), "--quiet"]).status.success());
+    fs::remove_file(dir.join("2gb1_A.dat")).unwrap();
+
+    // --- 2gb1 has been modified by the update and 1c5n obsoleted; there's no list of added entries
+    fs::create_dir_all(dir.join("updates")).unwrap();
+    fs::write(dir.join("updates/modified.pdb"), "2GB1\n").unwrap();
+    fs::write(dir.join("updates/obsolete.pdb"), "1c5n\n").unwrap();
+    let out = featurizer(&dir, &["sync", "--updates", "updates", "-p", &inputs(), "--quiet"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(dir.join("2gb1_A.dat").exists(), "a modified entry should be featurized again");
+    assert!(!dir.join("1c5n_L.dat").exists(), "outputs of an obsolete entry should be removed");
+    let manifest = rows(dir.join("manifest.tsv"));
+    assert_eq!(manifest.len(), 1);
+    assert_eq!(manifest[0][3], "A");
+    assert_eq!(fs::read_to_string(dir.join("dataset_version.txt")).unwrap(), "1\n");
+
+    // --- every sync bumps the dataset version, even when it has nothing to do
+    fs::remove_file(dir.join("updates/modified.pdb")).unwrap();
+    assert!(featurizer(&dir, &["sync", "--updates", "updates", "-p", &inputs(), "--quiet"]).status.success());
+    assert_eq!(rows(dir.join("manifest.tsv")).len(), 1);
+    assert_eq!(fs::read_to_string(dir.join("dataset_version.txt")).unwrap(), "2\n");
+}
+
+#[test]
+fn stats_aggregate_the_geometry_of_a_dataset() {
+    let dir = work_dir("stats");
+    fs::write(dir.join("list.txt"), "2gb1A\n1c5nL\n").unwrap();
+    assert!(featurizer(&dir, &["featurize", "-l", "list.txt", "-p", &inputs(), "--quiet"]).status.success());
+    let out = featurizer(&dir, &["stats", "-o", "stats.csv"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let text = fs::read_to_string(dir.join("stats.csv")).unwrap();
+    let bins = |name: &str| -> Vec<(f64, u64)> {
+        text.lines().map(|l| l.split(',').collect::<Vec<_>>()).filter(|t| t[0] == name)
+            .map(|t| (t[1].parse().unwrap_or(f64::NAN), t[3].parse().unwrap())).collect()
+    };
+    let total = |name: &str| bins(name).iter().map(|b| b.1).sum::<u64>();
+    // --- residues are counted from the records of the .dat files
+    let n_residues: usize = ["2gb1_A.dat", "1c5n_L.dat"].iter()
+        .map(|f| fs::read_to_string(dir.join(f)).unwrap().lines().filter(|l| !l.starts_with('#') && l.contains(" : ")).count()).sum();
+    assert_eq!(total("chains"), 2);
+    assert_eq!(total("residues") as usize, n_residues);
+    assert_eq!(total("ss") as usize, n_residues);
+    // --- every residue has its N-CA bond, and consecutive CA atoms are mostly 3.8 Å apart
+    assert_eq!(total("bond_N-CA") as usize, n_residues);
+    let ca_ca = bins("ca_ca");
+    let mode = ca_ca.iter().max_by_key(|b| b.1).unwrap().0;
+    assert!(mode > 3.7 && mode < 3.9, "the most frequent CA-CA distance: {}", mode);
+    assert!(total("ca_ca") as usize <= n_residues - 2);
+    assert!(total("hbond_energy") > 0);
+    // --- phi and psi are defined for residues that have both neighbours
+    let rama = total("ramachandran") as usize;
+    assert!(rama > 0 && rama <= n_residues - 4);
+}
+
+#[test]
+fn dry_runs_print_the_plan_without_featurizing() {
+    let dir = work_dir("dry_run");
+    assert!(featurizer(&dir, &["featurize", "-i", &format!("{}/2gb1.cif", inputs()), "-c", "A"]).status.success());
+    for report in ["manifest.tsv", "failures.tsv"] { let _ = fs::remove_file(dir.join(report)); }
+    fs::write(dir.join("list.txt"), "2gb1A\n1c5nLH\n2gb1X\n9zzzA\n").unwrap();
+    let out = featurizer(&dir, &["featurize", "-l", "list.txt", "-p", &inputs(), "--dry-run"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let plan: Vec<Vec<&str>> = stdout.lines().filter(|l| !l.starts_with('#')).map(|l| l.split('\t').collect()).collect();
+    let summary: Vec<(&str, &str, &str)> = plan.iter().map(|t| (t[0], t[1].rsplit('/').next().unwrap(), t[2])).collect();
+    // --- chains written together are expanded as by the actual run, a chain the deposit doesn't have is reported
+    assert_eq!(summary, vec![("exists", "2gb1.cif", "A"), ("todo", "1c5n.cif", "L"), ("todo", "1c5n.cif", "H"), ("unknown", "2gb1.cif", "X"),
+        ("missing", "9zzz", "-")]);
+    assert!(plan[0][3].ends_with("2gb1_A") && plan[2][3].ends_with("1c5n_H"), "{:?}", plan);
+    // --- nothing is written by a dry run
+    assert!(!dir.join("1c5n_L.dat").exists() && !dir.join("1c5n_H.dat").exists());
+    assert!(!dir.join("manifest.tsv").exists() && !dir.join("failures.tsv").exists());
+}
+
+#[test]
+fn updates_resolve_paths_of_the_command_line_in_the_working_directory() {
+    let dir = work_dir("update_paths");
+    fs::write(dir.join("list.txt"), "2gb1A\n9zzzA\n").unwrap();
+    fs::write(dir.join("settings.cfg"), "# no settings changed\n").unwrap();
+    fs::create_dir_all(dir.join("dataset")).unwrap();
+    let out = featurizer(&dir, &["update", "-l", "list.txt", "-p", &inputs(), "--dir", "dataset", "--config", "settings.cfg",
+        "--failures", "failures.tsv", "--quiet"]);
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("Incorrect settings"), "{}", String::from_utf8_lossy(&out.stderr));
+
+    // --- the manifest, the database and the outputs go to the dataset directory, the failures where the command was run
+    assert!(dir.join("dataset/2gb1_A.dat").exists());
+    assert!(dir.join("dataset/inputs.tsv").exists());
+    let manifest = rows(dir.join("dataset/manifest.tsv"));
+    assert_eq!(manifest.len(), 1);
+    assert!(dir.join("failures.tsv").exists());
+    assert!(!dir.join("dataset/failures.tsv").exists());
+}
+
+#[test]
+fn fold_dirs_receive_outputs_of_roots_with_a_directory() {
+    let dir = work_dir("fold_dirs");
+    fs::create_dir_all(dir.join("out")).unwrap();
+    let out = featurizer(&dir, &["featurize", "-i", &format!("{}/1c5n.cif", inputs()), "-c", "L,H", "-o", "out/1c5n", "--quiet"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(dir.join("out/1c5n_L.dat").exists() && dir.join("out/1c5n_H.dat").exists());
+
+    let out = featurizer(&dir, &["split", "-k", "2", "--fold-dirs"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let manifest = rows(dir.join("manifest.tsv"));
+    assert_eq!(manifest.len(), 2);
+    for row in &manifest {
+        // --- the output column gives the moved .dat file, the fold column the directory it was moved to
+        assert_eq!(row[1], format!("fold_{}/1c5n_{}.dat", row[12], row[3]));
+        assert!(dir.join(&row[1]).exists(), "{} not moved", row[1]);
+        assert!(!dir.join(format!("out/1c5n_{}.dat", row[3])).exists());
+        assert!(dir.join(format!("fold_{}/1c5n_{}.meta", row[12], row[3])).exists());
+    }
+}
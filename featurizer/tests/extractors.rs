@@ -0,0 +1,25 @@
+//! Checks of every registered feature extractor, see [`featurizer::testing`]: shape, determinism, atom-order stability
+//! and invariance under rigid-body motion, for a single-chain NMR structure and a chain numbered with insertion codes.
+
+use featurizer::features::registered_extractors;
+use featurizer::frames::{FrameReference, ResidueFrames};
+use featurizer::residue_encoding::{Encoding, ResidueEncoding};
+use featurizer::testing::check_extractor;
+
+const INPUTS: &str = "tests/input_files";
+
+#[test]
+fn registered_extractors_pass_all_checks() {
+    for (deposit, chain) in [("2gb1.cif", "A"), ("1c5n.cif", "L")] {
+        let fname = format!("{}/{}", INPUTS, deposit);
+        for extractor in registered_extractors() {
+            if let Err(message) = check_extractor(extractor.as_ref(), &fname, chain) { panic!("{} {}: {}", deposit, chain, message); }
+        }
+    }
+}
+
+featurizer::extractor_tests!(relative_frames_pass_all_checks,
+    ResidueFrames { reference: FrameReference::Relative, as_matrix: true }, "tests/input_files/2gb1.cif", "A");
+
+featurizer::extractor_tests!(blosum62_encoding_passes_all_checks,
+    ResidueEncoding { encoding: Encoding::Blosum62 }, "tests/input_files/2gb1.cif", "A");
@@ -0,0 +1,2900 @@
+//! End-to-end tests running the whole featurization pipeline on the small corpus of deposits in `tests/input_files`:
+//!
+//!   - `2gb1.cif`: protein G B1 domain, a small alpha/beta protein solved by NMR (a single model, with hydrogens)
+//!   - `1c5n.cif`: thrombin light (L) and heavy (H) chains, residues numbered with insertion codes, partial occupancies
+//!
+//! Expected values are read directly from the `_atom_site` records of the input files.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use bioshell_pdb::calc::Vec3;
+use bioshell_pdb::Structure;
+
+use featurizer::atoms::Granularity;
+use featurizer::ca_trace::CaTraceGeometry;
+use featurizer::centering::CenterFrame;
+use featurizer::chains::{load_chain, representative_chains, selected_chains};
+use featurizer::chi_angles::ChiAngles;
+use featurizer::conservation::{SequenceConservation, SequenceDatabase, MAX_HITS, MIN_IDENTITY};
+use featurizer::crops::Crop;
+use featurizer::deposit_cache::DepositCache;
+use featurizer::ensembles::group_conformers;
+use featurizer::exposure::HalfSphereExposure;
+use featurizer::filters::{statistics, ChainFilters};
+use featurizer::gaps::{ChainBreaks, Connectivity};
+use featurizer::hbonds::{HBondDirection, HBondPair, HBONDS_HEADER};
+use featurizer::importance::{importance_probe, sample_residues, ProbeParams, ProbeTarget};
+use featurizer::linkers::LinkerSplit;
+use featurizer::motifs::HBondMotifs;
+use featurizer::obsolete::{parse_replaced_by, ObsoleteEntries};
+use featurizer::quality::geometry_quality;
+use featurizer::ramachandran::RamachandranRegions;
+use featurizer::records;
+use featurizer::records::Numbering;
+use featurizer::release::{release_date, ReleaseDate};
+use featurizer::residue_encoding::{aa_index, ALPHABET};
+use featurizer::scales::PropertyScales;
+use featurizer::secondary::SsMethod;
+use featurizer::separation::SeparationRange;
+use featurizer::sifts::UniProtMapping;
+use featurizer::sources::Source;
+use featurizer::update::{check_input, read_input_database, write_input_database, InputState};
+use featurizer::{compute_chain_features, featurize_chain, featurize_loaded_chain, CancellationToken, FeaturizerConfig, FeaturizerError, LoadedDeposit};
+
+const INPUTS: &str = "tests/input_files";
+
+/// CA atom of a residue as given in the `_atom_site` category
+struct CaRecord {
+    res_seq: String,
+    label_seq: String,
+    i_code: String,
+    pos: [f64; 3],
+}
+
+/// Reads CA atoms of the first model of a chain, skipping alternate locations other than the first one
+fn ca_records(fname: &str, chain: &str) -> Vec<CaRecord> {
+    let text = fs::read_to_string(fname).unwrap();
+    let columns: Vec<&str> = text.lines().filter_map(|l| l.trim().strip_prefix("_atom_site.")).collect();
+    let col = |name: &str| columns.iter().position(|c| *c == name).unwrap();
+    let (atom, alt, x, ins, seq, asym, model) = (col("label_atom_id"), col("label_alt_id"), col("Cartn_x"),
+        col("pdbx_PDB_ins_code"), col("auth_seq_id"), col("auth_asym_id"), col("pdbx_PDB_model_num"));
+    let label_seq = col("label_seq_id");
+    let mut records: Vec<CaRecord> = vec![];
+    for line in text.lines().filter(|l| l.starts_with("ATOM")) {
+        let t: Vec<&str> = line.split_whitespace().collect();
+        if t[atom] != "CA" || t[asym] != chain || t[model] != "1" { continue; }
+        let i_code = if t[ins] == "?" { " " } else { t[ins] };
+        if let Some(last) = records.last() {
+            if last.res_seq == t[seq] && last.i_code == i_code { continue; }
+        }
+        if t[alt] != "." && t[alt] != "A" { continue; }
+        let pos = [t[x].parse().unwrap(), t[x + 1].parse().unwrap(), t[x + 2].parse().unwrap()];
+        records.push(CaRecord { res_seq: t[seq].to_string(), label_seq: t[label_seq].to_string(), i_code: i_code.to_string(), pos });
+    }
+
+    records
+}
+
+/// Atoms of every residue of the first model of a chain, by their names, skipping alternate locations other than the first one
+fn residue_atoms(fname: &str, chain: &str) -> Vec<HashMap<String, Vec3>> {
+    let text = fs::read_to_string(fname).unwrap();
+    let columns: Vec<&str> = text.lines().filter_map(|l| l.trim().strip_prefix("_atom_site.")).collect();
+    let col = |name: &str| columns.iter().position(|c| *c == name).unwrap();
+    let (atom, alt, x, ins, seq, asym, model) = (col("label_atom_id"), col("label_alt_id"), col("Cartn_x"),
+        col("pdbx_PDB_ins_code"), col("auth_seq_id"), col("auth_asym_id"), col("pdbx_PDB_model_num"));
+    let mut residues: Vec<HashMap<String, Vec3>> = vec![];
+    let mut last: Option<(String, String)> = None;
+    for line in text.lines().filter(|l| l.starts_with("ATOM ")) {
+        let t: Vec<&str> = line.split_whitespace().collect();
+        if t[asym] != chain || t[model] != "1" || (t[alt] != "." && t[alt] != "A") { continue; }
+        let id = (t[seq].to_string(), t[ins].to_string());
+        if last.as_ref() != Some(&id) {
+            residues.push(HashMap::new());
+            last = Some(id);
+        }
+        let pos = Vec3::new(t[x].parse().unwrap(), t[x + 1].parse().unwrap(), t[x + 2].parse().unwrap());
+        residues.last_mut().unwrap().insert(t[atom].trim_matches('"').to_string(), pos);
+    }
+
+    residues
+}
+
+/// Length of the sequence of the entity a chain belongs to, from the `_entity_poly_seq` category
+fn entity_length(fname: &str, entity_id: &str) -> usize {
+    let text = fs::read_to_string(fname).unwrap();
+    let mut in_loop = false;
+    let mut n = 0;
+    for line in text.lines() {
+        if line.starts_with("_entity_poly_seq.") { in_loop = true; continue; }
+        if !in_loop { continue; }
+        if line.starts_with('#') { break; }
+        if line.split_whitespace().next() == Some(entity_id) { n += 1; }
+    }
+
+    n
+}
+
+/// One-letter sequence of an entity, from the `_entity_poly_seq` category
+fn entity_sequence(fname: &str, entity_id: &str) -> String {
+    let text = fs::read_to_string(fname).unwrap();
+    let residues = text.lines().skip_while(|l| !l.starts_with("_entity_poly_seq.")).skip_while(|l| l.starts_with("_entity_poly_seq."))
+        .take_while(|l| !l.starts_with('#')).map(|l| l.split_whitespace().collect::<Vec<_>>()).filter(|t| t.len() >= 3 && t[0] == entity_id);
+
+    residues.map(|t| ALPHABET.as_bytes()[aa_index(t[2])] as char).collect()
+}
+
+/// A single line of a `.dat` file: `None` for a gap, otherwise the residue index and all the values after the colon
+fn parse_record(line: &str) -> Option<(usize, String, Vec<String>)> {
+    if line.trim_start().starts_with('-') { return None; }
+    let (head, tail) = line.split_once(" : ").unwrap();
+    let idx = head.split_whitespace().next().unwrap().parse().unwrap();
+    let mut values = tail.split_whitespace().map(|s| s.to_string());
+    let ss = values.next().unwrap();
+
+    Some((idx, ss, values.collect()))
+}
+
+fn out_root(name: &str) -> String {
+    let dir: PathBuf = std::env::temp_dir().join("featurizer_tests");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name).to_str().unwrap().to_string()
+}
+
+fn run(fname: &str, chain: &str, name: &str, config: &FeaturizerConfig) -> Vec<String> {
+    let root = out_root(name);
+    featurize_chain(fname, chain, &root, config, &CancellationToken::new()).unwrap();
+    fs::read_to_string(format!("{}.dat", root)).unwrap().lines().map(|l| l.to_string()).collect()
+}
+
+#[test]
+fn records_match_the_input_structure() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let lines = run(&fname, "A", "2gb1_A", &FeaturizerConfig::default());
+    let expected = ca_records(&fname, "A");
+    assert_eq!(lines.len(), entity_length(&fname, "1"));
+
+    let records: Vec<_> = lines.iter().filter_map(|l| parse_record(l)).collect();
+    assert_eq!(records.len(), expected.len());
+    for (k, ((idx, ss, values), ca)) in records.iter().zip(expected.iter()).enumerate() {
+        assert_eq!(*idx, k);
+        assert!(["H", "E", "C"].contains(&ss.as_str()), "unexpected secondary structure: {}", ss);
+        for d in 0..3 {
+            let v: f64 = values[d].parse().unwrap();
+            assert!((v - ca.pos[d]).abs() < 1e-3, "residue {}: {} != {}", k, v, ca.pos[d]);
+        }
+        assert_eq!(values.len(), 3, "H-bonds are not written to .dat records");
+    }
+    // --- H-bonds are listed in a .hbonds file: donor, acceptor, energy, N..O, H..O, angle and direction
+    let hbonds = fs::read_to_string(format!("{}.hbonds", out_root("2gb1_A"))).unwrap();
+    let mut hbond_lines = hbonds.lines();
+    assert_eq!(hbond_lines.next(), Some(HBONDS_HEADER));
+    let mut n_hbonds = 0;
+    for line in hbond_lines {
+        let hb = HBondPair::parse(line).unwrap_or_else(|| panic!("malformed H-bond: {}", line));
+        let (donor, acceptor): (usize, usize) = (hb.donor.parse().unwrap(), hb.acceptor.parse().unwrap());
+        assert!(donor < expected.len() && acceptor < expected.len() && donor != acceptor);
+        assert_eq!(hb.direction, if acceptor > donor { HBondDirection::Forward } else { HBondDirection::Backward });
+        assert!(hb.energy < 0.0);
+        assert!(hb.d_da > 2.0 && hb.d_da < 5.0);
+        n_hbonds += 1;
+    }
+    assert!(n_hbonds > 20, "only {} H-bonds found", n_hbonds);
+    // --- a beta hairpin and a helix: both the secondary structure types must be present
+    assert!(records.iter().any(|r| r.1 == "E"));
+    assert!(records.iter().any(|r| r.1 == "H"));
+}
+
+#[test]
+fn records_are_read_back_with_their_columns() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(CaTraceGeometry)], ..Default::default() };
+    let lines = run(&fname, "L", "1c5n_L_columns", &config);
+    let columns = records::read_columns(&out_root("1c5n_L_columns")).unwrap();
+    assert_eq!(records::feature_columns(&columns), 3);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 3)).collect();
+    let expected = ca_records(&fname, "L");
+    assert_eq!(parsed.len(), expected.len());
+    for (k, (r, ca)) in parsed.iter().zip(expected.iter()).enumerate() {
+        assert_eq!(r.index, k);
+        assert!(r.residue_id.contains(&ca.res_seq), "{} should give residue {}", r.residue_id, ca.res_seq);
+        assert!((r.ca[0] - ca.pos[0]).abs() < 1e-3);
+    }
+    let hbonds = records::read_hbonds(&out_root("1c5n_L_columns")).unwrap();
+    assert!(!hbonds.is_empty());
+    assert!(hbonds.iter().all(|hb| hb.energy < 0.0));
+}
+
+#[test]
+fn residues_with_insertion_codes_are_all_written() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let expected = ca_records(&fname, "L");
+    assert!(expected.iter().any(|r| r.i_code != " "), "the test deposit should use insertion codes");
+    let lines = run(&fname, "L", "1c5n_L", &FeaturizerConfig::default());
+
+    let records: Vec<_> = lines.iter().filter_map(|l| parse_record(l)).collect();
+    assert_eq!(records.len(), expected.len());
+    for (k, ((idx, _, values), ca)) in records.iter().zip(expected.iter()).enumerate() {
+        assert_eq!(*idx, k);
+        let x: f64 = values[0].parse().unwrap();
+        assert!((x - ca.pos[0]).abs() < 1e-3, "residue {}{}: {} != {}", ca.res_seq, ca.i_code, x, ca.pos[0]);
+    }
+    // --- the author numbering is written next to the 0-based index
+    let numbers: Vec<String> = lines.iter().filter_map(|l| records::parse_record(l, 0)).map(|r| r.author_number).collect();
+    let expected_numbers: Vec<String> = expected.iter().map(|r| format!("{}{}", r.res_seq, r.i_code.trim())).collect();
+    assert_eq!(numbers, expected_numbers);
+}
+
+#[test]
+fn label_numbering_follows_the_entity_sequence() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let expected: Vec<String> = ca_records(&fname, "L").iter().map(|r| r.label_seq.clone()).collect();
+    let config = FeaturizerConfig { numbering: Numbering::Label, ..Default::default() };
+    let lines = run(&fname, "L", "1c5n_L_label", &config);
+    let numbers: Vec<String> = lines.iter().filter_map(|l| records::parse_record(l, 0)).map(|r| r.author_number).collect();
+    assert_eq!(numbers, expected);
+    assert_eq!(records::read_columns(&out_root("1c5n_L_label")).unwrap()[3], "label_number");
+
+    // --- features computed in memory give both numberings
+    let features = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "L", &config).unwrap();
+    let labels: Vec<String> = features.label_numbers.iter().map(|n| n.unwrap().to_string()).collect();
+    assert_eq!(labels, expected);
+    let authors: Vec<String> = ca_records(&fname, "L").iter().map(|r| format!("{}{}", r.res_seq, r.i_code.trim())).collect();
+    assert_eq!(features.author_numbers, authors);
+}
+
+#[test]
+fn chains_are_moved_into_a_canonical_frame() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let records = |mode: CenterFrame, name: &str| -> Vec<records::DatRecord> {
+        let config = FeaturizerConfig { center_frame: mode, extractors: vec![Box::new(CaTraceGeometry)], ..Default::default() };
+        run(&fname, "A", name, &config).iter().filter_map(|l| records::parse_record(l, 3)).collect()
+    };
+    let deposited = records(CenterFrame::None, "2gb1_A_deposited");
+    let moment = |r: &[records::DatRecord], i: usize, j: usize| r.iter().map(|r| r.ca[i] * r.ca[j]).sum::<f64>() / r.len() as f64;
+
+    let centered = records(CenterFrame::Centroid, "2gb1_A_centroid");
+    for k in 0..3 {
+        let mean = centered.iter().map(|r| r.ca[k]).sum::<f64>() / centered.len() as f64;
+        assert!(mean.abs() < 0.01, "mean of coordinate {} is {}", k, mean);
+    }
+    let pca = records(CenterFrame::Pca, "2gb1_A_pca");
+    for (i, j) in [(0, 1), (0, 2), (1, 2)] { assert!(moment(&pca, i, j).abs() < 0.05, "covariance {}{} of the principal axes", i, j); }
+    assert!(moment(&pca, 0, 0) >= moment(&pca, 1, 1) && moment(&pca, 1, 1) >= moment(&pca, 2, 2));
+    let first = records(CenterFrame::FirstResidue, "2gb1_A_first");
+    assert!(first[0].ca.iter().all(|x| x.abs() < 1e-3));
+
+    // --- rigid-body invariant features don't change
+    for moved in [&centered, &pca, &first] {
+        for (a, b) in deposited.iter().zip(moved.iter()) {
+            for (x, y) in a.features.iter().zip(&b.features) { assert!(x.is_nan() && y.is_nan() || (x - y).abs() < 2e-3); }
+        }
+    }
+}
+
+#[test]
+fn extractors_append_their_columns() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let plain = run(&fname, "A", "2gb1_A_plain", &FeaturizerConfig::default());
+    let config = FeaturizerConfig { extractors: vec![Box::new(CaTraceGeometry), Box::new(ChiAngles)], ..Default::default() };
+    let extended = run(&fname, "A", "2gb1_A_extended", &config);
+    // --- 3 columns of CA geometry and 8 of chi angles
+    let n_columns = 3 + 8;
+
+    for (a, b) in plain.iter().zip(extended.iter()) {
+        let (Some((_, _, a)), Some((_, _, b))) = (parse_record(a), parse_record(b)) else { continue };
+        assert_eq!(b.len(), a.len() + n_columns);
+        // --- core columns and H-bonds are not affected by extractors
+        assert_eq!(a[..3], b[..3]);
+        assert_eq!(a[3..], b[3 + n_columns..]);
+    }
+}
+
+#[test]
+fn cancelled_featurization_leaves_no_output() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_cancelled");
+    let token = CancellationToken::new();
+    token.cancel();
+    let result = featurize_chain(&fname, "A", &root, &FeaturizerConfig::default(), &token);
+    assert!(matches!(result, Err(FeaturizerError::Cancelled)));
+    assert!(!PathBuf::from(format!("{}.dat", root)).exists());
+}
+
+#[test]
+fn chains_of_a_complex_are_featurized_separately() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let counts: HashMap<&str, usize> = ["L", "H"].iter()
+        .map(|c| (*c, run(&fname, c, &format!("1c5n_{}_sep", c), &FeaturizerConfig::default())
+            .iter().filter_map(|l| parse_record(l)).count()))
+        .collect();
+    assert_eq!(counts["L"], ca_records(&fname, "L").len());
+    assert_eq!(counts["H"], ca_records(&fname, "H").len());
+}
+
+#[test]
+fn identical_chains_are_represented_by_the_most_complete_one() {
+    // --- a homodimer: a copy of chain A as chain B, with its first five residues missing in chain A
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let mut lines: Vec<String> = vec![];
+    let mut copied: Vec<String> = vec![];
+    for line in text.lines() {
+        if !line.starts_with("ATOM ") { lines.push(line.to_string()); continue; }
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens[8].parse::<usize>().unwrap() > 5 { lines.push(line.to_string()); }
+        tokens[6] = "B";
+        tokens[18] = "B";
+        copied.push(tokens.join(" "));
+    }
+    let at = lines.iter().rposition(|l| l.starts_with("ATOM")).unwrap() + 1;
+    lines.splice(at..at, copied);
+    let loaded = LoadedDeposit::from_text("2gb1_dimer", &lines.join("\n"), &FeaturizerConfig::default()).unwrap();
+    let chains = vec!["A".to_string(), "B".to_string()];
+    assert_eq!(representative_chains(&loaded.deposit, &chains), vec!["B"]);
+
+    let loaded = LoadedDeposit::load(&format!("{}/1c5n.cif", INPUTS), &FeaturizerConfig::default()).unwrap();
+    let chains = vec!["L".to_string(), "H".to_string()];
+    assert_eq!(representative_chains(&loaded.deposit, &chains), chains, "chains of different entities are all kept");
+}
+
+#[test]
+fn chains_of_a_loaded_deposit_are_selected_together() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let config = FeaturizerConfig::default();
+    let loaded = LoadedDeposit::load(&fname, &config).unwrap();
+    for selection in ["L,H", "LH", " L , H,L"] {
+        assert_eq!(selected_chains(&loaded.deposit, selection), vec!["L", "H"], "wrong chains for {}", selection);
+    }
+    assert_eq!(selected_chains(&loaded.deposit, "X"), vec!["X"], "an unknown chain should be reported, not dropped");
+    for chain in ["L", "H"] {
+        let root = out_root(&format!("1c5n_{}_loaded", chain));
+        featurize_loaded_chain(&loaded, chain, &root, &config, &CancellationToken::new()).unwrap();
+        let from_loaded = fs::read_to_string(format!("{}.dat", root)).unwrap();
+        let lines = run(&fname, chain, &format!("1c5n_{}_single", chain), &config);
+        assert_eq!(from_loaded.lines().collect::<Vec<_>>(), lines, "chain {} should not depend on how the deposit is loaded", chain);
+    }
+}
+
+#[test]
+fn features_computed_in_memory_match_the_dat_file() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(CaTraceGeometry)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_memory", &config);
+    let loaded = LoadedDeposit::load(&fname, &config).unwrap();
+    let chain = compute_chain_features(&loaded, "A", &config).unwrap();
+    let records: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 3)).collect();
+    assert_eq!(chain.residue_ids.len(), records.len());
+    assert_eq!(chain.features.len(), 1);
+    assert_eq!(chain.features[0].0, "ca_geometry");
+    for (i, r) in records.iter().enumerate() {
+        assert_eq!(r.index, i);
+        assert_eq!(r.ss, chain.ss.as_bytes()[i] as char);
+        for k in 0..3 { assert!((r.ca[k] - chain.ca[i][k]).abs() < 1e-3); }
+        for (a, b) in r.features.iter().zip(&chain.features[0].1[i]) {
+            assert!((a.is_nan() && b.is_nan()) || (a - b).abs() < 1e-3, "residue {}: {} in the .dat file, {} computed", i, a, b);
+        }
+    }
+}
+
+#[test]
+fn deposits_are_parsed_once_for_repeated_chains() {
+    let (first, second) = (format!("{}/1c5n.cif", INPUTS), format!("{}/2gb1.cif", INPUTS));
+    let config = FeaturizerConfig::default();
+    let mut cache = DepositCache::new(1);
+    for fname in [&first, &first, &second, &first] { cache.get(fname, &config).unwrap(); }
+    // --- the least recently used deposit is dropped from a full cache
+    assert_eq!((cache.n_loads, cache.n_hits), (3, 1));
+    let mut disabled = DepositCache::new(0);
+    for _ in 0..2 { disabled.get(&first, &config).unwrap(); }
+    assert_eq!((disabled.n_loads, disabled.n_hits), (2, 0));
+}
+
+#[test]
+fn glycines_get_virtual_cb_in_contacts() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_cb_contacts");
+    let config = FeaturizerConfig { contacts: Some("CB:8.0".parse().unwrap()), ..Default::default() };
+    featurize_chain(&fname, "A", &root, &config, &CancellationToken::new()).unwrap();
+    // --- residue types by the .dat index, as lines follow the entity sequence
+    let dat = fs::read_to_string(format!("{}.dat", root)).unwrap();
+    let types: HashMap<usize, char> = dat.lines().zip(entity_sequence(&fname, "1").chars())
+        .filter_map(|(l, aa)| records::parse_record(l, 0).map(|r| (r.index, aa))).collect();
+    let contacts = fs::read_to_string(format!("{}.contacts", root)).unwrap();
+    let mut n_glycines = 0;
+    for line in contacts.lines() {
+        let t: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(t.len(), 7, "CB contacts should give virtual CB flags: {}", line);
+        for (idx, flag) in [(t[0], t[3]), (t[1], t[4])] {
+            let is_glycine = types[&idx.parse::<usize>().unwrap()] == 'G';
+            assert_eq!(flag == "1", is_glycine, "residue {}: wrong virtual CB flag", idx);
+            if is_glycine { n_glycines += 1; }
+        }
+    }
+    assert!(n_glycines > 0, "glycines should not be dropped from CB contacts");
+}
+
+#[test]
+fn residues_are_mapped_onto_uniprot_segments() {
+    // --- two segments of the SEQRES sequence, mapped onto different UniProt entries; residues 21-24 are not mapped
+    let table = out_root("pdb_chain_uniprot.tsv");
+    fs::write(&table, "# 2024/01/01\nPDB\tCHAIN\tSP_PRIMARY\tRES_BEG\tRES_END\tPDB_BEG\tPDB_END\tSP_BEG\tSP_END\n\
+        2gb1\tA\tP06654\t1\t20\t1\t20\t303\t322\n2gb1\tA\tP19909\t25\t56\t25\t56\t1\t32\n").unwrap();
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_uniprot");
+    let config = FeaturizerConfig { uniprot: Some(UniProtMapping::from_sifts(&table).unwrap()), ..Default::default() };
+    featurize_chain(&fname, "A", &root, &config, &CancellationToken::new()).unwrap();
+    let mapped: HashMap<usize, (String, i64)> = fs::read_to_string(format!("{}.uniprot", root)).unwrap().lines().map(|l| {
+        let t: Vec<&str> = l.split_whitespace().collect();
+        (t[0].parse().unwrap(), (t[1].to_string(), t[2].parse().unwrap()))
+    }).collect();
+    // --- lines of a .dat file follow the entity sequence
+    let dat = fs::read_to_string(format!("{}.dat", root)).unwrap();
+    for (pos, line) in dat.lines().enumerate() {
+        let Some(r) = records::parse_record(line, 0) else { continue };
+        let expected = match pos + 1 {
+            k @ 1..=20 => Some(("P06654".to_string(), 302 + k as i64)),
+            k @ 25..=56 => Some(("P19909".to_string(), k as i64 - 24)),
+            _ => None,
+        };
+        assert_eq!(mapped.get(&r.index).cloned(), expected, "residue {} at entity position {}", r.index, pos);
+    }
+}
+
+#[test]
+fn residues_near_ligands_are_labeled() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let root = out_root("1c5n_H_ligands");
+    let config = FeaturizerConfig { ligands: true, ..Default::default() };
+    featurize_chain(&fname, "H", &root, &config, &CancellationToken::new()).unwrap();
+    let lines = fs::read_to_string(format!("{}.ligands", root)).unwrap();
+    let rows: Vec<Vec<&str>> = lines.lines().map(|l| l.split_whitespace().collect()).collect();
+    assert!(!rows.is_empty());
+    for t in &rows {
+        assert_eq!(t.len(), 5);
+        let d: f64 = t[1].parse().unwrap();
+        assert!(d <= 10.0, "residue {} is too far from {}", t[0], t[2]);
+        assert_ne!(t[2], "HOH", "waters are not ligands");
+        assert_eq!(t[4] == "1", t[2] == "CA" || t[2] == "NA", "wrong metal flag of {}", t[2]);
+    }
+    // --- the thrombin heavy chain binds the ESI inhibitor in its active site
+    assert!(rows.iter().any(|t| t[2] == "ESI" && t[1].parse::<f64>().unwrap() < 4.0));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compressed_outputs_match_plain_ones() {
+    use std::io::BufRead;
+    use featurizer::compression::{open_output, Compression};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let plain = run(&fname, "A", "2gb1_A_plain", &FeaturizerConfig { gaps: true, ..Default::default() });
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        let root = out_root(&format!("2gb1_A_{:?}", compression));
+        let config = FeaturizerConfig { gaps: true, compression: Some(compression), ..Default::default() };
+        let summary = featurize_chain(&fname, "A", &root, &config, &CancellationToken::new()).unwrap();
+        assert_eq!(summary.output, compression.file_name(&format!("{}.dat", root)));
+        assert!(!PathBuf::from(format!("{}.dat", root)).exists(), "a plain text .dat file left");
+        assert!(PathBuf::from(format!("{}.meta", root)).exists(), ".meta files are not compressed");
+        assert!(PathBuf::from(compression.file_name(&format!("{}.gaps", root))).exists());
+        let lines: Vec<String> = open_output(&summary.output).unwrap().lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, plain);
+    }
+}
+
+#[test]
+fn inter_chain_contacts_are_chain_qualified() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let root = out_root("1c5n_L_inter");
+    let config = FeaturizerConfig { contacts: Some("CA:8.0".parse().unwrap()), inter_chain: true, ..Default::default() };
+    featurize_chain(&fname, "L", &root, &config, &CancellationToken::new()).unwrap();
+    let contacts = fs::read_to_string(format!("{}.contacts", root)).unwrap();
+    let partners: Vec<&str> = contacts.lines().map(|l| l.split_whitespace().nth(1).unwrap()).collect();
+    assert!(partners.iter().any(|p| p.starts_with("H:")), "the light chain should contact the heavy chain");
+    // --- partners of the featurized chain are given by indexes, those of the heavy chain and of hirudin (I) by their chain
+    for p in partners.iter().filter(|p| p.parse::<usize>().is_err()) {
+        let (chain, index) = p.split_once(':').unwrap_or_else(|| panic!("a partner neither indexed nor qualified by its chain: {}", p));
+        assert_ne!(chain, "L", "a residue of the featurized chain qualified as a partner of another chain: {}", p);
+        assert!(["H", "I"].contains(&chain), "a partner of an unexpected chain: {}", p);
+        assert!(index.parse::<usize>().is_ok(), "{}", p);
+    }
+}
+
+#[test]
+fn pairs_give_their_sequence_separation() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_separation");
+    let config = FeaturizerConfig { contacts: Some("CA:8.0".parse().unwrap()), ..Default::default() };
+    featurize_chain(&fname, "A", &root, &config, &CancellationToken::new()).unwrap();
+    // --- protein G has no missing residues, so separations are differences of indexes
+    let contacts = fs::read_to_string(format!("{}.contacts", root)).unwrap();
+    let mut ranges = HashSet::new();
+    for line in contacts.lines() {
+        let t: Vec<&str> = line.split_whitespace().collect();
+        let (i, j, separation): (i64, i64, i64) = (t[0].parse().unwrap(), t[1].parse().unwrap(), t[3].parse().unwrap());
+        assert_eq!(separation, j - i, "{}", line);
+        assert_eq!(t[4], SeparationRange::of(Some(separation)).to_string(), "{}", line);
+        ranges.insert(t[4].to_string());
+    }
+    assert!(ranges.contains("local") && ranges.contains("long"), "ranges found: {:?}", ranges);
+    for hb in records::read_hbonds(&root).unwrap() {
+        let (donor, acceptor): (i64, i64) = (hb.donor.parse().unwrap(), hb.acceptor.parse().unwrap());
+        assert_eq!(hb.separation, Some(acceptor - donor));
+    }
+    // --- residues of different chains have no separation
+    let root = out_root("1c5n_L_separation");
+    let config = FeaturizerConfig { contacts: Some("CA:8.0".parse().unwrap()), inter_chain: true, ..Default::default() };
+    featurize_chain(&format!("{}/1c5n.cif", INPUTS), "L", &root, &config, &CancellationToken::new()).unwrap();
+    let contacts = fs::read_to_string(format!("{}.contacts", root)).unwrap();
+    for t in contacts.lines().map(|l| l.split_whitespace().collect::<Vec<_>>()) {
+        assert_eq!(t[1].contains(':'), t[3..] == ["nan", "inter"], "{:?}", t);
+    }
+}
+
+#[test]
+fn chain_breaks_follow_missing_residues() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(ChainBreaks)], ..Default::default() };
+    let lines = run(&fname, "L", "1c5n_L_breaks", &config);
+    // --- gap lengths are counted from the '-' lines of missing residues
+    let mut n_missing = 0;
+    let mut first = true;
+    for line in &lines {
+        let Some(r) = records::parse_record(line, 2) else { n_missing += 1; continue };
+        assert_eq!(r.features[1], n_missing as f64, "residue {}: wrong gap length", r.index);
+        let expected_break = if first { 0.0 } else if n_missing > 0 { 1.0 } else { r.features[0] };
+        assert_eq!(r.features[0], expected_break, "residue {}: wrong chain break flag", r.index);
+        assert!(r.features[0] == 0.0 || r.features[0] == 1.0);
+        n_missing = 0;
+        first = false;
+    }
+}
+
+#[test]
+fn connected_neighbours_agree_with_chain_breaks() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(ChainBreaks), Box::new(Connectivity)], ..Default::default() };
+    let lines = run(&fname, "L", "1c5n_L_connectivity", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 6)).collect();
+    // --- terminal flags are set only where no entity residue is missing before (after) the residue
+    assert_eq!(parsed[0].features[2] == 1.0, !lines[0].starts_with('-'));
+    assert_eq!(parsed.last().unwrap().features[3] == 1.0, !lines.last().unwrap().starts_with('-'));
+    for (k, r) in parsed.iter().enumerate() {
+        let bonded_before = r.features[0] == 0.0 && k > 0;
+        assert_eq!(r.features[4], if bonded_before { parsed[k - 1].index as f64 } else { -1.0 }, "residue {}: wrong previous", r.index);
+        let bonded_after = parsed.get(k + 1).is_some_and(|n| n.features[0] == 0.0);
+        assert_eq!(r.features[5], if bonded_after { parsed[k + 1].index as f64 } else { -1.0 }, "residue {}: wrong next", r.index);
+    }
+}
+
+#[test]
+fn atom_records_broadcast_residue_features() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { granularity: Granularity::Atom, extractors: vec![Box::new(ChainBreaks)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_atoms", &config);
+    let residues: HashMap<usize, records::DatRecord> = lines.iter().filter_map(|l| records::parse_record(l, 2)).map(|r| (r.index, r)).collect();
+    let atoms = fs::read_to_string(format!("{}.atoms", out_root("2gb1_A_atoms"))).unwrap();
+    let mut n_ca = 0;
+    for line in atoms.lines() {
+        let (atom, features) = line.split_once(':').unwrap();
+        let t: Vec<&str> = atom.split_whitespace().collect();
+        let residue = &residues[&t[0].parse::<usize>().unwrap()];
+        // --- 2gb1 is an NMR structure, whose hydrogens are skipped
+        assert!(!t[2].starts_with('H'), "{}", line);
+        assert_eq!(t[1], &t[2][..1]);
+        if t[2] == "CA" {
+            n_ca += 1;
+            let ca: Vec<f64> = t[3..6].iter().map(|v| v.parse().unwrap()).collect();
+            assert_eq!(ca, residue.ca.to_vec());
+        }
+        let features: Vec<f64> = features.split_whitespace().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(features, residue.features);
+    }
+    assert_eq!(n_ca, residues.len());
+}
+
+#[test]
+fn helical_motifs_start_with_their_hbond_pattern() {
+    for (deposit, chain) in [("2gb1.cif", "A"), ("1c5n.cif", "L")] {
+        let fname = format!("{}/{}", INPUTS, deposit);
+        let config = FeaturizerConfig { extractors: vec![Box::new(HBondMotifs)], ..Default::default() };
+        let lines = run(&fname, chain, &format!("{}_motifs", deposit), &config);
+        let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 3)).collect();
+        let hbonds = records::read_hbonds(&out_root(&format!("{}_motifs", deposit))).unwrap();
+        assert!(parsed.iter().all(|r| r.features.iter().all(|v| *v == 0.0 || *v == 1.0)));
+        // --- the C=O of the residue before a 3-10 (pi) helix accepts an H-bond from the residue 3 (5) positions after it
+        for (column, n) in [(0, 3), (1, 5)] {
+            for k in 1..parsed.len() {
+                if parsed[k].features[column] == 0.0 || parsed[k - 1].features[column] == 1.0 { continue; }
+                let (donor, acceptor) = ((parsed[k - 1].index + n).to_string(), parsed[k - 1].index.to_string());
+                assert!(hbonds.iter().any(|hb| hb.donor == donor && hb.acceptor == acceptor), "{} {}: residue {} starts no {}-turn",
+                        deposit, chain, parsed[k - 1].index, n);
+            }
+        }
+    }
+}
+
+#[test]
+fn contact_numbers_count_ca_atoms_within_radii() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let hse = HalfSphereExposure::default();
+    let radii = hse.radii.clone();
+    let config = FeaturizerConfig { extractors: vec![Box::new(hse)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_hse", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 3 * radii.len())).collect();
+    for r in &parsed {
+        for (k, radius) in radii.iter().enumerate() {
+            let (up, down, cn) = (r.features[3 * k], r.features[3 * k + 1], r.features[3 * k + 2]);
+            let d: Vec<f64> = parsed.iter().filter(|o| o.index != r.index)
+                .map(|o| (0..3).map(|c| (o.ca[c] - r.ca[c]).powi(2)).sum::<f64>().sqrt()).collect();
+            // --- coordinates are written with three decimals, so CA atoms right at the radius may be counted either way
+            let expected = d.iter().filter(|d| **d <= *radius).count() as f64;
+            let borderline = d.iter().filter(|d| (**d - radius).abs() < 0.01).count() as f64;
+            assert!((cn - expected).abs() <= borderline, "residue {}: contact number {} within {} Å, expected {}", r.index, cn, radius, expected);
+            if !up.is_nan() { assert_eq!(up + down, cn, "residue {}: half-spheres should add up to the contact number", r.index); }
+        }
+    }
+    // --- contact numbers grow with the radius
+    assert!(parsed.iter().all(|r| r.features[2] <= r.features[5]));
+}
+
+#[test]
+fn interface_residues_are_flagged_against_other_chains() {
+    use featurizer::chain_pair::InterfaceResidues;
+
+    let config = FeaturizerConfig { extractors: vec![Box::new(InterfaceResidues)], ..Default::default() };
+    let lines = run(&format!("{}/1c5n.cif", INPUTS), "L", "1c5n_L_interface", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 2)).collect();
+    for r in &parsed {
+        let (flag, d) = (r.features[0], r.features[1]);
+        assert_eq!(flag == 1.0, d <= 5.0, "residue {}: flag {} at {} Å", r.index, flag, d);
+        assert!(d.is_nan() || d <= 10.0);
+    }
+    // --- the light chain of thrombin is bound to its heavy chain
+    assert!(parsed.iter().any(|r| r.features[0] == 1.0));
+    assert!(parsed.iter().any(|r| r.features[0] == 0.0));
+    // --- a chain alone in its deposit has no interface
+    let lines = run(&format!("{}/2gb1.cif", INPUTS), "A", "2gb1_A_interface", &config);
+    assert!(lines.iter().filter_map(|l| records::parse_record(l, 2)).all(|r| r.features[0] == 0.0));
+}
+
+#[test]
+fn exposure_classes_follow_relative_accessibility() {
+    use featurizer::exposure::{MaxAsaTable, SolventExposure};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    assert!(SolventExposure::new(&[0.5, 0.2], MaxAsaTable::Tien).is_err());
+    let exposure = SolventExposure::new(&[0.25, 0.5], MaxAsaTable::Miller).unwrap();
+    let config = FeaturizerConfig { extractors: vec![Box::new(exposure)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_exposure", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 3)).collect();
+    let mut counts = [0; 3];
+    for r in &parsed {
+        let (area, rsa, class) = (r.features[0], r.features[1], r.features[2]);
+        assert!(area >= 0.0 && rsa >= 0.0, "residue {}: area {}, relative accessibility {}", r.index, area, rsa);
+        let expected = if rsa < 0.25 { 0.0 } else if rsa < 0.5 { 1.0 } else { 2.0 };
+        // --- relative accessibility is written rounded, so a residue right at a threshold may be labelled either way
+        if (rsa - 0.25).abs() > 0.01 && (rsa - 0.5).abs() > 0.01 { assert_eq!(class, expected, "residue {}", r.index); }
+        counts[class as usize] += 1;
+    }
+    // --- protein G has a hydrophobic core packed between its sheet and helix, and its termini are exposed
+    assert!(counts.iter().all(|n| *n > 0), "{:?}", counts);
+    assert_eq!(parsed.last().unwrap().features[2], 2.0);
+}
+
+#[test]
+fn custom_scales_are_read_from_a_csv_table() {
+    // --- the first half of the alphabet, by one-letter codes, and methionine by its three-letter code
+    let table = out_root("custom_scales.csv");
+    let mut csv = "residue,index,double\n".to_string();
+    for (k, aa) in ALPHABET.chars().take(10).enumerate() { csv += &format!("{},{},{}\n", aa, k, 2 * k); }
+    csv += &format!("MET,{},{}\n", aa_index("MET"), 2 * aa_index("MET"));
+    fs::write(&table, csv).unwrap();
+    let scales = PropertyScales::from_csv(&table).unwrap();
+    assert_eq!(scales.names, vec!["index", "double"]);
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(scales)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_scales", &config);
+    // --- a line is written for every entity residue, so lines follow the entity sequence
+    for (line, aa) in lines.iter().zip(entity_sequence(&fname, "1").chars()) {
+        let Some(r) = records::parse_record(line, 2) else { continue };
+        let k = ALPHABET.find(aa).unwrap();
+        if k < 10 || aa == 'M' {
+            assert_eq!(r.features, vec![k as f64, 2.0 * k as f64], "residue {} {}", r.index, aa);
+        } else {
+            assert!(r.features.iter().all(|v| v.is_nan()), "residue {} {} is not in the table", r.index, aa);
+        }
+    }
+}
+
+#[test]
+fn ramachandran_regions_follow_secondary_structure() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(RamachandranRegions)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_rama", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 1)).collect();
+    assert!(parsed.first().unwrap().features[0].is_nan() && parsed.last().unwrap().features[0].is_nan(), "termini have no phi or psi");
+    let labels = |ss: char| -> Vec<u8> { parsed.iter().filter(|r| r.ss == ss && !r.features[0].is_nan()).map(|r| r.features[0] as u8).collect() };
+    let (helix, strand) = (labels('H'), labels('E'));
+    assert!(!helix.is_empty() && !strand.is_empty());
+    // --- helical residues are in the alpha basin, strands in the beta or polyproline II ones
+    assert!(helix.iter().filter(|l| **l == 0).count() * 10 >= helix.len() * 9, "helix labels: {:?}", helix);
+    assert!(strand.iter().filter(|l| **l == 1 || **l == 3).count() * 10 >= strand.len() * 9, "strand labels: {:?}", strand);
+}
+
+#[test]
+fn distorted_backbone_fails_the_geometry_gate() {
+    let strctr = load_chain(&format!("{}/2gb1.cif", INPUTS), "A").unwrap();
+    let resids = strctr.residue_ids();
+    let clean = geometry_quality(&strctr, &resids);
+    // --- CA of the 10th residue is moved onto CA of the 30th one: it clashes and stretches its bonds
+    let target = strctr.atom(&resids[30], " CA ").unwrap().pos.clone();
+    let mut atoms = strctr.atoms().to_vec();
+    let moved = atoms.iter_mut().find(|a| a.res_seq == resids[10].res_seq && a.name == " CA ").unwrap();
+    moved.pos = target;
+    let distorted = Structure::from_iterator(&strctr.id_code, atoms.iter());
+    let quality = geometry_quality(&distorted, &resids);
+    assert!(quality.n_clashes > clean.n_clashes);
+    assert!(quality.n_bond_outliers >= clean.n_bond_outliers + 2, "N-CA and CA-C bonds should be outliers");
+
+    let filters = ChainFilters { max_clashes: Some(clean.n_clashes), max_bond_outliers: Some(clean.n_bond_outliers), ..Default::default() };
+    let stats = statistics(&strctr, &resids, resids.len(), None, Source::Pdb, &filters);
+    assert!(filters.violations(&stats).is_empty());
+    let stats = statistics(&distorted, &resids, resids.len(), None, Source::Pdb, &filters);
+    let failed: Vec<&str> = filters.violations(&stats).iter().map(|v| v.criterion).collect();
+    assert!(failed.contains(&"max-clashes") && failed.contains(&"max-bond-outliers"), "violated: {:?}", failed);
+}
+
+#[test]
+fn deposits_are_filtered_by_their_release_date() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    assert_eq!(release_date(&fname).unwrap(), Some("1993-04-15".parse::<ReleaseDate>().unwrap()));
+
+    let before = |date: &str| ChainFilters { released_before: Some(date.parse().unwrap()), ..Default::default() };
+    let after = |date: &str| ChainFilters { released_after: Some(date.parse().unwrap()), ..Default::default() };
+    for (filters, passes) in [(before("1993-04-16"), true), (before("1993-04-15"), false), (after("1993-04-15"), true), (after("2000-01-01"), false)] {
+        let config = FeaturizerConfig { filters, ..Default::default() };
+        let result = featurize_chain(&fname, "A", &out_root("2gb1_released"), &config, &CancellationToken::new());
+        assert_eq!(result.is_ok(), passes, "{:?}: {:?}", config.filters, result.as_ref().err());
+        if !passes { assert!(matches!(result, Err(FeaturizerError::Filtered(_)))); }
+    }
+}
+
+#[test]
+fn missing_metadata_is_read_from_cached_rcsb_entries() {
+    use featurizer::remote::{entry_code, parse_entry};
+
+    let json = r#"{"rcsb_accession_info": {"deposit_date": "1991-05-15T00:00:00+0000", "initial_release_date": "1993-04-15T00:00:00+0000"},
+        "rcsb_entry_info": {"resolution_combined": [2.8, 3.1], "polymer_entity_count": 1}}"#;
+    let entry = parse_entry(json);
+    assert_eq!(entry.resolution, Some(2.8));
+    assert_eq!(entry.release_date, Some("1993-04-15".parse::<ReleaseDate>().unwrap()));
+    assert_eq!(parse_entry("{}").resolution, None);
+    assert_eq!(entry_code("mirror/pdb2GB1.ent.gz").as_deref(), Some("2gb1"));
+    assert_eq!(entry_code("models/AF-P12345-F1-model_v4.cif"), None);
+
+    // --- the NMR structure of protein G has no resolution, the cached entry gives one, so no request is sent
+    let cache = out_root("rcsb_cache");
+    fs::create_dir_all(&cache).unwrap();
+    fs::write(format!("{}/2gb1.json", cache), json).unwrap();
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let filters = |remote: bool| ChainFilters { max_resolution: Some(2.5), remote_metadata: remote.then(|| cache.clone()), ..Default::default() };
+    for (remote, passes) in [(false, true), (true, false)] {
+        let config = FeaturizerConfig { filters: filters(remote), ..Default::default() };
+        let result = featurize_chain(&fname, "A", &out_root("2gb1_remote"), &config, &CancellationToken::new());
+        assert_eq!(result.is_ok(), passes, "{:?}", result.as_ref().err());
+    }
+}
+
+#[test]
+fn touched_inputs_are_unchanged_until_their_content_changes() {
+    let fname = out_root("2gb1_update.cif");
+    fs::copy(format!("{}/2gb1.cif", INPUTS), &fname).unwrap();
+    let (state, changed) = check_input(&fname, None).unwrap();
+    assert!(changed, "a new input is always featurized");
+    assert_eq!(check_input(&fname, Some(&state)).unwrap(), (state.clone(), false));
+
+    let touched = InputState { mtime: state.mtime - 60, ..state.clone() };
+    assert!(!check_input(&fname, Some(&touched)).unwrap().1, "the same content with another mtime is unchanged");
+    fs::write(&fname, fs::read_to_string(&fname).unwrap().replace("2GB1", "2GB1 ")).unwrap();
+    assert!(check_input(&fname, Some(&state)).unwrap().1);
+
+    let db = out_root("2gb1_update.inputs.tsv");
+    let entries: BTreeMap<_, _> = [((fname.clone(), Some("A".to_string())), state.clone()), ((fname.clone(), None), state)].into_iter().collect();
+    write_input_database(&entries, &db).unwrap();
+    assert_eq!(read_input_database(&db).unwrap(), entries);
+}
+
+#[test]
+fn obsolete_entries_are_resolved_to_their_superseding_ones() {
+    let fname = out_root("obsolete.dat");
+    fs::write(&fname, " LIST OF OBSOLETE COORDINATE ENTRIES AND SUCCESSORS\n\
+        OBSLTE    31-JAN-94 1GB1     1XYZ\n\
+        OBSLTE    15-MAR-95 1XYZ     2GB1 3GB1\n\
+        OBSLTE    02-JUN-97 1ABC\n").unwrap();
+    let obsolete = ObsoleteEntries::from_file(&fname).unwrap();
+    assert_eq!(obsolete.replaced_by("1GB1").as_deref(), Some("1xyz"));
+    assert_eq!(obsolete.replaced_by("1abc"), None, "withdrawn entries have no successor");
+    assert_eq!(obsolete.resolve("1gb1", |c| c == "2gb1").as_deref(), Some("2gb1"), "supersessions are followed to an existing entry");
+    assert_eq!(obsolete.resolve("1gb1", |_| false), None);
+    assert_eq!(obsolete.resolve("2gb1", |_| true), None);
+
+    let json = r#"{"rcsb_repository_holdings_removed":{"id_codes_replaced_by":["4HHB","5HHB"],"remove_date":"2002-01-01"}}"#;
+    assert_eq!(parse_replaced_by(json).as_deref(), Some("4hhb"));
+    assert_eq!(parse_replaced_by(r#"{"rcsb_repository_holdings_removed":{"id_codes_replaced_by":[]}}"#), None);
+}
+
+#[test]
+fn importance_probe_finds_signal_in_ca_geometry() {
+    let config = FeaturizerConfig { extractors: vec![Box::new(CaTraceGeometry)], ..Default::default() };
+    let summaries: Vec<_> = [("2gb1.cif", "A"), ("1c5n.cif", "L"), ("1c5n.cif", "H")].iter().map(|(deposit, chain)| {
+        let root = out_root(&format!("{}_{}_probe", deposit, chain));
+        featurize_chain(&format!("{}/{}", INPUTS, deposit), chain, &root, &config, &CancellationToken::new()).unwrap()
+    }).collect();
+    let data = sample_residues(&summaries, &ProbeTarget::parse("ss=H"), &[], 10000, 0).unwrap();
+    assert_eq!(data.names, vec!["ca_geometry.0", "ca_geometry.1", "ca_geometry.2"]);
+    assert_eq!(data.rows.len(), data.target.len());
+    assert_eq!(sample_residues(&summaries, &ProbeTarget::parse("ss=H"), &[], 50, 0).unwrap().rows.len(), 50);
+
+    let report = importance_probe(&data, &ProbeParams { min_leaf: 5, ..Default::default() });
+    let total: f64 = report.importances.iter().map(|(_, v)| v).sum();
+    assert!((total - 1.0).abs() < 1e-6);
+    assert!(report.importances.windows(2).all(|w| w[0].1 >= w[1].1), "importances should be sorted");
+    // --- helices have a characteristic CA trace, so the baseline must do much better than the mean
+    assert!(report.r2_train > 0.5, "R2 {} is too low", report.r2_train);
+}
+
+#[test]
+fn crops_are_fixed_length_windows_of_the_records() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_crops");
+    let crop = Crop { length: 20, stride: 15 };
+    let config = FeaturizerConfig { crop: Some(crop.clone()), ..Default::default() };
+    let records = run(&fname, "A", "2gb1_A_crops", &config);
+    let crops = fs::read_to_string(format!("{}.crops", root)).unwrap();
+    let mut blocks: Vec<(usize, Vec<&str>)> = vec![];
+    for line in crops.lines() {
+        match line.strip_prefix("# window ") {
+            Some(header) => blocks.push((header.split_whitespace().nth(1).unwrap().parse().unwrap(), vec![])),
+            None => blocks.last_mut().unwrap().1.push(line),
+        }
+    }
+    assert_eq!(blocks.len(), crop.windows(records.len()).len());
+    for (first, lines) in &blocks {
+        assert_eq!(lines.len(), crop.length);
+        assert!(lines.iter().zip(&records[*first..]).all(|(l, r)| l == r), "crops should keep global indexes and H-bonds");
+    }
+    assert_eq!(blocks.last().unwrap().0 + crop.length, records.len());
+}
+
+#[test]
+fn long_chains_are_split_into_overlapping_parts() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_parts");
+    let split = LinkerSplit { max_length: 30, overlap: 5 };
+    let config = FeaturizerConfig { split: Some(split.clone()), ..Default::default() };
+    let records = run(&fname, "A", "2gb1_A_parts", &config);
+    let parts = fs::read_to_string(format!("{}.parts", root)).unwrap();
+    let headers: Vec<Vec<&str>> = parts.lines().filter_map(|l| l.strip_prefix("# part ")).map(|h| h.split_whitespace().collect()).collect();
+    assert!(headers.len() >= 2);
+    let ranges: Vec<(usize, usize)> = headers.iter().map(|h| (h[1].parse().unwrap(), h[2].parse().unwrap())).collect();
+    assert_eq!(ranges[0].0, 0);
+    assert_eq!(ranges.last().unwrap().1 + 1, records.len());
+    assert!(ranges.windows(2).all(|r| r[1].0 + 2 * split.overlap <= r[0].1 + 1), "consecutive parts should overlap");
+    assert!(headers.iter().all(|h| h[3] == "2gb1_A_parts"));
+}
+
+#[test]
+fn malformed_atom_records_are_skipped_in_lenient_mode() {
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let columns: Vec<&str> = text.lines().filter_map(|l| l.trim().strip_prefix("_atom_site.")).collect();
+    let (atom, x) = (columns.iter().position(|c| *c == "label_atom_id").unwrap(), columns.iter().position(|c| *c == "Cartn_x").unwrap());
+    // --- break the coordinates of the first CB atom
+    let mut done = false;
+    let broken: Vec<String> = text.lines().map(|l| {
+        let mut t: Vec<&str> = l.split_whitespace().collect();
+        if done || !l.starts_with("ATOM") || t[atom] != "CB" { return l.to_string(); }
+        done = true;
+        t[x] = "1.2.3";
+        t.join(" ")
+    }).collect();
+    let fname = out_root("2gb1_broken.cif");
+    fs::write(&fname, broken.join("\n")).unwrap();
+    let config = FeaturizerConfig { lenient: true, ..Default::default() };
+    let summary = featurize_chain(&fname, "A", &out_root("2gb1_A_lenient"), &config, &CancellationToken::new()).unwrap();
+    assert_eq!(summary.skipped_records, 1);
+    assert_eq!(summary.n_observed, ca_records(&format!("{}/2gb1.cif", INPUTS), "A").len());
+}
+
+#[test]
+fn graph_edges_are_typed_and_consistent_with_records() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { graph: Some(8.0), ..Default::default() };
+    let records = run(&fname, "A", "2gb1_A_graph", &config);
+    let graph = fs::read_to_string(format!("{}.graph", out_root("2gb1_A_graph"))).unwrap();
+    let nodes: Vec<&str> = graph.lines().filter(|l| l.starts_with("N ")).collect();
+    let edges: Vec<Vec<&str>> = graph.lines().filter(|l| l.starts_with("E ")).map(|l| l.split_whitespace().collect()).collect();
+    assert_eq!(nodes.len(), records.len());
+    let n_peptide = edges.iter().filter(|e| e[3] == "peptide").count();
+    assert_eq!(n_peptide, nodes.len() - 1, "2gb1 has no chain breaks");
+    // --- every H-bond of the .hbonds file is an edge of the graph, directed from the donor to the acceptor
+    let listed = records::read_hbonds(&out_root("2gb1_A_graph")).unwrap();
+    let hbonds: Vec<&Vec<&str>> = edges.iter().filter(|e| e[3] == "hbond").collect();
+    assert_eq!(hbonds.len(), listed.len());
+    for (e, hb) in hbonds.iter().zip(&listed) {
+        assert_eq!((e[1], e[2]), (hb.donor.as_str(), hb.acceptor.as_str()));
+    }
+    assert!(hbonds.iter().all(|e| e[5].parse::<f64>().unwrap() < 0.0));
+    for e in edges.iter().filter(|e| e[3] == "contact") {
+        let (i, j, d): (usize, usize, f64) = (e[1].parse().unwrap(), e[2].parse().unwrap(), e[4].parse().unwrap());
+        assert!(j > i + 1 && d <= 8.0);
+    }
+}
+
+#[test]
+fn conservation_is_computed_from_homologs_in_a_database() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let sequence = entity_sequence(&fname, "1");
+    // --- five homologs: three of them mutated at position 10, one lacking the first five residues; an unrelated sequence last
+    let mut homologs: Vec<String> = vec![sequence.clone(); 5];
+    for (k, aa) in ["W", "P", "C"].iter().enumerate() { homologs[k].replace_range(10..11, aa); }
+    homologs[4] = sequence[5..].to_string();
+    homologs.push("GSGSGSGSGSGSGSGSGSGSGSGSGSGS".to_string());
+    let db = out_root("2gb1_homologs.fasta");
+    let fasta: String = homologs.iter().enumerate().map(|(k, s)| format!(">seq{}\n{}\n", k, s)).collect();
+    fs::write(&db, fasta).unwrap();
+
+    let database = SequenceDatabase::from_fasta(&db).unwrap();
+    assert_eq!(database.len(), 6);
+    let extractor = SequenceConservation { database, max_hits: MAX_HITS, min_identity: MIN_IDENTITY };
+    let config = FeaturizerConfig { extractors: vec![Box::new(extractor)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_conservation", &config);
+    let values: Vec<Vec<f64>> = lines.iter().filter_map(|l| records::parse_record(l, 2)).map(|r| r.features).collect();
+    assert_eq!(values.len(), sequence.len());
+    // --- the unrelated sequence is not a homolog, so gap frequencies are counted out of six sequences
+    assert!((values[0][1] - 1.0 / 6.0).abs() < 1e-3, "gap frequency {} != 1/6", values[0][1]);
+    assert!(values[20][1].abs() < 1e-3);
+    assert!((values[20][0] - 1.0).abs() < 1e-3, "an invariant position should be fully conserved");
+    assert!(values[10][0] < values[20][0] && values[10][0] > 0.0, "a mutated position should be less conserved");
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn signed_manifests_are_verified() {
+    use featurizer::crypto::{read_signing_key, sign_file, verify_file};
+    let (key_file, manifest) = (out_root("sign.key"), out_root("signed_manifest.tsv"));
+    fs::write(&key_file, "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60\n").unwrap();
+    fs::write(&manifest, "#key\toutput\npdb/2gb1_A\t2gb1_A.dat\n").unwrap();
+    sign_file(&manifest, &read_signing_key(&key_file).unwrap()).unwrap();
+    assert_eq!(verify_file(&manifest).unwrap(), "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+    fs::write(&manifest, "#key\toutput\npdb/2gb1_B\t2gb1_A.dat\n").unwrap();
+    assert!(verify_file(&manifest).is_err(), "a modified manifest should not match its signature");
+}
+
+#[test]
+fn provenance_records_the_input_and_columns() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_meta");
+    let config = FeaturizerConfig { extractors: vec![Box::new(CaTraceGeometry), Box::new(ChiAngles)], ..Default::default() };
+    run(&fname, "A", "2gb1_A_meta", &config);
+    let meta: HashMap<String, String> = fs::read_to_string(format!("{}.meta", root)).unwrap().lines()
+        .filter_map(|l| l.split_once(" = ")).map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    assert_eq!(meta["input"], fname);
+    assert_eq!(meta["chain"], "A");
+    assert_eq!(meta["input_sha256"].len(), 64);
+    // --- 9 core columns, 3 of CA geometry, 8 of chi angles and H-bonds
+    assert_eq!(meta["columns"].split_whitespace().count(), 9 + 3 + 8 + 1);
+}
+
+#[test]
+fn provenance_records_entity_and_deposit_metadata() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_entity");
+    run(&fname, "A", "2gb1_A_entity", &FeaturizerConfig::default());
+    let meta: HashMap<String, String> = fs::read_to_string(format!("{}.meta", root)).unwrap().lines()
+        .filter_map(|l| l.split_once(" = ")).map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    assert_eq!(meta["entity.id"], "1");
+    assert_eq!(meta["entity.description"], "PROTEIN G");
+    assert_eq!(meta["entity.polymer_type"], "polypeptide(L)");
+    // --- the organism is given as a multi-line text field
+    assert_eq!(meta["entity.organism"], "Streptococcus sp. 'group G'");
+    assert_eq!(meta["entity.taxonomy_id"], "1320");
+    assert_eq!(meta["deposit.method"], "SOLUTION NMR");
+    assert_eq!(meta["deposit.deposition_date"], "1991-05-15");
+    // --- an NMR structure has no resolution and the enzyme has no EC number
+    assert!(!meta.contains_key("deposit.resolution"));
+    assert!(!meta.contains_key("entity.ec"));
+}
+
+#[test]
+fn streamed_chains_give_the_same_outputs() {
+    use featurizer::contacts::ContactSpec;
+    use featurizer::streaming::{atom_chain_ids, chain_text};
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let ids = atom_chain_ids(&fname).unwrap();
+    assert!(ids.contains(&"L".to_string()) && ids.contains(&"H".to_string()));
+    let text = chain_text(&fname, &["L"]).unwrap();
+    let streamed = LoadedDeposit::from_text("1c5n_L", &text, &FeaturizerConfig::default()).unwrap();
+    assert_eq!(featurizer::chains::polymer_chain_ids(&streamed.deposit), vec!["L".to_string()]);
+    // --- contacts of the chunked path are listed in the same order
+    for matrix in [false, true] {
+        let contacts = Some("CB:8.0".parse::<ContactSpec>().unwrap());
+        let config = FeaturizerConfig { contacts: contacts.clone(), contacts_matrix: matrix, ..Default::default() };
+        let low_memory = FeaturizerConfig { contacts, contacts_matrix: matrix, low_memory: true, ..Default::default() };
+        let (name, streamed_name) = (format!("1c5n_L_full_{}", matrix), format!("1c5n_L_streamed_{}", matrix));
+        assert_eq!(run(&fname, "L", &name, &config), run(&fname, "L", &streamed_name, &low_memory));
+        let read = |name: &str| fs::read_to_string(format!("{}.contacts", out_root(name))).unwrap();
+        assert_eq!(read(&name), read(&streamed_name));
+    }
+}
+
+#[test]
+fn chains_of_the_same_protein_share_a_conformer_group() {
+    let token = CancellationToken::new();
+    let config = FeaturizerConfig { conformer_groups: true, ..Default::default() };
+    let mut summaries = vec![];
+    for (deposit, chain, name) in [("2gb1.cif", "A", "2gb1_A_group"), ("2gb1.cif", "A", "2gb1_A_group_copy"),
+                                   ("1c5n.cif", "H", "1c5n_H_group"), ("1c5n.cif", "L", "1c5n_L_group")] {
+        summaries.push(featurize_chain(&format!("{}/{}", INPUTS, deposit), chain, &out_root(name), &config, &token).unwrap());
+    }
+    let groups = group_conformers(&summaries);
+    assert_eq!(groups.len(), 3);
+    assert!(groups.values().any(|chains| chains.len() == 2 && chains.iter().all(|s| s.id.contains("2gb1_A"))));
+    let meta = fs::read_to_string(format!("{}.meta", out_root("2gb1_A_group"))).unwrap();
+    assert!(meta.lines().any(|l| l == format!("entity.conformer_group = {}", summaries[0].conformer_group.as_ref().unwrap())));
+    // --- mapped chains are grouped by their UniProt accessions
+    let table = out_root("pdb_chain_uniprot_groups.tsv");
+    fs::write(&table, "2gb1\tA\tP19909\t25\t56\t25\t56\t1\t32\n2gb1\tA\tP06654\t1\t20\t1\t20\t303\t322\n").unwrap();
+    let config = FeaturizerConfig { conformer_groups: true, uniprot: Some(UniProtMapping::from_sifts(&table).unwrap()), ..Default::default() };
+    let mapped = featurize_chain(&format!("{}/2gb1.cif", INPUTS), "A", &out_root("2gb1_A_group_uniprot"), &config, &token).unwrap();
+    assert_eq!(mapped.conformer_group.as_deref(), Some("uniprot:P06654+P19909"));
+}
+
+#[test]
+fn stage_timings_are_reported_per_file() {
+    use featurizer::timings::{write_timings, StageTimings};
+
+    let token = CancellationToken::new();
+    let config = FeaturizerConfig { extractors: vec![Box::new(HalfSphereExposure::default())], ..Default::default() };
+    let summaries = vec![
+        featurize_chain(&format!("{}/1c5n.cif", INPUTS), "H", &out_root("1c5n_H_timings"), &config, &token).unwrap(),
+        featurize_chain(&format!("{}/1c5n.cif", INPUTS), "L", &out_root("1c5n_L_timings"), &config, &token).unwrap(),
+        featurize_chain(&format!("{}/2gb1.cif", INPUTS), "A", &out_root("2gb1_A_timings"), &config, &token).unwrap(),
+    ];
+    assert!(summaries.iter().all(|s| s.timings.parsing > std::time::Duration::ZERO && s.timings.hbonds > std::time::Duration::ZERO));
+    let fname = out_root("timings.tsv");
+    let total = write_timings(&summaries, &fname).unwrap();
+    let mut expected = StageTimings::default();
+    for s in &summaries { expected.add(&s.timings); }
+    assert_eq!(total, expected);
+    let text = fs::read_to_string(&fname).unwrap();
+    let lines: Vec<Vec<&str>> = text.lines().map(|l| l.split('\t').collect()).collect();
+    // --- a header, a row for each of the two input files and the totals
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], vec!["#input", "parsing", "hbonds", "secondary", "features", "writing", "total"]);
+    assert!(lines[1][0].ends_with("1c5n.cif") && lines[2][0].ends_with("2gb1.cif") && lines[3][0] == "total");
+    let stages: f64 = lines[3][1..6].iter().map(|v| v.parse::<f64>().unwrap()).sum();
+    assert!((stages - lines[3][6].parse::<f64>().unwrap()).abs() < 0.01);
+}
+
+#[test]
+fn dataset_manifest_detects_modified_files() {
+    use featurizer::reproducibility::{config_sha256, dataset_manifest, verify_dataset};
+
+    let config = FeaturizerConfig::default();
+    let summary = featurize_chain(&format!("{}/2gb1.cif", INPUTS), "A", &out_root("2gb1_A_dataset"), &config, &CancellationToken::new()).unwrap();
+    let dataset = dataset_manifest(&[summary], &config).unwrap();
+    assert_eq!(dataset.config_sha256, config_sha256(&FeaturizerConfig::default()));
+    assert_eq!((dataset.chains[0].pdb_id.as_str(), dataset.chains[0].chain.as_str()), ("2gb1", "A"));
+    let files = &dataset.chains[0].files;
+    assert!(files.iter().any(|f| f.path.ends_with(".dat")) && files.iter().all(|f| f.sha256.len() == 64));
+    assert!(verify_dataset(&dataset).is_empty());
+    let dat = files.iter().find(|f| f.path.ends_with(".dat")).unwrap();
+    fs::write(&dat.path, fs::read_to_string(&dat.path).unwrap().replacen('H', "E", 1)).unwrap();
+    let problems = verify_dataset(&dataset);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with(&dat.path));
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn chains_are_packed_into_shards() {
+    use featurizer::archive::{pack_dataset, ArchiveFormat};
+
+    let token = CancellationToken::new();
+    let summaries = vec![
+        featurize_chain(&format!("{}/2gb1.cif", INPUTS), "A", &out_root("2gb1_A_pack"), &FeaturizerConfig::default(), &token).unwrap(),
+        featurize_chain(&format!("{}/1c5n.cif", INPUTS), "L", &out_root("1c5n_L_pack"), &FeaturizerConfig::default(), &token).unwrap(),
+    ];
+    let prefix = out_root("shards/pack");
+    let index = pack_dataset(&summaries, ArchiveFormat::Tar, 1, &prefix).unwrap();
+    assert_eq!(index.len(), 2);
+    for (k, (entry, summary)) in index.iter().zip(&summaries).enumerate() {
+        assert_eq!(entry.shard, format!("pack-{:05}.tar", k));
+        assert!(PathBuf::from(format!("{}-{:05}.tar", prefix, k)).exists());
+        assert_eq!(entry.members, vec![format!("{}.dat", summary.key), format!("{}.meta", summary.key)]);
+    }
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn packed_chains_are_read_in_place() {
+    use featurizer::archive::{pack_dataset, write_index, ArchiveFormat};
+    use featurizer::dataset::FeatureDataset;
+
+    let token = CancellationToken::new();
+    let roots = [out_root("2gb1_A_mapped"), out_root("1c5n_L_mapped")];
+    let summaries = vec![
+        featurize_chain(&format!("{}/2gb1.cif", INPUTS), "A", &roots[0], &FeaturizerConfig::default(), &token).unwrap(),
+        featurize_chain(&format!("{}/1c5n.cif", INPUTS), "L", &roots[1], &FeaturizerConfig::default(), &token).unwrap(),
+    ];
+    let prefix = out_root("shards/mapped");
+    let index = pack_dataset(&summaries, ArchiveFormat::Tar, 1, &prefix).unwrap();
+    write_index(&index, &format!("{}.index.tsv", prefix)).unwrap();
+    let dataset = FeatureDataset::open(&format!("{}.index.tsv", prefix)).unwrap();
+    assert_eq!(dataset.len(), 2);
+    let chain = dataset.get(&summaries[0].key).unwrap();
+    assert_eq!(chain.dat().unwrap(), fs::read_to_string(format!("{}.dat", roots[0])).unwrap());
+    assert_eq!(chain.records().unwrap().len(), ca_records(&format!("{}/2gb1.cif", INPUTS), "A").len());
+    assert!(dataset.get("no_such_chain").is_none());
+}
+
+#[test]
+fn retry_list_skips_filtered_chains() {
+    use featurizer::failures::{write_retry_list, Failure};
+
+    let failure = |input: &str, chain: &str, kind: &str| Failure { input: input.to_string(), chain: chain.to_string(),
+        kind: kind.to_string(), message: String::new() };
+    let failures = vec![failure("/data/pdb/1abc.cif.gz", "A", "NoSuchChain"), failure("/data/pdb/1abc.cif.gz", "A", "Io"),
+        failure("/data/pdb/2xyz.cif", "B", "Filtered"), failure("3def", "-", "NotFound")];
+    let fname = out_root("failed.list");
+    write_retry_list(&failures, &fname).unwrap();
+    assert_eq!(fs::read_to_string(&fname).unwrap(), "1abc:A\n3def\n");
+}
+
+#[test]
+fn records_flushed_per_residue_match_buffered_ones() {
+    use featurizer::records::FlushPolicy;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let buffered = run(&fname, "A", "2gb1_A_buffered", &FeaturizerConfig::default());
+    let flushed = run(&fname, "A", "2gb1_A_flushed", &FeaturizerConfig { flush: FlushPolicy::Residue, ..Default::default() });
+    assert_eq!(flushed, buffered);
+}
+
+#[test]
+fn weak_and_surplus_hbonds_are_dropped() {
+    use featurizer::hbonds::HBondPolicy;
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let read = |name: &str, hbonds: HBondPolicy| {
+        run(&fname, "L", name, &FeaturizerConfig { hbonds, ..Default::default() });
+        records::read_hbonds(&out_root(name)).unwrap()
+    };
+    let all = read("1c5n_L_hbonds_all", HBondPolicy::default());
+    let strong = read("1c5n_L_hbonds_strong", HBondPolicy { cutoff: -2.0, ..Default::default() });
+    assert!(strong.len() < all.len());
+    assert!(strong.iter().all(|hb| hb.energy <= -2.0));
+    let best = read("1c5n_L_hbonds_best", HBondPolicy { best: Some(1), ..Default::default() });
+    assert!(best.len() < all.len());
+    for hb in &best {
+        assert_eq!(best.iter().filter(|other| other.donor == hb.donor).count(), 1, "residue {} donates twice", hb.donor);
+        assert_eq!(best.iter().filter(|other| other.acceptor == hb.acceptor).count(), 1, "residue {} accepts twice", hb.acceptor);
+    }
+}
+
+#[test]
+fn sheet_topology_pairs_strands_of_a_single_sheet() {
+    use featurizer::sheets::SheetTopologyFeatures;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(SheetTopologyFeatures)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_sheets", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 6)).collect();
+    let strands: std::collections::BTreeSet<usize> = parsed.iter().filter(|r| !r.features[0].is_nan()).map(|r| r.features[0] as usize).collect();
+    // --- the four strands of protein G B1 form a single sheet, with both parallel and antiparallel pairs
+    assert_eq!(strands.len(), 4, "strands found: {:?}", strands);
+    assert!(parsed.iter().filter(|r| !r.features[1].is_nan()).all(|r| r.features[1] == 0.0));
+    let orientations: Vec<f64> = parsed.iter().flat_map(|r| [r.features[3], r.features[5]]).filter(|v| !v.is_nan()).collect();
+    assert!(orientations.contains(&1.0) && orientations.contains(&-1.0));
+    for r in &parsed {
+        for k in [2, 4] {
+            if r.features[k].is_nan() { continue; }
+            let partner = &parsed[r.features[k] as usize];
+            assert!(!r.features[0].is_nan() && !partner.features[0].is_nan(), "bridge partners must be in strands");
+            assert_ne!(r.features[0], partner.features[0], "a strand can't be paired with itself");
+        }
+    }
+}
+
+#[test]
+fn helix_geometry_matches_an_alpha_helix() {
+    use featurizer::helices::HelixGeometry;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(HelixGeometry)], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_helix", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 5)).collect();
+    let helical: Vec<_> = parsed.iter().filter(|r| r.ss == 'H').collect();
+    assert!(parsed.iter().filter(|r| r.ss != 'H').all(|r| r.features.iter().all(|v| v.is_nan())));
+    let fitted: Vec<_> = helical.iter().filter(|r| !r.features[0].is_nan()).collect();
+    assert!(fitted.len() >= 10, "only {} helical residues fitted", fitted.len());
+    for r in &fitted {
+        let length = (r.features[0].powi(2) + r.features[1].powi(2) + r.features[2].powi(2)).sqrt();
+        assert!((length - 1.0).abs() < 1e-2, "residue {}: the axis is not a unit vector", r.index);
+    }
+    let n = fitted.len() as f64;
+    let rise = fitted.iter().map(|r| r.features[3]).sum::<f64>() / n;
+    let radius = fitted.iter().map(|r| r.features[4]).sum::<f64>() / n;
+    assert!((rise - 1.5).abs() < 0.2, "mean rise {}", rise);
+    assert!((radius - 2.3).abs() < 0.3, "mean radius {}", radius);
+}
+
+#[test]
+fn core_residues_are_deeper_than_surface_ones() {
+    use featurizer::depth::ResidueDepth;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(ResidueDepth::default())], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_depth", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 2)).collect();
+    let ca_depth: Vec<f64> = parsed.iter().map(|r| r.features[0]).collect();
+    assert!(ca_depth.iter().all(|d| d.is_finite() && *d > 1.0), "CA atoms lie within their own van der Waals spheres");
+    let (min, max) = ca_depth.iter().fold((f64::INFINITY, 0.0_f64), |(lo, hi), d| (lo.min(*d), hi.max(*d)));
+    assert!(max - min > 2.0, "depth range [{}, {}] is too narrow", min, max);
+    assert!(parsed.iter().all(|r| r.features[1] >= 0.0));
+}
+
+#[test]
+fn crystal_contacts_are_found_with_symmetry_mates() {
+    use featurizer::crystal::{space_group_operators, CrystalContacts};
+
+    assert_eq!(space_group_operators("P 21 21 21").map(|ops| ops.len()), Some(4));
+    assert_eq!(space_group_operators("C2").map(|ops| ops.len()), Some(4));
+    assert_eq!(space_group_operators("P 61 2 2").map(|ops| ops.len()), Some(12));
+    assert!(space_group_operators("X 9").is_none());
+    let config = FeaturizerConfig { extractors: vec![Box::new(CrystalContacts::default())], ..Default::default() };
+    // --- 1c5n has been solved in C 1 2 1
+    let lines = run(&format!("{}/1c5n.cif", INPUTS), "H", "1c5n_H_crystal", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 2)).collect();
+    let n_contacts = parsed.iter().filter(|r| r.features[0] == 1.0).count();
+    assert!(n_contacts > 0 && n_contacts < parsed.len() / 2, "{} of {} residues in crystal contacts", n_contacts, parsed.len());
+    for r in &parsed {
+        assert!(r.features[0] == 0.0 || r.features[0] == 1.0);
+        if r.features[0] == 1.0 { assert!(r.features[1] <= 4.0 && r.features[1] > 2.0, "distance {}", r.features[1]); }
+    }
+    // --- an NMR structure has no crystal lattice
+    let lines = run(&format!("{}/2gb1.cif", INPUTS), "A", "2gb1_A_crystal", &config);
+    assert!(lines.iter().filter_map(|l| records::parse_record(l, 2)).all(|r| r.features[0].is_nan() && r.features[1].is_nan()));
+}
+
+#[test]
+fn membrane_is_read_from_annotations_or_fitted() {
+    use bioshell_pdb::calc::Vec3;
+    use featurizer::membrane::{fit_membrane, MembraneFeatures};
+
+    // --- a membrane given in the frame of the deposit: the z = 0 plane, 5 Å thick on either side
+    let dir = out_root("membranes");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(format!("{}/2gb1.txt", dir), "# test membrane\ncenter 0 0 0\nnormal 0 0 2\nhalf_thickness 5\n").unwrap();
+    let membrane = MembraneFeatures { annotations: Some(dir), ..Default::default() };
+    let config = FeaturizerConfig { extractors: vec![Box::new(membrane)], ..Default::default() };
+    let lines = run(&format!("{}/2gb1.cif", INPUTS), "A", "2gb1_A_membrane", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 2)).collect();
+    for r in &parsed {
+        assert!((r.features[1] - r.ca[2]).abs() < 1e-3, "depth {} of CA at z = {}", r.features[1], r.ca[2]);
+        assert_eq!(r.features[0], if r.ca[2].abs() <= 5.0 { 1.0 } else { 0.0 });
+    }
+    // --- a soluble protein gets no membrane from the slab fit
+    let config = FeaturizerConfig { extractors: vec![Box::new(MembraneFeatures::default())], ..Default::default() };
+    let lines = run(&format!("{}/2gb1.cif", INPUTS), "A", "2gb1_A_membrane_fit", &config);
+    assert!(lines.iter().filter_map(|l| records::parse_record(l, 2)).all(|r| r.features[0] == 0.0 && r.features[1].is_nan()));
+    // --- a helix along z whose middle 20 residues are hydrophobic and the flanks charged
+    let ca: Vec<Vec3> = (0..40).map(|i| {
+        let angle = (100.0 * i as f64).to_radians();
+        Vec3::new(2.3 * angle.cos(), 2.3 * angle.sin(), 1.5 * i as f64)
+    }).collect();
+    let sequence: Vec<u8> = (0..40).map(|i| if (10..30).contains(&i) { b'L' } else { b'K' }).collect();
+    let plane = fit_membrane(&ca, &sequence, 15.0).unwrap();
+    assert!(plane.normal.z.abs() > 0.9, "normal {:?}", plane.normal);
+    assert!(plane.depth(&ca[20]).abs() < 3.0);
+}
+
+#[test]
+fn ca_only_traces_give_the_same_inference_features() {
+    use featurizer::inference::inference_extractors;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let text = fs::read_to_string(&fname).unwrap();
+    let trace: Vec<&str> = text.lines().filter(|l| !l.starts_with("ATOM") || l.split_whitespace().nth(3) == Some("CA")).collect();
+    let config = FeaturizerConfig { extractors: inference_extractors(), inference: true, ..Default::default() };
+    let full = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let ca_only = compute_chain_features(&LoadedDeposit::from_text("2gb1_trace", &trace.join("\n"), &config).unwrap(), "A", &config).unwrap();
+    assert_eq!(ca_only.residue_ids, full.residue_ids);
+    assert!(!full.ss.contains('-'));
+    assert!(ca_only.ss.chars().all(|c| c == '-'), "secondary structure of a CA trace is masked");
+    let names: Vec<&str> = ca_only.features.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["residue_encoding", "ca_geometry", "trace_breaks"]);
+    for ((name, expected), (_, values)) in full.features.iter().zip(&ca_only.features) {
+        for (e, v) in expected.iter().flatten().zip(values.iter().flatten()) {
+            assert!(e == v || (e.is_nan() && v.is_nan()), "{} differs for a CA trace: {} vs {}", name, e, v);
+        }
+    }
+    let breaks = &ca_only.features[2].1;
+    assert!(breaks.iter().all(|row| row[0] == 0.0), "a CA trace of a complete chain has no breaks");
+}
+
+#[test]
+fn ca_traces_are_assigned_secondary_structure() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let text = fs::read_to_string(&fname).unwrap();
+    let trace: Vec<&str> = text.lines().filter(|l| !l.starts_with("ATOM") || l.split_whitespace().nth(3) == Some("CA")).collect();
+    let dssp = compute_chain_features(&LoadedDeposit::load(&fname, &FeaturizerConfig::default()).unwrap(), "A", &FeaturizerConfig::default()).unwrap();
+    let config = FeaturizerConfig { ss_method: SsMethod::PSeaFromCa, inference: true, ..Default::default() };
+    let full = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let ca_only = compute_chain_features(&LoadedDeposit::from_text("2gb1_trace", &trace.join("\n"), &config).unwrap(), "A", &config).unwrap();
+    // --- CA atoms are all it takes, so a trace is assigned like its deposit
+    assert_eq!(ca_only.ss, full.ss);
+    assert!(ca_only.ss.contains('H') && ca_only.ss.contains('E'), "{}", ca_only.ss);
+    let agreement = dssp.ss.chars().zip(ca_only.ss.chars()).filter(|(a, b)| a == b).count();
+    assert!(agreement as f64 > 0.7 * dssp.ss.len() as f64, "{} vs DSSP {}", ca_only.ss, dssp.ss);
+    // --- a STRIDE-like helix is a DSSP helix confirmed by backbone torsions
+    let config = FeaturizerConfig { ss_method: SsMethod::StrideLike, ..Default::default() };
+    let stride = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    assert!(stride.ss.contains('H'));
+    assert!(stride.ss.chars().zip(dssp.ss.chars()).all(|(s, d)| s != 'H' || d == 'H'), "{} vs DSSP {}", stride.ss, dssp.ss);
+}
+
+#[test]
+fn broken_backbone_is_repaired_and_flagged() {
+    use featurizer::chains::polymer_structure;
+    use featurizer::repair::{repair_backbone, RepairFlags};
+
+    // --- O of residue 10 is removed and the peptide bond of residues 19 and 20 stretched to 1.6 Å
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let xyz = |t: &[&str]| [10, 11, 12].map(|k| t[k].parse::<f64>().unwrap());
+    let atom_of = |seq: &str, name: &str| text.lines().map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .find(|t| t.first() == Some(&"ATOM") && t[8] == seq && t[3] == name).unwrap();
+    let (removed_o, c19, n20) = (xyz(&atom_of("10", "O")), xyz(&atom_of("19", "C")), xyz(&atom_of("20", "N")));
+    let bond: Vec<f64> = (0..3).map(|k| n20[k] - c19[k]).collect();
+    let length = bond.iter().map(|v| v * v).sum::<f64>().sqrt();
+    let stretched: Vec<String> = (0..3).map(|k| format!("{:.3}", c19[k] + 1.6 * bond[k] / length)).collect();
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        let mut t: Vec<&str> = line.split_whitespace().collect();
+        if t.first() != Some(&"ATOM") { lines.push(line.to_string()); continue; }
+        if t[8] == "10" && t[3] == "O" { continue; }
+        if t[8] == "20" && t[3] == "N" { for k in 0..3 { t[10 + k] = &stretched[k]; } }
+        lines.push(t.join(" "));
+    }
+    let config = FeaturizerConfig { extractors: vec![Box::new(RepairFlags)], repair: true, ..Default::default() };
+    let loaded = LoadedDeposit::from_text("2gb1_broken", &lines.join("\n"), &config).unwrap();
+
+    let (repaired, _) = repair_backbone(&polymer_structure(&loaded.deposit));
+    let resids = repaired.residue_ids();
+    let o = &repaired.atom(&resids[9], " O  ").unwrap().pos;
+    let shift = ((o.x - removed_o[0]).powi(2) + (o.y - removed_o[1]).powi(2) + (o.z - removed_o[2]).powi(2)).sqrt();
+    assert!(shift < 0.5, "the rebuilt O is {} Å away from the deposited one", shift);
+    let (c, n) = (&repaired.atom(&resids[18], " C  ").unwrap().pos, &repaired.atom(&resids[19], " N  ").unwrap().pos);
+    assert!((((c.x - n.x).powi(2) + (c.y - n.y).powi(2) + (c.z - n.z).powi(2)).sqrt() - 1.329).abs() < 1e-3);
+
+    let features = compute_chain_features(&loaded, "A", &config).unwrap();
+    let flags = &features.features[0].1;
+    let rebuilt: Vec<usize> = (0..flags.len()).filter(|i| flags[*i][0] == 1.0).collect();
+    assert_eq!(rebuilt, vec![9]);
+    assert_eq!(flags[19][1], 1.0, "the stretched peptide bond is flagged at the residue whose N has been moved");
+    let unrepaired = compute_chain_features(&loaded, "A", &FeaturizerConfig { extractors: vec![Box::new(RepairFlags)], ..Default::default() }).unwrap();
+    assert!(unrepaired.features[0].1.iter().flatten().all(|v| v.is_nan()));
+}
+
+#[test]
+fn internal_coordinates_rebuild_the_backbone() {
+    use featurizer::chains::polymer_structure;
+    use featurizer::geometry::nerf;
+    use featurizer::internal_coords::InternalCoordinates;
+
+    let config = FeaturizerConfig { extractors: vec![Box::new(InternalCoordinates)], ..Default::default() };
+    let loaded = LoadedDeposit::load(&format!("{}/2gb1.cif", INPUTS), &config).unwrap();
+    let features = compute_chain_features(&loaded, "A", &config).unwrap();
+    let ic = &features.features[0].1;
+    assert!(ic.iter().all(|row| row.len() == 12));
+    // --- the first residue has no predecessor: only N-CA and the values within the residue are defined
+    for k in [0, 1, 2, 4, 5] { assert!(ic[0][k].is_nan(), "column {} of the first residue is {}", k, ic[0][k]); }
+    assert!(ic[0][3..].iter().enumerate().all(|(k, v)| k == 1 || k == 2 || !v.is_nan()));
+    for row in &ic[1..] {
+        for (k, ideal) in [(0, 1.329), (3, 1.458), (6, 1.525), (9, 1.231)] {
+            assert!((row[k] - ideal).abs() < 0.1, "bond {} is {} Å long", k, row[k]);
+        }
+        assert!(row[5].abs() > 150.0, "a cis peptide bond: omega = {}", row[5]);
+    }
+
+    // --- C of residue 10 placed by NeRF from the atoms before it
+    let strctr = polymer_structure(&loaded.deposit);
+    let resids = strctr.residue_ids();
+    let pos = |i: usize, name: &str| strctr.atom(&resids[i], name).unwrap().pos.clone();
+    let c = nerf(&pos(8, " C  "), &pos(9, " N  "), &pos(9, " CA "), ic[9][6], ic[9][7], ic[9][8]);
+    let actual = pos(9, " C  ");
+    let error = ((c.x - actual.x).powi(2) + (c.y - actual.y).powi(2) + (c.z - actual.z).powi(2)).sqrt();
+    assert!(error < 1e-3, "the rebuilt C is {} Å away from the deposited one", error);
+}
+
+#[test]
+fn validation_finds_truncated_and_outdated_outputs() {
+    use featurizer::validate::{validate_dir, validate_output};
+
+    let dir = std::env::temp_dir().join("featurizer_tests").join("validate");
+    fs::create_dir_all(&dir).unwrap();
+    let root = dir.join("2gb1_A").to_str().unwrap().to_string();
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(CaTraceGeometry), Box::new(RamachandranRegions)], ..Default::default() };
+    featurize_chain(&fname, "A", &root, &config, &CancellationToken::new()).unwrap();
+
+    let reports = validate_dir(dir.to_str().unwrap());
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].passed(), "{:?}", reports[0].problems);
+    assert_eq!(reports[0].n_records, ca_records(&fname, "A").len());
+
+    // --- the last record cut in half
+    let dat = format!("{}.dat", root);
+    let text = fs::read_to_string(&dat).unwrap();
+    fs::write(&dat, &text[..text.len() - 20]).unwrap();
+    let report = validate_output(&root);
+    assert!(report.problems.iter().any(|p| p.contains("truncated")), "{:?}", report.problems);
+    assert!(report.problems.iter().any(|p| p.contains("malformed")));
+    fs::write(&dat, &text).unwrap();
+
+    // --- a feature written by a different version, and a value out of range
+    let meta = format!("{}.meta", root);
+    let original = fs::read_to_string(&meta).unwrap();
+    fs::write(&meta, original.replace("feature.ca_geometry = [L, 3] v", "feature.ca_geometry = [L, 3] v99")).unwrap();
+    assert!(validate_output(&root).problems.iter().any(|p| p.starts_with("ca_geometry: written as v")));
+    fs::write(&meta, &original).unwrap();
+    let columns = records::read_columns(&root).unwrap();
+    let rama = columns.iter().position(|c| c == "rama_region.0").unwrap() - 5;
+    let mut lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+    let k = lines.iter().position(|l| l.contains(" : ")).unwrap();
+    let (left, data) = lines[k].split_once(" : ").unwrap();
+    let mut values: Vec<String> = data.split_whitespace().map(|v| v.to_string()).collect();
+    values[rama] = "7.000".to_string();
+    lines[k] = format!("{} : {}", left, values.join(" "));
+    fs::write(&dat, lines.join("\n") + "\n").unwrap();
+    let report = validate_output(&root);
+    assert_eq!(report.problems, vec!["rama_region.0: 1 values out of [0, 4]".to_string()]);
+}
+
+#[test]
+fn torsion_bins_follow_continuous_angles() {
+    use featurizer::torsions::{torsion_bin, TorsionAngles};
+
+    assert_eq!(torsion_bin(-180.0, 36), 0);
+    assert_eq!(torsion_bin(-170.5, 36), 0);
+    assert_eq!(torsion_bin(0.0, 36), 18);
+    assert_eq!(torsion_bin(180.0, 36), 35);
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(TorsionAngles { bins: Some(36) })], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_torsions", &config);
+    let parsed: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 4)).collect();
+    assert!(parsed[0].features[0].is_nan(), "phi is undefined at the N-terminus");
+    for r in parsed.iter().filter(|r| !r.features[0].is_nan() && !r.features[1].is_nan()) {
+        for k in 0..2 {
+            let bin = r.features[k + 2];
+            assert!((0.0..36.0).contains(&bin) && bin.fract() == 0.0);
+            // --- angles are written with three decimals, so they may round across a bin boundary
+            let low = -180.0 + 10.0 * bin;
+            assert!(r.features[k] >= low - 1e-3 && r.features[k] <= low + 10.0 + 1e-3, "{} is not in bin {}", r.features[k], bin);
+        }
+    }
+}
+
+#[test]
+fn sampled_entries_are_reproducible_and_ordered() {
+    use featurizer::splits::sample_entries;
+
+    let entries: Vec<usize> = (0..1000).collect();
+    let sample = sample_entries(entries.clone(), 50, 7);
+    assert_eq!(sample.len(), 50);
+    assert!(sample.windows(2).all(|w| w[0] < w[1]), "sampled entries must keep their order");
+    assert_eq!(sample, sample_entries(entries.clone(), 50, 7));
+    assert_ne!(sample, sample_entries(entries.clone(), 50, 8));
+    assert_eq!(sample_entries(entries.clone(), 2000, 7), entries);
+}
+
+#[test]
+fn clustered_chains_share_a_fold_and_folds_are_balanced() {
+    use featurizer::splits::{assign_folds, load_clusters};
+
+    let fname = out_root("clusters.txt");
+    fs::write(&fname, "# chain cluster\n1ABC_A c1\n1abc_B c1\n2xyz_A c1\n3def_A c2\n3def_B c2\n").unwrap();
+    let clusters = load_clusters(&fname).unwrap();
+    assert_eq!(clusters.len(), 5);
+    assert_eq!(clusters["1abc_a"], "c1");
+    let ids: Vec<String> = ["1abc_A", "1abc_B", "2xyz_A", "3def_A", "3def_B", "4ghi_A", "5jkl_A", "6mno_A", "7pqr_A"]
+        .iter().map(|s| s.to_string()).collect();
+    let folds = assign_folds(&ids, &clusters, 3);
+    assert_eq!(folds, assign_folds(&ids, &clusters, 3), "fold assignment must be deterministic");
+    assert!(folds[0] == folds[1] && folds[1] == folds[2], "{:?}", folds);
+    assert_eq!(folds[3], folds[4]);
+    assert_ne!(folds[0], folds[3]);
+    // --- the cluster of three, the cluster of two and four singletons give 3 chains in every fold
+    let mut sizes = [0; 3];
+    for f in &folds { sizes[*f] += 1; }
+    assert_eq!(sizes, [3, 3, 3]);
+    assert!(load_clusters(&out_root("no_such_clusters.txt")).is_err());
+}
+
+#[test]
+fn every_entry_falls_into_a_single_shard() {
+    use featurizer::splits::Shard;
+
+    let shards: Vec<Shard> = (0..8).map(|k| format!("{}/8", k).parse().unwrap()).collect();
+    let codes: Vec<String> = (0..1000).map(|k| format!("{}x{:02}", k % 10, k / 10)).collect();
+    let mut sizes = [0; 8];
+    for code in &codes {
+        let owners: Vec<usize> = (0..8).filter(|k| shards[*k].contains(code)).collect();
+        assert_eq!(owners.len(), 1, "{} belongs to shards {:?}", code, owners);
+        assert!(shards[owners[0]].contains(&code.to_uppercase()), "PDB IDs are case-insensitive");
+        sizes[owners[0]] += 1;
+    }
+    assert!(sizes.iter().all(|n| *n > 80 && *n < 170), "unbalanced shards: {:?}", sizes);
+    assert!("8/8".parse::<Shard>().is_err());
+    assert!("1-8".parse::<Shard>().is_err());
+}
+
+#[test]
+fn shard_manifests_are_merged() {
+    use featurizer::manifest::{merge_manifests, write_manifest};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_merged");
+    let summary = featurize_chain(&fname, "A", &root, &FeaturizerConfig::default(), &CancellationToken::new()).unwrap();
+    let key = summary.key.clone();
+    let mut other = summary.clone();
+    other.key = "pdb/1c5n_L".to_string();
+    let (first, second) = (out_root("shard_0.tsv"), out_root("shard_1.tsv"));
+    write_manifest(&[summary.clone()], &first).unwrap();
+    // --- the chain of the first shard is listed again, as if that shard was run twice
+    write_manifest(&[other, summary], &second).unwrap();
+    let merged = merge_manifests(&[first, second]).unwrap();
+    let keys: Vec<&str> = merged.iter().map(|s| s.key.as_str()).collect();
+    assert_eq!(keys, vec![key.as_str(), "pdb/1c5n_L"]);
+}
+
+#[test]
+fn log_lines_name_their_worker() {
+    use featurizer::logging::{init_logging, LogFormat};
+
+    let fname = out_root("run.log.json");
+    init_logging(Some(&fname), LogFormat::Json).unwrap();
+    std::thread::Builder::new().name("loader-3".to_string()).spawn(|| log::warn!("chain \"A\" of 2gb1\tskipped")).unwrap()
+        .join().unwrap();
+    std::thread::spawn(|| log::info!("unnamed worker")).join().unwrap();
+    let text = fs::read_to_string(&fname).unwrap();
+    let line = text.lines().find(|l| l.contains("\"worker\":\"loader-3\"")).unwrap();
+    assert!(line.starts_with("{\"time\":\"") && line.ends_with("\"message\":\"chain \\\"A\\\" of 2gb1\\tskipped\"}"), "{}", line);
+    assert!(line.contains("\"level\":\"WARN\""));
+    let line = text.lines().find(|l| l.contains("unnamed worker")).unwrap();
+    assert!(line.contains("\"worker\":\"worker-"), "{}", line);
+}
+
+#[test]
+fn polymer_types_are_told_apart() {
+    use featurizer::polymers::{polymer_type, PolymerPolicy, PolymerType};
+
+    assert_eq!(polymer_type(["MET", "LYS", "ALA"]), PolymerType::Protein);
+    assert_eq!(polymer_type(["DA", "DC", "DG", "DT"]), PolymerType::NucleicAcid);
+    assert_eq!(polymer_type(["A", "U", "G", "DT"]), PolymerType::NucleicAcid);
+    // --- selenomethionine is an amino acid; residues of unknown type are not counted
+    assert_eq!(polymer_type(["MSE", "GLY", "DA"]), PolymerType::Hybrid);
+    assert_eq!(polymer_type(["UNK", "UNK"]), PolymerType::Protein);
+    assert!(!PolymerPolicy::Protein.accepts(PolymerType::Hybrid) && PolymerPolicy::All.accepts(PolymerType::NucleicAcid));
+    // --- protein chains are featurized under either policy
+    let token = CancellationToken::new();
+    for polymer in [PolymerPolicy::Protein, PolymerPolicy::All] {
+        let config = FeaturizerConfig { polymer, ..Default::default() };
+        featurize_chain(&format!("{}/2gb1.cif", INPUTS), "A", &out_root("2gb1_A_polymer"), &config, &token).unwrap();
+    }
+}
+
+#[test]
+fn sequence_mismatches_are_masked_or_rejected() {
+    use featurizer::alignment::MismatchPolicy;
+
+    // --- Lys 4 of 2gb1 modelled as an arginine, not declared in the entity sequence
+    let mutated: String = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap().lines()
+        .map(|l| if l.starts_with("ATOM") { l.replace(" LYS A 1 4  ", " ARG A 1 4  ").replace("? 4  LYS A", "? 4  ARG A") } else { l.to_string() })
+        .map(|l| l + "\n").collect();
+    let fname = out_root("2gb1_K4R.cif");
+    fs::write(&fname, mutated).unwrap();
+    let warned = run(&fname, "A", "2gb1_K4R_warn", &FeaturizerConfig::default());
+    assert!(!warned[3].trim_start().starts_with('-'), "{}", warned[3]);
+    let config = FeaturizerConfig { on_mismatch: MismatchPolicy::Mask, ..Default::default() };
+    let masked = run(&fname, "A", "2gb1_K4R_mask", &config);
+    assert_eq!(masked.len(), warned.len());
+    assert!(masked[3].trim_start().starts_with('-'), "{}", masked[3]);
+    assert_eq!(masked[4], warned[4]);
+    let config = FeaturizerConfig { on_mismatch: MismatchPolicy::Reject, ..Default::default() };
+    let result = featurize_chain(&fname, "A", &out_root("2gb1_K4R_reject"), &config, &CancellationToken::new());
+    assert!(matches!(&result, Err(FeaturizerError::Filtered(m)) if m.contains("entity 4 K")), "{:?}", result.err());
+}
+
+#[test]
+fn neighbor_grid_finds_the_pairs_of_a_full_scan() {
+    use bioshell_pdb::calc::Vec3;
+    use featurizer::geometry::distance;
+    use featurizer::neighbors::NeighborGrid;
+
+    // --- points scattered in a 40 Å box, every tenth one missing
+    let mut state: u64 = 17;
+    let mut next = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as f64 / (1u64 << 31) as f64 * 40.0 - 20.0
+    };
+    let points: Vec<Option<Vec3>> = (0..500).map(|k| {
+        let p = Vec3::new(next(), next(), next());
+        (k % 10 != 0).then_some(p)
+    }).collect();
+    for (cell, cutoff) in [(8.0, 8.0), (4.0, 9.5), (12.0, 3.0)] {
+        let grid = NeighborGrid::new(&points, cell);
+        let mut expected = vec![];
+        for i in 0..points.len() {
+            for j in i + 1..points.len() {
+                if let (Some(a), Some(b)) = (&points[i], &points[j]) {
+                    if distance(a, b) <= cutoff { expected.push((i, j)); }
+                }
+            }
+        }
+        let found: Vec<(usize, usize)> = grid.pairs(cutoff).iter().map(|(i, j, _)| (*i, *j)).collect();
+        assert_eq!(found, expected);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let near: Vec<usize> = (0..points.len()).filter(|k| points[*k].as_ref().is_some_and(|p| distance(p, &origin) <= cutoff)).collect();
+        assert_eq!(grid.within(&origin, cutoff), near);
+    }
+}
+
+#[test]
+fn hbond_pairs_list_every_residue_pair_once() {
+    use featurizer::hbonds::{canonical_hbond_pairs, HBondPolicy};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { hbonds: HBondPolicy { pairs: true, ..Default::default() }, ..Default::default() };
+    let root = out_root("2gb1_A_hbpairs");
+    featurize_chain(&fname, "A", &root, &config, &CancellationToken::new()).unwrap();
+    let hbonds: Vec<HBondPair> = fs::read_to_string(format!("{}.hbonds", root)).unwrap().lines().filter_map(HBondPair::parse).collect();
+    let text = fs::read_to_string(format!("{}.hbpairs", root)).unwrap();
+    let lines: Vec<Vec<&str>> = text.lines().skip(1).map(|l| l.split_whitespace().collect()).collect();
+    assert_eq!(lines.len(), canonical_hbond_pairs(&hbonds).len());
+    let mut seen = HashSet::new();
+    let mut n_both = 0;
+    for t in &lines {
+        let (i, j): (usize, usize) = (t[0].parse().unwrap(), t[1].parse().unwrap());
+        assert!(i < j && seen.insert((i, j)), "{:?}", t);
+        assert_eq!(t[2] == "1", hbonds.iter().any(|hb| hb.donor == t[0] && hb.acceptor == t[1]));
+        assert_eq!(t[3] == "1", hbonds.iter().any(|hb| hb.donor == t[1] && hb.acceptor == t[0]));
+        if t[2] == "1" && t[3] == "1" { n_both += 1; }
+    }
+    // --- the antiparallel hairpins of protein G bond residue pairs both ways
+    assert!(n_both > 0);
+    assert_eq!(lines.len() + n_both, hbonds.iter().map(|hb| (hb.donor.clone(), hb.acceptor.clone())).collect::<HashSet<_>>().len());
+}
+
+#[test]
+fn rebuilt_structure_matches_the_dat_records() {
+    use featurizer::rebuild::{rebuild_chain, write_cif, write_pdb};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let lines = run(&fname, "A", "2gb1_A_rebuilt", &FeaturizerConfig::default());
+    let dat: Vec<records::DatRecord> = lines.iter().filter_map(|l| records::parse_record(l, 0)).collect();
+    let root = out_root("2gb1_A_rebuilt");
+    let chain = rebuild_chain(&root).unwrap();
+    assert_eq!(chain.chain, "A");
+    assert_eq!(chain.residues.len(), dat.len());
+    let pdb_fname = format!("{}.pdb", root);
+    write_pdb(&chain, &pdb_fname).unwrap();
+    let pdb = fs::read_to_string(&pdb_fname).unwrap();
+    let ca: Vec<&str> = pdb.lines().filter(|l| l.starts_with("ATOM") && &l[12..16] == " CA ").collect();
+    assert_eq!(ca.len(), dat.len());
+    for (line, record) in ca.iter().zip(&dat) {
+        let pos: Vec<f64> = [&line[30..38], &line[38..46], &line[46..54]].iter().map(|v| v.trim().parse().unwrap()).collect();
+        assert_eq!(pos, record.ca.to_vec(), "{}", line);
+        assert_eq!(line[22..27].trim(), record.author_number, "{}", line);
+    }
+    // --- protein G has a helix packed against a four-stranded sheet
+    assert!(pdb.lines().any(|l| l.starts_with("HELIX")));
+    assert!(pdb.lines().filter(|l| l.starts_with("SHEET")).count() >= 4);
+    let cif_fname = format!("{}.cif", root);
+    write_cif(&chain, "2gb1_A", &cif_fname).unwrap();
+    let cif = fs::read_to_string(&cif_fname).unwrap();
+    assert!(cif.starts_with("data_2gb1_A"));
+    assert_eq!(cif.lines().filter(|l| l.starts_with("ATOM ")).count(), dat.len());
+    assert!(cif.lines().any(|l| l.starts_with("HELX_P")));
+}
+
+#[test]
+fn chain_summaries_score_gaps_loops_and_difficulty() {
+    use featurizer::difficulty::difficulty_score;
+    use featurizer::manifest::{read_manifest, write_manifest};
+
+    let mut summaries = vec![];
+    for (deposit, chain) in [("2gb1", "A"), ("1c5n", "L")] {
+        let fname = format!("{}/{}.cif", INPUTS, deposit);
+        let root = out_root(&format!("{}_{}_summary", deposit, chain));
+        let summary = featurize_chain(&fname, chain, &root, &FeaturizerConfig::default(), &CancellationToken::new()).unwrap();
+        assert_eq!(summary.n_residues, entity_length(&fname, "1"));
+        let n_gaps = summary.n_residues - summary.n_observed;
+        assert!((summary.gap_fraction - n_gaps as f64 / summary.n_residues as f64).abs() < 1e-9);
+        let dat = fs::read_to_string(format!("{}.dat", root)).unwrap();
+        let n_loop = dat.lines().filter_map(parse_record).filter(|r| r.1 == "C").count();
+        assert!((summary.loop_fraction - n_loop as f64 / summary.n_observed as f64).abs() < 1e-9);
+        let expected = difficulty_score(summary.loop_fraction, summary.contact_order, summary.gap_fraction, summary.resolution);
+        assert!((summary.difficulty - expected).abs() < 1e-9);
+        assert!(summary.difficulty > 0.0 && summary.difficulty < 1.0, "{}", summary.difficulty);
+        summaries.push(summary);
+    }
+    // --- more missing residues, more loops and worse resolution make a chain harder; each term is capped at 1
+    let base = difficulty_score(0.2, 0.1, 0.0, Some(2.0));
+    assert!(difficulty_score(0.2, 0.1, 0.3, Some(2.0)) > base);
+    assert!(difficulty_score(0.5, 0.1, 0.0, Some(2.0)) > base);
+    assert!(difficulty_score(0.2, 0.1, 0.0, Some(3.5)) > base);
+    assert_eq!(difficulty_score(1.0, 0.6, 1.0, Some(10.0)), 1.0);
+
+    let manifest = out_root("summary_manifest.tsv");
+    write_manifest(&summaries, &manifest).unwrap();
+    let read = read_manifest(&manifest).unwrap();
+    assert_eq!(read.len(), summaries.len());
+    for (written, read) in summaries.iter().zip(&read) {
+        assert_eq!((&written.key, written.n_residues, written.n_observed), (&read.key, read.n_residues, read.n_observed));
+        assert!((written.difficulty - read.difficulty).abs() < 5e-4);
+        assert!((written.gap_fraction - read.gap_fraction).abs() < 5e-4);
+    }
+}
+
+#[test]
+fn hbond_geometry_is_measured_from_backbone_atoms() {
+    use featurizer::geometry::{distance, planar_angle};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    run(&fname, "A", "2gb1_A_hbgeom", &FeaturizerConfig::default());
+    let atoms = residue_atoms(&fname, "A");
+    let hbonds = records::read_hbonds(&out_root("2gb1_A_hbgeom")).unwrap();
+    assert!(!hbonds.is_empty());
+    for hb in &hbonds {
+        let (donor, acceptor): (usize, usize) = (hb.donor.parse().unwrap(), hb.acceptor.parse().unwrap());
+        let (n, o) = (&atoms[donor]["N"], &atoms[acceptor]["O"]);
+        assert!((hb.d_da - distance(n, o)).abs() < 2e-3, "{}", hb);
+        // --- 2gb1 gives amide hydrogens explicitly, except for the N-terminal NH3+ group
+        match atoms[donor].get("H") {
+            Some(h) => {
+                assert!((hb.d_ha - distance(h, o)).abs() < 2e-3, "{}", hb);
+                assert!((hb.angle_dha - planar_angle(n, h, o)).abs() < 0.1, "{}", hb);
+                assert!(hb.d_ha < hb.d_da && hb.angle_dha > 90.0, "{}", hb);
+            }
+            None => assert!(hb.d_ha.is_nan() && hb.angle_dha.is_nan(), "{}", hb),
+        }
+    }
+}
+
+#[test]
+fn contacts_match_a_full_scan_whether_written_at_once_or_in_chunks() {
+    use featurizer::contacts::{find_contacts, virtual_cb_flags, write_contacts, write_contacts_chunked, ContactSpec};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let strctr = load_chain(&fname, "A").unwrap();
+    let resids = strctr.residue_ids();
+    let entity_index: Vec<Option<usize>> = (0..resids.len()).map(Some).collect();
+    let spec: ContactSpec = "CA:8.0".parse().unwrap();
+    let contacts = find_contacts(&strctr, &resids, &spec);
+    let ca = ca_records(&fname, "A");
+    let dist = |a: &[f64; 3], b: &[f64; 3]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt();
+    let mut expected = vec![];
+    for i in 0..ca.len() {
+        for j in i + 1..ca.len() {
+            if dist(&ca[i].pos, &ca[j].pos) < 8.0 { expected.push((i, j)); }
+        }
+    }
+    let mut found: Vec<(usize, usize)> = contacts.iter().map(|(i, j, _)| (*i, *j)).collect();
+    found.sort();
+    assert_eq!(found, expected);
+    for (i, j, d) in &contacts { assert!((d - dist(&ca[*i].pos, &ca[*j].pos)).abs() < 1e-6); }
+    // --- packing contacts between the helix and the sheet are long-range
+    assert!(contacts.iter().any(|(i, j, _)| j - i > 20));
+
+    for (spec, as_matrix) in [("CA:8.0", false), ("CA:8.0", true), ("CB:10.0", false)] {
+        let spec: ContactSpec = spec.parse().unwrap();
+        let flags = spec.is_cb().then(|| virtual_cb_flags(&strctr, &resids));
+        let whole = out_root("2gb1_A_contacts_whole");
+        write_contacts(&find_contacts(&strctr, &resids, &spec), resids.len(), as_matrix, flags.as_deref(), &entity_index, &whole).unwrap();
+        for chunk in [1, 7, 1000] {
+            let chunked = out_root("2gb1_A_contacts_chunked");
+            let n = write_contacts_chunked(&strctr, &resids, &spec, as_matrix, flags.as_deref(), &entity_index, chunk, &chunked).unwrap();
+            assert_eq!(fs::read_to_string(&chunked).unwrap(), fs::read_to_string(&whole).unwrap(), "chunks of {}", chunk);
+            assert_eq!(n, find_contacts(&strctr, &resids, &spec).len());
+        }
+    }
+}
+
+#[test]
+fn residual_targets_are_deviations_from_an_ideal_backbone() {
+    use featurizer::chains::polymer_structure;
+    use featurizer::geometry::{add, distance, LocalFrame};
+    use featurizer::ideal_backbone::{build_ideal_backbone, ideal_residue, ResidualTargets};
+
+    // --- an ideal alpha helix rises by 6.4 Å per turn and bonds O(i) to N(i+4); strands are extended
+    let helix = build_ideal_backbone(-57.0, -47.0, 12);
+    for i in 0..8 {
+        assert!((distance(&helix[i][1], &helix[i + 4][1]) - 6.4).abs() < 0.1);
+        assert!((distance(&helix[i][3], &helix[i + 4][0]) - 3.1).abs() < 0.1);
+    }
+    let strand = build_ideal_backbone(-120.0, 130.0, 6);
+    for i in 0..4 { assert!(distance(&strand[i][1], &strand[i + 2][1]) > 6.5); }
+
+    let config = FeaturizerConfig { extractors: vec![Box::new(ResidualTargets)], ..Default::default() };
+    let loaded = LoadedDeposit::load(&format!("{}/2gb1.cif", INPUTS), &config).unwrap();
+    let features = compute_chain_features(&loaded, "A", &config).unwrap();
+    let targets = &features.features[0].1;
+    let last = targets.len() - 1;
+    assert!(targets[0].iter().chain(&targets[last]).all(|v| v.is_nan()), "terminal residues have no CA frame");
+    // --- ideal atoms moved by the deviations are the deposited ones
+    let strctr = polymer_structure(&loaded.deposit);
+    let resids = strctr.residue_ids();
+    let pos = |i: usize, name: &str| strctr.atom(&resids[i], name).unwrap().pos.clone();
+    let ss = features.ss.as_bytes();
+    let (mut helix_error, mut n_helix) = (0.0, 0);
+    for i in 1..last {
+        let frame = LocalFrame::from_ca(&pos(i - 1, " CA "), &pos(i, " CA "), &pos(i + 1, " CA "));
+        let ideal = ideal_residue(ss[i]);
+        for (k, (name, ideal_pos)) in [(" N  ", &ideal.n), (" C  ", &ideal.c), (" O  ", &ideal.o)].iter().enumerate() {
+            let d = Vec3::new(targets[i][3 * k], targets[i][3 * k + 1], targets[i][3 * k + 2]);
+            assert!(distance(&frame.to_global(&add(ideal_pos, &d)), &pos(i, name)) < 1e-6, "{} of residue {}", name, i);
+            if ss[i] == b'H' && ss[i - 1] == b'H' && ss[i + 1] == b'H' {
+                helix_error += (d.x * d.x + d.y * d.y + d.z * d.z).sqrt();
+                n_helix += 1;
+            }
+        }
+    }
+    // --- backbone atoms inside the helix of protein G lie close to those of an ideal helix
+    assert!(n_helix > 0 && helix_error / n_helix as f64 < 0.7, "mean deviation {}", helix_error / n_helix as f64);
+}
+
+#[test]
+fn residue_frames_compose_from_relative_transforms() {
+    use featurizer::frames::{residue_transforms, FrameReference, ResidueFrames};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let strctr = load_chain(&fname, "A").unwrap();
+    let resids = strctr.residue_ids();
+    let global = residue_transforms(&strctr, &resids, FrameReference::Global);
+    let relative = residue_transforms(&strctr, &resids, FrameReference::Relative);
+    let atoms = residue_atoms(&fname, "A");
+    for (i, t) in global.iter().enumerate() {
+        let (ca, c) = (&atoms[i]["CA"], &atoms[i]["C"]);
+        assert_eq!(t.translation, [ca.x, ca.y, ca.z]);
+        // --- the rotation is orthonormal, right-handed, with its X axis along CA->C
+        let r = &t.rotation;
+        for a in 0..3 {
+            for b in 0..3 {
+                let d: f64 = (0..3).map(|k| r[k][a] * r[k][b]).sum();
+                assert!((d - if a == b { 1.0 } else { 0.0 }).abs() < 1e-9);
+            }
+        }
+        let det = r[0][0] * (r[1][1] * r[2][2] - r[1][2] * r[2][1]) - r[0][1] * (r[1][0] * r[2][2] - r[1][2] * r[2][0])
+            + r[0][2] * (r[1][0] * r[2][1] - r[1][1] * r[2][0]);
+        assert!((det - 1.0).abs() < 1e-9);
+        let ca_c = [c.x - ca.x, c.y - ca.y, c.z - ca.z];
+        let length = ca_c.iter().map(|v| v * v).sum::<f64>().sqrt();
+        for k in 0..3 { assert!((r[k][0] - ca_c[k] / length).abs() < 1e-9); }
+        // --- the quaternion gives the same rotation
+        let [w, x, y, z] = t.quaternion();
+        assert!(((w * w + x * x + y * y + z * z) - 1.0).abs() < 1e-9 && w >= 0.0);
+        let from_q = [[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+                      [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+                      [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)]];
+        for a in 0..3 { for b in 0..3 { assert!((from_q[a][b] - r[a][b]).abs() < 1e-9); } }
+    }
+    // --- a frame is the frame of the preceding residue composed with its relative transform
+    assert!(relative[0].translation.iter().all(|v| v.is_nan()));
+    for i in 1..resids.len() {
+        let (prev, rel, expected) = (&global[i - 1], &relative[i], &global[i]);
+        for a in 0..3 {
+            let t: f64 = prev.translation[a] + (0..3).map(|k| prev.rotation[a][k] * rel.translation[k]).sum::<f64>();
+            assert!((t - expected.translation[a]).abs() < 1e-9);
+            for b in 0..3 {
+                let r: f64 = (0..3).map(|k| prev.rotation[a][k] * rel.rotation[k][b]).sum();
+                assert!((r - expected.rotation[a][b]).abs() < 1e-9);
+            }
+        }
+    }
+    // --- rows of the extractor: a quaternion or a matrix, then the translation
+    let config = FeaturizerConfig { extractors: vec![Box::new(ResidueFrames::default()), Box::new(ResidueFrames { reference: FrameReference::Global, as_matrix: true })],
+        ..Default::default() };
+    let features = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let (quaternions, matrices) = (&features.features[0].1, &features.features[1].1);
+    for (i, t) in global.iter().enumerate() {
+        assert_eq!(quaternions[i][..4], t.quaternion());
+        assert_eq!(matrices[i][..9], t.rotation.concat()[..]);
+        assert_eq!(quaternions[i][4..], t.translation);
+    }
+}
+
+#[test]
+fn ca_trace_geometry_follows_the_ca_atoms() {
+    use featurizer::ca_trace::ca_trace_geometry;
+    use featurizer::geometry::{dihedral_angle, planar_angle};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let strctr = load_chain(&fname, "A").unwrap();
+    let resids = strctr.residue_ids();
+    let ca: Vec<Vec3> = ca_records(&fname, "A").iter().map(|r| Vec3::new(r.pos[0], r.pos[1], r.pos[2])).collect();
+    let n = ca.len();
+    let geometry = ca_trace_geometry(&strctr, &resids);
+    assert_eq!(geometry.len(), n);
+    for (i, g) in geometry.iter().enumerate() {
+        if i + 1 < n {
+            let d = ((ca[i].x - ca[i + 1].x).powi(2) + (ca[i].y - ca[i + 1].y).powi(2) + (ca[i].z - ca[i + 1].z).powi(2)).sqrt();
+            assert!((g.r - d).abs() < 1e-6 && (3.7..3.9).contains(&g.r), "CA-CA of residue {}: {}", i, g.r);
+        } else { assert!(g.r.is_nan()); }
+        if i > 0 && i + 1 < n {
+            assert!((g.theta - planar_angle(&ca[i - 1], &ca[i], &ca[i + 1])).abs() < 1e-6);
+        } else { assert!(g.theta.is_nan()); }
+        if i > 0 && i + 2 < n {
+            assert!((g.tau - dihedral_angle(&ca[i - 1], &ca[i], &ca[i + 1], &ca[i + 2])).abs() < 1e-6);
+        } else { assert!(g.tau.is_nan()); }
+    }
+    // --- the helix of protein G (residues 22-36) gives the pseudo-torsion of a right-handed helix
+    let helix: Vec<f64> = (24..33).map(|i| geometry[i].tau).collect();
+    assert!(helix.iter().all(|tau| (30.0..70.0).contains(tau)), "{:?}", helix);
+
+    // --- a residue left out breaks the trace: angles spanning the break are masked
+    let skipped: Vec<_> = resids.iter().enumerate().filter(|(i, _)| *i != 20).map(|(_, r)| r.clone()).collect();
+    let broken = ca_trace_geometry(&strctr, &skipped);
+    assert!(broken[19].r > 4.2);
+    for i in [19, 20] { assert!(broken[i].theta.is_nan()); }
+    for i in [18, 19, 20] { assert!(broken[i].tau.is_nan()); }
+    assert!(!broken[17].tau.is_nan() && !broken[21].tau.is_nan());
+}
+
+#[test]
+fn interactions_match_a_scan_of_all_atom_pairs() {
+    use featurizer::chains::residue_heavy_atoms;
+    use featurizer::geometry::distance;
+    use featurizer::interactions::{find_interactions, InteractionType, DISULFIDE_CUTOFF, SALT_BRIDGE_CUTOFF};
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let strctr = load_chain(&fname, "H").unwrap();
+    let resids = strctr.residue_ids();
+    let found = find_interactions(&strctr, &resids);
+    let of_kind = |kind: InteractionType| -> BTreeMap<(usize, usize), f64> {
+        found.iter().filter(|it| it.kind == kind).map(|it| ((it.i, it.j), it.distance)).collect()
+    };
+    // --- the three disulfides of the thrombin heavy chain
+    let bonded: Vec<(i32, i32)> = of_kind(InteractionType::Disulfide).keys().map(|&(i, j)| (resids[i].res_seq, resids[j].res_seq)).collect();
+    assert_eq!(bonded, vec![(42, 58), (168, 182), (191, 220)]);
+
+    // --- every pair of residues is checked against every pair of their atoms
+    let atoms = residue_heavy_atoms(&strctr, &resids);
+    let charged = |i: usize, names: &[(&str, &str)]| -> Vec<Vec3> {
+        atoms[i].iter().filter(|a| names.iter().any(|(r, n)| a.res_name == *r && a.name == *n)).map(|a| a.pos.clone()).collect()
+    };
+    let cations = [("LYS", " NZ "), ("ARG", " NE "), ("ARG", " NH1"), ("ARG", " NH2")];
+    let anions = [("ASP", " OD1"), ("ASP", " OD2"), ("GLU", " OE1"), ("GLU", " OE2")];
+    let closest = |a: &[Vec3], b: &[Vec3]| a.iter().flat_map(|p| b.iter().map(move |q| distance(p, q))).fold(f64::INFINITY, f64::min);
+    let (mut disulfides, mut salt_bridges) = (BTreeMap::new(), BTreeMap::new());
+    for i in 0..resids.len() {
+        for j in i + 1..resids.len() {
+            let d = closest(&charged(i, &[("CYS", " SG ")]), &charged(j, &[("CYS", " SG ")]));
+            if d <= DISULFIDE_CUTOFF { disulfides.insert((i, j), d); }
+            let d = closest(&charged(i, &cations), &charged(j, &anions)).min(closest(&charged(i, &anions), &charged(j, &cations)));
+            if d <= SALT_BRIDGE_CUTOFF { salt_bridges.insert((i, j), d); }
+        }
+    }
+    assert!(!salt_bridges.is_empty());
+    for (expected, kind) in [(disulfides, InteractionType::Disulfide), (salt_bridges, InteractionType::SaltBridge)] {
+        let found = of_kind(kind);
+        assert_eq!(found.keys().collect::<Vec<_>>(), expected.keys().collect::<Vec<_>>(), "{} interactions", kind);
+        for (pair, d) in expected { assert!((found[&pair] - d).abs() < 1e-9); }
+    }
+}
+
+#[test]
+fn registered_extractors_give_rows_of_their_declared_shape() {
+    use featurizer::features::{extractors_by_name, registered_extractors};
+
+    let names: Vec<&str> = registered_extractors().iter().map(|e| e.info().name).collect();
+    assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len(), "feature names must be unique");
+    let picked = extractors_by_name(&["chi_angles", "ca_geometry"]).unwrap();
+    assert_eq!(picked.iter().map(|e| e.info().name).collect::<Vec<_>>(), vec!["chi_angles", "ca_geometry"]);
+    assert_eq!(extractors_by_name(&["ca_geometry", "no_such_feature"]).err().unwrap(), "unknown feature: no_such_feature");
+
+    // --- extractors that read their own input files, or the lattice and partner chains, need more than a deposit
+    let file_backed = ["sequence_profile", "conservation", "map_quality", "prediction_agreement", "membrane"];
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let extractors: Vec<_> = registered_extractors().into_iter()
+        .filter(|e| !e.needs_lattice() && !e.needs_partners() && !file_backed.contains(&e.info().name)).collect();
+    let shapes: Vec<(&str, String)> = extractors.iter().map(|e| (e.info().name, e.info().shape)).collect();
+    let config = FeaturizerConfig { extractors, ..Default::default() };
+    let chain = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    assert_eq!(chain.features.len(), shapes.len());
+    for ((name, shape), (column, rows)) in shapes.iter().zip(&chain.features) {
+        assert_eq!(name, column);
+        let width = shape.trim_matches(|c| c == '[' || c == ']').split(", ").nth(1).map_or(1, |w| w.parse::<usize>().unwrap());
+        assert_eq!(rows.len(), chain.residue_ids.len(), "rows of {}", name);
+        assert!(rows.iter().all(|r| r.len() == width), "{} should give rows of {} values", name, width);
+    }
+}
+
+#[test]
+fn chain_pairs_are_found_between_ca_atoms_of_two_chains() {
+    use featurizer::chain_pair::{chain_pair_features, interface_flags};
+    use featurizer::geometry::distance;
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let (light, heavy) = (load_chain(&fname, "L").unwrap(), load_chain(&fname, "H").unwrap());
+    let (ca_l, ca_h) = (ca_records(&fname, "L"), ca_records(&fname, "H"));
+    let cutoff = 8.0;
+    let pairs = chain_pair_features(&light, &heavy, cutoff);
+    let found: BTreeMap<(usize, usize), f64> = pairs.iter().map(|p| ((p.i, p.j), p.d_ca)).collect();
+    let mut expected = BTreeMap::new();
+    for (i, a) in ca_l.iter().enumerate() {
+        for (j, b) in ca_h.iter().enumerate() {
+            let d = (0..3).map(|k| (a.pos[k] - b.pos[k]).powi(2)).sum::<f64>().sqrt();
+            if d <= cutoff { expected.insert((i, j), d); }
+        }
+    }
+    assert!(!expected.is_empty());
+    assert_eq!(found.keys().collect::<Vec<_>>(), expected.keys().collect::<Vec<_>>());
+    for (pair, d) in &expected { assert!((found[pair] - d).abs() < 1e-6); }
+
+    // --- glycines get a virtual CB; angles are those of the swapped pair, read the other way round
+    let (resids_l, resids_h) = (light.residue_ids(), heavy.residue_ids());
+    let glycine = |s: &Structure, r: &bioshell_pdb::ResidueId| s.atom(r, " CA ").unwrap().res_name == "GLY";
+    let swapped: BTreeMap<(usize, usize), _> = chain_pair_features(&heavy, &light, cutoff).into_iter().map(|p| ((p.j, p.i), p)).collect();
+    for p in &pairs {
+        assert_eq!(p.virtual_cb, (glycine(&light, &resids_l[p.i]), glycine(&heavy, &resids_h[p.j])));
+        let q = &swapped[&(p.i, p.j)];
+        assert!((p.d_cb - q.d_cb).abs() < 1e-9 || (p.d_cb.is_nan() && q.d_cb.is_nan()));
+        assert!((p.omega - q.omega).abs() < 1e-6 || (p.omega.is_nan() && q.omega.is_nan()));
+    }
+
+    // --- residues of an interface are within 5 Å of the other chain, and only they are
+    let flags = interface_flags(&light, &heavy);
+    assert_eq!(flags.len(), resids_l.len());
+    for (flag, r) in flags.iter().zip(&resids_l) {
+        let close = light.atoms().iter().filter(|a| a.res_seq == r.res_seq && a.i_code == r.i_code)
+            .any(|a| heavy.atoms().iter().any(|b| distance(&a.pos, &b.pos) <= 5.0));
+        assert_eq!(*flag, close, "residue {}", r);
+    }
+    assert!(flags.iter().any(|f| *f));
+}
+
+#[test]
+fn chi_angles_are_defined_by_the_residue_type() {
+    use featurizer::chi_angles::ChiAngles;
+    use featurizer::geometry::dihedral_angle;
+
+    let n_chis = |name: &str| match name {
+        "GLY" | "ALA" => 0,
+        "SER" | "CYS" | "THR" | "VAL" => 1,
+        "ASP" | "ASN" | "ILE" | "LEU" | "HIS" | "PHE" | "TRP" | "TYR" | "PRO" => 2,
+        "GLN" | "GLU" | "MET" => 3,
+        _ => 4,
+    };
+    let chi1_end = |name: &str| match name {
+        "SER" => "OG", "THR" => "OG1", "CYS" => "SG", "VAL" | "ILE" => "CG1", _ => "CG",
+    };
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(ChiAngles)], ..Default::default() };
+    let chain = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let strctr = load_chain(&fname, "A").unwrap();
+    let atoms = residue_atoms(&fname, "A");
+    for (i, (resid, row)) in strctr.residue_ids().iter().zip(&chain.features[0].1).enumerate() {
+        let name = strctr.atom(resid, " CA ").unwrap().res_name.clone();
+        let n = n_chis(&name);
+        assert!(row[..n].iter().all(|chi| (-180.0..=180.0).contains(chi)), "{} {}: {:?}", name, i, row);
+        assert!(row[n..4].iter().all(|chi| chi.is_nan()), "{} {}: {:?}", name, i, row);
+        assert!(row[4..].iter().all(|m| *m == 0.0), "all the side chains of protein G are complete");
+        if n > 0 {
+            let a = &atoms[i];
+            let chi1 = dihedral_angle(&a["N"], &a["CA"], &a["CB"], &a[chi1_end(&name)]);
+            assert!((row[0] - chi1).abs() < 1e-6);
+        }
+    }
+
+    // --- an atom taken out of Lys 4 leaves its chi4 undefined and flagged
+    let text = fs::read_to_string(&fname).unwrap();
+    let trimmed: Vec<&str> = text.lines().filter(|l| {
+        let t: Vec<&str> = l.split_whitespace().collect();
+        !(l.starts_with("ATOM") && t[3] == "NZ" && t[5] == "LYS" && t[16] == "4")
+    }).collect();
+    let chain = compute_chain_features(&LoadedDeposit::from_text("2gb1_no_nz", &trimmed.join("\n"), &config).unwrap(), "A", &config).unwrap();
+    let lys = &chain.features[0].1[3];
+    assert!(lys[..3].iter().all(|chi| !chi.is_nan()) && lys[3].is_nan());
+    assert_eq!(lys[4..], [0.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn alanine_scan_measures_what_a_side_chain_buries() {
+    use featurizer::alanine_scan::{AlanineScan, BURIAL_RADIUS};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(AlanineScan)], ..Default::default() };
+    let chain = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let scan = &chain.features[0].1;
+    let sequence: Vec<char> = entity_sequence(&fname, "1").chars().collect();
+    for (i, row) in scan.iter().enumerate() {
+        if sequence[i] == 'G' || sequence[i] == 'A' { assert_eq!(row, &vec![0.0, 0.0], "residue {}{}", sequence[i], i + 1); }
+    }
+    // --- Trp 43 packs against the sheet and the helix of protein G
+    let trp = 42;
+    assert_eq!(sequence[trp], 'W');
+    assert!(scan[trp][0] > 0.0 && scan[trp][1] > 0.0, "Trp 43: {:?}", scan[trp]);
+    let atoms = residue_atoms(&fname, "A");
+    let heavy = |name: &str| !name.starts_with('H');
+    let removed: Vec<&Vec3> = atoms[trp].iter().filter(|(n, _)| heavy(n) && !["N", "CA", "C", "O", "CB"].contains(&n.as_str())).map(|(_, p)| p).collect();
+    let burial_lost: usize = atoms.iter().enumerate().filter(|(j, _)| *j != trp).map(|(_, r)| {
+        let center = r.get("CB").or(r.get("CA")).unwrap();
+        removed.iter().filter(|p| ((p.x - center.x).powi(2) + (p.y - center.y).powi(2) + (p.z - center.z).powi(2)).sqrt() <= BURIAL_RADIUS).count()
+    }).sum();
+    assert_eq!(scan[trp][0], burial_lost as f64);
+}
+
+#[test]
+fn residue_encodings_follow_the_fixed_alphabet() {
+    use featurizer::residue_encoding::{Encoding, ResidueEncoding, UNKNOWN_INDEX};
+
+    assert_eq!(ALPHABET, "ARNDCQEGHILKMFPSTWYVX");
+    assert_eq!(aa_index("TRP"), 17);
+    assert_eq!(aa_index("MSE"), aa_index("MET"));
+    assert_eq!(aa_index("HOH"), UNKNOWN_INDEX);
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: [Encoding::Onehot, Encoding::Index, Encoding::Blosum62].into_iter()
+        .map(|encoding| Box::new(ResidueEncoding { encoding }) as Box<dyn featurizer::features::FeatureExtractor>).collect(), ..Default::default() };
+    let chain = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let (onehot, index, blosum) = (&chain.features[0].1, &chain.features[1].1, &chain.features[2].1);
+    // --- MTYKLILNGK..., the sequence of protein G B1
+    let sequence: String = index.iter().map(|r| ALPHABET.as_bytes()[r[0] as usize] as char).collect();
+    assert_eq!(sequence, entity_sequence(&fname, "1"));
+    assert!(sequence.starts_with("MTYKLILNGK"));
+    for (i, aa) in sequence.chars().enumerate() {
+        let k = ALPHABET.find(aa).unwrap();
+        assert_eq!(onehot[i].iter().sum::<f64>(), 1.0);
+        assert_eq!(onehot[i][k], 1.0);
+        assert_eq!(blosum[i].len(), 20);
+    }
+    // --- BLOSUM62 rows: W-W 11, W-Y 2, W-A -3 and T-T 5
+    let trp = sequence.find('W').unwrap();
+    assert_eq!((blosum[trp][17], blosum[trp][18], blosum[trp][0]), (11.0, 2.0, -3.0));
+    assert_eq!(blosum[1][16], 5.0);
+}
+
+#[test]
+fn profiles_are_counted_from_the_alignment_columns() {
+    use featurizer::profiles::SequenceProfiles;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let query = entity_sequence(&fname, "1");
+    let dir = out_root("profiles");
+    fs::create_dir_all(&dir).unwrap();
+    // --- the third homolog has an insertion (lowercase) and a mutation at the first position, the fourth a gap at the third one
+    let mutated = format!("A{}kk{}", &query[1..10], &query[10..]);
+    let gapped = format!("{}-{}", &query[..2], &query[3..]);
+    fs::write(format!("{}/2gb1_A_profile.a3m", dir), format!(">query\n{}\n>h1\n{}\n>h2\n{}\n>h3\n{}\n", query, query, mutated, gapped)).unwrap();
+
+    let config = FeaturizerConfig { extractors: vec![Box::new(SequenceProfiles { dir: dir.clone() })], ..Default::default() };
+    let lines = run(&fname, "A", "2gb1_A_profile", &config);
+    let profile: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 20)).map(|r| r.features).collect();
+    assert_eq!(profile.len(), query.len());
+    let column = |aa: char| ALPHABET.find(aa).unwrap();
+    assert_eq!((profile[0][column('M')], profile[0][column('A')]), (0.75, 0.25));
+    assert_eq!(profile[2][column('Y')], 1.0);
+    for (i, aa) in query.chars().enumerate().skip(3) {
+        assert_eq!(profile[i][column(aa)], 1.0, "residue {}{}", aa, i + 1);
+        assert!((profile[i].iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+    // --- a chain without a profile is masked
+    let lines = run(&fname, "A", "2gb1_A_no_profile", &config);
+    assert!(lines.iter().filter_map(|l| records::parse_record(l, 20)).all(|r| r.features.iter().all(|v| v.is_nan())));
+}
+
+#[test]
+fn symmetry_labels_give_entity_positions() {
+    use featurizer::symmetry::SymmetryEquivalence;
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(SymmetryEquivalence)], ..Default::default() };
+    let loaded = LoadedDeposit::load(&fname, &config).unwrap();
+    for (chain, entity) in [("L", 1.0), ("H", 2.0)] {
+        let features = compute_chain_features(&loaded, chain, &config).unwrap();
+        let labels = &features.features[0].1;
+        let expected = ca_records(&fname, chain);
+        assert_eq!(labels.len(), expected.len());
+        for (row, ca) in labels.iter().zip(&expected) {
+            // --- chains of thrombin are all different entities, so none has a symmetry mate
+            assert_eq!(row, &vec![entity, ca.label_seq.parse::<f64>().unwrap() - 1.0, 1.0], "chain {} residue {}{}", chain, ca.res_seq, ca.i_code);
+        }
+    }
+}
+
+#[test]
+fn bfactors_are_read_from_the_atoms_and_normalized_per_chain() {
+    use featurizer::bfactors::{BFactorAtoms, BFactors};
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let text = fs::read_to_string(&fname).unwrap();
+    let columns: Vec<&str> = text.lines().filter_map(|l| l.trim().strip_prefix("_atom_site.")).collect();
+    let col = |name: &str| columns.iter().position(|c| *c == name).unwrap();
+    let (atom, alt, asym, occupancy, bfactor) = (col("label_atom_id"), col("label_alt_id"), col("auth_asym_id"), col("occupancy"), col("B_iso_or_equiv"));
+    let expected: Vec<(f64, f64)> = text.lines().filter(|l| l.starts_with("ATOM")).map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter(|t| t[atom] == "CA" && t[asym] == "L" && (t[alt] == "." || t[alt] == "A"))
+        .map(|t| (t[bfactor].parse().unwrap(), t[occupancy].parse().unwrap())).collect();
+
+    let config = FeaturizerConfig { extractors: vec![Box::new(BFactors { atoms: BFactorAtoms::Ca, normalize: false }),
+        Box::new(BFactors { atoms: BFactorAtoms::Backbone, normalize: false }), Box::new(BFactors { atoms: BFactorAtoms::Ca, normalize: true })],
+        ..Default::default() };
+    let chain = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "L", &config).unwrap();
+    let (ca, backbone, normalized) = (&chain.features[0].1, &chain.features[1].1, &chain.features[2].1);
+    assert_eq!(ca.len(), expected.len());
+    for (i, (b, occ)) in expected.iter().enumerate() {
+        assert_eq!(ca[i], vec![*b, *occ]);
+        // --- the CA columns, the second of N, CA, C and O, are the same in both modes
+        assert_eq!((backbone[i].len(), backbone[i][1], backbone[i][5]), (8, *b, *occ));
+    }
+    // --- Z-scores of B-factors, while occupancies are left as they are
+    let n = expected.len() as f64;
+    let mean = expected.iter().map(|e| e.0).sum::<f64>() / n;
+    let sdev = (expected.iter().map(|e| (e.0 - mean).powi(2)).sum::<f64>() / n).sqrt();
+    for (row, (b, occ)) in normalized.iter().zip(&expected) {
+        assert!((row[0] - (b - mean) / sdev).abs() < 1e-9);
+        assert_eq!(row[1], *occ);
+    }
+    let z: Vec<f64> = normalized.iter().map(|r| r[0]).collect();
+    assert!((z.iter().sum::<f64>() / n).abs() < 1e-9);
+    assert!((z.iter().map(|v| v * v).sum::<f64>() / n - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn library_featurization_reports_its_outputs_and_errors() {
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let root = out_root("2gb1_A_library");
+    let summary = featurize_chain(&fname, "A", &root, &FeaturizerConfig::default(), &CancellationToken::new()).unwrap();
+    assert_eq!((summary.id.as_str(), summary.chain.as_str(), summary.input.as_str()), (root.as_str(), "A", fname.as_str()));
+    assert_eq!((summary.n_residues, summary.n_observed), (entity_length(&fname, "1"), ca_records(&fname, "A").len()));
+    assert!(PathBuf::from(&summary.output).exists());
+
+    let missing = featurize_chain(&fname, "X", &out_root("2gb1_X_library"), &FeaturizerConfig::default(), &CancellationToken::new());
+    let error = missing.err().unwrap();
+    assert!(matches!(error, FeaturizerError::Pdb(_)));
+    assert_eq!(error.kind(), "NoSuchChain");
+    assert!(!PathBuf::from(format!("{}.dat", out_root("2gb1_X_library"))).exists());
+
+    // --- clones share the state of a token, so a worker sees a cancellation requested elsewhere
+    let token = CancellationToken::new();
+    let worker = token.clone();
+    std::thread::spawn(move || token.cancel()).join().unwrap();
+    assert!(worker.is_cancelled());
+    let root = out_root("2gb1_A_library_cancelled");
+    assert!(matches!(featurize_chain(&fname, "A", &root, &FeaturizerConfig::default(), &worker), Err(FeaturizerError::Cancelled)));
+    assert_eq!(FeaturizerError::Cancelled.kind(), "Cancelled");
+}
+
+#[test]
+fn assemblies_are_built_by_the_symmetry_operators() {
+    use featurizer::assembly::{build_assembly, chain_neighborhood, expand_operator_expression, read_operators};
+    use featurizer::chains::polymer_structure;
+
+    let ops = |expression: &str| -> Vec<String> { expand_operator_expression(expression).iter().map(|c| c.join("x")).collect() };
+    assert_eq!(ops("1"), vec!["1"]);
+    assert_eq!(ops("1,2,5"), vec!["1", "2", "5"]);
+    assert_eq!(ops("(1-3)"), vec!["1", "2", "3"]);
+    assert_eq!(ops("(1-2)(3,4)"), vec!["1x3", "1x4", "2x3", "2x4"]);
+
+    // --- a dimer of protein G: the identity and a two-fold axis along Z, shifted far away along X
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        if line.starts_with("_pdbx_struct_oper_list.") { continue; }
+        if line.starts_with("_pdbx_struct_assembly_gen.oper_expression") { lines.push("_pdbx_struct_assembly_gen.oper_expression 1,2".to_string()); continue; }
+        lines.push(line.to_string());
+        if line.starts_with("_pdbx_struct_assembly_gen.asym_id_list") {
+            lines.push("#\nloop_".to_string());
+            for item in ["id", "matrix[1][1]", "matrix[1][2]", "matrix[1][3]", "vector[1]", "matrix[2][1]", "matrix[2][2]", "matrix[2][3]", "vector[2]",
+                         "matrix[3][1]", "matrix[3][2]", "matrix[3][3]", "vector[3]"] {
+                lines.push(format!("_pdbx_struct_oper_list.{}", item));
+            }
+            lines.push("1 1 0 0 0 0 1 0 0 0 0 1 0".to_string());
+            lines.push("2 -1 0 0 1000 0 -1 0 0 0 0 1 0".to_string());
+        }
+    }
+    let fname = format!("{}.cif", out_root("2gb1_dimer_assembly"));
+    fs::write(&fname, lines.join("\n")).unwrap();
+    assert_eq!(read_operators(&fname).unwrap().len(), 2);
+
+    let asymmetric = polymer_structure(&LoadedDeposit::load(&fname, &FeaturizerConfig::default()).unwrap().deposit);
+    let assembly = build_assembly(&fname, &asymmetric, "1").unwrap();
+    let chain_ca = |chain: &str| -> Vec<Vec3> {
+        assembly.atoms().iter().filter(|a| a.chain_id == chain && a.name == " CA ").map(|a| a.pos.clone()).collect()
+    };
+    let (original, copy) = (chain_ca("A"), chain_ca("A-2"));
+    assert_eq!(original.len(), ca_records(&format!("{}/2gb1.cif", INPUTS), "A").len());
+    assert_eq!(copy.len(), original.len());
+    for (a, b) in original.iter().zip(&copy) {
+        assert!((b.x - (1000.0 - a.x)).abs() < 1e-9 && (b.y + a.y).abs() < 1e-9 && (b.z - a.z).abs() < 1e-9);
+    }
+    assert_eq!(chain_neighborhood(&assembly, "A", 12.0).atoms().len(), asymmetric.atoms().len());
+    assert_eq!(chain_neighborhood(&assembly, "A", 2000.0).atoms().len(), assembly.atoms().len());
+    assert!(build_assembly(&fname, &asymmetric, "2").is_err());
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn feature_descriptions_are_written_as_json() {
+    use featurizer::features::{core_features, describe_features, registered_extractors};
+
+    let fname = format!("{}.json", out_root("features"));
+    describe_features(Some(&fname)).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&fname).unwrap()).unwrap();
+    for kind in ["core", "per-residue", "pair", "segment", "atom"] { assert!(json[kind].is_array(), "{} features", kind); }
+    assert_eq!(json["core"].as_array().unwrap().len(), core_features().len());
+    let per_residue = json["per-residue"].as_array().unwrap();
+    assert_eq!(per_residue.len(), registered_extractors().len());
+    let ca_geometry = per_residue.iter().find(|f| f["name"] == "ca_geometry").unwrap();
+    assert_eq!((ca_geometry["shape"].as_str(), ca_geometry["version"].as_u64()), (Some("[L, 3]"), Some(1)));
+}
+
+#[test]
+fn gaps_are_runs_of_missing_entity_residues() {
+    use featurizer::gaps::{find_gaps, write_gaps};
+
+    // --- protein G with residues 1-2, 21-23 and 56 taken out of the structure
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let strctr = load_chain(&fname, "A").unwrap();
+    let all = strctr.residue_ids();
+    let missing: Vec<bool> = (0..all.len()).map(|i| i < 2 || (20..23).contains(&i) || i == 55).collect();
+    let resids: Vec<_> = all.iter().zip(&missing).filter(|(_, m)| !**m).map(|(r, _)| r.clone()).collect();
+    let gaps = find_gaps(&missing, &strctr, &resids);
+    let found: Vec<_> = gaps.iter().map(|g| (g.entity_start, g.length, g.before, g.after)).collect();
+    assert_eq!(found, vec![(0, 2, None, Some(0)), (20, 3, Some(17), Some(18)), (55, 1, Some(49), None)]);
+    let ca = ca_records(&fname, "A");
+    let d = (0..3).map(|k| (ca[19].pos[k] - ca[23].pos[k]).powi(2)).sum::<f64>().sqrt();
+    assert!((gaps[1].distance - d).abs() < 1e-6);
+    assert!(gaps[0].distance.is_nan() && gaps[2].distance.is_nan());
+
+    let out = format!("{}.gaps", out_root("2gb1_A_synthetic_gaps"));
+    write_gaps(&gaps, &out).unwrap();
+    let lines: Vec<Vec<String>> = fs::read_to_string(&out).unwrap().lines().map(|l| l.split_whitespace().map(|t| t.to_string()).collect()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0][..4], ["0", "2", "-", "0"]);
+    assert_eq!(lines[1][..4], ["20", "3", "17", "18"]);
+    assert!((lines[1][5].parse::<f64>().unwrap() - d / 4.0).abs() < 1e-3, "the distance is spread over four peptide bonds");
+    assert_eq!(lines[2][..4], ["55", "1", "49", "-"]);
+}
+
+#[test]
+fn map_quality_is_matched_by_residue_numbers_with_insertion_codes() {
+    use featurizer::map_quality::MapQualityFeatures;
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let residues = ca_records(&fname, "L");
+    assert!(residues.iter().any(|r| r.i_code != " "));
+    let dir = out_root("map_quality");
+    fs::create_dir_all(&dir).unwrap();
+    // --- the third residue is not listed; RSCC alternates between well and poorly supported residues
+    let rscc = |k: usize| if k % 2 == 0 { 0.95 } else { 0.5 };
+    let lines: Vec<String> = residues.iter().enumerate().filter(|(k, _)| *k != 2)
+        .map(|(k, r)| format!("L {}{} UNK {:.2} {:.2}", r.res_seq, r.i_code.trim(), 0.01 * k as f64, rscc(k))).collect();
+    fs::write(format!("{}/1c5n.txt", dir), format!("# chain residue name rsr rscc\n{}\n", lines.join("\n"))).unwrap();
+
+    let config = FeaturizerConfig { extractors: vec![Box::new(MapQualityFeatures { dir: dir.clone(), min_rscc: Some(0.8) })], ..Default::default() };
+    let records: Vec<_> = run(&fname, "L", "1c5n_L_map", &config).iter().filter_map(|l| records::parse_record(l, 3)).map(|r| r.features).collect();
+    assert_eq!(records.len(), residues.len());
+    for (k, values) in records.iter().enumerate() {
+        if k == 2 {
+            assert!(values[0].is_nan() && values[1].is_nan() && values[2] == 0.0);
+            continue;
+        }
+        assert!((values[0] - 0.01 * k as f64).abs() < 1e-6 && (values[1] - rscc(k)).abs() < 1e-6, "residue {}: {:?}", k, values);
+        assert_eq!(values[2], if rscc(k) >= 0.8 { 1.0 } else { 0.0 });
+    }
+}
+
+#[test]
+fn conformers_take_the_atoms_of_their_alternate_location() {
+    use featurizer::conformers::{chain_altloc_labels, select_conformer};
+
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    assert_eq!(chain_altloc_labels(&fname, "L").unwrap(), Vec::<char>::new());
+    assert_eq!(chain_altloc_labels(&fname, "H").unwrap(), vec!['A', 'B']);
+
+    // --- alternate atoms of Met 84, 106, 210 and Glu 97A, as given in the _atom_site category
+    let text = fs::read_to_string(&fname).unwrap();
+    let columns: Vec<&str> = text.lines().filter_map(|l| l.trim().strip_prefix("_atom_site.")).collect();
+    let col = |name: &str| columns.iter().position(|c| *c == name).unwrap();
+    let (atom, alt, x, seq, asym) = (col("label_atom_id"), col("label_alt_id"), col("Cartn_x"), col("auth_seq_id"), col("auth_asym_id"));
+    let alternates: Vec<(char, i32, String, [f64; 3])> = text.lines().filter(|l| l.starts_with("ATOM")).map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter(|t| t[asym] == "H" && t[alt] != ".")
+        .map(|t| (t[alt].chars().next().unwrap(), t[seq].parse().unwrap(), t[atom].to_string(), [t[x].parse().unwrap(), t[x + 1].parse().unwrap(), t[x + 2].parse().unwrap()]))
+        .collect();
+
+    let strctr = load_chain(&fname, "H").unwrap();
+    let n_fixed = strctr.atoms().iter().filter(|a| a.alt_loc == ' ').count();
+    for label in ['A', 'B'] {
+        let conformer = select_conformer(&strctr, label);
+        assert!(conformer.atoms().iter().all(|a| a.alt_loc == ' ' || a.alt_loc == label));
+        let expected: Vec<_> = alternates.iter().filter(|a| a.0 == label).collect();
+        assert_eq!(conformer.atoms().len(), n_fixed + expected.len());
+        for (_, res_seq, name, pos) in expected {
+            let a = conformer.atoms().iter().find(|a| a.res_seq == *res_seq && a.name.trim() == name).unwrap();
+            assert_eq!([a.pos.x, a.pos.y, a.pos.z], *pos, "{} {} of conformer {}", res_seq, name, label);
+        }
+    }
+    // --- residues without the requested location contribute their first one
+    assert_eq!(select_conformer(&strctr, 'C').atoms().len(), select_conformer(&strctr, 'A').atoms().len());
+}
+
+#[test]
+fn record_keys_are_namespaced_by_the_source() {
+    use featurizer::manifest::{read_manifest, source_statistics, write_manifest};
+
+    for (name, source) in [("2gb1.cif", Source::Pdb), ("pdb2gb1.ent.gz", Source::Pdb), ("AF-P06654-F1-model_v4.cif.gz", Source::Afdb),
+                           ("model_01.cif", Source::Local), ("0abc.cif", Source::Local)] {
+        assert_eq!(Source::detect(&format!("some/dir/{}", name)), source, "{}", name);
+    }
+    // --- the same deposit, saved under an AlphaFold DB and a local name
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let mut summaries = vec![];
+    for (name, source) in [("AF-P06654-F1-model_v4", None), ("model_01", None), ("model_02", Some(Source::Afdb))] {
+        let fname = format!("{}.cif", out_root(name));
+        fs::write(&fname, &text).unwrap();
+        let config = FeaturizerConfig { source, ..Default::default() };
+        summaries.push(featurize_chain(&fname, "A", &out_root(&format!("{}_A", name)), &config, &CancellationToken::new()).unwrap());
+    }
+    let keys: Vec<&str> = summaries.iter().map(|s| s.key.as_str()).collect();
+    assert_eq!(keys, vec!["afdb/AF-P06654-F1-model_v4_A", "local/model_01_A", "afdb/model_02_A"]);
+    let n = ca_records(&format!("{}/2gb1.cif", INPUTS), "A").len();
+    let stats = source_statistics(&summaries);
+    assert_eq!(stats.into_iter().collect::<Vec<_>>(), vec![(Source::Afdb, (2, 2 * n)), (Source::Local, (1, n))]);
+
+    let manifest = format!("{}.tsv", out_root("sources_manifest"));
+    write_manifest(&summaries, &manifest).unwrap();
+    let read: Vec<(String, Source)> = read_manifest(&manifest).unwrap().into_iter().map(|s| (s.key, s.source)).collect();
+    assert_eq!(read, summaries.iter().map(|s| (s.key.clone(), s.source)).collect::<Vec<_>>());
+}
+
+#[test]
+fn low_plddt_residues_of_models_are_written_as_missing() {
+    use featurizer::plddt::Plddt;
+
+    // --- a model of protein G whose first five residues are predicted with low confidence
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let model: Vec<String> = text.lines().map(|line| {
+        if !line.starts_with("ATOM ") { return line.to_string(); }
+        let mut t: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
+        t[14] = if t[16].parse::<i32>().unwrap() <= 5 { "30.00" } else { "90.00" }.to_string();
+        t.join(" ")
+    }).collect();
+    let n = ca_records(&format!("{}/2gb1.cif", INPUTS), "A").len();
+    let config = FeaturizerConfig { extractors: vec![Box::new(Plddt)], min_plddt: Some(50.0), ..Default::default() };
+    for (name, n_masked) in [("AF-P19909-F1-model_v4", 5), ("local_model", 0)] {
+        let fname = format!("{}.cif", out_root(name));
+        fs::write(&fname, model.join("\n")).unwrap();
+        let lines = run(&fname, "A", &format!("{}_A", name), &config);
+        assert_eq!(lines.len(), n);
+        // --- only AlphaFold DB models give pLDDT in the B-factor column, so only they are masked
+        let written: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 1)).collect();
+        assert_eq!(written.len(), n - n_masked, "{}", name);
+        assert!(lines[..n_masked].iter().all(|l| l.trim_start().starts_with('-')));
+        for r in &written { assert_eq!(r.features[0], if r.index < 5 { 30.0 } else { 90.0 }); }
+    }
+}
+
+#[test]
+fn microenvironments_count_atoms_and_residues_in_shells() {
+    use featurizer::chains::residue_heavy_atoms;
+    use featurizer::geometry::distance;
+    use featurizer::microenvironment::{MicroEnvironment, ATOM_TYPES, RESIDUE_CLASSES};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let config = FeaturizerConfig { extractors: vec![Box::new(MicroEnvironment::default())], ..Default::default() };
+    let chain = compute_chain_features(&LoadedDeposit::load(&fname, &config).unwrap(), "A", &config).unwrap();
+    let rows = &chain.features[0].1;
+
+    // --- every shell is counted anew from all pairs of atoms, around CB or CA of glycine
+    let strctr = load_chain(&fname, "A").unwrap();
+    let resids = strctr.residue_ids();
+    assert_eq!(rows.len(), resids.len());
+    let shells = MicroEnvironment::default().shells;
+    let shell = |d: f64| shells.iter().position(|r| d < *r);
+    let atoms = residue_heavy_atoms(&strctr, &resids);
+    let centers: Vec<_> = atoms.iter().map(|res| res.iter().find(|a| a.name == " CB ").or(res.iter().find(|a| a.name == " CA ")).unwrap().clone()).collect();
+    let per_shell = ATOM_TYPES.len() + RESIDUE_CLASSES.len();
+    for (i, center) in centers.iter().enumerate() {
+        let mut expected = vec![0.0; shells.len() * per_shell];
+        for a in atoms.iter().enumerate().filter(|(j, _)| *j != i).flat_map(|(_, res)| res) {
+            let t = ATOM_TYPES.iter().position(|e| a.name.trim().starts_with(*e));
+            if let (Some(t), Some(s)) = (t, shell(distance(&a.pos, &center.pos))) { expected[s * per_shell + t] += 1.0; }
+        }
+        for (_, other) in centers.iter().enumerate().filter(|(j, _)| *j != i) {
+            let aa = ALPHABET.as_bytes()[aa_index(&other.res_name)] as char;
+            let c = RESIDUE_CLASSES.iter().position(|c| c.contains(aa));
+            if let (Some(c), Some(s)) = (c, shell(distance(&other.pos, &center.pos))) { expected[s * per_shell + ATOM_TYPES.len() + c] += 1.0; }
+        }
+        assert_eq!(rows[i], expected, "microenvironment of residue {}", i);
+    }
+    // --- a residue in the core of protein G is surrounded by more atoms than one at the end of the chain
+    let atoms_within = |i: usize| rows[i].chunks(per_shell).map(|s| s[..ATOM_TYPES.len()].iter().sum::<f64>()).sum::<f64>();
+    assert!(atoms_within(42) > atoms_within(0));
+}
+
+#[test]
+fn augmented_variants_are_reproducible_corruptions_of_the_trace() {
+    use featurizer::augmentation::{write_augmented, Augmentation};
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let strctr = load_chain(&fname, "A").unwrap();
+    let resids = strctr.residue_ids();
+    let ca = ca_records(&fname, "A");
+    let n = resids.len();
+    // --- coil only between residues 10 and 20, so that's where a loop is removed from
+    let ss: Vec<u8> = (0..n).map(|i| if (10..20).contains(&i) { b'C' } else { b'H' }).collect();
+    let variants = |aug: &Augmentation, chain_id: &str| -> Vec<Vec<String>> {
+        let out = format!("{}.aug", out_root(&format!("augmented_{}", chain_id)));
+        write_augmented(&strctr, &resids, &ss, aug, chain_id, &out).unwrap();
+        fs::read_to_string(&out).unwrap().lines().map(|l| l.split_whitespace().map(|t| t.to_string()).collect()).collect()
+    };
+
+    let exact = Augmentation { n_variants: 2, ca_noise: 0.0, mask_fraction: 0.0, loop_length: 0, seed: 7 };
+    let lines = variants(&exact, "2gb1_A");
+    assert_eq!(lines.len(), 2 * n);
+    for (line, (i, r)) in lines.iter().zip((0..2).flat_map(|_| ca.iter().enumerate())) {
+        assert_eq!(line[1].parse::<usize>().unwrap(), i);
+        for k in 0..3 { assert!((line[2 + k].parse::<f64>().unwrap() - r.pos[k]).abs() < 1e-3); }
+        assert_eq!(line[5], "0");
+    }
+
+    let noisy = Augmentation { n_variants: 3, ca_noise: 0.5, mask_fraction: 0.1, loop_length: 5, seed: 7 };
+    let lines = variants(&noisy, "2gb1_A");
+    // --- the same seed and chain give the same variants, another chain gets other ones
+    assert_eq!(lines, variants(&noisy, "2gb1_A"));
+    assert_ne!(lines, variants(&noisy, "1c5n_L"));
+    assert_ne!(lines, variants(&Augmentation { seed: 8, ..noisy.clone() }, "2gb1_A"));
+    for variant in lines.chunks(n) {
+        let flagged: Vec<usize> = (0..n).filter(|i| variant[*i][5] == "1").collect();
+        assert!(flagged.iter().all(|i| variant[*i][2] == "NaN"));
+        assert!(flagged.windows(5).any(|w| w[4] == w[0] + 4 && w[0] >= 10 && w[4] < 20), "no loop removed: {:?}", flagged);
+        let displacement = (0..n).filter(|i| !flagged.contains(i))
+            .map(|i| (0..3).map(|k| (variant[i][2 + k].parse::<f64>().unwrap() - ca[i].pos[k]).powi(2)).sum::<f64>()).sum::<f64>();
+        // --- mean squared displacement of a 3D Gaussian with sigma 0.5 Å is 0.75 Å^2
+        let msd = displacement / (n - flagged.len()) as f64;
+        assert!(msd > 0.3 && msd < 1.5, "mean squared displacement: {}", msd);
+    }
+}
+
+#[test]
+fn diffs_report_residues_that_moved_between_conformations() {
+    use featurizer::diff::write_chain_diff;
+
+    // --- the second conformation is rotated and shifted as a whole; Trp43 of the third strand is also moved away by 10 Å
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let text = fs::read_to_string(&fname).unwrap();
+    let moved: Vec<String> = text.lines().map(|line| {
+        if !line.starts_with("ATOM ") { return line.to_string(); }
+        let mut t: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
+        let (x, y, z): (f64, f64, f64) = (t[10].parse().unwrap(), t[11].parse().unwrap(), t[12].parse().unwrap());
+        let shift = if t[16] == "43" { 10.0 } else { 0.0 };
+        (t[10], t[11], t[12]) = (format!("{:.3}", -y + 3.0 + shift), format!("{:.3}", x - 2.0), format!("{:.3}", z + 5.0));
+        t.join(" ")
+    }).collect();
+    let second = format!("{}.cif", out_root("2gb1_moved"));
+    fs::write(&second, moved.join("\n")).unwrap();
+    let out = format!("{}.diff", out_root("2gb1_moved"));
+    write_chain_diff(&load_chain(&fname, "A").unwrap(), &load_chain(&second, "A").unwrap(), &out).unwrap();
+
+    let text = fs::read_to_string(&out).unwrap();
+    let rows: Vec<Vec<&str>> = text.lines().filter(|l| !l.starts_with('#')).map(|l| l.split_whitespace().collect()).collect();
+    assert_eq!(rows.len(), 56);
+    // --- columns are read from the end of a line, since residue IDs may hold spaces
+    let column = |row: &Vec<&str>, k: usize| row[row.len() - k].to_string();
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!((row[0].parse::<usize>().unwrap(), row[1].parse::<usize>().unwrap()), (i, i));
+        let displacement: f64 = column(row, 3).parse().unwrap();
+        if i == 42 { assert!(displacement > 9.0, "Trp43 moved by {}", displacement); }
+        else { assert!(displacement < 0.5, "residue {} moved by {}", i, displacement); }
+        assert_eq!(column(row, 4), if column(row, 6) == column(row, 5) { "0" } else { "1" });
+    }
+    // --- the moved residue loses its backbone H-bonds and its partners of the fourth strand lose theirs to it
+    let lost = |i: usize| column(&rows[i], 2).parse::<usize>().unwrap();
+    assert!(lost(42) > 0);
+    assert!((0..rows.len()).any(|i| i.abs_diff(42) > 2 && lost(i) > 0));
+    assert!(text.lines().next().unwrap().starts_with("# aligned residues: 56 CA rmsd:"));
+}
+
+#[test]
+fn models_are_evaluated_after_a_backbone_superposition() {
+    use featurizer::evaluate::{evaluate_model, write_evaluation};
+
+    // --- a model is the reference rotated as a whole, with the carbonyl carbon of Lys10 pulled out by 1 Å
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let text = fs::read_to_string(&fname).unwrap();
+    let model: Vec<String> = text.lines().map(|line| {
+        if !line.starts_with("ATOM ") { return line.to_string(); }
+        let mut t: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
+        let (x, y, z): (f64, f64, f64) = (t[10].parse().unwrap(), t[11].parse().unwrap(), t[12].parse().unwrap());
+        let shift = if t[16] == "10" && t[3] == "C" { 1.0 } else { 0.0 };
+        (t[10], t[11], t[12]) = (format!("{:.3}", z + shift), format!("{:.3}", x + 4.0), format!("{:.3}", y - 1.0));
+        t.join(" ")
+    }).collect();
+    let model_fname = format!("{}.cif", out_root("2gb1_model"));
+    fs::write(&model_fname, model.join("\n")).unwrap();
+    let reference = load_chain(&fname, "A").unwrap();
+    let eval = evaluate_model(&load_chain(&model_fname, "A").unwrap(), &reference).unwrap();
+    // --- outliers of the deposit itself are counted when it's evaluated against itself
+    let native = evaluate_model(&reference, &reference).unwrap();
+    assert!(native.backbone_rmsd < 1e-6 && native.ca_rmsd < 1e-6);
+
+    assert_eq!(eval.residues.len(), 56);
+    assert!(eval.backbone_rmsd > 0.0 && eval.backbone_rmsd < 0.1, "backbone rmsd: {}", eval.backbone_rmsd);
+    for (i, (r, n)) in eval.residues.iter().zip(&native.residues).enumerate() {
+        assert!(r.ca_deviation < 0.05, "CA of residue {} deviates by {}", i, r.ca_deviation);
+        if i == 9 {
+            // --- N-CA-C-O of Lys10 are off by 1 Å at one of the four atoms
+            assert!(r.backbone_rmsd > 0.4 && r.backbone_rmsd < 0.6, "{}", r.backbone_rmsd);
+            assert!(r.bond_outliers > n.bond_outliers && r.angle_outliers >= n.angle_outliers);
+        } else {
+            assert!(r.backbone_rmsd < 0.05);
+            assert_eq!((r.bond_outliers, r.angle_outliers), (n.bond_outliers, n.angle_outliers), "outliers of residue {}", i);
+        }
+    }
+    let out = format!("{}.eval", out_root("2gb1_model"));
+    write_evaluation(&eval, &out).unwrap();
+    let text = fs::read_to_string(&out).unwrap();
+    assert!(text.starts_with("# aligned residues: 56 backbone rmsd: "));
+    assert_eq!(text.lines().filter(|l| !l.starts_with('#')).count(), 56);
+}
+
+#[test]
+fn fragment_libraries_give_back_the_backbone_of_their_windows() {
+    use bioshell_pdb::ResidueId;
+    use featurizer::fragments::FragmentLibrary;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let strctr = load_chain(&fname, "A").unwrap();
+    let resids = strctr.residue_ids();
+    let mut library = FragmentLibrary::new(4, 0.2);
+    // --- every window of protein G is complete and connected
+    assert_eq!(library.add_chain(&strctr, &resids), resids.len() - 3);
+    assert_eq!(library.entries.values().map(|e| e.count as usize).sum::<usize>(), resids.len() - 3);
+    assert!(library.entries.keys().all(|k| k.len() == 3));
+
+    let out = format!("{}.bin", out_root("2gb1_fragments"));
+    library.write(&out).unwrap();
+    let read = FragmentLibrary::read(&out).unwrap();
+    assert_eq!((read.length, read.bin_width, read.entries.len()), (4, 0.2, library.entries.len()));
+    for ((key, entry), (key_read, entry_read)) in library.entries.iter().zip(&read.entries) {
+        assert_eq!((key, entry.count), (key_read, entry_read.count));
+        assert_eq!(entry.atoms.iter().map(|p| (p.x, p.y, p.z)).collect::<Vec<_>>(), entry_read.atoms.iter().map(|p| (p.x, p.y, p.z)).collect::<Vec<_>>());
+    }
+
+    // --- a window alone in its bin gets its own N, C and O back, also when the chain is rotated as a whole
+    let moved = |p: &Vec3| Vec3::new(p.z + 1.0, p.x - 2.0, p.y + 3.0);
+    let atom = |r: &ResidueId, name: &str| strctr.atom(r, name).unwrap().pos.clone();
+    let mut n_checked = 0;
+    for window in resids.windows(4) {
+        let ca: Vec<Vec3> = window.iter().map(|r| atom(r, " CA ")).collect();
+        if read.entries[&read.key(&ca)].count > 1 { continue; }
+        let expected: Vec<Vec3> = window[1..3].iter().flat_map(|r| [" N  ", " C  ", " O  "].map(|name| atom(r, name))).collect();
+        let found = read.lookup(&ca.iter().map(moved).collect::<Vec<_>>()).unwrap();
+        for (p, q) in expected.iter().zip(&found) {
+            let p = moved(p);
+            assert!((p.x - q.x).abs() < 1e-6 && (p.y - q.y).abs() < 1e-6 && (p.z - q.z).abs() < 1e-6);
+        }
+        n_checked += 1;
+    }
+    assert!(n_checked > 0);
+    // --- a mirror image has the opposite chirality, so its last distance falls into another bin
+    let ca: Vec<Vec3> = resids[..4].iter().map(|r| atom(r, " CA ")).collect();
+    let mirrored: Vec<Vec3> = ca.iter().map(|p| Vec3::new(-p.x, p.y, p.z)).collect();
+    let (key, mirrored_key) = (read.key(&ca), read.key(&mirrored));
+    assert_eq!(key[..2], mirrored_key[..2]);
+    assert_ne!(key[2].signum(), mirrored_key[2].signum());
+}
+
+#[test]
+fn predicted_models_are_compared_with_their_experimental_structures() {
+    use featurizer::agreement::PredictionAgreement;
+
+    // --- a "model" of protein G: the deposit rotated as a whole, without its first two residues and with the CA of Glu19 moved by 1.5 Å
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let model: Vec<String> = text.lines().filter_map(|line| {
+        if !line.starts_with("ATOM ") { return Some(line.to_string()); }
+        let mut t: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
+        if t[16].parse::<i32>().unwrap() <= 2 { return None; }
+        let (x, y, z): (f64, f64, f64) = (t[10].parse().unwrap(), t[11].parse().unwrap(), t[12].parse().unwrap());
+        let shift = if t[16] == "19" && t[3] == "CA" { 1.5 } else { 0.0 };
+        (t[10], t[11], t[12]) = (format!("{:.3}", y + shift), format!("{:.3}", z - 7.0), format!("{:.3}", x + 2.0));
+        Some(t.join(" "))
+    }).collect();
+    let fname = format!("{}.cif", out_root("AF-P06653-F1-model_v4"));
+    fs::write(&fname, model.join("\n")).unwrap();
+    let mapping = format!("{}.txt", out_root("agreement"));
+    fs::write(&mapping, format!("# model reference chain\nAF-P06653-F1-model_v4_A {}/2gb1.cif A\n", INPUTS)).unwrap();
+    let config = FeaturizerConfig { extractors: vec![Box::new(PredictionAgreement::from_file(&mapping).unwrap())], ..Default::default() };
+
+    // --- the model is found by the name of its outputs, which are written to another directory
+    let lines = run(&fname, "A", "AF-P06653-F1-model_v4_A", &config);
+    let deviations: Vec<f64> = lines.iter().filter_map(|l| records::parse_record(l, 1)).map(|r| r.features[0]).collect();
+    assert_eq!(deviations.len(), 54);
+    for (i, d) in deviations.iter().enumerate() {
+        // --- Glu19 is the 17th residue of the model; the superposition spreads a bit of its shift over the other residues
+        if i == 16 { assert!(*d > 1.3 && *d < 1.6, "deviation of Glu19: {}", d); }
+        else { assert!(*d < 0.1, "deviation of residue {}: {}", i, d); }
+    }
+    // --- a chain that has no reference gets NaN
+    let lines = run(&fname, "A", "another_model_A", &config);
+    assert!(lines.iter().filter_map(|l| records::parse_record(l, 1)).all(|r| r.features[0].is_nan()));
+    fs::write(&mapping, "AF-P06653-F1-model_v4_A 2gb1.cif\n").unwrap();
+    assert!(PredictionAgreement::from_file(&mapping).is_err());
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn zip_shards_hold_the_outputs_of_their_chains() {
+    use std::io::Read;
+    use featurizer::archive::{pack_dataset, read_index, write_index, ArchiveFormat};
+
+    let token = CancellationToken::new();
+    let chains = [("2gb1", "A"), ("1c5n", "L"), ("1c5n", "H")];
+    let summaries: Vec<_> = chains.iter().map(|(code, chain)| {
+        featurize_chain(&format!("{}/{}.cif", INPUTS, code), chain, &out_root(&format!("{}_{}_zip", code, chain)), &FeaturizerConfig::default(), &token).unwrap()
+    }).collect();
+    let prefix = out_root("shards/zipped");
+    let index = pack_dataset(&summaries, ArchiveFormat::Zip, 2, &prefix).unwrap();
+    // --- three chains, two per shard
+    assert_eq!(index.iter().map(|e| e.shard.as_str()).collect::<Vec<_>>(), vec!["zipped-00000.zip", "zipped-00000.zip", "zipped-00001.zip"]);
+    write_index(&index, &format!("{}.index.tsv", prefix)).unwrap();
+    let read = read_index(&format!("{}.index.tsv", prefix)).unwrap();
+    assert_eq!(read.iter().map(|e| (&e.key, &e.shard, &e.members)).collect::<Vec<_>>(), index.iter().map(|e| (&e.key, &e.shard, &e.members)).collect::<Vec<_>>());
+
+    // --- members of a shard are the output files of its chains, named by their keys
+    for (entry, summary) in index.iter().zip(&summaries) {
+        let mut shard = zip::ZipArchive::new(fs::File::open(out_root(&format!("shards/{}", entry.shard))).unwrap()).unwrap();
+        for (member, ext) in entry.members.iter().zip([".dat", ".meta"]) {
+            assert_eq!(member, &format!("{}{}", summary.key, ext));
+            let mut text = String::new();
+            shard.by_name(member).unwrap().read_to_string(&mut text).unwrap();
+            assert_eq!(text, fs::read_to_string(format!("{}{}", summary.id, ext)).unwrap());
+        }
+    }
+}
+
+#[test]
+fn hbonds_to_other_chains_are_listed_with_their_direction() {
+    let fname = format!("{}/1c5n.cif", INPUTS);
+    let read = |name: &str, inter_chain: bool| {
+        run(&fname, "L", name, &FeaturizerConfig { inter_chain, ..Default::default() });
+        records::read_hbonds(&out_root(name)).unwrap()
+    };
+    let alone = read("1c5n_L_hbonds_alone", false);
+    let with_partners = read("1c5n_L_hbonds_inter", true);
+    assert!(alone.iter().all(|hb| matches!(hb.direction, HBondDirection::Forward | HBondDirection::Backward)));
+
+    // --- the N-terminus of the light chain is bonded to the heavy chain both as a donor and as an acceptor
+    let n_heavy = ca_records(&fname, "H").len();
+    let partner = |index: &str| -> usize {
+        let (chain, k) = index.split_once(':').unwrap_or_else(|| panic!("{} is not chain-qualified", index));
+        assert!(chain == "H" || chain == "I");
+        k.parse().unwrap()
+    };
+    for hb in with_partners.iter().filter(|hb| matches!(hb.direction, HBondDirection::Out | HBondDirection::In)) {
+        let (own, other) = if hb.direction == HBondDirection::Out { (&hb.donor, &hb.acceptor) } else { (&hb.acceptor, &hb.donor) };
+        assert!(own.parse::<usize>().is_ok(), "residues of the featurized chain are given by plain indexes: {}", own);
+        assert!(partner(other) < n_heavy);
+        assert!(hb.separation.is_none() && hb.energy < 0.0);
+    }
+    for direction in [HBondDirection::Out, HBondDirection::In] {
+        assert!(with_partners.iter().any(|hb| hb.direction == direction && hb.donor.contains(':') == (direction == HBondDirection::In)), "no {} H-bond", direction);
+    }
+    // --- partner chains add no H-bonds within the chain
+    let within = |hbonds: &[HBondPair]| -> HashSet<(String, String)> { hbonds.iter()
+        .filter(|hb| matches!(hb.direction, HBondDirection::Forward | HBondDirection::Backward)).map(|hb| (hb.donor.clone(), hb.acceptor.clone())).collect() };
+    assert!(within(&with_partners).is_subset(&within(&alone)));
+}
+
+#[test]
+fn observed_residues_are_mapped_onto_their_entity() {
+    use bioshell_pdb::ResidueId;
+    use featurizer::alignment::map_to_entity;
+
+    let fname = format!("{}/2gb1.cif", INPUTS);
+    let text = fs::read_to_string(&fname).unwrap();
+    let sequence = entity_sequence(&fname, "1");
+    let n = sequence.len();
+    // --- the deposit with its atom records changed by `edit`, which may also add records
+    let edited = |name: &str, edit: &dyn Fn(Vec<String>) -> Vec<Vec<String>>| -> (Structure, Vec<ResidueId>) {
+        let lines: Vec<String> = text.lines().flat_map(|line| {
+            if !line.starts_with("ATOM ") { return vec![line.to_string()]; }
+            edit(line.split_whitespace().map(|s| s.to_string()).collect()).into_iter().map(|t| t.join(" ")).collect()
+        }).collect();
+        let out = format!("{}.cif", out_root(name));
+        fs::write(&out, lines.join("\n")).unwrap();
+        let strctr = load_chain(&out, "A").unwrap();
+        let resids = strctr.residue_ids();
+        (strctr, resids)
+    };
+    let identity: Vec<Option<usize>> = (0..n).map(Some).collect();
+
+    // --- residues are matched one by one when the types agree, whatever the author numbering, here from -9 up
+    let (strctr, resids) = edited("2gb1_negative", &|mut t| { t[16] = (t[16].parse::<i32>().unwrap() - 10).to_string(); vec![t] });
+    assert_eq!(resids[0].res_seq, -9);
+    assert_eq!(map_to_entity(sequence.as_bytes(), &vec![true; n], &strctr, &resids), identity);
+    // --- the first three residues are not observed, so the others are shifted by three positions
+    let (strctr, resids) = edited("2gb1_no_n_terminus", &|t| if t[16].parse::<i32>().unwrap() <= 3 { vec![] } else { vec![t] });
+    let observed: Vec<bool> = (0..n).map(|i| i >= 3).collect();
+    assert_eq!(map_to_entity(sequence.as_bytes(), &observed, &strctr, &resids), (3..n).map(Some).collect::<Vec<_>>());
+
+    // --- Phe30 changed to alanine doesn't match, so the chain is aligned: the residue is still mapped, as a substitution
+    let (strctr, resids) = edited("2gb1_mutant", &|mut t| { if t[16] == "30" { t[5] = "ALA".to_string(); t[17] = "ALA".to_string(); } vec![t] });
+    assert_eq!(map_to_entity(sequence.as_bytes(), &vec![true; n], &strctr, &resids), identity);
+    // --- a residue inserted as 20A is left out of the entity; residues around it keep their positions
+    let (strctr, resids) = edited("2gb1_insertion", &|t| {
+        if t[16] != "20" { return vec![t]; }
+        let mut inserted = t.clone();
+        (inserted[5], inserted[9], inserted[17]) = ("TRP".to_string(), "A".to_string(), "TRP".to_string());
+        vec![t, inserted]
+    });
+    assert_eq!(resids.len(), n + 1);
+    assert_eq!((resids[20].res_seq, resids[20].i_code), (20, 'A'));
+    let mapping = map_to_entity(sequence.as_bytes(), &vec![true; n], &strctr, &resids);
+    let mut expected = identity.clone();
+    expected.insert(20, None);
+    assert_eq!(mapping, expected);
+}
+
+#[test]
+fn nucleic_acid_and_hybrid_chains_are_featurized_only_on_request() {
+    use featurizer::polymers::PolymerPolicy;
+
+    // --- protein G with its first `n` residues turned into nucleotides: the residue names are replaced in the entity,
+    // --- the sequence scheme and the atom records, and CA atoms take the name of the C4' atom of a nucleotide
+    let text = fs::read_to_string(format!("{}/2gb1.cif", INPUTS)).unwrap();
+    let bases = ["DA", "DC", "DG", "DT"];
+    let nucleotides = |n: usize| -> String {
+        let base = |num: &str| num.parse::<usize>().ok().filter(|k| *k <= n).map(|k| bases[(k - 1) % 4]);
+        text.lines().map(|line| {
+            let mut t: Vec<&str> = line.split_whitespace().collect();
+            if line.starts_with("ATOM ") {
+                let Some(b) = base(t[8]) else { return line.to_string() };
+                (t[5], t[17]) = (b, b);
+                if t[3] == "CA" { (t[3], t[19]) = ("\"C4'\"", "\"C4'\""); }
+            } else if t.len() == 4 && t[0] == "1" && t[3] == "n" {
+                let Some(b) = base(t[1]) else { return line.to_string() };
+                t[2] = b;
+            } else if t.len() == 12 && t[0] == "A" && t[11] == "n" {
+                let Some(b) = base(t[2]) else { return line.to_string() };
+                (t[3], t[7], t[8]) = (b, b, b);
+            } else { return line.to_string(); }
+            t.join(" ")
+        }).collect::<Vec<_>>().join("\n")
+    };
+    let ca = ca_records(&format!("{}/2gb1.cif", INPUTS), "A");
+    let token = CancellationToken::new();
+    for (name, n, kind) in [("2gb1_hybrid", 4, "hybrid"), ("2gb1_nucleic", 56, "nucleic acid")] {
+        let fname = format!("{}.cif", out_root(name));
+        fs::write(&fname, nucleotides(n)).unwrap();
+        // --- rejected by default, before anything is written
+        let root = out_root(&format!("{}_A", name));
+        let _ = fs::remove_file(format!("{}.dat", root));
+        let result = featurize_chain(&fname, "A", &root, &FeaturizerConfig::default(), &token);
+        assert!(matches!(&result, Err(FeaturizerError::Filtered(m)) if m.contains(kind)), "{:?}", result.err());
+        assert!(!PathBuf::from(format!("{}.dat", root)).exists());
+
+        // --- with --polymer all every residue is written, nucleotides at their C4' atoms
+        let config = FeaturizerConfig { polymer: PolymerPolicy::All, ..Default::default() };
+        let lines = run(&fname, "A", &format!("{}_A", name), &config);
+        let written: Vec<_> = lines.iter().filter_map(|l| records::parse_record(l, 0)).collect();
+        assert_eq!(written.len(), ca.len(), "{}", name);
+        for (r, expected) in written.iter().zip(&ca) {
+            for d in 0..3 { assert!((r.ca[d] - expected.pos[d]).abs() < 1e-3, "{} residue {}", name, r.index); }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Golden-file tests: fixture chains of `tests/input_files` are featurized and every output file is compared line by line
+//! with its copy stored in `tests/golden`, so any change of the written records shows up in a review.
+//!
+//! After an intended change of the outputs the golden files are written anew with:
+//! ```text
+//! FEATURIZER_BLESS=1 cargo test --test golden
+//! ```
+//! Deposits of the corpus listed in `tests/input_files/corpus.txt` are downloaded from the RCSB with:
+//! ```text
+//! cargo run -- fetch -l tests/input_files/corpus.txt -p tests/input_files
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use featurizer::ca_trace::CaTraceGeometry;
+use featurizer::chi_angles::ChiAngles;
+use featurizer::{featurize_chain, CancellationToken, FeaturizerConfig};
+
+const INPUTS: &str = "tests/input_files";
+const GOLDEN: &str = "tests/golden";
+
+/// Fixture chains: an NMR structure with hydrogens, two chains of a crystal structure numbered with insertion codes
+/// and holding alternate locations, a helix of the ROP four-helix bundle, the beta sandwich of the tenascin
+/// fibronectin type III domain and a ten-model NMR ensemble of ubiquitin
+const FIXTURES: [(&str, &str); 6] = [("2gb1", "A"), ("1c5n", "L"), ("1c5n", "H"), ("1rop", "A"), ("1ten", "A"), ("1d3z", "A")];
+
+/// Output files compared with their golden copies
+const COMPARED: [&str; 3] = ["dat", "hbonds", "gaps"];
+
+fn blessing() -> bool { std::env::var("FEATURIZER_BLESS").map_or(false, |v| v == "1") }
+
+/// Compares an output file with its golden copy, or replaces the copy when blessing
+fn compare(output: &Path, golden: &Path) {
+    let found = fs::read_to_string(output).unwrap();
+    if blessing() {
+        fs::create_dir_all(GOLDEN).unwrap();
+        fs::write(golden, found).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(golden).unwrap_or_else(|_| panic!("{} is missing; run FEATURIZER_BLESS=1 cargo test --test golden", golden.display()));
+    assert_eq!(found.lines().count(), expected.lines().count(), "{}: number of lines differs from {}", output.display(), golden.display());
+    for (k, (f, e)) in found.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(f, e, "{}: line {} differs from {}", output.display(), k + 1, golden.display());
+    }
+}
+
+#[test]
+fn outputs_match_the_golden_files() {
+    let dir = std::env::temp_dir().join("featurizer_golden");
+    fs::create_dir_all(&dir).unwrap();
+    let config = FeaturizerConfig { gaps: true, extractors: vec![Box::new(CaTraceGeometry), Box::new(ChiAngles)], ..Default::default() };
+    for (code, chain) in FIXTURES {
+        let root = dir.join(format!("{}_{}", code, chain)).to_str().unwrap().to_string();
+        featurize_chain(&format!("{}/{}.cif", INPUTS, code), chain, &root, &config, &CancellationToken::new()).unwrap();
+        for ext in COMPARED {
+            compare(&PathBuf::from(format!("{}.{}", root, ext)), &Path::new(GOLDEN).join(format!("{}_{}.{}", code, chain, ext)));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * C API of the deep-bbq v.2 featurizer.
+ *
+ * featurize_file() computes features of a single chain and returns them in a buffer owned by the library,
+ * released with featurizer_free_buffer(). All numbers in the buffer are little-endian:
+ *
+ *   offset       size  content
+ *   0            4     magic bytes "DBQ2"
+ *   4            4     uint32_t version of the layout (FEATURIZER_LAYOUT_VERSION)
+ *   8            4     uint32_t number of observed residues L
+ *   12           4     uint32_t number of feature blocks B
+ *   16 + 48 k    32    name of the k-th block, NUL-padded
+ *   48 + 48 k    4     uint32_t number of its columns n
+ *   52 + 48 k    4     reserved, zero
+ *   56 + 48 k    8     uint64_t offset of its values from the start of the buffer
+ *
+ * Values of a block are L x n doubles, row by row, i.e. a row per residue; an offset is always a multiple of 8
+ * and the buffer itself is 8-byte aligned, so (const double *)(data + offset) may be read on a little-endian host.
+ * The first two blocks are always "ss" (secondary structure: 0 for H, 1 for E, 2 for C) and "ca" (CA coordinates),
+ * followed by the requested features, named as in `featurizer describe-features`.
+ */
+
+#ifndef FEATURIZER_H
+#define FEATURIZER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FEATURIZER_LAYOUT_VERSION 1
+
+#define FEATURIZER_OK 0
+#define FEATURIZER_INVALID_ARGUMENT 1
+#define FEATURIZER_IO_ERROR 2
+#define FEATURIZER_PDB_ERROR 3
+#define FEATURIZER_FILTERED 4
+#define FEATURIZER_INTERNAL_ERROR 5
+#define FEATURIZER_CANCELLED 6
+
+typedef struct FeaturizerOptions {
+    /* comma-separated names of the features to compute, e.g. "ca_geometry,hse"; NULL or empty for none */
+    const char *features;
+    /* alternate location label of the conformer to featurize; 0 for the default conformer */
+    char conformer;
+    /* when non-zero, malformed atom records are skipped instead of failing the whole deposit */
+    int lenient;
+} FeaturizerOptions;
+
+typedef struct FeatureBuffer {
+    uint8_t *data;
+    size_t len;
+} FeatureBuffer;
+
+/* Computes features of a chain of a deposit file (mmCIF or PDB, possibly gzipped) into out_buffer;
+ * options may be NULL. Returns FEATURIZER_OK or an error code, described by featurizer_last_error(). */
+int featurize_file(const char *path, const char *chain, const FeaturizerOptions *options, FeatureBuffer *out_buffer);
+
+/* Releases a buffer filled by featurize_file() */
+void featurizer_free_buffer(FeatureBuffer *buffer);
+
+/* Message of the last error reported in the calling thread; valid until the next call of featurize_file() */
+const char *featurizer_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FEATURIZER_H */
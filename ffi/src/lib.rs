@@ -0,0 +1,189 @@
+//! C API of the featurizer, so a C or C++ modeling pipeline can embed it directly; see `include/featurizer.h`.
+//!
+//! [`featurize_file()`] computes features of a single chain and returns them in a buffer owned by the library,
+//! released with [`featurizer_free_buffer()`]. All numbers in the buffer are little-endian:
+//!
+//! | offset               | size | content                                                                  |
+//! |----------------------|------|--------------------------------------------------------------------------|
+//! | 0                    | 4    | magic bytes `DBQ2`                                                       |
+//! | 4                    | 4    | `u32` version of the layout, see [`LAYOUT_VERSION`]                      |
+//! | 8                    | 4    | `u32` number of observed residues `L`                                    |
+//! | 12                   | 4    | `u32` number of feature blocks `B`                                       |
+//! | 16 + 48 k            | 32   | name of the k-th block, NUL-padded                                       |
+//! | 48 + 48 k            | 4    | `u32` number of its columns `n`                                          |
+//! | 52 + 48 k            | 4    | reserved, zero                                                           |
+//! | 56 + 48 k            | 8    | `u64` offset of its values from the start of the buffer                 |
+//!
+//! Values of a block are `L x n` doubles, row by row, i.e. a row per residue; an offset is always a multiple of 8
+//! and the buffer itself is 8-byte aligned, so `data + offset` may be read as an array of doubles on a little-endian host.
+//! The first two blocks are always `ss` (secondary structure: 0 for H, 1 for E, 2 for C) and `ca` (CA coordinates),
+//! followed by the requested features, named as in `featurizer describe-features`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use featurizer::features::extractors_by_name;
+use featurizer::{compute_chain_features, ChainFeatures, FeaturizerConfig, FeaturizerError, LoadedDeposit};
+
+/// Version of the binary layout of a [`FeatureBuffer`]; bumped every time the layout changes
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// The call succeeded
+pub const FEATURIZER_OK: c_int = 0;
+/// A NULL pointer, a string that is not UTF-8 or an unknown feature name was given
+pub const FEATURIZER_INVALID_ARGUMENT: c_int = 1;
+/// The deposit file can't be read
+pub const FEATURIZER_IO_ERROR: c_int = 2;
+/// The deposit can't be parsed or it has no such chain
+pub const FEATURIZER_PDB_ERROR: c_int = 3;
+/// The chain was rejected by the chain filters
+pub const FEATURIZER_FILTERED: c_int = 4;
+/// An unexpected internal error
+pub const FEATURIZER_INTERNAL_ERROR: c_int = 5;
+/// The featurization was cancelled before it finished
+pub const FEATURIZER_CANCELLED: c_int = 6;
+
+const MAGIC: &[u8; 4] = b"DBQ2";
+const HEADER_SIZE: usize = 16;
+const NAME_SIZE: usize = 32;
+const BLOCK_HEADER_SIZE: usize = 48;
+
+/// Options of [`featurize_file()`]; a NULL pointer stands for the defaults
+#[repr(C)]
+pub struct FeaturizerOptions {
+    /// comma-separated names of the features to compute, e.g. `ca_geometry,hse`; NULL or empty for none
+    pub features: *const c_char,
+    /// alternate location label of the conformer to featurize; 0 for the default conformer
+    pub conformer: c_char,
+    /// when non-zero, malformed atom records are skipped instead of failing the whole deposit
+    pub lenient: c_int,
+}
+
+/// Features of a chain in the binary layout described above; owned by the library
+#[repr(C)]
+pub struct FeatureBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn fail(code: c_int, message: String) -> c_int {
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(message.replace('\0', " ")).unwrap_or_default());
+    code
+}
+
+fn error_code(e: &FeaturizerError) -> c_int {
+    match e {
+        FeaturizerError::Io(_) => FEATURIZER_IO_ERROR,
+        FeaturizerError::Pdb(_) => FEATURIZER_PDB_ERROR,
+        FeaturizerError::Filtered(_) => FEATURIZER_FILTERED,
+        FeaturizerError::Cancelled => FEATURIZER_CANCELLED,
+    }
+}
+
+/// Reads a C string; `None` for a NULL pointer or a string that is not UTF-8
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() { None } else { CStr::from_ptr(s).to_str().ok() }
+}
+
+/// Writes features of a chain in the binary layout of a [`FeatureBuffer`]
+fn encode(features: &ChainFeatures) -> Vec<u8> {
+    let ss: Vec<Vec<f64>> = features.ss.bytes().map(|c| vec![match c { b'H' => 0.0, b'E' => 1.0, _ => 2.0 }]).collect();
+    let ca: Vec<Vec<f64>> = features.ca.iter().map(|p| p.to_vec()).collect();
+    let mut blocks: Vec<(&str, &[Vec<f64>])> = vec![("ss", &ss), ("ca", &ca)];
+    blocks.extend(features.features.iter().map(|(name, rows)| (name.as_str(), rows.as_slice())));
+
+    let n_residues = features.ca.len();
+    let mut out: Vec<u8> = Vec::new();
+    out.extend(MAGIC);
+    out.extend(LAYOUT_VERSION.to_le_bytes());
+    out.extend((n_residues as u32).to_le_bytes());
+    out.extend((blocks.len() as u32).to_le_bytes());
+    let mut offset = HEADER_SIZE + blocks.len() * BLOCK_HEADER_SIZE;
+    for (name, rows) in &blocks {
+        let n_columns = rows.first().map_or(0, |r| r.len());
+        // --- names are truncated so they always end with NUL
+        let mut padded = [0u8; NAME_SIZE];
+        let len = name.len().min(NAME_SIZE - 1);
+        padded[..len].copy_from_slice(&name.as_bytes()[..len]);
+        out.extend(padded);
+        out.extend((n_columns as u32).to_le_bytes());
+        out.extend(0u32.to_le_bytes());
+        out.extend((offset as u64).to_le_bytes());
+        offset += n_residues * n_columns * 8;
+    }
+    for (_, rows) in &blocks {
+        for v in rows.iter().flatten() { out.extend(v.to_le_bytes()); }
+    }
+    // --- the header and every value take a multiple of 8 bytes, so the buffer is a whole number of words
+    debug_assert_eq!(out.len() % 8, 0);
+
+    out
+}
+
+fn featurize(path: &str, chain: &str, options: Option<&FeaturizerOptions>) -> Result<Vec<u8>, c_int> {
+    let names = match options.map(|o| o.features).filter(|f| !f.is_null()) {
+        Some(f) => unsafe { c_str(f) }.ok_or_else(|| fail(FEATURIZER_INVALID_ARGUMENT, "feature names are not UTF-8".to_string()))?,
+        None => "",
+    };
+    let names: Vec<&str> = names.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).collect();
+    let extractors = extractors_by_name(&names).map_err(|e| fail(FEATURIZER_INVALID_ARGUMENT, e))?;
+    let conformer = options.map(|o| o.conformer as u8).filter(|c| *c != 0).map(|c| c as char);
+    let config = FeaturizerConfig { extractors, conformer, lenient: options.is_some_and(|o| o.lenient != 0), ..Default::default() };
+    let features = LoadedDeposit::load(path, &config).and_then(|loaded| compute_chain_features(&loaded, chain, &config))
+        .map_err(|e| fail(error_code(&e), e.to_string()))?;
+
+    Ok(encode(&features))
+}
+
+/// Computes features of a chain of a deposit file (mmCIF or PDB, possibly gzipped) into `out_buffer`,
+/// which must be released with [`featurizer_free_buffer()`]; returns [`FEATURIZER_OK`] or an error code,
+/// described by [`featurizer_last_error()`].
+///
+/// # Safety
+/// `path` and `chain` must be NUL-terminated strings, `options` must be NULL or point to valid options
+/// and `out_buffer` must point to a writable [`FeatureBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn featurize_file(path: *const c_char, chain: *const c_char, options: *const FeaturizerOptions,
+                                        out_buffer: *mut FeatureBuffer) -> c_int {
+    if out_buffer.is_null() { return fail(FEATURIZER_INVALID_ARGUMENT, "the output buffer is NULL".to_string()); }
+    (*out_buffer) = FeatureBuffer { data: ptr::null_mut(), len: 0 };
+    let (Some(path), Some(chain)) = (c_str(path), c_str(chain)) else {
+        return fail(FEATURIZER_INVALID_ARGUMENT, "the path or the chain ID is NULL or not UTF-8".to_string());
+    };
+    let options = options.as_ref();
+    match catch_unwind(AssertUnwindSafe(|| featurize(path, chain, options))) {
+        Ok(Ok(bytes)) => {
+            // --- the buffer is allocated as words, so values of the blocks are aligned for doubles
+            let words: Box<[u64]> = bytes.chunks_exact(8).map(|w| u64::from_ne_bytes(w.try_into().unwrap())).collect();
+            (*out_buffer).len = bytes.len();
+            (*out_buffer).data = Box::into_raw(words) as *mut u8;
+            FEATURIZER_OK
+        }
+        Ok(Err(code)) => code,
+        Err(_) => fail(FEATURIZER_INTERNAL_ERROR, format!("the featurizer panicked on chain {} of {}", chain, path)),
+    }
+}
+
+/// Releases a buffer filled by [`featurize_file()`]; a buffer already released or never filled is left untouched.
+///
+/// # Safety
+/// `buffer` must be NULL or point to a [`FeatureBuffer`] filled by [`featurize_file()`].
+#[no_mangle]
+pub unsafe extern "C" fn featurizer_free_buffer(buffer: *mut FeatureBuffer) {
+    let Some(buffer) = buffer.as_mut() else { return };
+    if buffer.data.is_null() { return; }
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data as *mut u64, buffer.len / 8)));
+    *buffer = FeatureBuffer { data: ptr::null_mut(), len: 0 };
+}
+
+/// Message of the last error reported by [`featurize_file()`] in the calling thread; valid until the next call
+#[no_mangle]
+pub extern "C" fn featurizer_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
@@ -0,0 +1,66 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use featurizer_ffi::*;
+
+const INPUT: &str = "../featurizer/tests/input_files/2gb1.cif";
+
+fn u32_at(bytes: &[u8], offset: usize) -> usize { u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize }
+
+fn u64_at(bytes: &[u8], offset: usize) -> usize { u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize }
+
+#[test]
+fn buffers_follow_the_documented_layout() {
+    let (path, chain) = (CString::new(INPUT).unwrap(), CString::new("A").unwrap());
+    let features = CString::new("ca_geometry").unwrap();
+    let options = FeaturizerOptions { features: features.as_ptr(), conformer: 0, lenient: 0 };
+    let mut buffer = FeatureBuffer { data: ptr::null_mut(), len: 0 };
+    assert_eq!(unsafe { featurize_file(path.as_ptr(), chain.as_ptr(), &options, &mut buffer) }, FEATURIZER_OK);
+    // --- blocks start at multiples of 8, so C callers may read values in place as doubles
+    assert_eq!(buffer.data as usize % 8, 0, "the buffer is not aligned for doubles");
+    let bytes = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }.to_vec();
+    unsafe { featurizer_free_buffer(&mut buffer) };
+    assert!(buffer.data.is_null());
+
+    assert_eq!(&bytes[0..4], b"DBQ2");
+    assert_eq!(u32_at(&bytes, 4), LAYOUT_VERSION as usize);
+    let (n_residues, n_blocks) = (u32_at(&bytes, 8), u32_at(&bytes, 12));
+    assert_eq!(n_residues, 56);
+    assert_eq!(n_blocks, 3);
+    let mut end = 16 + 48 * n_blocks;
+    let mut names = vec![];
+    for k in 0..n_blocks {
+        let header = 16 + 48 * k;
+        names.push(CStr::from_bytes_until_nul(&bytes[header..header + 32]).unwrap().to_str().unwrap().to_string());
+        let (n_columns, offset) = (u32_at(&bytes, header + 32), u64_at(&bytes, header + 40));
+        assert_eq!(u32_at(&bytes, header + 36), 0);
+        assert_eq!(offset, end, "values of block {} don't follow the previous block", k);
+        assert_eq!(offset % 8, 0);
+        end = offset + n_residues * n_columns * 8;
+    }
+    assert_eq!(end, bytes.len());
+    assert_eq!(names, ["ss", "ca", "ca_geometry"]);
+
+    // --- secondary structure codes are 0, 1 or 2 and CA coordinates are finite
+    let values: Vec<f64> = bytes[16 + 48 * n_blocks..].chunks(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect();
+    assert!(values[..n_residues].iter().all(|v| [0.0, 1.0, 2.0].contains(v)));
+    assert!(values[n_residues..4 * n_residues].iter().all(|v| v.is_finite()));
+}
+
+#[test]
+fn failures_are_reported_with_their_codes() {
+    let chain = CString::new("A").unwrap();
+    let mut buffer = FeatureBuffer { data: ptr::null_mut(), len: 0 };
+
+    let missing = CString::new("../featurizer/tests/input_files/9zzz.cif").unwrap();
+    assert_eq!(unsafe { featurize_file(missing.as_ptr(), chain.as_ptr(), ptr::null(), &mut buffer) }, FEATURIZER_IO_ERROR);
+    assert!(buffer.data.is_null());
+
+    let (path, features) = (CString::new(INPUT).unwrap(), CString::new("no_such_feature").unwrap());
+    let options = FeaturizerOptions { features: features.as_ptr(), conformer: 0, lenient: 0 };
+    assert_eq!(unsafe { featurize_file(path.as_ptr(), chain.as_ptr(), &options, &mut buffer) }, FEATURIZER_INVALID_ARGUMENT);
+    let message = unsafe { CStr::from_ptr(featurizer_last_error()) }.to_str().unwrap();
+    assert!(message.contains("no_such_feature"), "unexpected message: {}", message);
+
+    assert_eq!(unsafe { featurize_file(ptr::null(), chain.as_ptr(), ptr::null(), &mut buffer) }, FEATURIZER_INVALID_ARGUMENT);
+}
@@ -0,0 +1,117 @@
+//! Python bindings of the featurizer, so a PyTorch data loader can featurize chains on the fly:
+//! ```python
+//! import deep_bbq2
+//! f = deep_bbq2.featurize("2gb1.cif", "A", features=["ca_geometry", "hse"])
+//! f["ca"].shape, f["hse"].shape      # (56, 3), (56, 6)
+//! ```
+//! Every array has a row per observed residue of the chain, as returned by [`compute_chain_features()`].
+
+use numpy::{PyArray1, PyArray2};
+use pyo3::exceptions::{PyIOError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use featurizer::dataset;
+use featurizer::features::{extractors_by_name, registered_extractors};
+use featurizer::records::feature_names;
+use featurizer::{compute_chain_features, FeaturizerConfig, FeaturizerError, LoadedDeposit};
+
+fn to_py_err(e: FeaturizerError) -> PyErr {
+    match e {
+        FeaturizerError::Io(e) => PyIOError::new_err(e.to_string()),
+        e => PyValueError::new_err(e.to_string()),
+    }
+}
+
+/// Computes features of a chain of a deposit file and returns them as a dict of numpy arrays.
+///
+/// The dict gives the entity `sequence`, `residue_ids`, both `author_numbers` and `label_numbers` of residues,
+/// `entity_index` (-1 for residues not aligned to the entity, also in `label_numbers`),
+/// secondary structure `ss` (a string of H, E and C), `ca` coordinates and an array for each of the requested
+/// `features`, named as listed by `describe_features()`; all the arrays have a row per observed residue.
+#[pyfunction]
+#[pyo3(signature = (fname, chain, features = None))]
+fn featurize<'py>(py: Python<'py>, fname: &str, chain: &str, features: Option<Vec<String>>) -> PyResult<Bound<'py, PyDict>> {
+    let names: Vec<&str> = features.iter().flatten().map(|f| f.as_str()).collect();
+    let config = FeaturizerConfig { extractors: extractors_by_name(&names).map_err(PyValueError::new_err)?, ..Default::default() };
+    let loaded = LoadedDeposit::load(fname, &config).map_err(to_py_err)?;
+    let chain_features = compute_chain_features(&loaded, chain, &config).map_err(to_py_err)?;
+
+    let out = PyDict::new_bound(py);
+    out.set_item("sequence", &chain_features.entity_sequence)?;
+    out.set_item("residue_ids", &chain_features.residue_ids)?;
+    out.set_item("author_numbers", &chain_features.author_numbers)?;
+    let label_numbers: Vec<i64> = chain_features.label_numbers.iter().map(|n| n.map_or(-1, |n| n as i64)).collect();
+    out.set_item("label_numbers", PyArray1::from_vec_bound(py, label_numbers))?;
+    let entity_index: Vec<i64> = chain_features.entity_index.iter().map(|e| e.map_or(-1, |e| e as i64)).collect();
+    out.set_item("entity_index", PyArray1::from_vec_bound(py, entity_index))?;
+    out.set_item("ss", &chain_features.ss)?;
+    let ca: Vec<Vec<f64>> = chain_features.ca.iter().map(|p| p.to_vec()).collect();
+    out.set_item("ca", PyArray2::from_vec2_bound(py, &ca)?)?;
+    for (name, values) in &chain_features.features {
+        out.set_item(name, PyArray2::from_vec2_bound(py, values)?)?;
+    }
+
+    Ok(out)
+}
+
+/// Describes every feature that may be requested from `featurize()`: a dict of its name, description, shape,
+/// units, range, mask and version
+#[pyfunction]
+fn describe_features(py: Python<'_>) -> PyResult<Vec<Bound<'_, PyDict>>> {
+    registered_extractors().iter().map(|e| {
+        let info = e.info();
+        let d = PyDict::new_bound(py);
+        d.set_item("name", info.name)?;
+        d.set_item("description", info.description)?;
+        d.set_item("shape", &info.shape)?;
+        d.set_item("units", info.units)?;
+        d.set_item("range", info.range)?;
+        d.set_item("mask", info.mask)?;
+        d.set_item("version", info.version)?;
+        Ok(d)
+    }).collect()
+}
+
+/// A dataset packed into tar shards by `featurizer pack`, opened by its index file; shards are memory-mapped
+#[pyclass]
+struct FeatureDataset {
+    dataset: dataset::FeatureDataset,
+}
+
+#[pymethods]
+impl FeatureDataset {
+    #[new]
+    fn new(index: &str) -> PyResult<Self> {
+        Ok(FeatureDataset { dataset: dataset::FeatureDataset::open(index).map_err(to_py_err)? })
+    }
+
+    fn __len__(&self) -> usize { self.dataset.len() }
+
+    /// Keys of all the chains, in the order of the index
+    fn keys(&self) -> Vec<String> { self.dataset.keys().map(|k| k.to_string()).collect() }
+
+    /// Records of a chain as a dict: `residue_ids`, `ss` (a string), `ca` coordinates and the `features` matrix,
+    /// whose columns are named by `columns`; arrays have a row per observed residue
+    fn chain<'py>(&self, py: Python<'py>, key: &str) -> PyResult<Bound<'py, PyDict>> {
+        let chain = self.dataset.get(key).ok_or_else(|| PyKeyError::new_err(key.to_string()))?;
+        let records = chain.records().map_err(to_py_err)?;
+        let out = PyDict::new_bound(py);
+        out.set_item("residue_ids", records.iter().map(|r| r.residue_id.clone()).collect::<Vec<_>>())?;
+        out.set_item("ss", records.iter().map(|r| r.ss).collect::<String>())?;
+        let ca: Vec<Vec<f64>> = records.iter().map(|r| r.ca.to_vec()).collect();
+        out.set_item("ca", PyArray2::from_vec2_bound(py, &ca)?)?;
+        let features: Vec<Vec<f64>> = records.iter().map(|r| r.features.clone()).collect();
+        out.set_item("features", PyArray2::from_vec2_bound(py, &features)?)?;
+        out.set_item("columns", feature_names(&chain.columns().unwrap_or_default()))?;
+        Ok(out)
+    }
+}
+
+#[pymodule]
+fn deep_bbq2(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(featurize, m)?)?;
+    m.add_function(wrap_pyfunction!(describe_features, m)?)?;
+    m.add_class::<FeatureDataset>()?;
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! The featurizer compiled to WebAssembly, for in-browser demos: a dropped PDB or mmCIF file goes in as bytes and
+//! its features come out as JSON, with no filesystem access.
+//! ```js
+//! import init, { chain_ids, featurize } from "./pkg/featurizer_wasm.js";
+//! await init();
+//! const bytes = new Uint8Array(await file.arrayBuffer());
+//! const features = JSON.parse(featurize(bytes, JSON.parse(chain_ids(bytes))[0], "ca_geometry,hse"));
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use featurizer::chains::polymer_chain_ids;
+use featurizer::features::{extractors_by_name, registered_extractors, FeatureInfo};
+use featurizer::{compute_chain_features, FeaturizerConfig, LoadedDeposit};
+
+/// Name standing for the dropped file, which has no path
+const UPLOADED: &str = "uploaded";
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, JsError> {
+    serde_json::to_string(value).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// IDs of the polymer chains of a deposit given as the content of a PDB or mmCIF file, as a JSON array
+#[wasm_bindgen]
+pub fn chain_ids(bytes: &[u8]) -> Result<String, JsError> {
+    let loaded = LoadedDeposit::from_text(UPLOADED, &String::from_utf8_lossy(bytes), &FeaturizerConfig::default())
+        .map_err(|e| JsError::new(&e.to_string()))?;
+    to_json(&polymer_chain_ids(&loaded.deposit))
+}
+
+/// Computes features of a chain of a deposit given as the content of a PDB or mmCIF file.
+///
+/// `features` gives comma-separated names of the per-residue features, as listed by [`describe_features()`].
+/// Returns a JSON object with the entity sequence, residue IDs, author and label numbers, secondary structure, CA coordinates and a matrix
+/// for every feature, a row per observed residue; undefined values are `null`. Files are read in the lenient mode,
+/// so malformed atom records of uploaded files are skipped. Features that need the crystal lattice or side files,
+/// such as `crystal_contacts` or `sequence_profile`, are rejected.
+#[wasm_bindgen]
+pub fn featurize(bytes: &[u8], chain: &str, features: &str) -> Result<String, JsError> {
+    let names: Vec<&str> = features.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).collect();
+    let extractors = extractors_by_name(&names).map_err(|e| JsError::new(&e))?;
+    // --- the crystal lattice is built from the input file read again and side files are read from disk, neither of which a dropped file has
+    if let Some(e) = extractors.iter().find(|e| e.needs_lattice() || e.reads_files()) {
+        return Err(JsError::new(&format!("{} needs access to files, which is not available in the browser", e.info().name)));
+    }
+    let config = FeaturizerConfig { extractors, lenient: true, ..Default::default() };
+    let loaded = LoadedDeposit::from_text(UPLOADED, &String::from_utf8_lossy(bytes), &config).map_err(|e| JsError::new(&e.to_string()))?;
+    let chain_features = compute_chain_features(&loaded, chain, &config).map_err(|e| JsError::new(&e.to_string()))?;
+
+    to_json(&chain_features)
+}
+
+/// Descriptions of all the per-residue features that may be requested from [`featurize()`], as a JSON array
+#[wasm_bindgen]
+pub fn describe_features() -> Result<String, JsError> {
+    let infos: Vec<FeatureInfo> = registered_extractors().iter().map(|e| e.info()).collect();
+    to_json(&infos)
+}